@@ -0,0 +1,95 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+//! Benchmarks for loading workflows: parsing their definitions, building
+//! their graphs and evaluating the expressions every task runs
+//!
+//! Run with `just bench`.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use jackdaw::durableengine::DurableEngine;
+use jackdaw::expressions::{Evaluation, evaluate_expression};
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::fmt::Write;
+use std::hint::black_box;
+
+/// A workflow of `tasks` tasks, mixing the kinds of task large workflows
+/// are made of
+fn large_workflow(tasks: usize) -> String {
+    let mut yaml = String::from(
+        "document:\n  dsl: '1.0.2'\n  namespace: default\n  name: large\n  version: '1.0.0'\ndo:\n",
+    );
+    for i in 0..tasks {
+        match i % 4 {
+            0 => writeln!(
+                yaml,
+                "  - task{i}:\n      set:\n        step: {i}\n        total: ${{ .total + {i} }}"
+            ),
+            1 => writeln!(
+                yaml,
+                "  - task{i}:\n      switch:\n        - even:\n            when: ${{ .total % 2 == 0 }}\n            then: continue\n        - default:\n            then: continue"
+            ),
+            2 => writeln!(
+                yaml,
+                "  - task{i}:\n      call: http\n      with:\n        method: get\n        endpoint: https://example.com/tasks/{i}"
+            ),
+            _ => writeln!(
+                yaml,
+                "  - task{i}:\n      if: ${{ .total > {i} }}\n      wait:\n        seconds: 1"
+            ),
+        }
+        .unwrap();
+    }
+    yaml
+}
+
+fn parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for tasks in [100, 1_000, 10_000] {
+        let yaml = large_workflow(tasks);
+        group.throughput(Throughput::Bytes(yaml.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(tasks), &yaml, |b, yaml| {
+            b.iter(|| serde_yaml::from_str::<WorkflowDefinition>(black_box(yaml)).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn graph_construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_graph");
+    for tasks in [100, 1_000, 10_000] {
+        let workflow: WorkflowDefinition = serde_yaml::from_str(&large_workflow(tasks)).unwrap();
+        group.throughput(Throughput::Elements(tasks as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(tasks),
+            &workflow,
+            |b, workflow| {
+                b.iter(|| {
+                    DurableEngine::validate_workflow_graph(black_box(workflow))
+                        .unwrap()
+                        .0
+                        .node_count()
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn expressions(c: &mut Criterion) {
+    let context = json!({ "order": { "total": 42, "items": [1, 2, 3] } });
+    c.bench_function("evaluate_expression", |b| {
+        b.iter(|| {
+            evaluate_expression(
+                Evaluation::default(),
+                black_box("${ .order.total + (.order.items | length) }"),
+                &context,
+            )
+            .unwrap()
+        });
+    });
+}
+
+criterion_group!(benches, parsing, graph_construction, expressions);
+criterion_main!(benches);
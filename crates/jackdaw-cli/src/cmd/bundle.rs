@@ -0,0 +1,93 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+
+use crate::bundle::create_bundle;
+use crate::cmd::run::discover_workflow_files;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("{source}"))]
+    Bundle { source: crate::bundle::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::bundle::Error> for Error {
+    fn from(source: crate::bundle::Error) -> Self {
+        Error::Bundle { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct BundleArgs {
+    /// Workflow file to bundle
+    #[arg(value_name = "WORKFLOW")]
+    pub workflow: PathBuf,
+
+    /// Path of the bundle archive to write
+    #[arg(short = 'o', long, value_name = "PATH", default_value = "bundle.tar")]
+    pub output: PathBuf,
+
+    /// Workflow registry paths used to find sub-workflows started with `run.workflow`
+    #[arg(short = 'r', long = "registry", value_name = "PATH")]
+    pub registry: Option<Vec<PathBuf>>,
+
+    /// Fail when a workflow references an unset `${{ env.NAME }}` variable
+    #[arg(long)]
+    pub strict_env: bool,
+
+    /// Enable verbose output
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+}
+
+/// Handle the bundle command
+///
+/// # Errors
+///
+/// Returns an error if the workflow, a sub-workflow or a referenced resource
+/// cannot be loaded, or the archive cannot be written.
+pub async fn handle_bundle(args: BundleArgs) -> Result<()> {
+    let registry = match &args.registry {
+        Some(paths) => discover_workflow_files(paths)?,
+        None => Vec::new(),
+    };
+
+    println!(
+        "{} {}",
+        style("Bundling:").bold().cyan(),
+        args.workflow.display()
+    );
+
+    let manifest = create_bundle(&args.workflow, &registry, &args.output, args.strict_env).await?;
+
+    if args.verbose {
+        for name in &manifest.subworkflows {
+            println!("  • Sub-workflow {name}");
+        }
+        for (reference, bundled) in &manifest.resources {
+            println!("  • {reference} → {bundled}");
+        }
+    }
+
+    println!(
+        "{} Wrote {} ({} sub-workflow(s), {} resource(s))",
+        style("✓").green().bold(),
+        args.output.display(),
+        manifest.subworkflows.len(),
+        manifest.resources.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use clap::{Parser, Subcommand};
+use console::style;
+use snafu::prelude::*;
+
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+use crate::output::format_bytes;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("{source}"))]
+    Cache { source: crate::cache::Error },
+
+    #[snafu(display("Invalid format '{format}'. Valid formats: table, json"))]
+    InvalidFormat { format: String },
+
+    #[snafu(display("Failed to serialize cache statistics: {source}"))]
+    Serialize { source: serde_json::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::cache::Error> for Error {
+    fn from(source: crate::cache::Error) -> Self {
+        Error::Cache { source }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Serialize { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Remove cached task results, or only the expired ones with --expired
+    Purge(PurgeArgs),
+    /// Show how many results are cached and how much space they take
+    Stats(StatsArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct PurgeArgs {
+    /// Only remove entries whose TTL has passed
+    #[arg(long)]
+    pub expired: bool,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+#[derive(Parser, Debug)]
+pub struct StatsArgs {
+    /// Output format (table or json)
+    #[arg(short = 'f', long, default_value = "table", value_name = "FORMAT")]
+    pub format: String,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the cache command
+///
+/// An in-memory cache does not outlive a process, so unless another cache
+/// provider is selected the redb database at `--cache-db` is used.
+///
+/// # Errors
+///
+/// Returns an error if the cache provider cannot be created or its entries
+/// cannot be read or removed.
+pub async fn handle_cache(args: CacheArgs, config: JackdawConfig) -> Result<()> {
+    match args.command {
+        CacheCommand::Purge(args) => purge(args, config).await,
+        CacheCommand::Stats(args) => stats(args, config).await,
+    }
+}
+
+async fn purge(args: PurgeArgs, config: JackdawConfig) -> Result<()> {
+    let providers = args.providers.durable();
+    let config = args.overrides.merge_with_config(config);
+
+    let cache = providers.create_cache(&config).await?;
+    let removed = if args.expired {
+        cache.purge_expired().await?
+    } else {
+        cache.purge_all().await?
+    };
+    cache.compact().await?;
+
+    println!(
+        "{} Removed {} {}cache {}",
+        style("✓").green(),
+        removed,
+        if args.expired { "expired " } else { "" },
+        if removed == 1 { "entry" } else { "entries" }
+    );
+    Ok(())
+}
+
+/// Print the entries stored by the cache
+///
+/// Hits and misses are counted by the process that looks results up, so they
+/// are reported at the end of `jackdaw run --verbose` rather than here.
+async fn stats(args: StatsArgs, config: JackdawConfig) -> Result<()> {
+    if args.format != "table" && args.format != "json" {
+        return InvalidFormatSnafu {
+            format: args.format,
+        }
+        .fail();
+    }
+    let providers = args.providers.durable();
+    let config = args.overrides.merge_with_config(config);
+
+    let cache = providers.create_cache(&config).await?;
+    let stats = cache.stats().await?;
+
+    if args.format == "json" {
+        let stored = serde_json::json!({ "entries": stats.entries, "bytes": stats.bytes });
+        println!("{}", serde_json::to_string_pretty(&stored)?);
+    } else {
+        println!(
+            "{} {} cached {}, {} stored",
+            style("→").cyan(),
+            stats.entries,
+            if stats.entries == 1 {
+                "result"
+            } else {
+                "results"
+            },
+            format_bytes(stats.bytes)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,521 @@
+use clap::{Args, Parser, Subcommand};
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+
+use crate::config::JackdawConfig;
+use crate::container::ContainerRuntime;
+use crate::durableengine::ShadowMode;
+use crate::providers::visualization::{ColorScheme, Palette};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Config { source: crate::config::Error },
+
+    #[snafu(display("Failed to read config file {}: {source}", path.display()))]
+    ReadConfig {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("YAML serialization error: {source}"))]
+    Yaml { source: serde_yaml::Error },
+
+    #[snafu(display("Configuration has {count} invalid value(s)"))]
+    InvalidConfig { count: usize },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::config::Error> for Error {
+    fn from(source: crate::config::Error) -> Self {
+        Error::Config { source }
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(source: serde_yaml::Error) -> Self {
+        Error::Yaml { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Validate the configuration files and environment
+    Check,
+    /// Show the configuration layers, or the effective configuration with --resolved
+    Show(ShowArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct ShowArgs {
+    /// Print the effective configuration after merging defaults, files, env and CLI flags
+    #[arg(long)]
+    pub resolved: bool,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Command line flags that override values from the configuration files
+#[derive(Args, Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ConfigOverrides {
+    /// Path to the durable persistence database
+    #[arg(short = 'd', long, value_name = "PATH")]
+    pub durable_db: Option<PathBuf>,
+
+    /// Retries of a failed persistence write before it is buffered in memory
+    #[arg(long, value_name = "N")]
+    pub persistence_retries: Option<u32>,
+
+    /// Delay before the first retry of a failed persistence write (ISO 8601 duration)
+    #[arg(long, value_name = "DURATION")]
+    pub persistence_retry_backoff: Option<String>,
+
+    /// Persistence writes buffered in memory while the store is unavailable
+    #[arg(long, value_name = "N")]
+    pub persistence_spill_capacity: Option<usize>,
+
+    /// How long another process waits to take over an instance whose lease
+    /// is no longer renewed (ISO 8601 duration)
+    #[arg(long, value_name = "DURATION")]
+    pub lease_ttl: Option<String>,
+
+    /// Path to the cache database (if different from durable db)
+    #[arg(short = 'c', long, value_name = "PATH")]
+    pub cache_db: Option<PathBuf>,
+
+    /// How long cached task results are served (ISO 8601 duration, e.g. PT24H)
+    #[arg(long, value_name = "DURATION")]
+    pub cache_ttl: Option<String>,
+
+    /// How often expired cache entries are removed (ISO 8601 duration)
+    #[arg(long, value_name = "DURATION")]
+    pub cache_eviction_interval: Option<String>,
+
+    /// Run workflows in parallel
+    #[arg(short = 'p', long)]
+    pub parallel: bool,
+
+    /// Enable verbose output
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+
+    /// Generate workflow visualization after execution
+    #[arg(long)]
+    pub visualize: bool,
+
+    /// Visualization tool to use (graphviz, d2, mermaid, builtin, or a registered provider)
+    #[arg(long, value_name = "VIZTOOL")]
+    pub viz_tool: Option<String>,
+
+    /// Visualization output format (svg, png, pdf, ascii, markdown)
+    #[arg(long, value_name = "FORMAT")]
+    pub viz_format: Option<String>,
+
+    /// Visualization output path (optional, defaults to stdout for ascii)
+    #[arg(long, value_name = "PATH")]
+    pub viz_output: Option<PathBuf>,
+
+    /// Colors diagrams are drawn with (default, or colorblind)
+    #[arg(long, value_name = "PALETTE")]
+    pub viz_palette: Option<Palette>,
+
+    /// Background diagrams are drawn on (light or dark)
+    #[arg(long, value_name = "SCHEME")]
+    pub viz_color_scheme: Option<ColorScheme>,
+
+    /// Font size of diagram labels
+    #[arg(long, value_name = "SIZE")]
+    pub viz_font_size: Option<u32>,
+
+    /// Fill color of a task type in diagrams (e.g. call=#56B4E9); may be repeated
+    #[arg(long = "viz-task-color", value_name = "TYPE=COLOR", value_parser = parse_task_color)]
+    pub viz_task_colors: Vec<(String, String)>,
+
+    /// Start diagrams at this task, drawing the tasks before it as one node
+    #[arg(long, value_name = "TASK")]
+    pub viz_from_task: Option<String>,
+
+    /// Number of tasks diagrams draw, the tasks after them drawn as one node
+    #[arg(long, value_name = "TASKS")]
+    pub viz_max_depth: Option<usize>,
+
+    /// Draw runs of consecutive successful tasks as one node
+    #[arg(long)]
+    pub viz_collapse_successful: bool,
+
+    /// Draw only the tasks that ran up to the last failed one
+    #[arg(long)]
+    pub viz_only_failed: bool,
+
+    /// Maximum time to wait for each workflow to complete (ISO 8601 duration, e.g. PT5M)
+    #[arg(long, value_name = "DURATION")]
+    pub timeout: Option<String>,
+
+    /// Fail when a workflow references an unset `${{ env.NAME }}` variable
+    #[arg(long)]
+    pub strict_env: bool,
+
+    /// Resolve relative resource paths against this directory instead of the workflow file's directory
+    #[arg(long, value_name = "DIR")]
+    pub base_dir: Option<PathBuf>,
+
+    /// Clone git catalogs into this directory
+    #[arg(long, value_name = "DIR")]
+    pub catalog_dir: Option<PathBuf>,
+
+    /// Directory, file, git repository or HTTP server sub-workflows are looked up in; may be repeated
+    #[arg(long = "workflow-source", value_name = "PATH_OR_URI")]
+    pub workflow_sources: Vec<String>,
+
+    /// How deep workflows may call each other with run.workflow (default 16)
+    #[arg(long, value_name = "DEPTH")]
+    pub max_call_depth: Option<usize>,
+
+    /// How unsigned workflow definitions are treated (off, warn, enforce)
+    #[arg(long, value_name = "POLICY")]
+    pub signature_policy: Option<String>,
+
+    /// Public key (PEM) whose workflow signatures are trusted; may be repeated
+    #[arg(long = "trusted-key", value_name = "PATH")]
+    pub trusted_keys: Vec<PathBuf>,
+
+    /// Admission policy file evaluated before instances start and tasks execute
+    #[arg(long, value_name = "PATH")]
+    pub policy_file: Option<PathBuf>,
+
+    /// Engine snapshot, written by `jackdaw snapshot`, to start from
+    #[arg(long, value_name = "PATH")]
+    pub snapshot: Option<PathBuf>,
+
+    /// Process environment variable passed to tasks (name or PREFIX*); may be repeated
+    #[arg(long = "env-allow", value_name = "PATTERN")]
+    pub env_allow: Vec<String>,
+
+    /// Process environment variable never passed to tasks (name or PREFIX*); may be repeated
+    #[arg(long = "env-deny", value_name = "PATTERN")]
+    pub env_deny: Vec<String>,
+
+    /// Container runtime that runs container tasks (docker, podman or kubernetes)
+    #[arg(long, value_name = "PROVIDER")]
+    pub container_provider: Option<ContainerRuntime>,
+
+    /// User (and optionally group) container tasks run as, e.g. 1000:1000
+    #[arg(long, value_name = "USER[:GROUP]")]
+    pub container_user: Option<String>,
+
+    /// Network mode of container tasks (bridge, host, none, or a network name)
+    #[arg(long, value_name = "MODE")]
+    pub container_network: Option<String>,
+
+    /// CPU limit of container tasks, in CPUs
+    #[arg(long, value_name = "CPUS")]
+    pub container_cpus: Option<f64>,
+
+    /// Memory limit of container tasks (e.g. 512m, 2g)
+    #[arg(long, value_name = "SIZE")]
+    pub container_memory: Option<String>,
+
+    /// Maximum time to wait for container images to be pulled (ISO 8601 duration, e.g. PT5M)
+    #[arg(long, value_name = "DURATION")]
+    pub container_pull_timeout: Option<String>,
+
+    /// Maximum number of idle containers kept for tasks with `keepWarm` set
+    #[arg(long, value_name = "COUNT")]
+    pub container_pool_size: Option<usize>,
+
+    /// How long a warm container may stay idle before it is removed (ISO 8601 duration)
+    #[arg(long, value_name = "DURATION")]
+    pub container_pool_idle_ttl: Option<String>,
+
+    /// Destination of emitted events (stdout, http(s)://..., mqtt://host:port/topic); may be repeated
+    #[arg(long = "event-sink", value_name = "URI")]
+    pub event_sinks: Vec<String>,
+
+    /// Maximum number of fork branches running at once across the engine
+    #[arg(long, value_name = "COUNT")]
+    pub fork_max_parallelism: Option<usize>,
+
+    /// Iterations run at once by `for` loops with `parallel: true` and no limit of their own
+    #[arg(long, value_name = "COUNT")]
+    pub for_parallelism: Option<usize>,
+
+    /// How the numbers of workflow data are held: float, or decimal to keep the digits of numbers passed on unchanged
+    #[arg(long, value_name = "MODE")]
+    pub numbers: Option<String>,
+
+    /// OpenAPI document whose API is mocked, with calls to it routed to the mock; may be repeated
+    #[arg(long = "mock", value_name = "SPEC")]
+    pub mocks: Vec<PathBuf>,
+
+    /// How often `serve` logs the memory held by the engine (ISO 8601 duration)
+    #[arg(long, value_name = "DURATION")]
+    pub memory_sample_interval: Option<String>,
+
+    /// Resume the instances a stopped process left running when `serve` or `worker` starts
+    #[arg(long)]
+    pub recover_on_start: bool,
+
+    /// Check that the resources a workflow references are available before it starts
+    #[arg(long)]
+    pub preflight: bool,
+
+    /// Expression printed and recorded after every task (e.g. `.order.status`); may be repeated
+    #[arg(long = "watch-expr", value_name = "EXPR")]
+    pub watch_exprs: Vec<String>,
+
+    /// Record a run manifest (workflow, input and resource digests, resolved config) per instance
+    #[arg(long)]
+    pub run_manifests: bool,
+
+    /// Candidate workflow version run in the shadow of the version it replaces; may be repeated
+    #[arg(long = "shadow", value_name = "FILE")]
+    pub shadow_workflows: Vec<PathBuf>,
+
+    /// How shadow runs treat tasks with side effects (stub or sandbox)
+    #[arg(long, value_name = "MODE")]
+    pub shadow_mode: Option<ShadowMode>,
+}
+
+/// Parse a `TYPE=COLOR` task color
+fn parse_task_color(value: &str) -> std::result::Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(task_type, color)| (task_type.trim().to_string(), color.trim().to_string()))
+        .ok_or_else(|| format!("expected TYPE=COLOR, got '{value}'"))
+}
+
+impl ConfigOverrides {
+    /// Merge CLI arguments with config file settings
+    /// CLI arguments take precedence over config file settings
+    #[must_use]
+    pub fn merge_with_config(self, config: JackdawConfig) -> JackdawConfig {
+        JackdawConfig {
+            durable_db: self.durable_db.or(config.durable_db),
+            persistence_retries: self.persistence_retries.or(config.persistence_retries),
+            persistence_retry_backoff: self
+                .persistence_retry_backoff
+                .or(config.persistence_retry_backoff),
+            persistence_spill_capacity: self
+                .persistence_spill_capacity
+                .or(config.persistence_spill_capacity),
+            lease_ttl: self.lease_ttl.or(config.lease_ttl),
+            cache_db: self.cache_db.or(config.cache_db),
+            cache_ttl: self.cache_ttl.or(config.cache_ttl),
+            cache_eviction_interval: self
+                .cache_eviction_interval
+                .or(config.cache_eviction_interval),
+            cache: config.cache,
+            secrets: config.secrets,
+            parallel: if self.parallel { true } else { config.parallel },
+            verbose: if self.verbose { true } else { config.verbose },
+            visualize: if self.visualize {
+                true
+            } else {
+                config.visualize
+            },
+            viz_tool: self.viz_tool.or(config.viz_tool),
+            viz_format: self.viz_format.or(config.viz_format),
+            viz_output: self.viz_output.or(config.viz_output),
+            viz_palette: self.viz_palette.or(config.viz_palette),
+            viz_color_scheme: self.viz_color_scheme.or(config.viz_color_scheme),
+            viz_font_size: self.viz_font_size.or(config.viz_font_size),
+            viz_task_colors: {
+                let mut colors = config.viz_task_colors;
+                colors.extend(self.viz_task_colors);
+                colors
+            },
+            viz_from_task: self.viz_from_task.or(config.viz_from_task),
+            viz_max_depth: self.viz_max_depth.or(config.viz_max_depth),
+            viz_collapse_successful: if self.viz_collapse_successful {
+                true
+            } else {
+                config.viz_collapse_successful
+            },
+            viz_only_failed: if self.viz_only_failed {
+                true
+            } else {
+                config.viz_only_failed
+            },
+            timeout: self.timeout.or(config.timeout),
+            strict_env: if self.strict_env {
+                true
+            } else {
+                config.strict_env
+            },
+            base_dir: self.base_dir.or(config.base_dir),
+            catalog_dir: self.catalog_dir.or(config.catalog_dir),
+            workflow_sources: if self.workflow_sources.is_empty() {
+                config.workflow_sources
+            } else {
+                self.workflow_sources
+            },
+            max_call_depth: self.max_call_depth.or(config.max_call_depth),
+            signature_policy: self.signature_policy.or(config.signature_policy),
+            trusted_keys: if self.trusted_keys.is_empty() {
+                config.trusted_keys
+            } else {
+                self.trusted_keys
+            },
+            policy_file: self.policy_file.or(config.policy_file),
+            snapshot: self.snapshot.or(config.snapshot),
+            env_allow: if self.env_allow.is_empty() {
+                config.env_allow
+            } else {
+                self.env_allow
+            },
+            env_deny: if self.env_deny.is_empty() {
+                config.env_deny
+            } else {
+                self.env_deny
+            },
+            container_provider: self.container_provider.or(config.container_provider),
+            container_user: self.container_user.or(config.container_user),
+            container_network: self.container_network.or(config.container_network),
+            container_cpus: self.container_cpus.or(config.container_cpus),
+            container_memory: self.container_memory.or(config.container_memory),
+            container_pull_timeout: self
+                .container_pull_timeout
+                .or(config.container_pull_timeout),
+            container_pool_size: self.container_pool_size.or(config.container_pool_size),
+            container_pool_idle_ttl: self
+                .container_pool_idle_ttl
+                .or(config.container_pool_idle_ttl),
+            event_sinks: if self.event_sinks.is_empty() {
+                config.event_sinks
+            } else {
+                self.event_sinks
+            },
+            fork_max_parallelism: self.fork_max_parallelism.or(config.fork_max_parallelism),
+            for_parallelism: self.for_parallelism.or(config.for_parallelism),
+            numbers: self.numbers.or(config.numbers),
+            mocks: if self.mocks.is_empty() {
+                config.mocks
+            } else {
+                self.mocks
+            },
+            memory_sample_interval: self
+                .memory_sample_interval
+                .or(config.memory_sample_interval),
+            recover_on_start: if self.recover_on_start {
+                true
+            } else {
+                config.recover_on_start
+            },
+            preflight: if self.preflight {
+                true
+            } else {
+                config.preflight
+            },
+            watch_exprs: if self.watch_exprs.is_empty() {
+                config.watch_exprs
+            } else {
+                self.watch_exprs
+            },
+            run_manifests: if self.run_manifests {
+                true
+            } else {
+                config.run_manifests
+            },
+            shadow_workflows: if self.shadow_workflows.is_empty() {
+                config.shadow_workflows
+            } else {
+                self.shadow_workflows
+            },
+            shadow_mode: self.shadow_mode.or(config.shadow_mode),
+            listener_auth: config.listener_auth,
+        }
+    }
+}
+
+/// Handle the config command
+///
+/// # Errors
+///
+/// Returns an error if the configuration cannot be loaded, is invalid,
+/// or cannot be rendered.
+pub async fn handle_config(args: ConfigArgs) -> Result<()> {
+    match args.command {
+        ConfigCommand::Check => check_config(),
+        ConfigCommand::Show(show_args) => show_config(show_args),
+    }
+}
+
+fn check_config() -> Result<()> {
+    let files = JackdawConfig::config_files();
+    if files.is_empty() {
+        println!("{} No config files found, using defaults", style("→").dim());
+    }
+    for file in &files {
+        println!("{} Found {}", style("→").dim(), file.display());
+    }
+
+    let config = match JackdawConfig::load_unvalidated() {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{} {}", style("✗").red(), e);
+            return Err(e.into());
+        }
+    };
+
+    let issues = config.validate();
+    if issues.is_empty() {
+        println!("{} Configuration is valid", style("✓").green());
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!(
+            "  {} [{}] {}",
+            style("ERROR").red().bold(),
+            style(&issue.key).yellow(),
+            issue.message
+        );
+    }
+    println!("{} Configuration is invalid", style("✗").red());
+
+    Err(Error::InvalidConfig {
+        count: issues.len(),
+    })
+}
+
+fn show_config(args: ShowArgs) -> Result<()> {
+    if args.resolved {
+        let config = args
+            .overrides
+            .merge_with_config(JackdawConfig::load_unvalidated()?);
+        print!("{}", serde_yaml::to_string(&config)?);
+        return Ok(());
+    }
+
+    println!("{}", style("# defaults").dim());
+    print!("{}", serde_yaml::to_string(&JackdawConfig::default())?);
+
+    for file in JackdawConfig::config_files() {
+        let contents = std::fs::read_to_string(&file).context(ReadConfigSnafu { path: &file })?;
+        println!("\n{}", style(format!("# {}", file.display())).dim());
+        println!("{}", contents.trim_end());
+    }
+
+    let mut env_vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(key, _)| key.starts_with("JACKDAW__"))
+        .collect();
+    if !env_vars.is_empty() {
+        env_vars.sort();
+        println!("\n{}", style("# environment").dim());
+        for (key, value) in env_vars {
+            println!("{key}={value}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("No debug bundle recorded for instance {instance_id}"))]
+    BundleNotFound { instance_id: String },
+
+    #[snafu(display("Failed to serialize debug bundle: {source}"))]
+    Serialize { source: serde_json::Error },
+
+    #[snafu(display("Failed to write {}: {source}", path.display()))]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Serialize { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct DebugBundleArgs {
+    /// ID of the faulted workflow instance
+    #[arg(value_name = "INSTANCE_ID")]
+    pub instance_id: String,
+
+    /// Write the bundle to this file instead of printing it
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the debug-bundle subcommand
+///
+/// Exports, as JSON, the diagnostic bundle the engine stored when the instance
+/// faulted: its error, the failing task and its definition, the redacted
+/// context and task input, its last events, and the runtime it ran on. As with
+/// `history`, unless another persistence provider is selected the bundle is
+/// read from the redb database at `--durable-db`.
+///
+/// # Errors
+///
+/// Returns an error if the persistence provider cannot be created, no bundle
+/// is recorded for the instance, or the output file cannot be written.
+pub async fn handle_debug_bundle(
+    instance_id: String,
+    output: Option<PathBuf>,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    let providers = providers.durable();
+
+    let persistence = providers.create_persistence(&config).await?;
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .build()?;
+    let Some(bundle) = engine.debug_bundle(&instance_id).await? else {
+        return BundleNotFoundSnafu { instance_id }.fail();
+    };
+    let json = serde_json::to_string_pretty(&bundle)?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, json).context(WriteSnafu { path: path.clone() })?;
+            println!(
+                "{} Wrote debug bundle of instance {} to {}",
+                style("✓").green(),
+                instance_id,
+                path.display()
+            );
+        }
+        None => println!("{json}"),
+    }
+    Ok(())
+}
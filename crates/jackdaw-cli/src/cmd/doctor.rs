@@ -0,0 +1,71 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+
+use crate::builder::DurableEngineBuilder;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Unavailable executors: {}", executors.join(", ")))]
+    Unavailable { executors: Vec<String> },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct DoctorArgs {
+    /// Enable verbose output
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+}
+
+/// Handle the doctor subcommand
+///
+/// Checks the dependencies of every registered executor, such as the Python
+/// and Node.js interpreters, and prints their versions.
+///
+/// # Errors
+///
+/// Returns an error if the engine cannot be created or any executor is
+/// missing a dependency.
+pub async fn handle_doctor(_args: DoctorArgs) -> Result<()> {
+    let engine = DurableEngineBuilder::new().build()?;
+    let health = engine.check_executors().await;
+
+    let width = health.keys().map(String::len).max().unwrap_or(0);
+    let mut unavailable = Vec::new();
+    println!("{}", style("Executors").bold());
+    for (call_type, health) in &health {
+        if health.is_ready() {
+            println!(
+                "  {} {call_type:<width$}  {}",
+                style("✓").green(),
+                health.version.as_deref().unwrap_or_default()
+            );
+        } else {
+            println!(
+                "  {} {call_type:<width$}  {}",
+                style("✗").red(),
+                health.missing.join(", ")
+            );
+            unavailable.push(call_type.clone());
+        }
+    }
+
+    ensure!(
+        unavailable.is_empty(),
+        UnavailableSnafu {
+            executors: unavailable
+        }
+    );
+    Ok(())
+}
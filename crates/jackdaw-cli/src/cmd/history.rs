@@ -0,0 +1,153 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+use crate::executionhistory::{TimelineEntry, timeline};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("Failed to read events: {source}"))]
+    Persistence { source: crate::persistence::Error },
+
+    #[snafu(display("Invalid format '{format}'. Valid formats: table, json"))]
+    InvalidFormat { format: String },
+
+    #[snafu(display("No events recorded for instance {instance_id}"))]
+    InstanceNotFound { instance_id: String },
+
+    #[snafu(display("Failed to serialize history: {source}"))]
+    Serialize { source: serde_json::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::persistence::Error> for Error {
+    fn from(source: crate::persistence::Error) -> Self {
+        Error::Persistence { source }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Serialize { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct HistoryArgs {
+    /// ID of the workflow instance to inspect
+    #[arg(value_name = "INSTANCE_ID")]
+    pub instance_id: String,
+
+    /// Output format (table or json)
+    #[arg(short = 'f', long, default_value = "table", value_name = "FORMAT")]
+    pub format: String,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the history subcommand
+///
+/// Prints the events recorded for an instance in the order they happened.
+/// An in-memory store cannot hold the history of another process, so unless
+/// another persistence provider is selected the events are read from the redb
+/// database at `--durable-db`.
+///
+/// # Errors
+///
+/// Returns an error if the format is unknown, the persistence provider cannot
+/// be created, or no events are recorded for the instance.
+pub async fn handle_history(
+    instance_id: String,
+    format: String,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    if format != "table" && format != "json" {
+        return InvalidFormatSnafu { format }.fail();
+    }
+    let providers = providers.durable();
+
+    let persistence = providers.create_persistence(&config).await?;
+    let reads = providers.create_read_persistence(&persistence).await?;
+    let events = reads.get_events(&instance_id).await?;
+    ensure!(!events.is_empty(), InstanceNotFoundSnafu { instance_id });
+
+    let entries = timeline(&events);
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        println!(
+            "{} History of workflow instance {}\n",
+            style("→").cyan(),
+            instance_id
+        );
+        print_table(&entries);
+    }
+    Ok(())
+}
+
+/// Print the timeline as a table with one row per event
+fn print_table(entries: &[TimelineEntry]) {
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+                entry.event.to_string(),
+                entry.task.clone().unwrap_or_default(),
+                entry
+                    .duration_ms
+                    .map(|ms| format!("{ms}ms"))
+                    .unwrap_or_default(),
+                // Errors can span lines; the table shows the first
+                entry
+                    .detail
+                    .as_deref()
+                    .and_then(|detail| detail.lines().next())
+                    .unwrap_or_default()
+                    .to_string(),
+            ]
+        })
+        .collect();
+
+    let header = ["TIME", "EVENT", "TASK", "DURATION", "DETAIL"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: &[String; 5]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", style(format_row(&header.map(str::to_string))).bold());
+    for row in &rows {
+        println!("{}", format_row(row));
+    }
+}
@@ -0,0 +1,250 @@
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use console::style;
+use snafu::prelude::*;
+
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+use crate::durableengine::{
+    DurableEngine, FailureCluster, InstanceFilter, RunManifest, parse_iso8601_duration,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Invalid format '{format}'. Valid formats: table, json"))]
+    InvalidFormat { format: String },
+
+    #[snafu(display("Failed to serialize failures: {source}"))]
+    Serialize { source: serde_json::Error },
+
+    #[snafu(display("Instance {instance_id} has no run manifest"))]
+    NoManifest { instance_id: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Serialize { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct InstancesArgs {
+    #[command(subcommand)]
+    pub command: InstancesCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InstancesCommand {
+    /// Group recently failed instances by the fingerprint of their error
+    Failures(FailuresArgs),
+    /// Show the run manifest of an instance, or how it differs from another's
+    Manifest(ManifestArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct FailuresArgs {
+    /// Only look at instances started within this long ago (ISO 8601 duration)
+    #[arg(long, default_value = "P1D", value_name = "DURATION")]
+    pub since: String,
+
+    /// Only look at instances of this workflow ("namespace/name/version")
+    #[arg(short = 'w', long, value_name = "WORKFLOW")]
+    pub workflow: Option<String>,
+
+    /// Output format (table or json)
+    #[arg(short = 'f', long, default_value = "table", value_name = "FORMAT")]
+    pub format: String,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+#[derive(Parser, Debug)]
+pub struct ManifestArgs {
+    /// Instance whose manifest to show
+    pub instance_id: String,
+
+    /// Instance to compare against, listing only what differs
+    #[arg(long, value_name = "INSTANCE_ID")]
+    pub compare: Option<String>,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the instances command
+///
+/// As with `history`, unless another persistence provider is selected the
+/// instances are read from the redb database at `--durable-db`.
+///
+/// # Errors
+///
+/// Returns an error if the persistence provider cannot be created or the
+/// instances cannot be read.
+pub async fn handle_instances(args: InstancesArgs, config: JackdawConfig) -> Result<()> {
+    match args.command {
+        InstancesCommand::Failures(args) => failures(args, config).await,
+        InstancesCommand::Manifest(args) => manifest(args, config).await,
+    }
+}
+
+async fn manifest(args: ManifestArgs, config: JackdawConfig) -> Result<()> {
+    let providers = args.providers.durable();
+    let config = args.overrides.merge_with_config(config);
+
+    let persistence = providers.create_persistence(&config).await?;
+    let reads = providers.create_read_persistence(&persistence).await?;
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .with_read_persistence(reads)
+        .build()?;
+
+    let manifest = load_manifest(&engine, &args.instance_id).await?;
+    let Some(other) = args.compare else {
+        println!("{}", serde_json::to_string_pretty(&manifest)?);
+        return Ok(());
+    };
+    let differences = manifest.differences(&load_manifest(&engine, &other).await?);
+    if differences.is_empty() {
+        println!(
+            "{} The instances ran with identical manifests",
+            style("✓").green()
+        );
+    }
+    for difference in differences {
+        println!(
+            "{}\n  - {}\n  + {}",
+            style(&difference.field).bold(),
+            difference.left.as_deref().unwrap_or("(none)"),
+            difference.right.as_deref().unwrap_or("(none)")
+        );
+    }
+    Ok(())
+}
+
+async fn failures(args: FailuresArgs, config: JackdawConfig) -> Result<()> {
+    if args.format != "table" && args.format != "json" {
+        return InvalidFormatSnafu {
+            format: args.format,
+        }
+        .fail();
+    }
+    let providers = args.providers.durable();
+    let config = args.overrides.merge_with_config(config);
+
+    let window = parse_iso8601_duration(&args.since)?;
+    let filter = InstanceFilter {
+        workflow_id: args.workflow,
+        started_after: chrono::Duration::from_std(window)
+            .ok()
+            .and_then(|window| Utc::now().checked_sub_signed(window)),
+        ..InstanceFilter::default()
+    };
+
+    let persistence = providers.create_persistence(&config).await?;
+    let reads = providers.create_read_persistence(&persistence).await?;
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .with_read_persistence(reads)
+        .build()?;
+    let clusters = engine.failure_clusters(&filter).await?;
+
+    if args.format == "json" {
+        println!("{}", serde_json::to_string_pretty(&clusters)?);
+    } else if clusters.is_empty() {
+        println!(
+            "{} No instances failed in the last {}",
+            style("✓").green(),
+            args.since
+        );
+    } else {
+        print_table(&clusters);
+    }
+    Ok(())
+}
+
+async fn load_manifest(engine: &DurableEngine, instance_id: &str) -> Result<RunManifest> {
+    match engine.manifest(instance_id).await? {
+        Some(manifest) => Ok(manifest),
+        None => NoManifestSnafu { instance_id }.fail(),
+    }
+}
+
+/// Print the clusters as a table, the largest first
+fn print_table(clusters: &[FailureCluster]) {
+    let rows: Vec<[String; 6]> = clusters
+        .iter()
+        .map(|cluster| {
+            [
+                cluster.id.clone(),
+                cluster.count.to_string(),
+                cluster.fingerprint.task_name.clone().unwrap_or_default(),
+                cluster.fingerprint.error_type.clone(),
+                cluster
+                    .last_seen
+                    .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default(),
+                cluster.fingerprint.message.clone(),
+            ]
+        })
+        .collect();
+
+    let header = [
+        "FINGERPRINT",
+        "COUNT",
+        "TASK",
+        "TYPE",
+        "LAST SEEN",
+        "MESSAGE",
+    ];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: &[String; 6]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", style(format_row(&header.map(str::to_string))).bold());
+    for row in &rows {
+        println!("{}", format_row(row));
+    }
+}
@@ -0,0 +1,117 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+
+use crate::builder::DurableEngineBuilder;
+use crate::mock::{MockApi, MockServer};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Mock { source: crate::mock::Error },
+
+    #[snafu(display(
+        "Cannot route calls to the mock of '{}': the document names no absolute server URL",
+        path.display()
+    ))]
+    Unroutable { path: PathBuf },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::mock::Error> for Error {
+    fn from(source: crate::mock::Error) -> Self {
+        Error::Mock { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct MockArgs {
+    /// OpenAPI document (JSON or YAML) describing the API to mock
+    #[arg(value_name = "SPEC")]
+    pub spec: PathBuf,
+
+    /// Port to listen on
+    #[arg(short = 'p', long, default_value_t = 8081)]
+    pub port: u16,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+}
+
+/// Handle the mock subcommand
+///
+/// Serves the operations of an OpenAPI document with their example
+/// responses, or responses built from their schemas, until interrupted.
+///
+/// # Errors
+///
+/// Returns an error if the document cannot be loaded or the address cannot
+/// be bound.
+pub async fn handle_mock(args: MockArgs) -> Result<()> {
+    let api = MockApi::load(&args.spec)?;
+    let base_path = api.base_path().to_string();
+    let operations: Vec<(String, String, u16)> = api
+        .operations()
+        .iter()
+        .map(|operation| {
+            (
+                operation.method.clone(),
+                operation.path.clone(),
+                operation.status,
+            )
+        })
+        .collect();
+
+    let server = api.start(&format!("{}:{}", args.host, args.port)).await?;
+    println!(
+        "{} Mocking {} on {}{}",
+        style("✓").green(),
+        args.spec.display(),
+        server.url(),
+        base_path
+    );
+    for (method, path, status) in &operations {
+        println!("  {method:<7} {base_path}{path} → {status}");
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+    eprintln!("\nReceived Ctrl+C, shutting down gracefully...");
+    server.stop().await;
+    Ok(())
+}
+
+/// Start a mock server for each OpenAPI document and route calls to its API there
+///
+/// The servers listen on free local ports and stop when dropped.
+///
+/// # Errors
+///
+/// Returns an error if a document cannot be loaded, names no absolute server
+/// URL, or its server cannot be started.
+pub(crate) async fn start_mocks(
+    specs: &[PathBuf],
+    mut builder: DurableEngineBuilder,
+) -> Result<(DurableEngineBuilder, Vec<MockServer>)> {
+    let mut servers = Vec::new();
+    for spec in specs {
+        let api = MockApi::load(spec)?;
+        let Some(server_url) = api.server_url().map(str::to_string) else {
+            return UnroutableSnafu { path: spec.clone() }.fail();
+        };
+        let base_path = api.base_path().to_string();
+        let server = api.start("127.0.0.1:0").await?;
+        println!(
+            "{} Mocking {} at {}{}",
+            style("→").cyan(),
+            server_url,
+            server.url(),
+            base_path
+        );
+        builder = builder.with_mock_route(server_url, format!("{}{base_path}", server.url()));
+        servers.push(server);
+    }
+    Ok((builder, servers))
+}
@@ -0,0 +1,45 @@
+pub mod bundle;
+pub mod cache;
+pub mod cancel;
+pub mod config;
+pub mod debug_bundle;
+pub mod doctor;
+pub mod history;
+pub mod instances;
+pub mod mock;
+pub mod oci;
+pub mod replay;
+pub mod resume;
+pub mod run;
+pub mod serve;
+pub mod snapshot;
+pub mod stats;
+pub mod suspend;
+pub mod test;
+pub mod top;
+pub mod validate;
+pub mod visualize;
+pub mod worker;
+
+pub use bundle::{BundleArgs, handle_bundle};
+pub use cache::{CacheArgs, handle_cache};
+pub use cancel::{CancelArgs, handle_cancel};
+pub use config::{ConfigArgs, handle_config};
+pub use debug_bundle::{DebugBundleArgs, handle_debug_bundle};
+pub use doctor::{DoctorArgs, handle_doctor};
+pub use history::{HistoryArgs, handle_history};
+pub use instances::{InstancesArgs, handle_instances};
+pub use mock::{MockArgs, handle_mock};
+pub use oci::{PullArgs, PushArgs, handle_pull, handle_push};
+pub use replay::{ReplayArgs, handle_replay};
+pub use resume::{ResumeArgs, handle_resume};
+pub use run::{RunArgs, handle_run};
+pub use serve::{ServeArgs, handle_serve};
+pub use snapshot::{SnapshotArgs, handle_snapshot};
+pub use stats::{StatsArgs, handle_stats};
+pub use suspend::{SuspendArgs, handle_suspend};
+pub use test::{TestArgs, handle_test};
+pub use top::{TopArgs, handle_top};
+pub use validate::{ValidateArgs, handle_validate};
+pub use visualize::{VisualizeArgs, handle_visualize};
+pub use worker::{WorkerArgs, handle_worker};
@@ -0,0 +1,188 @@
+use clap::{Args, Parser};
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+
+use crate::bundle::extract_bundle;
+use crate::oci::{Credentials, OciClient, PASSWORD_ENV, Reference, USERNAME_ENV};
+use crate::signing::signature_path;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Bundle { source: crate::bundle::Error },
+
+    #[snafu(display("{source}"))]
+    Oci { source: crate::oci::Error },
+
+    #[snafu(display("Failed to read '{}': {source}", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to write '{}': {source}", path.display()))]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to serialize bundle manifest: {source}"))]
+    Serialize { source: serde_json::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::bundle::Error> for Error {
+    fn from(source: crate::bundle::Error) -> Self {
+        Error::Bundle { source }
+    }
+}
+
+impl From<crate::oci::Error> for Error {
+    fn from(source: crate::oci::Error) -> Self {
+        Error::Oci { source }
+    }
+}
+
+/// Registry connection options shared by `push` and `pull`
+#[derive(Args, Debug)]
+pub struct RegistryArgs {
+    /// Registry username
+    #[arg(long, env = USERNAME_ENV, value_name = "USERNAME")]
+    pub username: Option<String>,
+
+    /// Registry password or access token
+    #[arg(long, env = PASSWORD_ENV, value_name = "PASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
+
+    /// Use plain HTTP instead of HTTPS (always used for localhost registries)
+    #[arg(long)]
+    pub plain_http: bool,
+}
+
+impl RegistryArgs {
+    /// Build a registry client from these options
+    #[must_use]
+    pub fn client(&self) -> OciClient {
+        let credentials = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => Some(Credentials {
+                username: username.clone(),
+                password: password.clone(),
+            }),
+            (Some(_) | None, _) => None,
+        };
+        OciClient::new(credentials).with_plain_http(self.plain_http)
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct PushArgs {
+    /// Artifact reference to push to, e.g. ghcr.io/org/workflow:1.2.0
+    #[arg(value_name = "REFERENCE")]
+    pub reference: String,
+
+    /// Bundle archive to push, as created by `jackdaw bundle`
+    #[arg(short = 'b', long, value_name = "PATH", default_value = "bundle.tar")]
+    pub bundle: PathBuf,
+
+    /// Detached signature of the bundle to attach (defaults to `<bundle>.sig` if it exists)
+    #[arg(long, value_name = "PATH")]
+    pub signature: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub registry: RegistryArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct PullArgs {
+    /// Artifact reference to pull, by tag or digest
+    #[arg(value_name = "REFERENCE")]
+    pub reference: String,
+
+    /// Path to write the bundle archive to; an attached signature is written to `<path>.sig`
+    #[arg(short = 'o', long, value_name = "PATH", default_value = "bundle.tar")]
+    pub output: PathBuf,
+
+    #[command(flatten)]
+    pub registry: RegistryArgs,
+}
+
+/// Handle the push command
+///
+/// # Errors
+///
+/// Returns an error if the bundle is invalid or the registry rejects the push.
+pub async fn handle_push(args: PushArgs) -> Result<()> {
+    let reference = Reference::parse(&args.reference)?;
+
+    // Extracting validates the archive before anything is uploaded
+    let bundle = extract_bundle(&args.bundle)?;
+    let config = serde_json::to_vec(&bundle.manifest).context(SerializeSnafu)?;
+    let archive = std::fs::read(&args.bundle).context(ReadSnafu {
+        path: args.bundle.clone(),
+    })?;
+    let signature_file = args
+        .signature
+        .clone()
+        .or_else(|| Some(signature_path(&args.bundle)).filter(|path| path.is_file()));
+    let signature = match &signature_file {
+        Some(path) => Some(std::fs::read_to_string(path).context(ReadSnafu { path })?),
+        None => None,
+    };
+
+    println!(
+        "{} {} → {}",
+        style("Pushing:").bold().cyan(),
+        args.bundle.display(),
+        reference
+    );
+
+    let digest = args
+        .registry
+        .client()
+        .push(&reference, &archive, &config, signature.as_deref())
+        .await?;
+
+    if let Some(path) = &signature_file {
+        println!("  • Attached signature {}", path.display());
+    }
+
+    println!(
+        "{} Pushed {}@{}",
+        style("✓").green().bold(),
+        reference,
+        digest
+    );
+    Ok(())
+}
+
+/// Handle the pull command
+///
+/// # Errors
+///
+/// Returns an error if the artifact cannot be pulled or verified, or the
+/// bundle cannot be written.
+pub async fn handle_pull(args: PullArgs) -> Result<()> {
+    let reference = Reference::parse(&args.reference)?;
+
+    println!("{} {}", style("Pulling:").bold().cyan(), reference);
+
+    let pulled = args.registry.client().pull(&reference).await?;
+    std::fs::write(&args.output, &pulled.bundle).context(WriteSnafu {
+        path: args.output.clone(),
+    })?;
+    if let Some(signature) = &pulled.signature {
+        let path = signature_path(&args.output);
+        std::fs::write(&path, signature).context(WriteSnafu { path: &path })?;
+        println!("  • Wrote signature {}", path.display());
+    }
+
+    println!(
+        "{} Wrote {} ({})",
+        style("✓").green().bold(),
+        args.output.display(),
+        pulled.manifest_digest
+    );
+    Ok(())
+}
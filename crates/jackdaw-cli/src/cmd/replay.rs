@@ -0,0 +1,177 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+use crate::durableengine::ReplayReport;
+use crate::signing::SignatureVerifier;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("{source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("{source}"))]
+    Workflow { source: crate::cmd::resume::Error },
+
+    #[snafu(display("Signature verification failed: {source}"))]
+    Signature { source: crate::signing::Error },
+
+    #[snafu(display("Invalid format '{format}'. Valid formats: table, json"))]
+    InvalidFormat { format: String },
+
+    #[snafu(display("Failed to serialize report: {source}"))]
+    Serialize { source: serde_json::Error },
+
+    #[snafu(display("Replay of instance {instance_id} diverged {divergence}"))]
+    Nondeterministic {
+        instance_id: String,
+        divergence: crate::durableengine::Divergence,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<crate::cmd::resume::Error> for Error {
+    fn from(source: crate::cmd::resume::Error) -> Self {
+        Error::Workflow { source }
+    }
+}
+
+impl From<crate::signing::Error> for Error {
+    fn from(source: crate::signing::Error) -> Self {
+        Error::Signature { source }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Serialize { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ReplayArgs {
+    /// ID of the workflow instance to replay
+    #[arg(value_name = "INSTANCE_ID")]
+    pub instance_id: String,
+
+    /// Workflow file to replay the instance against
+    #[arg(value_name = "WORKFLOW")]
+    pub workflow: PathBuf,
+
+    /// Output format (table or json)
+    #[arg(short = 'f', long, default_value = "table", value_name = "FORMAT")]
+    pub format: String,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the replay subcommand
+///
+/// Re-executes the workflow against the recorded events of an instance,
+/// taking the recorded results of tasks with side effects, and reports where
+/// the replay departs from the recorded run. As with `history`, the events
+/// are read from the redb database at `--durable-db` unless another
+/// persistence provider is selected.
+///
+/// # Errors
+///
+/// Returns an error if the format is unknown, the workflow or the instance
+/// cannot be loaded, or the replay diverges from the recorded run.
+pub async fn handle_replay(
+    instance_id: String,
+    workflow_path: PathBuf,
+    format: String,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    if format != "table" && format != "json" {
+        return InvalidFormatSnafu { format }.fail();
+    }
+    let providers = providers.durable();
+
+    let persistence = providers.create_persistence(&config).await?;
+    let mut builder = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .with_env_policy(config.env_policy());
+    if let Some(base_dir) = &config.base_dir {
+        builder = builder.with_base_dir(base_dir);
+    }
+    if let Some(catalog_dir) = &config.catalog_dir {
+        builder = builder.with_catalog_dir(catalog_dir);
+    }
+    for source in &config.workflow_sources {
+        builder = builder.with_workflow_source(source.clone());
+    }
+    if let Some(depth) = config.max_call_depth {
+        builder = builder.with_max_call_depth(depth);
+    }
+    let engine = builder.build()?;
+
+    SignatureVerifier::from_config(&config)?.verify_file(&workflow_path)?;
+    let workflow = crate::cmd::resume::load_workflow(&workflow_path, config.strict_env)?;
+    let report = engine.replay_instance(workflow, &instance_id).await?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    match report.divergence {
+        Some(divergence) => NondeterministicSnafu {
+            instance_id,
+            divergence,
+        }
+        .fail(),
+        None => Ok(()),
+    }
+}
+
+/// Print the tasks the replay went through and where it diverged
+fn print_report(report: &ReplayReport) {
+    println!(
+        "{} Replayed workflow instance {}",
+        style("→").cyan(),
+        report.instance_id
+    );
+    println!(
+        "  {} recorded result{} used, {} task{} run again",
+        report.replayed.len(),
+        if report.replayed.len() == 1 { "" } else { "s" },
+        report.reexecuted.len(),
+        if report.reexecuted.len() == 1 {
+            ""
+        } else {
+            "s"
+        },
+    );
+    match &report.divergence {
+        Some(divergence) => println!("{} Diverged {divergence}", style("✗").red()),
+        None => println!("{} Replay matches the recorded run", style("✓").green()),
+    }
+}
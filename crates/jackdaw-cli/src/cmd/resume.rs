@@ -0,0 +1,172 @@
+use clap::Parser;
+use console::style;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::mock::start_mocks;
+use crate::cmd::run::{ProviderArgs, discover_workflow_files, engine_builder};
+use crate::config::JackdawConfig;
+use crate::interpolation::interpolate_env;
+use crate::output::{filter_internal_fields, redact_secrets};
+use crate::signing::SignatureVerifier;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("{source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Failed to read workflow '{}': {source}", path.display()))]
+    ReadWorkflow {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to parse workflow '{}': {source}", path.display()))]
+    ParseWorkflow {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[snafu(display("Interpolation error: {source}"))]
+    Interpolation { source: crate::interpolation::Error },
+
+    #[snafu(display("Failed to serialize output: {source}"))]
+    Serialize { source: serde_json::Error },
+
+    #[snafu(display("Signature verification failed: {source}"))]
+    Signature { source: crate::signing::Error },
+
+    #[snafu(display("{source}"))]
+    Policy {
+        source: crate::middleware::policy::LoadError,
+    },
+
+    #[snafu(display("Mock error: {source}"))]
+    Mock { source: crate::cmd::mock::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<crate::interpolation::Error> for Error {
+    fn from(source: crate::interpolation::Error) -> Self {
+        Error::Interpolation { source }
+    }
+}
+
+impl From<crate::signing::Error> for Error {
+    fn from(source: crate::signing::Error) -> Self {
+        Error::Signature { source }
+    }
+}
+
+impl From<crate::middleware::policy::LoadError> for Error {
+    fn from(source: crate::middleware::policy::LoadError) -> Self {
+        Error::Policy { source }
+    }
+}
+
+impl From<crate::cmd::mock::Error> for Error {
+    fn from(source: crate::cmd::mock::Error) -> Self {
+        Error::Mock { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ResumeArgs {
+    /// ID of the suspended workflow instance
+    #[arg(value_name = "INSTANCE_ID")]
+    pub instance_id: String,
+
+    /// Workflow file the instance was started from
+    #[arg(value_name = "WORKFLOW")]
+    pub workflow: PathBuf,
+
+    /// Workflow registry paths used to find sub-workflows started with `run.workflow`
+    #[arg(short = 'r', long = "registry", value_name = "PATH")]
+    pub registry: Option<Vec<PathBuf>>,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the resume subcommand
+///
+/// Continues a suspended instance from its last checkpoint in the configured
+/// persistence provider, replaying the tasks it completed before it was
+/// suspended, and prints the workflow output.
+///
+/// # Errors
+///
+/// Returns an error if the providers cannot be created, a workflow cannot be
+/// loaded, the instance is not suspended, or the resumed run fails.
+pub async fn handle_resume(
+    instance_id: String,
+    workflow_path: PathBuf,
+    registry: Option<Vec<PathBuf>>,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    let persistence = providers.create_persistence(&config).await?;
+    let cache = providers.create_cache(&config).await?;
+    let builder = engine_builder(&config, persistence, cache)?;
+    // The mock servers stop when dropped at the end of the command
+    let (builder, _mocks) = start_mocks(&config.mocks, builder).await?;
+    let engine = builder.build()?;
+    let verifier = SignatureVerifier::from_config(&config)?;
+
+    if let Some(registry_paths) = registry {
+        for path in discover_workflow_files(&registry_paths)? {
+            verifier.verify_file(&path)?;
+            let workflow = load_workflow(&path, config.strict_env)?;
+            engine.set_workflow_path(&workflow, &path).await;
+            engine.register_workflow(workflow).await?;
+        }
+    }
+
+    verifier.verify_file(&workflow_path)?;
+    let workflow = load_workflow(&workflow_path, config.strict_env)?;
+    engine.set_workflow_path(&workflow, &workflow_path).await;
+
+    println!(
+        "{} Resuming workflow instance {}",
+        style("→").cyan(),
+        instance_id
+    );
+    let output = engine.resume_instance(workflow, &instance_id).await;
+    engine.wait_for_shadows().await;
+    engine.shutdown().await;
+    let output = output?;
+
+    let filtered = redact_secrets(&filter_internal_fields(&output));
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&filtered).context(SerializeSnafu)?
+    );
+    Ok(())
+}
+
+pub(crate) fn load_workflow(path: &Path, strict_env: bool) -> Result<WorkflowDefinition> {
+    let yaml = std::fs::read_to_string(path).context(ReadWorkflowSnafu { path })?;
+    let yaml = interpolate_env(&yaml, strict_env)?;
+    serde_yaml::from_str(&yaml).context(ParseWorkflowSnafu { path })
+}
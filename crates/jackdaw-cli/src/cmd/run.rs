@@ -0,0 +1,1237 @@
+use clap::{Args, Parser};
+use console::style;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::builder::DurableEngineBuilder;
+use crate::bundle::{ExtractedBundle, Provenance, extract_bundle, is_bundle};
+use crate::cache::CacheProvider;
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::mock::start_mocks;
+use crate::config::JackdawConfig;
+use crate::durableengine::{DryRunStep, DurableEngine, EngineSnapshot};
+use crate::interpolation::interpolate_env;
+use crate::middleware::PolicyMiddleware;
+use crate::oci::{Credentials, OciClient, Reference};
+use crate::output::{filter_internal_fields, format_cache_stats, redact_secrets};
+use crate::persistence::PersistenceProvider;
+use crate::providers::cache::{InMemoryCache, PostgresCache, RedbCache, RedisCache, SqliteCache};
+use crate::providers::persistence::{
+    InMemoryPersistence, ObjectStorePersistence, PostgresPersistence, RedbPersistence,
+    ResilientPersistence, SqlitePersistence,
+};
+use crate::providers::visualization::DiagramFormat;
+use crate::signing::{SignatureVerifier, Verification};
+use crate::telemetry::Telemetry;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid workflow file: {message}"))]
+    InvalidWorkflowFile { message: String },
+
+    #[snafu(display("Path error: {message}"))]
+    Path { message: String },
+
+    #[snafu(display("I/O error: {source}"))]
+    Io { source: std::io::Error },
+
+    #[snafu(display("YAML parsing error: {source}"))]
+    Yaml { source: serde_yaml::Error },
+
+    #[snafu(display("JSON serialization error: {source}"))]
+    Json { source: serde_json::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Execution error: {source}"))]
+    Execution {
+        source: crate::execution_handle::Error,
+    },
+
+    #[snafu(display("Cache error: {source}"))]
+    Cache { source: crate::cache::Error },
+
+    #[snafu(display("Persistence error: {source}"))]
+    Persistence { source: crate::persistence::Error },
+
+    #[snafu(display("Progress display error: {source}"))]
+    Progress { source: std::io::Error },
+
+    #[snafu(display("Configuration error: {source}"))]
+    Config { source: crate::config::Error },
+
+    #[snafu(display("Interpolation error: {source}"))]
+    Interpolation { source: crate::interpolation::Error },
+
+    #[snafu(display("Bundle error: {source}"))]
+    Bundle { source: crate::bundle::Error },
+
+    #[snafu(display("OCI error: {source}"))]
+    Oci { source: crate::oci::Error },
+
+    #[snafu(display("Signature verification failed: {source}"))]
+    Signature { source: crate::signing::Error },
+
+    #[snafu(display("{source}"))]
+    Policy {
+        source: crate::middleware::policy::LoadError,
+    },
+
+    #[snafu(display("Mock error: {source}"))]
+    Mock { source: crate::cmd::mock::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<crate::execution_handle::Error> for Error {
+    fn from(source: crate::execution_handle::Error) -> Self {
+        Error::Execution { source }
+    }
+}
+
+impl From<crate::cache::Error> for Error {
+    fn from(source: crate::cache::Error) -> Self {
+        Error::Cache { source }
+    }
+}
+
+impl From<crate::persistence::Error> for Error {
+    fn from(source: crate::persistence::Error) -> Self {
+        Error::Persistence { source }
+    }
+}
+
+impl From<crate::config::Error> for Error {
+    fn from(source: crate::config::Error) -> Self {
+        Error::Config { source }
+    }
+}
+
+impl From<crate::interpolation::Error> for Error {
+    fn from(source: crate::interpolation::Error) -> Self {
+        Error::Interpolation { source }
+    }
+}
+
+impl From<crate::bundle::Error> for Error {
+    fn from(source: crate::bundle::Error) -> Self {
+        Error::Bundle { source }
+    }
+}
+
+impl From<crate::signing::Error> for Error {
+    fn from(source: crate::signing::Error) -> Self {
+        Error::Signature { source }
+    }
+}
+
+impl From<crate::middleware::policy::LoadError> for Error {
+    fn from(source: crate::middleware::policy::LoadError) -> Self {
+        Error::Policy { source }
+    }
+}
+
+impl From<crate::cmd::mock::Error> for Error {
+    fn from(source: crate::cmd::mock::Error) -> Self {
+        Error::Mock { source }
+    }
+}
+
+impl From<crate::oci::Error> for Error {
+    fn from(source: crate::oci::Error) -> Self {
+        Error::Oci { source }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Error::Io { source }
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(source: serde_yaml::Error) -> Self {
+        Error::Yaml { source }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Json { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct RunArgs {
+    /// Workflow file(s) to execute. Can be a single file, multiple files, a directory, a bundle (.tar)
+    /// or a bundle in an OCI registry (oci://ghcr.io/org/workflow:1.2.0)
+    #[arg(required = true, value_name = "WORKFLOW")]
+    pub workflows: Vec<PathBuf>,
+
+    /// Enable debug mode (show detailed execution information)
+    #[arg(long)]
+    pub debug: bool,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+
+    /// Input data for the workflow (JSON string or path to JSON file)
+    #[arg(short = 'i', long, value_name = "INPUT")]
+    pub input: Option<String>,
+
+    /// Workflow registry paths - directories or files containing workflows that can be called
+    #[arg(short = 'r', long = "registry", value_name = "PATH")]
+    pub registry: Option<Vec<PathBuf>>,
+
+    /// Write a Chrome trace of where the run spends its time (open in chrome://tracing or Perfetto)
+    #[arg(long, value_name = "PATH")]
+    pub profile_out: Option<PathBuf>,
+
+    /// Print which tasks would run with which inputs, without running call,
+    /// run, emit, listen or wait tasks
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Persistence and cache provider selection shared by commands that run workflows
+#[derive(Args, Debug, Clone)]
+pub struct ProviderArgs {
+    /// Persistence provider to use (memory, redb, sqlite, postgres, object-store)
+    #[arg(long, value_name = "PERSISTENCE_PROVIDER", default_value = "memory")]
+    pub persistence_provider: String,
+
+    /// Cache provider to use (memory, redb, sqlite, postgres, redis)
+    #[arg(long, value_name = "CACHE_PROVIDER", default_value = "memory")]
+    pub cache_provider: String,
+
+    /// SQLite database URL (e.g., 'workflow.db' or ':memory:')
+    #[arg(long, value_name = "SQLITE_DB_URL", env = "SQLITE_DB_URL")]
+    pub sqlite_db_url: Option<String>,
+
+    /// PostgreSQL database name
+    #[arg(long, value_name = "POSTGRES_DB_NAME", env = "POSTGRES_DB_NAME")]
+    pub postgres_db_name: Option<String>,
+
+    /// PostgreSQL user
+    #[arg(long, value_name = "POSTGRES_USER", env = "POSTGRES_USER")]
+    pub postgres_user: Option<String>,
+
+    /// PostgreSQL password
+    #[arg(long, value_name = "POSTGRES_PASSWORD", env = "POSTGRES_PASSWORD")]
+    pub postgres_password: Option<String>,
+
+    /// PostgreSQL hostname
+    #[arg(long, value_name = "POSTGRES_HOSTNAME", env = "POSTGRES_HOSTNAME")]
+    pub postgres_hostname: Option<String>,
+
+    /// Hostname of a PostgreSQL read replica that serves instance listings and
+    /// status queries, so they do not contend with running instances
+    #[arg(
+        long,
+        value_name = "POSTGRES_READ_HOSTNAME",
+        env = "POSTGRES_READ_HOSTNAME"
+    )]
+    pub postgres_read_hostname: Option<String>,
+
+    /// Object store URL for the object-store persistence provider
+    /// (e.g., 's3://bucket/jackdaw', 'gs://bucket/jackdaw' or 'file:///var/lib/jackdaw')
+    #[arg(long, value_name = "OBJECT_STORE_URL", env = "OBJECT_STORE_URL")]
+    pub object_store_url: Option<String>,
+
+    /// Redis URL for the redis cache provider (e.g., 'redis://localhost:6379'),
+    /// overriding `cache.redis_url` from the configuration
+    #[arg(long, value_name = "REDIS_URL")]
+    pub redis_url: Option<String>,
+}
+
+impl ProviderArgs {
+    /// The providers of a command that works on what earlier processes stored
+    ///
+    /// Inspecting or controlling instances and cache entries is pointless in
+    /// a store that starts empty, so the in-memory providers are replaced by
+    /// the redb databases at `--durable-db` and `--cache-db`.
+    #[must_use]
+    pub fn durable(mut self) -> Self {
+        if self.persistence_provider == "memory" {
+            self.persistence_provider = "redb".to_string();
+        }
+        if self.cache_provider == "memory" {
+            self.cache_provider = "redb".to_string();
+        }
+        self
+    }
+
+    /// Create the selected persistence provider
+    ///
+    /// # Errors
+    /// Returns an error if the provider name is unknown, required connection
+    /// parameters are missing, or the provider fails to initialize.
+    pub async fn create_persistence(
+        &self,
+        config: &JackdawConfig,
+    ) -> Result<Arc<dyn PersistenceProvider>> {
+        let persistence: Arc<dyn PersistenceProvider> = match self.persistence_provider.as_str() {
+            "memory" => {
+                // Use in-memory persistence provider (no files created)
+                Arc::new(InMemoryPersistence::new())
+            }
+            "redb" => {
+                let durable_db = config
+                    .durable_db
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from("workflow.db"));
+                Arc::new(RedbPersistence::new(
+                    durable_db.to_str().unwrap_or("workflow.db"),
+                )?)
+            }
+            "sqlite" => {
+                let db_url =
+                    self.sqlite_db_url
+                        .as_ref()
+                        .ok_or_else(|| Error::InvalidWorkflowFile {
+                            message:
+                                "SQLite persistence provider requires --sqlite-db-url parameter"
+                                    .to_string(),
+                        })?;
+                Arc::new(SqlitePersistence::new(db_url).await?)
+            }
+            "postgres" => {
+                let db_url = build_postgres_url(
+                    self.postgres_db_name.as_ref(),
+                    self.postgres_user.as_ref(),
+                    self.postgres_password.as_ref(),
+                    self.postgres_hostname.as_ref(),
+                )?;
+                Arc::new(PostgresPersistence::new(&db_url).await?)
+            }
+            "object-store" => {
+                let url =
+                    self.object_store_url
+                        .as_ref()
+                        .ok_or_else(|| Error::InvalidWorkflowFile {
+                            message:
+                                "Object store persistence provider requires --object-store-url parameter"
+                                    .to_string(),
+                        })?;
+                Arc::new(ObjectStorePersistence::from_url(url)?)
+            }
+            _ => {
+                return Err(Error::InvalidWorkflowFile {
+                    message: format!(
+                        "Invalid persistence provider '{}'. Valid options: memory, redb, sqlite, postgres, object-store",
+                        self.persistence_provider
+                    ),
+                });
+            }
+        };
+
+        // Ride out outages of the store when retries or spilling are configured
+        match config.persistence_resilience()? {
+            Some(options) => Ok(Arc::new(ResilientPersistence::new(persistence, options))),
+            None => Ok(persistence),
+        }
+    }
+
+    /// Create the provider that serves instance listings and status queries
+    ///
+    /// This is the PostgreSQL read replica when `--postgres-read-hostname` is
+    /// set, and `persistence` otherwise.
+    ///
+    /// # Errors
+    /// Returns an error if connection parameters are missing or the replica
+    /// cannot be reached.
+    pub async fn create_read_persistence(
+        &self,
+        persistence: &Arc<dyn PersistenceProvider>,
+    ) -> Result<Arc<dyn PersistenceProvider>> {
+        match (
+            self.persistence_provider.as_str(),
+            &self.postgres_read_hostname,
+        ) {
+            ("postgres", Some(hostname)) => {
+                let db_url = build_postgres_url(
+                    self.postgres_db_name.as_ref(),
+                    self.postgres_user.as_ref(),
+                    self.postgres_password.as_ref(),
+                    Some(hostname),
+                )?;
+                Ok(Arc::new(PostgresPersistence::replica(&db_url).await?))
+            }
+            _ => Ok(persistence.clone()),
+        }
+    }
+
+    /// Create the selected cache provider
+    ///
+    /// # Errors
+    /// Returns an error if the provider name is unknown, required connection
+    /// parameters are missing, or the provider fails to initialize.
+    pub async fn create_cache(&self, config: &JackdawConfig) -> Result<Arc<dyn CacheProvider>> {
+        let cache: Arc<dyn CacheProvider> = match self.cache_provider.as_str() {
+            "memory" => Arc::new(InMemoryCache::new()),
+            "redb" => {
+                let cache_db_path = config
+                    .cache_db
+                    .as_ref()
+                    .map(|p| p.to_str().unwrap_or("cache.db"))
+                    .unwrap_or("cache.db");
+                let cache_persistence = Arc::new(RedbPersistence::new(cache_db_path)?);
+                Arc::new(RedbCache::new(cache_persistence.db.clone())?)
+            }
+            "sqlite" => {
+                let db_url =
+                    self.sqlite_db_url
+                        .as_ref()
+                        .ok_or_else(|| Error::InvalidWorkflowFile {
+                            message: "SQLite cache provider requires --sqlite-db-url parameter"
+                                .to_string(),
+                        })?;
+                Arc::new(SqliteCache::new(db_url).await?)
+            }
+            "postgres" => {
+                let db_url = build_postgres_url(
+                    self.postgres_db_name.as_ref(),
+                    self.postgres_user.as_ref(),
+                    self.postgres_password.as_ref(),
+                    self.postgres_hostname.as_ref(),
+                )?;
+                Arc::new(PostgresCache::new(&db_url).await?)
+            }
+            "redis" => {
+                let url = self
+                    .redis_url
+                    .as_ref()
+                    .or(config.cache.redis_url.as_ref())
+                    .ok_or_else(|| Error::InvalidWorkflowFile {
+                        message:
+                            "Redis cache provider requires --redis-url or JACKDAW__CACHE__REDIS_URL"
+                                .to_string(),
+                    })?;
+                Arc::new(RedisCache::new(url).await?)
+            }
+            _ => {
+                return Err(Error::InvalidWorkflowFile {
+                    message: format!(
+                        "Invalid cache provider '{}'. Valid options: memory, redb, sqlite, postgres, redis",
+                        self.cache_provider
+                    ),
+                });
+            }
+        };
+
+        Ok(cache)
+    }
+}
+
+/// Discover all workflow files from the provided paths
+pub(crate) fn discover_workflow_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut workflow_files = Vec::new();
+
+    for path in paths {
+        if path.is_file() {
+            // Single file
+            if is_workflow_file(path) {
+                workflow_files.push(path.clone());
+            } else {
+                return Err(Error::InvalidWorkflowFile {
+                    message: format!(
+                        "File {} is not a valid workflow file (.yaml or .yml)",
+                        path.display()
+                    ),
+                });
+            }
+        } else if path.is_dir() {
+            // Directory - recursively find all workflow files
+            let entries = std::fs::read_dir(path).context(IoSnafu)?;
+
+            for entry in entries {
+                let entry = entry?;
+                let entry_path = entry.path();
+                if entry_path.is_file() && is_workflow_file(&entry_path) {
+                    workflow_files.push(entry_path);
+                }
+            }
+        } else {
+            return Err(Error::Path {
+                message: format!("Path {} does not exist", path.display()),
+            });
+        }
+    }
+
+    if workflow_files.is_empty() {
+        return Err(Error::Path {
+            message: "No workflow files found in the provided paths".to_string(),
+        });
+    }
+
+    Ok(workflow_files)
+}
+
+/// Check if a file is a workflow file based on extension
+fn is_workflow_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext == "yaml" || ext == "yml")
+}
+
+/// Parse the input of a run, given as a JSON string or the path of a JSON file
+fn parse_input(input: Option<&String>) -> Result<serde_json::Value> {
+    let Some(input_str) = input else {
+        return Ok(serde_json::json!({}));
+    };
+    // Try to parse as JSON first
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(input_str) {
+        return Ok(json);
+    }
+    // Try to read as file path
+    let input_path = PathBuf::from(input_str);
+    if input_path.exists() {
+        let file_content = std::fs::read_to_string(&input_path)?;
+        Ok(serde_json::from_str(&file_content)?)
+    } else {
+        Err(Error::InvalidWorkflowFile {
+            message: format!(
+                "Input '{}' is neither valid JSON nor a valid file path",
+                input_str
+            ),
+        })
+    }
+}
+
+/// Walk a single workflow without running the tasks that reach outside the
+/// engine, and print what it would do
+async fn dry_run_workflow(
+    workflow_path: &Path,
+    engine: &DurableEngine,
+    input: Option<&String>,
+    strict_env: bool,
+) -> Result<()> {
+    let workflow_yaml = std::fs::read_to_string(workflow_path)?;
+    let workflow_yaml = interpolate_env(&workflow_yaml, strict_env)?;
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml)?;
+    engine.set_workflow_path(&workflow, workflow_path).await;
+
+    let report = engine.dry_run(&workflow, parse_input(input)?).await?;
+    println!(
+        "{} Dry run of {}",
+        style("→").cyan(),
+        style(workflow_path.display()).bold()
+    );
+    for step in &report.steps {
+        match step {
+            DryRunStep::Run {
+                task_name,
+                task_type,
+                input,
+                stubbed,
+            } => {
+                let marker = if *stubbed {
+                    style("○").yellow()
+                } else {
+                    style("●").green()
+                };
+                let note = if *stubbed { " (not run)" } else { "" };
+                println!(
+                    "  {marker} {} {}{}",
+                    style(task_name).cyan(),
+                    style(format!("[{task_type}]")).dim(),
+                    style(note).yellow()
+                );
+                let input = redact_secrets(input);
+                println!("      input: {}", serde_json::to_string(&input)?);
+            }
+            DryRunStep::Skipped {
+                task_name,
+                condition,
+            } => println!(
+                "  {} {} skipped, condition false: {}",
+                style("–").dim(),
+                style(task_name).cyan(),
+                style(condition).dim()
+            ),
+            DryRunStep::CaseMatched { task_name, case } => println!(
+                "  {} {} matched case {}",
+                style("↳").dim(),
+                style(task_name).cyan(),
+                style(case).bold()
+            ),
+        }
+    }
+    match (&report.output, &report.error) {
+        (_, Some(error)) => println!("{} Stopped: {}", style("✗").red(), style(error).red()),
+        (Some(output), None) => {
+            let output = redact_secrets(&filter_internal_fields(output));
+            println!(
+                "{} Output: {}",
+                style("✓").green(),
+                serde_json::to_string_pretty(&output)?
+            );
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// Execute a single workflow with progress indication
+async fn execute_workflow(
+    workflow_path: &PathBuf,
+    engine: Arc<DurableEngine>,
+    progress: Option<&ProgressBar>,
+    _verbose: bool,
+    input: Option<&String>,
+    timeout: std::time::Duration,
+    strict_env: bool,
+    preflight: bool,
+) -> Result<(String, serde_json::Value, WorkflowDefinition)> {
+    if let Some(pb) = progress {
+        pb.set_message(format!("Loading {}", workflow_path.display()));
+    }
+
+    // Read and parse workflow, resolving ${{ env.* }} placeholders first
+    let workflow_yaml = std::fs::read_to_string(workflow_path)?;
+    let workflow_yaml = interpolate_env(&workflow_yaml, strict_env)?;
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml)?;
+
+    if let Some(pb) = progress {
+        pb.set_message(format!("Executing {}", workflow.document.name));
+    }
+
+    let input_data = parse_input(input)?;
+
+    // Execute workflow, resolving relative resource paths against its file
+    engine.set_workflow_path(&workflow, workflow_path).await;
+
+    // Report every unavailable resource before any task runs
+    if preflight {
+        if let Some(pb) = progress {
+            pb.set_message(format!("Checking resources of {}", workflow.document.name));
+        }
+        let report = engine.preflight(&workflow).await;
+        if !report.is_ok() {
+            return Err(crate::durableengine::Error::Preflight { report }.into());
+        }
+    }
+
+    let handle = engine.execute(workflow.clone(), input_data).await?;
+    let instance_id = handle.instance_id().to_string();
+
+    // Wait for completion within the configured timeout
+    let result = handle.wait_for_completion(timeout).await?;
+
+    if let Some(pb) = progress {
+        pb.finish_with_message(format!("Completed {}", workflow_path.display()));
+    }
+
+    Ok((instance_id, result, workflow))
+}
+
+/// Parse diagram format from string
+fn parse_diagram_format(format_str: &str) -> Result<DiagramFormat> {
+    match format_str.to_lowercase().as_str() {
+        "svg" => Ok(DiagramFormat::Svg),
+        "png" => Ok(DiagramFormat::Png),
+        "pdf" => Ok(DiagramFormat::Pdf),
+        "ascii" => Ok(DiagramFormat::Ascii),
+        "markdown" | "md" => Ok(DiagramFormat::Markdown),
+        _ => Err(Error::InvalidWorkflowFile {
+            message: format!(
+                "Invalid format '{format_str}'. Valid formats: svg, png, pdf, ascii, markdown"
+            ),
+        }),
+    }
+}
+
+/// Build PostgreSQL connection URL and validate all required parameters are provided
+fn build_postgres_url(
+    db_name: Option<&String>,
+    user: Option<&String>,
+    password: Option<&String>,
+    hostname: Option<&String>,
+) -> Result<String> {
+    let db_name = db_name.ok_or_else(|| Error::InvalidWorkflowFile {
+        message:
+            "PostgreSQL provider requires --postgres-db-name parameter or POSTGRES_DB_NAME env var"
+                .to_string(),
+    })?;
+    let user = user.ok_or_else(|| Error::InvalidWorkflowFile {
+        message: "PostgreSQL provider requires --postgres-user parameter or POSTGRES_USER env var"
+            .to_string(),
+    })?;
+    let password = password.ok_or_else(|| Error::InvalidWorkflowFile {
+        message: "PostgreSQL provider requires --postgres-password parameter or POSTGRES_PASSWORD env var".to_string(),
+    })?;
+    let hostname = hostname.ok_or_else(|| Error::InvalidWorkflowFile {
+        message: "PostgreSQL provider requires --postgres-hostname parameter or POSTGRES_HOSTNAME env var".to_string(),
+    })?;
+
+    Ok(format!(
+        "postgresql://{}:{}@{}/{}",
+        user, password, hostname, db_name
+    ))
+}
+
+/// A builder of an engine with the given providers and the settings of `config`
+///
+/// The commands that run workflows build their engines with it, adding what
+/// only they use, such as a read replica.
+///
+/// # Errors
+///
+/// Returns an error if a setting is invalid, or the policy, trusted keys,
+/// snapshot or a shadow workflow cannot be loaded.
+pub(crate) fn engine_builder(
+    config: &JackdawConfig,
+    persistence: Arc<dyn PersistenceProvider>,
+    cache: Arc<dyn CacheProvider>,
+) -> Result<DurableEngineBuilder> {
+    let mut builder = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .with_cache(cache)
+        .with_telemetry(Arc::new(Telemetry::new()))
+        .with_env_policy(config.env_policy())
+        .with_container_defaults(config.container_defaults())
+        .with_container_provider(config.container_provider.unwrap_or_default());
+    let (pool_size, pool_idle_ttl) = config.container_pool()?;
+    builder = builder.with_container_pool(pool_size, pool_idle_ttl);
+    if let Some(limit) = config.fork_max_parallelism {
+        builder = builder.with_fork_max_parallelism(limit);
+    }
+    if let Some(workers) = config.for_parallelism {
+        builder = builder.with_for_parallelism(workers);
+    }
+    builder = builder.with_number_mode(config.number_mode()?);
+    if let Some(ttl) = config.cache_ttl()? {
+        builder = builder.with_cache_ttl(ttl);
+    }
+    builder = builder.with_lease_ttl(config.lease_ttl()?);
+    builder = builder.with_secrets(config.secrets_provider()?);
+    for sink in config.event_sinks()? {
+        builder = builder.with_event_sink(sink);
+    }
+    if let Some(base_dir) = &config.base_dir {
+        builder = builder.with_base_dir(base_dir);
+    }
+    if let Some(catalog_dir) = &config.catalog_dir {
+        builder = builder.with_catalog_dir(catalog_dir);
+    }
+    for source in &config.workflow_sources {
+        builder = builder.with_workflow_source(source.clone());
+    }
+    if let Some(depth) = config.max_call_depth {
+        builder = builder.with_max_call_depth(depth);
+    }
+    // Sub-workflows and catalog functions are checked as workflow files are
+    builder = builder
+        .with_signature_verifier(SignatureVerifier::from_config(config)?)
+        .with_strict_env(config.strict_env);
+    if let Some(path) = &config.policy_file {
+        builder = builder.with_middleware(Arc::new(PolicyMiddleware::load(path)?));
+    }
+    if let Some(path) = &config.snapshot {
+        builder = builder.with_snapshot(EngineSnapshot::read(path)?);
+    }
+    if config.run_manifests {
+        builder = builder.with_run_manifests(config.manifest_config());
+    }
+    for expr in &config.watch_exprs {
+        builder = builder.with_watch_expr(expr);
+    }
+    let shadow_mode = config.shadow_mode.unwrap_or_default();
+    for path in &config.shadow_workflows {
+        let workflow_yaml = std::fs::read_to_string(path)?;
+        let workflow_yaml = interpolate_env(&workflow_yaml, config.strict_env)?;
+        builder = builder.with_shadow(serde_yaml::from_str(&workflow_yaml)?, shadow_mode);
+    }
+    if let Some(auth) = &config.listener_auth {
+        builder = builder.with_listener_auth(auth.clone());
+    }
+    Ok(builder)
+}
+
+/// Handle the run subcommand with graceful shutdown support
+pub async fn handle_run(
+    workflows: Vec<PathBuf>,
+    input: Option<String>,
+    registry: Option<Vec<PathBuf>>,
+    config: JackdawConfig,
+    multi_progress: MultiProgress,
+    debug: bool,
+    dry_run: bool,
+    providers: ProviderArgs,
+) -> Result<()> {
+    // Set up signal handler for graceful shutdown
+    let shutdown_signal = async {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+            let sigint_result = signal(SignalKind::interrupt());
+            let sigterm_result = signal(SignalKind::terminate());
+
+            if let (Ok(mut sigint), Ok(mut sigterm)) = (sigint_result, sigterm_result) {
+                tokio::select! {
+                    _ = sigint.recv() => {
+                        eprintln!("\nReceived SIGINT (Ctrl+C), shutting down gracefully...");
+                    }
+                    _ = sigterm.recv() => {
+                        eprintln!("\nReceived SIGTERM, shutting down gracefully...");
+                    }
+                }
+            } else {
+                eprintln!("Warning: Failed to set up signal handlers");
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            if let Ok(()) = tokio::signal::ctrl_c().await {
+                eprintln!("\nReceived Ctrl+C, shutting down gracefully...");
+            } else {
+                eprintln!("Warning: Failed to listen for Ctrl+C");
+            }
+        }
+    };
+
+    // Run the workflow execution with shutdown signal handling
+    tokio::select! {
+        result = run_workflows_internal(
+            workflows,
+            input,
+            registry,
+            config,
+            multi_progress,
+            debug,
+            dry_run,
+            providers,
+        ) => {
+            result
+        }
+        _ = shutdown_signal => {
+            // Graceful shutdown - just exit cleanly
+            eprintln!("Shutdown complete.");
+            std::process::exit(0);
+        }
+    }
+}
+
+/// Pull a bundle from an OCI registry and unpack it
+///
+/// Registry credentials are read from the environment. The bundle is checked
+/// against the signature attached to the artifact before it is unpacked.
+async fn pull_bundle(reference: &str, verifier: &SignatureVerifier) -> Result<ExtractedBundle> {
+    let reference = Reference::parse(reference)?;
+    let pulled = OciClient::new(Credentials::from_env())
+        .pull(&reference)
+        .await?;
+    let source = format!("oci://{reference}");
+    let verification = verifier.verify(&source, &pulled.bundle, pulled.signature.as_deref())?;
+
+    let archive = tempfile::NamedTempFile::new()?;
+    std::fs::write(archive.path(), &pulled.bundle)?;
+    let mut bundle = extract_bundle(archive.path())?;
+    bundle.provenance = Provenance {
+        source,
+        digest: pulled.manifest_digest,
+        signer: signer(&verification),
+    };
+    Ok(bundle)
+}
+
+/// The trusted identity that signed a verified file
+fn signer(verification: &Verification) -> Option<String> {
+    match verification {
+        Verification::Verified { identity } => Some(identity.clone()),
+        Verification::Skipped | Verification::Unverified { .. } => None,
+    }
+}
+
+/// Check a workflow file against the signature policy
+fn verify_workflow_file(verifier: &SignatureVerifier, path: &Path, verbose: bool) -> Result<()> {
+    let verification = verifier.verify_file(path)?;
+    if verbose && let Some(identity) = signer(&verification) {
+        println!("  • {} signed by {}", path.display(), identity);
+    }
+    Ok(())
+}
+
+/// Internal function that runs workflows (separated for signal handling)
+async fn run_workflows_internal(
+    workflows: Vec<PathBuf>,
+    input: Option<String>,
+    registry: Option<Vec<PathBuf>>,
+    config: JackdawConfig,
+    multi_progress: MultiProgress,
+    debug: bool,
+    dry_run: bool,
+    providers: ProviderArgs,
+) -> Result<()> {
+    // Set debug mode
+    crate::output::set_debug_mode(debug);
+
+    // Print banner (only in debug mode)
+    if debug {
+        println!(
+            "{}\n",
+            style("Jackdaw Serverless Workflow Runtime").bold().cyan()
+        );
+    }
+
+    let verifier = SignatureVerifier::from_config(&config)?;
+
+    // Unpack bundles; their sub-workflows join the registry. The extracted
+    // directories are removed when `bundles` is dropped at the end of the run.
+    // A bundle's signature covers the whole archive, so the files unpacked
+    // from it are not checked individually.
+    let mut registry = registry;
+    let mut bundles = Vec::new();
+    let mut bundled_files = Vec::new();
+    let mut workflow_paths = Vec::new();
+    for path in workflows {
+        let oci_reference = path.to_str().filter(|p| p.starts_with("oci://"));
+        if oci_reference.is_some() || is_bundle(&path) {
+            let bundle = match oci_reference {
+                Some(reference) => pull_bundle(reference, &verifier).await?,
+                None => {
+                    let verification = verifier.verify_file(&path)?;
+                    let mut bundle = extract_bundle(&path)?;
+                    bundle.provenance.signer = signer(&verification);
+                    bundle
+                }
+            };
+            workflow_paths.push(bundle.workflow_path());
+            bundled_files.push(bundle.workflow_path());
+            let subworkflows = bundle.subworkflow_paths();
+            if !subworkflows.is_empty() {
+                bundled_files.extend(subworkflows.iter().cloned());
+                registry.get_or_insert_with(Vec::new).extend(subworkflows);
+            }
+            bundles.push(bundle);
+        } else {
+            workflow_paths.push(path);
+        }
+    }
+
+    // Discover workflow files
+    let workflow_files = discover_workflow_files(&workflow_paths)?;
+    let completion_timeout = config.completion_timeout()?;
+
+    for file in workflow_files.iter().filter(|f| !bundled_files.contains(f)) {
+        verify_workflow_file(&verifier, file, config.verbose)?;
+    }
+
+    if config.verbose {
+        println!(
+            "{} Found {} workflow file(s):",
+            style("→").cyan(),
+            workflow_files.len()
+        );
+        for file in &workflow_files {
+            println!("  • {}", file.display());
+        }
+        println!();
+    }
+
+    // Initialize persistence and cache based on provider selection
+    if config.verbose || debug {
+        println!("{} Initializing providers...", style("→").cyan());
+        println!("  • Persistence: {}", providers.persistence_provider);
+        println!("  • Cache: {}", providers.cache_provider);
+        println!();
+    }
+
+    let persistence = providers.create_persistence(&config).await?;
+    let cache = providers.create_cache(&config).await?;
+
+    let builder = engine_builder(&config, persistence.clone(), cache.clone())?;
+    // The mock servers stop when dropped at the end of the command
+    let (builder, _mocks) = start_mocks(&config.mocks, builder).await?;
+    let engine = Arc::new(builder.build()?);
+
+    // Warm containers outlive the run unless they are removed, so drain the
+    // pool whether the workflows succeed or fail
+    let result = async {
+        // Record where bundled workflows came from on the instances they start
+        for bundle in &bundles {
+            let workflow_yaml = std::fs::read_to_string(bundle.workflow_path())?;
+            let workflow_yaml = interpolate_env(&workflow_yaml, config.strict_env)?;
+            let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml)?;
+            let mut metadata = serde_json::Map::new();
+            metadata.insert(
+                "provenance".to_string(),
+                serde_json::to_value(&bundle.provenance)?,
+            );
+            engine.set_workflow_metadata(&workflow, metadata).await;
+        }
+
+        // Register workflows from registry paths (if provided)
+        if let Some(registry_paths) = registry {
+            if config.verbose {
+                println!(
+                    "{} Registering workflows from registry...",
+                    style("→").cyan()
+                );
+            }
+            let registry_files = discover_workflow_files(&registry_paths)?;
+            for workflow_path in &registry_files {
+                if !bundled_files.contains(workflow_path) {
+                    verify_workflow_file(&verifier, workflow_path, config.verbose)?;
+                }
+                let workflow_yaml = std::fs::read_to_string(workflow_path)?;
+                let workflow_yaml = interpolate_env(&workflow_yaml, config.strict_env)?;
+                let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml)?;
+                engine.set_workflow_path(&workflow, workflow_path).await;
+                engine.register_workflow(workflow).await?;
+                if config.verbose {
+                    println!("  • Registered workflow from {}", workflow_path.display());
+                }
+            }
+            if config.verbose {
+                println!();
+            }
+        }
+
+        // Execute workflows
+        if dry_run {
+            for workflow_path in &workflow_files {
+                dry_run_workflow(workflow_path, &engine, input.as_ref(), config.strict_env).await?;
+            }
+        } else if config.parallel && workflow_files.len() > 1 {
+            // Parallel execution using futures::join_all
+            if debug || config.verbose {
+                multi_progress.println(format!(
+                    "{} Executing {} workflows in parallel...\n",
+                    style("→").cyan(),
+                    workflow_files.len()
+                ))?;
+            }
+
+            let futures: Vec<_> = workflow_files
+                .iter()
+                .map(|workflow_path| {
+                    let engine_clone = engine.clone();
+                    let verbose = config.verbose;
+                    let strict_env = config.strict_env;
+                    let preflight = config.preflight;
+                    let path = workflow_path.clone();
+                    let input_clone = input.clone();
+                    let pb = multi_progress.add(ProgressBar::new_spinner());
+                    let style_result = ProgressStyle::default_spinner()
+                        .template("{spinner:.cyan} {msg}")
+                        .map_err(|e| Error::Progress {
+                            source: std::io::Error::other(e.to_string()),
+                        });
+
+                    async move {
+                        let style = match style_result {
+                            Ok(s) => s,
+                            Err(e) => return (path, Err(e)),
+                        };
+                        pb.set_style(style);
+                        pb.enable_steady_tick(std::time::Duration::from_millis(100));
+
+                        let result = execute_workflow(
+                            &path,
+                            engine_clone,
+                            Some(&pb),
+                            verbose,
+                            input_clone.as_ref(),
+                            completion_timeout,
+                            strict_env,
+                            preflight,
+                        )
+                        .await;
+                        pb.finish_and_clear();
+                        (path, result)
+                    }
+                })
+                .collect();
+
+            let results = futures::future::join_all(futures).await;
+
+            // Print results
+            if debug || config.verbose {
+                multi_progress.println(format!("\n{}", style("Results:").bold().green()))?;
+            }
+            for (path, result) in results {
+                match result {
+                    Ok((instance_id, output, workflow)) => {
+                        if debug || config.verbose {
+                            multi_progress.println(format!(
+                                "\n{} {}",
+                                style("✓").green(),
+                                style(path.display()).bold()
+                            ))?;
+                        }
+
+                        // Always output the final result as JSON (even in non-debug mode)
+                        let filtered = redact_secrets(&filter_internal_fields(&output));
+                        multi_progress.println(serde_json::to_string_pretty(&filtered)?)?;
+
+                        // Visualization if requested
+                        if config.visualize {
+                            let viz_format = config.viz_format.as_deref().unwrap_or("svg");
+                            let format = parse_diagram_format(viz_format)?;
+                            let output_path = config.viz_output.as_deref();
+
+                            multi_progress.println(format!(
+                                "\n{} Generating visualization...",
+                                style("→").cyan()
+                            ))?;
+
+                            let viz_tool = config.viz_tool.as_deref().unwrap_or("d2");
+                            engine
+                                .visualize_execution(
+                                    &workflow,
+                                    &instance_id,
+                                    output_path,
+                                    format,
+                                    viz_tool,
+                                    &config.diagram_style(),
+                                    &config.diagram_filter(),
+                                )
+                                .await?;
+
+                            if let Some(output_path) = output_path {
+                                multi_progress.println(format!(
+                                    "{} Visualization saved to: {}",
+                                    style("✓").green(),
+                                    output_path.display()
+                                ))?;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let error_msg = format!("{e}");
+                        multi_progress.println(format!(
+                            "\n{} {} - {}",
+                            style("✗").red(),
+                            style(path.display()).bold(),
+                            style(&error_msg).red()
+                        ))?;
+                        return Err(e);
+                    }
+                }
+            }
+        } else {
+            // Sequential execution with progress
+            if debug || config.verbose {
+                multi_progress.println(format!(
+                    "{} Executing {} workflow(s)...\n",
+                    style("→").cyan(),
+                    workflow_files.len()
+                ))?;
+            }
+
+            // Only show progress bars in debug/verbose mode
+            let pb = if debug || config.verbose {
+                let progress_bar =
+                    multi_progress.add(ProgressBar::new(workflow_files.len() as u64));
+                progress_bar.set_style(
+                    ProgressStyle::default_bar()
+                        .template("{spinner:.cyan} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                        .map_err(|e| Error::Progress {
+                            source: std::io::Error::other(e.to_string()),
+                        })?
+                        .progress_chars("#>-"),
+                );
+                Some(progress_bar)
+            } else {
+                None
+            };
+
+            for workflow_path in workflow_files {
+                match execute_workflow(
+                    &workflow_path,
+                    engine.clone(),
+                    pb.as_ref(),
+                    config.verbose,
+                    input.as_ref(),
+                    completion_timeout,
+                    config.strict_env,
+                    config.preflight,
+                )
+                .await
+                {
+                    Ok((instance_id, result, workflow)) => {
+                        // Always output the final result as JSON (even in non-debug mode)
+                        let filtered = redact_secrets(&filter_internal_fields(&result));
+                        multi_progress.println(serde_json::to_string_pretty(&filtered)?)?;
+
+                        // Visualization if requested
+                        if config.visualize {
+                            let viz_format = config.viz_format.as_deref().unwrap_or("svg");
+                            let format = parse_diagram_format(viz_format)?;
+                            let output_path = config.viz_output.as_deref();
+
+                            multi_progress.println(format!(
+                                "\n{} Generating visualization...",
+                                style("→").cyan()
+                            ))?;
+
+                            let viz_tool = config.viz_tool.as_deref().unwrap_or("d2");
+                            engine
+                                .visualize_execution(
+                                    &workflow,
+                                    &instance_id,
+                                    output_path,
+                                    format,
+                                    viz_tool,
+                                    &config.diagram_style(),
+                                    &config.diagram_filter(),
+                                )
+                                .await?;
+
+                            if let Some(output_path) = output_path {
+                                multi_progress.println(format!(
+                                    "{} Visualization saved to: {}",
+                                    style("✓").green(),
+                                    output_path.display()
+                                ))?;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        multi_progress.println(format!(
+                            "{} Failed: {} - {}",
+                            style("✗").red(),
+                            style(workflow_path.display()).bold(),
+                            style(&e).red()
+                        ))?;
+                        return Err(e);
+                    }
+                }
+                if let Some(ref progress_bar) = pb {
+                    progress_bar.inc(1);
+                }
+            }
+
+            if let Some(progress_bar) = pb {
+                progress_bar.finish_with_message("All workflows completed");
+            }
+        }
+
+        Ok::<(), Error>(())
+    }
+    .await;
+    if result.is_ok() && (config.verbose || debug) {
+        match engine.cache_stats().await {
+            Ok(stats) => format_cache_stats(&stats),
+            Err(e) => tracing::warn!("Failed to read cache statistics: {e}"),
+        }
+    }
+    engine.wait_for_shadows().await;
+    engine.shutdown().await;
+    result
+}
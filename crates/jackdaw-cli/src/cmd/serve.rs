@@ -0,0 +1,980 @@
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::{Next, from_fn_with_state},
+    response::sse::{Event, KeepAlive, Sse},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use console::style;
+use serde::{Deserialize, Serialize};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::mock::start_mocks;
+use crate::cmd::run::{ProviderArgs, discover_workflow_files, engine_builder};
+use crate::config::JackdawConfig;
+use crate::durableengine::{
+    DurableEngine, Error as EngineError, InstanceFilter, InstanceState, InstanceStatus,
+    MemoryReport, parse_iso8601_duration,
+};
+use crate::interpolation::interpolate_env;
+use crate::persistence::{Change, PersistenceProvider};
+use crate::signing::SignatureVerifier;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Failed to read workflow file '{}': {source}", path.display()))]
+    ReadWorkflow {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to parse workflow file '{}': {source}", path.display()))]
+    ParseWorkflow {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[snafu(display("Interpolation error: {source}"))]
+    Interpolation { source: crate::interpolation::Error },
+
+    #[snafu(display("Signature verification failed: {source}"))]
+    Signature { source: crate::signing::Error },
+
+    #[snafu(display("{source}"))]
+    Policy {
+        source: crate::middleware::policy::LoadError,
+    },
+
+    #[snafu(display("Mock error: {source}"))]
+    Mock { source: crate::cmd::mock::Error },
+
+    #[snafu(display("Failed to bind to {address}: {source}"))]
+    Bind {
+        address: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Server error: {source}"))]
+    Server { source: std::io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<crate::interpolation::Error> for Error {
+    fn from(source: crate::interpolation::Error) -> Self {
+        Error::Interpolation { source }
+    }
+}
+
+impl From<crate::signing::Error> for Error {
+    fn from(source: crate::signing::Error) -> Self {
+        Error::Signature { source }
+    }
+}
+
+impl From<crate::middleware::policy::LoadError> for Error {
+    fn from(source: crate::middleware::policy::LoadError) -> Self {
+        Error::Policy { source }
+    }
+}
+
+impl From<crate::cmd::mock::Error> for Error {
+    fn from(source: crate::cmd::mock::Error) -> Self {
+        Error::Mock { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ServeArgs {
+    /// Address to bind the management API to
+    #[arg(
+        short = 'b',
+        long,
+        default_value = "127.0.0.1:8080",
+        value_name = "ADDR"
+    )]
+    pub bind: String,
+
+    /// Workflow registry paths - workflows that can be started by reference
+    #[arg(short = 'r', long = "registry", value_name = "PATH")]
+    pub registry: Option<Vec<PathBuf>>,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Instance state as reported by the management API
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceSummary {
+    pub instance_id: String,
+    pub workflow_id: Option<String>,
+    pub status: InstanceState,
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Metadata recorded when the instance started, such as bundle provenance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl From<InstanceStatus> for InstanceSummary {
+    fn from(status: InstanceStatus) -> Self {
+        Self {
+            instance_id: status.instance_id,
+            workflow_id: status.workflow_id,
+            status: status.state,
+            started_at: status.started_at,
+            output: status.output,
+            error: status.error,
+            metadata: status.metadata,
+        }
+    }
+}
+
+/// Request body for `POST /instances`
+#[derive(Debug, Deserialize)]
+struct StartRequest {
+    /// Registered workflow key ("namespace/name/version")
+    workflow: Option<String>,
+    /// Inline workflow definition, used instead of `workflow`
+    definition: Option<serde_json::Value>,
+    input: Option<serde_json::Value>,
+    /// Queue the instance for a `jackdaw worker` instead of running it here
+    #[serde(default)]
+    queue: bool,
+}
+
+struct ManagedInstance {
+    workflow: WorkflowDefinition,
+    summary: InstanceSummary,
+}
+
+/// Response body of `GET /debug/memory`
+#[derive(Debug, Serialize)]
+struct DebugMemory {
+    #[serde(flatten)]
+    report: MemoryReport,
+    /// Instances whose summaries the server keeps for the management API
+    managed_instances: usize,
+}
+
+/// Query of `GET /changes` and `GET /changes/stream`
+#[derive(Debug, Deserialize)]
+struct ChangesQuery {
+    /// Offset of the last change the consumer has seen; 0 starts from the first
+    #[serde(default)]
+    after: u64,
+    /// Only return the changes of this instance
+    instance: Option<String>,
+    /// Most changes returned, at most `MAX_CHANGES`
+    limit: Option<usize>,
+}
+
+/// Response body of `GET /changes`
+#[derive(Debug, Serialize)]
+struct ChangesPage {
+    changes: Vec<Change>,
+    /// Offset to pass as `after` for the next page
+    next: u64,
+}
+
+/// Most changes returned by one request, or sent per poll of a stream
+const MAX_CHANGES: usize = 1000;
+
+/// Query of `GET /stats`
+#[derive(Debug, Deserialize)]
+struct StatsQuery {
+    /// Only this workflow, by name or "namespace/name/version" key
+    workflow: Option<String>,
+    /// Only runs started within this long ago, as an ISO 8601 duration
+    since: Option<String>,
+}
+
+/// How often a change stream polls the feed once it has caught up
+const CHANGE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Consecutive samples of growing memory after which a leak is suspected
+const GROWTH_SAMPLES: usize = 6;
+
+/// Header carrying the base64 signature of a workflow posted to `/workflows`
+const SIGNATURE_HEADER: &str = "x-jackdaw-signature";
+
+struct ServerState {
+    engine: Arc<DurableEngine>,
+    /// Checked for outages of the store, during which instances are not changed
+    persistence: Arc<dyn PersistenceProvider>,
+    verifier: SignatureVerifier,
+    /// Whether placeholders of posted workflows must name set variables
+    strict_env: bool,
+    /// Instances started here, until they finish; finished instances are read
+    /// back from persistence
+    instances: RwLock<HashMap<String, ManagedInstance>>,
+}
+
+type SharedState = Arc<ServerState>;
+
+/// Handle the serve subcommand
+///
+/// Starts a long-running HTTP management API backed by a single `DurableEngine`.
+/// With `recover_on_start`, the instances a stopped process left running are
+/// resumed in the background.
+///
+/// # Errors
+///
+/// Returns an error if the providers cannot be created, a registry workflow
+/// cannot be loaded, or the server fails to bind.
+pub async fn handle_serve(
+    bind: String,
+    registry: Option<Vec<PathBuf>>,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    let persistence = providers.create_persistence(&config).await?;
+    let reads = providers.create_read_persistence(&persistence).await?;
+    let cache = providers.create_cache(&config).await?;
+    let eviction_interval = config
+        .cache_eviction_interval()
+        .map_err(crate::cmd::run::Error::from)?;
+    let eviction = crate::cache::spawn_eviction(cache.clone(), eviction_interval);
+    let memory_sample_interval = config
+        .memory_sample_interval()
+        .map_err(crate::cmd::run::Error::from)?;
+    let builder = engine_builder(&config, persistence.clone(), cache)?.with_read_persistence(reads);
+    // The mock servers stop when dropped at the end of the command
+    let (builder, _mocks) = start_mocks(&config.mocks, builder).await?;
+    let engine = Arc::new(builder.build()?);
+    let verifier = SignatureVerifier::from_config(&config)?;
+
+    if let Some(registry_paths) = registry {
+        for path in discover_workflow_files(&registry_paths)? {
+            verifier.verify_file(&path)?;
+            let workflow_yaml =
+                std::fs::read_to_string(&path).context(ReadWorkflowSnafu { path: &path })?;
+            let workflow_yaml = interpolate_env(&workflow_yaml, config.strict_env)?;
+            let workflow: WorkflowDefinition =
+                serde_yaml::from_str(&workflow_yaml).context(ParseWorkflowSnafu { path: &path })?;
+            engine.set_workflow_path(&workflow, &path).await;
+            engine.register_workflow(workflow).await?;
+            if config.verbose {
+                println!("  • Registered workflow from {}", path.display());
+            }
+        }
+    }
+
+    let state: SharedState = Arc::new(ServerState {
+        engine: engine.clone(),
+        persistence,
+        verifier,
+        strict_env: config.strict_env,
+        instances: RwLock::new(HashMap::new()),
+    });
+
+    let sampler = memory_sample_interval
+        .map(|interval| tokio::spawn(sample_memory(engine.clone(), interval)));
+    let recovery = config
+        .recover_on_start
+        .then(|| tokio::spawn(recover(engine.clone())));
+
+    let app = Router::new()
+        .route("/workflows", get(list_workflows).post(register_workflow))
+        .route("/instances", get(list_instances).post(start_instance))
+        .route("/instances/:id", get(get_instance))
+        .route("/instances/:id/cancel", post(cancel_instance))
+        .route("/instances/:id/suspend", post(suspend_instance))
+        .route("/instances/:id/resume", post(resume_instance))
+        .route("/stats", get(workflow_stats))
+        .route("/changes", get(list_changes))
+        .route("/changes/stream", get(stream_changes))
+        .route("/debug/memory", get(debug_memory))
+        .route("/metrics", get(metrics));
+    #[cfg(feature = "jemalloc")]
+    let app = app.route("/debug/memory/profile", post(dump_heap_profile));
+    let app = app
+        .layer(from_fn_with_state(state.clone(), reject_while_degraded))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&bind)
+        .await
+        .context(BindSnafu { address: &bind })?;
+
+    println!(
+        "{} Jackdaw management API listening on http://{}",
+        style("✓").green(),
+        bind
+    );
+
+    let served = axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            eprintln!("\nReceived Ctrl+C, shutting down gracefully...");
+        })
+        .await
+        .context(ServerSnafu);
+    eviction.abort();
+    if let Some(sampler) = sampler {
+        sampler.abort();
+    }
+    if let Some(recovery) = recovery {
+        recovery.abort();
+    }
+    engine.shutdown().await;
+    served
+}
+
+/// Resume the instances a stopped process left running, logging how each run ends
+async fn recover(engine: Arc<DurableEngine>) {
+    match engine.recover_incomplete().await {
+        Ok(runs) => {
+            for run in runs {
+                match run.output {
+                    Ok(_) => tracing::info!("Recovered instance {} completed", run.instance_id),
+                    Err(e) => {
+                        tracing::warn!("Recovered instance {} failed: {}", run.instance_id, e);
+                    }
+                }
+            }
+        }
+        Err(e) => tracing::warn!("Failed to recover incomplete instances: {e}"),
+    }
+}
+
+/// Log the engine's memory every `interval`, warning when it keeps growing
+///
+/// Allocated bytes are followed with the `jemalloc` feature, otherwise the
+/// bytes retained by running instances.
+async fn sample_memory(engine: Arc<DurableEngine>, interval: std::time::Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    let mut previous = None;
+    let mut growing = 0;
+    loop {
+        ticks.tick().await;
+        let report = match engine.memory_report().await {
+            Ok(report) => report,
+            Err(e) => {
+                tracing::warn!("Failed to measure memory: {e}");
+                continue;
+            }
+        };
+        let retained = report.retained_bytes();
+        let allocated = report.allocator.map(|allocator| allocator.allocated);
+        tracing::info!(
+            target: "jackdaw::memory",
+            instances = report.instances.len(),
+            retained_bytes = retained,
+            event_bus_queued = report.event_bus.queued,
+            cache_bytes = report.cache.map(|cache| cache.bytes),
+            allocated_bytes = allocated,
+            "Memory sample"
+        );
+
+        let current = allocated.unwrap_or(retained);
+        growing = match previous {
+            Some(previous) if current > previous => growing + 1,
+            Some(_) | None => 0,
+        };
+        previous = Some(current);
+        if growing == GROWTH_SAMPLES {
+            tracing::warn!(
+                target: "jackdaw::memory",
+                "Memory has grown for {GROWTH_SAMPLES} consecutive samples, to {current} bytes; \
+                 see /debug/memory for what is retained"
+            );
+        }
+    }
+}
+
+/// Refuse to start or change instances while persistence writes are being
+/// buffered in memory; queries keep being answered
+async fn reject_while_degraded(
+    State(state): State<SharedState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::GET
+        && request.uri().path().starts_with("/instances")
+        && state.persistence.is_degraded()
+    {
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Persistence is unavailable; instances cannot be started or changed until it recovers",
+        );
+    }
+    next.run(request).await
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({ "error": message.into() }))).into_response()
+}
+
+/// Map an engine error to a response, using 404/409 for unknown instances and refused transitions
+/// and 403 for policy denials
+fn engine_error_response(error: &EngineError) -> Response {
+    let status = if matches!(error, EngineError::InstanceNotFound { .. }) {
+        StatusCode::NOT_FOUND
+    } else if matches!(
+        error,
+        EngineError::Middleware {
+            source: crate::middleware::Error::Denied { .. }
+        }
+    ) {
+        StatusCode::FORBIDDEN
+    } else if matches!(
+        error,
+        EngineError::IllegalTransition { .. } | EngineError::InstanceLeased { .. }
+    ) {
+        StatusCode::CONFLICT
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    error_response(status, error.to_string())
+}
+
+fn workflow_key(workflow: &WorkflowDefinition) -> String {
+    format!(
+        "{}/{}/{}",
+        workflow.document.namespace, workflow.document.name, workflow.document.version
+    )
+}
+
+/// `GET /workflows` - list registered workflows
+async fn list_workflows(State(state): State<SharedState>) -> Response {
+    Json(state.engine.registered_workflows().await).into_response()
+}
+
+/// `POST /workflows` - register a workflow definition (YAML or JSON body)
+///
+/// The body's signature, if any, is passed in the `X-Jackdaw-Signature` header.
+/// Its `${{ env.NAME }}` placeholders are substituted once it is verified, as
+/// in workflow files.
+async fn register_workflow(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if let Err(e) = state.verifier.verify("posted workflow", &body, signature) {
+        return error_response(StatusCode::FORBIDDEN, e.to_string());
+    }
+
+    let body = match std::str::from_utf8(&body) {
+        Ok(body) => body,
+        Err(e) => {
+            return error_response(StatusCode::BAD_REQUEST, format!("Invalid workflow: {e}"));
+        }
+    };
+    let body = match interpolate_env(body, state.strict_env) {
+        Ok(body) => body,
+        Err(e) => {
+            return error_response(StatusCode::BAD_REQUEST, format!("Invalid workflow: {e}"));
+        }
+    };
+    let workflow: WorkflowDefinition = match serde_yaml::from_str(&body) {
+        Ok(workflow) => workflow,
+        Err(e) => {
+            return error_response(StatusCode::BAD_REQUEST, format!("Invalid workflow: {e}"));
+        }
+    };
+    if let Err(e) = DurableEngine::validate_workflow_graph(&workflow) {
+        return error_response(StatusCode::UNPROCESSABLE_ENTITY, e.to_string());
+    }
+
+    let key = workflow_key(&workflow);
+    match state.engine.register_workflow(workflow).await {
+        Ok(()) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "workflow": key })),
+        )
+            .into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// `POST /instances` - start a new workflow instance
+async fn start_instance(
+    State(state): State<SharedState>,
+    Json(request): Json<StartRequest>,
+) -> Response {
+    let workflow = match (request.definition, request.workflow) {
+        (Some(definition), _) => {
+            // Inline definitions carry no signature; register a signed workflow instead
+            if let Err(e) = state
+                .verifier
+                .verify("inline workflow definition", &[], None)
+            {
+                return error_response(StatusCode::FORBIDDEN, e.to_string());
+            }
+            match serde_json::from_value::<WorkflowDefinition>(definition) {
+                Ok(workflow) => workflow,
+                Err(e) => {
+                    return error_response(
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid workflow: {e}"),
+                    );
+                }
+            }
+        }
+        (None, Some(key)) => match state.engine.get_registered_workflow(&key).await {
+            Some(workflow) => workflow,
+            None => {
+                return error_response(
+                    StatusCode::NOT_FOUND,
+                    format!("Workflow not found in registry: {key}"),
+                );
+            }
+        },
+        (None, None) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "Request must include either 'workflow' or 'definition'",
+            );
+        }
+    };
+
+    let input = request.input.unwrap_or_else(|| serde_json::json!({}));
+    if request.queue {
+        return match state.engine.enqueue(&workflow, input).await {
+            Ok(instance_id) => (
+                StatusCode::ACCEPTED,
+                Json(InstanceSummary {
+                    instance_id,
+                    workflow_id: Some(workflow_key(&workflow)),
+                    status: InstanceState::Pending,
+                    started_at: None,
+                    output: None,
+                    error: None,
+                    metadata: None,
+                }),
+            )
+                .into_response(),
+            Err(e) => engine_error_response(&e),
+        };
+    }
+
+    let instance_id = uuid::Uuid::new_v4().to_string();
+    let summary = InstanceSummary {
+        instance_id: instance_id.clone(),
+        workflow_id: Some(workflow_key(&workflow)),
+        status: InstanceState::Running,
+        started_at: Some(Utc::now()),
+        output: None,
+        error: None,
+        metadata: None,
+    };
+
+    state.instances.write().await.insert(
+        instance_id.clone(),
+        ManagedInstance {
+            workflow: workflow.clone(),
+            summary: summary.clone(),
+        },
+    );
+    spawn_instance(&state, instance_id, workflow, input, false).await;
+
+    (StatusCode::CREATED, Json(summary)).into_response()
+}
+
+/// Run (or resume) an instance in the background and record its outcome
+async fn spawn_instance(
+    state: &SharedState,
+    instance_id: String,
+    workflow: WorkflowDefinition,
+    input: serde_json::Value,
+    resume: bool,
+) {
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        let result = if resume {
+            task_state
+                .engine
+                .resume_instance(workflow, &instance_id)
+                .await
+        } else {
+            task_state
+                .engine
+                .run_instance(workflow, Some(instance_id.clone()), input)
+                .await
+        };
+
+        // The suspend endpoint has already recorded the new status
+        if matches!(result, Err(EngineError::InstanceSuspended { .. })) {
+            return;
+        }
+
+        // The engine has recorded the state the run ended in
+        let recorded = task_state.engine.instance_state(&instance_id).await.ok();
+        let mut instances = task_state.instances.write().await;
+        if recorded.is_some_and(InstanceState::is_finished) {
+            // `GET /instances/:id` reads it back from persistence from now on
+            instances.remove(&instance_id);
+            return;
+        }
+        let Some(instance) = instances.get_mut(&instance_id) else {
+            return;
+        };
+        if matches!(
+            instance.summary.status,
+            InstanceState::Cancelling | InstanceState::Cancelled
+        ) {
+            // The cancellation has already been recorded by the cancel endpoint
+            instance.summary.status = InstanceState::Cancelled;
+            return;
+        }
+        match result {
+            Ok(output) => {
+                instance.summary.status = recorded.unwrap_or(InstanceState::Completed);
+                instance.summary.output = Some(output);
+            }
+            Err(e) => {
+                instance.summary.status = recorded.unwrap_or(InstanceState::Faulted);
+                instance.summary.error = Some(e.to_string());
+            }
+        }
+    });
+}
+
+/// `GET /instances` - list the unfinished instances managed by this server
+async fn list_instances(State(state): State<SharedState>) -> Response {
+    let mut summaries: Vec<InstanceSummary> = state
+        .instances
+        .read()
+        .await
+        .values()
+        .map(|instance| instance.summary.clone())
+        .collect();
+    summaries.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    Json(summaries).into_response()
+}
+
+/// `GET /instances/:id` - query instance status
+///
+/// Finished instances, and instances started by a previous server process,
+/// are reconstructed from their persisted event history.
+async fn get_instance(State(state): State<SharedState>, Path(id): Path<String>) -> Response {
+    if let Some(instance) = state.instances.read().await.get(&id) {
+        return Json(instance.summary.clone()).into_response();
+    }
+
+    match state.engine.get_instance_status(&id).await {
+        Ok(status) => Json(InstanceSummary::from(status)).into_response(),
+        Err(e) => engine_error_response(&e),
+    }
+}
+
+/// `GET /stats` - summaries of the recorded runs of each workflow
+async fn workflow_stats(
+    State(state): State<SharedState>,
+    Query(query): Query<StatsQuery>,
+) -> Response {
+    let mut filter = InstanceFilter::default();
+    if let Some(since) = &query.since {
+        let window = match parse_iso8601_duration(since) {
+            Ok(window) => window,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+        };
+        filter.started_after = chrono::Duration::from_std(window)
+            .ok()
+            .and_then(|window| Utc::now().checked_sub_signed(window));
+    }
+    match state.engine.workflow_stats(&filter).await {
+        Ok(mut stats) => {
+            if let Some(workflow) = &query.workflow {
+                stats.retain(|stats| stats.is_workflow(workflow));
+            }
+            Json(stats).into_response()
+        }
+        Err(e) => engine_error_response(&e),
+    }
+}
+
+/// `GET /changes` - a page of the change feed of recorded events
+async fn list_changes(
+    State(state): State<SharedState>,
+    Query(query): Query<ChangesQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(MAX_CHANGES).min(MAX_CHANGES);
+    match state
+        .engine
+        .changes(query.after, query.instance.as_deref(), limit)
+        .await
+    {
+        Ok(changes) => {
+            let next = changes.last().map_or(query.after, |change| change.offset);
+            Json(ChangesPage { changes, next }).into_response()
+        }
+        Err(e) => engine_error_response(&e),
+    }
+}
+
+/// `GET /changes/stream` - follow the change feed as server-sent events
+///
+/// Each event's ID is its offset, so a client that reconnects with
+/// `Last-Event-ID` resumes after the last change it received.
+async fn stream_changes(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<ChangesQuery>,
+) -> Response {
+    let after = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(query.after);
+    let limit = query.limit.unwrap_or(MAX_CHANGES).min(MAX_CHANGES);
+    let instance = query.instance;
+    // A provider without a change feed is reported before the stream starts
+    let first = match state
+        .engine
+        .changes(after, instance.as_deref(), limit)
+        .await
+    {
+        Ok(changes) => std::collections::VecDeque::from(changes),
+        Err(e) => return engine_error_response(&e),
+    };
+
+    let stream = futures::stream::unfold(
+        (state, after, first),
+        move |(state, mut after, mut pending)| {
+            let instance = instance.clone();
+            async move {
+                loop {
+                    if let Some(change) = pending.pop_front() {
+                        after = change.offset;
+                        let event = Event::default()
+                            .id(change.offset.to_string())
+                            .json_data(&change.event);
+                        return Some((event, (state, after, pending)));
+                    }
+                    match state
+                        .engine
+                        .changes(after, instance.as_deref(), limit)
+                        .await
+                    {
+                        Ok(changes) if changes.is_empty() => {
+                            tokio::time::sleep(CHANGE_POLL_INTERVAL).await;
+                        }
+                        Ok(changes) => pending.extend(changes),
+                        Err(e) => {
+                            tracing::warn!("Failed to read the change feed: {}", e);
+                            return None;
+                        }
+                    }
+                }
+            }
+        },
+    );
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// `GET /debug/memory` - memory held by the engine and the server
+async fn debug_memory(State(state): State<SharedState>) -> Response {
+    match state.engine.memory_report().await {
+        Ok(report) => Json(DebugMemory {
+            report,
+            managed_instances: state.instances.read().await.len(),
+        })
+        .into_response(),
+        Err(e) => engine_error_response(&e),
+    }
+}
+
+/// `GET /metrics` - the engine's metrics in the Prometheus text format
+async fn metrics(State(state): State<SharedState>) -> Response {
+    use std::fmt::Write as _;
+
+    match state.engine.metrics().await {
+        Ok(mut body) => {
+            let _ = write!(
+                body,
+                "# HELP jackdaw_managed_instances Instances kept by the management API\n\
+                 # TYPE jackdaw_managed_instances gauge\n\
+                 jackdaw_managed_instances {}\n",
+                state.instances.read().await.len()
+            );
+            (
+                [(
+                    axum::http::header::CONTENT_TYPE,
+                    "text/plain; version=0.0.4",
+                )],
+                body,
+            )
+                .into_response()
+        }
+        Err(e) => engine_error_response(&e),
+    }
+}
+
+/// `POST /debug/memory/profile` - write a jemalloc heap profile to the temp directory
+#[cfg(feature = "jemalloc")]
+async fn dump_heap_profile() -> Response {
+    let path = std::env::temp_dir().join(format!(
+        "jackdaw-{}.heap",
+        Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+    match crate::durableengine::dump_heap_profile(&path) {
+        Ok(()) => Json(serde_json::json!({ "path": path })).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// `POST /instances/:id/cancel` - cancel a running or suspended instance
+async fn cancel_instance(State(state): State<SharedState>, Path(id): Path<String>) -> Response {
+    let mut instances = state.instances.write().await;
+    let Some(instance) = instances.get_mut(&id) else {
+        drop(instances);
+        return unmanaged_instance_response(&state, &id, "cancelled").await;
+    };
+    let previous_status = instance.summary.status;
+    let next_status = if previous_status == InstanceState::Running {
+        InstanceState::Cancelling
+    } else {
+        InstanceState::Cancelled
+    };
+    if !previous_status.can_transition_to(next_status) {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!("Instance {id} is {previous_status} and cannot be cancelled"),
+        );
+    }
+
+    instance.summary.status = next_status;
+    let summary = instance.summary.clone();
+    drop(instances);
+
+    let reason = Some("Cancelled via management API".to_string());
+    match state.engine.cancel_instance(&id, reason).await {
+        Ok(()) => {
+            // A suspended instance has no run left to evict it once it finishes
+            if summary.status == InstanceState::Cancelled {
+                state.instances.write().await.remove(&id);
+            }
+            Json(summary).into_response()
+        }
+        Err(e) => {
+            if let Some(instance) = state.instances.write().await.get_mut(&id) {
+                instance.summary.status = previous_status;
+            }
+            engine_error_response(&e)
+        }
+    }
+}
+
+/// Refuse to change an instance this server does not manage
+///
+/// Finished instances are evicted once their run ends, and are refused as
+/// they would have been before; other instances are not found.
+async fn unmanaged_instance_response(state: &ServerState, id: &str, action: &str) -> Response {
+    match state.engine.instance_state(id).await {
+        Ok(status) if status.is_finished() => error_response(
+            StatusCode::CONFLICT,
+            format!("Instance {id} is {status} and cannot be {action}"),
+        ),
+        Ok(_) | Err(_) => {
+            error_response(StatusCode::NOT_FOUND, format!("Instance not found: {id}"))
+        }
+    }
+}
+
+/// `POST /instances/:id/suspend` - stop a running instance at its last checkpoint
+async fn suspend_instance(State(state): State<SharedState>, Path(id): Path<String>) -> Response {
+    let mut instances = state.instances.write().await;
+    let Some(instance) = instances.get_mut(&id) else {
+        drop(instances);
+        return unmanaged_instance_response(&state, &id, "suspended").await;
+    };
+    if !instance
+        .summary
+        .status
+        .can_transition_to(InstanceState::Suspended)
+    {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "Instance {id} is {} and cannot be suspended",
+                instance.summary.status
+            ),
+        );
+    }
+
+    instance.summary.status = InstanceState::Suspended;
+    let summary = instance.summary.clone();
+    drop(instances);
+
+    let reason = Some("Suspended via management API".to_string());
+    match state.engine.suspend_instance(&id, reason).await {
+        Ok(()) => Json(summary).into_response(),
+        Err(e) => {
+            if let Some(instance) = state.instances.write().await.get_mut(&id) {
+                instance.summary.status = InstanceState::Running;
+            }
+            engine_error_response(&e)
+        }
+    }
+}
+
+/// `POST /instances/:id/resume` - resume a suspended instance from its checkpoint
+///
+/// Tasks completed before the suspension are replayed from the event history.
+async fn resume_instance(State(state): State<SharedState>, Path(id): Path<String>) -> Response {
+    let mut instances = state.instances.write().await;
+    let Some(instance) = instances.get_mut(&id) else {
+        drop(instances);
+        return unmanaged_instance_response(&state, &id, "resumed").await;
+    };
+    if instance.summary.status != InstanceState::Suspended {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "Instance {id} is {} and cannot be resumed",
+                instance.summary.status
+            ),
+        );
+    }
+
+    instance.summary.status = InstanceState::Running;
+    let summary = instance.summary.clone();
+    let workflow = instance.workflow.clone();
+    drop(instances);
+
+    spawn_instance(&state, id, workflow, serde_json::json!({}), true).await;
+
+    Json(summary).into_response()
+}
@@ -0,0 +1,125 @@
+use clap::Parser;
+use console::style;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::path::PathBuf;
+
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::run::discover_workflow_files;
+use crate::interpolation::interpolate_env;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Failed to read workflow file '{}': {source}", path.display()))]
+    ReadWorkflow {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to parse workflow file '{}': {source}", path.display()))]
+    ParseWorkflow {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[snafu(display("Interpolation error: {source}"))]
+    Interpolation { source: crate::interpolation::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<crate::interpolation::Error> for Error {
+    fn from(source: crate::interpolation::Error) -> Self {
+        Error::Interpolation { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct SnapshotArgs {
+    /// Workflow registry paths - the workflows the snapshot registers
+    #[arg(short = 'r', long = "registry", value_name = "PATH", required = true)]
+    pub registry: Vec<PathBuf>,
+
+    /// Path of the snapshot to write
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "PATH",
+        default_value = "engine.snapshot.json"
+    )]
+    pub output: PathBuf,
+
+    /// Fail when a workflow references an unset `${{ env.NAME }}` variable
+    #[arg(long)]
+    pub strict_env: bool,
+
+    /// Enable verbose output
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+}
+
+/// Handle the snapshot command
+///
+/// # Errors
+///
+/// Returns an error if a workflow cannot be loaded or the snapshot cannot be
+/// written.
+pub async fn handle_snapshot(args: SnapshotArgs) -> Result<()> {
+    let engine = DurableEngineBuilder::new().build()?;
+    for path in discover_workflow_files(&args.registry)? {
+        let workflow_yaml =
+            std::fs::read_to_string(&path).context(ReadWorkflowSnafu { path: &path })?;
+        let workflow_yaml = interpolate_env(&workflow_yaml, args.strict_env)?;
+        let workflow: WorkflowDefinition =
+            serde_yaml::from_str(&workflow_yaml).context(ParseWorkflowSnafu { path: &path })?;
+        engine.set_workflow_path(&workflow, &path).await;
+        engine.register_workflow(workflow).await?;
+        if args.verbose {
+            println!("  • Registered workflow from {}", path.display());
+        }
+    }
+
+    let snapshot = engine.snapshot().await?;
+    snapshot.write(&args.output)?;
+
+    if args.verbose {
+        for (call_type, health) in &snapshot.executor_health {
+            if !health.is_ready() {
+                println!(
+                    "  • Executor {call_type} is missing {}",
+                    health.missing.join(", ")
+                );
+            }
+        }
+    }
+
+    println!(
+        "{} Wrote {} ({} workflow(s), {} expression(s), {} OpenAPI document(s), {} proto file(s))",
+        style("✓").green().bold(),
+        args.output.display(),
+        snapshot.workflows.len(),
+        snapshot.expressions.len(),
+        snapshot.openapi_documents.len(),
+        snapshot.proto_descriptors.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use chrono::Utc;
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+use crate::durableengine::{InstanceFilter, WorkflowStats, parse_iso8601_duration};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Invalid format '{format}'. Valid formats: table, json"))]
+    InvalidFormat { format: String },
+
+    #[snafu(display("Failed to serialize stats: {source}"))]
+    Serialize { source: serde_json::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Serialize { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct StatsArgs {
+    /// Only summarize this workflow, by name or "namespace/name/version" key
+    #[arg(short = 'w', long, value_name = "WORKFLOW")]
+    pub workflow: Option<String>,
+
+    /// Only count runs started within this long ago (ISO 8601 duration, e.g. P7D)
+    #[arg(long, value_name = "DURATION")]
+    pub since: Option<String>,
+
+    /// Output format (table or json)
+    #[arg(short = 'f', long, default_value = "table", value_name = "FORMAT")]
+    pub format: String,
+
+    /// Recount the stored rollups from the recorded history first, e.g. for
+    /// a store written by an earlier version
+    #[arg(long)]
+    pub rebuild: bool,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the stats subcommand
+///
+/// Prints, per workflow, how many runs the persistence provider recorded, the
+/// share of finished runs that completed, percentiles of how long completed
+/// runs took, and the tasks failed runs most often stopped in. As with
+/// `history`, unless another persistence provider is selected the runs are
+/// read from the redb database at `--durable-db`: from the rollups stored
+/// there, or with `--since` from the recorded instances.
+///
+/// # Errors
+///
+/// Returns an error if the format or window is invalid, the persistence
+/// provider cannot be created, or the rollups or instances cannot be read
+/// or rebuilt.
+pub async fn handle_stats(
+    workflow: Option<String>,
+    since: Option<String>,
+    format: String,
+    rebuild: bool,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    if format != "table" && format != "json" {
+        return InvalidFormatSnafu { format }.fail();
+    }
+    let providers = providers.durable();
+
+    let mut filter = InstanceFilter::default();
+    if let Some(since) = since {
+        let window = parse_iso8601_duration(&since)?;
+        filter.started_after = chrono::Duration::from_std(window)
+            .ok()
+            .and_then(|window| Utc::now().checked_sub_signed(window));
+    }
+
+    let persistence = providers.create_persistence(&config).await?;
+    let reads = providers.create_read_persistence(&persistence).await?;
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .with_read_persistence(reads)
+        .build()?;
+    if rebuild {
+        engine.rebuild_workflow_rollups().await?;
+    }
+    let mut stats = engine.workflow_stats(&filter).await?;
+    if let Some(workflow) = workflow {
+        stats.retain(|stats| stats.is_workflow(&workflow));
+    }
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else if stats.is_empty() {
+        println!("{} No runs recorded", style("→").cyan());
+    } else {
+        print_table(&stats);
+    }
+    Ok(())
+}
+
+/// Print the stats as a table with one row per workflow
+fn print_table(stats: &[WorkflowStats]) {
+    let ms = |ms: u64| format!("{ms}ms");
+    let rows: Vec<[String; 8]> = stats
+        .iter()
+        .map(|stats| {
+            [
+                stats.workflow_id.clone(),
+                stats.runs.to_string(),
+                stats
+                    .success_rate
+                    .map(|rate| format!("{:.1}%", rate * 100.0))
+                    .unwrap_or_default(),
+                stats
+                    .duration_ms
+                    .map(|duration| ms(duration.p50))
+                    .unwrap_or_default(),
+                stats
+                    .duration_ms
+                    .map(|duration| ms(duration.p95))
+                    .unwrap_or_default(),
+                stats
+                    .duration_ms
+                    .map(|duration| ms(duration.p99))
+                    .unwrap_or_default(),
+                stats.in_flight.to_string(),
+                stats
+                    .failing_tasks
+                    .first()
+                    .map(|task| format!("{} ({})", task.task_name, task.failures))
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    let header = [
+        "WORKFLOW",
+        "RUNS",
+        "SUCCESS",
+        "P50",
+        "P95",
+        "P99",
+        "IN FLIGHT",
+        "MOST FAILING TASK",
+    ];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: &[String; 8]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", style(format_row(&header.map(str::to_string))).bold());
+    for row in &rows {
+        println!("{}", format_row(row));
+    }
+}
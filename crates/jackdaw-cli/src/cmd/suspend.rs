@@ -0,0 +1,80 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("{source}"))]
+    Engine { source: crate::durableengine::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct SuspendArgs {
+    /// ID of the workflow instance to suspend
+    #[arg(value_name = "INSTANCE_ID")]
+    pub instance_id: String,
+
+    /// Reason recorded with the suspension
+    #[arg(long, value_name = "REASON")]
+    pub reason: Option<String>,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the suspend subcommand
+///
+/// Records a suspension for the instance in the configured persistence
+/// provider, by default the redb database at `--durable-db`. A process running the instance against the same provider
+/// observes the suspension and stops at its last checkpoint; the instance can
+/// later be continued with `jackdaw resume`.
+///
+/// # Errors
+///
+/// Returns an error if the providers cannot be created, the instance does not
+/// exist or has already finished, or the suspension cannot be persisted.
+pub async fn handle_suspend(
+    instance_id: String,
+    reason: Option<String>,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    let persistence = providers.durable().create_persistence(&config).await?;
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .build()?;
+
+    engine.suspend_instance(&instance_id, reason).await?;
+
+    println!(
+        "{} Suspended workflow instance {}",
+        style("✓").green(),
+        instance_id
+    );
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::bdd;
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::run::discover_workflow_files;
+use crate::coverage::{Coverage, CoverageFormat};
+use crate::persistence::PersistenceProvider;
+use crate::providers::persistence::InMemoryPersistence;
+use crate::testing;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("{source}"))]
+    Testing { source: crate::testing::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("{count} test(s) failed"))]
+    TestsFailed { count: usize },
+
+    #[snafu(display("Feature scenarios failed"))]
+    ScenariosFailed,
+
+    #[snafu(display("Failed to write coverage report '{}': {source}", path.display()))]
+    WriteCoverage {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::testing::Error> for Error {
+    fn from(source: crate::testing::Error) -> Self {
+        Error::Testing { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct TestArgs {
+    /// Workflow file(s) whose `x-tests` to run. Can be a single file, multiple files, or a directory
+    #[arg(required_unless_present = "features", value_name = "WORKFLOW")]
+    pub workflows: Vec<PathBuf>,
+
+    /// Directory or file of Gherkin `.feature` scenarios to run against workflows
+    #[arg(long, value_name = "PATH")]
+    pub features: Option<PathBuf>,
+
+    /// Only run tests and scenarios whose name contains this text
+    #[arg(long, value_name = "TEXT")]
+    pub filter: Option<String>,
+
+    /// Seconds a task or workflow test may run before it fails
+    #[arg(long, default_value_t = 60)]
+    pub timeout: u64,
+
+    /// Fail when a workflow references an unset `${{ env.NAME }}` variable
+    #[arg(long)]
+    pub strict_env: bool,
+
+    /// Report which tasks, switch cases and catch blocks the tests hit (text, json or html)
+    #[arg(long, value_name = "FORMAT")]
+    pub coverage: Option<CoverageFormat>,
+
+    /// File to write the coverage report to, instead of printing it
+    #[arg(long, value_name = "PATH", requires = "coverage")]
+    pub coverage_output: Option<PathBuf>,
+}
+
+/// Handle the test command
+///
+/// Runs the test cases declared in the `x-tests` section of each workflow
+/// file, on an engine with in-memory persistence and cache, and then the
+/// scenarios of the `--features` files. With `--coverage`, it then reports
+/// which parts of the workflows the tests and scenarios hit.
+///
+/// # Errors
+///
+/// Returns an error if a workflow file or its tests cannot be loaded, any
+/// test or scenario fails, or the coverage report cannot be written.
+pub async fn handle_test(args: TestArgs) -> Result<()> {
+    let coverage = args.coverage.map(|_| Arc::new(Mutex::new(Coverage::new())));
+
+    let mut result = Ok(());
+    if !args.workflows.is_empty() {
+        result = run_workflow_tests(&args, coverage.as_ref()).await;
+    }
+    if let Some(features) = &args.features
+        && result.is_ok()
+    {
+        let passed = bdd::run_features(features, args.filter.clone(), coverage.clone()).await;
+        if !passed {
+            result = ScenariosFailedSnafu.fail();
+        }
+    }
+
+    // Reported even when tests fail, as coverage helps to see why
+    if let (Some(format), Some(coverage)) = (args.coverage, coverage)
+        && let Ok(coverage) = coverage.lock()
+    {
+        let report = coverage.render(format);
+        match &args.coverage_output {
+            Some(path) => {
+                std::fs::write(path, report).context(WriteCoverageSnafu { path })?;
+                println!("Coverage report written to {}", path.display());
+            }
+            None => println!("\n{}\n{report}", style("Coverage:").bold()),
+        }
+    }
+    result
+}
+
+/// Run the `x-tests` of the workflow files, printing a summary
+///
+/// With `coverage`, the workflows and the events of their runs are added to it.
+async fn run_workflow_tests(
+    args: &TestArgs,
+    coverage: Option<&Arc<Mutex<Coverage>>>,
+) -> Result<()> {
+    let workflow_files = discover_workflow_files(&args.workflows)?;
+    let timeout = Duration::from_secs(args.timeout);
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for workflow_path in &workflow_files {
+        println!(
+            "\n{} {}",
+            style("Testing:").bold().cyan(),
+            workflow_path.display()
+        );
+
+        let (workflow, tests) = testing::load(workflow_path, args.strict_env)?;
+        if let Some(coverage) = coverage
+            && let Ok(mut coverage) = coverage.lock()
+        {
+            coverage.add_workflow(&workflow);
+        }
+        let tests: Vec<_> = tests
+            .iter()
+            .filter(|test| {
+                args.filter
+                    .as_ref()
+                    .is_none_or(|filter| test.name.contains(filter.as_str()))
+            })
+            .collect();
+        if tests.is_empty() {
+            println!("  {}", style("No tests").dim());
+            continue;
+        }
+
+        let persistence = Arc::new(InMemoryPersistence::new());
+        let engine = DurableEngineBuilder::new()
+            .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+            .build()?;
+        engine.set_workflow_path(&workflow, workflow_path).await;
+        let mut outcomes = Vec::with_capacity(tests.len());
+        for test in tests {
+            outcomes.push((
+                test.name.as_str(),
+                testing::run_test(&engine, &workflow, test, timeout).await,
+            ));
+        }
+        engine.shutdown().await;
+        if let Some(coverage) = coverage {
+            record_coverage(persistence.as_ref(), coverage).await;
+        }
+
+        // Printed after the runs so the results are not interleaved with task output
+        for (name, outcome) in outcomes {
+            match outcome {
+                Ok(()) => {
+                    passed += 1;
+                    println!("  {} {}", style("✓").green().bold(), name);
+                }
+                Err(reason) => {
+                    failed += 1;
+                    println!("  {} {}: {}", style("✗").red().bold(), name, reason);
+                }
+            }
+        }
+    }
+
+    println!("\n{}", style("═".repeat(60)).dim());
+    println!(
+        "{} {} passed, {} failed",
+        style("Summary:").bold(),
+        style(passed).green(),
+        if failed > 0 {
+            style(failed.to_string()).red().bold()
+        } else {
+            style(failed.to_string()).dim()
+        }
+    );
+
+    ensure!(failed == 0, TestsFailedSnafu { count: failed });
+    Ok(())
+}
+
+/// Add the events of every instance in `persistence` to `coverage`
+async fn record_coverage(persistence: &dyn PersistenceProvider, coverage: &Mutex<Coverage>) {
+    for instance_id in persistence.list_instance_ids().await.unwrap_or_default() {
+        let Ok(events) = persistence.get_events(&instance_id).await else {
+            continue;
+        };
+        if let Ok(mut coverage) = coverage.lock() {
+            coverage.record(&events);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use snafu::prelude::*;
+
+use crate::builder::DurableEngineBuilder;
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::run::ProviderArgs;
+use crate::config::JackdawConfig;
+use crate::durableengine::{DurableEngine, InstanceFilter, InstanceStatus};
+use crate::executionhistory::{TimelineEntry, timeline};
+use crate::persistence::{InstanceState, PersistenceProvider};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Failed to read events: {source}"))]
+    Persistence { source: crate::persistence::Error },
+
+    #[snafu(display("Terminal error: {source}"))]
+    Terminal { source: std::io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<crate::persistence::Error> for Error {
+    fn from(source: crate::persistence::Error) -> Self {
+        Error::Persistence { source }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Error::Terminal { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct TopArgs {
+    /// Seconds between refreshes of the instances shown
+    #[arg(long, default_value_t = 1, value_name = "SECONDS")]
+    pub interval: u64,
+
+    /// Also show instances that have finished
+    #[arg(short = 'a', long)]
+    pub all: bool,
+
+    /// Number of recent events shown for the selected instance
+    #[arg(long, default_value_t = 20, value_name = "COUNT")]
+    pub events: usize,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the top subcommand
+///
+/// Shows the instances recorded in the persistence provider until `q` is
+/// pressed, refreshing them every `--interval` seconds, with the latest
+/// events of the selected one. As with `history`, unless another persistence
+/// provider is selected the instances are read from the redb database at
+/// `--durable-db`.
+///
+/// # Errors
+///
+/// Returns an error if the providers cannot be created, the instances cannot
+/// be read when the monitor starts, or the terminal cannot be drawn on.
+pub async fn handle_top(args: TopArgs, config: JackdawConfig) -> Result<()> {
+    let providers = args.providers.durable();
+    let config = args.overrides.merge_with_config(config);
+
+    let persistence = providers.create_persistence(&config).await?;
+    let reads = providers.create_read_persistence(&persistence).await?;
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence)
+        .with_read_persistence(reads.clone())
+        .build()?;
+
+    let mut monitor = Monitor {
+        engine,
+        reads,
+        all: args.all,
+        events: args.events,
+        instances: Vec::new(),
+        recent: Vec::new(),
+        table: TableState::default(),
+        pending: None,
+        message: None,
+    };
+    // Fail before taking over the terminal if the instances cannot be read
+    monitor.refresh().await?;
+
+    let mut terminal = ratatui::try_init()?;
+    let result = monitor
+        .run(&mut terminal, Duration::from_secs(args.interval.max(1)))
+        .await;
+    ratatui::try_restore()?;
+    result
+}
+
+/// Change to an instance the monitor asks to confirm before making it
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Cancel,
+    Suspend,
+}
+
+impl Action {
+    fn verb(self) -> &'static str {
+        match self {
+            Action::Cancel => "Cancel",
+            Action::Suspend => "Suspend",
+        }
+    }
+}
+
+/// The instances shown and the state of the keys pressed
+struct Monitor {
+    engine: DurableEngine,
+    reads: Arc<dyn PersistenceProvider>,
+    /// Show finished instances as well
+    all: bool,
+    /// Number of recent events shown
+    events: usize,
+    /// Instances shown, the latest started first
+    instances: Vec<InstanceStatus>,
+    /// Latest events of the selected instance
+    recent: Vec<TimelineEntry>,
+    table: TableState,
+    /// Action waiting for `y` to confirm it
+    pending: Option<Action>,
+    /// Outcome of the last action, or why the last refresh failed
+    message: Option<String>,
+}
+
+impl Monitor {
+    /// Draw and refresh the instances until `q` or `Esc` is pressed
+    async fn run(&mut self, terminal: &mut DefaultTerminal, interval: Duration) -> Result<()> {
+        let mut refreshed = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let timeout = interval.saturating_sub(refreshed.elapsed());
+            // Waiting for a key blocks, so other tasks are moved off this thread meanwhile
+            let pressed = tokio::task::block_in_place(|| -> std::io::Result<Option<KeyCode>> {
+                if event::poll(timeout)?
+                    && let Event::Key(key) = event::read()?
+                    && key.kind == KeyEventKind::Press
+                {
+                    return Ok(Some(key.code));
+                }
+                Ok(None)
+            })?;
+            if let Some(code) = pressed {
+                if self.key(code).await {
+                    return Ok(());
+                }
+                // Show the outcome of the key at once
+                self.refresh_or_report().await;
+                refreshed = Instant::now();
+            } else if refreshed.elapsed() >= interval {
+                self.refresh_or_report().await;
+                refreshed = Instant::now();
+            }
+        }
+    }
+
+    /// Refresh the instances, keeping the selection on the same instance
+    async fn refresh(&mut self) -> Result<()> {
+        let selected = self.selected().map(|status| status.instance_id.clone());
+        let mut instances = self
+            .engine
+            .list_instances(&InstanceFilter::default())
+            .await?;
+        let all = self.all;
+        instances.retain(|status| all || !status.state.is_finished());
+        instances.sort_by_key(|status| std::cmp::Reverse(status.started_at));
+
+        let index = selected
+            .and_then(|id| instances.iter().position(|status| status.instance_id == id))
+            .or_else(|| {
+                let last = instances.len().checked_sub(1)?;
+                Some(self.table.selected().unwrap_or(0).min(last))
+            });
+        self.table.select(index);
+        self.instances = instances;
+
+        self.recent = match self.selected() {
+            Some(status) => {
+                let entries = timeline(&self.reads.get_events(&status.instance_id).await?);
+                let skip = entries.len().saturating_sub(self.events);
+                entries.into_iter().skip(skip).collect()
+            }
+            None => Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Refresh the instances, showing why if they cannot be read rather
+    /// than leaving the monitor
+    async fn refresh_or_report(&mut self) {
+        if let Err(e) = self.refresh().await {
+            self.message = Some(format!("Failed to refresh: {e}"));
+        }
+    }
+
+    fn selected(&self) -> Option<&InstanceStatus> {
+        self.instances.get(self.table.selected()?)
+    }
+
+    /// Act on a key press, returning whether to leave the monitor
+    async fn key(&mut self, code: KeyCode) -> bool {
+        if let Some(action) = self.pending.take() {
+            if code == KeyCode::Char('y') {
+                self.apply(action).await;
+            } else {
+                self.message = Some(format!("{} skipped", action.verb()));
+            }
+            return false;
+        }
+        if code == KeyCode::Esc {
+            return true;
+        }
+        if code == KeyCode::Down {
+            self.table.select_next();
+        } else if code == KeyCode::Up {
+            self.table.select_previous();
+        } else if let KeyCode::Char(key) = code {
+            match key {
+                'q' => return true,
+                'j' => self.table.select_next(),
+                'k' => self.table.select_previous(),
+                'a' => self.all = !self.all,
+                'c' if self.selected().is_some() => self.pending = Some(Action::Cancel),
+                's' if self.selected().is_some() => self.pending = Some(Action::Suspend),
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Cancel or suspend the selected instance
+    async fn apply(&mut self, action: Action) {
+        let Some(instance_id) = self.selected().map(|status| status.instance_id.clone()) else {
+            return;
+        };
+        let result = match action {
+            Action::Cancel => self.engine.cancel_instance(&instance_id, None).await,
+            Action::Suspend => self.engine.suspend_instance(&instance_id, None).await,
+        };
+        self.message = Some(match (action, result) {
+            (Action::Cancel, Ok(())) => format!("Cancelled workflow instance {instance_id}"),
+            (Action::Suspend, Ok(())) => format!("Suspended workflow instance {instance_id}"),
+            (action, Err(e)) => format!("{} failed: {e}", action.verb()),
+        });
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [instances_area, events_area, footer_area] = Layout::vertical([
+            Constraint::Percentage(50),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let now = Utc::now();
+        let rows = self.instances.iter().map(|status| {
+            Row::new([
+                status.instance_id.clone(),
+                status.workflow_id.clone().unwrap_or_default(),
+                status.state.to_string(),
+                status.current_task.clone().unwrap_or_default(),
+                elapsed(status, now),
+            ])
+            .style(Style::default().fg(state_color(status.state)))
+        });
+        let shown = if self.all { "all" } else { "live" };
+        let instances = Table::new(
+            rows,
+            [
+                Constraint::Length(36),
+                Constraint::Fill(2),
+                Constraint::Length(10),
+                Constraint::Fill(1),
+                Constraint::Length(9),
+            ],
+        )
+        .header(
+            Row::new(["ID", "WORKFLOW", "STATE", "CURRENT TASK", "ELAPSED"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Instances ({shown}: {}) ", self.instances.len())),
+        );
+        frame.render_stateful_widget(instances, instances_area, &mut self.table);
+
+        let rows = self.recent.iter().map(|entry| {
+            Row::new([
+                entry.timestamp.format("%H:%M:%S%.3f").to_string(),
+                entry.event.to_string(),
+                entry.task.clone().unwrap_or_default(),
+                // Errors can span lines; the table shows the first
+                entry
+                    .detail
+                    .as_deref()
+                    .and_then(|detail| detail.lines().next())
+                    .unwrap_or_default()
+                    .to_string(),
+            ])
+        });
+        let title = match self.selected() {
+            Some(status) => format!(" Recent events of {} ", status.instance_id),
+            None => " Recent events ".to_string(),
+        };
+        let events = Table::new(
+            rows,
+            [
+                Constraint::Length(12),
+                Constraint::Length(20),
+                Constraint::Fill(1),
+                Constraint::Fill(2),
+            ],
+        )
+        .header(
+            Row::new(["TIME", "EVENT", "TASK", "DETAIL"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(events, events_area);
+
+        let footer = match (self.pending, &self.message) {
+            (Some(action), _) => Line::from(format!(
+                "{} {}? y to confirm, any other key to skip",
+                action.verb(),
+                self.selected()
+                    .map(|status| status.instance_id.as_str())
+                    .unwrap_or_default()
+            ))
+            .style(Style::default().fg(Color::Yellow)),
+            (None, Some(message)) => Line::from(message.as_str()),
+            (None, None) => {
+                Line::from("↑/↓ select  c cancel  s suspend  a show finished instances  q quit")
+                    .style(Style::default().add_modifier(Modifier::DIM))
+            }
+        };
+        frame.render_widget(Paragraph::new(footer), footer_area);
+    }
+}
+
+/// Color of the row of an instance in `state`
+fn state_color(state: InstanceState) -> Color {
+    match state {
+        InstanceState::Pending | InstanceState::Running => Color::Reset,
+        InstanceState::Suspended | InstanceState::Cancelling => Color::Yellow,
+        InstanceState::Completed => Color::Green,
+        InstanceState::Faulted | InstanceState::TimedOut => Color::Red,
+        InstanceState::Cancelled => Color::DarkGray,
+    }
+}
+
+/// How long an instance has run, or ran for if it has finished
+fn elapsed(status: &InstanceStatus, now: DateTime<Utc>) -> String {
+    let Some(started_at) = status.started_at else {
+        return String::new();
+    };
+    let seconds = (status.finished_at.unwrap_or(now) - started_at)
+        .num_seconds()
+        .max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m{seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
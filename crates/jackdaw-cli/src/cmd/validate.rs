@@ -6,7 +6,9 @@ use snafu::prelude::*;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
-use crate::expressions;
+use crate::contract::{self, ContractIssue};
+use crate::durableengine::FunctionRef;
+use crate::{expressions, interpolation};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -61,6 +63,10 @@ pub struct ValidateArgs {
     /// Show verbose output including all expressions checked
     #[arg(short = 'v', long)]
     pub verbose: bool,
+
+    /// Treat unset `${{ env.NAME }}` variables as errors instead of warnings
+    #[arg(long)]
+    pub strict_env: bool,
 }
 
 #[derive(Debug)]
@@ -96,7 +102,7 @@ pub async fn handle_validate(args: ValidateArgs) -> Result<()> {
             workflow_path.display()
         );
 
-        match validate_workflow(workflow_path, args.verbose).await {
+        match validate_workflow(workflow_path, args.verbose, args.strict_env).await {
             Ok((errors, warnings)) => {
                 total_errors += errors;
                 total_warnings += warnings;
@@ -152,11 +158,34 @@ pub async fn handle_validate(args: ValidateArgs) -> Result<()> {
     Ok(())
 }
 
-async fn validate_workflow(workflow_path: &PathBuf, verbose: bool) -> Result<(usize, usize)> {
+async fn validate_workflow(
+    workflow_path: &PathBuf,
+    verbose: bool,
+    strict_env: bool,
+) -> Result<(usize, usize)> {
     let mut issues: Vec<ValidationIssue> = Vec::new();
 
-    // 1. Parse the workflow
+    // 1. Resolve ${{ env.* }} placeholders and parse the workflow
     let workflow_yaml = std::fs::read_to_string(workflow_path)?;
+    for name in interpolation::referenced_env_vars(&workflow_yaml) {
+        if std::env::var(&name).is_err() {
+            issues.push(ValidationIssue {
+                severity: if strict_env {
+                    IssueSeverity::Error
+                } else {
+                    IssueSeverity::Warning
+                },
+                location: format!("env.{name}"),
+                message: format!("Environment variable '{name}' is not set"),
+            });
+        }
+    }
+    let workflow_yaml = interpolation::interpolate_with(&workflow_yaml, false, |name| {
+        Some(std::env::var(name).unwrap_or_default())
+    })
+    .map_err(|e| Error::InvalidWorkflowFile {
+        message: e.to_string(),
+    })?;
     let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml)?;
 
     // 2. Validate graph structure
@@ -186,7 +215,14 @@ async fn validate_workflow(workflow_path: &PathBuf, verbose: bool) -> Result<(us
     if verbose {
         println!("  {} Validating expressions...", style("→").dim());
     }
-    let expressions = extract_all_expressions(&workflow);
+    // Only jq expressions can be checked without running them
+    let language = expressions::ExpressionLanguage::of_workflow(&workflow);
+    let expressions =
+        if language.is_ok_and(|language| language == expressions::ExpressionLanguage::Jq) {
+            extract_all_expressions(&workflow)
+        } else {
+            Vec::new()
+        };
 
     if verbose && !expressions.is_empty() {
         println!("    Found {} expression(s) to validate", expressions.len());
@@ -210,7 +246,27 @@ async fn validate_workflow(workflow_path: &PathBuf, verbose: bool) -> Result<(us
     }
     validate_references(&workflow, &mut issues);
 
-    // 5. Report issues
+    // 5. Check calls against the OpenAPI and proto documents they reference
+    if verbose {
+        println!("  {} Checking call contracts...", style("→").dim());
+    }
+    let workflow_dir = workflow_path.parent().unwrap_or_else(|| Path::new("."));
+    for issue in contract::check_workflow(&workflow, workflow_dir).await {
+        issues.push(match issue {
+            ContractIssue::Violation { location, message } => ValidationIssue {
+                severity: IssueSeverity::Error,
+                location,
+                message,
+            },
+            ContractIssue::Unchecked { location, message } => ValidationIssue {
+                severity: IssueSeverity::Warning,
+                location,
+                message,
+            },
+        });
+    }
+
+    // 6. Report issues
     let errors: Vec<_> = issues
         .iter()
         .filter(|i| i.severity == IssueSeverity::Error)
@@ -685,7 +741,7 @@ fn validate_expression_syntax(expr: &str) -> std::result::Result<(), String> {
 
     // Try to evaluate the expression with a dummy context
     // This will compile the expression and check for syntax errors
-    match expressions::evaluate_expression(expr, &context) {
+    match expressions::evaluate_expression(expressions::Evaluation::default(), expr, &context) {
         Ok(_) => Ok(()),
         Err(e) => {
             // Extract meaningful error message
@@ -710,7 +766,10 @@ fn validate_expression_syntax(expr: &str) -> std::result::Result<(), String> {
                     }
                     message
                 }
-                expressions::Error::Evaluation { message } => message,
+                expressions::Error::Evaluation { message }
+                | expressions::Error::JsEvaluation { message } => message,
+                expressions::Error::UnsupportedLanguage { language } => language,
+                expressions::Error::UnsupportedMode { mode } => mode,
             };
             Err(error_msg)
         }
@@ -722,7 +781,7 @@ fn validate_references(workflow: &WorkflowDefinition, issues: &mut Vec<Validatio
 
     // Collect defined functions
     let mut defined_functions = HashSet::new();
-    let mut has_catalogs = false;
+    let mut catalogs = HashSet::new();
 
     if let Some(use_) = &workflow.use_ {
         // Collect inline function definitions
@@ -732,11 +791,12 @@ fn validate_references(workflow: &WorkflowDefinition, issues: &mut Vec<Validatio
             }
         }
 
-        // Check if catalogs are defined
-        if let Some(catalogs) = &use_.catalogs {
-            has_catalogs = !catalogs.is_empty();
+        // Collect catalog names
+        if let Some(defined_catalogs) = &use_.catalogs {
+            catalogs.extend(defined_catalogs.keys().cloned());
         }
     }
+    let has_catalogs = !catalogs.is_empty();
 
     // Check function references in 'call' tasks
     for entry in &workflow.do_.entries {
@@ -744,6 +804,21 @@ fn validate_references(workflow: &WorkflowDefinition, issues: &mut Vec<Validatio
             if let TaskDefinition::Call(call_task) = task_def {
                 let function_ref = &call_task.call;
 
+                // Catalog references naming their catalog must name a defined one
+                if let Some(catalog) =
+                    FunctionRef::parse(function_ref).and_then(|reference| reference.catalog)
+                    && !catalogs.contains(catalog)
+                {
+                    issues.push(ValidationIssue {
+                        severity: IssueSeverity::Error,
+                        location: format!("task.{task_name}.call"),
+                        message: format!(
+                            "Function '{function_ref}' names catalog '{catalog}', which is not defined in 'use.catalogs'"
+                        ),
+                    });
+                    continue;
+                }
+
                 // Skip HTTP/HTTPS calls and catalog references
                 if function_ref.starts_with("http://")
                     || function_ref.starts_with("https://")
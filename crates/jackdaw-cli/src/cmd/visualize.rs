@@ -0,0 +1,299 @@
+use clap::Parser;
+use console::style;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::{ResultExt, prelude::*};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Invalid format '{}'. Valid formats: svg, png, pdf, ascii, markdown",
+        format
+    ))]
+    InvalidFormat { format: String },
+
+    #[snafu(display("Failed to read workflow file '{}'", path.display()))]
+    ReadWorkflow {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to interpolate workflow file '{}'", path.display()))]
+    InterpolateWorkflow {
+        path: PathBuf,
+        source: crate::interpolation::Error,
+    },
+
+    #[snafu(display("Failed to parse workflow file '{}'", path.display()))]
+    ParseWorkflow {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[snafu(display(
+        "Output path (-o/--output) is required for {} format. Only ascii format can output to stdout.",
+        format
+    ))]
+    MissingOutputPath { format: String },
+
+    #[snafu(display("{message}"))]
+    NotImplemented { message: String },
+
+    #[snafu(display("Failed to find workflow files: {source}"))]
+    DiscoverWorkflows { source: crate::cmd::run::Error },
+
+    #[snafu(display("Unknown visualization tool '{tool}', expected one of: {}", known.join(", ")))]
+    UnknownTool { tool: String, known: Vec<String> },
+
+    #[snafu(display("{tool} cannot render {format:?} diagrams"))]
+    UnsupportedFormat { tool: String, format: DiagramFormat },
+
+    #[snafu(display("Failed to render visualization: {source}"))]
+    Render {
+        source: crate::providers::visualization::Error,
+    },
+
+    #[snafu(display("Failed to read events: {source}"))]
+    Persistence { source: crate::persistence::Error },
+}
+
+use crate::persistence::PersistenceProvider;
+use crate::providers::persistence::RedbPersistence;
+use crate::providers::visualization::{
+    self, CallGraph, ColorScheme, DiagramFormat, DiagramStyle, ExecutionState, Palette,
+    VisualizationProvider,
+};
+
+#[derive(Parser, Debug)]
+pub struct VisualizeArgs {
+    /// Workflow file to visualize
+    #[arg(required_unless_present = "registry", value_name = "WORKFLOW")]
+    pub workflow: Option<PathBuf>,
+
+    /// Render the call graph between the workflows in these files or
+    /// directories instead, highlighting missing references and cycles
+    #[arg(short = 'r', long, value_name = "PATH", conflicts_with = "workflow")]
+    pub registry: Option<Vec<PathBuf>>,
+
+    /// Workflow instance ID to show execution state (optional)
+    #[arg(short = 'i', long, value_name = "ID")]
+    pub instance_id: Option<String>,
+
+    /// Path to the durable persistence database (required if showing execution state)
+    #[arg(short = 'd', long, default_value = "workflow.db", value_name = "PATH")]
+    pub durable_db: PathBuf,
+
+    /// Visualization tool to use (graphviz, d2, mermaid, builtin, or a registered provider)
+    #[arg(short = 't', long, default_value = "graphviz", value_name = "TOOL")]
+    pub tool: String,
+
+    /// Output format (svg, png, pdf, ascii, markdown)
+    #[arg(short = 'f', long, default_value = "svg", value_name = "FORMAT")]
+    pub format: String,
+
+    /// Output path (optional, defaults to stdout for ascii and markdown)
+    #[arg(short = 'o', long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Colors to draw with (default, colorblind)
+    #[arg(long, default_value_t = Palette::Default, value_name = "PALETTE")]
+    pub palette: Palette,
+
+    /// Background to draw on (light, dark)
+    #[arg(long, default_value_t = ColorScheme::Light, value_name = "SCHEME")]
+    pub color_scheme: ColorScheme,
+
+    /// Font size of labels
+    #[arg(long, value_name = "SIZE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub font_size: Option<u32>,
+
+    /// Enable verbose output
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+}
+
+/// Parse diagram format from string
+fn parse_diagram_format(format_str: &str) -> Result<DiagramFormat, Error> {
+    match format_str.to_lowercase().as_str() {
+        "svg" => Ok(DiagramFormat::Svg),
+        "png" => Ok(DiagramFormat::Png),
+        "pdf" => Ok(DiagramFormat::Pdf),
+        "ascii" => Ok(DiagramFormat::Ascii),
+        "markdown" | "md" => Ok(DiagramFormat::Markdown),
+        _ => Err(Error::InvalidFormat {
+            format: format_str.to_string(),
+        }),
+    }
+}
+
+/// Read, interpolate and parse a workflow file
+fn read_workflow(path: &Path) -> Result<WorkflowDefinition, Error> {
+    let workflow_yaml = std::fs::read_to_string(path).context(ReadWorkflowSnafu { path })?;
+    let workflow_yaml = crate::interpolation::interpolate_env(&workflow_yaml, false)
+        .context(InterpolateWorkflowSnafu { path })?;
+    serde_yaml::from_str(&workflow_yaml).context(ParseWorkflowSnafu { path })
+}
+
+/// The provider `--tool` selects, drawing in the style the arguments ask for
+///
+/// SVG diagrams are drawn by the built-in renderer if the tool is not installed.
+fn select_provider(
+    args: &VisualizeArgs,
+    format: DiagramFormat,
+) -> Result<Arc<dyn VisualizationProvider>, Error> {
+    let provider = visualization::provider(&args.tool).ok_or_else(|| Error::UnknownTool {
+        tool: args.tool.clone(),
+        known: visualization::provider_names(),
+    })?;
+    let provider = visualization::installed_or_builtin(provider, format).context(RenderSnafu)?;
+    if !provider.capabilities().supports(format) {
+        return Err(Error::UnsupportedFormat {
+            tool: provider.name().to_string(),
+            format,
+        });
+    }
+    let diagram_style = DiagramStyle {
+        palette: args.palette,
+        color_scheme: args.color_scheme,
+        font_size: args.font_size,
+        ..DiagramStyle::default()
+    };
+    Ok(provider.styled(&diagram_style).unwrap_or(provider))
+}
+
+/// Render the call graph between the workflows of a registry
+///
+/// Missing references and cycles are listed, and highlighted in the diagram.
+fn visualize_registry(args: &VisualizeArgs, registry: &[PathBuf]) -> Result<(), Error> {
+    let format = parse_diagram_format(&args.format)?;
+    if !format.is_terminal_output() && args.output.is_none() {
+        return Err(Error::MissingOutputPath {
+            format: args.format.clone(),
+        });
+    }
+    let provider = select_provider(args, format)?;
+
+    let workflows = crate::cmd::run::discover_workflow_files(registry)
+        .context(DiscoverWorkflowsSnafu)?
+        .iter()
+        .map(|path| read_workflow(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let graph = CallGraph::new(&workflows);
+
+    if args.verbose {
+        println!(
+            "{} {} workflow(s), {} call(s)",
+            style("→").cyan(),
+            graph.workflows.len(),
+            graph.calls.len()
+        );
+    }
+    for call in graph.missing() {
+        eprintln!(
+            "{} {} (task {}) runs {}, which is not in the registry",
+            style("✗").red(),
+            call.caller,
+            call.task,
+            call.reference
+        );
+    }
+    for cycle in graph.cycles() {
+        eprintln!(
+            "{} Workflows call each other in a cycle: {}",
+            style("⚠").yellow(),
+            cycle.join(", ")
+        );
+    }
+
+    provider
+        .render_call_graph(&graph, args.output.as_deref(), format)
+        .context(RenderSnafu)?;
+    if let Some(output_path) = &args.output {
+        println!(
+            "{} Visualization saved to: {}",
+            style("✓").green(),
+            output_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Handle the visualize subcommand
+pub async fn handle_visualize(args: VisualizeArgs) -> Result<(), Error> {
+    if args.verbose {
+        println!("{}\n", style("Workflow Visualization").bold().cyan());
+    }
+
+    if let Some(registry) = &args.registry {
+        return visualize_registry(&args, registry);
+    }
+    let Some(workflow_path) = &args.workflow else {
+        return Err(Error::NotImplemented {
+            message: "A workflow file or --registry is required".to_string(),
+        });
+    };
+
+    // Read and parse workflow
+    let workflow = read_workflow(workflow_path)?;
+
+    if args.verbose {
+        println!(
+            "{} Loaded workflow: {}",
+            style("→").cyan(),
+            workflow.document.name
+        );
+    }
+
+    // Parse format
+    let format = parse_diagram_format(&args.format)?;
+
+    // Validate output path for file formats
+    if !format.is_terminal_output() && args.output.is_none() {
+        return Err(Error::MissingOutputPath {
+            format: args.format.clone(),
+        });
+    }
+
+    let provider = select_provider(&args, format)?;
+    if args.verbose {
+        println!(
+            "{} Generating {} visualization using {}...",
+            style("→").cyan(),
+            args.format,
+            provider.name()
+        );
+    }
+
+    // Tasks are drawn in the state the instance's recorded events leave them in
+    let execution_state = match &args.instance_id {
+        Some(instance_id) => {
+            let persistence =
+                RedbPersistence::new(args.durable_db.to_str().unwrap_or("workflow.db"))
+                    .context(PersistenceSnafu)?;
+            let events = persistence
+                .get_events(instance_id)
+                .await
+                .context(PersistenceSnafu)?;
+            Some(ExecutionState::from_events(&events))
+        }
+        None => None,
+    };
+
+    provider
+        .render(
+            &workflow,
+            args.output.as_deref(),
+            format,
+            execution_state.as_ref(),
+        )
+        .context(RenderSnafu)?;
+    if let Some(output_path) = &args.output {
+        println!(
+            "{} Visualization saved to: {}",
+            style("✓").green(),
+            output_path.display()
+        );
+    }
+    Ok(())
+}
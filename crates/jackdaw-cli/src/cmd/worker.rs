@@ -0,0 +1,275 @@
+use axum::{
+    Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use clap::Parser;
+use console::style;
+use snafu::prelude::*;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::cmd::config::ConfigOverrides;
+use crate::cmd::mock::start_mocks;
+use crate::cmd::resume::load_workflow;
+use crate::cmd::run::{ProviderArgs, discover_workflow_files, engine_builder};
+use crate::config::JackdawConfig;
+use crate::durableengine::{DurableEngine, parse_iso8601_duration};
+use crate::signing::SignatureVerifier;
+use crate::telemetry::TickOutcome;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{source}"))]
+    Run { source: crate::cmd::run::Error },
+
+    #[snafu(display("{source}"))]
+    Resume { source: crate::cmd::resume::Error },
+
+    #[snafu(display("Engine error: {source}"))]
+    Engine { source: crate::durableengine::Error },
+
+    #[snafu(display("Signature verification failed: {source}"))]
+    Signature { source: crate::signing::Error },
+
+    #[snafu(display("{source}"))]
+    Policy {
+        source: crate::middleware::policy::LoadError,
+    },
+
+    #[snafu(display("Mock error: {source}"))]
+    Mock { source: crate::cmd::mock::Error },
+
+    #[snafu(display("Failed to bind to {address}: {source}"))]
+    Bind {
+        address: String,
+        source: std::io::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::cmd::run::Error> for Error {
+    fn from(source: crate::cmd::run::Error) -> Self {
+        Error::Run { source }
+    }
+}
+
+impl From<crate::cmd::resume::Error> for Error {
+    fn from(source: crate::cmd::resume::Error) -> Self {
+        Error::Resume { source }
+    }
+}
+
+impl From<crate::durableengine::Error> for Error {
+    fn from(source: crate::durableengine::Error) -> Self {
+        Error::Engine { source }
+    }
+}
+
+impl From<crate::signing::Error> for Error {
+    fn from(source: crate::signing::Error) -> Self {
+        Error::Signature { source }
+    }
+}
+
+impl From<crate::middleware::policy::LoadError> for Error {
+    fn from(source: crate::middleware::policy::LoadError) -> Self {
+        Error::Policy { source }
+    }
+}
+
+impl From<crate::cmd::mock::Error> for Error {
+    fn from(source: crate::cmd::mock::Error) -> Self {
+        Error::Mock { source }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct WorkerArgs {
+    /// How long to wait before polling again when the queue is empty (ISO 8601 duration)
+    #[arg(long, default_value = "PT1S", value_name = "DURATION")]
+    pub poll_interval: String,
+
+    /// Serve the worker's metrics at `/metrics` on this address, in the Prometheus text format
+    #[arg(long, value_name = "ADDRESS")]
+    pub metrics_bind: Option<String>,
+
+    /// Workflow registry paths used to find sub-workflows started with `run.workflow`
+    #[arg(short = 'r', long = "registry", value_name = "PATH")]
+    pub registry: Option<Vec<PathBuf>>,
+
+    #[command(flatten)]
+    pub providers: ProviderArgs,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+/// Handle the worker subcommand
+///
+/// Runs the workflow starts queued in the configured persistence provider,
+/// one instance at a time, until interrupted. With `recover_on_start`, the
+/// instances a stopped process left running are resumed first. Any number of workers may share
+/// one store: each queued instance is claimed by taking its lease, which the
+/// worker renews while the instance runs. An instance interrupted with its
+/// worker is resumed by another worker once the lease lapses. With
+/// `metrics_bind`, the worker's metrics are served at `/metrics`.
+///
+/// # Errors
+///
+/// Returns an error if the providers cannot be created, a registry workflow
+/// cannot be loaded, the poll interval is invalid, or the metrics address
+/// cannot be bound.
+pub async fn handle_worker(
+    poll_interval: String,
+    metrics_bind: Option<String>,
+    registry: Option<Vec<PathBuf>>,
+    providers: ProviderArgs,
+    config: JackdawConfig,
+) -> Result<()> {
+    let poll_interval = parse_iso8601_duration(&poll_interval)?;
+    let persistence = providers.create_persistence(&config).await?;
+    let cache = providers.create_cache(&config).await?;
+    let builder = engine_builder(&config, persistence, cache)?;
+    // The mock servers stop when dropped at the end of the command
+    let (builder, _mocks) = start_mocks(&config.mocks, builder).await?;
+    let engine = Arc::new(builder.build()?);
+    let verifier = SignatureVerifier::from_config(&config)?;
+
+    if let Some(registry_paths) = registry {
+        for path in discover_workflow_files(&registry_paths)? {
+            verifier.verify_file(&path)?;
+            let workflow = load_workflow(&path, config.strict_env)?;
+            engine.set_workflow_path(&workflow, &path).await;
+            engine.register_workflow(workflow).await?;
+            if config.verbose {
+                println!("  • Registered workflow from {}", path.display());
+            }
+        }
+    }
+
+    let metrics = match metrics_bind {
+        Some(address) => Some(serve_metrics(engine.clone(), &address).await?),
+        None => None,
+    };
+
+    println!(
+        "{} Jackdaw worker polling for queued workflows",
+        style("✓").green()
+    );
+
+    // Instances a stopped process left running are resumed before the queue
+    // is polled, so a queued instance is not claimed while it is recovered
+    let run = async {
+        if config.recover_on_start {
+            recover(&engine).await;
+        }
+        work(&engine, poll_interval).await;
+    };
+
+    // An instance still running when the worker stops keeps its start queued
+    tokio::select! {
+        () = run => {}
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("\nReceived Ctrl+C, shutting down gracefully...");
+        }
+    }
+    if let Some(metrics) = metrics {
+        metrics.abort();
+    }
+    engine.shutdown().await;
+    Ok(())
+}
+
+/// Run queued instances, waiting `poll_interval` whenever the queue is empty
+///
+/// Each poll of the queue is a tick reported to the engine's telemetry.
+async fn work(engine: &DurableEngine, poll_interval: std::time::Duration) {
+    loop {
+        let started = Instant::now();
+        let outcome = match engine.run_next_queued().await {
+            Ok(Some(run)) => {
+                report(&run.instance_id, &run.output);
+                TickOutcome::Ran
+            }
+            Ok(None) => TickOutcome::Idle,
+            Err(e) => {
+                tracing::warn!("Failed to take a workflow from the queue: {}", e);
+                TickOutcome::Failed
+            }
+        };
+        if let Some(telemetry) = engine.telemetry() {
+            telemetry.record_tick(outcome, started.elapsed());
+        }
+        if outcome != TickOutcome::Ran {
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+}
+
+/// Serve `/metrics` on `address` until the returned task is aborted
+async fn serve_metrics(
+    engine: Arc<DurableEngine>,
+    address: &str,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .context(BindSnafu { address })?;
+    println!(
+        "{} Metrics served at http://{}/metrics",
+        style("✓").green(),
+        address
+    );
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(engine);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app.into_make_service()).await {
+            tracing::warn!("Metrics server stopped: {}", e);
+        }
+    }))
+}
+
+/// `GET /metrics` - the worker's metrics in the Prometheus text format
+async fn metrics(State(engine): State<Arc<DurableEngine>>) -> Response {
+    match engine.metrics().await {
+        Ok(body) => (
+            [(
+                axum::http::header::CONTENT_TYPE,
+                "text/plain; version=0.0.4",
+            )],
+            body,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Resume the instances a stopped process left running
+async fn recover(engine: &DurableEngine) {
+    match engine.recover_incomplete().await {
+        Ok(runs) => {
+            for run in runs {
+                report(&run.instance_id, &run.output);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to recover incomplete instances: {}", e),
+    }
+}
+
+/// Print the outcome of an instance the worker ran
+fn report(instance_id: &str, output: &crate::durableengine::Result<serde_json::Value>) {
+    match output {
+        Ok(_) => println!("{} Instance {} completed", style("✓").green(), instance_id),
+        Err(e) => println!(
+            "{} Instance {} failed: {}",
+            style("✗").red(),
+            instance_id,
+            e
+        ),
+    }
+}
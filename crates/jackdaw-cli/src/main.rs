@@ -0,0 +1,459 @@
+use clap::Parser;
+use indicatif::MultiProgress;
+use snafu::prelude::*;
+use tracing_subscriber::Layer as _;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+mod cmd;
+
+// The command line is built on the engine of the `jackdaw` crate
+use jackdaw::{
+    bdd, builder, bundle, cache, config, container, contract, coverage, durableengine,
+    execution_handle, executionhistory, expressions, interpolation, middleware, mock, oci, output,
+    persistence, profile, providers, signing, telemetry, testing,
+};
+
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+use cmd::{
+    BundleArgs, CacheArgs, CancelArgs, ConfigArgs, DebugBundleArgs, DoctorArgs, HistoryArgs,
+    InstancesArgs, MockArgs, PullArgs, PushArgs, ReplayArgs, ResumeArgs, RunArgs, ServeArgs,
+    SnapshotArgs, StatsArgs, SuspendArgs, TestArgs, TopArgs, ValidateArgs, VisualizeArgs,
+    WorkerArgs, handle_bundle, handle_cache, handle_cancel, handle_config, handle_debug_bundle,
+    handle_doctor, handle_history, handle_instances, handle_mock, handle_pull, handle_push,
+    handle_replay, handle_resume, handle_run, handle_serve, handle_snapshot, handle_stats,
+    handle_suspend, handle_test, handle_top, handle_validate, handle_visualize, handle_worker,
+};
+use config::JackdawConfig;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Run error: {source}"))]
+    Run { source: cmd::run::Error },
+
+    #[snafu(display("Validate error: {source}"))]
+    Validate { source: cmd::validate::Error },
+
+    #[snafu(display("Visualization error: {source}"))]
+    Visualize { source: cmd::visualize::Error },
+
+    #[snafu(display("Config error: {source}"))]
+    Config { source: cmd::config::Error },
+
+    #[snafu(display("Doctor error: {source}"))]
+    Doctor { source: cmd::doctor::Error },
+
+    #[snafu(display("Serve error: {source}"))]
+    Serve { source: cmd::serve::Error },
+
+    #[snafu(display("Bundle error: {source}"))]
+    Bundle { source: cmd::bundle::Error },
+
+    #[snafu(display("Snapshot error: {source}"))]
+    Snapshot { source: cmd::snapshot::Error },
+
+    #[snafu(display("Worker error: {source}"))]
+    Worker { source: cmd::worker::Error },
+
+    #[snafu(display("Cancel error: {source}"))]
+    Cancel { source: cmd::cancel::Error },
+
+    #[snafu(display("Suspend error: {source}"))]
+    Suspend { source: cmd::suspend::Error },
+
+    #[snafu(display("Resume error: {source}"))]
+    Resume { source: cmd::resume::Error },
+
+    #[snafu(display("History error: {source}"))]
+    History { source: cmd::history::Error },
+
+    #[snafu(display("Stats error: {source}"))]
+    Stats { source: cmd::stats::Error },
+
+    #[snafu(display("Instances error: {source}"))]
+    Instances { source: cmd::instances::Error },
+
+    #[snafu(display("Debug bundle error: {source}"))]
+    DebugBundle { source: cmd::debug_bundle::Error },
+
+    #[snafu(display("Replay error: {source}"))]
+    Replay { source: cmd::replay::Error },
+
+    #[snafu(display("Push error: {source}"))]
+    Push { source: cmd::oci::Error },
+
+    #[snafu(display("Pull error: {source}"))]
+    Pull { source: cmd::oci::Error },
+
+    #[snafu(display("Mock error: {source}"))]
+    Mock { source: cmd::mock::Error },
+
+    #[snafu(display("Test error: {source}"))]
+    Test { source: cmd::test::Error },
+
+    #[snafu(display("Cache error: {source}"))]
+    Cache { source: cmd::cache::Error },
+
+    #[snafu(display("Top error: {source}"))]
+    Top { source: cmd::top::Error },
+
+    #[snafu(display("Failed to create profile '{}': {source}", path.display()))]
+    Profile {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "jackdaw")]
+#[command(author = "Armin Graf")]
+#[command(version = env!("CARGO_PKG_VERSION"))]
+#[command(about = "A durable, cached, graph-based execution engine for Serverless Workflow", long_about = None)]
+#[command(help_template = "\
+{before-help}{name} {version}
+{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Parser, Debug)]
+enum Commands {
+    /// Execute workflow(s)
+    Run(RunArgs),
+    /// Validate workflow(s) without executing
+    Validate(ValidateArgs),
+    /// Visualize workflow structure and execution state
+    Visualize(VisualizeArgs),
+    /// Inspect and validate Jackdaw configuration
+    Config(ConfigArgs),
+    /// Check that executors can run tasks, e.g. that interpreters are installed
+    Doctor(DoctorArgs),
+    /// Run a long-lived server with a REST API for managing workflow instances
+    Serve(ServeArgs),
+    /// Run workflows queued in a shared persistence store, alongside other workers
+    Worker(WorkerArgs),
+    /// Package a workflow and its external resources into a self-contained archive
+    Bundle(BundleArgs),
+    /// Record the setup of an engine for its workflows, to cut the startup of serve and worker
+    Snapshot(SnapshotArgs),
+    /// Cancel a running or suspended workflow instance
+    Cancel(CancelArgs),
+    /// Suspend a running workflow instance at its last checkpoint
+    Suspend(SuspendArgs),
+    /// Resume a suspended workflow instance, replaying its completed tasks
+    Resume(ResumeArgs),
+    /// Print the event timeline of a workflow instance
+    History(HistoryArgs),
+    /// Summarize the recorded runs of each workflow: success rate, durations and failing tasks
+    Stats(StatsArgs),
+    /// Inspect recorded workflow instances, e.g. group recent failures by error
+    Instances(InstancesArgs),
+    /// Export the diagnostic bundle captured when a workflow instance faulted
+    DebugBundle(DebugBundleArgs),
+    /// Re-execute a recorded workflow instance to check it is deterministic
+    Replay(ReplayArgs),
+    /// Push a workflow bundle to an OCI registry
+    Push(PushArgs),
+    /// Pull a workflow bundle from an OCI registry
+    Pull(PullArgs),
+    /// Serve mock responses for the operations of an OpenAPI document
+    Mock(MockArgs),
+    /// Run the `x-tests` sections of workflow files and Gherkin feature scenarios
+    Test(TestArgs),
+    /// Inspect or remove cached task results
+    Cache(CacheArgs),
+    /// Monitor live workflow instances in the terminal, cancelling or suspending them
+    Top(TopArgs),
+}
+
+/// Initialize tracing/logging with indicatif integration
+fn init_tracing(verbose: bool) {
+    let indicatif_layer = tracing_indicatif::IndicatifLayer::new();
+
+    let filter_layer = if verbose {
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("debug"))
+    } else {
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"))
+    };
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(indicatif_layer)
+        .init();
+}
+
+/// Initialize tracing as `init_tracing` does, and also write the profiling
+/// spans to `path` until the returned guard is dropped
+fn init_profiled_tracing(
+    verbose: bool,
+    path: &std::path::Path,
+) -> std::io::Result<tracing_chrome::FlushGuard> {
+    let indicatif_layer = tracing_indicatif::IndicatifLayer::new();
+
+    let filter_layer = if verbose {
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("debug"))
+    } else {
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"))
+    };
+    let (profile_layer, guard) = profile::chrome_layer(path)?;
+
+    // The log filter only applies to the log, so the profile gets its trace-level spans
+    tracing_subscriber::registry()
+        .with(indicatif_layer.with_filter(filter_layer))
+        .with(profile_layer)
+        .init();
+    Ok(guard)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+
+    // Load configuration from file, env vars, and defaults; `config` loads and
+    // reports on it itself, so it is not warned about twice
+    let global_config = if matches!(cli.command, Commands::Config(_)) {
+        JackdawConfig::default()
+    } else {
+        JackdawConfig::load().unwrap_or_else(|e| {
+            eprintln!(
+                "Warning: ignoring configuration ({e}); run `jackdaw config check` for details"
+            );
+            JackdawConfig::default()
+        })
+    };
+
+    match cli.command {
+        Commands::Run(args) => {
+            // Extract workflows, input, registry, debug flag, and providers before merging
+            let workflows = args.workflows.clone();
+            let input = args.input.clone();
+            let registry = args.registry.clone();
+            let debug = args.debug;
+            let providers = args.providers.clone();
+            let profile_out = args.profile_out.clone();
+            let dry_run = args.dry_run;
+
+            // Merge CLI args with config (CLI takes precedence)
+            let config = args.overrides.merge_with_config(global_config);
+
+            // Initialize tracing/logging with indicatif bridge; the profile is
+            // written out when the guard is dropped after the run
+            let _profile = match &profile_out {
+                Some(path) => Some(
+                    init_profiled_tracing(config.verbose, path)
+                        .context(ProfileSnafu { path: path.clone() })?,
+                ),
+                None => {
+                    init_tracing(config.verbose);
+                    None
+                }
+            };
+
+            // Initialize MultiProgress for coordinating progress bars and logs/traces
+            let multi_progress = MultiProgress::new();
+
+            handle_run(
+                workflows,
+                input,
+                registry,
+                config,
+                multi_progress,
+                debug,
+                dry_run,
+                providers,
+            )
+            .await
+            .context(RunSnafu)
+        }
+        Commands::Validate(args) => {
+            // Initialize tracing/logging with indicatif bridge
+            init_tracing(args.verbose);
+
+            handle_validate(args).await.context(ValidateSnafu)
+        }
+        Commands::Visualize(args) => {
+            // Initialize tracing/logging with indicatif bridge
+            init_tracing(args.verbose);
+
+            handle_visualize(args).await.context(VisualizeSnafu)
+        }
+        Commands::Config(args) => handle_config(args).await.context(ConfigSnafu),
+        Commands::Doctor(args) => {
+            init_tracing(args.verbose);
+
+            handle_doctor(args).await.context(DoctorSnafu)
+        }
+        Commands::Serve(args) => {
+            let bind = args.bind.clone();
+            let registry = args.registry.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_serve(bind, registry, providers, config)
+                .await
+                .context(ServeSnafu)
+        }
+        Commands::Worker(args) => {
+            let poll_interval = args.poll_interval.clone();
+            let metrics_bind = args.metrics_bind.clone();
+            let registry = args.registry.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_worker(poll_interval, metrics_bind, registry, providers, config)
+                .await
+                .context(WorkerSnafu)
+        }
+        Commands::Bundle(args) => {
+            init_tracing(args.verbose);
+
+            handle_bundle(args).await.context(BundleSnafu)
+        }
+        Commands::Snapshot(args) => {
+            init_tracing(args.verbose);
+
+            handle_snapshot(args).await.context(SnapshotSnafu)
+        }
+        Commands::Cancel(args) => {
+            let instance_id = args.instance_id.clone();
+            let reason = args.reason.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_cancel(instance_id, reason, providers, config)
+                .await
+                .context(CancelSnafu)
+        }
+        Commands::Suspend(args) => {
+            let instance_id = args.instance_id.clone();
+            let reason = args.reason.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_suspend(instance_id, reason, providers, config)
+                .await
+                .context(SuspendSnafu)
+        }
+        Commands::Resume(args) => {
+            let instance_id = args.instance_id.clone();
+            let workflow = args.workflow.clone();
+            let registry = args.registry.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_resume(instance_id, workflow, registry, providers, config)
+                .await
+                .context(ResumeSnafu)
+        }
+        Commands::History(args) => {
+            let instance_id = args.instance_id.clone();
+            let format = args.format.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_history(instance_id, format, providers, config)
+                .await
+                .context(HistorySnafu)
+        }
+        Commands::Stats(args) => {
+            let workflow = args.workflow.clone();
+            let since = args.since.clone();
+            let format = args.format.clone();
+            let rebuild = args.rebuild;
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_stats(workflow, since, format, rebuild, providers, config)
+                .await
+                .context(StatsSnafu)
+        }
+        Commands::Replay(args) => {
+            let instance_id = args.instance_id.clone();
+            let workflow = args.workflow.clone();
+            let format = args.format.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_replay(instance_id, workflow, format, providers, config)
+                .await
+                .context(ReplaySnafu)
+        }
+        Commands::Push(args) => {
+            init_tracing(false);
+
+            handle_push(args).await.context(PushSnafu)
+        }
+        Commands::Pull(args) => {
+            init_tracing(false);
+
+            handle_pull(args).await.context(PullSnafu)
+        }
+        Commands::Mock(args) => {
+            init_tracing(false);
+
+            handle_mock(args).await.context(MockSnafu)
+        }
+        Commands::Test(args) => {
+            init_tracing(false);
+
+            handle_test(args).await.context(TestSnafu)
+        }
+        Commands::Cache(args) => {
+            init_tracing(false);
+
+            handle_cache(args, global_config).await.context(CacheSnafu)
+        }
+        Commands::Top(args) => {
+            // No tracing: log lines would be written over the monitor
+            handle_top(args, global_config).await.context(TopSnafu)
+        }
+        Commands::Instances(args) => {
+            init_tracing(false);
+
+            handle_instances(args, global_config)
+                .await
+                .context(InstancesSnafu)
+        }
+        Commands::DebugBundle(args) => {
+            let instance_id = args.instance_id.clone();
+            let output = args.output.clone();
+            let providers = args.providers.clone();
+            let config = args.overrides.merge_with_config(global_config);
+
+            init_tracing(config.verbose);
+
+            handle_debug_bundle(instance_id, output, providers, config)
+                .await
+                .context(DebugBundleSnafu)
+        }
+    }
+}
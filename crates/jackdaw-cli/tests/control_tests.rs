@@ -0,0 +1,90 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for controlling, from the command line, instances another process started
+use chrono::Utc;
+use jackdaw::durableengine::InstanceState;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::RedbPersistence;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use std::path::Path;
+use std::process::Command;
+
+/// Record a running instance in the redb database `jackdaw` uses by default
+/// in `dir`, as a process does before it stops mid-run
+async fn start_elsewhere(dir: &Path, instance_id: &str) {
+    let persistence = RedbPersistence::new(dir.join("workflow.db").to_str().unwrap()).unwrap();
+    persistence
+        .transition_state(instance_id, None, InstanceState::Running)
+        .await
+        .unwrap();
+    persistence
+        .save_event(WorkflowEvent::WorkflowStarted {
+            instance_id: instance_id.to_string(),
+            workflow_id: "default/long-running/1.0.0".to_string(),
+            timestamp: Utc::now(),
+            initial_data: json!({}),
+            metadata: None,
+        })
+        .await
+        .unwrap();
+}
+
+/// Run `jackdaw` in `dir` with `args`, without selecting any provider
+fn jackdaw(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_jackdaw"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("jackdaw should start");
+    assert!(
+        output.status.success(),
+        "jackdaw {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+async fn recorded_state(dir: &Path, instance_id: &str) -> Option<InstanceState> {
+    RedbPersistence::new(dir.join("workflow.db").to_str().unwrap())
+        .unwrap()
+        .get_state(instance_id)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_cancel_finds_instances_in_the_durable_store() {
+    let dir = tempfile::tempdir().unwrap();
+    start_elsewhere(dir.path(), "orphan").await;
+
+    let stdout = jackdaw(dir.path(), &["cancel", "orphan", "--reason", "superseded"]);
+    assert!(
+        stdout.contains("Cancelled workflow instance orphan"),
+        "{stdout}"
+    );
+    assert_eq!(
+        recorded_state(dir.path(), "orphan").await,
+        Some(InstanceState::Cancelling)
+    );
+}
+
+#[tokio::test]
+async fn test_suspend_finds_instances_in_the_durable_store() {
+    let dir = tempfile::tempdir().unwrap();
+    start_elsewhere(dir.path(), "orphan").await;
+
+    let stdout = jackdaw(
+        dir.path(),
+        &["suspend", "orphan", "--reason", "maintenance window"],
+    );
+    assert!(
+        stdout.contains("Suspended workflow instance orphan"),
+        "{stdout}"
+    );
+    assert_eq!(
+        recorded_state(dir.path(), "orphan").await,
+        Some(InstanceState::Suspended)
+    );
+}
@@ -0,0 +1,267 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Cache error: {message}"))]
+    Cache { message: String },
+
+    #[snafu(display("Serialization error: {source}"))]
+    Serialization { source: serde_json::Error },
+
+    #[snafu(display("Database error: {message}"))]
+    Database { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub key: String,
+    pub inputs: serde_json::Value,
+    pub output: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+    /// When the entry stops being served; never if `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl CacheEntry {
+    /// Whether the entry has expired
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+/// Pluggable cache provider for idempotent task execution
+///
+/// `get` does not return expired entries; they are removed by `purge_expired`.
+#[async_trait]
+#[allow(dead_code)]
+pub trait CacheProvider: Send + Sync + std::fmt::Debug {
+    async fn get(&self, key: &str) -> Result<Option<CacheEntry>>;
+    async fn set(&self, entry: CacheEntry) -> Result<()>;
+    async fn invalidate(&self, key: &str) -> Result<()>;
+
+    /// Remove the expired entries, returning how many were removed
+    async fn purge_expired(&self) -> Result<u64>;
+
+    /// Remove every entry, returning how many were removed
+    async fn purge_all(&self) -> Result<u64>;
+
+    /// Reclaim the space of removed entries, if the store does not on its own
+    async fn compact(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Memory held by the entries, for caches that keep them in memory
+    async fn footprint(&self) -> Result<Option<CacheFootprint>> {
+        Ok(None)
+    }
+
+    /// Lookups served since the provider was created, and what it stores
+    async fn stats(&self) -> Result<CacheStats>;
+}
+
+/// How well a cache is saving work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Lookups that found an unexpired entry
+    pub hits: u64,
+    /// Lookups that found no entry, or an expired one
+    pub misses: u64,
+    /// Stored entries, including expired ones not yet purged
+    pub entries: u64,
+    /// Size of the stored inputs and outputs
+    pub bytes: u64,
+}
+
+impl CacheStats {
+    /// Share of lookups that were hits, or `None` before the first lookup
+    #[must_use]
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        #[allow(clippy::cast_precision_loss)]
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+}
+
+/// Hits and misses counted by a cache provider's `get`
+#[derive(Debug, Default)]
+pub struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    /// Count a lookup, returning its result
+    pub fn record(&self, entry: Option<CacheEntry>) -> Option<CacheEntry> {
+        let counter = if entry.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
+    }
+
+    /// The counts, with the `entries` and `bytes` stored by the provider
+    #[must_use]
+    pub fn stats(&self, entries: u64, bytes: u64) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries,
+            bytes,
+        }
+    }
+}
+
+/// Memory held by the entries of an in-memory cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheFootprint {
+    pub entries: usize,
+    /// Size of the entries' inputs and outputs, as serialized JSON
+    pub bytes: usize,
+}
+
+/// When an entry written now with `ttl` expires
+#[must_use]
+pub fn expires_at(ttl: Option<Duration>) -> Option<DateTime<Utc>> {
+    ttl.and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+}
+
+/// Remove the expired entries of `cache` every `interval`, compacting it after
+/// entries were removed
+///
+/// The pass runs until the returned handle is aborted; failures are logged.
+#[must_use]
+pub fn spawn_eviction(
+    cache: Arc<dyn CacheProvider>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            match cache.purge_expired().await {
+                Ok(0) => {}
+                Ok(removed) => {
+                    tracing::debug!("Evicted {removed} expired cache entries");
+                    if let Err(e) = cache.compact().await {
+                        tracing::warn!("Failed to compact the cache: {e}");
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to evict expired cache entries: {e}"),
+            }
+        }
+    })
+}
+
+// Helper to filter out internal descriptor fields from cache key computation
+// Recursively removes fields starting with "__" from objects
+fn filter_internal_fields(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(obj) => {
+            let filtered: serde_json::Map<String, serde_json::Value> = obj
+                .iter()
+                .filter(|(key, _)| !key.starts_with("__"))
+                .map(|(k, v)| (k.clone(), filter_internal_fields(v)))
+                .collect();
+            serde_json::Value::Object(filtered)
+        }
+        serde_json::Value::Array(arr) => {
+            serde_json::Value::Array(arr.iter().map(filter_internal_fields).collect())
+        }
+        serde_json::Value::Null
+        | serde_json::Value::Bool(_)
+        | serde_json::Value::Number(_)
+        | serde_json::Value::String(_) => value.clone(),
+    }
+}
+
+// Helper to generate deterministic cache keys
+// Note: Filters out internal descriptor fields (__workflow, __runtime, __task)
+// so they don't affect caching
+// Uses SHA-256 for deterministic hashing across runs
+#[must_use]
+pub fn compute_cache_key(task_name: &str, inputs: &serde_json::Value) -> String {
+    use sha2::{Digest, Sha256};
+
+    let filtered_inputs = filter_internal_fields(inputs);
+
+    // Sort the keys, so the key does not depend on the order fields were set in
+    let normalized = crate::ordering::canonical(&filtered_inputs);
+    let inputs_json = serde_json::to_string(&normalized).unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{task_name}:{inputs_json}"));
+    let result = hasher.finalize();
+
+    format!("{}:{:x}", task_name, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_expiry() {
+        let entry = |expires_at| CacheEntry {
+            key: "k".to_string(),
+            inputs: serde_json::Value::Null,
+            output: serde_json::Value::Null,
+            timestamp: Utc::now(),
+            expires_at,
+        };
+        assert!(!entry(None).is_expired());
+        assert!(!entry(expires_at(Some(Duration::from_secs(60)))).is_expired());
+        assert!(entry(Some(Utc::now() - chrono::Duration::seconds(1))).is_expired());
+    }
+
+    #[test]
+    fn test_counters() {
+        let counters = CacheCounters::default();
+        assert_eq!(counters.stats(0, 0).hit_rate(), None);
+
+        let entry = CacheEntry {
+            key: "k".to_string(),
+            inputs: serde_json::Value::Null,
+            output: serde_json::Value::Null,
+            timestamp: Utc::now(),
+            expires_at: None,
+        };
+        assert!(counters.record(Some(entry)).is_some());
+        assert!(counters.record(None).is_none());
+        assert!(counters.record(None).is_none());
+
+        let stats = counters.stats(1, 8);
+        assert_eq!(
+            (stats.hits, stats.misses, stats.entries, stats.bytes),
+            (1, 2, 1, 8)
+        );
+        assert_eq!(stats.hit_rate(), Some(1.0 / 3.0));
+    }
+
+    #[test]
+    fn test_cache_keys_do_not_depend_on_field_order() {
+        let inputs =
+            serde_json::json!({ "b": [1, { "d": 2, "c": 3 }], "a": "x", "__workflow": {} });
+        let reordered = serde_json::json!({ "a": "x", "b": [1, { "c": 3, "d": 2 }] });
+        // Pinned, so that keys stored by earlier versions keep matching
+        let key = "greet:d2bcc804cd7e4535bbe7877bb776441a9de7075243d53ab419243439a98e656e";
+        assert_eq!(compute_cache_key("greet", &inputs), key);
+        assert_eq!(compute_cache_key("greet", &reordered), key);
+    }
+}
+
+// pub const CACHE_TABLE: redb::TableDefinition<&str, &[u8]> = redb::TableDefinition::new("cache");
@@ -0,0 +1,287 @@
+//! CloudEvents produced by `emit` tasks and the sinks they are published to
+//!
+//! An `emit` task's `event.with` attributes become a [CloudEvents 1.0]
+//! envelope. `source` and `type` are the only attributes a workflow has to
+//! provide; `id` and `time` are generated when missing, and any attribute that
+//! is not part of the CloudEvents context becomes an extension attribute.
+//!
+//! [CloudEvents 1.0]: https://github.com/cloudevents/spec/blob/v1.0.2/cloudevents/spec.md
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use snafu::prelude::*;
+
+/// CloudEvents specification version of the events jackdaw produces
+pub const SPEC_VERSION: &str = "1.0";
+
+/// Maximum length of an extension attribute name
+const MAX_EXTENSION_NAME_LEN: usize = 20;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Invalid event: {message}"))]
+    InvalidEvent { message: String },
+
+    #[snafu(display("Unsupported event sink '{uri}', expected stdout, http(s):// or mqtt://"))]
+    UnsupportedSink { uri: String },
+
+    #[snafu(display("Event sink '{uri}' needs jackdaw built with the `{feature}` feature"))]
+    DisabledSink { uri: String, feature: String },
+
+    #[snafu(display("Failed to publish event to {sink}: {message}"))]
+    Publish { sink: String, message: String },
+
+    #[snafu(display("Invalid read mode '{mode}', expected data, envelope or raw"))]
+    InvalidReadMode { mode: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A CloudEvents 1.0 envelope, in the JSON event format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudEvent {
+    pub specversion: String,
+    pub id: String,
+    pub source: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datacontenttype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dataschema: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    /// Extension attributes, serialized alongside the context attributes
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl CloudEvent {
+    /// Build an event from the evaluated attributes of an `emit` task
+    ///
+    /// `default_source` is used when the attributes have no `source`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `type` is missing, a context attribute is not a
+    /// string, or an extension attribute has an invalid name or value
+    pub fn from_attributes(
+        mut attributes: Map<String, Value>,
+        default_source: &str,
+    ) -> Result<Self> {
+        let mut take = |name: &str| -> Result<Option<String>> {
+            match attributes.remove(name) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(value)) => Ok(Some(value)),
+                Some(other) => Err(Error::InvalidEvent {
+                    message: format!("attribute '{name}' must be a string, got {other}"),
+                }),
+            }
+        };
+
+        let id = take("id")?.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let source = take("source")?.unwrap_or_else(|| default_source.to_string());
+        let event_type = take("type")?.ok_or_else(|| Error::InvalidEvent {
+            message: "attribute 'type' is required".to_string(),
+        })?;
+        let time = take("time")?.or_else(|| Some(Utc::now().to_rfc3339()));
+        let subject = take("subject")?;
+        let mut datacontenttype = take("datacontenttype")?;
+        let dataschema = take("dataschema")?;
+        // The event format carries its own spec version
+        take("specversion")?;
+
+        let data = attributes.remove("data").filter(|data| !data.is_null());
+        if datacontenttype.is_none() && data.is_some() {
+            datacontenttype = Some("application/json".to_string());
+        }
+
+        for (name, value) in &attributes {
+            validate_extension(name, value)?;
+        }
+
+        Ok(Self {
+            specversion: SPEC_VERSION.to_string(),
+            id,
+            source,
+            event_type,
+            time,
+            subject,
+            datacontenttype,
+            dataschema,
+            data,
+            extensions: attributes,
+        })
+    }
+}
+
+/// Extension names are lowercase letters and digits; values are scalars
+fn validate_extension(name: &str, value: &Value) -> Result<()> {
+    if name.is_empty()
+        || name.len() > MAX_EXTENSION_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        return Err(Error::InvalidEvent {
+            message: format!(
+                "extension attribute name '{name}' must be 1-{MAX_EXTENSION_NAME_LEN} lowercase letters or digits"
+            ),
+        });
+    }
+    match value {
+        Value::String(_) | Value::Number(_) | Value::Bool(_) => Ok(()),
+        Value::Null | Value::Array(_) | Value::Object(_) => Err(Error::InvalidEvent {
+            message: format!("extension attribute '{name}' must be a string, number or boolean"),
+        }),
+    }
+}
+
+/// An event received by an engine, which its `listen` tasks consume
+#[derive(Debug, Clone)]
+pub struct InboundEvent {
+    /// The event, normally a CloudEvent in the JSON event format
+    pub event: Value,
+    /// The listen task whose listener received the event, if it arrived
+    /// through a listener
+    pub listener: Option<String>,
+}
+
+/// How a `listen` task reads the events it receives, from `listen.read`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadMode {
+    /// The data of the event: `data`, or `data_base64` for binary data
+    Data,
+    /// The whole CloudEvents envelope, context attributes included
+    #[default]
+    Envelope,
+    /// The event as it arrived, without reading it as a CloudEvent: the body
+    /// of the request that delivered it, for events received by a listener
+    Raw,
+}
+
+impl ReadMode {
+    /// The mode `listen.read` names, `envelope` when it names none
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `read` names no mode
+    pub fn parse(read: Option<&str>) -> Result<Self> {
+        match read {
+            None | Some("envelope") => Ok(Self::Envelope),
+            Some("data") => Ok(Self::Data),
+            Some("raw") => Ok(Self::Raw),
+            Some(mode) => InvalidReadModeSnafu { mode }.fail(),
+        }
+    }
+
+    /// Shape an event as the mode reads it
+    ///
+    /// In `data` mode, a value that is not a CloudEvent is passed whole.
+    #[must_use]
+    pub fn read(self, event: Value) -> Value {
+        match (self, event) {
+            (Self::Data, Value::Object(mut envelope))
+                if ["specversion", "data", "data_base64"]
+                    .iter()
+                    .any(|attribute| envelope.contains_key(*attribute)) =>
+            {
+                envelope
+                    .remove("data")
+                    .or_else(|| envelope.remove("data_base64"))
+                    .unwrap_or(Value::Null)
+            }
+            (Self::Data | Self::Envelope | Self::Raw, event) => event,
+        }
+    }
+}
+
+/// Destination that events emitted by workflows are published to
+#[async_trait]
+pub trait EventSink: Send + Sync + std::fmt::Debug {
+    /// Publish an event
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event cannot be delivered
+    async fn publish(&self, event: &CloudEvent) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use serde_json::json;
+
+    fn attributes(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_event_from_attributes() {
+        let event = CloudEvent::from_attributes(
+            attributes(json!({
+                "type": "com.example.order.placed",
+                "data": { "orderId": 42 },
+                "tenant": "acme"
+            })),
+            "/default/orders",
+        )
+        .unwrap();
+        assert_eq!(event.source, "/default/orders");
+        assert_eq!(event.datacontenttype.as_deref(), Some("application/json"));
+        assert!(event.time.is_some());
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json.get("specversion"), Some(&json!("1.0")));
+        assert_eq!(json.get("type"), Some(&json!("com.example.order.placed")));
+        assert_eq!(json.get("tenant"), Some(&json!("acme")));
+        assert_eq!(json.pointer("/data/orderId"), Some(&json!(42)));
+        assert!(json.get("subject").is_none());
+    }
+
+    #[test]
+    fn test_event_requires_type() {
+        let err = CloudEvent::from_attributes(attributes(json!({ "source": "/a" })), "/b");
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_read_modes() {
+        let envelope = json!({ "specversion": "1.0", "type": "t", "data": { "id": 1 } });
+        assert_eq!(ReadMode::Data.read(envelope.clone()), json!({ "id": 1 }));
+        assert_eq!(ReadMode::Envelope.read(envelope.clone()), envelope);
+        assert_eq!(ReadMode::Raw.read(envelope.clone()), envelope);
+
+        let binary = json!({ "specversion": "1.0", "type": "t", "data_base64": "AAE=" });
+        assert_eq!(ReadMode::Data.read(binary), json!("AAE="));
+        let empty = json!({ "specversion": "1.0", "type": "t" });
+        assert_eq!(ReadMode::Data.read(empty), Value::Null);
+        let body = json!({ "customField": "custom value" });
+        assert_eq!(ReadMode::Data.read(body.clone()), body);
+
+        assert_eq!(ReadMode::parse(None).unwrap(), ReadMode::Envelope);
+        assert_eq!(ReadMode::parse(Some("raw")).unwrap(), ReadMode::Raw);
+        assert!(ReadMode::parse(Some("body")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_extensions_rejected() {
+        for ext in [json!({ "Tenant": "acme" }), json!({ "tenant": ["a"] })] {
+            let mut attrs = attributes(ext);
+            attrs.insert("type".to_string(), json!("t"));
+            assert!(CloudEvent::from_attributes(attrs, "/s").is_err());
+        }
+    }
+}
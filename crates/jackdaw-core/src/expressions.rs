@@ -0,0 +1,838 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use jaq_core::Ctx;
+use tracing::debug;
+
+use crate::text::{MAX_ERROR_LEN, truncate};
+
+mod javascript;
+
+/// Regex for null-safe field access transformation
+#[allow(clippy::expect_used)]
+static RE_FIELD_ACCESS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\.[a-zA-Z_][a-zA-Z0-9_]*)\.([a-zA-Z_][a-zA-Z0-9_]*)")
+        .expect("hardcoded regex should be valid")
+});
+
+/// Regex for null-safe array operations transformation
+#[allow(clippy::expect_used)]
+static RE_ARRAY_OPS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\((\.[a-zA-Z_][a-zA-Z0-9_]*(?:\.[a-zA-Z0-9_]*)*)\s*\+\s*\[")
+        .expect("hardcoded regex should be valid")
+});
+
+/// Regex for variable references in expressions
+#[allow(clippy::expect_used)]
+static RE_VAR_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\$([a-zA-Z_][a-zA-Z0-9_]*)").expect("hardcoded regex should be valid")
+});
+
+/// Regex for field paths in expressions, rooted at `.` or at a variable, which
+/// may index arrays between their fields, as in `.items[0].name`
+#[allow(clippy::expect_used)]
+static RE_FIELD_PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(^|[^a-zA-Z0-9_)\]}."?])(\$[a-zA-Z_][a-zA-Z0-9_]*)?(\.[a-zA-Z_][a-zA-Z0-9_]*(?:\.[a-zA-Z_][a-zA-Z0-9_]*|\[[^\[\]"]*\])*)"#,
+    )
+    .expect("hardcoded regex should be valid")
+});
+
+/// Regex for the segments of a field path: a field, or an index
+#[allow(clippy::expect_used)]
+static RE_PATH_SEGMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\.([a-zA-Z_][a-zA-Z0-9_]*)|(\[[^\[\]"]*\])"#)
+        .expect("hardcoded regex should be valid")
+});
+
+/// Field of the data holding the results of the branches of the last fork
+/// that completed, which expressions read as `$branches`
+pub const BRANCHES_KEY: &str = "__branches";
+
+/// Expressions as preprocessed for each mode, by their source
+///
+/// A workflow evaluates the same expressions for every item of a loop and
+/// every instance it runs, so each is preprocessed once and shared afterwards.
+static PREPROCESSED: LazyLock<Mutex<HashMap<ExpressionMode, HashMap<Box<str>, Arc<str>>>>> =
+    LazyLock::new(Mutex::default);
+
+/// How many expressions [`PREPROCESSED`] holds per mode before it starts
+/// over, so that expressions built at run time cannot grow it without bound
+const MAX_PREPROCESSED: usize = 4096;
+
+/// Functions every expression can call, besides the jq standard library
+///
+/// They spell out common transforms under names workflow authors look for,
+/// so that these do not need a `run` task. `$uuid` (a random UUID) and `$now`
+/// (the current time, RFC 3339) are bound as variables by
+/// [`evaluate_expression_with_input`] when an expression uses them.
+/// `_strict_field` reads fields in [`ExpressionMode::Strict`].
+const BUILTIN_DEFS: &str = r#"
+def _strict_field($name):
+  if type == "object" and (has($name) | not) then error("missing field \($name)")
+  elif type == "null" then error("cannot read field \($name) of null")
+  else .[$name] end;
+def base64encode: @base64;
+def base64decode: @base64d;
+def urlencode: @uri;
+def regex_match($re): test($re);
+def regex_match($re; $flags): test($re; $flags);
+def regex_capture($re): capture($re);
+def regex_replace($re; $replacement): gsub($re; $replacement);
+"#;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Expression evaluation error: {message}"))]
+    Evaluation { message: String },
+
+    #[snafu(display("JQ load errors: {errors:?}"))]
+    JqLoad { errors: String },
+
+    #[snafu(display("JQ compile errors: {errors:?}"))]
+    JqCompile { errors: String },
+
+    #[snafu(display("JQ evaluation error: {message}"))]
+    JqEvaluation { message: String },
+
+    #[snafu(display("JavaScript evaluation error: {message}"))]
+    JsEvaluation { message: String },
+
+    #[snafu(display("Unsupported expression language '{language}', expected jq or js"))]
+    UnsupportedLanguage { language: String },
+
+    #[snafu(display("Unsupported expression mode '{mode}', expected strict or lax"))]
+    UnsupportedMode { mode: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The language `${ }` expressions are written in
+///
+/// Workflows choose it with `evaluate.language`. Bare expressions, such as
+/// `input.from: .field`, are always jq.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExpressionLanguage {
+    /// jq, evaluated by jaq
+    #[default]
+    Jq,
+    /// JavaScript, evaluated by an embedded QuickJS engine
+    JavaScript,
+}
+
+impl ExpressionLanguage {
+    /// The language named by `evaluate.language`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the language is not supported.
+    pub fn parse(language: &str) -> Result<Self> {
+        match language.to_ascii_lowercase().as_str() {
+            "jq" => Ok(Self::Jq),
+            "js" | "javascript" => Ok(Self::JavaScript),
+            _ => Err(Error::UnsupportedLanguage {
+                language: language.to_string(),
+            }),
+        }
+    }
+
+    /// The language the expressions of `workflow` are written in
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workflow declares an unsupported language.
+    pub fn of_workflow(workflow: &WorkflowDefinition) -> Result<Self> {
+        serde_json::to_value(workflow)
+            .ok()
+            .and_then(|definition| declared(&definition, "language").map(str::to_string))
+            .map_or(Ok(Self::Jq), |language| Self::parse(&language))
+    }
+}
+
+/// How `${ }` jq expressions treat missing data
+///
+/// Workflows choose it with `evaluate.mode`. JavaScript expressions already
+/// throw on unknown names, and read missing properties as null in both modes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpressionMode {
+    /// Missing fields, and the fields of missing objects, are null
+    #[default]
+    Lax,
+    /// Reading a missing field, or a field of null, fails the expression, and
+    /// with it the task
+    Strict,
+}
+
+impl ExpressionMode {
+    /// The mode named by `evaluate.mode`
+    ///
+    /// `loose`, the name the specification uses, is accepted for `lax`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mode is not supported.
+    pub fn parse(mode: &str) -> Result<Self> {
+        match mode.to_ascii_lowercase().as_str() {
+            "lax" | "loose" => Ok(Self::Lax),
+            "strict" => Ok(Self::Strict),
+            _ => Err(Error::UnsupportedMode {
+                mode: mode.to_string(),
+            }),
+        }
+    }
+
+    /// The mode the expressions of `workflow` are evaluated in
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workflow declares an unsupported mode.
+    pub fn of_workflow(workflow: &WorkflowDefinition) -> Result<Self> {
+        serde_json::to_value(workflow)
+            .ok()
+            .and_then(|definition| declared(&definition, "mode").map(str::to_string))
+            .map_or(Ok(Self::Lax), |mode| Self::parse(&mode))
+    }
+}
+
+/// The `evaluate` setting a workflow definition declares
+fn declared<'a>(definition: &'a Value, setting: &str) -> Option<&'a str> {
+    definition.get("evaluate")?.get(setting)?.as_str()
+}
+
+/// How the `${ }` expressions of a workflow are evaluated, per its `evaluate`
+///
+/// Runtimes read it from the workflow definition once and pass it to every
+/// evaluation, since the data an expression is evaluated against, such as a
+/// task's raw output, does not say which workflow it belongs to. The default
+/// is jq in lax mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Evaluation {
+    pub language: ExpressionLanguage,
+    pub mode: ExpressionMode,
+}
+
+impl Evaluation {
+    /// How the expressions of `workflow` are evaluated
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workflow declares an unsupported language or mode.
+    pub fn of_workflow(workflow: &WorkflowDefinition) -> Result<Self> {
+        Ok(Self {
+            language: ExpressionLanguage::of_workflow(workflow)?,
+            mode: ExpressionMode::of_workflow(workflow)?,
+        })
+    }
+}
+
+/// Handles preprocessing of jq expressions to add null-safe operations
+///
+/// This preprocessor applies transformations to make jq expressions more robust
+/// by automatically adding null-safe operators in common patterns.
+#[derive(Debug)]
+pub struct ExpressionPreprocessor {
+    /// Enable null-safe field access transformations
+    null_safe_enabled: bool,
+}
+
+impl Default for ExpressionPreprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExpressionPreprocessor {
+    /// Create a new preprocessor with null-safe transformations enabled
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            null_safe_enabled: true,
+        }
+    }
+
+    /// Preprocess a jq expression with configured transformations
+    ///
+    #[must_use]
+    pub fn preprocess(&self, expr: &str) -> String {
+        let mut result = expr.to_string();
+
+        if self.null_safe_enabled {
+            result = self.apply_null_safe_field_access(&result);
+            result = self.apply_null_safe_array_ops(&result);
+        }
+
+        result
+    }
+
+    /// Apply null-safe transformations to nested field access patterns
+    ///
+    /// Transforms `.parent.child` into `(.parent // {}).child` to prevent
+    /// errors when parent is null/missing.
+    #[must_use]
+    fn apply_null_safe_field_access(&self, expr: &str) -> String {
+        // Transform: .parent.child -> (.parent // {}).child
+        // This ensures that if .parent is null/missing, we get an empty object
+        // instead of a jq error
+        RE_FIELD_ACCESS
+            .replace_all(expr, |caps: &regex::Captures| {
+                format!("({} // {}).{}", &caps[1], "{}", &caps[2])
+            })
+            .to_string()
+    }
+
+    /// Apply null-safe transformations to array addition patterns
+    ///
+    /// Transforms `(.field + [x])` into `((.field // []) + [x])` to prevent
+    /// errors when the field is null/missing.
+    #[must_use]
+    fn apply_null_safe_array_ops(&self, expr: &str) -> String {
+        // Transform: (.field + [...]) -> ((.field // []) + [...])
+        // This ensures that if .field is null/missing, we treat it as an empty array
+        // before appending new elements
+        RE_ARRAY_OPS
+            .replace_all(expr, |caps: &regex::Captures| {
+                format!("(({} // []) + [", &caps[1])
+            })
+            .to_string()
+    }
+}
+
+/// Rewrite the field paths of `expr`, outside of string literals, to read
+/// every field with `_strict_field`
+///
+/// Transforms `.parent.child` into `(_strict_field("parent") | _strict_field("child"))`,
+/// `$input.field` into `($input | _strict_field("field"))`, and
+/// `.items[0].name` into `(_strict_field("items") | .[0] | _strict_field("name"))`.
+fn strict_field_access(expr: &str) -> String {
+    let rewrite = |code: &str| {
+        RE_FIELD_PATH
+            .replace_all(code, |caps: &regex::Captures| {
+                let fields = RE_PATH_SEGMENT.captures_iter(&caps[3]).map(|segment| {
+                    match (segment.get(1), segment.get(2)) {
+                        (Some(field), _) => format!("_strict_field(\"{}\")", field.as_str()),
+                        (None, index) => {
+                            format!(".{}", index.map_or("", |index| index.as_str()))
+                        }
+                    }
+                });
+                let path: Vec<String> = caps
+                    .get(2)
+                    .map(|variable| variable.as_str().to_string())
+                    .into_iter()
+                    .chain(fields)
+                    .collect();
+                format!("{}({})", &caps[1], path.join(" | "))
+            })
+            .to_string()
+    };
+
+    let mut result = String::with_capacity(expr.len());
+    let mut code = String::new();
+    let mut chars = expr.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            code.push(c);
+            continue;
+        }
+        // Copy the string literal as it is
+        result.push_str(&rewrite(&code));
+        code.clear();
+        result.push(c);
+        while let Some(c) = chars.next() {
+            result.push(c);
+            if c == '\\' {
+                result.extend(chars.next());
+            } else if c == '"' {
+                break;
+            }
+        }
+    }
+    result.push_str(&rewrite(&code));
+    result
+}
+
+/// The jq source of a `${ }` expression, or `None` if `expression` is a
+/// plain string
+#[must_use]
+pub fn expression_source(expression: &str) -> Option<&str> {
+    expression
+        .trim()
+        .strip_prefix("${")
+        .and_then(|expr| expr.strip_suffix('}'))
+        .map(str::trim)
+}
+
+/// `expr` preprocessed for `mode`, interned in [`PREPROCESSED`]
+///
+/// `expr` is the source of a `${ }` expression, as returned by
+/// [`expression_source`].
+#[must_use]
+pub fn preprocessed(mode: ExpressionMode, expr: &str) -> Arc<str> {
+    let preprocess = || -> Arc<str> {
+        match mode {
+            ExpressionMode::Lax => ExpressionPreprocessor::new().preprocess(expr).into(),
+            ExpressionMode::Strict => strict_field_access(expr).into(),
+        }
+    };
+    let Ok(mut interned) = PREPROCESSED.lock() else {
+        return preprocess();
+    };
+    let interned = interned.entry(mode).or_default();
+    if let Some(preprocessed) = interned.get(expr) {
+        return Arc::clone(preprocessed);
+    }
+    if interned.len() >= MAX_PREPROCESSED {
+        interned.clear();
+    }
+    let preprocessed = preprocess();
+    interned.insert(expr.into(), Arc::clone(&preprocessed));
+    preprocessed
+}
+
+/// Intern `expr` as preprocessed for `mode` by an earlier process, such as
+/// the one that took an engine snapshot, so that it is not preprocessed again
+pub fn intern_preprocessed(mode: ExpressionMode, expr: &str, preprocessed: &str) {
+    let Ok(mut interned) = PREPROCESSED.lock() else {
+        return;
+    };
+    let interned = interned.entry(mode).or_default();
+    if interned.len() >= MAX_PREPROCESSED {
+        return;
+    }
+    interned.insert(expr.into(), preprocessed.into());
+}
+
+/// Evaluates an expression with the given context.
+///
+/// # Errors
+///
+/// Returns an error if expression evaluation fails or if jq compilation/execution encounters an error.
+pub fn evaluate_expression(
+    evaluation: Evaluation,
+    expression: &str,
+    context: &Value,
+) -> Result<Value> {
+    evaluate_expression_with_input(evaluation, expression, context, &Value::Null)
+}
+
+/// Evaluates an expression with access to both context and input values.
+///
+/// # Errors
+///
+/// Returns an error if expression evaluation fails or if jq compilation/execution encounters an error.
+///
+pub fn evaluate_expression_with_input(
+    evaluation: Evaluation,
+    expression: &str,
+    context: &Value,
+    input: &Value,
+) -> Result<Value> {
+    let Some(jq_expr_raw) = expression_source(expression) else {
+        return Ok(Value::String(expression.to_string()));
+    };
+    if evaluation.language == ExpressionLanguage::JavaScript {
+        return javascript::evaluate(jq_expr_raw, context, input);
+    }
+
+    // In lax mode, apply null-safe transformations using preprocessor
+    // This handles patterns like:
+    // - .parent.child -> (.parent // {}).child
+    // - (.field + [...]) -> ((.field // []) + [...])
+    // In strict mode, read every field so that a missing one is an error
+    let mut jq_expr = preprocessed(evaluation.mode, jq_expr_raw)
+        .as_ref()
+        .to_string();
+
+    // Build evaluation context and bind variables
+    // We need to detect which $variables are used and bind them using jaq's 'as' syntax
+    let eval_context = if let Some(obj) = context.as_object() {
+        let mut combined = obj.clone();
+        let mut var_bindings = Vec::new();
+
+        // Handle $input
+        if jq_expr.contains("$input") {
+            // Strip internal descriptors from $input as they shouldn't be part of data flow
+            combined.insert("input".to_string(), strip_descriptors(input));
+            var_bindings.push("input".to_string());
+        }
+
+        // Handle $workflow - check if workflow descriptor is in context
+        if jq_expr.contains("$workflow") {
+            if let Some(workflow_desc) = combined.get("__workflow").cloned() {
+                combined.insert("workflow".to_string(), workflow_desc);
+            }
+            var_bindings.push("workflow".to_string());
+        }
+
+        // Handle $runtime - check if runtime descriptor is in context
+        if jq_expr.contains("$runtime") {
+            if let Some(runtime_desc) = combined.get("__runtime").cloned() {
+                combined.insert("runtime".to_string(), runtime_desc);
+            }
+            var_bindings.push("runtime".to_string());
+        }
+
+        // Handle $uuid and $now - generated for each evaluation
+        if jq_expr.contains("$uuid") {
+            combined.insert(
+                "uuid".to_string(),
+                Value::String(uuid::Uuid::new_v4().to_string()),
+            );
+            var_bindings.push("uuid".to_string());
+        }
+        if jq_expr.contains("$now") {
+            combined.insert(
+                "now".to_string(),
+                Value::String(
+                    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                ),
+            );
+            var_bindings.push("now".to_string());
+        }
+
+        // Handle $secrets - the secrets declared in `use.secrets`
+        if jq_expr.contains("$secrets") {
+            if let Some(secrets) = combined.get(crate::secrets::SECRETS_KEY).cloned() {
+                combined.insert("secrets".to_string(), secrets);
+            }
+            var_bindings.push("secrets".to_string());
+        }
+
+        // Handle $branches - the results of the branches of the last fork
+        if jq_expr.contains("$branches") {
+            if let Some(branches) = combined.get(BRANCHES_KEY).cloned() {
+                combined.insert("branches".to_string(), branches);
+            }
+            var_bindings.push("branches".to_string());
+        }
+
+        // Detect all $varname references in the expression
+        for cap in RE_VAR_REFERENCE.captures_iter(&jq_expr.clone()) {
+            let var_name = &cap[1];
+            // Only bind if the variable exists in context and we haven't already added it
+            if combined.contains_key(var_name) && !var_bindings.contains(&var_name.to_string()) {
+                var_bindings.push(var_name.to_string());
+            }
+        }
+
+        // Build the variable bindings at the start of the expression
+        // Format: .varname as $varname | .var2 as $var2 | <original expression>
+        if !var_bindings.is_empty() {
+            let bindings: Vec<String> = var_bindings
+                .iter()
+                .map(|v| format!(".{v} as ${v}"))
+                .collect();
+            jq_expr = format!("{} | {}", bindings.join(" | "), jq_expr);
+        }
+
+        Value::Object(combined)
+    } else {
+        // Context is not an object (e.g., a string after input filtering)
+        // Keep it as-is and special variables will be handled via jaq vars if needed
+        context.clone()
+    };
+
+    debug!("  Evaluating jq expression: {}", jq_expr);
+
+    evaluate_jq(&jq_expr, &eval_context)
+}
+
+/// Evaluates a jq expression on a value (used for output filtering)
+///
+/// # Errors
+///
+/// Returns an error if jq compilation/execution encounters an error.
+#[allow(dead_code)]
+pub fn evaluate_jq_expression(jq_expr: &str, value: &Value) -> Result<Value> {
+    evaluate_jq(jq_expr, value)
+}
+
+/// Evaluates a jq expression with access to $input variable (used for output.as expressions)
+///
+/// # Errors
+///
+/// Returns an error if jq compilation/execution encounters an error.
+pub fn evaluate_jq_expression_with_context(
+    jq_expr: &str,
+    value: &Value,
+    context: &Value,
+) -> Result<Value> {
+    // If the expression uses $input, we need to bind it
+    if jq_expr.contains("$input") {
+        // Strip descriptors from context when used as $input
+        let input_value = strip_descriptors(context);
+
+        // Wrap both value and input in an object so we can bind them
+        let mut wrapper = serde_json::Map::new();
+        wrapper.insert("__value".to_string(), value.clone());
+        wrapper.insert("__input".to_string(), input_value);
+
+        // Bind $input, then evaluate expression on the value
+        let modified_expr = format!(".__input as $input | .__value | {jq_expr}");
+
+        evaluate_jq(&modified_expr, &Value::Object(wrapper))
+    } else {
+        // No $input, just evaluate directly
+        evaluate_jq(jq_expr, value)
+    }
+}
+
+/// Evaluates a jq expression directly without requiring ${ } wrapper
+///
+/// # Errors
+///
+/// Returns an error if jq compilation/execution encounters an error.
+#[tracing::instrument(
+    target = "jackdaw::profile",
+    level = "trace",
+    name = "expression.eval",
+    skip_all
+)]
+pub fn evaluate_jq(jq_expr: &str, context: &Value) -> Result<Value> {
+    use jaq_core::{
+        compile::Compiler,
+        load::{Arena, File, Loader},
+    };
+
+    // Create arena and loader with standard library (including jaq-json defs)
+    let arena = Arena::default();
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+
+    // Load the expression as a "file", after the built-in functions
+    let code = format!("{BUILTIN_DEFS}{jq_expr}");
+    let file: File<_, ()> = File {
+        path: (),
+        code: code.as_str(),
+    };
+
+    let modules = loader.load(&arena, file).map_err(|errs| Error::JqLoad {
+        errors: format!("{errs:?}"),
+    })?;
+
+    // Compile with standard library native functions (including jaq-json funs)
+    let compiler = Compiler::default().with_funs(jaq_std::funs().chain(jaq_json::funs()));
+    let filter = compiler.compile(modules).map_err(|errs| Error::JqCompile {
+        errors: format!("{errs:?}"),
+    })?;
+
+    // Convert serde_json::Value to jaq_json::Val using From trait
+    let input: jaq_json::Val = context.clone().into();
+
+    let inputs = jaq_core::RcIter::new(core::iter::empty());
+    let mut results: Vec<_> = filter.run((Ctx::new([], &inputs), input)).collect();
+
+    if results.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    match results.remove(0) {
+        Ok(val) => {
+            let result: serde_json::Value = val.into();
+            Ok(result)
+        }
+        // jaq quotes the values it cannot use, which may be the whole input
+        Err(e) => Err(Error::JqEvaluation {
+            message: truncate(&e.to_string(), MAX_ERROR_LEN).into_owned(),
+        }),
+    }
+}
+
+/// Remove internal descriptor fields from a value (used for $input in output.as expressions)
+#[must_use]
+pub fn strip_descriptors(value: &Value) -> Value {
+    if let Value::Object(obj) = value {
+        let mut cleaned = obj.clone();
+        cleaned.remove("__workflow");
+        cleaned.remove("__runtime");
+        cleaned.remove(crate::secrets::SECRETS_KEY);
+        cleaned.remove(BRANCHES_KEY);
+        Value::Object(cleaned)
+    } else {
+        value.clone()
+    }
+}
+
+/// Evaluates a value recursively, processing any expression strings found.
+///
+/// # Errors
+///
+/// Returns an error if expression evaluation fails or if jq compilation/execution encounters an error.
+#[allow(dead_code)]
+pub fn evaluate_value(evaluation: Evaluation, value: &Value, context: &Value) -> Result<Value> {
+    evaluate_value_with_input(evaluation, value, context, &Value::Null)
+}
+
+/// Evaluates a value recursively with input, processing any expression strings found.
+///
+/// # Errors
+///
+/// Returns an error if expression evaluation fails or if jq compilation/execution encounters an error.
+#[allow(dead_code)]
+pub fn evaluate_value_with_input(
+    evaluation: Evaluation,
+    value: &Value,
+    context: &Value,
+    input: &Value,
+) -> Result<Value> {
+    match value {
+        Value::String(s) => evaluate_expression_with_input(evaluation, s, context, input),
+        Value::Object(map) => {
+            let mut result = serde_json::Map::new();
+            for (k, v) in map {
+                result.insert(
+                    k.clone(),
+                    evaluate_value_with_input(evaluation, v, context, input)?,
+                );
+            }
+            Ok(Value::Object(result))
+        }
+        Value::Array(arr) => {
+            let mut result = Vec::new();
+            for item in arr {
+                result.push(evaluate_value_with_input(evaluation, item, context, input)?);
+            }
+            Ok(Value::Array(result))
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => Ok(value.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_functions_transform_strings() {
+        let context = json!({ "name": "jackdaw", "path": "a b/c", "order": "order-42" });
+        let cases = [
+            ("${ .name | base64encode }", json!("amFja2Rhdw==")),
+            ("${ .name | base64encode | base64decode }", json!("jackdaw")),
+            ("${ .path | urlencode }", json!("a%20b%2Fc")),
+            ("${ .order | regex_match(\"^order-[0-9]+$\") }", json!(true)),
+            ("${ .name | regex_match(\"JACK\"; \"i\") }", json!(true)),
+            (
+                "${ .order | regex_capture(\"-(?<id>[0-9]+)\") }",
+                json!({ "id": "42" }),
+            ),
+            (
+                "${ .order | regex_replace(\"[0-9]\"; \"#\") }",
+                json!("order-##"),
+            ),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(
+                evaluate_expression(Evaluation::default(), expression, &context).unwrap(),
+                expected,
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_uuid_and_now_are_bound_when_used() {
+        let context = json!({});
+        let first = evaluate_expression(Evaluation::default(), "${ $uuid }", &context).unwrap();
+        let second = evaluate_expression(Evaluation::default(), "${ $uuid }", &context).unwrap();
+        assert!(uuid::Uuid::parse_str(first.as_str().unwrap()).is_ok());
+        assert_ne!(first, second);
+
+        let now = evaluate_expression(Evaluation::default(), "${ $now }", &context).unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(now.as_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_missing_fields() {
+        let strict = Evaluation {
+            mode: ExpressionMode::Strict,
+            ..Evaluation::default()
+        };
+        let context = json!({ "user": { "name": "ada" }, "items": [{ "sku": "a-1" }] });
+        let cases = [
+            ("${ .user.name }", json!("ada")),
+            ("${ \"user.missing\" }", json!("user.missing")),
+            ("${ .user.missing? // \"none\" }", json!("none")),
+            ("${ .items[0].sku }", json!("a-1")),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(
+                evaluate_expression(strict, expression, &context).unwrap(),
+                expected,
+                "{expression}"
+            );
+        }
+        for expression in [
+            "${ .user.missing }",
+            "${ .account.id }",
+            "${ $input.limit }",
+            "${ .items[0].price }",
+        ] {
+            let error = evaluate_expression_with_input(strict, expression, &context, &json!({}))
+                .unwrap_err()
+                .to_string();
+            assert!(error.contains("missing field"), "{expression}: {error}");
+        }
+        let error = evaluate_expression(strict, "${ .items[1].sku }", &context)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("cannot read field sku of null"), "{error}");
+
+        assert_eq!(
+            evaluate_expression(Evaluation::default(), "${ .account.id }", &context).unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_errors_quoting_giant_values_are_cut() {
+        let context = json!({ "blob": "é".repeat(MAX_ERROR_LEN) });
+        let error = evaluate_expression(Evaluation::default(), "${ .blob + 1 }", &context)
+            .unwrap_err()
+            .to_string();
+        assert!(error.len() < MAX_ERROR_LEN + 64, "{} bytes", error.len());
+    }
+
+    #[test]
+    fn test_expressions_are_preprocessed_once_per_mode() {
+        let lax = preprocessed(ExpressionMode::Lax, ".order.total");
+        let strict = preprocessed(ExpressionMode::Strict, ".order.total");
+        assert_eq!(&*lax, "(.order // {}).total");
+        assert_eq!(
+            &*strict,
+            "(_strict_field(\"order\") | _strict_field(\"total\"))"
+        );
+        assert_eq!(
+            &*preprocessed(ExpressionMode::Strict, ".items[0].name"),
+            "(_strict_field(\"items\") | .[0] | _strict_field(\"name\"))"
+        );
+        assert!(Arc::ptr_eq(
+            &lax,
+            &preprocessed(ExpressionMode::Lax, ".order.total")
+        ));
+        assert!(Arc::ptr_eq(
+            &strict,
+            &preprocessed(ExpressionMode::Strict, ".order.total")
+        ));
+    }
+
+    #[test]
+    fn test_interned_expressions_are_not_preprocessed_again() {
+        assert_eq!(expression_source(" ${ .seeded } "), Some(".seeded"));
+        assert_eq!(expression_source(".seeded"), None);
+
+        intern_preprocessed(ExpressionMode::Lax, ".seeded", ".restored");
+        assert_eq!(&*preprocessed(ExpressionMode::Lax, ".seeded"), ".restored");
+        assert_eq!(
+            &*preprocessed(ExpressionMode::Strict, ".seeded"),
+            "(_strict_field(\"seeded\"))"
+        );
+    }
+}
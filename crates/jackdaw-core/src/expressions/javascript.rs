@@ -0,0 +1,228 @@
+//! JavaScript runtime expressions
+//!
+//! Workflows declaring `evaluate: { language: js }` write their `${ }`
+//! expressions in JavaScript. Each expression is evaluated in-process by an
+//! embedded QuickJS engine, in a context of its own so that expressions do not
+//! see what others left behind: the fields of the current data are in scope by
+//! name, the whole of it is `$data`, and the variables jq expressions see
+//! (`$input`, `$workflow`, `$runtime`, `$secrets`, `$uuid`, `$now` and the
+//! variables of the data, such as the `$item` of a `for` task) are bound under
+//! the same names.
+//!
+//! An expression that has not returned after [`EVALUATION_TIMEOUT`] is
+//! interrupted and fails. Each thread keeps one engine runtime, so an
+//! evaluation costs about as much as a jq one rather than a process start.
+
+use rquickjs::convert::Coerced;
+use rquickjs::{Context, Runtime};
+use serde_json::{Map, Value};
+use std::time::{Duration, Instant};
+
+use super::{BRANCHES_KEY, Error, RE_VAR_REFERENCE, Result, strip_descriptors};
+
+/// Longest a JavaScript expression may run before it is stopped
+pub const EVALUATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reads the expression, data and variables from `__request`, and returns the
+/// result as JSON
+const EVALUATOR: &str = r"
+(() => {
+  const { expression, data, variables } = JSON.parse(globalThis.__request);
+  delete globalThis.__request;
+  const names = Object.keys(variables);
+  const evaluate = new Function('$data', ...names, `with (Object($data ?? {})) { return (${expression}\n); }`);
+  const result = evaluate(data, ...names.map((name) => variables[name]));
+  return JSON.stringify(result === undefined ? null : result);
+})()
+";
+
+thread_local! {
+    /// The engine runtime of this thread, started by its first evaluation
+    static RUNTIME: std::result::Result<Runtime, String> =
+        Runtime::new().map_err(|e| e.to_string());
+}
+
+/// The variables `expression` uses, from the context and input of the evaluation
+fn variables(expression: &str, context: &Value, input: &Value) -> Map<String, Value> {
+    let mut variables = Map::new();
+    for capture in RE_VAR_REFERENCE.captures_iter(expression) {
+        let Some(name) = capture.get(1).map(|name| name.as_str()) else {
+            continue;
+        };
+        if name == "data" || variables.contains_key(name) {
+            continue;
+        }
+        let value = match name {
+            "input" => Some(strip_descriptors(input)),
+            "workflow" => context.get("__workflow").cloned(),
+            "runtime" => context.get("__runtime").cloned(),
+            "secrets" => context.get(crate::secrets::SECRETS_KEY).cloned(),
+            "branches" => context.get(BRANCHES_KEY).cloned(),
+            "uuid" => Some(Value::String(uuid::Uuid::new_v4().to_string())),
+            "now" => Some(Value::String(
+                chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            )),
+            _ => context.get(name).cloned(),
+        };
+        if let Some(value) = value {
+            variables.insert(format!("${name}"), value);
+        }
+    }
+    variables
+}
+
+/// Evaluate the JavaScript `expression` against `context`
+///
+/// # Errors
+///
+/// Returns an error if the engine cannot be started, or the expression
+/// throws, does not return a JSON value, or runs for longer than
+/// [`EVALUATION_TIMEOUT`].
+pub(super) fn evaluate(expression: &str, context: &Value, input: &Value) -> Result<Value> {
+    evaluate_within(expression, context, input, EVALUATION_TIMEOUT)
+}
+
+fn evaluate_within(
+    expression: &str,
+    context: &Value,
+    input: &Value,
+    timeout: Duration,
+) -> Result<Value> {
+    let request = serde_json::json!({
+        "expression": expression,
+        "data": strip_descriptors(context),
+        "variables": variables(expression, context, input),
+    });
+    RUNTIME.with(|runtime| {
+        let runtime = runtime
+            .as_ref()
+            .map_err(|e| failed(format!("Failed to start the JavaScript engine: {e}")))?;
+        let deadline = Instant::now() + timeout;
+        runtime.set_interrupt_handler(Some(Box::new(move || Instant::now() >= deadline)));
+        let result = run(runtime, expression, &request, deadline, timeout);
+        runtime.set_interrupt_handler(None);
+        result
+    })
+}
+
+/// Run the evaluator on `request` in a fresh context of `runtime`
+fn run(
+    runtime: &Runtime,
+    expression: &str,
+    request: &Value,
+    deadline: Instant,
+    timeout: Duration,
+) -> Result<Value> {
+    let context = Context::full(runtime)
+        .map_err(|e| failed(format!("Failed to start the JavaScript engine: {e}")))?;
+    context.with(|ctx| {
+        ctx.globals()
+            .set("__request", request.to_string())
+            .map_err(|e| failed(format!("Failed to pass the data to {expression}: {e}")))?;
+        match ctx.eval::<Option<String>, _>(EVALUATOR) {
+            Ok(Some(json)) => serde_json::from_str(&json)
+                .map_err(|e| failed(format!("{expression} did not return JSON: {e}"))),
+            Ok(None) => Err(failed(format!("{expression} did not return JSON"))),
+            Err(error) => {
+                if Instant::now() >= deadline {
+                    Err(failed(format!(
+                        "{expression}: did not finish within {}ms",
+                        timeout.as_millis()
+                    )))
+                } else if let rquickjs::Error::Exception = error {
+                    Err(failed(format!("{expression}: {}", describe(&ctx.catch()))))
+                } else {
+                    Err(failed(format!("{expression}: {error}")))
+                }
+            }
+        }
+    })
+}
+
+/// The name and message of a thrown error, or the thrown value as a string
+fn describe(thrown: &rquickjs::Value<'_>) -> String {
+    if let Some(exception) = thrown.as_exception() {
+        let name = exception
+            .get::<_, Option<String>>("name")
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| "Error".to_string());
+        return match exception.message() {
+            Some(message) => format!("{name}: {message}"),
+            None => name,
+        };
+    }
+    thrown
+        .get::<Coerced<String>>()
+        .map_or_else(|_| "unknown error".to_string(), |thrown| thrown.0)
+}
+
+fn failed(message: String) -> Error {
+    Error::JsEvaluation { message }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expressions_see_data_and_variables() {
+        let context =
+            json!({ "user": { "name": "ada" }, "item": 3, "__runtime": { "name": "jackdaw" } });
+        let input = json!({ "limit": 5 });
+        let cases = [
+            ("user.name.toUpperCase()", json!("ADA")),
+            ("$item * 2 < $input.limit", json!(false)),
+            ("$data.user.name.length", json!(3)),
+            ("$runtime.name", json!("jackdaw")),
+            ("[1, 2].map((n) => n + item)", json!([4, 5])),
+            ("undefined", Value::Null),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(
+                evaluate(expression, &context, &input).unwrap(),
+                expected,
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_thrown_errors_are_reported() {
+        let error = evaluate("missing.field", &json!({}), &Value::Null)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("ReferenceError"), "{error}");
+    }
+
+    #[test]
+    fn test_expressions_do_not_share_globals() {
+        evaluate("globalThis.leaked = 1", &json!({}), &Value::Null).unwrap();
+        assert_eq!(
+            evaluate("typeof leaked", &json!({}), &Value::Null).unwrap(),
+            json!("undefined")
+        );
+    }
+
+    #[test]
+    fn test_expressions_that_never_return_time_out() {
+        let started = Instant::now();
+        let error = evaluate_within(
+            "(() => { while (true) {} })()",
+            &json!({}),
+            &Value::Null,
+            Duration::from_millis(500),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("did not finish within 500ms"), "{error}");
+        assert!(
+            started.elapsed() < EVALUATION_TIMEOUT,
+            "{:?}",
+            started.elapsed()
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! The execution graph of a workflow's top-level tasks
+//!
+//! A runtime walks this graph from the first task, following the edges a
+//! task's `then` directive, or the declaration order, leads to.
+
+use petgraph::{graph::DiGraph, stable_graph::NodeIndex};
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::collections::HashMap;
+
+use crate::expressions::{ExpressionLanguage, ExpressionMode};
+use crate::task_ext::TaskDefinitionExt;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Task {task} transitions to unknown task: {target}"))]
+    UnknownTarget { task: String, target: String },
+
+    #[snafu(display("Expression error: {source}"))]
+    Expression { source: crate::expressions::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::expressions::Error> for Error {
+    fn from(source: crate::expressions::Error) -> Self {
+        Error::Expression { source }
+    }
+}
+
+/// The execution graph of a workflow's top-level tasks
+///
+/// Nodes borrow the task names and definitions of the workflow they were
+/// built from, so building the graph of a workflow with thousands of tasks
+/// copies none of them.
+pub type TaskGraph<'a> = DiGraph<(&'a str, &'a TaskDefinition), ()>;
+
+/// Where execution goes after a task completes, per its `then`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlowDirective {
+    /// Run the next task of the enclosing list
+    Continue,
+    /// Leave the enclosing list; the workflow itself completes when exited from
+    Exit,
+    /// Complete the workflow, from whatever depth the task runs at
+    End,
+    /// Run the named task of the enclosing list
+    Goto(String),
+}
+
+impl FlowDirective {
+    #[must_use]
+    pub fn parse(then: &str) -> Self {
+        match then {
+            "continue" => Self::Continue,
+            "exit" => Self::Exit,
+            "end" => Self::End,
+            name => Self::Goto(name.to_string()),
+        }
+    }
+}
+
+/// Build an execution graph from a workflow definition
+///
+/// Returns a tuple of (graph, ``task_name_to_node_index_map``). The graph
+/// only checks the structure of the workflow and its expression settings;
+/// runtimes check what their executors need of each task on top of it.
+///
+/// # Errors
+/// Returns an error if a task transitions to a task that does not exist, or
+/// the workflow asks for an unsupported expression language or mode.
+#[tracing::instrument(
+    target = "jackdaw::profile",
+    level = "trace",
+    name = "graph.build",
+    skip_all
+)]
+pub fn build_graph(
+    workflow: &WorkflowDefinition,
+) -> Result<(TaskGraph<'_>, HashMap<&str, NodeIndex>)> {
+    let task_count = workflow.do_.entries.iter().flatten().count();
+    let mut graph = TaskGraph::with_capacity(task_count, task_count);
+    let mut nodes = HashMap::with_capacity(task_count);
+    let mut tasks = Vec::with_capacity(task_count);
+
+    // Iterate over all task entries in the Map and preserve order
+    for entry in &workflow.do_.entries {
+        for (name, task) in entry {
+            let node = graph.add_node((name.as_str(), task));
+            nodes.insert(name.as_str(), node);
+            tasks.push((name.as_str(), task, node));
+        }
+    }
+
+    // Each task transitions to the targets of its `then`, and to the next task
+    // in declaration order unless its `then` leads elsewhere
+    for (i, &(name, task, src)) in tasks.iter().enumerate() {
+        let next = tasks.get(i + 1).map(|&(_, _, node)| node);
+        let mut targets = Vec::new();
+        for target in get_task_transitions(task) {
+            match FlowDirective::parse(&target) {
+                FlowDirective::Continue => targets.extend(next),
+                FlowDirective::Exit | FlowDirective::End => {}
+                FlowDirective::Goto(target) => {
+                    let dst = nodes.get(target.as_str()).context(UnknownTargetSnafu {
+                        task: name,
+                        target: target.as_str(),
+                    })?;
+                    targets.push(*dst);
+                }
+            }
+        }
+        if task.then().is_none() {
+            targets.extend(next);
+        }
+        targets.dedup();
+        for dst in targets {
+            graph.add_edge(src, dst, ());
+        }
+    }
+
+    ExpressionLanguage::of_workflow(workflow)?;
+    ExpressionMode::of_workflow(workflow)?;
+
+    Ok((graph, nodes))
+}
+
+/// Extract all transition targets from a task definition
+///
+/// Returns the `then` directives this task can transition with: task names,
+/// or `continue`, `exit` and `end`
+#[must_use]
+pub fn get_task_transitions(task: &TaskDefinition) -> Vec<String> {
+    let mut transitions = Vec::new();
+    if let TaskDefinition::Switch(t) = task {
+        for entry in &t.switch.entries {
+            for case in entry.values() {
+                if let Some(then) = &case.then {
+                    transitions.push(then.clone());
+                }
+            }
+        }
+    }
+    transitions.extend(task.then().map(str::to_string));
+    transitions
+}
+
+/// The task declared after `current`, which `continue` transitions to
+///
+/// Nodes are added in declaration order, so this is the node after `current`.
+#[must_use]
+pub fn next_in_sequence<N, E>(graph: &DiGraph<N, E>, current: NodeIndex) -> Option<NodeIndex> {
+    let next = NodeIndex::new(current.index() + 1);
+    (next.index() < graph.node_count()).then_some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    fn workflow(tasks: serde_json::Value) -> WorkflowDefinition {
+        serde_json::from_value(json!({
+            "document": {
+                "dsl": "1.0.2",
+                "namespace": "test",
+                "name": "graph",
+                "version": "1.0.0"
+            },
+            "do": tasks
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tasks_follow_declaration_order_and_then() {
+        let workflow = workflow(json!([
+            { "first": { "set": { "a": 1 }, "then": "third" } },
+            { "second": { "set": { "b": 2 } } },
+            { "third": { "set": { "c": 3 } } }
+        ]));
+        let (graph, nodes) = build_graph(&workflow).unwrap();
+
+        let node = |name: &str| *nodes.get(name).unwrap();
+        let targets: Vec<_> = graph.neighbors(node("first")).collect();
+        assert_eq!(targets, vec![node("third")]);
+        assert_eq!(
+            next_in_sequence(&graph, node("first")),
+            Some(node("second"))
+        );
+        assert_eq!(next_in_sequence(&graph, node("third")), None);
+    }
+
+    #[test]
+    fn test_unknown_target_is_rejected() {
+        let workflow = workflow(json!([
+            { "first": { "set": { "a": 1 }, "then": "missing" } }
+        ]));
+        let error = build_graph(&workflow).unwrap_err().to_string();
+        assert_eq!(error, "Task first transitions to unknown task: missing");
+    }
+}
@@ -0,0 +1,61 @@
+//! # Jackdaw Core
+//!
+//! The stable core of [jackdaw](https://docs.rs/jackdaw): the execution graph
+//! of a workflow, the types a run is recorded with, runtime expression
+//! evaluation, and the traits persistence, cache, secret and event providers
+//! implement.
+//!
+//! Custom runtimes and providers can depend on this crate alone, without the
+//! executors, listeners and container runtimes of the `jackdaw` crate, which
+//! re-exports every module here under the same path. The API of this crate
+//! follows semver on its own: breaking changes to it bump its major version,
+//! whatever the version of `jackdaw`.
+//!
+//! The engine that runs the graph lives in the `jackdaw` crate, and the
+//! command line in `jackdaw-cli`; both follow their own versions.
+//!
+//! ## Modules
+//!
+//! - [`graph`] - The execution graph of a workflow's top-level tasks
+//! - [`workflow`] - Events and checkpoints recorded while a workflow runs
+//! - [`persistence`] - The [`persistence::PersistenceProvider`] trait and instance states
+//! - [`cache`] - The [`cache::CacheProvider`] trait for task results
+//! - [`secrets`] - The [`secrets::SecretsProvider`] trait
+//! - [`events`] - The [`events::EventSink`] trait and `CloudEvents` envelopes
+//! - [`expressions`] - jq and JavaScript runtime expressions
+//! - [`numbers`] - How the numbers of workflow data are held
+//! - [`ordering`] - The order maps and JSON objects are visited in
+//! - [`descriptors`] - The `$workflow` and `$runtime` descriptors of a run
+//! - [`task_ext`] - Helpers on the task definitions of the SDK
+//! - [`text`] - Cutting long text down to size without splitting characters
+//!
+//! ## Implementing a provider
+//!
+//! ```rust
+//! use async_trait::async_trait;
+//! use jackdaw_core::secrets::{Result, SecretsProvider};
+//!
+//! /// Secrets baked in at build time
+//! #[derive(Debug)]
+//! struct StaticSecrets;
+//!
+//! #[async_trait]
+//! impl SecretsProvider for StaticSecrets {
+//!     async fn get(&self, name: &str) -> Result<Option<String>> {
+//!         Ok((name == "api_token").then(|| "s3cr3t".to_string()))
+//!     }
+//! }
+//! ```
+
+pub mod cache;
+pub mod descriptors;
+pub mod events;
+pub mod expressions;
+pub mod graph;
+pub mod numbers;
+pub mod ordering;
+pub mod persistence;
+pub mod secrets;
+pub mod task_ext;
+pub mod text;
+pub mod workflow;
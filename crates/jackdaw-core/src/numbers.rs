@@ -0,0 +1,143 @@
+//! How the numbers of workflow data are held
+//!
+//! `serde_json` reads every number that is not an integer into an `f64`, so
+//! `1234567890.123456789` returned by an API becomes `1234567890.1234567`,
+//! and large amounts of money lose their cents. Built with the `arbitrary-precision`
+//! feature, which enables the feature of the same name of `serde_json`, every
+//! number keeps the digits it was written with, and the engine holds numbers
+//! in one of two modes:
+//!
+//! - [`NumberMode::Float`], the default, rounds the numbers entering the
+//!   engine to `f64` as builds without the feature do, so outputs, cache keys
+//!   and recorded events are the same either way.
+//! - [`NumberMode::Decimal`] keeps them as written where they are carried
+//!   unchanged: task outputs, expressions that pass them on, cache keys and
+//!   persistence.
+//!
+//! Decimal mode preserves numbers, it does not compute with them: jq
+//! expressions compute arithmetic such as `.price * 2` on `f64`, so
+//! `${ .a + .b }` is `0.30000000000000004` for `0.1` and `0.2` in both modes.
+
+use serde_json::{Number, Value};
+use snafu::prelude::*;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid number mode '{mode}', expected float or decimal"))]
+    InvalidMode { mode: String },
+
+    #[snafu(display("Decimal numbers need jackdaw built with the arbitrary-precision feature"))]
+    Unavailable,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// How the engine holds the numbers of workflow data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberMode {
+    /// Numbers that are not integers are rounded to `f64`
+    #[default]
+    Float,
+    /// Numbers keep the digits they were written with
+    Decimal,
+}
+
+impl NumberMode {
+    /// The mode named by the `numbers` setting
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mode is neither `float` nor `decimal`, or is
+    /// `decimal` in a build without the `arbitrary-precision` feature.
+    pub fn parse(mode: &str) -> Result<Self> {
+        let mode = match mode.to_ascii_lowercase().as_str() {
+            "float" => NumberMode::Float,
+            "decimal" => NumberMode::Decimal,
+            _ => {
+                return InvalidModeSnafu {
+                    mode: mode.to_string(),
+                }
+                .fail();
+            }
+        };
+        ensure!(mode.is_available(), UnavailableSnafu);
+        Ok(mode)
+    }
+
+    /// Whether this build can hold numbers in the mode
+    #[must_use]
+    pub const fn is_available(self) -> bool {
+        match self {
+            NumberMode::Float => true,
+            NumberMode::Decimal => cfg!(feature = "arbitrary-precision"),
+        }
+    }
+
+    /// `value` with its numbers held in the mode
+    ///
+    /// ```rust
+    /// use jackdaw_core::numbers::NumberMode;
+    ///
+    /// let value = serde_json::json!({ "total": 19.99, "items": [1, 2] });
+    /// assert_eq!(NumberMode::Float.apply(value.clone()), value);
+    /// ```
+    #[must_use]
+    pub fn apply(self, mut value: Value) -> Value {
+        // Without the feature, serde_json has rounded the numbers already
+        if self == NumberMode::Float && cfg!(feature = "arbitrary-precision") {
+            round(&mut value);
+        }
+        value
+    }
+}
+
+/// Round the numbers of `value` that are not integers to `f64`
+fn round(value: &mut Value) {
+    match value {
+        Value::Number(number) if !number.is_i64() && !number.is_u64() => {
+            if let Some(rounded) = number.as_f64().and_then(Number::from_f64) {
+                *number = rounded;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(round),
+        Value::Object(map) => map.values_mut().for_each(round),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_parse_modes() {
+        assert_eq!(NumberMode::parse("float").unwrap(), NumberMode::Float);
+        assert!(matches!(
+            NumberMode::parse("bigint"),
+            Err(Error::InvalidMode { .. })
+        ));
+        assert_eq!(
+            NumberMode::parse("Decimal").is_ok(),
+            cfg!(feature = "arbitrary-precision")
+        );
+    }
+
+    #[test]
+    fn test_float_mode_rounds_decimals() {
+        let value: Value =
+            serde_json::from_str(r#"{"amount": 1234567890.123456789, "count": 3}"#).unwrap();
+        let rounded = NumberMode::Float.apply(value);
+        assert_eq!(rounded["amount"].to_string(), "1234567890.1234567");
+        assert_eq!(rounded["count"], 3);
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn test_decimal_mode_keeps_digits() {
+        let value: Value = serde_json::from_str(r#"{"amount": 1234567890.123456789}"#).unwrap();
+        let kept = NumberMode::Decimal.apply(value);
+        assert_eq!(kept["amount"].to_string(), "1234567890.123456789");
+    }
+}
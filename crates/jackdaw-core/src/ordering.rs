@@ -0,0 +1,119 @@
+//! Deterministic ordering of workflow maps and JSON objects
+//!
+//! Cache keys, digests, replays and the output of tasks must not change when
+//! nothing but the iteration order of a map does, so the order jackdaw visits
+//! maps in is fixed:
+//!
+//! - `do`, `fork.branches`, `switch` and `try` entries are lists in the DSL.
+//!   Tasks run, branches start and cases are matched in document order, and
+//!   a fork's output has one field per branch, in the order the branches are
+//!   declared.
+//! - JSON objects keep the order their fields were inserted in. Jackdaw
+//!   enables the `preserve_order` feature of `serde_json`, so workflow data,
+//!   inputs and outputs keep the order of the documents and expressions that
+//!   produced them, whether or not another dependency enables the feature
+//!   too.
+//! - Maps the workflow model holds as hash maps, such as the fields of `set`,
+//!   the arguments in `with` and the attributes of emitted events, have no
+//!   document order left once parsed. Jackdaw visits them in key order, with
+//!   [`in_key_order`] and [`to_canonical_value`].
+//! - Cache keys and digests are computed over [`canonical`] JSON, in which
+//!   the keys of every object are sorted, so they depend on none of the above.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The entries of a map, sorted by key
+///
+/// ```rust
+/// use std::collections::HashMap;
+///
+/// let fields = HashMap::from([("zeta", 1), ("alpha", 2)]);
+/// let keys: Vec<_> = jackdaw_core::ordering::in_key_order(&fields)
+///     .into_iter()
+///     .map(|(key, _)| *key)
+///     .collect();
+/// assert_eq!(keys, ["alpha", "zeta"]);
+/// ```
+#[must_use]
+pub fn in_key_order<'a, K: Ord + 'a, V: 'a>(
+    entries: impl IntoIterator<Item = (&'a K, &'a V)>,
+) -> Vec<(&'a K, &'a V)> {
+    let mut entries: Vec<_> = entries.into_iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+/// `value` with the keys of every object, at any depth, sorted
+#[must_use]
+pub fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            in_key_order(map)
+                .into_iter()
+                .map(|(key, value)| (key.clone(), canonical(value)))
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(canonical).collect()),
+        scalar @ (Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_)) => {
+            scalar.clone()
+        }
+    }
+}
+
+/// Serialize part of the workflow model to [`canonical`] JSON
+///
+/// Hash maps of the model serialize in an order that changes from run to
+/// run; use this instead of `serde_json::to_value` wherever the result is
+/// hashed, stored or becomes data of an instance.
+///
+/// # Errors
+/// Returns an error if `value` cannot be represented as JSON.
+pub fn to_canonical_value<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<Value> {
+    serde_json::to_value(value).map(|value| canonical(&value))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_objects_keep_insertion_order() {
+        let mut map = Map::new();
+        for key in ["zeta", "alpha", "mu"] {
+            map.insert(key.to_string(), json!(null));
+        }
+        let keys: Vec<_> = map.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["zeta", "alpha", "mu"]);
+
+        let parsed: Value = serde_json::from_str(r#"{"b": 1, "a": {"d": 2, "c": 3}}"#).unwrap();
+        assert_eq!(parsed.to_string(), r#"{"b":1,"a":{"d":2,"c":3}}"#);
+    }
+
+    #[test]
+    fn test_canonical_sorts_nested_objects() {
+        let value = json!({ "b": [1, { "d": 2, "c": 3 }], "a": "x" });
+        assert_eq!(
+            canonical(&value).to_string(),
+            r#"{"a":"x","b":[1,{"c":3,"d":2}]}"#
+        );
+        assert_eq!(canonical(&value), value);
+    }
+
+    #[test]
+    fn test_hash_maps_serialize_canonically() {
+        let fields: HashMap<String, Value> = (0..32)
+            .map(|i| (format!("field{i:02}"), json!({ "z": i, "a": i })))
+            .collect();
+        let value = to_canonical_value(&fields).unwrap();
+        let keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+        assert_eq!(value["field07"].to_string(), r#"{"a":7,"z":7}"#);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::workflow::WorkflowCheckpoint;
+use crate::workflow::WorkflowEvent;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Persistence error: {message}"))]
+    Persistence { message: String },
+
+    #[snafu(display("Database error: {message}"))]
+    Database { message: String },
+
+    #[snafu(display("Serialization error: {source}"))]
+    Serialization { source: serde_json::Error },
+
+    #[snafu(display("Event not found: {instance_id}"))]
+    EventNotFound { instance_id: String },
+
+    #[snafu(display("Checkpoint not found: {instance_id}"))]
+    CheckpointNotFound { instance_id: String },
+
+    #[snafu(display("Lease {token} on instance {instance_id} was taken over by another process"))]
+    LeaseLost { instance_id: String, token: u64 },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Lifecycle state of a workflow instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstanceState {
+    /// Accepted, but not started yet
+    Pending,
+    Running,
+    Suspended,
+    /// Cancelled while running; the task in flight is being stopped
+    Cancelling,
+    Completed,
+    #[serde(alias = "failed")]
+    Faulted,
+    /// Stopped by the workflow's timeout
+    TimedOut,
+    Cancelled,
+}
+
+impl InstanceState {
+    /// Whether the instance has stopped running for good, or until it is run again
+    #[must_use]
+    pub fn is_finished(self) -> bool {
+        match self {
+            Self::Completed | Self::Faulted | Self::TimedOut | Self::Cancelled => true,
+            Self::Pending | Self::Running | Self::Suspended | Self::Cancelling => false,
+        }
+    }
+
+    /// Whether the state machine allows moving from this state to `next`
+    ///
+    /// A pending instance faults if it is refused admission. A running
+    /// instance may be run again, recovering a run that stopped with its
+    /// process, and so may a faulted or timed out one. Completed and
+    /// cancelled instances are final.
+    #[must_use]
+    pub fn can_transition_to(self, next: Self) -> bool {
+        match self {
+            Self::Pending => matches!(next, Self::Running | Self::Faulted | Self::Cancelled),
+            Self::Running => matches!(
+                next,
+                Self::Running
+                    | Self::Suspended
+                    | Self::Cancelling
+                    | Self::Completed
+                    | Self::Faulted
+                    | Self::TimedOut
+            ),
+            Self::Suspended => matches!(next, Self::Running | Self::Cancelled),
+            Self::Cancelling => next == Self::Cancelled,
+            Self::Faulted | Self::TimedOut => next == Self::Running,
+            Self::Completed | Self::Cancelled => false,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Suspended => "suspended",
+            Self::Cancelling => "cancelling",
+            Self::Completed => "completed",
+            Self::Faulted => "faulted",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::fmt::Display for InstanceState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for InstanceState {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        [
+            Self::Pending,
+            Self::Running,
+            Self::Suspended,
+            Self::Cancelling,
+            Self::Completed,
+            Self::Faulted,
+            Self::TimedOut,
+            Self::Cancelled,
+        ]
+        .into_iter()
+        .find(|state| state.as_str() == s)
+        .ok_or_else(|| format!("unknown instance state '{s}'"))
+    }
+}
+
+/// Ownership of an instance by one process, which lapses unless it is renewed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Instance the lease is held on
+    pub instance_id: String,
+    /// Process holding the lease
+    pub owner: String,
+    /// Fencing token, increased every time the instance changes hands
+    pub token: u64,
+    /// When the lease lapses and another process may take the instance over
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Lease {
+    /// When a lease taken now for `ttl` lapses
+    #[must_use]
+    pub fn expiry(ttl: Duration) -> DateTime<Utc> {
+        let now = Utc::now();
+        chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| now.checked_add_signed(ttl))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// A recorded event and its position in the change feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// Position of the event among the events of every instance, starting at 1
+    pub offset: u64,
+    pub event: WorkflowEvent,
+}
+
+/// A workflow start waiting for a worker to run it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedStart {
+    /// Instance the start creates
+    pub instance_id: String,
+    /// Definition of the workflow to run, as JSON
+    pub workflow: serde_json::Value,
+    /// Input of the instance
+    pub input: serde_json::Value,
+    /// When the start was queued; workers take the oldest first
+    pub enqueued_at: DateTime<Utc>,
+}
+
+/// How many of the latest completed runs a [`WorkflowRollup`] keeps the duration of
+pub const ROLLUP_DURATIONS: usize = 1000;
+
+/// How a run of a workflow ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunOutcome {
+    /// Completed, faulted, timed out or cancelled
+    pub state: InstanceState,
+    /// How long a completed run took, in milliseconds
+    pub duration_ms: Option<u64>,
+    /// The task a failed run stopped in
+    pub failed_task: Option<String>,
+}
+
+/// A change to the runs counted in a [`WorkflowRollup`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RunUpdate {
+    Started,
+    /// A run ended; `previous` is how it ended before, if it was run again
+    /// after faulting or timing out
+    Finished {
+        outcome: RunOutcome,
+        previous: Option<RunOutcome>,
+    },
+}
+
+/// Running totals of the runs of one workflow
+///
+/// Rollups are updated as runs start and end, so reading them does not
+/// depend on how much history the provider holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkflowRollup {
+    /// The "namespace/name/version" key of the workflow
+    pub workflow_id: String,
+    pub runs: u64,
+    pub completed: u64,
+    /// Runs that faulted or timed out
+    pub failed: u64,
+    pub cancelled: u64,
+    /// How long the latest completed runs took, in milliseconds, the oldest
+    /// first; see [`ROLLUP_DURATIONS`]
+    pub durations_ms: VecDeque<u64>,
+    /// Failed runs by the task they stopped in
+    pub failing_tasks: BTreeMap<String, u64>,
+}
+
+impl WorkflowRollup {
+    #[must_use]
+    pub fn new(workflow_id: impl Into<String>) -> Self {
+        Self {
+            workflow_id: workflow_id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Count a run starting or ending
+    pub fn apply(&mut self, update: &RunUpdate) {
+        match update {
+            RunUpdate::Started => self.runs += 1,
+            RunUpdate::Finished { outcome, previous } => {
+                if let Some(previous) = previous {
+                    self.uncount(previous);
+                }
+                self.count(outcome);
+            }
+        }
+    }
+
+    fn count(&mut self, outcome: &RunOutcome) {
+        if let Some(count) = self.counter(outcome.state) {
+            *count += 1;
+        }
+        if outcome.state == InstanceState::Completed
+            && let Some(ms) = outcome.duration_ms
+        {
+            self.durations_ms.push_back(ms);
+            if self.durations_ms.len() > ROLLUP_DURATIONS {
+                self.durations_ms.pop_front();
+            }
+        }
+        if let Some(task) = &outcome.failed_task {
+            *self.failing_tasks.entry(task.clone()).or_default() += 1;
+        }
+    }
+
+    fn uncount(&mut self, outcome: &RunOutcome) {
+        if let Some(count) = self.counter(outcome.state) {
+            *count = count.saturating_sub(1);
+        }
+        if let Some(task) = &outcome.failed_task
+            && let Some(failures) = self.failing_tasks.get_mut(task)
+        {
+            *failures = failures.saturating_sub(1);
+            if *failures == 0 {
+                self.failing_tasks.remove(task);
+            }
+        }
+    }
+
+    /// The total a run ending in `state` counts towards
+    fn counter(&mut self, state: InstanceState) -> Option<&mut u64> {
+        match state {
+            InstanceState::Completed => Some(&mut self.completed),
+            InstanceState::Faulted | InstanceState::TimedOut => Some(&mut self.failed),
+            InstanceState::Cancelled => Some(&mut self.cancelled),
+            InstanceState::Pending
+            | InstanceState::Running
+            | InstanceState::Suspended
+            | InstanceState::Cancelling => None,
+        }
+    }
+}
+
+/// Fencing tokens of the leases a provider holds, keyed by instance ID
+///
+/// Providers that store leases remember the token of each lease they take,
+/// and check it against the stored lease in the same transaction as every
+/// write for that instance; see [`check_fence`].
+#[derive(Debug, Default)]
+pub struct FencingTokens {
+    held: Mutex<HashMap<String, u64>>,
+}
+
+impl FencingTokens {
+    /// Token of the lease held on an instance, if any
+    pub fn token(&self, instance_id: &str) -> Option<u64> {
+        self.held
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(instance_id)
+            .copied()
+    }
+
+    /// Remember a lease that was just taken or renewed
+    pub fn hold(&self, lease: &Lease) {
+        self.held
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(lease.instance_id.clone(), lease.token);
+    }
+
+    /// Forget a lease that was given up, unless a newer one is held
+    pub fn release(&self, lease: &Lease) {
+        let mut held = self
+            .held
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if held.get(&lease.instance_id) == Some(&lease.token) {
+            held.remove(&lease.instance_id);
+        }
+    }
+}
+
+/// Refuse a write made under lease `token` once the stored lease has a newer token
+///
+/// # Errors
+/// Returns [`Error::LeaseLost`] if `stored` is not `token`.
+pub fn check_fence(instance_id: &str, token: u64, stored: Option<u64>) -> Result<()> {
+    ensure!(stored == Some(token), LeaseLostSnafu { instance_id, token });
+    Ok(())
+}
+
+#[async_trait]
+pub trait PersistenceProvider: Send + Sync + std::fmt::Debug {
+    async fn save_event(&self, event: WorkflowEvent) -> Result<()>;
+    async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>>;
+    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()>;
+    async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>>;
+    /// IDs of every instance with recorded events, in no particular order
+    async fn list_instance_ids(&self) -> Result<Vec<String>>;
+
+    /// Record the completion of a task together with the checkpoint taken after it
+    ///
+    /// Providers backed by a transactional store write both or neither. The
+    /// default writes the event and then the checkpoint; if the process stops
+    /// between the two, resuming the instance runs the task again rather than
+    /// trusting the stale checkpoint.
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> Result<()> {
+        self.save_event(event).await?;
+        self.save_checkpoint(checkpoint).await
+    }
+
+    /// The lifecycle state recorded for an instance
+    ///
+    /// The default records no states, and the engine falls back to the state
+    /// the instance's events imply.
+    async fn get_state(&self, _instance_id: &str) -> Result<Option<InstanceState>> {
+        Ok(None)
+    }
+
+    /// Record `to` as the state of an instance, if its recorded state is still `from`
+    ///
+    /// `from` is `None` for an instance without a recorded state. Returns
+    /// whether the state was recorded; the engine checks the transition again
+    /// when it was not.
+    async fn transition_state(
+        &self,
+        _instance_id: &str,
+        _from: Option<InstanceState>,
+        _to: InstanceState,
+    ) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Take the lease of an instance for `owner`, or renew the lease it holds
+    ///
+    /// A lease held by another owner is only taken once it has lapsed, and
+    /// taking it increases the fencing token: from then on, writes for the
+    /// instance made by the process holding the old token fail with
+    /// [`Error::LeaseLost`]. Returns `None` while another owner holds the lease.
+    ///
+    /// The default hands out leases without storing them, which suits stores
+    /// a single process has open.
+    async fn acquire_lease(
+        &self,
+        instance_id: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<Option<Lease>> {
+        Ok(Some(Lease {
+            instance_id: instance_id.to_string(),
+            owner: owner.to_string(),
+            token: 1,
+            expires_at: Lease::expiry(ttl),
+        }))
+    }
+
+    /// Give up a lease, so any process may take the instance straight away
+    async fn release_lease(&self, _lease: &Lease) -> Result<()> {
+        Ok(())
+    }
+
+    /// Up to `limit` recorded events with an offset above `after`, in order
+    ///
+    /// Offsets only grow, and an event becomes visible in the feed after
+    /// every event with a lower offset, so a consumer that resumes after the
+    /// last offset it processed sees each event exactly once. `instance_id`
+    /// narrows the feed to one instance; the offsets are the same.
+    ///
+    /// # Errors
+    /// The default returns an error: the provider does not keep a change feed.
+    async fn changes(
+        &self,
+        _after: u64,
+        _instance_id: Option<&str>,
+        _limit: usize,
+    ) -> Result<Vec<Change>> {
+        Err(Error::Persistence {
+            message: "This persistence provider does not keep a change feed".to_string(),
+        })
+    }
+
+    /// Queue a workflow start for a worker to claim
+    ///
+    /// # Errors
+    /// The default returns an error: queued starts need a store shared by
+    /// every worker.
+    async fn enqueue_start(&self, start: QueuedStart) -> Result<()> {
+        Err(Error::Persistence {
+            message: format!(
+                "Cannot queue {}: this persistence provider does not support queued starts",
+                start.instance_id
+            ),
+        })
+    }
+
+    /// Up to `limit` queued starts, oldest first
+    ///
+    /// Starts stay queued while they run, so a start whose worker died is
+    /// listed again; workers claim one by taking the lease of its instance.
+    async fn queued_starts(&self, _limit: usize) -> Result<Vec<QueuedStart>> {
+        Ok(Vec::new())
+    }
+
+    /// Remove a start from the queue once its instance has run
+    async fn remove_start(&self, _instance_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Store the diagnostic bundle captured when an instance faulted,
+    /// replacing any bundle stored for it before
+    ///
+    /// The default keeps no bundles.
+    async fn save_debug_bundle(
+        &self,
+        _instance_id: &str,
+        _bundle: serde_json::Value,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// The diagnostic bundle stored for an instance, if any
+    async fn get_debug_bundle(&self, _instance_id: &str) -> Result<Option<serde_json::Value>> {
+        Ok(None)
+    }
+
+    /// Apply `update` to the rollup of a workflow, starting one on its first run
+    ///
+    /// Providers backed by a transactional store read and write the rollup in
+    /// one transaction, so updates made by several processes are all kept.
+    /// The default keeps no rollups.
+    async fn update_rollup(&self, _workflow_id: &str, _update: &RunUpdate) -> Result<()> {
+        Ok(())
+    }
+
+    /// Replace every rollup, e.g. with rollups recounted from the recorded events
+    async fn replace_rollups(&self, _rollups: Vec<WorkflowRollup>) -> Result<()> {
+        Ok(())
+    }
+
+    /// The rollup of every workflow, or `None` if the provider has none
+    ///
+    /// Reading the rollups does not read any events, so it takes as long
+    /// however much history is recorded.
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        Ok(None)
+    }
+
+    /// Whether writes are being held back because the store is unavailable
+    fn is_degraded(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,135 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use snafu::prelude::*;
+use std::sync::Arc;
+
+/// Key under which the secrets a workflow declares in `use.secrets` are
+/// placed in its data, where expressions read them as `$secrets`
+pub const SECRETS_KEY: &str = "__secrets";
+
+/// What secret values are replaced with when output is printed or persisted
+pub const REDACTED: &str = "***";
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Secret '{name}' not found"))]
+    NotFound { name: String },
+
+    #[snafu(display("Invalid secret name '{name}'"))]
+    InvalidName { name: String },
+
+    #[snafu(display("Failed to read secret '{name}': {source}"))]
+    Read {
+        name: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Secret store error: {message}"))]
+    Backend { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Pluggable source of named secrets that tasks can receive as environment variables
+#[async_trait]
+pub trait SecretsProvider: Send + Sync + std::fmt::Debug {
+    /// Look up a secret by name, returning `None` if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the secret store cannot be read
+    async fn get(&self, name: &str) -> Result<Option<String>>;
+
+    /// Look up a secret by name, failing if it does not exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the secret does not exist or the secret store cannot be read
+    async fn require(&self, name: &str) -> Result<String> {
+        self.get(name).await?.context(NotFoundSnafu { name })
+    }
+}
+
+/// Masks secret values in data before it is printed or persisted
+///
+/// Every occurrence of a secret value in a string is replaced with
+/// [`REDACTED`], and the secrets placed in the data under [`SECRETS_KEY`] are
+/// removed.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    values: Arc<Vec<String>>,
+}
+
+impl Redactor {
+    /// Mask `values`; empty values are ignored
+    #[must_use]
+    pub fn new(values: impl IntoIterator<Item = String>) -> Self {
+        let mut values: Vec<String> = values.into_iter().filter(|v| !v.is_empty()).collect();
+        // Longer values first, so a secret containing another is masked whole
+        values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+        values.dedup();
+        Self {
+            values: Arc::new(values),
+        }
+    }
+
+    /// A redactor masking `values` as well as the values this one masks
+    #[must_use]
+    pub fn merged(&self, values: impl IntoIterator<Item = String>) -> Self {
+        Self::new(self.values.iter().cloned().chain(values))
+    }
+
+    /// Whether there is nothing to mask
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// `text` with the secret values masked
+    #[must_use]
+    pub fn redact_str(&self, text: &str) -> String {
+        self.values.iter().fold(text.to_string(), |text, value| {
+            text.replace(value.as_str(), REDACTED)
+        })
+    }
+
+    /// Mask the secret values in `value` in place
+    pub fn redact(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => {
+                if self
+                    .values
+                    .iter()
+                    .any(|secret| text.contains(secret.as_str()))
+                {
+                    *text = self.redact_str(text);
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+            Value::Object(map) => {
+                map.remove(SECRETS_KEY);
+                map.values_mut().for_each(|item| self.redact(item));
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redactor_masks_values_and_drops_secrets() {
+        let redactor = Redactor::new(["s3cr3t".to_string(), String::new()]);
+        let mut data = json!({
+            "header": "Bearer s3cr3t",
+            "items": ["s3cr3t", 1],
+            "__secrets": { "API_KEY": "s3cr3t" }
+        });
+        redactor.redact(&mut data);
+        assert_eq!(data, json!({ "header": "Bearer ***", "items": ["***", 1] }));
+    }
+}
@@ -10,9 +10,18 @@ pub trait TaskDefinitionExt {
     /// Get the export configuration for this task
     fn export(&self) -> Option<&OutputDataModelDefinition>;
 
+    /// Get the `if` condition that decides whether this task runs
+    fn condition(&self) -> Option<&str>;
+
     /// Get the input configuration for this task
     fn input(&self) -> Option<&InputDataModelDefinition>;
 
+    /// Get the output configuration for this task
+    fn output(&self) -> Option<&OutputDataModelDefinition>;
+
+    /// Get the flow directive (`continue`, `exit`, `end` or a task name) this task transitions with
+    fn then(&self) -> Option<&str>;
+
     /// Get the timeout configuration for this task
     fn timeout(&self) -> Option<&OneOfTimeoutDefinitionOrReference>;
 
@@ -38,6 +47,23 @@ impl TaskDefinitionExt for TaskDefinition {
         }
     }
 
+    fn condition(&self) -> Option<&str> {
+        match self {
+            TaskDefinition::Call(t) => t.common.if_.as_deref(),
+            TaskDefinition::Do(t) => t.common.if_.as_deref(),
+            TaskDefinition::Emit(t) => t.common.if_.as_deref(),
+            TaskDefinition::For(t) => t.common.if_.as_deref(),
+            TaskDefinition::Fork(t) => t.common.if_.as_deref(),
+            TaskDefinition::Listen(t) => t.common.if_.as_deref(),
+            TaskDefinition::Raise(t) => t.common.if_.as_deref(),
+            TaskDefinition::Run(t) => t.common.if_.as_deref(),
+            TaskDefinition::Set(t) => t.common.if_.as_deref(),
+            TaskDefinition::Switch(t) => t.common.if_.as_deref(),
+            TaskDefinition::Try(t) => t.common.if_.as_deref(),
+            TaskDefinition::Wait(t) => t.common.if_.as_deref(),
+        }
+    }
+
     fn input(&self) -> Option<&InputDataModelDefinition> {
         match self {
             TaskDefinition::Call(t) => t.common.input.as_ref(),
@@ -55,6 +81,40 @@ impl TaskDefinitionExt for TaskDefinition {
         }
     }
 
+    fn output(&self) -> Option<&OutputDataModelDefinition> {
+        match self {
+            TaskDefinition::Call(t) => t.common.output.as_ref(),
+            TaskDefinition::Do(t) => t.common.output.as_ref(),
+            TaskDefinition::Emit(t) => t.common.output.as_ref(),
+            TaskDefinition::For(t) => t.common.output.as_ref(),
+            TaskDefinition::Fork(t) => t.common.output.as_ref(),
+            TaskDefinition::Listen(t) => t.common.output.as_ref(),
+            TaskDefinition::Raise(t) => t.common.output.as_ref(),
+            TaskDefinition::Run(t) => t.common.output.as_ref(),
+            TaskDefinition::Set(t) => t.common.output.as_ref(),
+            TaskDefinition::Switch(t) => t.common.output.as_ref(),
+            TaskDefinition::Try(t) => t.common.output.as_ref(),
+            TaskDefinition::Wait(t) => t.common.output.as_ref(),
+        }
+    }
+
+    fn then(&self) -> Option<&str> {
+        match self {
+            TaskDefinition::Call(t) => t.common.then.as_deref(),
+            TaskDefinition::Do(t) => t.common.then.as_deref(),
+            TaskDefinition::Emit(t) => t.common.then.as_deref(),
+            TaskDefinition::For(t) => t.common.then.as_deref(),
+            TaskDefinition::Fork(t) => t.common.then.as_deref(),
+            TaskDefinition::Listen(t) => t.common.then.as_deref(),
+            TaskDefinition::Raise(t) => t.common.then.as_deref(),
+            TaskDefinition::Run(t) => t.common.then.as_deref(),
+            TaskDefinition::Set(t) => t.common.then.as_deref(),
+            TaskDefinition::Switch(t) => t.common.then.as_deref(),
+            TaskDefinition::Try(t) => t.common.then.as_deref(),
+            TaskDefinition::Wait(t) => t.common.then.as_deref(),
+        }
+    }
+
     fn timeout(&self) -> Option<&OneOfTimeoutDefinitionOrReference> {
         match self {
             TaskDefinition::Call(t) => t.common.timeout.as_ref(),
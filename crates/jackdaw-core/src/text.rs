@@ -0,0 +1,92 @@
+//! Cutting text down to size without splitting characters
+//!
+//! The output of scripts, response bodies and the values quoted in
+//! expression errors can be megabytes long, and would otherwise end up whole
+//! in the console, in logs and in every event recording an error. Text is cut
+//! with [`truncate`] instead of by slicing, which panics when the offset falls
+//! inside a multi-byte character; it also keeps grapheme clusters, such as an
+//! emoji with its modifiers or a letter with its accents, whole.
+
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Most bytes of an error message recorded with an event
+pub const MAX_ERROR_LEN: usize = 16 * 1024;
+
+/// Most bytes of a value, or of the output of a task, shown in the console
+pub const MAX_DISPLAY_LEN: usize = 8 * 1024;
+
+/// Most bytes of a document or response body quoted in an error message
+pub const MAX_EXCERPT_LEN: usize = 200;
+
+/// `text`, cut to at most `max_len` bytes and followed by a note of how many
+/// were left out if it is longer
+///
+/// The text is cut at the end of the last grapheme cluster that fits, so
+/// neither a character nor a cluster of them is ever split.
+///
+/// ```rust
+/// use jackdaw_core::text::truncate;
+///
+/// assert_eq!(truncate("short", 10), "short");
+/// assert_eq!(truncate("naïve café", 4), "naï… (8 more bytes)");
+/// ```
+#[must_use]
+pub fn truncate(text: &str, max_len: usize) -> Cow<'_, str> {
+    if text.len() <= max_len {
+        return Cow::Borrowed(text);
+    }
+    let kept = prefix(text, max_len);
+    Cow::Owned(format!("{kept}… ({} more bytes)", text.len() - kept.len()))
+}
+
+/// The longest start of `text` that is at most `max_len` bytes and ends at a
+/// grapheme cluster boundary
+#[must_use]
+pub fn prefix(text: &str, max_len: usize) -> &str {
+    let end = text
+        .grapheme_indices(true)
+        .map(|(start, grapheme)| start + grapheme.len())
+        .take_while(|end| *end <= max_len)
+        .last()
+        .unwrap_or(0);
+    text.get(..end).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_text_is_kept() {
+        assert!(matches!(truncate("hello", 5), Cow::Borrowed("hello")));
+        assert_eq!(truncate("", 0), "");
+    }
+
+    #[test]
+    fn test_characters_are_not_split() {
+        // 'é' takes two bytes, so a cut after three would split it
+        assert_eq!(prefix("héllo", 2), "h");
+        assert_eq!(prefix("héllo", 3), "hé");
+        assert_eq!(prefix("日本語", 4), "日");
+        assert_eq!(truncate("日本語", 4), "日… (6 more bytes)");
+    }
+
+    #[test]
+    fn test_grapheme_clusters_are_not_split() {
+        // A family emoji joins three emoji of four bytes with two joiners of three
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(prefix(&format!("{family}!"), 17), "");
+        assert_eq!(prefix(&format!("{family}!"), 18), family);
+        // An accent combining with the letter before it
+        assert_eq!(prefix("ae\u{301}", 2), "a");
+    }
+
+    #[test]
+    fn test_giant_text_is_bounded() {
+        let giant = "x".repeat(10 * MAX_ERROR_LEN);
+        let cut = truncate(&giant, MAX_ERROR_LEN);
+        assert!(cut.len() < MAX_ERROR_LEN + 32);
+        assert!(cut.ends_with(&format!("… ({} more bytes)", 9 * MAX_ERROR_LEN)));
+    }
+}
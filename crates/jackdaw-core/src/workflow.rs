@@ -0,0 +1,297 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WorkflowEvent {
+    WorkflowStarted {
+        instance_id: String,
+        workflow_id: String,
+        timestamp: DateTime<Utc>,
+        initial_data: serde_json::Value,
+        /// Metadata attached to the instance at start, such as the provenance of its bundle
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<serde_json::Map<String, serde_json::Value>>,
+    },
+    TaskEntered {
+        instance_id: String,
+        task_name: String,
+        timestamp: DateTime<Utc>,
+    },
+    TaskCreated {
+        instance_id: String,
+        task_name: String,
+        task_type: String,
+        timestamp: DateTime<Utc>,
+    },
+    TaskStarted {
+        instance_id: String,
+        task_name: String,
+        timestamp: DateTime<Utc>,
+    },
+    TaskRetried {
+        instance_id: String,
+        task_name: String,
+        attempt: u32,
+        timestamp: DateTime<Utc>,
+    },
+    TaskCompleted {
+        instance_id: String,
+        task_name: String,
+        result: serde_json::Value,
+        timestamp: DateTime<Utc>,
+        duration_ms: i64,
+    },
+    WorkflowCompleted {
+        instance_id: String,
+        final_data: serde_json::Value,
+        timestamp: DateTime<Utc>,
+        duration_ms: i64,
+    },
+    /// Emitted when a workflow starts correlating events
+    ///
+    /// Spec-compliant event following the Serverless Workflow specification.
+    WorkflowCorrelationStarted {
+        instance_id: String,
+        /// The qualified name of the workflow instance
+        name: String,
+        /// The date and time at which correlation started
+        started_at: DateTime<Utc>,
+    },
+    /// Emitted when a workflow completes correlating events
+    ///
+    /// Spec-compliant event following the Serverless Workflow specification,
+    /// with a jackdaw extension field `correlation_output` for library convenience.
+    WorkflowCorrelationCompleted {
+        instance_id: String,
+        /// The qualified name of the workflow instance
+        name: String,
+        /// The ID of the correlation context (identifies which correlation completed)
+        correlation_context: String,
+        /// A key/value mapping of the correlation keys
+        correlation_keys: BTreeMap<String, String>,
+        /// The date and time at which correlation completed
+        completed_at: DateTime<Utc>,
+        /// **Jackdaw extension**: The output data from processing this correlation
+        ///
+        /// This is a non-spec extension field that makes it convenient for library
+        /// users to get the result of each correlation/iteration without querying
+        /// workflow instance state.
+        correlation_output: Option<serde_json::Value>,
+    },
+    WorkflowFailed {
+        instance_id: String,
+        error: String,
+        timestamp: DateTime<Utc>,
+    },
+    WorkflowCancelled {
+        instance_id: String,
+        reason: Option<String>,
+        timestamp: DateTime<Utc>,
+    },
+    WorkflowSuspended {
+        instance_id: String,
+        reason: Option<String>,
+        checkpoint_data: serde_json::Value,
+        timestamp: DateTime<Utc>,
+    },
+    WorkflowResumed {
+        instance_id: String,
+        timestamp: DateTime<Utc>,
+    },
+    TaskCancelled {
+        instance_id: String,
+        task_name: String,
+        reason: Option<String>,
+        timestamp: DateTime<Utc>,
+    },
+    TaskSuspended {
+        instance_id: String,
+        task_name: String,
+        state: serde_json::Value,
+        timestamp: DateTime<Utc>,
+    },
+    TaskResumed {
+        instance_id: String,
+        task_name: String,
+        timestamp: DateTime<Utc>,
+    },
+    TaskFaulted {
+        instance_id: String,
+        task_name: String,
+        error: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// Emitted once a task that was cancelled, suspended or timed out has
+    /// stopped the work its executor started
+    TaskReaped {
+        instance_id: String,
+        task_name: String,
+        /// Why the task was stopped: "timeout", "cancelled" or "suspended"
+        reason: String,
+        /// Processes, containers and requests that were stopped
+        resources: Vec<ReapedResource>,
+        /// The executor did not stop within the grace period, so the task was
+        /// dropped; child processes it spawned are killed when dropped
+        abandoned: bool,
+        timestamp: DateTime<Utc>,
+    },
+    /// Emitted when a task's output is taken from the cache instead of
+    /// executing the task
+    TaskCacheHit {
+        instance_id: String,
+        task_name: String,
+        cache_key: String,
+        /// When the cached output was stored
+        cached_at: DateTime<Utc>,
+        timestamp: DateTime<Utc>,
+    },
+    /// Emitted when a task's `if` condition is false, so the task does not run
+    /// and passes its input through as its output
+    TaskSkipped {
+        instance_id: String,
+        task_name: String,
+        /// The condition that evaluated to false
+        condition: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// Emitted when a case of a switch task matches
+    SwitchCaseMatched {
+        instance_id: String,
+        task_name: String,
+        /// Name of the case that matched
+        case: String,
+        timestamp: DateTime<Utc>,
+    },
+    /// Emitted when a try task catches an error of one of its tasks
+    ErrorCaught {
+        instance_id: String,
+        task_name: String,
+        /// The caught error, as exposed to the catch block
+        error: serde_json::Value,
+        timestamp: DateTime<Utc>,
+    },
+    /// Emitted after a task completes when watch expressions are set, with
+    /// the value of each expression at that point
+    WatchesEvaluated {
+        instance_id: String,
+        task_name: String,
+        /// Values keyed by the watch expression
+        values: serde_json::Map<String, serde_json::Value>,
+        timestamp: DateTime<Utc>,
+    },
+    /// Emitted after a candidate version of the workflow ran in the shadow of
+    /// the instance, with where the outputs of the two runs differ
+    ShadowCompared {
+        instance_id: String,
+        /// The candidate, as "namespace/name/version"
+        candidate: String,
+        /// Where the output of the candidate differs from the instance's
+        differences: Vec<OutputDifference>,
+        /// Why the candidate failed, if it did
+        error: Option<String>,
+        /// Tasks with side effects that took the instance's results
+        stubbed: Vec<String>,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+/// A value that differs between the outputs of two runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputDifference {
+    /// JSON pointer to the value, empty for the whole output
+    pub path: String,
+    /// The value in the first output, if it has one there
+    pub left: Option<serde_json::Value>,
+    /// The value in the second output, if it has one there
+    pub right: Option<serde_json::Value>,
+}
+
+/// Work an executor stopped because its task was cancelled or timed out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ReapedResource {
+    /// A child process that was killed
+    Process { pid: Option<u32>, command: String },
+    /// A container that was removed
+    Container { id: String, image: String },
+    /// A request that was aborted
+    Request { target: String },
+}
+
+impl WorkflowEvent {
+    #[must_use]
+    pub fn instance_id(&self) -> &str {
+        match self {
+            WorkflowEvent::WorkflowStarted { instance_id, .. }
+            | WorkflowEvent::TaskEntered { instance_id, .. }
+            | WorkflowEvent::TaskCreated { instance_id, .. }
+            | WorkflowEvent::TaskStarted { instance_id, .. }
+            | WorkflowEvent::TaskRetried { instance_id, .. }
+            | WorkflowEvent::TaskCompleted { instance_id, .. }
+            | WorkflowEvent::WorkflowCompleted { instance_id, .. }
+            | WorkflowEvent::WorkflowCorrelationStarted { instance_id, .. }
+            | WorkflowEvent::WorkflowCorrelationCompleted { instance_id, .. }
+            | WorkflowEvent::WorkflowFailed { instance_id, .. }
+            | WorkflowEvent::WorkflowCancelled { instance_id, .. }
+            | WorkflowEvent::WorkflowSuspended { instance_id, .. }
+            | WorkflowEvent::WorkflowResumed { instance_id, .. }
+            | WorkflowEvent::TaskCancelled { instance_id, .. }
+            | WorkflowEvent::TaskSuspended { instance_id, .. }
+            | WorkflowEvent::TaskResumed { instance_id, .. }
+            | WorkflowEvent::TaskFaulted { instance_id, .. }
+            | WorkflowEvent::TaskReaped { instance_id, .. }
+            | WorkflowEvent::TaskCacheHit { instance_id, .. }
+            | WorkflowEvent::TaskSkipped { instance_id, .. }
+            | WorkflowEvent::SwitchCaseMatched { instance_id, .. }
+            | WorkflowEvent::ErrorCaught { instance_id, .. }
+            | WorkflowEvent::WatchesEvaluated { instance_id, .. }
+            | WorkflowEvent::ShadowCompared { instance_id, .. } => instance_id,
+        }
+    }
+
+    /// When the event occurred
+    #[must_use]
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            WorkflowEvent::WorkflowStarted { timestamp, .. }
+            | WorkflowEvent::TaskEntered { timestamp, .. }
+            | WorkflowEvent::TaskCreated { timestamp, .. }
+            | WorkflowEvent::TaskStarted { timestamp, .. }
+            | WorkflowEvent::TaskRetried { timestamp, .. }
+            | WorkflowEvent::TaskCompleted { timestamp, .. }
+            | WorkflowEvent::WorkflowCompleted { timestamp, .. }
+            | WorkflowEvent::WorkflowFailed { timestamp, .. }
+            | WorkflowEvent::WorkflowCancelled { timestamp, .. }
+            | WorkflowEvent::WorkflowSuspended { timestamp, .. }
+            | WorkflowEvent::WorkflowResumed { timestamp, .. }
+            | WorkflowEvent::TaskCancelled { timestamp, .. }
+            | WorkflowEvent::TaskSuspended { timestamp, .. }
+            | WorkflowEvent::TaskResumed { timestamp, .. }
+            | WorkflowEvent::TaskFaulted { timestamp, .. }
+            | WorkflowEvent::TaskReaped { timestamp, .. }
+            | WorkflowEvent::TaskCacheHit { timestamp, .. }
+            | WorkflowEvent::TaskSkipped { timestamp, .. }
+            | WorkflowEvent::SwitchCaseMatched { timestamp, .. }
+            | WorkflowEvent::ErrorCaught { timestamp, .. }
+            | WorkflowEvent::WatchesEvaluated { timestamp, .. }
+            | WorkflowEvent::ShadowCompared { timestamp, .. }
+            | WorkflowEvent::WorkflowCorrelationStarted {
+                started_at: timestamp,
+                ..
+            }
+            | WorkflowEvent::WorkflowCorrelationCompleted {
+                completed_at: timestamp,
+                ..
+            } => *timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowCheckpoint {
+    pub instance_id: String,
+    pub current_task: String,
+    pub data: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
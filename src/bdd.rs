@@ -0,0 +1,391 @@
+//! Gherkin scenarios run against workflows
+//!
+//! `jackdaw test --features <DIR>` runs the `.feature` files in a directory
+//! with the steps below, so workflows can be specified as Given/When/Then
+//! scenarios without writing Rust:
+//!
+//! ```gherkin
+//! Feature: Greeter
+//!   Scenario: greets by name
+//!     Given the workflow file "../workflows/greeter.sw.yaml"
+//!     And the workflow input is:
+//!       """
+//!       name: Rex
+//!       """
+//!     When the workflow is executed
+//!     Then the workflow should complete with output:
+//!       """
+//!       message: Hello Rex
+//!       """
+//!     And task "greet" should complete
+//!     And a TaskCompleted event should be recorded for task "greet"
+//! ```
+//!
+//! Workflow files, and the resources of inline definitions, are resolved
+//! relative to the feature file. Each scenario runs on its own engine with
+//! in-memory persistence and cache.
+
+use cucumber::gherkin::Step;
+use cucumber::writer::Stats as _;
+use cucumber::{World, given, then, when};
+use futures::FutureExt;
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::DurableEngineBuilder;
+use crate::coverage::Coverage;
+use crate::durableengine::DurableEngine;
+use crate::observer::WorkflowObserver;
+use crate::testing;
+use crate::workflow::WorkflowEvent;
+
+/// How long a scenario's workflow may run before it fails
+const RUN_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// State of a scenario
+#[derive(Debug, World)]
+#[world(init = Self::new)]
+pub struct WorkflowWorld {
+    engine: DurableEngine,
+    /// The feature file the scenario belongs to
+    feature_path: Option<PathBuf>,
+    workflow: Option<WorkflowDefinition>,
+    input: Value,
+    /// Output of the run, or its error
+    outcome: Option<Result<Value, String>>,
+    events: Vec<WorkflowEvent>,
+}
+
+impl WorkflowWorld {
+    async fn new() -> Result<Self, crate::durableengine::Error> {
+        Ok(Self {
+            engine: DurableEngineBuilder::new().build()?,
+            feature_path: None,
+            workflow: None,
+            input: Value::Object(serde_json::Map::new()),
+            outcome: None,
+            events: Vec::new(),
+        })
+    }
+
+    fn output(&self) -> Result<&Value, String> {
+        match &self.outcome {
+            Some(Ok(output)) => Ok(output),
+            Some(Err(error)) => Err(format!("the workflow failed: {error}")),
+            None => Err("the workflow was not executed".to_string()),
+        }
+    }
+
+    /// Variant names and results of the events recorded for a task
+    fn task_events(&self, task: &str) -> Vec<(String, Value)> {
+        self.events
+            .iter()
+            .filter_map(event_parts)
+            .filter(|(_, fields)| fields.get("task_name").and_then(Value::as_str) == Some(task))
+            .map(|(kind, mut fields)| (kind, fields.remove("result").unwrap_or(Value::Null)))
+            .collect()
+    }
+}
+
+/// Collects the events of a run
+#[derive(Debug, Default)]
+struct EventLog(Mutex<Vec<WorkflowEvent>>);
+
+#[async_trait::async_trait]
+impl WorkflowObserver for EventLog {
+    async fn on_event(&self, event: &WorkflowEvent) {
+        if let Ok(mut events) = self.0.lock() {
+            events.push(event.clone());
+        }
+    }
+}
+
+/// The variant name of an event and its fields
+fn event_parts(event: &WorkflowEvent) -> Option<(String, serde_json::Map<String, Value>)> {
+    match serde_json::to_value(event).ok()? {
+        Value::Object(map) => map
+            .into_iter()
+            .next()
+            .and_then(|(kind, fields)| match fields {
+                Value::Object(fields) => Some((kind, fields)),
+                Value::Null
+                | Value::Bool(_)
+                | Value::Number(_)
+                | Value::String(_)
+                | Value::Array(_) => None,
+            }),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) => {
+            None
+        }
+    }
+}
+
+/// Parse the YAML or JSON of a doc string, skipping a leading content type line
+fn parse_docstring(step: &Step) -> Result<Value, String> {
+    let docstring = step
+        .docstring
+        .as_deref()
+        .ok_or_else(|| format!("step '{}' needs a doc string", step.value))?;
+    let content: Vec<&str> = docstring
+        .lines()
+        .skip_while(|line| matches!(line.trim(), "" | "yaml" | "json"))
+        .collect();
+    serde_yaml::from_str(&content.join("\n")).map_err(|e| format!("invalid doc string: {e}"))
+}
+
+/// Whether every field of `expected` is in `actual` with the same value
+fn contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+            actual
+                .get(key)
+                .is_some_and(|actual| contains(actual, value))
+        }),
+        (actual, expected) => actual == expected,
+    }
+}
+
+#[given(expr = "a workflow with definition:")]
+async fn given_definition(world: &mut WorkflowWorld, step: &Step) -> Result<(), String> {
+    let definition = parse_docstring(step)?;
+    let workflow: WorkflowDefinition =
+        serde_json::from_value(definition).map_err(|e| format!("invalid workflow: {e}"))?;
+    if let Some(feature_path) = &world.feature_path {
+        world
+            .engine
+            .set_workflow_path(&workflow, feature_path)
+            .await;
+    }
+    world.workflow = Some(workflow);
+    Ok(())
+}
+
+#[given(expr = "the workflow file {string}")]
+async fn given_workflow_file(world: &mut WorkflowWorld, path: String) -> Result<(), String> {
+    let path = match world.feature_path.as_deref().and_then(Path::parent) {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    };
+    let (workflow, _) = testing::load(&path, false).map_err(|e| e.to_string())?;
+    world.engine.set_workflow_path(&workflow, &path).await;
+    world.workflow = Some(workflow);
+    Ok(())
+}
+
+// The conformance test kit phrases this step "given the workflow input is:"
+#[given(regex = r"^(?:given )?the workflow input is:$")]
+async fn given_input(world: &mut WorkflowWorld, step: &Step) -> Result<(), String> {
+    world.input = parse_docstring(step)?;
+    Ok(())
+}
+
+#[when(expr = "the workflow is executed")]
+async fn when_executed(world: &mut WorkflowWorld) -> Result<(), String> {
+    let workflow = world
+        .workflow
+        .clone()
+        .ok_or("no workflow was given; start with 'Given a workflow with definition:'")?;
+
+    let log = Arc::new(EventLog::default());
+    let outcome = match world
+        .engine
+        .start_with_observer(
+            workflow,
+            world.input.clone(),
+            Arc::clone(&log) as Arc<dyn WorkflowObserver>,
+        )
+        .await
+    {
+        Ok(handle) => handle
+            .wait_for_completion(RUN_TIMEOUT)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    world.outcome = Some(outcome);
+    world.events = log
+        .0
+        .lock()
+        .map(|events| events.clone())
+        .unwrap_or_default();
+    Ok(())
+}
+
+#[then(expr = "the workflow should complete")]
+async fn then_completes(world: &mut WorkflowWorld) -> Result<(), String> {
+    world.output().map(|_| ())
+}
+
+#[then(expr = "the workflow should complete with output:")]
+async fn then_completes_with_output(world: &mut WorkflowWorld, step: &Step) -> Result<(), String> {
+    let expected = parse_docstring(step)?;
+    let output = world.output()?;
+    if *output != expected {
+        return Err(format!("expected output {expected}, but got {output}"));
+    }
+    Ok(())
+}
+
+#[then(expr = "the workflow output should contain:")]
+async fn then_output_contains(world: &mut WorkflowWorld, step: &Step) -> Result<(), String> {
+    let expected = parse_docstring(step)?;
+    let output = world.output()?;
+    if !contains(output, &expected) {
+        return Err(format!(
+            "expected output containing {expected}, but got {output}"
+        ));
+    }
+    Ok(())
+}
+
+#[then(expr = "the workflow should fault")]
+async fn then_faults(world: &mut WorkflowWorld) -> Result<(), String> {
+    match &world.outcome {
+        Some(Err(_)) => Ok(()),
+        Some(Ok(output)) => Err(format!("expected a fault, but got {output}")),
+        None => Err("the workflow was not executed".to_string()),
+    }
+}
+
+#[then(expr = "the workflow should fault with error containing {string}")]
+async fn then_faults_with(world: &mut WorkflowWorld, text: String) -> Result<(), String> {
+    match &world.outcome {
+        Some(Err(error)) if error.contains(&text) => Ok(()),
+        Some(Err(error)) => Err(format!(
+            "expected an error containing '{text}', but it failed with: {error}"
+        )),
+        Some(Ok(output)) => Err(format!(
+            "expected an error containing '{text}', but got {output}"
+        )),
+        None => Err("the workflow was not executed".to_string()),
+    }
+}
+
+#[then(expr = "task {string} should complete")]
+async fn then_task_completes(world: &mut WorkflowWorld, task: String) -> Result<(), String> {
+    if !world
+        .task_events(&task)
+        .iter()
+        .any(|(kind, _)| kind == "TaskCompleted")
+    {
+        return Err(format!("task '{task}' did not complete"));
+    }
+    Ok(())
+}
+
+#[then(expr = "task {string} should complete with output:")]
+async fn then_task_output(
+    world: &mut WorkflowWorld,
+    task: String,
+    step: &Step,
+) -> Result<(), String> {
+    let expected = parse_docstring(step)?;
+    let output = world
+        .task_events(&task)
+        .into_iter()
+        .find(|(kind, _)| kind == "TaskCompleted")
+        .map(|(_, output)| output)
+        .ok_or_else(|| format!("task '{task}' did not complete"))?;
+    if output != expected {
+        return Err(format!(
+            "expected task '{task}' to output {expected}, but got {output}"
+        ));
+    }
+    Ok(())
+}
+
+#[then(expr = "task {string} should not run")]
+async fn then_task_skipped(world: &mut WorkflowWorld, task: String) -> Result<(), String> {
+    if world
+        .task_events(&task)
+        .iter()
+        .any(|(kind, _)| kind == "TaskStarted")
+    {
+        return Err(format!("task '{task}' ran"));
+    }
+    Ok(())
+}
+
+#[then(expr = "a {word} event should be recorded")]
+async fn then_event_recorded(world: &mut WorkflowWorld, kind: String) -> Result<(), String> {
+    if !world
+        .events
+        .iter()
+        .filter_map(event_parts)
+        .any(|(recorded, _)| recorded == kind)
+    {
+        return Err(format!("no {kind} event was recorded"));
+    }
+    Ok(())
+}
+
+#[then(expr = "a {word} event should be recorded for task {string}")]
+async fn then_task_event_recorded(
+    world: &mut WorkflowWorld,
+    kind: String,
+    task: String,
+) -> Result<(), String> {
+    if !world
+        .task_events(&task)
+        .iter()
+        .any(|(recorded, _)| recorded == kind)
+    {
+        return Err(format!("no {kind} event was recorded for task '{task}'"));
+    }
+    Ok(())
+}
+
+/// Run the scenarios of the `.feature` files in `path`, printing the results
+///
+/// With a `filter`, only scenarios whose name contains it run. Steps that
+/// match no step definition fail their scenario. With `coverage`, the
+/// workflow and events of each scenario are added to it. Returns whether
+/// every scenario passed.
+pub async fn run_features(
+    path: &Path,
+    filter: Option<String>,
+    coverage: Option<Arc<Mutex<Coverage>>>,
+) -> bool {
+    let writer = WorkflowWorld::cucumber()
+        .with_default_cli()
+        .fail_on_skipped()
+        .before(|feature, _rule, _scenario, world| {
+            world.feature_path = feature.path.clone();
+            async {}.boxed_local()
+        })
+        .after(move |_feature, _rule, _scenario, _finished, world| {
+            if let (Some(coverage), Some(world)) = (&coverage, world)
+                && let Some(workflow) = &world.workflow
+                && let Ok(mut coverage) = coverage.lock()
+            {
+                coverage.add_workflow(workflow);
+                coverage.record(&world.events);
+            }
+            async {}.boxed_local()
+        })
+        .filter_run(path, move |_feature, _rule, scenario| {
+            filter
+                .as_deref()
+                .is_none_or(|filter| scenario.name.contains(filter))
+        })
+        .await;
+    !writer.execution_has_failed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_contains_matches_subset() {
+        let output = json!({ "user": { "name": "Rex", "age": 3 }, "ok": true });
+        assert!(contains(&output, &json!({ "user": { "name": "Rex" } })));
+        assert!(contains(&output, &json!({})));
+        assert!(!contains(&output, &json!({ "user": { "name": "Max" } })));
+        assert!(!contains(&output, &json!({ "missing": null })));
+    }
+}
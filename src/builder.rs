@@ -4,11 +4,31 @@
 
 use crate::{
     cache::CacheProvider,
-    durableengine::{DurableEngine, Result},
+    container::{ContainerOptions, ContainerRuntime},
+    durableengine::{
+        DEFAULT_LEASE_TTL, DEFAULT_MAX_CALL_DEPTH, DurableEngine, EngineConfig, EngineSnapshot,
+        Result, ShadowMode, stats::RunRollups,
+    },
+    events::EventSink,
+    listeners::ListenerAuth,
+    middleware::TaskMiddleware,
+    mock::MockRoutes,
+    numbers::NumberMode,
+    observer::{ObservedPersistence, WorkflowObserver},
     persistence::PersistenceProvider,
-    providers::{cache::mem::InMemoryCache, persistence::InMemoryPersistence},
+    providers::{
+        cache::mem::InMemoryCache, container::WarmPool, persistence::InMemoryPersistence,
+        secrets::EnvSecretsProvider,
+    },
+    secrets::SecretsProvider,
+    signing::SignatureVerifier,
+    task_env::EnvPolicy,
+    telemetry::Telemetry,
 };
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Builder for creating a [`DurableEngine`](crate::durableengine::DurableEngine)
 ///
@@ -49,8 +69,34 @@ use std::sync::Arc;
 #[allow(dead_code)]
 pub struct DurableEngineBuilder {
     persistence: Option<Arc<dyn PersistenceProvider>>,
+    read_persistence: Option<Arc<dyn PersistenceProvider>>,
     cache: Option<Arc<dyn CacheProvider>>,
+    cache_ttl: Option<Duration>,
+    lease_ttl: Duration,
     event_buffer_size: usize,
+    base_dir: Option<PathBuf>,
+    middleware: Vec<Arc<dyn TaskMiddleware>>,
+    env_policy: EnvPolicy,
+    secrets: Option<Arc<dyn SecretsProvider>>,
+    container_defaults: ContainerOptions,
+    container_pool: WarmPool,
+    container_runtime: ContainerRuntime,
+    event_sinks: Vec<Arc<dyn EventSink>>,
+    fork_max_parallelism: Option<usize>,
+    for_parallelism: Option<usize>,
+    number_mode: NumberMode,
+    mock_routes: MockRoutes,
+    watch_exprs: Vec<String>,
+    manifest_config: Option<serde_json::Value>,
+    shadows: Vec<(WorkflowDefinition, ShadowMode)>,
+    listener_auth: Option<ListenerAuth>,
+    catalog_dir: Option<PathBuf>,
+    workflow_sources: Vec<String>,
+    max_call_depth: usize,
+    verifier: SignatureVerifier,
+    strict_env: bool,
+    telemetry: Option<Arc<Telemetry>>,
+    snapshot: Option<EngineSnapshot>,
 }
 
 #[allow(dead_code)]
@@ -61,12 +107,40 @@ impl DurableEngineBuilder {
     /// - Uses in-memory persistence (not persisted across restarts)
     /// - Uses in-memory cache (not persisted across restarts)
     /// - Event buffer size of 1000
+    /// - Tasks see none of the process environment
+    /// - Secrets are read from the process environment
     #[must_use]
     pub fn new() -> Self {
         Self {
             persistence: None,
+            read_persistence: None,
             cache: None,
+            cache_ttl: None,
+            lease_ttl: DEFAULT_LEASE_TTL,
             event_buffer_size: 1000,
+            base_dir: None,
+            middleware: Vec::new(),
+            env_policy: EnvPolicy::default(),
+            secrets: None,
+            container_defaults: ContainerOptions::default(),
+            container_pool: WarmPool::default(),
+            container_runtime: ContainerRuntime::default(),
+            event_sinks: Vec::new(),
+            fork_max_parallelism: None,
+            for_parallelism: None,
+            number_mode: NumberMode::default(),
+            mock_routes: MockRoutes::default(),
+            watch_exprs: Vec::new(),
+            manifest_config: None,
+            shadows: Vec::new(),
+            listener_auth: None,
+            catalog_dir: None,
+            workflow_sources: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            verifier: SignatureVerifier::default(),
+            strict_env: false,
+            telemetry: None,
+            snapshot: None,
         }
     }
 
@@ -96,6 +170,40 @@ impl DurableEngineBuilder {
         self
     }
 
+    /// Serve instance listings and status queries from another provider
+    ///
+    /// Typically a read replica of the persistence store, so reporting never
+    /// contends with the event appends of running instances. The replica may
+    /// lag behind: the engine itself reads what it needs to run instances
+    /// from the persistence provider. Defaults to the persistence provider.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "postgres")]
+    /// # {
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::providers::persistence::PostgresPersistence;
+    /// use std::sync::Arc;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let primary = PostgresPersistence::new("postgresql://jackdaw@primary/jackdaw").await?;
+    /// let replica = PostgresPersistence::replica("postgresql://jackdaw@replica/jackdaw").await?;
+    ///
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_persistence(Arc::new(primary))
+    ///     .with_read_persistence(Arc::new(replica))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_read_persistence(mut self, persistence: Arc<dyn PersistenceProvider>) -> Self {
+        self.read_persistence = Some(persistence);
+        self
+    }
+
     /// Set the cache provider
     ///
     /// If not set, an in-memory cache provider will be used by default.
@@ -122,6 +230,56 @@ impl DurableEngineBuilder {
         self
     }
 
+    /// Stop serving cached task results `ttl` after they were written
+    ///
+    /// Tasks can choose their own TTL with `metadata.cacheTtl`, an ISO 8601
+    /// duration. Without this setting, results of other tasks never expire.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_cache_ttl(Duration::from_secs(3600))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Let other processes take over an instance `ttl` after this engine stops
+    /// renewing its lease
+    ///
+    /// The engine renews the lease of each instance it runs three times per
+    /// TTL. If the process dies, other processes sharing its persistence can
+    /// resume the instance once the TTL has passed. Defaults to 30 seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_lease_ttl(Duration::from_secs(10))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_lease_ttl(mut self, ttl: Duration) -> Self {
+        self.lease_ttl = ttl;
+        self
+    }
+
     /// Set the event buffer size for streaming execution
     ///
     /// This controls how many events can be buffered before backpressure is applied.
@@ -148,6 +306,610 @@ impl DurableEngineBuilder {
         self
     }
 
+    /// Set the directory used to resolve relative resource paths
+    ///
+    /// By default, relative script sources, catalogs, proto files and `OpenAPI`
+    /// schemas are resolved against the directory of the workflow file registered
+    /// with [`DurableEngine::set_workflow_path`]. Setting a base directory
+    /// overrides that for every workflow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_base_dir("./workflows")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
+    /// Add a middleware consulted before instances start and tasks execute
+    ///
+    /// Middleware runs in the order it is added; the first to refuse an
+    /// instance or task fails it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::middleware::PolicyMiddleware;
+    /// use std::sync::Arc;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let policies = PolicyMiddleware::load("./policies.yaml".as_ref())?;
+    ///
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_middleware(Arc::new(policies))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_middleware(mut self, middleware: Arc<dyn TaskMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Set which variables of the process environment tasks may see
+    ///
+    /// Script, shell and container tasks receive only the variables allowed by
+    /// this policy (none by default), their own `environment`, and the secrets
+    /// they request. See [`crate::task_env`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::task_env::EnvPolicy;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let policy = EnvPolicy::new(
+    ///     vec!["PATH".to_string(), "AWS_*".to_string()],
+    ///     vec!["AWS_SECRET_ACCESS_KEY".to_string()],
+    /// );
+    ///
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_env_policy(policy)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_env_policy(mut self, policy: EnvPolicy) -> Self {
+        self.env_policy = policy;
+        self
+    }
+
+    /// Set the provider that resolves the secrets workflows declare in
+    /// `use.secrets` and tasks request with `metadata.env.fromSecret`
+    ///
+    /// If not set, secrets are read from the process environment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::providers::secrets::EnvSecretsProvider;
+    /// use std::sync::Arc;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_secrets(Arc::new(EnvSecretsProvider::with_prefix("JACKDAW_SECRET_")))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_secrets(mut self, secrets: Arc<dyn SecretsProvider>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    /// Set the defaults for container tasks
+    ///
+    /// Container tasks use these settings unless they set their own in
+    /// `metadata.container`. See [`ContainerOptions`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::container::ContainerOptions;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_container_defaults(ContainerOptions {
+    ///         network: Some("none".to_string()),
+    ///         memory: Some("512m".to_string()),
+    ///         ..ContainerOptions::default()
+    ///     })
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_container_defaults(mut self, defaults: ContainerOptions) -> Self {
+        self.container_defaults = defaults;
+        self
+    }
+
+    /// Set the size and idle TTL of the warm container pool
+    ///
+    /// Container tasks with `keepWarm` set in `metadata.container` run in a
+    /// container kept alive between executions instead of a new one. At most
+    /// `max_size` idle containers are kept (4 by default), each for at most
+    /// `idle_ttl` (5 minutes by default). Call [`DurableEngine::shutdown`] or
+    /// [`DurableEngine::drain_container_pool`] before the engine is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use std::time::Duration;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_container_pool(8, Duration::from_secs(60))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_container_pool(mut self, max_size: usize, idle_ttl: Duration) -> Self {
+        self.container_pool = WarmPool::new(max_size, idle_ttl);
+        self
+    }
+
+    /// Choose the container runtime that runs container tasks
+    ///
+    /// Docker is used by default. Podman is reached through its
+    /// Docker-compatible API socket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::container::ContainerRuntime;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_container_provider(ContainerRuntime::Podman)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_container_provider(mut self, runtime: ContainerRuntime) -> Self {
+        self.container_runtime = runtime;
+        self
+    }
+
+    /// Add a destination for the events published by `emit` tasks
+    ///
+    /// Every emitted event is published to each sink, in the order they are
+    /// added. Without sinks, events are only recorded in the task output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::providers::events::HttpSink;
+    /// use std::sync::Arc;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_event_sink(Arc::new(HttpSink::new("https://events.example.com/ingest")))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.event_sinks.push(sink);
+        self
+    }
+
+    /// Limit how many fork branches run at once across the engine
+    ///
+    /// Branches of every fork, in every instance, share `limit` permits and
+    /// wait for one before they start, so a fork with hundreds of branches
+    /// does not make hundreds of calls at once. A fork can set a lower limit
+    /// of its own with `metadata.fork.maxParallelism`. Branches of forks
+    /// nested in a branch run under the permit of that branch. Without a
+    /// limit, all branches of a fork start together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_fork_max_parallelism(16)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_fork_max_parallelism(mut self, limit: usize) -> Self {
+        self.fork_max_parallelism = Some(limit.max(1));
+        self
+    }
+
+    /// Number of iterations run at once by `for` loops that run in parallel
+    ///
+    /// Applies to loops that set `parallel: true` in `metadata.for` without a
+    /// `maxParallelism` of their own; without this setting they run 4 at once.
+    /// Loops that do not set `parallel` run their iterations one after
+    /// another whatever this is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_for_parallelism(8)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_for_parallelism(mut self, workers: usize) -> Self {
+        self.for_parallelism = Some(workers.max(1));
+        self
+    }
+
+    /// Hold the numbers of workflow data in `mode`
+    ///
+    /// In [`NumberMode::Decimal`], numbers keep the digits they were written
+    /// with through task outputs, expressions that pass them on, cache keys
+    /// and persistence, instead of being rounded to `f64`. Arithmetic in
+    /// expressions is still computed on `f64`. Building the engine fails in
+    /// that mode unless jackdaw is built with the `arbitrary-precision` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::numbers::NumberMode;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_number_mode(NumberMode::Decimal)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_number_mode(mut self, mode: NumberMode) -> Self {
+        self.number_mode = mode;
+        self
+    }
+
+    /// Send HTTP and OpenAPI calls to `base_url` to a mock server at `target`
+    ///
+    /// Calls to URLs starting with `base_url` go to `target` instead, keeping
+    /// the rest of the URL, so workflows can run against a
+    /// [`MockApi`](crate::mock::MockApi) without reaching the real API.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_mock_route("https://api.example.com/v1", "http://127.0.0.1:8081/v1")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_mock_route(
+        mut self,
+        base_url: impl Into<String>,
+        target: impl Into<String>,
+    ) -> Self {
+        self.mock_routes.add(base_url, target);
+        self
+    }
+
+    /// Evaluate `expr` against the workflow data after every task completes
+    ///
+    /// The values of watch expressions are printed as tasks complete and are
+    /// recorded in the instance history, so the progress of long workflows
+    /// can be followed without dumping whole contexts. Bare jq (`.order.status`)
+    /// and wrapped runtime expressions (`${ .order.status }`) are accepted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_watch_expr(".order.status")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_watch_expr(mut self, expr: impl Into<String>) -> Self {
+        self.watch_exprs.push(expr.into());
+        self
+    }
+
+    /// Record a run manifest when each instance starts
+    ///
+    /// The manifest holds digests of the workflow definition, the input and
+    /// the documents and sub-workflows the tasks reference, the engine
+    /// version, and `config`, the resolved configuration the engine runs
+    /// with. Read it back with
+    /// [`DurableEngine::manifest`](crate::durableengine::DurableEngine::manifest).
+    /// Remote documents are fetched when instances start.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use serde_json::json;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_run_manifests(json!({ "cache_ttl": "PT1H" }))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_run_manifests(mut self, config: serde_json::Value) -> Self {
+        self.manifest_config = Some(config);
+        self
+    }
+
+    /// Run `candidate` in the shadow of the workflow it replaces
+    ///
+    /// Once an instance of the workflow with the candidate's namespace and
+    /// name completes, the candidate runs with the same input in a sandbox
+    /// engine, and where its output differs is recorded in a
+    /// `ShadowCompared` event of the instance. `mode` decides whether the
+    /// candidate's tasks with side effects take the instance's results or
+    /// run against mock servers. Shadow runs go on in the background after
+    /// the instance has returned, and never fail it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::durableengine::ShadowMode;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let candidate = serde_yaml::from_str(&std::fs::read_to_string("order-v2.yaml")?)?;
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_shadow(candidate, ShadowMode::Stub)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_shadow(mut self, candidate: WorkflowDefinition, mode: ShadowMode) -> Self {
+        self.shadows.push((candidate, mode));
+        self
+    }
+
+    /// Authenticate the requests HTTP listeners receive with `auth`
+    ///
+    /// Applies to the listeners of listen tasks whose source sets no
+    /// `authentication` of its own; without it, such listeners accept any
+    /// request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::listeners::ListenerAuth;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_listener_auth(ListenerAuth::Jwt {
+    ///         jwks_uri: "https://idp.example.com/.well-known/jwks.json".to_string(),
+    ///         issuer: Some("https://idp.example.com".to_string()),
+    ///         audience: Some("orders".to_string()),
+    ///     })
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_listener_auth(mut self, auth: ListenerAuth) -> Self {
+        self.listener_auth = Some(auth);
+        self
+    }
+
+    /// Set the directory git catalogs are cloned into
+    ///
+    /// A catalog in `use.catalogs` whose URI is a git repository is cloned
+    /// once, into a directory under this one, and its functions are read from
+    /// the clone from then on. Defaults to `jackdaw-catalogs` under the
+    /// system's temporary directory; remove a clone to fetch the catalog again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_catalog_dir("/var/cache/jackdaw/catalogs")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_catalog_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.catalog_dir = Some(dir.into());
+        self
+    }
+
+    /// Look up sub-workflows that are not registered in `source`
+    ///
+    /// A `run.workflow` task whose workflow is not registered with
+    /// [`DurableEngine::register_workflow`](crate::durableengine::DurableEngine::register_workflow)
+    /// finds it in the first source that has it, and the workflow is
+    /// registered from then on. A source is a directory or file of workflows,
+    /// scanned the first time a workflow is missing, a git repository with
+    /// workflows under `workflows/` (any catalog URI the engine clones, such
+    /// as `git+https://github.com/acme/workflows.git#main`), or an HTTP server
+    /// publishing `workflows/{namespace}/{name}/{version}/workflow.yaml`.
+    /// Sources are searched in the order they are added; may be called more
+    /// than once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_workflow_source("workflows/")
+    ///     .with_workflow_source("https://workflows.example.com")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_workflow_source(mut self, source: impl Into<String>) -> Self {
+        self.workflow_sources.push(source.into());
+        self
+    }
+
+    /// Set how deep workflows may call each other with `run.workflow`
+    ///
+    /// An instance started by a `run.workflow` task nested `depth` calls
+    /// below the instance that was started directly fails when `depth`
+    /// exceeds this limit, naming the cycle if a workflow ends up calling
+    /// itself. Defaults to [`DEFAULT_MAX_CALL_DEPTH`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_max_call_depth(4)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = depth;
+        self
+    }
+
+    /// Check the sub-workflows and catalog functions the engine loads itself
+    /// against a signature policy
+    ///
+    /// Workflows found in workflow sources and the manifests of catalog
+    /// functions are verified against the signature next to them, `<file>.sig`
+    /// or `<url>.sig`, before they run. By default signatures are not checked.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::signing::{SignaturePolicy, SignatureVerifier, TrustedKey};
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let key = TrustedKey::load("cosign.pub".as_ref())?;
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_signature_verifier(SignatureVerifier::new(SignaturePolicy::Enforce, vec![key])?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_signature_verifier(mut self, verifier: SignatureVerifier) -> Self {
+        self.verifier = verifier;
+        self
+    }
+
+    /// Fail on `${{ env.X }}` placeholders of unset variables in the
+    /// sub-workflows and catalog functions the engine loads itself
+    ///
+    /// Placeholders are substituted as in the workflow files the CLI loads;
+    /// by default unset variables are left as empty strings.
+    #[must_use]
+    pub fn with_strict_env(mut self, strict: bool) -> Self {
+        self.strict_env = strict;
+        self
+    }
+
+    /// Collect metrics of the workflows the engine runs in `telemetry`
+    ///
+    /// The telemetry observes every event the engine records and every
+    /// request the listeners of listen tasks handle.
+    /// [`DurableEngine::metrics`](crate::durableengine::DurableEngine::metrics)
+    /// renders them in the Prometheus text format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::telemetry::Telemetry;
+    /// use std::sync::Arc;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_telemetry(Arc::new(Telemetry::new()))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Start the engine from `snapshot`, taken by
+    /// [`DurableEngine::snapshot`](crate::durableengine::DurableEngine::snapshot)
+    ///
+    /// The engine registers the snapshot's workflows, and reuses its
+    /// preprocessed expressions, `OpenAPI` documents, compiled proto files and
+    /// executor health instead of setting them up again.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use jackdaw::DurableEngineBuilder;
+    /// use jackdaw::durableengine::EngineSnapshot;
+    /// use std::path::Path;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new()
+    ///     .with_snapshot(EngineSnapshot::read(Path::new("engine.snapshot.json"))?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_snapshot(mut self, snapshot: EngineSnapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
     /// Build the engine
     ///
     /// This creates the [`DurableEngine`](crate::durableengine::DurableEngine) with
@@ -155,7 +917,8 @@ impl DurableEngineBuilder {
     /// in-memory defaults will be used.
     ///
     /// # Errors
-    /// Returns an error if the engine cannot be initialized.
+    /// Returns an error if the engine cannot be initialized, e.g. from a
+    /// snapshot taken by another version of jackdaw.
     ///
     /// # Examples
     ///
@@ -173,9 +936,58 @@ impl DurableEngineBuilder {
             .persistence
             .unwrap_or_else(|| Arc::new(InMemoryPersistence::new()));
 
+        // The rollups of each workflow count runs as they start and end
+        let persistence: Arc<dyn PersistenceProvider> = Arc::new(ObservedPersistence::new(
+            Arc::clone(&persistence),
+            Arc::new(RunRollups::new(persistence)),
+        ));
+
+        // Telemetry observes every event the engine records
+        let persistence: Arc<dyn PersistenceProvider> = match &self.telemetry {
+            Some(telemetry) => Arc::new(ObservedPersistence::new(
+                persistence,
+                Arc::clone(telemetry) as Arc<dyn WorkflowObserver>,
+            )),
+            None => persistence,
+        };
+
         let cache = self.cache.unwrap_or_else(|| Arc::new(InMemoryCache::new()));
 
-        DurableEngine::new_with_config(persistence, cache, self.event_buffer_size)
+        let secrets = self
+            .secrets
+            .unwrap_or_else(|| Arc::new(EnvSecretsProvider::new()));
+
+        DurableEngine::new_with_config(EngineConfig {
+            persistence,
+            cache,
+            event_buffer_size: self.event_buffer_size,
+            base_dir: self.base_dir,
+            middleware: self.middleware,
+            env_policy: self.env_policy,
+            secrets,
+            container_defaults: self.container_defaults,
+            container_pool: self.container_pool,
+            container_runtime: self.container_runtime,
+            event_sinks: self.event_sinks,
+            fork_max_parallelism: self.fork_max_parallelism,
+            for_parallelism: self.for_parallelism,
+            number_mode: self.number_mode,
+            mock_routes: self.mock_routes,
+            cache_ttl: self.cache_ttl,
+            lease_ttl: self.lease_ttl,
+            reads: self.read_persistence,
+            watch_exprs: self.watch_exprs,
+            manifest_config: self.manifest_config,
+            shadows: self.shadows,
+            listener_auth: self.listener_auth,
+            catalog_dir: self.catalog_dir,
+            workflow_sources: self.workflow_sources,
+            max_call_depth: self.max_call_depth,
+            verifier: self.verifier,
+            strict_env: self.strict_env,
+            telemetry: self.telemetry,
+            snapshot: self.snapshot,
+        })
     }
 }
 
@@ -0,0 +1,561 @@
+//! Self-contained workflow bundles for offline execution
+//!
+//! A bundle is a tar archive holding a workflow, every sub-workflow it runs and
+//! every external document they reference (script sources, proto files,
+//! `OpenAPI` documents and WebAssembly modules). References are rewritten to
+//! point at the bundled copies, which resolve relative to the extracted
+//! workflow file, so a bundle runs without network or filesystem access beyond
+//! the archive itself.
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use sha2::{Digest, Sha256};
+use snafu::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::durableengine::resources::{PathAnchor, resolve_path};
+use crate::interpolation::interpolate_text;
+
+/// Name of the manifest file at the root of every bundle
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Bundle layout version written to, and required in, the manifest
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// File name of the entry workflow inside a bundle
+const ENTRY_WORKFLOW: &str = "workflow.sw.yaml";
+
+/// Directory holding bundled resources inside a bundle
+const RESOURCES_DIR: &str = "resources";
+
+/// Keys whose `endpoint` points at a document that is bundled
+const RESOURCE_KEYS: &[&str] = &["source", "document", "proto", "resource", "module"];
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Failed to read workflow '{}': {source}", path.display()))]
+    ReadWorkflow {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to parse workflow '{}': {source}", path.display()))]
+    ParseWorkflow {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[snafu(display("Workflow '{}' has no document.{field}", path.display()))]
+    MissingDocumentField { path: PathBuf, field: String },
+
+    #[snafu(display("Interpolation error: {source}"))]
+    Interpolation { source: crate::interpolation::Error },
+
+    #[snafu(display("Failed to read {resource}: {source}"))]
+    ReadResource {
+        resource: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to fetch '{url}': {message}"))]
+    FetchResource { url: String, message: String },
+
+    #[snafu(display(
+        "Sub-workflow '{key}' referenced by '{}' was not found in the registry",
+        path.display()
+    ))]
+    MissingSubworkflow { key: String, path: PathBuf },
+
+    #[snafu(display("Failed to serialize workflow: {source}"))]
+    Serialize { source: serde_yaml::Error },
+
+    #[snafu(display("Bundle archive error for '{}': {source}", path.display()))]
+    Archive {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Invalid bundle manifest: {source}"))]
+    Manifest { source: serde_json::Error },
+
+    #[snafu(display(
+        "Unsupported bundle format version {version} (expected {BUNDLE_FORMAT_VERSION})"
+    ))]
+    UnsupportedVersion { version: u32 },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::interpolation::Error> for Error {
+    fn from(source: crate::interpolation::Error) -> Self {
+        Error::Interpolation { source }
+    }
+}
+
+/// Describes the contents of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// Bundle layout version
+    pub format_version: u32,
+
+    /// Path of the entry workflow inside the bundle
+    pub workflow: String,
+
+    /// Paths of the bundled sub-workflows inside the bundle
+    pub subworkflows: Vec<String>,
+
+    /// Resource references as written in the workflows, mapped to their bundled paths
+    pub resources: BTreeMap<String, String>,
+}
+
+/// Where a bundle was loaded from, recorded in the metadata of the instances it runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Path of the bundle archive, or the `oci://` reference it was pulled from
+    pub source: String,
+
+    /// Digest of the bundle archive, or of the artifact manifest for OCI references
+    pub digest: String,
+
+    /// Trusted identity whose signature over the bundle was verified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+}
+
+/// A bundle unpacked into a temporary directory
+///
+/// The directory is removed when this value is dropped.
+#[derive(Debug)]
+pub struct ExtractedBundle {
+    dir: tempfile::TempDir,
+    pub manifest: BundleManifest,
+    pub provenance: Provenance,
+}
+
+impl ExtractedBundle {
+    /// Path of the extracted entry workflow
+    #[must_use]
+    pub fn workflow_path(&self) -> PathBuf {
+        self.dir.path().join(&self.manifest.workflow)
+    }
+
+    /// Paths of the extracted sub-workflows
+    #[must_use]
+    pub fn subworkflow_paths(&self) -> Vec<PathBuf> {
+        self.manifest
+            .subworkflows
+            .iter()
+            .map(|name| self.dir.path().join(name))
+            .collect()
+    }
+}
+
+/// The `sha256:<hex>` digest of `data`, as used by OCI registries
+#[must_use]
+pub fn content_digest(data: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(data))
+}
+
+/// Whether `path` looks like a bundle archive
+#[must_use]
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext == "tar")
+}
+
+/// Package a workflow, its sub-workflows and their external resources into a tar archive
+///
+/// Sub-workflows referenced by `run.workflow` are looked up in `registry`.
+/// `${{ env.NAME }}` placeholders are only expanded to locate resources; the
+/// bundled workflows keep them so they are interpolated again at run time.
+///
+/// # Errors
+///
+/// Returns an error if a workflow cannot be read or parsed, a resource cannot
+/// be fetched, a sub-workflow is missing from the registry, or the archive
+/// cannot be written.
+pub async fn create_bundle(
+    workflow_path: &Path,
+    registry: &[PathBuf],
+    output: &Path,
+    strict_env: bool,
+) -> Result<BundleManifest> {
+    let registry_index = index_registry(registry, strict_env)?;
+
+    let mut manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        workflow: ENTRY_WORKFLOW.to_string(),
+        subworkflows: Vec::new(),
+        resources: BTreeMap::new(),
+    };
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut bundled: HashMap<String, String> = HashMap::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut queue: VecDeque<(PathBuf, String)> = VecDeque::new();
+    queue.push_back((workflow_path.to_path_buf(), ENTRY_WORKFLOW.to_string()));
+
+    while let Some((path, bundle_name)) = queue.pop_front() {
+        let mut doc = load_document(&path)?;
+        seen.insert(document_key(&doc, &path, strict_env)?);
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut references = Vec::new();
+        for_each_resource_ref(&mut doc, None, &mut |uri| references.push(uri.clone()));
+
+        let mut rewrites: HashMap<String, String> = HashMap::new();
+        for raw in references {
+            let uri = interpolate_text(&raw, strict_env)?;
+            if uri.trim_start().starts_with("${") {
+                tracing::warn!(
+                    "Not bundling '{}' in '{}': runtime expressions cannot be resolved ahead of time",
+                    raw,
+                    path.display()
+                );
+                continue;
+            }
+
+            let (location, data) = fetch_resource(&uri, dir).await?;
+            let bundled_path = if let Some(existing) = bundled.get(&location) {
+                existing.clone()
+            } else {
+                let name = bundled_resource_name(&location);
+                entries.push((name.clone(), data));
+                bundled.insert(location, name.clone());
+                name
+            };
+            manifest.resources.insert(uri, bundled_path.clone());
+            rewrites.insert(raw, bundled_path);
+        }
+        for_each_resource_ref(&mut doc, None, &mut |uri| {
+            if let Some(bundled_path) = rewrites.get(uri.as_str()) {
+                uri.clone_from(bundled_path);
+            }
+        });
+
+        let mut subworkflows = Vec::new();
+        collect_subworkflow_keys(&doc, None, strict_env, &mut subworkflows)?;
+        for key in subworkflows {
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            let sub_path = registry_index
+                .get(&key)
+                .context(MissingSubworkflowSnafu {
+                    key: key.clone(),
+                    path: path.clone(),
+                })?
+                .clone();
+            let name = format!("{}.sw.yaml", key.replace('/', "-"));
+            manifest.subworkflows.push(name.clone());
+            queue.push_back((sub_path, name));
+        }
+
+        let yaml = serde_yaml::to_string(&doc).context(SerializeSnafu)?;
+        entries.push((bundle_name, yaml.into_bytes()));
+    }
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest).context(ManifestSnafu)?;
+    entries.push((MANIFEST_FILE.to_string(), manifest_json));
+    write_archive(output, &entries)?;
+
+    Ok(manifest)
+}
+
+/// Unpack a bundle into a temporary directory
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be read or its manifest is missing,
+/// malformed, or from an unsupported format version.
+pub fn extract_bundle(path: &Path) -> Result<ExtractedBundle> {
+    let archive = std::fs::read(path).context(ArchiveSnafu { path })?;
+    let dir = tempfile::tempdir().context(ArchiveSnafu { path })?;
+    tar::Archive::new(archive.as_slice())
+        .unpack(dir.path())
+        .context(ArchiveSnafu { path })?;
+
+    let manifest_json =
+        std::fs::read_to_string(dir.path().join(MANIFEST_FILE)).context(ArchiveSnafu { path })?;
+    let manifest: BundleManifest = serde_json::from_str(&manifest_json).context(ManifestSnafu)?;
+    ensure!(
+        manifest.format_version == BUNDLE_FORMAT_VERSION,
+        UnsupportedVersionSnafu {
+            version: manifest.format_version
+        }
+    );
+
+    let provenance = Provenance {
+        source: path.display().to_string(),
+        digest: content_digest(&archive),
+        signer: None,
+    };
+    Ok(ExtractedBundle {
+        dir,
+        manifest,
+        provenance,
+    })
+}
+
+/// Map "namespace/name/version" keys to the registry files defining them
+fn index_registry(registry: &[PathBuf], strict_env: bool) -> Result<HashMap<String, PathBuf>> {
+    let mut index = HashMap::new();
+    for path in registry {
+        let doc = load_document(path)?;
+        index.insert(document_key(&doc, path, strict_env)?, path.clone());
+    }
+    Ok(index)
+}
+
+fn load_document(path: &Path) -> Result<Value> {
+    let yaml = std::fs::read_to_string(path).context(ReadWorkflowSnafu { path })?;
+    serde_yaml::from_str(&yaml).context(ParseWorkflowSnafu { path })
+}
+
+/// The "namespace/name/version" key of a workflow document
+fn document_key(doc: &Value, path: &Path, strict_env: bool) -> Result<String> {
+    let document = doc.get("document");
+    let mut parts = Vec::with_capacity(3);
+    for field in ["namespace", "name", "version"] {
+        let value = document
+            .and_then(|d| d.get(field))
+            .and_then(scalar_string)
+            .context(MissingDocumentFieldSnafu { path, field })?;
+        parts.push(interpolate_text(&value, strict_env)?);
+    }
+    Ok(parts.join("/"))
+}
+
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Null
+        | Value::Bool(_)
+        | Value::Sequence(_)
+        | Value::Mapping(_)
+        | Value::Tagged(_) => None,
+    }
+}
+
+/// Call `f` with every bundleable resource URI in a workflow document
+fn for_each_resource_ref(value: &mut Value, parent: Option<&str>, f: &mut dyn FnMut(&mut String)) {
+    match value {
+        Value::Mapping(map) => {
+            let is_resource = parent.is_some_and(|key| RESOURCE_KEYS.contains(&key));
+            for (key, child) in map.iter_mut() {
+                let key = key.as_str();
+                if is_resource && key == Some("endpoint") {
+                    if let Some(uri) = endpoint_uri_mut(child) {
+                        f(uri);
+                    }
+                } else {
+                    for_each_resource_ref(child, key, f);
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                for_each_resource_ref(item, None, f);
+            }
+        }
+        Value::Tagged(tagged) => for_each_resource_ref(&mut tagged.value, parent, f),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+/// The URI of an endpoint given either as a string or as `{ uri: ... }`
+fn endpoint_uri_mut(endpoint: &mut Value) -> Option<&mut String> {
+    match endpoint {
+        Value::String(uri) => Some(uri),
+        Value::Mapping(map) => match map.get_mut("uri") {
+            Some(Value::String(uri)) => Some(uri),
+            Some(_) | None => None,
+        },
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::Sequence(_) | Value::Tagged(_) => {
+            None
+        }
+    }
+}
+
+/// Collect the keys of workflows started by `run.workflow` tasks
+fn collect_subworkflow_keys(
+    value: &Value,
+    parent: Option<&str>,
+    strict_env: bool,
+    keys: &mut Vec<String>,
+) -> Result<()> {
+    match value {
+        Value::Mapping(map) => {
+            for (key, child) in map {
+                let key = key.as_str();
+                if parent == Some("run")
+                    && key == Some("workflow")
+                    && let Value::Mapping(reference) = child
+                {
+                    if let Some(workflow_key) = subworkflow_key(reference, strict_env)? {
+                        keys.push(workflow_key);
+                    }
+                } else {
+                    collect_subworkflow_keys(child, key, strict_env, keys)?;
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                collect_subworkflow_keys(item, None, strict_env, keys)?;
+            }
+        }
+        Value::Tagged(tagged) => collect_subworkflow_keys(&tagged.value, parent, strict_env, keys)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+    Ok(())
+}
+
+fn subworkflow_key(reference: &Mapping, strict_env: bool) -> Result<Option<String>> {
+    let mut parts = Vec::with_capacity(3);
+    for field in ["namespace", "name", "version"] {
+        let Some(value) = reference.get(field).and_then(scalar_string) else {
+            return Ok(None);
+        };
+        parts.push(interpolate_text(&value, strict_env)?);
+    }
+    Ok(Some(parts.join("/")))
+}
+
+/// Read a resource, returning a stable location string and its contents
+async fn fetch_resource(uri: &str, workflow_dir: &Path) -> Result<(String, Vec<u8>)> {
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        let response = reqwest::get(uri).await.map_err(|e| Error::FetchResource {
+            url: uri.to_string(),
+            message: e.to_string(),
+        })?;
+        if !response.status().is_success() {
+            return Err(Error::FetchResource {
+                url: uri.to_string(),
+                message: format!("HTTP {}", response.status()),
+            });
+        }
+        let data = response.bytes().await.map_err(|e| Error::FetchResource {
+            url: uri.to_string(),
+            message: e.to_string(),
+        })?;
+        return Ok((uri.to_string(), data.to_vec()));
+    }
+
+    let resolved = resolve_path(
+        uri,
+        Some(PathAnchor::WorkflowDir(workflow_dir.to_path_buf())),
+    );
+    let data = std::fs::read(&resolved.path).context(ReadResourceSnafu {
+        resource: resolved.to_string(),
+    })?;
+    let location = std::fs::canonicalize(&resolved.path)
+        .unwrap_or(resolved.path)
+        .to_string_lossy()
+        .into_owned();
+    Ok((location, data))
+}
+
+/// Path inside the bundle for a resource, unique per location
+fn bundled_resource_name(location: &str) -> String {
+    let file_name = location
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit(['/', '\\']).next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("resource");
+    let digest = format!("{:x}", Sha256::digest(location.as_bytes()));
+    let prefix = digest.get(..12).unwrap_or(&digest);
+    format!("{RESOURCES_DIR}/{prefix}-{file_name}")
+}
+
+fn write_archive(output: &Path, entries: &[(String, Vec<u8>)]) -> Result<()> {
+    let file = std::fs::File::create(output).context(ArchiveSnafu { path: output })?;
+    let mut builder = tar::Builder::new(file);
+    for (name, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, data.as_slice())
+            .context(ArchiveSnafu { path: output })?;
+    }
+    builder.finish().context(ArchiveSnafu { path: output })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    const WORKFLOW: &str = r"
+document:
+  dsl: '1.0.2'
+  namespace: test
+  name: parent
+  version: '1.0.0'
+do:
+  - script:
+      run:
+        script:
+          language: python
+          source:
+            endpoint: file://./scripts/task.py
+  - child:
+      run:
+        workflow:
+          namespace: test
+          name: child
+          version: '1.0.0'
+  - inline:
+      run:
+        script:
+          language: python
+          code: print('hi')
+";
+
+    #[test]
+    fn test_collects_resource_references() {
+        let mut doc: Value = serde_yaml::from_str(WORKFLOW).unwrap();
+        let mut references = Vec::new();
+        for_each_resource_ref(&mut doc, None, &mut |uri| references.push(uri.clone()));
+        assert_eq!(references, vec!["file://./scripts/task.py"]);
+    }
+
+    #[test]
+    fn test_rewrites_resource_references() {
+        let mut doc: Value = serde_yaml::from_str(WORKFLOW).unwrap();
+        for_each_resource_ref(&mut doc, None, &mut |uri| {
+            *uri = "resources/abc-task.py".to_string();
+        });
+        let yaml = serde_yaml::to_string(&doc).unwrap();
+        assert!(yaml.contains("endpoint: resources/abc-task.py"), "{yaml}");
+    }
+
+    #[test]
+    fn test_collects_subworkflow_keys() {
+        let doc: Value = serde_yaml::from_str(WORKFLOW).unwrap();
+        let mut keys = Vec::new();
+        collect_subworkflow_keys(&doc, None, false, &mut keys).unwrap();
+        assert_eq!(keys, vec!["test/child/1.0.0"]);
+        assert_eq!(
+            document_key(&doc, Path::new("parent.sw.yaml"), false).unwrap(),
+            "test/parent/1.0.0"
+        );
+    }
+
+    #[test]
+    fn test_bundled_resource_name() {
+        let name = bundled_resource_name("https://example.com/specs/api.yaml?v=2");
+        assert!(name.starts_with("resources/"), "{name}");
+        assert!(name.ends_with("-api.yaml"), "{name}");
+        assert_ne!(name, bundled_resource_name("/other/specs/api.yaml"));
+    }
+}
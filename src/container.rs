@@ -1,5 +1,16 @@
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::context::Reaper;
+use crate::task_output::TaskOutputStreamer;
+
+/// How long services started alongside a task may take to become ready, when
+/// no timeout is configured
+pub const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// Container execution result
 #[derive(Debug, Clone)]
@@ -13,7 +24,7 @@ pub struct ContainerResult {
 }
 
 /// Container execution configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ContainerConfig {
     /// Container image name
     pub image: String,
@@ -29,6 +40,371 @@ pub struct ContainerConfig {
     pub volumes: Option<std::collections::HashMap<String, String>>,
     /// Port mappings (container_port -> host_port)
     pub ports: Option<std::collections::HashMap<u16, u16>>,
+    /// User (and optionally group) the command runs as, e.g. `1000` or `1000:1000`
+    pub user: Option<String>,
+    /// Network mode, e.g. `bridge`, `host`, `none` or a network name
+    pub network_mode: Option<String>,
+    /// CPU limit, in CPUs
+    pub cpus: Option<f64>,
+    /// Memory limit, in bytes
+    pub memory: Option<i64>,
+    /// Maximum time to wait for the image to be pulled
+    pub pull_timeout: Option<Duration>,
+    /// Run the command in a container kept running between executions, if the
+    /// provider has a warm pool
+    pub keep_warm: bool,
+    /// Use the local image without pulling it, e.g. because it was just built
+    pub skip_pull: bool,
+    /// Stops and removes the container when it fires, e.g. because the task
+    /// was cancelled or timed out
+    pub cancellation: CancellationToken,
+    /// Records the containers removed because `cancellation` fired
+    pub reaper: Reaper,
+}
+
+/// Runtime settings of a container task, read from `metadata.container`
+///
+/// The container process definition of the DSL only describes the image and
+/// command, so settings such as the working directory or resource limits are
+/// given in the task metadata:
+///
+/// ```yaml
+/// - build:
+///     run:
+///       container:
+///         image: rust:1
+///         command: cargo build
+///     metadata:
+///       container:
+///         workingDir: /src
+///         user: "1000"
+///         group: "1000"
+///         network: none
+///         cpus: 2
+///         memory: 1g
+///         pullTimeout: PT5M
+///         keepWarm: true
+///         build:
+///           context: ./builder
+///           dockerfile: Dockerfile
+///           args: { RUST_VERSION: "1.85" }
+///         compose: ./compose.yaml
+///         services:
+///           db:
+///             image: postgres:16
+///             environment: { POSTGRES_PASSWORD: secret }
+///             ready: pg_isready -U postgres
+///         readyTimeout: PT2M
+/// ```
+///
+/// Settings a task leaves out fall back to the engine defaults.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ContainerOptions {
+    /// Working directory of the command
+    pub working_dir: Option<String>,
+    /// User name or UID the command runs as
+    pub user: Option<String>,
+    /// Group name or GID the command runs as; requires `user`
+    pub group: Option<String>,
+    /// Network mode, e.g. `bridge`, `host`, `none` or a network name
+    pub network: Option<String>,
+    /// CPU limit, in CPUs (fractions allowed)
+    pub cpus: Option<f64>,
+    /// Memory limit, in bytes or with a `k`, `m` or `g` suffix
+    pub memory: Option<String>,
+    /// Maximum time to wait for the image to be pulled, as an ISO 8601 duration
+    pub pull_timeout: Option<String>,
+    /// Reuse a running container from the warm pool instead of starting a new one
+    pub keep_warm: Option<bool>,
+    /// Build the task's image from a Dockerfile instead of pulling it
+    pub build: Option<ImageBuild>,
+    /// Compose file, relative to the workflow file, whose services are started
+    /// alongside the task
+    pub compose: Option<String>,
+    /// Services started alongside the task, by name; these replace services of
+    /// the compose file with the same name
+    pub services: Option<BTreeMap<String, ServiceSpec>>,
+    /// Maximum time for the services to become ready, as an ISO 8601 duration
+    pub ready_timeout: Option<String>,
+}
+
+/// A container started alongside a task and removed once the task finishes
+///
+/// The service and the task's container share a private network, on which the
+/// service is reachable by its name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ServiceSpec {
+    /// Container image name
+    pub image: String,
+    /// Arguments passed to the image's entrypoint, replacing its default command
+    pub command: Option<Vec<String>>,
+    /// Environment variables
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    /// Shell command run in the service until it succeeds, before the task starts
+    pub ready: Option<String>,
+}
+
+/// How to build a container task's image, read from `metadata.container.build`
+///
+/// The image is built from the context directory and tagged with the task's
+/// `image` before the container runs. Unchanged layers are reused from the
+/// provider's build cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ImageBuild {
+    /// Build context directory, relative to the workflow file
+    pub context: String,
+    /// Path of the Dockerfile within the context (`Dockerfile` by default)
+    pub dockerfile: Option<String>,
+    /// Build arguments
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+    /// Stage of a multi-stage build to stop at
+    pub target: Option<String>,
+}
+
+impl ContainerOptions {
+    /// Read `metadata.container` from a serialized task definition
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `metadata.container` is malformed
+    pub fn from_task(task: &serde_json::Value) -> serde_json::Result<Self> {
+        match task.pointer("/metadata/container") {
+            Some(options) => serde_json::from_value(options.clone()),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// These options, with unset fields taken from `defaults`
+    #[must_use]
+    pub fn or(self, defaults: &Self) -> Self {
+        Self {
+            working_dir: self.working_dir.or_else(|| defaults.working_dir.clone()),
+            user: self.user.or_else(|| defaults.user.clone()),
+            group: self.group.or_else(|| defaults.group.clone()),
+            network: self.network.or_else(|| defaults.network.clone()),
+            cpus: self.cpus.or(defaults.cpus),
+            memory: self.memory.or_else(|| defaults.memory.clone()),
+            pull_timeout: self.pull_timeout.or_else(|| defaults.pull_timeout.clone()),
+            keep_warm: self.keep_warm.or(defaults.keep_warm),
+            // Builds and services only make sense for the task that defines them
+            build: self.build,
+            compose: self.compose,
+            services: self.services,
+            ready_timeout: self.ready_timeout,
+        }
+    }
+
+    /// How long the task's services may take to become ready
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ready timeout is not a valid ISO 8601 duration
+    pub fn ready_timeout(&self) -> Result<Duration> {
+        self.ready_timeout
+            .as_deref()
+            .map_or(Ok(DEFAULT_READY_TIMEOUT), |timeout| {
+                parse_timeout("ready", timeout)
+            })
+    }
+
+    /// Apply these options to a container configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a group is given without a user, or if a limit or
+    /// timeout is invalid
+    pub fn apply(&self, config: &mut ContainerConfig) -> Result<()> {
+        config.user = match (&self.user, &self.group) {
+            (Some(user), Some(group)) => Some(format!("{user}:{group}")),
+            (Some(user), None) => Some(user.clone()),
+            (None, Some(_)) => {
+                return Err(Error::Options {
+                    message: "a group requires a user".to_string(),
+                });
+            }
+            (None, None) => None,
+        };
+        if let Some(cpus) = self.cpus
+            && !(cpus.is_finite() && cpus > 0.0)
+        {
+            return Err(Error::Options {
+                message: format!("CPU limit must be positive, got {cpus}"),
+            });
+        }
+        config.working_dir.clone_from(&self.working_dir);
+        config.network_mode.clone_from(&self.network);
+        config.cpus = self.cpus;
+        config.keep_warm = self.keep_warm.unwrap_or(false);
+        config.memory = self.memory.as_deref().map(parse_memory).transpose()?;
+        config.pull_timeout = self
+            .pull_timeout
+            .as_deref()
+            .map(|timeout| parse_timeout("pull", timeout))
+            .transpose()?;
+        Ok(())
+    }
+}
+
+fn parse_timeout(name: &str, timeout: &str) -> Result<Duration> {
+    crate::durableengine::timeout::parse_iso8601_duration(timeout).map_err(|e| Error::Options {
+        message: format!("invalid {name} timeout: {e}"),
+    })
+}
+
+/// The subset of a Compose file that services are read from
+#[derive(Debug, Deserialize)]
+struct ComposeFile {
+    #[serde(default)]
+    services: BTreeMap<String, ComposeService>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ComposeService {
+    image: Option<String>,
+    command: Option<ComposeCommand>,
+    environment: Option<ComposeEnvironment>,
+    healthcheck: Option<ComposeHealthcheck>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ComposeCommand {
+    Shell(String),
+    Exec(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ComposeEnvironment {
+    Map(HashMap<String, serde_yaml::Value>),
+    List(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct ComposeHealthcheck {
+    test: Option<ComposeCommand>,
+    #[serde(default)]
+    disable: bool,
+}
+
+/// Read the services of a Compose file
+///
+/// Only `image`, `command`, `environment` and `healthcheck.test` are used; the
+/// health check becomes the service's ready command. Variables without a value
+/// in `environment` are left unset rather than taken from jackdaw's environment.
+///
+/// # Errors
+///
+/// Returns an error if the file is not valid YAML, or a service has no image
+pub fn parse_compose(yaml: &str) -> Result<BTreeMap<String, ServiceSpec>> {
+    let compose: ComposeFile = serde_yaml::from_str(yaml).map_err(|e| Error::Options {
+        message: format!("invalid compose file: {e}"),
+    })?;
+
+    compose
+        .services
+        .into_iter()
+        .map(|(name, service)| {
+            let image = service.image.ok_or_else(|| Error::Options {
+                message: format!("compose service '{name}' has no image"),
+            })?;
+            let command = service.command.map(|command| match command {
+                ComposeCommand::Shell(command) => {
+                    command.split_whitespace().map(String::from).collect()
+                }
+                ComposeCommand::Exec(args) => args,
+            });
+            let environment = match service.environment {
+                Some(ComposeEnvironment::Map(map)) => map
+                    .into_iter()
+                    .filter_map(|(key, value)| {
+                        let value = match value {
+                            serde_yaml::Value::Null => return None,
+                            serde_yaml::Value::String(value) => value,
+                            other => serde_yaml::to_string(&other).ok()?.trim().to_string(),
+                        };
+                        Some((key, value))
+                    })
+                    .collect(),
+                Some(ComposeEnvironment::List(list)) => list
+                    .into_iter()
+                    .filter_map(|entry| {
+                        let (key, value) = entry.split_once('=')?;
+                        Some((key.to_string(), value.to_string()))
+                    })
+                    .collect(),
+                None => HashMap::new(),
+            };
+            let ready = service
+                .healthcheck
+                .filter(|healthcheck| !healthcheck.disable)
+                .and_then(|healthcheck| healthcheck.test)
+                .and_then(|test| match test {
+                    ComposeCommand::Shell(command) => Some(command),
+                    ComposeCommand::Exec(args) => match args.split_first() {
+                        Some((kind, rest)) if kind == "CMD-SHELL" => Some(rest.join(" ")),
+                        Some((kind, rest)) if kind == "CMD" => Some(
+                            rest.iter()
+                                .map(|arg| shell_quote(arg))
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                        ),
+                        _ => None,
+                    },
+                });
+            Ok((
+                name,
+                ServiceSpec {
+                    image,
+                    command,
+                    environment,
+                    ready,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Quote an argument for `sh -c`
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@,".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Parse a memory size such as `536870912`, `512m` or `2g` into bytes
+///
+/// # Errors
+///
+/// Returns an error if the size is not a positive number with an optional
+/// `b`, `k`, `m` or `g` suffix
+pub fn parse_memory(size: &str) -> Result<i64> {
+    let size = size.trim().to_lowercase();
+    let (digits, multiplier) = match size.char_indices().last() {
+        Some((i, 'b')) => (size.get(..i), 1),
+        Some((i, 'k')) => (size.get(..i), 1 << 10),
+        Some((i, 'm')) => (size.get(..i), 1 << 20),
+        Some((i, 'g')) => (size.get(..i), 1 << 30),
+        _ => (Some(size.as_str()), 1),
+    };
+    digits
+        .and_then(|digits| digits.parse::<i64>().ok())
+        .filter(|value| *value > 0)
+        .and_then(|value| value.checked_mul(multiplier))
+        .ok_or_else(|| Error::Options {
+            message: format!("invalid memory size '{size}', expected e.g. 512m or 2g"),
+        })
 }
 
 #[derive(Debug, Snafu)]
@@ -60,17 +436,261 @@ pub enum Error {
 
     #[snafu(display("Image pull failed: {message}"))]
     ImagePull { message: String },
+
+    #[snafu(display("Invalid container options: {message}"))]
+    Options { message: String },
+
+    #[snafu(display("Image build failed: {message}"))]
+    Build { message: String },
+
+    #[snafu(display("Container network error: {message}"))]
+    Network { message: String },
+
+    #[snafu(display("Service failed: {message}"))]
+    Service { message: String },
+
+    #[snafu(display("Container cancelled: {message}"))]
+    Cancelled { message: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Container runtime that runs container tasks, chosen with `container_provider`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    /// The Docker daemon, found as the Docker CLI finds it
+    #[default]
+    Docker,
+    /// Podman, through its Docker-compatible API socket
+    Podman,
+    /// A Kubernetes cluster, running each task as a Job
+    Kubernetes,
+}
+
+impl std::fmt::Display for ContainerRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+            Self::Kubernetes => "kubernetes",
+        })
+    }
+}
+
+impl std::str::FromStr for ContainerRuntime {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "docker" => Ok(Self::Docker),
+            "podman" => Ok(Self::Podman),
+            "kubernetes" => Ok(Self::Kubernetes),
+            _ => Err(format!(
+                "unknown container provider '{name}', expected docker, podman or kubernetes"
+            )),
+        }
+    }
+}
+
 /// Container provider trait for executing containers
 #[async_trait]
 pub trait ContainerProvider: Send + Sync + std::fmt::Debug {
     /// Execute a container with the given configuration
     ///
+    /// The container is removed as soon as `config.cancellation` fires.
+    ///
     /// # Errors
     ///
-    /// Returns an error if container creation, execution, or cleanup fails
+    /// Returns an error if container creation, execution, or cleanup fails,
+    /// or if the execution is cancelled
     async fn execute(&self, config: ContainerConfig) -> Result<ContainerResult>;
+
+    /// Build an image and tag it as `tag`, returning the ID (digest) of the image
+    ///
+    /// Build output is printed through `streamer` as it arrives.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the build fails, or if the provider cannot build images
+    async fn build_image(
+        &self,
+        build: &ImageBuild,
+        tag: &str,
+        streamer: Option<&TaskOutputStreamer>,
+    ) -> Result<String> {
+        let _ = (build, streamer);
+        Err(Error::Build {
+            message: format!("{tag}: this container provider cannot build images"),
+        })
+    }
+
+    /// Start `services` on a private network, execute a container on the same
+    /// network once every service is ready, then remove the services and network
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a service cannot be started or does not become ready
+    /// within `ready_timeout`, if the container fails to execute, or if the
+    /// provider cannot run services
+    async fn execute_with_services(
+        &self,
+        services: &BTreeMap<String, ServiceSpec>,
+        ready_timeout: Duration,
+        config: ContainerConfig,
+    ) -> Result<ContainerResult> {
+        let _ = (services, ready_timeout);
+        Err(Error::Service {
+            message: format!(
+                "{}: this container provider cannot run services",
+                config.image
+            ),
+        })
+    }
+
+    /// Check that `image` is present or can be pulled, without pulling it
+    ///
+    /// Providers that cannot tell accept every image.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the image is neither present nor in its registry
+    async fn check_image(&self, image: &str) -> Result<()> {
+        let _ = image;
+        Ok(())
+    }
+
+    /// Remove the idle containers kept for tasks with `keep_warm` set
+    async fn drain_pool(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("1024").unwrap(), 1024);
+        assert_eq!(parse_memory("512m").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(parse_memory("2G").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert!(parse_memory("").is_err());
+        assert!(parse_memory("-1k").is_err());
+        assert!(parse_memory("lots").is_err());
+    }
+
+    #[test]
+    fn test_task_options_override_defaults() {
+        let task = json!({
+            "run": { "container": { "image": "alpine" } },
+            "metadata": { "container": { "workingDir": "/src", "memory": "256m" } }
+        });
+        let defaults = ContainerOptions {
+            network: Some("none".to_string()),
+            memory: Some("1g".to_string()),
+            ..ContainerOptions::default()
+        };
+        let options = ContainerOptions::from_task(&task).unwrap().or(&defaults);
+        assert_eq!(options.working_dir.as_deref(), Some("/src"));
+        assert_eq!(options.network.as_deref(), Some("none"));
+        assert_eq!(options.memory.as_deref(), Some("256m"));
+
+        assert!(
+            ContainerOptions::from_task(&json!({ "metadata": { "container": { "cpu": 1 } } }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_apply_options() {
+        let options = ContainerOptions {
+            user: Some("1000".to_string()),
+            group: Some("100".to_string()),
+            cpus: Some(0.5),
+            pull_timeout: Some("PT30S".to_string()),
+            ..ContainerOptions::default()
+        };
+        let mut config = ContainerConfig::default();
+        options.apply(&mut config).unwrap();
+        assert_eq!(config.user.as_deref(), Some("1000:100"));
+        assert_eq!(config.cpus, Some(0.5));
+        assert_eq!(config.pull_timeout, Some(Duration::from_secs(30)));
+
+        let group_only = ContainerOptions {
+            group: Some("100".to_string()),
+            ..ContainerOptions::default()
+        };
+        assert!(group_only.apply(&mut config).is_err());
+    }
+
+    #[test]
+    fn test_parse_compose() {
+        let services = parse_compose(
+            r#"
+services:
+  db:
+    image: postgres:16
+    environment:
+      POSTGRES_PASSWORD: secret
+      POSTGRES_PORT: 5432
+      FROM_HOST:
+    healthcheck:
+      test: ["CMD-SHELL", "pg_isready -U postgres"]
+  cache:
+    image: redis:7
+    command: redis-server --save ""
+    environment:
+      - MODE=test
+    healthcheck:
+      test: ["CMD", "redis-cli", "ping"]
+"#,
+        )
+        .unwrap();
+
+        let db = services.get("db").unwrap();
+        assert_eq!(db.image, "postgres:16");
+        assert_eq!(db.ready.as_deref(), Some("pg_isready -U postgres"));
+        assert_eq!(db.environment.get("POSTGRES_PORT").unwrap(), "5432");
+        assert!(!db.environment.contains_key("FROM_HOST"));
+
+        let cache = services.get("cache").unwrap();
+        assert_eq!(cache.ready.as_deref(), Some("redis-cli ping"));
+        assert_eq!(cache.environment.get("MODE").unwrap(), "test");
+        assert_eq!(
+            cache
+                .command
+                .as_deref()
+                .unwrap()
+                .first()
+                .map(String::as_str),
+            Some("redis-server")
+        );
+
+        assert!(parse_compose("services:\n  app:\n    build: .\n").is_err());
+    }
+
+    #[test]
+    fn test_inline_services() {
+        let task = json!({
+            "metadata": { "container": {
+                "services": { "db": { "image": "postgres:16", "ready": "pg_isready" } },
+                "readyTimeout": "PT10S"
+            } }
+        });
+        let options = ContainerOptions::from_task(&task)
+            .unwrap()
+            .or(&ContainerOptions::default());
+        let services = options.services.as_ref().unwrap();
+        assert_eq!(services.get("db").unwrap().image, "postgres:16");
+        assert_eq!(options.ready_timeout().unwrap(), Duration::from_secs(10));
+        assert_eq!(
+            ContainerOptions::default().ready_timeout().unwrap(),
+            DEFAULT_READY_TIMEOUT
+        );
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
 }
@@ -1,17 +1,42 @@
+//! State of a running workflow instance
+//!
+//! Tasks and executors read the data of an instance and the input of the
+//! task being run through [`Context`]: copies with [`Context::data`] and
+//! [`Context::get`], or a [`DataView`] for evaluating expressions. Writes go
+//! through [`Context::set`], [`Context::remove`], [`Context::merge_data`] and
+//! [`Context::set_task_input`], which keep the descriptors and secrets the
+//! engine places in the data out of reach of tasks and mark the data changed.
+
 use chrono::Utc;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use snafu::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, RwLockReadGuard};
+use tokio_util::sync::CancellationToken;
 
 type Data = Arc<RwLock<serde_json::Value>>;
 
+/// Fields of the data the engine keeps for itself: the `$workflow` and
+/// `$runtime` descriptors, the secrets of the instance and the results of
+/// the branches of the last fork
+const RESERVED_KEYS: [&str; 4] = [
+    "__workflow",
+    "__runtime",
+    crate::secrets::SECRETS_KEY,
+    crate::expressions::BRANCHES_KEY,
+];
+
 use crate::cache::CacheProvider;
 use crate::descriptors::{RuntimeDescriptor, WorkflowDescriptor};
+use crate::durableengine::WarmStart;
+use crate::durableengine::subworkflows::CALL_CHAIN;
+use crate::durableengine::versions::RESOLVED_VERSIONS;
 use crate::executionhistory::ExecutionHistory;
+use crate::expressions::Evaluation;
+use crate::mock::MockRoutes;
 use crate::persistence::PersistenceProvider;
-use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
+use crate::workflow::{ReapedResource, WorkflowCheckpoint, WorkflowEvent};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -26,15 +51,24 @@ pub enum Error {
 
     #[snafu(display("Context error: {message}"))]
     Context { message: String },
+
+    #[snafu(display("Cannot write {pointer}: {message}"))]
+    Pointer { pointer: String, message: String },
+
+    #[snafu(display("{pointer} is reserved for the engine"))]
+    Reserved { pointer: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Execution state that changes during workflow execution
+///
+/// The data and task input are read and written through the methods of
+/// [`Context`].
 #[derive(Clone)]
 pub struct ExecutionState {
-    pub data: Data,
-    pub task_input: Arc<RwLock<serde_json::Value>>,
+    pub(crate) data: Data,
+    pub(crate) task_input: Data,
     pub current_task: Arc<RwLock<String>>,
     pub next_task: Arc<RwLock<Option<String>>>,
     pub task_index: Option<usize>,
@@ -46,6 +80,69 @@ pub struct ExecutionState {
     pub suspended: Arc<RwLock<bool>>,
     /// Suspension reason - optional message explaining why workflow was suspended
     pub suspension_reason: Arc<RwLock<Option<String>>>,
+    /// Cancelled when the instance is cancelled or suspended, or the task in
+    /// flight times out; executors stop the work they started when it fires
+    pub cancellation: CancellationToken,
+    /// Work executors stopped when the cancellation token fired
+    pub reaper: Reaper,
+    /// Set in fork branches holding one of the engine's fork permits, whose
+    /// nested forks run under that permit instead of waiting for another
+    pub holds_fork_permit: bool,
+}
+
+/// Records the processes, containers and requests executors stopped when
+/// their task was cancelled or timed out, to be reported in a `TaskReaped` event
+#[derive(Debug, Clone, Default)]
+pub struct Reaper(Arc<std::sync::Mutex<Vec<ReapedResource>>>);
+
+impl Reaper {
+    pub fn record(&self, resource: ReapedResource) {
+        tracing::debug!("Reaped {:?}", resource);
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(resource);
+    }
+
+    /// Take the resources recorded so far
+    #[must_use]
+    pub fn take(&self) -> Vec<ReapedResource> {
+        std::mem::take(
+            &mut *self
+                .0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        )
+    }
+}
+
+/// Read-only view of the data or task input of an instance, for evaluating
+/// expressions against it without a copy
+///
+/// Writes to the context wait until the view is dropped.
+#[derive(Debug)]
+pub struct DataView<'a>(RwLockReadGuard<'a, serde_json::Value>);
+
+impl std::ops::Deref for DataView<'_> {
+    type Target = serde_json::Value;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// How [`Context::merge_data`] combines a value with the data of an instance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The value replaces the data
+    Replace,
+    /// The fields of the value replace the fields of the same name; a value
+    /// that is not an object replaces the data
+    #[default]
+    Shallow,
+    /// Objects are merged field by field, at every level; anything else
+    /// replaces what it is merged into
+    Deep,
 }
 
 /// Static workflow metadata (immutable during execution)
@@ -57,6 +154,15 @@ pub struct WorkflowMetadata {
     pub initial_input: Arc<serde_json::Value>,
     pub runtime_descriptor: Arc<RuntimeDescriptor>,
     pub workflow_descriptor: Arc<WorkflowDescriptor>,
+    /// Versions the version ranges of the workflow were resolved to when the
+    /// instance was started, see [`crate::durableengine::versions`]
+    pub resolved_versions: Arc<HashMap<String, String>>,
+    /// Workflows calling the instance, outermost first, see
+    /// [`crate::durableengine::DEFAULT_MAX_CALL_DEPTH`]
+    pub call_chain: Arc<Vec<String>>,
+    /// How the expressions of the workflow are evaluated, passed to every
+    /// evaluation since the data evaluated against need not carry `$workflow`
+    pub evaluation: Evaluation,
 }
 
 /// External services for I/O operations
@@ -65,6 +171,11 @@ pub struct ExecutionServices {
     pub persistence: Arc<dyn PersistenceProvider>,
     pub cache: Arc<dyn CacheProvider>,
     pub history: Arc<ExecutionHistory>,
+    /// Base URLs of APIs whose calls go to mock servers instead
+    pub mocks: Arc<MockRoutes>,
+    /// Documents and descriptors restored from the snapshot the engine was
+    /// started from
+    pub warm: Arc<WarmStart>,
 }
 
 /// Tracking metadata (could potentially be eliminated or simplified)
@@ -89,6 +200,8 @@ pub struct Context {
 impl Context {
     /// Creates a new context for workflow execution.
     ///
+    /// `metadata` is recorded in the `WorkflowStarted` event of a new instance.
+    ///
     /// # Errors
     ///
     /// Returns an error if:
@@ -101,19 +214,39 @@ impl Context {
         cache: Arc<dyn CacheProvider>,
         instance_id: Option<String>,
         initial_data: serde_json::Value,
+        metadata: Option<serde_json::Map<String, serde_json::Value>>,
     ) -> Result<Self> {
         let instance_id = instance_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let checkpoint = persistence
+            .get_checkpoint(&instance_id)
+            .await
+            .context(PersistenceSnafu)?;
         let events = persistence
             .get_events(&instance_id)
             .await
             .context(PersistenceSnafu)?;
+        let events = drop_uncommitted_completions(&instance_id, events, checkpoint.as_ref());
         let history = Arc::new(ExecutionHistory::new(&events));
 
-        let (data, current_task) = if let Some(checkpoint) = persistence
-            .get_checkpoint(&instance_id)
-            .await
-            .context(PersistenceSnafu)?
-        {
+        // A resumed instance keeps the input and versions it was originally started with
+        let initial_data = history.initial_data().cloned().unwrap_or(initial_data);
+        let started_metadata = history.started_metadata().or(metadata.as_ref());
+        let resolved_versions = started_metadata
+            .and_then(|metadata| metadata.get(RESOLVED_VERSIONS))
+            .and_then(serde_json::Value::as_object)
+            .map(|versions| {
+                versions
+                    .iter()
+                    .filter_map(|(key, version)| Some((key.clone(), version.as_str()?.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let call_chain = started_metadata
+            .and_then(|metadata| metadata.get(CALL_CHAIN))
+            .and_then(|chain| serde_json::from_value(chain.clone()).ok())
+            .unwrap_or_default();
+
+        let (data, current_task) = if let Some(checkpoint) = checkpoint {
             (checkpoint.data, checkpoint.current_task)
         } else {
             let first_task_name = workflow
@@ -124,19 +257,32 @@ impl Context {
                 .ok_or(Error::NoTasks)?
                 .clone();
 
-            persistence
-                .save_event(WorkflowEvent::WorkflowStarted {
-                    instance_id: instance_id.clone(),
-                    workflow_id: workflow.document.name.clone(),
-                    timestamp: Utc::now(),
-                    initial_data: initial_data.clone(),
-                })
-                .await
-                .context(PersistenceSnafu)?;
+            // An instance resumed before its first checkpoint has already been started
+            if events.is_empty() {
+                persistence
+                    .save_event(WorkflowEvent::WorkflowStarted {
+                        instance_id: instance_id.clone(),
+                        workflow_id: format!(
+                            "{}/{}/{}",
+                            workflow.document.namespace,
+                            workflow.document.name,
+                            workflow.document.version
+                        ),
+                        timestamp: Utc::now(),
+                        initial_data: initial_data.clone(),
+                        metadata,
+                    })
+                    .await
+                    .context(PersistenceSnafu)?;
+            }
 
             (initial_data.clone(), first_task_name)
         };
 
+        let evaluation = Evaluation::of_workflow(workflow).map_err(|e| Error::Context {
+            message: e.to_string(),
+        })?;
+
         // Create runtime descriptor
         let runtime_descriptor =
             RuntimeDescriptor::new("jackdaw".to_string(), env!("CARGO_PKG_VERSION").to_string());
@@ -176,6 +322,9 @@ impl Context {
                 cancellation_reason: Arc::new(RwLock::new(None)),
                 suspended: Arc::new(RwLock::new(false)),
                 suspension_reason: Arc::new(RwLock::new(None)),
+                cancellation: CancellationToken::new(),
+                reaper: Reaper::default(),
+                holds_fork_permit: false,
             },
             metadata: WorkflowMetadata {
                 instance_id,
@@ -183,11 +332,16 @@ impl Context {
                 initial_input: Arc::new(initial_data.clone()),
                 runtime_descriptor: Arc::new(runtime_descriptor),
                 workflow_descriptor: Arc::new(workflow_descriptor),
+                resolved_versions: Arc::new(resolved_versions),
+                call_chain: Arc::new(call_chain),
+                evaluation,
             },
             services: ExecutionServices {
                 persistence,
                 cache,
                 history,
+                mocks: Arc::default(),
+                warm: Arc::default(),
             },
             tracking: ExecutionTracking {
                 data_modified: Arc::new(RwLock::new(false)),
@@ -197,30 +351,145 @@ impl Context {
         })
     }
 
+    /// A copy of this context with a child of its cancellation token
+    ///
+    /// Cancelling the copy's token stops only the work started with the copy,
+    /// e.g. a task that timed out, while cancelling the instance stops both.
+    #[must_use]
+    pub fn with_child_cancellation(&self) -> Self {
+        let mut ctx = self.clone();
+        ctx.state.cancellation = self.state.cancellation.child_token();
+        ctx
+    }
+
+    /// A copy of this context with data of its own
+    ///
+    /// Tasks run with the copy read and change `data` instead of the data of
+    /// the instance, so they can run alongside other tasks of the instance.
+    #[must_use]
+    pub fn with_own_data(&self, data: serde_json::Value, task_input: serde_json::Value) -> Self {
+        let mut ctx = self.clone();
+        ctx.state.data = Arc::new(RwLock::new(data));
+        ctx.state.task_input = Arc::new(RwLock::new(task_input));
+        ctx.state.next_task = Arc::new(RwLock::new(None));
+        ctx
+    }
+
+    /// A copy of the data of the instance
+    pub async fn data(&self) -> serde_json::Value {
+        self.state.data.read().await.clone()
+    }
+
+    /// A copy of the input of the task being run
+    pub async fn task_input(&self) -> serde_json::Value {
+        self.state.task_input.read().await.clone()
+    }
+
+    /// Read-only view of the data of the instance
+    pub async fn view_data(&self) -> DataView<'_> {
+        DataView(self.state.data.read().await)
+    }
+
+    /// Read-only view of the input of the task being run
+    pub async fn view_task_input(&self) -> DataView<'_> {
+        DataView(self.state.task_input.read().await)
+    }
+
+    /// The value at the JSON `pointer` in the data, e.g. `/order/items/0`
+    pub async fn get(&self, pointer: &str) -> Option<serde_json::Value> {
+        self.state.data.read().await.pointer(pointer).cloned()
+    }
+
+    /// Set the value at the JSON `pointer` in the data, creating the objects
+    /// leading to it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pointer` is malformed, leads through a value that
+    /// is neither an object nor an array or past the end of an array, or into
+    /// the fields the engine keeps for itself.
+    pub async fn set(&self, pointer: &str, value: serde_json::Value) -> Result<()> {
+        check_reserved(pointer)?;
+        let mut data = self.state.data.write().await;
+        set_pointer(&mut data, pointer, value)?;
+        *self.tracking.data_modified.write().await = true;
+        Ok(())
+    }
+
+    /// Remove the value at the JSON `pointer` from the data, returning it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pointer` is malformed or leads into the fields the
+    /// engine keeps for itself.
+    pub async fn remove(&self, pointer: &str) -> Result<Option<serde_json::Value>> {
+        check_reserved(pointer)?;
+        let (parent, key) = split_pointer(pointer)?;
+        let mut data = self.state.data.write().await;
+        let removed = match data.pointer_mut(parent) {
+            Some(serde_json::Value::Object(object)) => object.remove(&key),
+            Some(serde_json::Value::Array(array)) => match key.parse::<usize>() {
+                Ok(index) if index < array.len() => Some(array.remove(index)),
+                _ => None,
+            },
+            Some(_) | None => None,
+        };
+        if removed.is_some() {
+            *self.tracking.data_modified.write().await = true;
+        }
+        Ok(removed)
+    }
+
+    /// Combine `value` with the data of the instance
+    pub async fn merge_data(&self, value: serde_json::Value, strategy: MergeStrategy) {
+        let mut data = self.state.data.write().await;
+        match strategy {
+            MergeStrategy::Replace => *data = value,
+            MergeStrategy::Shallow => match (&mut *data, value) {
+                (serde_json::Value::Object(data), serde_json::Value::Object(value)) => {
+                    data.extend(value);
+                }
+                (data, value) => *data = value,
+            },
+            MergeStrategy::Deep => deep_merge(&mut data, value),
+        }
+        *self.tracking.data_modified.write().await = true;
+    }
+
+    /// Set the input of the next task
+    pub async fn set_task_input(&self, value: serde_json::Value) {
+        *self.state.task_input.write().await = value;
+    }
+
+    /// Place a field the engine keeps for itself, such as the secrets of the
+    /// instance, in the data and the task input, where expressions read it
+    pub(crate) async fn inject(&self, key: &str, value: serde_json::Value) {
+        for data in [&self.state.data, &self.state.task_input] {
+            if let serde_json::Value::Object(data) = &mut *data.write().await {
+                data.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+
+    /// Set the `key` field of the data to the output of a task
     pub async fn merge(&self, key: &str, value: serde_json::Value) {
         let mut data = self.state.data.write().await;
         if let Some(obj) = data.as_object_mut() {
             obj.insert(key.to_string(), value);
-            *self.tracking.data_modified.write().await = true;
-            // Track that this key was set by a task
-            self.tracking
-                .task_output_keys
-                .write()
-                .await
-                .insert(key.to_string());
         } else {
             // If data is not an object (e.g., after input filtering to a scalar),
             // replace it with a new object containing the key-value pair
             let mut new_obj = serde_json::Map::new();
             new_obj.insert(key.to_string(), value);
             *data = serde_json::Value::Object(new_obj);
-            *self.tracking.data_modified.write().await = true;
-            self.tracking
-                .task_output_keys
-                .write()
-                .await
-                .insert(key.to_string());
         }
+        *self.tracking.data_modified.write().await = true;
+        // Track that this key was set by a task
+        self.tracking
+            .task_output_keys
+            .write()
+            .await
+            .insert(key.to_string());
     }
 
     /// Saves the current workflow execution state as a checkpoint.
@@ -229,19 +498,37 @@ impl Context {
     ///
     /// Returns an error if there is a persistence error when saving the checkpoint.
     pub async fn save_checkpoint(&self, task_name: &str) -> Result<()> {
-        let data = self.state.data.read().await;
+        let checkpoint = self.checkpoint(task_name).await;
         self.services
             .persistence
-            .save_checkpoint(WorkflowCheckpoint {
-                instance_id: self.metadata.instance_id.clone(),
-                current_task: task_name.to_string(),
-                data: data.clone(),
-                timestamp: Utc::now(),
-            })
+            .save_checkpoint(checkpoint)
+            .await
+            .context(PersistenceSnafu)
+    }
+
+    /// Records a task's completion event together with the checkpoint taken after it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is a persistence error when committing the task.
+    pub async fn commit_task(&self, task_name: &str, event: WorkflowEvent) -> Result<()> {
+        let checkpoint = self.checkpoint(task_name).await;
+        self.services
+            .persistence
+            .commit_task(event, checkpoint)
             .await
             .context(PersistenceSnafu)
     }
 
+    async fn checkpoint(&self, task_name: &str) -> WorkflowCheckpoint {
+        WorkflowCheckpoint {
+            instance_id: self.metadata.instance_id.clone(),
+            current_task: task_name.to_string(),
+            data: self.data().await,
+            timestamp: Utc::now(),
+        }
+    }
+
     /// Cancel the workflow execution
     ///
     /// # Errors
@@ -252,6 +539,7 @@ impl Context {
         // Set cancellation flags
         *self.state.cancelled.write().await = true;
         *self.state.cancellation_reason.write().await = reason.clone();
+        self.state.cancellation.cancel();
 
         // Emit WorkflowCancelled event
         self.services
@@ -281,7 +569,7 @@ impl Context {
         self.save_checkpoint(&current_task).await?;
 
         // Emit WorkflowSuspended event with checkpoint data
-        let data = self.state.data.read().await.clone();
+        let data = self.data().await;
         self.services
             .persistence
             .save_event(WorkflowEvent::WorkflowSuspended {
@@ -326,3 +614,145 @@ impl Context {
         *self.state.suspended.read().await
     }
 }
+
+/// The JSON pointer to the top-level field `key` of the data
+#[must_use]
+pub fn field_pointer(key: &str) -> String {
+    format!("/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// What [`Context::set`] and [`Context::remove`] expect
+const FIELD_POINTER: &str = "expected a JSON pointer to a field, such as /order/id";
+
+/// Refuse writes into the fields the engine keeps for itself
+fn check_reserved(pointer: &str) -> Result<()> {
+    if pointer
+        .split('/')
+        .nth(1)
+        .is_some_and(|key| RESERVED_KEYS.contains(&key))
+    {
+        return ReservedSnafu { pointer }.fail();
+    }
+    Ok(())
+}
+
+/// The pointer to the parent of what `pointer` points to, and its last key
+fn split_pointer(pointer: &str) -> Result<(&str, String)> {
+    let (parent, key) = pointer
+        .rsplit_once('/')
+        .filter(|_| pointer.starts_with('/'))
+        .context(PointerSnafu {
+            pointer,
+            message: FIELD_POINTER,
+        })?;
+    Ok((parent, key.replace("~1", "/").replace("~0", "~")))
+}
+
+/// Set the value at `pointer` in `target`, creating the objects leading to it
+fn set_pointer(
+    target: &mut serde_json::Value,
+    pointer: &str,
+    value: serde_json::Value,
+) -> Result<()> {
+    let fail = |message: String| PointerSnafu { pointer, message }.fail();
+    let mut keys: Vec<String> = pointer
+        .split('/')
+        .skip(1)
+        .map(|key| key.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    let Some(last) = keys.pop().filter(|_| pointer.starts_with('/')) else {
+        return fail(FIELD_POINTER.to_string());
+    };
+    let mut current = target;
+    for key in keys {
+        current = match current {
+            serde_json::Value::Object(object) => object
+                .entry(key)
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new())),
+            serde_json::Value::Array(array) => {
+                let len = array.len();
+                let Some(item) = key.parse::<usize>().ok().and_then(|i| array.get_mut(i)) else {
+                    return fail(format!("no item {key} in an array of {len}"));
+                };
+                item
+            }
+            scalar @ (serde_json::Value::Null
+            | serde_json::Value::Bool(_)
+            | serde_json::Value::Number(_)
+            | serde_json::Value::String(_)) => {
+                return fail(format!("cannot set field {key} of {scalar}"));
+            }
+        };
+    }
+    match current {
+        serde_json::Value::Object(object) => {
+            object.insert(last, value);
+        }
+        serde_json::Value::Array(array) => {
+            let index = if last == "-" {
+                array.len()
+            } else {
+                last.parse::<usize>().unwrap_or(usize::MAX)
+            };
+            if let Some(item) = array.get_mut(index) {
+                *item = value;
+            } else if index == array.len() {
+                array.push(value);
+            } else {
+                return fail(format!("no item {last} in an array of {}", array.len()));
+            }
+        }
+        scalar @ (serde_json::Value::Null
+        | serde_json::Value::Bool(_)
+        | serde_json::Value::Number(_)
+        | serde_json::Value::String(_)) => {
+            return fail(format!("cannot set field {last} of {scalar}"));
+        }
+    }
+    Ok(())
+}
+
+/// Merge `value` into `target`, object fields at every level
+fn deep_merge(target: &mut serde_json::Value, value: serde_json::Value) {
+    match (target, value) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(value)) => {
+            for (key, value) in value {
+                match target.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+
+/// Drops task completions recorded after the instance's latest checkpoint
+///
+/// A provider that cannot commit a task's event and checkpoint together may
+/// stop between the two, leaving a completion the checkpointed data does not
+/// reflect. Replaying it would skip the task's export, so those tasks run
+/// again instead; a cached result makes the rerun a cache hit.
+fn drop_uncommitted_completions(
+    instance_id: &str,
+    events: Vec<WorkflowEvent>,
+    checkpoint: Option<&WorkflowCheckpoint>,
+) -> Vec<WorkflowEvent> {
+    let committed = |event: &WorkflowEvent| {
+        let WorkflowEvent::TaskCompleted { timestamp, .. } = event else {
+            return true;
+        };
+        checkpoint.is_some_and(|checkpoint| *timestamp <= checkpoint.timestamp)
+    };
+    let (kept, dropped): (Vec<_>, Vec<_>) = events.into_iter().partition(committed);
+    for event in &dropped {
+        if let WorkflowEvent::TaskCompleted { task_name, .. } = event {
+            tracing::warn!(
+                "Instance {instance_id}: task '{task_name}' completed after the last checkpoint and will run again"
+            );
+        }
+    }
+    kept
+}
@@ -0,0 +1,553 @@
+//! Contract checks of call tasks against the documents they reference
+//!
+//! `call: openapi` and `call: grpc` tasks name an operation of an OpenAPI
+//! document or a method of a proto service. [`check_workflow`] loads those
+//! documents and checks each call against them before anything runs: the
+//! operation or method exists, required parameters are provided (as literals
+//! or as expressions), and literal request bodies and arguments match their
+//! schema or message. Calls whose document cannot be loaded, or is named by an
+//! expression, are reported as unchecked rather than failing.
+
+#[cfg(feature = "grpc")]
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::durableengine::resources::{PathAnchor, resolve_path};
+use crate::executor::Endpoint;
+use crate::mock::resolve;
+
+/// How deep request bodies are checked against their schema
+const MAX_SCHEMA_DEPTH: usize = 8;
+
+/// HTTP methods that can hold an operation in an OpenAPI path item
+const OPERATION_METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// A finding of the contract checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContractIssue {
+    /// The call does not match the document it references
+    Violation { location: String, message: String },
+    /// The call could not be checked, e.g. because its document is unreachable
+    Unchecked { location: String, message: String },
+}
+
+/// A `call: openapi` or `call: grpc` task
+struct CallSite {
+    location: String,
+    call: String,
+    with: Value,
+}
+
+/// Check the OpenAPI and gRPC calls of a workflow against their documents
+///
+/// Relative document paths are resolved against `workflow_dir`, as they are
+/// when the workflow runs; remote OpenAPI documents are fetched.
+pub async fn check_workflow(
+    workflow: &WorkflowDefinition,
+    workflow_dir: &Path,
+) -> Vec<ContractIssue> {
+    let mut calls = Vec::new();
+    if let Ok(tasks) = serde_json::to_value(&workflow.do_) {
+        collect_calls(&tasks, &mut calls);
+    }
+
+    let mut issues = Vec::new();
+    let mut documents: HashMap<String, Result<Value, String>> = HashMap::new();
+    #[cfg(feature = "grpc")]
+    let mut pools: HashMap<String, Result<DescriptorPool, String>> = HashMap::new();
+    for site in calls {
+        match site.call.as_str() {
+            "openapi" => {
+                let Some(uri) = endpoint_uri(&site.with, "/document/endpoint") else {
+                    unchecked(&mut issues, &site, "the document is not a literal URI");
+                    continue;
+                };
+                if !documents.contains_key(&uri) {
+                    let document = load_openapi(&uri, workflow_dir).await;
+                    documents.insert(uri.clone(), document);
+                }
+                match documents.get(&uri) {
+                    Some(Ok(document)) => check_openapi(&site, document, &mut issues),
+                    Some(Err(e)) => unchecked(&mut issues, &site, e),
+                    None => {}
+                }
+            }
+            #[cfg(feature = "grpc")]
+            "grpc" => {
+                let Some(uri) = endpoint_uri(&site.with, "/proto/endpoint") else {
+                    unchecked(&mut issues, &site, "the proto file is not a literal URI");
+                    continue;
+                };
+                let pool = pools
+                    .entry(uri.clone())
+                    .or_insert_with(|| load_proto(&uri, workflow_dir));
+                match pool {
+                    Ok(pool) => check_grpc(&site, pool, &mut issues),
+                    Err(e) => unchecked(&mut issues, &site, e),
+                }
+            }
+            #[cfg(not(feature = "grpc"))]
+            "grpc" => unchecked(
+                &mut issues,
+                &site,
+                "jackdaw was built without the `grpc` feature",
+            ),
+            _ => {}
+        }
+    }
+    issues
+}
+
+/// Collect the OpenAPI and gRPC calls of a task list and the lists nested in it
+fn collect_calls(tasks: &Value, calls: &mut Vec<CallSite>) {
+    let Some(entries) = tasks.as_array() else {
+        return;
+    };
+    for (name, task) in entries.iter().filter_map(Value::as_object).flatten() {
+        if let Some(call) = task.get("call").and_then(Value::as_str) {
+            if call == "openapi" || call == "grpc" {
+                calls.push(CallSite {
+                    location: format!("task.{name}.call"),
+                    call: call.to_string(),
+                    with: task.get("with").cloned().unwrap_or(Value::Null),
+                });
+            }
+            continue;
+        }
+        for pointer in ["/do", "/try", "/catch/do", "/fork/branches"] {
+            if let Some(nested) = task.pointer(pointer) {
+                collect_calls(nested, calls);
+            }
+        }
+    }
+}
+
+fn unchecked(issues: &mut Vec<ContractIssue>, site: &CallSite, reason: &str) {
+    issues.push(ContractIssue::Unchecked {
+        location: site.location.clone(),
+        message: format!("Call was not checked: {reason}"),
+    });
+}
+
+fn violation(issues: &mut Vec<ContractIssue>, site: &CallSite, message: String) {
+    issues.push(ContractIssue::Violation {
+        location: site.location.clone(),
+        message,
+    });
+}
+
+/// The URI of an endpoint, unless it is missing or computed by an expression
+fn endpoint_uri(with: &Value, pointer: &str) -> Option<String> {
+    let endpoint: Endpoint = serde_json::from_value(with.pointer(pointer)?.clone()).ok()?;
+    let uri = endpoint.uri();
+    (!uri.contains("${")).then(|| uri.to_string())
+}
+
+/// Whether a value is fully known before the workflow runs
+fn is_literal(value: &Value) -> bool {
+    match value {
+        Value::String(s) => !s.contains("${"),
+        Value::Array(items) => items.iter().all(is_literal),
+        Value::Object(map) => map.values().all(is_literal),
+        Value::Null | Value::Bool(_) | Value::Number(_) => true,
+    }
+}
+
+async fn load_openapi(uri: &str, workflow_dir: &Path) -> Result<Value, String> {
+    let text = if uri.starts_with("http://") || uri.starts_with("https://") {
+        let response = reqwest::get(uri)
+            .await
+            .map_err(|e| format!("failed to fetch '{uri}': {e}"))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "failed to fetch '{uri}': HTTP {}",
+                response.status()
+            ));
+        }
+        response
+            .text()
+            .await
+            .map_err(|e| format!("failed to fetch '{uri}': {e}"))?
+    } else {
+        let resolved = resolve_path(
+            uri,
+            Some(PathAnchor::WorkflowDir(workflow_dir.to_path_buf())),
+        );
+        std::fs::read_to_string(&resolved.path)
+            .map_err(|e| format!("failed to read {resolved}: {e}"))?
+    };
+    serde_json::from_str(&text)
+        .or_else(|_| serde_yaml::from_str(&text))
+        .map_err(|e| format!("'{uri}' is not a JSON or YAML document: {e}"))
+}
+
+#[cfg(feature = "grpc")]
+fn load_proto(uri: &str, workflow_dir: &Path) -> Result<DescriptorPool, String> {
+    let resolved = resolve_path(
+        uri,
+        Some(PathAnchor::WorkflowDir(workflow_dir.to_path_buf())),
+    );
+    let path = resolved.path.to_string_lossy();
+    let descriptors = crate::listeners::grpc::compile_proto(&path)
+        .map_err(|e| format!("failed to compile proto file {resolved}: {e}"))?;
+    DescriptorPool::from_file_descriptor_set(descriptors)
+        .map_err(|e| format!("invalid descriptors in proto file {resolved}: {e}"))
+}
+
+/// Check an OpenAPI call: the operation, its required parameters and its body
+fn check_openapi(site: &CallSite, document: &Value, issues: &mut Vec<ContractIssue>) {
+    let Some(operation_id) = site.with.get("operationId").and_then(Value::as_str) else {
+        violation(issues, site, "The call names no operationId".to_string());
+        return;
+    };
+    if operation_id.contains("${") {
+        unchecked(issues, site, "the operationId is computed by an expression");
+        return;
+    }
+    let Some((path_item, operation)) = find_operation(document, operation_id) else {
+        violation(
+            issues,
+            site,
+            format!("Operation '{operation_id}' is not defined in the OpenAPI document"),
+        );
+        return;
+    };
+
+    // Parameters given as a single expression are only known at runtime
+    let parameters = site
+        .with
+        .get("parameters")
+        .cloned()
+        .unwrap_or_else(|| Value::Object(serde_json::Map::new()));
+    let Some(provided) = parameters.as_object() else {
+        return;
+    };
+
+    let mut body_schema = None;
+    let mut body_required = false;
+    let declared = [path_item, operation]
+        .into_iter()
+        .filter_map(|item| item.get("parameters").and_then(Value::as_array))
+        .flatten()
+        .map(|parameter| resolve(document, parameter, 0));
+    for parameter in declared {
+        let name = parameter.get("name").and_then(Value::as_str).unwrap_or("");
+        let location = parameter.get("in").and_then(Value::as_str).unwrap_or("");
+        let required = location == "path"
+            || parameter
+                .get("required")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+        if location == "body" {
+            // Swagger 2.0 request bodies; the call passes them as `body`
+            body_schema = parameter.get("schema");
+            body_required = required;
+        } else if required && !provided.contains_key(name) {
+            violation(
+                issues,
+                site,
+                format!(
+                    "Required {location} parameter '{name}' of operation '{operation_id}' is not provided"
+                ),
+            );
+        }
+    }
+
+    if let Some(request_body) = operation.get("requestBody") {
+        let request_body = resolve(document, request_body, 0);
+        body_required = request_body
+            .get("required")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        body_schema = request_body
+            .get("content")
+            .and_then(Value::as_object)
+            .and_then(|content| {
+                content
+                    .get("application/json")
+                    .or_else(|| content.values().next())
+            })
+            .and_then(|media| media.get("schema"));
+    }
+
+    match provided.get("body") {
+        None if body_required => violation(
+            issues,
+            site,
+            format!("Operation '{operation_id}' requires a request body, but none is provided"),
+        ),
+        Some(body) if is_literal(body) => {
+            if let Some(mismatch) =
+                body_schema.and_then(|schema| schema_mismatch(document, schema, body, "body", 0))
+            {
+                violation(
+                    issues,
+                    site,
+                    format!(
+                        "Request body of operation '{operation_id}' does not match its schema: {mismatch}"
+                    ),
+                );
+            }
+        }
+        Some(_) | None => {}
+    }
+}
+
+/// The path item and operation with an operationId
+fn find_operation<'a>(document: &'a Value, operation_id: &str) -> Option<(&'a Value, &'a Value)> {
+    document
+        .get("paths")?
+        .as_object()?
+        .values()
+        .flat_map(|path_item| {
+            OPERATION_METHODS
+                .iter()
+                .filter_map(move |method| Some((path_item, path_item.get(*method)?)))
+        })
+        .find(|(_, operation)| {
+            operation.get("operationId").and_then(Value::as_str) == Some(operation_id)
+        })
+}
+
+/// The first way a value does not match a schema, if any
+fn schema_mismatch(
+    document: &Value,
+    schema: &Value,
+    value: &Value,
+    path: &str,
+    depth: usize,
+) -> Option<String> {
+    if depth > MAX_SCHEMA_DEPTH {
+        return None;
+    }
+    let schema = resolve(document, schema, 0);
+
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array)
+        && let Some(mismatch) = all_of
+            .iter()
+            .find_map(|part| schema_mismatch(document, part, value, path, depth + 1))
+    {
+        return Some(mismatch);
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(alternatives) = schema.get(key).and_then(Value::as_array)
+            && alternatives
+                .iter()
+                .all(|part| schema_mismatch(document, part, value, path, depth + 1).is_some())
+        {
+            return Some(format!("{path} matches none of the schemas in {key}"));
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        return Some(format!(
+            "{path} is {value}, which is not one of {}",
+            Value::Array(allowed.clone())
+        ));
+    }
+
+    if value.is_null() && schema.get("nullable").and_then(Value::as_bool) == Some(true) {
+        return None;
+    }
+    if let Some(expected) = schema.get("type").and_then(Value::as_str)
+        && !has_type(value, expected)
+    {
+        return Some(format!(
+            "{path} should be {expected}, but is {}",
+            type_name(value)
+        ));
+    }
+
+    match value {
+        Value::Object(map) => {
+            let required = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str);
+            for name in required {
+                if !map.contains_key(name) {
+                    return Some(format!("{path} is missing required property '{name}'"));
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object)?;
+            map.iter().find_map(|(name, item)| {
+                let property = properties.get(name)?;
+                schema_mismatch(
+                    document,
+                    property,
+                    item,
+                    &format!("{path}.{name}"),
+                    depth + 1,
+                )
+            })
+        }
+        Value::Array(items) => {
+            let item_schema = schema.get("items")?;
+            items.iter().enumerate().find_map(|(index, item)| {
+                schema_mismatch(
+                    document,
+                    item_schema,
+                    item,
+                    &format!("{path}[{index}]"),
+                    depth + 1,
+                )
+            })
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => None,
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        // Types this check does not know are not held against the value
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Check a gRPC call: the service, the method and the fields of its arguments
+#[cfg(feature = "grpc")]
+fn check_grpc(site: &CallSite, pool: &DescriptorPool, issues: &mut Vec<ContractIssue>) {
+    let Some(service_name) = site.with.pointer("/service/name").and_then(Value::as_str) else {
+        violation(issues, site, "The call names no service".to_string());
+        return;
+    };
+    let Some(service) = pool.get_service_by_name(service_name) else {
+        violation(
+            issues,
+            site,
+            format!("Service '{service_name}' is not defined in the proto file"),
+        );
+        return;
+    };
+    let method_name = site
+        .with
+        .get("method")
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    let Some(method) = service
+        .methods()
+        .find(|method| method.name() == method_name)
+    else {
+        violation(
+            issues,
+            site,
+            format!("Method '{method_name}' is not defined in service '{service_name}'"),
+        );
+        return;
+    };
+
+    let Some(arguments) = site.with.get("arguments") else {
+        return;
+    };
+    let input = method.input();
+    let mut unknown_fields = false;
+    if let Some(fields) = arguments.as_object() {
+        for name in fields.keys().filter(|name| !has_field(&input, name)) {
+            unknown_fields = true;
+            violation(
+                issues,
+                site,
+                format!(
+                    "Field '{name}' is not defined in message {}",
+                    input.full_name()
+                ),
+            );
+        }
+    }
+    // Unknown fields are reported above; the rest is checked by converting
+    if !unknown_fields
+        && is_literal(arguments)
+        && let Err(e) = DynamicMessage::deserialize(input.clone(), arguments.clone())
+    {
+        violation(
+            issues,
+            site,
+            format!("Arguments do not match {}: {e}", input.full_name()),
+        );
+    }
+}
+
+#[cfg(feature = "grpc")]
+fn has_field(message: &MessageDescriptor, name: &str) -> bool {
+    message.get_field_by_name(name).is_some() || message.get_field_by_json_name(name).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_mismatch() {
+        let document = json!({
+            "components": {"schemas": {"Pet": {
+                "type": "object",
+                "required": ["name"],
+                "properties": {"name": {"type": "string"}, "tags": {"type": "array", "items": {"type": "string"}}}
+            }}}
+        });
+        let schema = json!({"$ref": "#/components/schemas/Pet"});
+        let check = |value: Value| schema_mismatch(&document, &schema, &value, "body", 0);
+
+        assert_eq!(check(json!({"name": "Rex", "tags": ["good"]})), None);
+        assert_eq!(
+            check(json!({"tags": []})).unwrap(),
+            "body is missing required property 'name'"
+        );
+        assert_eq!(
+            check(json!({"name": "Rex", "tags": [1]})).unwrap(),
+            "body.tags[0] should be string, but is a number"
+        );
+    }
+
+    #[test]
+    fn test_collects_nested_calls() {
+        let tasks = json!([
+            {"outer": {"do": [
+                {"inner": {"call": "openapi", "with": {}}}
+            ]}},
+            {"guarded": {"try": [], "catch": {"do": [
+                {"fallback": {"call": "grpc", "with": {}}}
+            ]}}},
+            {"plain": {"call": "http", "with": {}}}
+        ]);
+        let mut calls = Vec::new();
+        collect_calls(&tasks, &mut calls);
+        let locations: Vec<&str> = calls.iter().map(|call| call.location.as_str()).collect();
+        assert_eq!(locations, vec!["task.inner.call", "task.fallback.call"]);
+    }
+
+    #[test]
+    fn test_literal_values() {
+        assert!(is_literal(&json!({"a": [1, "x"]})));
+        assert!(!is_literal(&json!({"a": ["${ .x }"]})));
+    }
+}
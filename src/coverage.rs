@@ -0,0 +1,441 @@
+//! Coverage of workflow definitions by test runs
+//!
+//! A [`Coverage`] lists the tasks, switch cases and catch blocks of the
+//! workflows under test, and counts how often the recorded events of test
+//! runs hit each of them. `jackdaw test --coverage` reports the result as
+//! text, JSON or HTML, so it is visible which parts of a workflow no test
+//! exercises.
+
+use serde::Serialize;
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+use crate::workflow::WorkflowEvent;
+
+/// Formats coverage reports are rendered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CoverageFormat {
+    #[default]
+    Text,
+    Json,
+    Html,
+}
+
+impl std::str::FromStr for CoverageFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "html" => Ok(Self::Html),
+            _ => Err(format!(
+                "unknown coverage format '{name}', expected text, json or html"
+            )),
+        }
+    }
+}
+
+/// How often the parts of workflows were hit, keyed by "namespace/name/version"
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Coverage {
+    workflows: BTreeMap<String, WorkflowCoverage>,
+}
+
+/// How often the parts of one workflow were hit
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorkflowCoverage {
+    /// Tasks, by name
+    pub tasks: BTreeMap<String, u64>,
+    /// Cases of switch tasks, by task name and case name
+    pub switch_cases: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Catch blocks, by the name of their try task
+    pub catch_blocks: BTreeMap<String, u64>,
+}
+
+/// Covered and total counts of one kind of coverage point
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub covered: usize,
+    pub total: usize,
+}
+
+impl Tally {
+    fn add(&mut self, hits: u64) {
+        self.total += 1;
+        if hits > 0 {
+            self.covered += 1;
+        }
+    }
+
+    /// Percentage of points covered; 100 if there are none
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.covered as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+impl WorkflowCoverage {
+    /// Covered and total tasks, switch cases and catch blocks
+    #[must_use]
+    pub fn tallies(&self) -> (Tally, Tally, Tally) {
+        let mut tasks = Tally::default();
+        self.tasks.values().for_each(|hits| tasks.add(*hits));
+        let mut cases = Tally::default();
+        self.switch_cases
+            .values()
+            .flat_map(BTreeMap::values)
+            .for_each(|hits| cases.add(*hits));
+        let mut catches = Tally::default();
+        self.catch_blocks
+            .values()
+            .for_each(|hits| catches.add(*hits));
+        (tasks, cases, catches)
+    }
+}
+
+impl Coverage {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the tasks, switch cases and catch blocks of a workflow, unhit
+    pub fn add_workflow(&mut self, workflow: &WorkflowDefinition) {
+        let coverage = self.workflows.entry(workflow_key(workflow)).or_default();
+        if let Ok(tasks) = serde_json::to_value(&workflow.do_) {
+            collect_points(&tasks, coverage);
+        }
+    }
+
+    /// Count the hits recorded in the events of test runs
+    ///
+    /// Events are attributed to workflows by the `WorkflowStarted` event of
+    /// their instance; instances of workflows that were not added are ignored.
+    pub fn record(&mut self, events: &[WorkflowEvent]) {
+        let mut instances: HashMap<&str, &str> = HashMap::new();
+        for event in events {
+            if let WorkflowEvent::WorkflowStarted {
+                instance_id,
+                workflow_id,
+                ..
+            } = event
+            {
+                instances.insert(instance_id, workflow_id);
+            }
+        }
+
+        for event in events {
+            let Some(coverage) = instances
+                .get(event.instance_id())
+                .and_then(|workflow_id| self.workflows.get_mut(*workflow_id))
+            else {
+                continue;
+            };
+            match event {
+                WorkflowEvent::TaskStarted { task_name, .. } => {
+                    if let Some(hits) = coverage.tasks.get_mut(task_name) {
+                        *hits += 1;
+                    }
+                }
+                WorkflowEvent::SwitchCaseMatched {
+                    task_name, case, ..
+                } => {
+                    if let Some(hits) = coverage
+                        .switch_cases
+                        .get_mut(task_name)
+                        .and_then(|cases| cases.get_mut(case))
+                    {
+                        *hits += 1;
+                    }
+                }
+                WorkflowEvent::ErrorCaught { task_name, .. } => {
+                    if let Some(hits) = coverage.catch_blocks.get_mut(task_name) {
+                        *hits += 1;
+                    }
+                }
+                WorkflowEvent::WorkflowStarted { .. }
+                | WorkflowEvent::TaskEntered { .. }
+                | WorkflowEvent::TaskCreated { .. }
+                | WorkflowEvent::TaskRetried { .. }
+                | WorkflowEvent::TaskCompleted { .. }
+                | WorkflowEvent::WorkflowCompleted { .. }
+                | WorkflowEvent::WorkflowCorrelationStarted { .. }
+                | WorkflowEvent::WorkflowCorrelationCompleted { .. }
+                | WorkflowEvent::WorkflowFailed { .. }
+                | WorkflowEvent::WorkflowCancelled { .. }
+                | WorkflowEvent::WorkflowSuspended { .. }
+                | WorkflowEvent::WorkflowResumed { .. }
+                | WorkflowEvent::TaskCancelled { .. }
+                | WorkflowEvent::TaskSuspended { .. }
+                | WorkflowEvent::TaskResumed { .. }
+                | WorkflowEvent::TaskFaulted { .. }
+                | WorkflowEvent::TaskReaped { .. }
+                | WorkflowEvent::TaskCacheHit { .. }
+                | WorkflowEvent::TaskSkipped { .. }
+                | WorkflowEvent::WatchesEvaluated { .. }
+                | WorkflowEvent::ShadowCompared { .. } => {}
+            }
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Coverage of each workflow, keyed by "namespace/name/version"
+    #[must_use]
+    pub fn workflows(&self) -> &BTreeMap<String, WorkflowCoverage> {
+        &self.workflows
+    }
+
+    /// Render the coverage report
+    #[must_use]
+    pub fn render(&self, format: CoverageFormat) -> String {
+        match format {
+            CoverageFormat::Text => self.render_text(),
+            CoverageFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+            CoverageFormat::Html => self.render_html(),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = String::new();
+        for (key, coverage) in &self.workflows {
+            let (tasks, cases, catches) = coverage.tallies();
+            let _ = writeln!(out, "{key}");
+            for (kind, tally) in [
+                ("Tasks", tasks),
+                ("Switch cases", cases),
+                ("Catch blocks", catches),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "  {kind:<13} {}/{} ({:.0}%)",
+                    tally.covered,
+                    tally.total,
+                    tally.percent()
+                );
+            }
+            for name in missed(coverage) {
+                let _ = writeln!(out, "    not hit: {name}");
+            }
+        }
+        out
+    }
+
+    fn render_html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Workflow coverage</title>\n<style>\n\
+             body { font-family: sans-serif; }\n\
+             table { border-collapse: collapse; margin-bottom: 2em; }\n\
+             td, th { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }\n\
+             .hit { background: #e6ffed; }\n.missed { background: #ffeef0; }\n\
+             </style>\n</head>\n<body>\n<h1>Workflow coverage</h1>\n",
+        );
+        for (key, coverage) in &self.workflows {
+            let (tasks, cases, catches) = coverage.tallies();
+            let _ = writeln!(
+                out,
+                "<h2>{}</h2>\n<p>Tasks {}/{}, switch cases {}/{}, catch blocks {}/{}</p>",
+                escape_html(key),
+                tasks.covered,
+                tasks.total,
+                cases.covered,
+                cases.total,
+                catches.covered,
+                catches.total
+            );
+            out.push_str("<table>\n<tr><th>Kind</th><th>Name</th><th>Hits</th></tr>\n");
+            for (kind, name, hits) in points(coverage) {
+                let _ = writeln!(
+                    out,
+                    "<tr class=\"{}\"><td>{kind}</td><td>{}</td><td>{hits}</td></tr>",
+                    if hits > 0 { "hit" } else { "missed" },
+                    escape_html(&name)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+fn workflow_key(workflow: &WorkflowDefinition) -> String {
+    format!(
+        "{}/{}/{}",
+        workflow.document.namespace, workflow.document.name, workflow.document.version
+    )
+}
+
+/// Add the coverage points of a task list and the tasks nested in it
+fn collect_points(tasks: &Value, coverage: &mut WorkflowCoverage) {
+    let Some(entries) = tasks.as_array() else {
+        return;
+    };
+    for (name, task) in entries.iter().filter_map(Value::as_object).flatten() {
+        coverage.tasks.entry(name.clone()).or_insert(0);
+        if let Some(cases) = task.get("switch").and_then(Value::as_array) {
+            let switch = coverage.switch_cases.entry(name.clone()).or_default();
+            for case in cases
+                .iter()
+                .filter_map(Value::as_object)
+                .flat_map(|case| case.keys())
+            {
+                switch.entry(case.clone()).or_insert(0);
+            }
+        }
+        if task.get("try").is_some() {
+            coverage.catch_blocks.entry(name.clone()).or_insert(0);
+        }
+        for pointer in ["/do", "/try", "/catch/do", "/fork/branches"] {
+            if let Some(nested) = task.pointer(pointer) {
+                collect_points(nested, coverage);
+            }
+        }
+    }
+}
+
+/// Every coverage point of a workflow: its kind, name and hits
+fn points(coverage: &WorkflowCoverage) -> Vec<(&'static str, String, u64)> {
+    let tasks = coverage
+        .tasks
+        .iter()
+        .map(|(name, hits)| ("task", name.clone(), *hits));
+    let cases = coverage.switch_cases.iter().flat_map(|(task, cases)| {
+        cases
+            .iter()
+            .map(move |(case, hits)| ("switch case", format!("{task}.{case}"), *hits))
+    });
+    let catches = coverage
+        .catch_blocks
+        .iter()
+        .map(|(task, hits)| ("catch block", format!("{task}.catch"), *hits));
+    tasks.chain(cases).chain(catches).collect()
+}
+
+/// Names of the coverage points of a workflow that were never hit
+fn missed(coverage: &WorkflowCoverage) -> Vec<String> {
+    points(coverage)
+        .into_iter()
+        .filter(|(_, _, hits)| *hits == 0)
+        .map(|(kind, name, _)| format!("{kind} {name}"))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use chrono::Utc;
+
+    const WORKFLOW: &str = r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: routing
+  version: '1.0.0'
+do:
+  - route:
+      switch:
+        - big:
+            when: ${ .n > 10 }
+            then: handleBig
+        - small:
+            then: handleSmall
+  - handleSmall:
+      try:
+        - risky:
+            set:
+              ok: true
+      catch:
+        do:
+          - recover:
+              set:
+                recovered: true
+      then: end
+  - handleBig:
+      set:
+        big: true
+"#;
+
+    fn started(task_name: &str) -> WorkflowEvent {
+        WorkflowEvent::TaskStarted {
+            instance_id: "i".to_string(),
+            task_name: task_name.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_records_hits_of_tasks_and_cases() {
+        let workflow: WorkflowDefinition = serde_yaml::from_str(WORKFLOW).unwrap();
+        let mut coverage = Coverage::new();
+        coverage.add_workflow(&workflow);
+        coverage.record(&[
+            WorkflowEvent::WorkflowStarted {
+                instance_id: "i".to_string(),
+                workflow_id: "default/routing/1.0.0".to_string(),
+                timestamp: Utc::now(),
+                initial_data: Value::Null,
+                metadata: None,
+            },
+            started("route"),
+            WorkflowEvent::SwitchCaseMatched {
+                instance_id: "i".to_string(),
+                task_name: "route".to_string(),
+                case: "small".to_string(),
+                timestamp: Utc::now(),
+            },
+            started("handleSmall"),
+            started("risky"),
+        ]);
+
+        let routing = coverage.workflows().get("default/routing/1.0.0").unwrap();
+        let (tasks, cases, catches) = routing.tallies();
+        assert_eq!(
+            tasks,
+            Tally {
+                covered: 3,
+                total: 5
+            }
+        );
+        assert_eq!(
+            cases,
+            Tally {
+                covered: 1,
+                total: 2
+            }
+        );
+        assert_eq!(
+            catches,
+            Tally {
+                covered: 0,
+                total: 1
+            }
+        );
+        assert_eq!(
+            missed(routing),
+            vec![
+                "task handleBig".to_string(),
+                "task recover".to_string(),
+                "switch case route.big".to_string(),
+                "catch block handleSmall.catch".to_string(),
+            ]
+        );
+    }
+}
@@ -1,35 +1,110 @@
 use async_recursion::async_recursion;
 use chrono::Utc;
-use petgraph::{graph::DiGraph, stable_graph::NodeIndex};
+use futures::FutureExt;
+use petgraph::stable_graph::NodeIndex;
 use serverless_workflow_core::models::task::TaskDefinition;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use snafu::prelude::*;
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::sync::{RwLock, Semaphore, broadcast};
+use tokio_util::sync::CancellationToken;
 
+#[cfg(feature = "viz")]
+use crate::providers::visualization::{self, ExecutionState};
 use crate::{
-    context::Context,
+    container::{ContainerOptions, ContainerRuntime},
+    context::{Context, MergeStrategy, Reaper},
+    events::{EventSink, InboundEvent},
     execution_handle::ExecutionHandle,
-    executor::Executor,
-    listeners::grpc::GrpcListener,
+    executor::{ErrorKind, Executor, Health, WorkflowError},
+    listeners::ListenerAuth,
+    middleware::{InstanceRequest, TaskMiddleware},
+    mock::MockRoutes,
+    numbers::NumberMode,
+    observer::{ObservedPersistence, WorkflowObserver},
     output,
     persistence::PersistenceProvider,
+    process,
     providers::{
-        executors::{OpenApiExecutor, PythonExecutor, RestExecutor, TypeScriptExecutor},
-        visualization::{D2Provider, ExecutionState, GraphvizProvider, VisualizationProvider},
+        container::{self, WarmPool},
+        executors::{
+            NativeFunctionExecutor, OpenApiExecutor, PythonExecutor, RestExecutor,
+            TypeScriptExecutor,
+        },
+        secrets::EnvSecretsProvider,
     },
+    secrets::SecretsProvider,
+    signing::SignatureVerifier,
+    task_env::EnvPolicy,
+    telemetry::Telemetry,
+    text::{MAX_ERROR_LEN, truncate},
     workflow::WorkflowEvent,
 };
 
 use super::cache::CacheProvider;
 
 // Submodules
-mod catalog;
+pub(crate) mod catalog;
+mod control;
+mod debug_bundle;
+mod definitions;
+mod dry_run;
 mod export;
+mod failures;
+mod flow;
 mod graph;
+mod lease;
 mod listeners;
+mod manifest;
+pub(crate) mod memory;
+mod preflight;
+mod queue;
+mod recovery;
+mod replay;
+pub(crate) mod resources;
+mod retry;
+mod schema;
+mod secrets;
+mod shadow;
+mod snapshot;
+pub(crate) mod stats;
+mod status;
+pub(crate) mod subworkflows;
 mod tasks;
-mod timeout;
+pub(crate) mod timeout;
+pub(crate) mod versions;
+mod watch;
+
+pub use crate::persistence::InstanceState;
+pub use catalog::FunctionRef;
+pub use debug_bundle::DebugBundle;
+pub use dry_run::{DryRunReport, DryRunStep};
+pub use failures::{ErrorFingerprint, FailureCluster};
+pub use graph::TaskGraph;
+pub use lease::DEFAULT_LEASE_TTL;
+pub use manifest::{ManifestDifference, ResourceDigest, RunManifest};
+#[cfg(feature = "jemalloc")]
+pub use memory::dump_heap_profile;
+pub use memory::{AllocatorStats, EventBusMemory, InstanceMemory, ListenerMemory, MemoryReport};
+pub use preflight::{PreflightProblem, PreflightReport};
+pub use queue::QueuedRun;
+pub use recovery::RecoveredRun;
+pub use replay::{Divergence, ReplayReport};
+pub use shadow::ShadowMode;
+pub use snapshot::{
+    EngineSnapshot, SNAPSHOT_FORMAT, SnapshotExpression, SnapshotWorkflow, WarmStart,
+};
+pub use stats::{DurationPercentiles, TaskFailures, WorkflowStats};
+pub use status::{InstanceFilter, InstanceStatus};
+pub use subworkflows::DEFAULT_MAX_CALL_DEPTH;
+pub use timeout::parse_iso8601_duration;
+
+/// Number of received events kept for listen tasks that have not consumed them yet
+const EVENT_BUS_CAPACITY: usize = 1024;
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
@@ -40,6 +115,10 @@ pub enum Error {
     #[snafu(display("Task execution error: {message}"))]
     TaskExecution { message: String },
 
+    /// An error raised by a `raise` task
+    #[snafu(display("{error}"))]
+    Raised { error: WorkflowError },
+
     #[snafu(display("Listener error: {message}"))]
     Listener { message: String },
 
@@ -49,9 +128,31 @@ pub enum Error {
     #[snafu(display("Timeout: {message}"))]
     Timeout { message: String },
 
+    #[snafu(display("Workflow instance not found: {instance_id}"))]
+    InstanceNotFound { instance_id: String },
+
+    #[snafu(display("Workflow instance {instance_id} is {from} and cannot become {to}"))]
+    IllegalTransition {
+        instance_id: String,
+        from: InstanceState,
+        to: InstanceState,
+    },
+
+    #[snafu(display("Workflow instance {instance_id} was suspended"))]
+    InstanceSuspended { instance_id: String },
+
+    #[snafu(display("Workflow instance {instance_id} is running in another process"))]
+    InstanceLeased { instance_id: String },
+
     #[snafu(display("I/O error: {source}"))]
     Io { source: std::io::Error },
 
+    #[snafu(display("Failed to read {resource}: {source}"))]
+    ResourceRead {
+        resource: String,
+        source: std::io::Error,
+    },
+
     #[snafu(display("Executor error: {source}"))]
     Executor { source: crate::executor::Error },
 
@@ -67,15 +168,20 @@ pub enum Error {
     #[snafu(display("Expression error: {source}"))]
     Expression { source: crate::expressions::Error },
 
+    #[snafu(display("Invalid workflow graph: {source}"))]
+    Graph { source: crate::graph::Error },
+
     #[snafu(display("Serialization error: {source}"))]
     Serialization { source: serde_json::Error },
 
     #[snafu(display("Listener setup error: {source}"))]
     ListenerSetup { source: crate::listeners::Error },
 
+    #[cfg(feature = "grpc")]
     #[snafu(display("Protobuf compilation error: {source}"))]
     Protobuf { source: protox::Error },
 
+    #[cfg(feature = "grpc")]
     #[snafu(display("Protobuf descriptor error: {source}"))]
     ProtobufDescriptor {
         source: prost_reflect::DescriptorError,
@@ -85,6 +191,22 @@ pub enum Error {
     Visualization {
         source: crate::providers::visualization::Error,
     },
+
+    #[snafu(display("{source}"))]
+    Middleware { source: crate::middleware::Error },
+
+    #[snafu(display("Secret error: {source}"))]
+    Secret { source: crate::secrets::Error },
+
+    #[snafu(display("Event error: {source}"))]
+    Event { source: crate::events::Error },
+
+    #[snafu(display("Pre-flight checks failed: {report}"))]
+    Preflight { report: PreflightReport },
+
+    /// Input or output that does not match its `schema`
+    #[snafu(display("Schema validation error: {message}"))]
+    SchemaValidation { message: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -114,6 +236,12 @@ impl From<crate::expressions::Error> for Error {
     }
 }
 
+impl From<crate::graph::Error> for Error {
+    fn from(source: crate::graph::Error) -> Self {
+        Error::Graph { source }
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(source: serde_json::Error) -> Self {
         Error::Serialization { source }
@@ -132,12 +260,14 @@ impl From<crate::listeners::Error> for Error {
     }
 }
 
+#[cfg(feature = "grpc")]
 impl From<protox::Error> for Error {
     fn from(source: protox::Error) -> Self {
         Error::Protobuf { source }
     }
 }
 
+#[cfg(feature = "grpc")]
 impl From<prost_reflect::DescriptorError> for Error {
     fn from(source: prost_reflect::DescriptorError) -> Self {
         Error::ProtobufDescriptor { source }
@@ -150,20 +280,247 @@ impl From<crate::providers::visualization::Error> for Error {
     }
 }
 
+impl From<crate::middleware::Error> for Error {
+    fn from(source: crate::middleware::Error) -> Self {
+        Error::Middleware { source }
+    }
+}
+
+impl From<crate::secrets::Error> for Error {
+    fn from(source: crate::secrets::Error) -> Self {
+        Error::Secret { source }
+    }
+}
+
+impl From<crate::events::Error> for Error {
+    fn from(source: crate::events::Error) -> Self {
+        Error::Event { source }
+    }
+}
+
+impl Error {
+    /// What went wrong, if the failure was categorized
+    ///
+    /// Executors categorize the failures of the work they start; a task
+    /// running out of time is a timeout.
+    #[must_use]
+    pub fn kind(&self) -> Option<ErrorKind> {
+        if let Error::Executor { source } = self {
+            source.kind()
+        } else if let Error::Timeout { .. } = self {
+            Some(ErrorKind::Timeout)
+        } else {
+            None
+        }
+    }
+
+    /// The error as the workflow sees it, e.g. in `catch` filters
+    ///
+    /// Raised errors and the errors executors describe are kept as they are.
+    /// Other failures get the standard type of their category, with the
+    /// error message as the detail; failures that fit no other type are
+    /// runtime errors. The instance is only set if the error carried one.
+    #[must_use]
+    pub fn to_workflow_error(&self) -> WorkflowError {
+        if let Error::Raised { error } = self {
+            return error.clone();
+        }
+        if let Error::Executor { source } = self
+            && let Some(error) = source.workflow_error()
+        {
+            return error.clone();
+        }
+
+        let error = if let Some(kind) = self.kind() {
+            let name = kind.name();
+            let title: String = name
+                .chars()
+                .take(1)
+                .flat_map(char::to_uppercase)
+                .chain(name.chars().skip(1))
+                .collect();
+            WorkflowError::new(kind.error_type(), kind.status(), format!("{title} Error"))
+        } else if let Error::Expression { .. }
+        | Error::Graph {
+            source: crate::graph::Error::Expression { .. },
+        } = self
+        {
+            WorkflowError::new(WorkflowError::EXPRESSION, 400, "Expression Error")
+        } else if let Error::SchemaValidation { .. } = self {
+            WorkflowError::new(WorkflowError::VALIDATION, 400, "Validation Error")
+        } else if let Error::Configuration { .. } = self {
+            WorkflowError::new(WorkflowError::CONFIGURATION, 400, "Configuration Error")
+        } else if let Error::Middleware {
+            source: crate::middleware::Error::Denied { .. },
+        } = self
+        {
+            WorkflowError::new(WorkflowError::AUTHORIZATION, 403, "Authorization Error")
+        } else {
+            WorkflowError::new(WorkflowError::RUNTIME, 500, "Runtime Error")
+        };
+        error.with_detail(self.to_string())
+    }
+}
+
+/// Settings of a [`DurableEngine`], gathered by [`crate::DurableEngineBuilder`]
+pub(crate) struct EngineConfig {
+    pub(crate) persistence: Arc<dyn PersistenceProvider>,
+    pub(crate) cache: Arc<dyn CacheProvider>,
+    pub(crate) event_buffer_size: usize,
+    pub(crate) base_dir: Option<PathBuf>,
+    pub(crate) middleware: Vec<Arc<dyn TaskMiddleware>>,
+    pub(crate) env_policy: EnvPolicy,
+    pub(crate) secrets: Arc<dyn SecretsProvider>,
+    pub(crate) container_defaults: ContainerOptions,
+    pub(crate) container_pool: WarmPool,
+    pub(crate) container_runtime: ContainerRuntime,
+    pub(crate) event_sinks: Vec<Arc<dyn EventSink>>,
+    pub(crate) fork_max_parallelism: Option<usize>,
+    pub(crate) for_parallelism: Option<usize>,
+    pub(crate) number_mode: NumberMode,
+    pub(crate) mock_routes: MockRoutes,
+    pub(crate) cache_ttl: Option<std::time::Duration>,
+    pub(crate) lease_ttl: std::time::Duration,
+    pub(crate) reads: Option<Arc<dyn PersistenceProvider>>,
+    pub(crate) watch_exprs: Vec<String>,
+    pub(crate) manifest_config: Option<serde_json::Value>,
+    pub(crate) shadows: Vec<(WorkflowDefinition, ShadowMode)>,
+    pub(crate) listener_auth: Option<ListenerAuth>,
+    pub(crate) catalog_dir: Option<PathBuf>,
+    pub(crate) workflow_sources: Vec<String>,
+    pub(crate) max_call_depth: usize,
+    pub(crate) verifier: SignatureVerifier,
+    pub(crate) strict_env: bool,
+    pub(crate) telemetry: Option<Arc<Telemetry>>,
+    pub(crate) snapshot: Option<EngineSnapshot>,
+}
+
+impl EngineConfig {
+    /// The default settings, with the given providers
+    pub(crate) fn new(
+        persistence: Arc<dyn PersistenceProvider>,
+        cache: Arc<dyn CacheProvider>,
+    ) -> Self {
+        Self {
+            persistence,
+            cache,
+            event_buffer_size: 1000,
+            base_dir: None,
+            middleware: Vec::new(),
+            env_policy: EnvPolicy::default(),
+            secrets: Arc::new(EnvSecretsProvider::new()),
+            container_defaults: ContainerOptions::default(),
+            container_pool: WarmPool::default(),
+            container_runtime: ContainerRuntime::default(),
+            event_sinks: Vec::new(),
+            fork_max_parallelism: None,
+            for_parallelism: None,
+            number_mode: NumberMode::default(),
+            mock_routes: MockRoutes::default(),
+            cache_ttl: None,
+            lease_ttl: lease::DEFAULT_LEASE_TTL,
+            reads: None,
+            watch_exprs: Vec::new(),
+            manifest_config: None,
+            shadows: Vec::new(),
+            listener_auth: None,
+            catalog_dir: None,
+            workflow_sources: Vec::new(),
+            max_call_depth: subworkflows::DEFAULT_MAX_CALL_DEPTH,
+            verifier: SignatureVerifier::default(),
+            strict_env: false,
+            telemetry: None,
+            snapshot: None,
+        }
+    }
+}
+
+/// Clones share the executors, providers, registries and listeners of the
+/// engine they are cloned from
+#[derive(Clone)]
 pub struct DurableEngine {
     executors: Arc<HashMap<String, Box<dyn Executor>>>,
     persistence: Arc<dyn PersistenceProvider>,
+    /// Serves instance listings and status queries, e.g. from a read replica
+    reads: Arc<dyn PersistenceProvider>,
     cache: Arc<dyn CacheProvider>,
+    /// How long task results stay cached when tasks do not set `metadata.cacheTtl`
+    cache_ttl: Option<std::time::Duration>,
     /// Registry of active gRPC listeners, keyed by bind address
     /// Using Arc<GrpcListener> to allow adding methods progressively
-    grpc_listeners: Arc<RwLock<HashMap<String, Arc<GrpcListener>>>>,
+    #[cfg(feature = "grpc")]
+    grpc_listeners: Arc<RwLock<HashMap<String, Arc<crate::listeners::grpc::GrpcListener>>>>,
     /// Registry of active HTTP listeners, keyed by bind address
     /// Using Arc<HttpListener> to allow adding routes progressively
     http_listeners: Arc<RwLock<HashMap<String, Arc<crate::listeners::HttpListener>>>>,
     /// Registry of workflows for nested execution, keyed by "namespace/name/version"
     workflow_registry: Arc<RwLock<HashMap<String, WorkflowDefinition>>>,
+    /// Instances currently running in this engine, keyed by instance ID
+    active_instances: control::ActiveInstances,
+    /// Directories of the files workflows were loaded from, keyed by "namespace/name/version"
+    workflow_dirs: Arc<RwLock<HashMap<String, PathBuf>>>,
+    /// Metadata recorded on instances of a workflow, keyed by "namespace/name/version"
+    workflow_metadata: Arc<RwLock<HashMap<String, serde_json::Map<String, serde_json::Value>>>>,
+    /// Directory that overrides the workflow directory when resolving relative resource paths
+    base_dir: Option<PathBuf>,
+    /// Hooks consulted before instances start and tasks execute, in registration order
+    middleware: Arc<Vec<Arc<dyn TaskMiddleware>>>,
+    /// Variables of the process environment passed to script, shell and container tasks
+    env_policy: Arc<EnvPolicy>,
+    /// Source of the secrets workflows declare in `use.secrets` and tasks
+    /// request with `metadata.env.fromSecret`
+    secrets: Arc<dyn SecretsProvider>,
+    /// Settings of container tasks that do not set them in `metadata.container`
+    container_defaults: Arc<ContainerOptions>,
+    /// Containers kept running between executions of tasks with `keepWarm` set
+    container_pool: Arc<WarmPool>,
+    /// Container runtime that runs container tasks
+    container_runtime: ContainerRuntime,
+    /// Destinations of the events published by `emit` tasks
+    event_sinks: Arc<Vec<Arc<dyn EventSink>>>,
+    /// Events received by listeners or emitted by workflows, which listen tasks consume
+    event_bus: broadcast::Sender<InboundEvent>,
+    /// Permits shared by the branches of all forks, bounding how many run at once
+    fork_permits: Option<Arc<Semaphore>>,
+    /// Iterations run at once by parallel `for` loops that set no limit
+    for_parallelism: Option<usize>,
+    /// How the numbers of workflow inputs and task outputs are held
+    number_mode: NumberMode,
+    /// Base URLs of APIs whose calls go to mock servers instead
+    mock_routes: Arc<MockRoutes>,
     /// Event buffer size for streaming execution
     event_buffer_size: usize,
+    /// Identifies this engine as the holder of instance leases
+    owner: String,
+    /// How long an instance lease lasts without being renewed
+    lease_ttl: std::time::Duration,
+    /// Expressions evaluated against the workflow data after every task
+    watch_exprs: Arc<Vec<String>>,
+    /// Configuration recorded in the run manifest of each instance, if manifests are recorded
+    manifest_config: Option<Arc<serde_json::Value>>,
+    /// Candidate versions run in the shadow of the workflows they replace
+    shadows: Arc<Vec<shadow::Shadow>>,
+    /// Results the tasks of a shadow run take instead of running, if this
+    /// engine runs one
+    shadow_stubs: Option<Arc<shadow::ShadowStubs>>,
+    /// Shadow runs going on in the background
+    shadow_runs: Arc<std::sync::Mutex<Vec<tokio::task::JoinHandle<()>>>>,
+    /// How HTTP listeners authenticate requests when their source sets no `authentication`
+    listener_auth: Option<Arc<ListenerAuth>>,
+    /// Functions resolved from catalogs, and where git catalogs are cloned
+    catalogs: Arc<catalog::Catalogs>,
+    /// Where sub-workflows that are not registered are looked up
+    subworkflows: Arc<subworkflows::WorkflowSources>,
+    /// How deep workflows may call each other
+    max_call_depth: usize,
+    /// Checks the sub-workflows and catalog functions the engine loads itself
+    verifier: Arc<SignatureVerifier>,
+    /// Whether unset variables in their `${{ env.X }}` placeholders are errors
+    strict_env: bool,
+    /// Metrics of the workflows the engine runs and the requests its listeners handle
+    telemetry: Option<Arc<Telemetry>>,
+    /// Documents and descriptors restored from the snapshot the engine was started from
+    warm_start: Arc<WarmStart>,
 }
 
 impl std::fmt::Debug for DurableEngine {
@@ -177,13 +534,16 @@ impl std::fmt::Debug for DurableEngine {
 impl DurableEngine {
     /// Create a new ``DurableEngine`` instance
     ///
+    /// Unlike [`crate::DurableEngineBuilder`], the engine keeps no workflow
+    /// rollups with `persistence`.
+    ///
     /// # Errors
     /// Currently, this function does not return errors, but returns `Result` for future extensibility
     pub fn new(
         persistence: Arc<dyn PersistenceProvider>,
         cache: Arc<dyn CacheProvider>,
     ) -> Result<Self> {
-        Self::new_with_config(persistence, cache, 1000)
+        Self::new_with_config(EngineConfig::new(persistence, cache))
     }
 
     /// Create a new ``DurableEngine`` instance with custom configuration
@@ -191,12 +551,45 @@ impl DurableEngine {
     /// This is used internally by the builder. Use [`crate::DurableEngineBuilder`] instead.
     ///
     /// # Errors
-    /// Currently, this function does not return errors, but returns `Result` for future extensibility
-    pub(crate) fn new_with_config(
-        persistence: Arc<dyn PersistenceProvider>,
-        cache: Arc<dyn CacheProvider>,
-        event_buffer_size: usize,
-    ) -> Result<Self> {
+    /// Returns an error if the engine is started from a snapshot it cannot use,
+    /// or holds numbers in a mode this build does not support.
+    pub(crate) fn new_with_config(config: EngineConfig) -> Result<Self> {
+        let EngineConfig {
+            persistence,
+            cache,
+            event_buffer_size,
+            base_dir,
+            middleware,
+            env_policy,
+            secrets,
+            container_defaults,
+            container_pool,
+            container_runtime,
+            event_sinks,
+            fork_max_parallelism,
+            for_parallelism,
+            number_mode,
+            mock_routes,
+            cache_ttl,
+            lease_ttl,
+            reads,
+            watch_exprs,
+            manifest_config,
+            shadows,
+            listener_auth,
+            catalog_dir,
+            workflow_sources,
+            max_call_depth,
+            verifier,
+            strict_env,
+            telemetry,
+            snapshot,
+        } = config;
+        if !number_mode.is_available() {
+            return Err(Error::Configuration {
+                message: crate::numbers::Error::Unavailable.to_string(),
+            });
+        }
         let mut executors: HashMap<String, Box<dyn Executor>> = HashMap::new();
         executors.insert(
             "http".into(),
@@ -210,17 +603,78 @@ impl DurableEngine {
             "openapi".into(),
             Box::new(OpenApiExecutor(reqwest::Client::new())),
         );
+        #[cfg(feature = "grpc")]
+        executors.insert(
+            "grpc".into(),
+            Box::new(crate::providers::executors::GrpcExecutor::new()),
+        );
+        #[cfg(feature = "mqtt")]
+        executors.insert(
+            "asyncapi".into(),
+            Box::new(crate::providers::executors::AsyncApiExecutor(
+                reqwest::Client::new(),
+            )),
+        );
         executors.insert("python".into(), Box::new(PythonExecutor::new()));
         executors.insert("javascript".into(), Box::new(TypeScriptExecutor::new()));
-        Ok(Self {
+        #[cfg(feature = "wasm")]
+        executors.insert(
+            "wasm".into(),
+            Box::new(crate::providers::executors::WasmExecutor::new()),
+        );
+        executors.insert("native".into(), Box::new(NativeFunctionExecutor::new()));
+        let mut engine = Self {
             executors: Arc::new(executors),
+            reads: reads.unwrap_or_else(|| persistence.clone()),
             persistence,
             cache,
+            cache_ttl,
+            #[cfg(feature = "grpc")]
             grpc_listeners: Arc::new(RwLock::new(HashMap::new())),
             http_listeners: Arc::new(RwLock::new(HashMap::new())),
             workflow_registry: Arc::new(RwLock::new(HashMap::new())),
+            active_instances: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            workflow_dirs: Arc::new(RwLock::new(HashMap::new())),
+            workflow_metadata: Arc::new(RwLock::new(HashMap::new())),
+            base_dir,
+            middleware: Arc::new(middleware),
+            env_policy: Arc::new(env_policy),
+            secrets,
+            container_defaults: Arc::new(container_defaults),
+            container_pool: Arc::new(container_pool),
+            container_runtime,
+            event_sinks: Arc::new(event_sinks),
+            event_bus: broadcast::channel(EVENT_BUS_CAPACITY).0,
+            fork_permits: fork_max_parallelism.map(|limit| Arc::new(Semaphore::new(limit))),
+            for_parallelism,
+            number_mode,
+            mock_routes: Arc::new(mock_routes),
             event_buffer_size,
-        })
+            owner: uuid::Uuid::new_v4().to_string(),
+            lease_ttl,
+            watch_exprs: Arc::new(watch_exprs),
+            manifest_config: manifest_config.map(Arc::new),
+            shadows: Arc::new(
+                shadows
+                    .into_iter()
+                    .map(|(candidate, mode)| shadow::Shadow { candidate, mode })
+                    .collect(),
+            ),
+            shadow_stubs: None,
+            shadow_runs: Arc::default(),
+            listener_auth: listener_auth.map(Arc::new),
+            catalogs: Arc::new(catalog::Catalogs::new(catalog_dir)),
+            subworkflows: Arc::new(subworkflows::WorkflowSources::new(workflow_sources)),
+            max_call_depth,
+            verifier: Arc::new(verifier),
+            strict_env,
+            telemetry,
+            warm_start: Arc::default(),
+        };
+        if let Some(snapshot) = snapshot {
+            engine.restore_snapshot(snapshot)?;
+        }
+        Ok(engine)
     }
 
     /// Validate workflow graph structure without executing
@@ -231,10 +685,7 @@ impl DurableEngine {
     /// Returns an error if the workflow graph cannot be built due to structural issues.
     pub fn validate_workflow_graph(
         workflow: &WorkflowDefinition,
-    ) -> Result<(
-        DiGraph<(String, TaskDefinition), ()>,
-        HashMap<String, NodeIndex>,
-    )> {
+    ) -> Result<(TaskGraph<'_>, HashMap<&str, NodeIndex>)> {
         graph::build_graph(workflow)
     }
 
@@ -254,6 +705,178 @@ impl DurableEngine {
         Ok(())
     }
 
+    #[allow(dead_code)]
+    /// Register an async Rust function that `call` tasks can call by name
+    ///
+    /// A task with `call: <name>` runs the function in-process, passing it the
+    /// call's `with` arguments, or the task input if there are none; its
+    /// output is the task's output and an `Err` fails the task. Functions can
+    /// also be called with `call: native`, naming them in `with.function`.
+    /// Registering a name again replaces the function.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use jackdaw::DurableEngineBuilder;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let engine = DurableEngineBuilder::new().build()?;
+    /// engine.register_function("fraud-check", |input| async move {
+    ///     let amount = input["amount"].as_f64().unwrap_or_default();
+    ///     Ok::<_, String>(serde_json::json!({ "suspicious": amount > 10_000.0 }))
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if `name` is a built-in call type, such as `http`.
+    pub fn register_function<F, Fut, E>(&self, name: &str, function: F) -> Result<()>
+    where
+        F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = std::result::Result<serde_json::Value, E>>
+            + Send
+            + 'static,
+        E: std::fmt::Display,
+    {
+        if self.executors.contains_key(name) {
+            return Err(Error::Configuration {
+                message: format!("Cannot register function {name}: it is a built-in call type"),
+            });
+        }
+        let native = self
+            .native_functions()
+            .ok_or_else(|| Error::Configuration {
+                message: "The native function executor is not available".to_string(),
+            })?;
+        native.register(
+            name,
+            Arc::new(move |input| {
+                let output = function(input);
+                async move { output.await.map_err(|e| e.to_string()) }.boxed()
+            }),
+        );
+        Ok(())
+    }
+
+    /// The executor of registered Rust functions
+    pub(crate) fn native_functions(&self) -> Option<&NativeFunctionExecutor> {
+        self.executors
+            .get("native")
+            .and_then(|executor| executor.as_any().downcast_ref())
+    }
+
+    /// Attach metadata to every instance of `workflow` started by this engine
+    ///
+    /// The metadata is recorded in the instance's `WorkflowStarted` event, e.g.
+    /// the source and digest of the bundle the workflow was loaded from.
+    pub async fn set_workflow_metadata(
+        &self,
+        workflow: &WorkflowDefinition,
+        metadata: serde_json::Map<String, serde_json::Value>,
+    ) {
+        let key = format!(
+            "{}/{}/{}",
+            workflow.document.namespace, workflow.document.name, workflow.document.version
+        );
+        self.workflow_metadata.write().await.insert(key, metadata);
+    }
+
+    async fn workflow_metadata(
+        &self,
+        workflow: &WorkflowDefinition,
+    ) -> Option<serde_json::Map<String, serde_json::Value>> {
+        let key = format!(
+            "{}/{}/{}",
+            workflow.document.namespace, workflow.document.name, workflow.document.version
+        );
+        self.workflow_metadata.read().await.get(&key).cloned()
+    }
+
+    /// Look up a registered workflow by its "namespace/name/version" key
+    pub async fn get_registered_workflow(&self, key: &str) -> Option<WorkflowDefinition> {
+        self.workflow_registry.read().await.get(key).cloned()
+    }
+
+    /// List the keys of all registered workflows, sorted alphabetically
+    pub async fn registered_workflows(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .workflow_registry
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Check that every registered executor can run tasks
+    ///
+    /// Returns the health of each executor by call type, such as a missing
+    /// Python interpreter. Each executor checks its dependencies once and
+    /// reuses the result.
+    pub async fn check_executors(&self) -> BTreeMap<String, Health> {
+        let mut health = BTreeMap::new();
+        for (call_type, executor) in self.executors.iter() {
+            health.insert(call_type.clone(), executor.health_check().await);
+        }
+        health
+    }
+
+    /// Lookups of cached task results made through this engine's cache, and
+    /// the entries it stores
+    ///
+    /// # Errors
+    /// Returns an error if the cache provider cannot read its entries.
+    pub async fn cache_stats(&self) -> Result<crate::cache::CacheStats> {
+        Ok(self.cache.stats().await?)
+    }
+
+    /// The telemetry the engine reports to, if it was built with one
+    #[must_use]
+    pub fn telemetry(&self) -> Option<&Arc<Telemetry>> {
+        self.telemetry.as_ref()
+    }
+
+    /// The engine's metrics in the Prometheus text format
+    ///
+    /// These are the gauges of the [`MemoryReport`], the lookups and hit
+    /// ratio of the cache and, if the engine was built with one, the metrics
+    /// of its [`Telemetry`].
+    ///
+    /// # Errors
+    /// Returns an error if the cache cannot report its footprint or lookups.
+    pub async fn metrics(&self) -> Result<String> {
+        let mut metrics = self.memory_report().await?.to_prometheus();
+        metrics.push_str(&crate::telemetry::cache_metrics(&self.cache_stats().await?));
+        if let Some(telemetry) = &self.telemetry {
+            metrics.push_str(&telemetry.to_prometheus());
+        }
+        Ok(metrics)
+    }
+
+    /// Remove the idle containers kept running for tasks with `keepWarm` set
+    ///
+    /// Call this before the engine is dropped; warm containers otherwise keep
+    /// running after the process exits.
+    pub async fn drain_container_pool(&self) {
+        if let Ok(provider) =
+            container::connect(self.container_runtime, self.container_pool.clone()).await
+        {
+            provider.drain_pool().await;
+        }
+    }
+
+    /// Release what the engine's tasks left running outside the process
+    ///
+    /// Removes the idle warm containers and kills the processes that shell
+    /// and script tasks started and left running after their command exited.
+    /// Call this before the engine is dropped.
+    pub async fn shutdown(&self) {
+        self.drain_container_pool().await;
+        process::reap_orphans();
+    }
+
     #[allow(dead_code)]
     /// Wait for a workflow instance to complete
     ///
@@ -280,6 +903,24 @@ impl DurableEngine {
                             message: format!("Workflow failed: {error}"),
                         });
                     }
+                    WorkflowEvent::WorkflowCancelled { reason, .. } => {
+                        return Err(Error::WorkflowExecution {
+                            message: format!(
+                                "Workflow cancelled: {}",
+                                reason.as_deref().unwrap_or("No reason provided")
+                            ),
+                        });
+                    }
+                    WorkflowEvent::WorkflowSuspended { reason, .. } => {
+                        return Err(Error::WorkflowExecution {
+                            message: format!(
+                                "Workflow suspended: {}",
+                                reason.as_deref().unwrap_or("No reason provided")
+                            ),
+                        });
+                    }
+                    // Anything earlier belongs to a run before the instance was resumed
+                    WorkflowEvent::WorkflowResumed { .. } => break,
                     WorkflowEvent::WorkflowStarted { .. }
                     | WorkflowEvent::TaskCreated { .. }
                     | WorkflowEvent::TaskStarted { .. }
@@ -288,13 +929,17 @@ impl DurableEngine {
                     | WorkflowEvent::TaskCompleted { .. }
                     | WorkflowEvent::WorkflowCorrelationStarted { .. }
                     | WorkflowEvent::WorkflowCorrelationCompleted { .. }
-                    | WorkflowEvent::WorkflowCancelled { .. }
-                    | WorkflowEvent::WorkflowSuspended { .. }
-                    | WorkflowEvent::WorkflowResumed { .. }
                     | WorkflowEvent::TaskCancelled { .. }
                     | WorkflowEvent::TaskSuspended { .. }
                     | WorkflowEvent::TaskResumed { .. }
-                    | WorkflowEvent::TaskFaulted { .. } => {}
+                    | WorkflowEvent::TaskFaulted { .. }
+                    | WorkflowEvent::TaskReaped { .. }
+                    | WorkflowEvent::TaskCacheHit { .. }
+                    | WorkflowEvent::TaskSkipped { .. }
+                    | WorkflowEvent::SwitchCaseMatched { .. }
+                    | WorkflowEvent::ErrorCaught { .. }
+                    | WorkflowEvent::WatchesEvaluated { .. }
+                    | WorkflowEvent::ShadowCompared { .. } => {}
                 }
             }
 
@@ -313,6 +958,11 @@ impl DurableEngine {
     #[allow(dead_code)]
     /// Resume a workflow execution from a previously saved checkpoint
     ///
+    /// Tasks completed by earlier runs are replayed from the instance's event
+    /// history, restoring their outputs and following the branches they took,
+    /// and execution continues with the first task that did not complete. Use
+    /// [`DurableEngine::resume_instance`] to continue a suspended instance.
+    ///
     /// # Errors
     /// Returns an error if the workflow execution fails or if the instance cannot be resumed
     pub async fn resume(
@@ -374,19 +1024,81 @@ impl DurableEngine {
         workflow: WorkflowDefinition,
         input: serde_json::Value,
     ) -> Result<ExecutionHandle> {
+        self.start(workflow, input, None).await
+    }
+
+    #[allow(dead_code)]
+    /// Execute a workflow, reporting every event it records to an observer
+    ///
+    /// Like [`DurableEngine::execute`], but the observer is called with each
+    /// event as the instance records it, including the task events the
+    /// handle does not stream, so progress can be rendered live instead of
+    /// polling persistence. Pass an [`tokio::sync::mpsc::Sender`] to receive
+    /// the events on a channel; it is dropped, closing the channel, when the
+    /// instance finishes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use jackdaw::DurableEngineBuilder;
+    /// # use jackdaw::workflow::WorkflowEvent;
+    /// # use serverless_workflow_core::models::workflow::WorkflowDefinition;
+    /// # use std::sync::Arc;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let engine = DurableEngineBuilder::new().build()?;
+    /// # let workflow_yaml = "document:\n  dsl: '1.0.2'\n  namespace: test\n  name: test\n  version: 1.0.0\ndo: []";
+    /// # let workflow: WorkflowDefinition = serde_yaml::from_str(workflow_yaml)?;
+    /// let (events, mut progress) = tokio::sync::mpsc::channel(100);
+    /// let _handle = engine
+    ///     .start_with_observer(workflow, serde_json::json!({}), Arc::new(events))
+    ///     .await?;
+    ///
+    /// while let Some(event) = progress.recv().await {
+    ///     if let WorkflowEvent::TaskCompleted { task_name, .. } = event {
+    ///         println!("{task_name} done");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the workflow execution fails
+    pub async fn start_with_observer(
+        &self,
+        workflow: WorkflowDefinition,
+        input: serde_json::Value,
+        observer: Arc<dyn WorkflowObserver>,
+    ) -> Result<ExecutionHandle> {
+        self.start(workflow, input, Some(observer)).await
+    }
+
+    async fn start(
+        &self,
+        workflow: WorkflowDefinition,
+        input: serde_json::Value,
+        observer: Option<Arc<dyn WorkflowObserver>>,
+    ) -> Result<ExecutionHandle> {
+        let input = self.number_mode.apply(input);
+
         // Create channels for event streaming and cancellation
         let (event_tx, event_rx) = tokio::sync::mpsc::channel(self.event_buffer_size);
-        let (cancel_tx, _cancel_rx) = tokio::sync::mpsc::channel::<()>(1);
+        let (cancel_tx, mut cancel_rx) = tokio::sync::mpsc::channel::<()>(1);
 
         let instance_id = uuid::Uuid::new_v4().to_string();
+        self.transition(&instance_id, InstanceState::Pending)
+            .await?;
+
+        let started_metadata = self.workflow_metadata(&workflow).await;
 
-        // Clone what we need for the background task
-        let executors = self.executors.clone();
-        let persistence = self.persistence.clone();
-        let cache = self.cache.clone();
-        let workflow_registry = self.workflow_registry.clone();
-        let grpc_listeners = self.grpc_listeners.clone();
-        let http_listeners = self.http_listeners.clone();
+        // The background task runs the instance in a clone of this engine,
+        // recording its events through the observer if there is one
+        let mut temp_engine = self.clone();
+        if let Some(observer) = observer {
+            temp_engine.persistence =
+                Arc::new(ObservedPersistence::new(self.persistence.clone(), observer));
+        }
+        let persistence = temp_engine.persistence.clone();
 
         let instance_id_clone = instance_id.clone();
 
@@ -404,24 +1116,36 @@ impl DurableEngine {
                     ),
                     timestamp: Utc::now(),
                     initial_data: input.clone(),
+                    metadata: started_metadata,
                 })
                 .await;
 
-            // Create a temporary engine for this execution
-            // This is needed because we can't move self into the spawned task
-            let temp_engine = match DurableEngine::new(persistence.clone(), cache.clone()) {
-                Ok(mut engine) => {
-                    engine.executors = executors;
-                    engine.workflow_registry = workflow_registry;
-                    engine.grpc_listeners = grpc_listeners;
-                    engine.http_listeners = http_listeners;
-                    engine
-                }
-                Err(e) => {
+            // Execute the workflow, stopping it if the handle is cancelled
+            let start_time = Utc::now();
+            let run = temp_engine.run_instance(
+                workflow.clone(),
+                Some(instance_id_clone.clone()),
+                input.clone(),
+            );
+            tokio::pin!(run);
+            let result = tokio::select! {
+                result = &mut run => result,
+                Some(()) = cancel_rx.recv() => {
+                    let reason = Some("Cancelled via execution handle".to_string());
+                    match temp_engine.cancel_instance(&instance_id_clone, reason.clone()).await {
+                        // Let the run observe the cancellation and record it
+                        Ok(()) => {
+                            let _ = run.await;
+                        }
+                        // The instance has not started tracking yet, so just drop it
+                        Err(e) => {
+                            tracing::debug!("Dropping instance {}: {}", instance_id_clone, e);
+                        }
+                    }
                     let _ = event_tx
-                        .send(WorkflowEvent::WorkflowFailed {
-                            instance_id: instance_id_clone.clone(),
-                            error: format!("Failed to create engine: {}", e),
+                        .send(WorkflowEvent::WorkflowCancelled {
+                            instance_id: instance_id_clone,
+                            reason,
                             timestamp: Utc::now(),
                         })
                         .await;
@@ -429,16 +1153,6 @@ impl DurableEngine {
                 }
             };
 
-            // Execute the workflow
-            let start_time = Utc::now();
-            let result = temp_engine
-                .run_instance(
-                    workflow.clone(),
-                    Some(instance_id_clone.clone()),
-                    input.clone(),
-                )
-                .await;
-
             // Send completion or failure event
             match result {
                 Ok(final_data) => {
@@ -452,49 +1166,118 @@ impl DurableEngine {
                         })
                         .await;
                 }
+                Err(Error::InstanceSuspended { .. }) => {
+                    // Forward the suspension recorded by suspend_instance
+                    let suspended = persistence
+                        .get_events(&instance_id_clone)
+                        .await
+                        .ok()
+                        .and_then(|events| {
+                            events.into_iter().rev().find(|event| {
+                                matches!(event, WorkflowEvent::WorkflowSuspended { .. })
+                            })
+                        });
+                    if let Some(event) = suspended {
+                        let _ = event_tx.send(event).await;
+                    }
+                }
                 Err(e) => {
-                    // For TaskExecution errors (e.g., from Raise tasks), extract just the message
-                    // to preserve the JSON error format. For other errors, use the full error string.
+                    // For TaskExecution errors, extract just the message. Raised errors
+                    // display as their JSON object. For other errors, use the full error string.
                     let error_msg = match &e {
                         Error::TaskExecution { message } => message.clone(),
-                        Error::WorkflowExecution { .. }
+                        Error::Raised { .. }
+                        | Error::WorkflowExecution { .. }
                         | Error::Listener { .. }
                         | Error::Configuration { .. }
                         | Error::Timeout { .. }
+                        | Error::InstanceNotFound { .. }
+                        | Error::IllegalTransition { .. }
+                        | Error::InstanceSuspended { .. }
+                        | Error::InstanceLeased { .. }
                         | Error::Io { .. }
+                        | Error::ResourceRead { .. }
                         | Error::Executor { .. }
                         | Error::Persistence { .. }
                         | Error::Cache { .. }
                         | Error::Context { .. }
                         | Error::Expression { .. }
+                        | Error::Graph { .. }
                         | Error::Serialization { .. }
                         | Error::ListenerSetup { .. }
-                        | Error::Protobuf { .. }
-                        | Error::ProtobufDescriptor { .. }
-                        | Error::Visualization { .. } => e.to_string(),
+                        | Error::Visualization { .. }
+                        | Error::Middleware { .. }
+                        | Error::Secret { .. }
+                        | Error::Event { .. }
+                        | Error::Preflight { .. }
+                        | Error::SchemaValidation { .. } => e.to_string(),
+                        #[cfg(feature = "grpc")]
+                        Error::Protobuf { .. } | Error::ProtobufDescriptor { .. } => e.to_string(),
                     };
                     let _ = event_tx
                         .send(WorkflowEvent::WorkflowFailed {
                             instance_id: instance_id_clone,
-                            error: error_msg,
+                            error: truncate(&error_msg, MAX_ERROR_LEN).into_owned(),
                             timestamp: Utc::now(),
                         })
                         .await;
                 }
             }
 
-            // TODO: Handle cancellation via cancel_rx
             // TODO: Emit correlation events for perpetual workflows
         });
 
         Ok(ExecutionHandle::new(instance_id, event_rx, cancel_tx))
     }
 
-    pub(crate) async fn run_instance(
+    /// Run an instance of a workflow and wait for its output
+    ///
+    /// The instance gets a new ID unless `instance_id` names one.
+    ///
+    /// # Errors
+    /// Returns an error if the workflow fails, or its events cannot be persisted.
+    pub async fn run_instance(
+        &self,
+        workflow: WorkflowDefinition,
+        instance_id: Option<String>,
+        initial_data: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.run_called_instance(workflow, instance_id, initial_data, &[])
+            .await
+    }
+
+    /// Run an instance of a workflow called by the workflows in `call_chain`,
+    /// outermost first
+    pub(crate) async fn run_called_instance(
         &self,
         workflow: WorkflowDefinition,
         instance_id: Option<String>,
         initial_data: serde_json::Value,
+        call_chain: &[String],
+    ) -> Result<serde_json::Value> {
+        let instance_id = instance_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let lease = self.take_lease(&instance_id).await?;
+        let shadowed = (!self.shadows.is_empty()).then(|| (workflow.clone(), initial_data.clone()));
+        let result = self
+            .run_leased(workflow, instance_id.clone(), initial_data, call_chain)
+            .await;
+        lease.release().await;
+
+        // Candidate versions run once the instance has completed, without
+        // holding up its result
+        if let (Some((workflow, input)), Ok(output)) = (shadowed, &result) {
+            self.spawn_shadows(&workflow, &instance_id, input, output);
+        }
+        result
+    }
+
+    /// Run an instance whose lease this engine holds
+    async fn run_leased(
+        &self,
+        workflow: WorkflowDefinition,
+        instance_id: String,
+        initial_data: serde_json::Value,
+        call_chain: &[String],
     ) -> Result<serde_json::Value> {
         // Check if workflow has a timeout
         let workflow_timeout = workflow
@@ -503,75 +1286,238 @@ impl DurableEngine {
             .and_then(|timeout_def| timeout::parse_timeout_duration(timeout_def).ok());
 
         // Execute workflow with timeout if specified
-        let execution_future = self.run_instance_inner(workflow, instance_id, initial_data);
+        let cancellation = CancellationToken::new();
+        let reaper = Reaper::default();
+        let execution_future = self.run_instance_inner(
+            workflow,
+            instance_id.clone(),
+            initial_data,
+            cancellation.clone(),
+            reaper.clone(),
+            call_chain,
+        );
 
-        if let Some(timeout_duration) = workflow_timeout {
-            match tokio::time::timeout(timeout_duration, execution_future).await {
+        let mut timed_out = false;
+        let result = if let Some(timeout_duration) = workflow_timeout {
+            let mut execution_future = std::pin::pin!(execution_future);
+            match tokio::time::timeout(timeout_duration, &mut execution_future).await {
                 Ok(result) => result,
-                Err(_) => Err(Error::Timeout {
-                    message: format!("Workflow execution timed out after {:?}", timeout_duration),
-                }),
+                Err(_) => {
+                    timed_out = true;
+                    // Let the task in flight stop the work its executor started
+                    cancellation.cancel();
+                    let current_task = self
+                        .live_status(&instance_id)
+                        .await
+                        .ok()
+                        .and_then(|status| status.current_task);
+                    match current_task {
+                        Some(task_name) => {
+                            control::reap_task(
+                                self.persistence.as_ref(),
+                                &instance_id,
+                                &task_name,
+                                "timeout",
+                                &reaper,
+                                execution_future,
+                            )
+                            .await;
+                        }
+                        None => {
+                            let _ =
+                                tokio::time::timeout(control::CANCELLATION_GRACE, execution_future)
+                                    .await;
+                        }
+                    }
+                    Err(Error::Timeout {
+                        message: format!(
+                            "Workflow execution timed out after {:?}",
+                            timeout_duration
+                        ),
+                    })
+                }
             }
         } else {
             execution_future.await
+        };
+
+        let outcome = match &result {
+            Ok(_) => InstanceState::Completed,
+            // The suspension and a refused transition have already been handled
+            Err(Error::InstanceSuspended { .. }) => return result,
+            Err(Error::IllegalTransition {
+                instance_id: refused,
+                ..
+            }) if *refused == instance_id => return result,
+            Err(_) if timed_out => InstanceState::TimedOut,
+            Err(_) => InstanceState::Faulted,
+        };
+        if let Err(e) = &result {
+            self.save_failure(&instance_id, e).await;
         }
+        self.save_outcome(&instance_id, outcome).await;
+        result
     }
 
     #[async_recursion]
     async fn run_instance_inner(
         &self,
         workflow: WorkflowDefinition,
-        instance_id: Option<String>,
+        instance_id: String,
         initial_data: serde_json::Value,
+        cancellation: CancellationToken,
+        reaper: Reaper,
+        call_chain: &[String],
     ) -> Result<serde_json::Value> {
-        let ctx = Context::new(
+        // Admission checks run before anything is recorded for the instance
+        for middleware in self.middleware.iter() {
+            middleware
+                .before_instance(&InstanceRequest {
+                    instance_id: &instance_id,
+                    workflow: &workflow,
+                    input: &initial_data,
+                })
+                .await?;
+        }
+        self.validate_schema(
             &workflow,
-            self.persistence.clone(),
+            workflow.input.as_ref(),
+            &initial_data,
+            "Workflow input",
+        )
+        .await?;
+        let secrets = self.resolve_secrets(&workflow).await?;
+        self.transition(&instance_id, InstanceState::Running)
+            .await?;
+
+        let metadata = self
+            .start_metadata(&workflow, &instance_id, &initial_data, call_chain)
+            .await?;
+        let mut ctx = Context::new(
+            &workflow,
+            secrets::redacting(self.persistence.clone(), &secrets),
             self.cache.clone(),
-            instance_id,
+            Some(instance_id),
             initial_data,
+            metadata,
         )
         .await?;
+        secrets::inject(&ctx, secrets).await;
+        ctx.state.cancellation = cancellation;
+        ctx.state.reaper = reaper;
+        ctx.services.mocks = Arc::clone(&self.mock_routes);
+        ctx.services.warm = Arc::clone(&self.warm_start);
+
+        let (_tracked, interrupt) = self.track_instance(&ctx).await?;
+        let result = self.run_graph(&workflow, &ctx, interrupt, None).await;
+        if let Err(e) = &result {
+            self.capture_debug_bundle(&ctx, e).await;
+        }
+        result
+    }
 
-        let (graph, task_names) = graph::build_graph(&workflow)?;
+    /// Execute the workflow's task graph within an instance context
+    ///
+    /// With a replay check, top-level tasks are checked against a recorded
+    /// run instead of being executed (see [`DurableEngine::replay_instance`]).
+    async fn run_graph(
+        &self,
+        workflow: &WorkflowDefinition,
+        ctx: &Context,
+        mut interrupt: tokio::sync::watch::Receiver<bool>,
+        mut check: Option<&mut replay::ReplayCheck>,
+    ) -> Result<serde_json::Value> {
+        let (graph, task_names) = graph::build_graph(workflow)?;
 
-        // Initialize all listeners BEFORE starting task execution
-        self.initialize_listeners(&workflow).await?;
+        // Initialize all listeners BEFORE starting task execution; a replay
+        // or stubbed shadow run takes the events its listeners received from
+        // the recorded run
+        if check.is_none() && self.shadow_stubs.is_none() {
+            self.initialize_listeners(workflow).await?;
+        }
 
-        let current_task_name = ctx.state.current_task.read().await.clone();
+        // A resumed instance replays the path recorded by its previous runs
+        let mut replay = ctx.services.history.replay();
+        let current_task_name = match replay.next_task() {
+            Some(name) => name.to_string(),
+            None => ctx.state.current_task.read().await.clone(),
+        };
         let mut current =
             task_names
-                .get(&current_task_name)
+                .get(current_task_name.as_str())
                 .copied()
                 .ok_or(Error::TaskExecution {
                     message: format!("Task not found: {current_task_name}"),
                 })?;
 
         loop {
-            let (task_name, task) = &graph[current];
+            let (task_name, task) = graph[current];
 
-            if let Some(_replayed_result) = ctx.services.history.is_task_completed(task_name) {
+            if let Some(replayed_result) = replay.take(task_name) {
                 output::format_task_skipped(task_name);
-                if let Some(next) = graph.neighbors(current).next() {
-                    current = next;
+                ctx.set_task_input(replayed_result).await;
+
+                if let Some(next_name) = replay.next_task() {
+                    current = *task_names.get(next_name).ok_or(Error::TaskExecution {
+                        message: format!("Recorded task not found: {next_name}"),
+                    })?;
                     continue;
                 }
-                break;
+
+                // The branch a switch took is only recorded by the task that
+                // followed it, so a switch that ends the recorded path runs again
+                if !matches!(task, TaskDefinition::Switch(_)) {
+                    match flow::after(task, ctx).await {
+                        flow::FlowDirective::Continue => {
+                            if let Some(next) = graph::next_in_sequence(&graph, current) {
+                                current = next;
+                                continue;
+                            }
+                        }
+                        flow::FlowDirective::Goto(next_name) => {
+                            current = *task_names.get(next_name.as_str()).ok_or(
+                                Error::TaskExecution {
+                                    message: format!("Next task not found: {next_name}"),
+                                },
+                            )?;
+                            continue;
+                        }
+                        flow::FlowDirective::Exit | flow::FlowDirective::End => {}
+                    }
+                    break;
+                }
             }
 
             ctx.services
                 .persistence
                 .save_event(WorkflowEvent::TaskEntered {
                     instance_id: ctx.metadata.instance_id.clone(),
-                    task_name: task_name.clone(),
+                    task_name: task_name.to_string(),
                     timestamp: Utc::now(),
                 })
                 .await?;
 
             // Save the original context before exec_task (which may apply input.from filtering)
-            let original_context = ctx.state.data.read().await.clone();
+            let original_context = ctx.data().await;
 
-            let result = match self.exec_task(task_name, task, &ctx).await {
+            // Run the task, abandoning it if the instance is cancelled meanwhile
+            let task_future = async {
+                match check.as_deref_mut() {
+                    Some(check) => check.replay_task(self, task_name, task, ctx).await,
+                    None => self.exec_task(task_name, task, ctx).await,
+                }
+            };
+            tokio::pin!(task_future);
+            let outcome = tokio::select! {
+                outcome = &mut task_future => outcome,
+                Ok(_) = interrupt.wait_for(|interrupted| *interrupted) => {
+                    // The cancellation token has fired; give the executor a
+                    // moment to stop what it started
+                    return Err(control::task_interrupted(ctx, task_name, task_future).await);
+                }
+            };
+
+            let result = match outcome {
                 Ok(r) => r,
                 Err(e) => {
                     // Task execution failed - emit task.faulted.v1 event
@@ -580,8 +1526,8 @@ impl DurableEngine {
                         .persistence
                         .save_event(WorkflowEvent::TaskFaulted {
                             instance_id: ctx.metadata.instance_id.clone(),
-                            task_name: task_name.clone(),
-                            error: e.to_string(),
+                            task_name: task_name.to_string(),
+                            error: truncate(&e.to_string(), MAX_ERROR_LEN).into_owned(),
                             timestamp: Utc::now(),
                         })
                         .await;
@@ -592,7 +1538,8 @@ impl DurableEngine {
 
             // Restore the original context before applying export
             // This ensures that input.from filtering doesn't affect export merging
-            *ctx.state.data.write().await = original_context;
+            ctx.merge_data(original_context, MergeStrategy::Replace)
+                .await;
 
             // Calculate task duration
             let task_end_time = Utc::now();
@@ -625,65 +1572,49 @@ impl DurableEngine {
             // Format task output with duration
             output::format_task_output(&result, duration_ms);
 
-            ctx.services
-                .persistence
-                .save_event(WorkflowEvent::TaskCompleted {
-                    instance_id: ctx.metadata.instance_id.clone(),
-                    task_name: task_name.clone(),
-                    result: result.clone(),
-                    timestamp: task_end_time,
-                    duration_ms,
-                })
-                .await?;
-
             // Update task_input for the next task before result gets moved
             // According to the spec, each task's transformed output becomes the next task's input
-            *ctx.state.task_input.write().await = result.clone();
+            ctx.set_task_input(result.clone()).await;
 
             // Handle export.as to update context
-            export::apply_export_to_context(task, &result, &ctx).await?;
+            export::apply_export_to_context(task, &result, ctx).await?;
 
-            ctx.save_checkpoint(task_name).await?;
-
-            // Check if we've reached the end of the graph naturally
-            let has_next_edge = graph.neighbors(current).next().is_some();
-
-            // Check if the task set a specific next task (e.g., for Switch tasks)
-            let next_task_name = {
-                let mut next = ctx.state.next_task.write().await;
-                next.take() // Take the value and reset to None
-            };
-
-            if let Some(next_name) = next_task_name {
-                // Task explicitly set the next task (e.g., Switch task)
-
-                // Special cases for flow directives:
-                // - "end" means gracefully terminate the workflow
-                // - "exit" means exit current scope (terminates workflow since we're in main scope)
-                // Note: In future, "exit" will only exit the current scope for nested workflows
-                if next_name == "end" || next_name == "exit" {
-                    break;
+            // The completion and the checkpoint holding its export are committed together
+            ctx.commit_task(
+                task_name,
+                WorkflowEvent::TaskCompleted {
+                    instance_id: ctx.metadata.instance_id.clone(),
+                    task_name: task_name.to_string(),
+                    result: result.clone(),
+                    timestamp: task_end_time,
+                    duration_ms,
+                },
+            )
+            .await?;
+            self.evaluate_watches(task_name, ctx).await?;
+
+            // Follow the task's flow directive; in the main scope, exiting
+            // the task list completes the workflow just as `end` does
+            current = match flow::after(task, ctx).await {
+                flow::FlowDirective::Continue => match graph::next_in_sequence(&graph, current) {
+                    Some(next) => next,
+                    None => break,
+                },
+                flow::FlowDirective::Exit | flow::FlowDirective::End => break,
+                flow::FlowDirective::Goto(next_name) => {
+                    *task_names
+                        .get(next_name.as_str())
+                        .ok_or(Error::TaskExecution {
+                            message: format!("Next task not found: {next_name}"),
+                        })?
                 }
-
-                current = *task_names.get(&next_name).ok_or(Error::TaskExecution {
-                    message: format!("Next task not found: {next_name}"),
-                })?;
-            } else if has_next_edge {
-                current = graph
-                    .neighbors(current)
-                    .next()
-                    .ok_or(Error::TaskExecution {
-                        message: format!("Next task edge missing after task: {task_name}"),
-                    })?;
-            } else {
-                break;
-            }
+            };
         }
 
         // Workflow completed - according to the spec, the workflow output is the last task's transformed output
         // "If no more tasks are defined, the transformed output is passed to the workflow output transformation step."
         // "Workflow `output.as` | Last task's transformed output | Transformed workflow output"
-        let mut final_data = ctx.state.task_input.read().await.clone();
+        let mut final_data = ctx.task_input().await;
 
         // Apply workflow output filter if specified
         if let Some(output_config) = &workflow.output
@@ -703,7 +1634,11 @@ impl DurableEngine {
             // 2. Bare JQ expressions: .field (older examples)
             final_data = if expr_str.trim().starts_with("${") {
                 // Wrapped expression - use evaluate_expression which handles ${ } syntax
-                crate::expressions::evaluate_expression(expr_str, &final_data)?
+                crate::expressions::evaluate_expression(
+                    ctx.metadata.evaluation,
+                    expr_str,
+                    &final_data,
+                )?
             } else {
                 // Bare JQ expression - use evaluate_jq directly
                 crate::expressions::evaluate_jq(expr_str, &final_data)?
@@ -712,7 +1647,16 @@ impl DurableEngine {
         if let serde_json::Value::Object(ref mut obj) = final_data {
             obj.remove("__workflow");
             obj.remove("__runtime");
+            obj.remove(crate::secrets::SECRETS_KEY);
+            obj.remove(crate::expressions::BRANCHES_KEY);
         }
+        self.validate_schema(
+            workflow,
+            workflow.output.as_ref(),
+            &final_data,
+            "Workflow output",
+        )
+        .await?;
 
         // Calculate workflow duration
         let workflow_end_time = Utc::now();
@@ -751,15 +1695,23 @@ impl DurableEngine {
     }
 }
 
+#[cfg(feature = "viz")]
 impl DurableEngine {
     /// Visualize workflow execution after completion
     ///
+    /// Tasks are drawn in the state the instance's recorded events leave them
+    /// in: succeeded, failed, still running, or not executed.
+    ///
     /// # Arguments
     /// * `workflow` - The workflow definition
     /// * `instance_id` - The workflow instance to visualize
     /// * `output_path` - Optional output path (None for stdout/ASCII)
     /// * `format` - Output format
-    /// * `tool` - Visualization tool to use ("graphviz" or "d2")
+    /// * `tool` - Name of a registered visualization provider, e.g. "graphviz" or "d2"
+    /// * `style` - Colors, color scheme and font size of the diagram
+    /// * `filter` - Which tasks the diagram draws
+    ///
+    /// SVG diagrams are drawn by the built-in renderer if the tool is not installed.
     ///
     /// # Errors
     /// Returns an error if the visualization tool is not available, not installed, or if rendering fails
@@ -770,23 +1722,28 @@ impl DurableEngine {
         output_path: Option<&std::path::Path>,
         format: crate::providers::visualization::DiagramFormat,
         tool: &str,
+        style: &visualization::DiagramStyle,
+        filter: &visualization::DiagramFilter,
     ) -> Result<()> {
-        // Get execution events
-        let _events = self.persistence.get_events(instance_id).await?;
-
-        // Build execution state from events
-        let execution_state = ExecutionState::new();
-
-        // Select provider
-        let provider: Box<dyn VisualizationProvider> = match tool {
-            "graphviz" => Box::new(GraphvizProvider::new()),
-            "d2" => Box::new(D2Provider::new()),
-            _ => {
-                return Err(Error::Configuration {
-                    message: format!("Unknown visualization tool: {tool}"),
-                });
-            }
-        };
+        // Build execution state from the instance's events
+        let events = self.persistence.get_events(instance_id).await?;
+        let execution_state = ExecutionState::from_events(&events);
+
+        // Select provider, drawing SVGs without the tool if it is not installed
+        let provider = visualization::provider(tool).ok_or_else(|| Error::Configuration {
+            message: format!(
+                "Unknown visualization tool: {tool}, expected one of: {}",
+                visualization::provider_names().join(", ")
+            ),
+        })?;
+        let provider = visualization::installed_or_builtin(provider, format)?;
+        let provider = provider.styled(style).unwrap_or(provider);
+        let provider = provider.filtered(filter).unwrap_or(provider);
+        if !provider.capabilities().supports(format) {
+            return Err(Error::Configuration {
+                message: format!("{} cannot render {format:?} diagrams", provider.name()),
+            });
+        }
 
         // Check availability
         if !provider.is_available()? {
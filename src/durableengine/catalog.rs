@@ -1,16 +1,287 @@
+//! Functions published in the catalogs a workflow uses (`use.catalogs`)
+//!
+//! A call to `name:version` runs a function from the first catalog of the
+//! workflow, and `name:version@catalog` one from the catalog of that name.
+//! Catalogs are local directories (`file://` URIs or plain paths), HTTP
+//! servers, or git repositories (`git+https://`, `git+ssh://`, `git@` or URIs
+//! ending in `.git`, optionally followed by `#branch-or-tag`), which are
+//! cloned once into the catalog directory. A version of a function is the
+//! `function.yaml` at `{name}/{version}/` in a local directory, and at
+//! `functions/{name}/{version}/` on an HTTP server or in a git repository.
+//!
+//! The manifest of a function is either a workflow, run as a nested workflow,
+//! or a single task such as a `call` or a `run`, run as the only task of a
+//! nested workflow so that it goes to the executor of its call type. The
+//! `with` arguments of the call are the input of the nested workflow. Each
+//! function is fetched and parsed once, and kept for the engine's lifetime.
+
+use serde_json::Value;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use snafu::prelude::*;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::context::Context;
 
+use super::resources::{ResolvedPath, resolve_path};
+use super::versions::{VersionReq, function_key};
 use super::{DurableEngine, Error, IoSnafu, Result};
 
+/// A `name:version` function reference, naming its catalog after an `@`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionRef<'a> {
+    pub name: &'a str,
+    /// A version, or a range of versions
+    pub version: &'a str,
+    /// Name of the catalog in `use.catalogs`; the first catalog if `None`
+    pub catalog: Option<&'a str>,
+}
+
+impl<'a> FunctionRef<'a> {
+    /// Parse `name:version` or `name:version@catalog`
+    ///
+    /// Returns `None` for URIs and for calls that do not name a version.
+    #[must_use]
+    pub fn parse(call: &'a str) -> Option<Self> {
+        if call.contains("://") {
+            return None;
+        }
+        let (name, rest) = call.split_once(':')?;
+        let (version, catalog) = match rest.split_once('@') {
+            Some((version, catalog)) => (version, Some(catalog)),
+            None => (rest, None),
+        };
+        if name.is_empty()
+            || version.is_empty()
+            || version.contains(':')
+            || catalog.is_some_and(str::is_empty)
+        {
+            return None;
+        }
+        Some(Self {
+            name,
+            version,
+            catalog,
+        })
+    }
+}
+
+/// Where a catalog publishes its functions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CatalogSource {
+    /// A local directory, as written in the workflow
+    Dir(String),
+    /// Base URL of an HTTP catalog
+    Http(String),
+    /// A git repository, and the branch or tag to check out
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+}
+
+impl CatalogSource {
+    /// The source a catalog endpoint URI points to
+    ///
+    /// # Errors
+    /// Returns an error for URI schemes catalogs cannot be read from.
+    pub(crate) fn parse(uri: &str) -> Result<Self> {
+        let git = |uri: &str| {
+            let (url, reference) = match uri.split_once('#') {
+                Some((url, reference)) => (url, Some(reference.to_string())),
+                None => (uri, None),
+            };
+            Self::Git {
+                url: url.to_string(),
+                reference,
+            }
+        };
+
+        if let Some(url) = uri.strip_prefix("git+") {
+            Ok(git(url))
+        } else if uri.starts_with("git@")
+            || uri.starts_with("ssh://")
+            || uri
+                .split('#')
+                .next()
+                .is_some_and(|url| url.ends_with(".git"))
+        {
+            Ok(git(uri))
+        } else if uri.starts_with("http://") || uri.starts_with("https://") {
+            Ok(Self::Http(uri.trim_end_matches('/').to_string()))
+        } else if uri.starts_with("file://") || !uri.contains("://") {
+            Ok(Self::Dir(uri.to_string()))
+        } else {
+            Err(Error::Configuration {
+                message: format!("Unsupported catalog URI scheme: {uri}"),
+            })
+        }
+    }
+}
+
+/// URI of the catalog functions are looked up in: the catalog named
+/// `catalog`, or the first catalog the workflow uses
+pub(crate) fn catalog_uri(workflow: &WorkflowDefinition, catalog: Option<&str>) -> Option<String> {
+    use serverless_workflow_core::models::resource::OneOfEndpointDefinitionOrUri;
+    let catalogs = workflow.use_.as_ref()?.catalogs.as_ref()?;
+    let catalog = match catalog {
+        Some(name) => catalogs.get(name)?,
+        None => catalogs.values().next()?,
+    };
+    Some(match &catalog.endpoint {
+        OneOfEndpointDefinitionOrUri::Uri(uri) => uri.clone(),
+        OneOfEndpointDefinitionOrUri::Endpoint(endpoint_def) => endpoint_def.uri.clone(),
+    })
+}
+
+/// Catalog functions resolved by an engine, and the git catalogs it cloned
+#[derive(Debug, Default)]
+pub(crate) struct Catalogs {
+    /// Directory git catalogs are cloned into; a directory under the
+    /// system's temporary directory if `None`
+    dir: Option<PathBuf>,
+    /// The workflows running functions, keyed by the file or URL of their manifest
+    functions: RwLock<HashMap<String, Arc<WorkflowDefinition>>>,
+    /// Held while a git catalog is cloned, so that it is cloned once
+    clone_lock: Mutex<()>,
+}
+
+impl Catalogs {
+    pub(crate) fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            ..Self::default()
+        }
+    }
+}
+
+/// Where the manifest of a function is read from
+enum Manifest {
+    File(ResolvedPath),
+    Url(String),
+}
+
+impl Manifest {
+    fn key(&self) -> String {
+        match self {
+            Self::File(resolved) => resolved.path.display().to_string(),
+            Self::Url(url) => url.clone(),
+        }
+    }
+}
+
+/// Parse the manifest of a function into the workflow that runs it
+///
+/// A manifest without a `document` is a task, which becomes the only task
+/// of a workflow named after the function.
+fn parse_manifest(content: &str, name: &str, version: &str) -> Result<WorkflowDefinition> {
+    let invalid = |e: &dyn std::fmt::Display| Error::Configuration {
+        message: format!("Failed to parse catalog function {name}: {e}"),
+    };
+    let manifest: Value = serde_yaml::from_str(content).map_err(|e| invalid(&e))?;
+    let workflow = if manifest.get("document").is_some() {
+        manifest
+    } else {
+        serde_json::json!({
+            "document": {
+                "dsl": "1.0.2",
+                "namespace": "catalog",
+                "name": name,
+                "version": version,
+            },
+            "do": [{ name: manifest }],
+        })
+    };
+    serde_json::from_value(workflow).map_err(|e| invalid(&e))
+}
+
+/// Name of the directory a git catalog is cloned into
+fn checkout_name(url: &str, reference: Option<&str>) -> String {
+    let repository: String = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    let digest =
+        crate::bundle::content_digest(format!("{url}#{}", reference.unwrap_or("")).as_bytes());
+    let digest = digest.trim_start_matches("sha256:");
+    format!("{repository}-{}", digest.get(..12).unwrap_or(digest))
+}
+
 impl DurableEngine {
+    /// The local directory a directory or git catalog publishes its
+    /// functions in, `None` for HTTP catalogs
+    ///
+    /// # Errors
+    /// Returns an error if a git catalog cannot be cloned.
+    pub(super) async fn catalog_root(
+        &self,
+        workflow: &WorkflowDefinition,
+        source: &CatalogSource,
+    ) -> Result<Option<ResolvedPath>> {
+        match source {
+            CatalogSource::Dir(dir) => Ok(Some(self.resolve_resource(workflow, dir).await)),
+            CatalogSource::Http(_) => Ok(None),
+            CatalogSource::Git { url, reference } => {
+                let checkout = self.clone_catalog(url, reference.as_deref()).await?;
+                Ok(Some(resolve_path(
+                    &checkout.join("functions").display().to_string(),
+                    None,
+                )))
+            }
+        }
+    }
+
+    /// Clone a git catalog into the catalog directory, unless it already is
+    pub(super) async fn clone_catalog(
+        &self,
+        url: &str,
+        reference: Option<&str>,
+    ) -> Result<PathBuf> {
+        let dir = self
+            .catalogs
+            .dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("jackdaw-catalogs"));
+        let checkout = dir.join(checkout_name(url, reference));
+
+        let _cloning = self.catalogs.clone_lock.lock().await;
+        if checkout.join(".git").exists() {
+            return Ok(checkout);
+        }
+        tokio::fs::create_dir_all(&dir).await.context(IoSnafu)?;
+
+        let mut git = tokio::process::Command::new("git");
+        git.args(["clone", "--quiet", "--depth", "1"]);
+        if let Some(reference) = reference {
+            git.args(["--branch", reference]);
+        }
+        git.arg(url).arg(&checkout);
+        let output = git.output().await.map_err(|e| Error::TaskExecution {
+            message: format!("Failed to run git to clone catalog {url}: {e}"),
+        })?;
+        if !output.status.success() {
+            return Err(Error::TaskExecution {
+                message: format!(
+                    "Failed to clone catalog {url}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+        tracing::info!("Cloned catalog {url} into {}", checkout.display());
+        Ok(checkout)
+    }
+
     /// Try to load and execute a function from a catalog
     ///
     /// Supports three formats:
-    /// 1. "function-name:version" - lookup in catalog
+    /// 1. "function-name:version[@catalog]" - lookup in catalog
     /// 2. "https://..." - direct URL to function.yaml
     /// 3. "file://..." - direct file path to function.yaml
     ///
@@ -21,135 +292,202 @@ impl DurableEngine {
         with_params: &HashMap<String, serde_json::Value>,
         ctx: &Context,
     ) -> Result<Option<serde_json::Value>> {
+        let workflow = &ctx.metadata.workflow;
+
         // Parse the function reference to determine if it's a catalog function
-        let function_url = if function_name.starts_with("http://")
+        let (manifest, name, version) = if function_name.starts_with("http://")
             || function_name.starts_with("https://")
         {
             // Direct HTTP(S) URL
-            function_name.to_string()
+            (
+                Manifest::Url(function_name.to_string()),
+                "function",
+                "0.0.0".to_string(),
+            )
         } else if function_name.starts_with("file://") {
-            // Direct file URL
-            function_name.to_string()
+            // Direct file URL, relative to the workflow file
+            (
+                Manifest::File(self.resolve_resource(workflow, function_name).await),
+                "function",
+                "0.0.0".to_string(),
+            )
         } else if function_name.contains(':') {
-            // Catalog reference: "function-name:version"
-            let parts: Vec<&str> = function_name.split(':').collect();
-            if parts.len() != 2 {
+            let Some(reference) = FunctionRef::parse(function_name) else {
                 return Err(Error::Configuration {
                     message: format!("Invalid catalog function reference: {function_name}"),
                 });
-            }
-            let (name, version) = (
-                parts.first().ok_or_else(|| Error::Configuration {
-                    message: format!("Invalid catalog function reference: {function_name}"),
-                })?,
-                parts.get(1).ok_or_else(|| Error::Configuration {
-                    message: format!("Invalid catalog function reference: {function_name}"),
-                })?,
-            );
-
-            // Look up in catalogs
-            let Some(catalogs) = ctx
-                .metadata
-                .workflow
-                .use_
-                .as_ref()
-                .and_then(|use_| use_.catalogs.as_ref())
-            else {
-                return Ok(None); // No catalogs defined
             };
 
-            // Try to find in any catalog
-            // Use first catalog for now
-            let function_url = if let Some(catalog) = catalogs.values().next() {
-                // Extract URI from the endpoint enum
-                use serverless_workflow_core::models::resource::OneOfEndpointDefinitionOrUri;
-                let catalog_uri = match &catalog.endpoint {
-                    OneOfEndpointDefinitionOrUri::Uri(uri) => uri.as_str(),
-                    OneOfEndpointDefinitionOrUri::Endpoint(endpoint_def) => &endpoint_def.uri,
-                };
-
-                // Build function URL based on catalog structure
-                let url = if catalog_uri.starts_with("file://") {
-                    let base_path = catalog_uri.strip_prefix("file://").ok_or_else(|| {
-                        Error::Configuration {
-                            message: format!("Invalid file:// URI: {catalog_uri}"),
-                        }
-                    })?;
-                    format!("file://{base_path}/{name}/{version}/function.yaml")
-                } else if catalog_uri.starts_with("http://") || catalog_uri.starts_with("https://")
-                {
-                    // For HTTP catalogs, follow the structure: {catalog}/functions/{name}/{version}/function.yaml
-                    format!(
-                        "{}/functions/{name}/{version}/function.yaml",
-                        catalog_uri.trim_end_matches('/'),
-                    )
-                } else {
-                    return Err(Error::Configuration {
-                        message: format!("Unsupported catalog URI scheme: {catalog_uri}"),
-                    });
+            let Some(catalog_uri) = catalog_uri(workflow, reference.catalog) else {
+                return match reference.catalog {
+                    Some(catalog) => Err(Error::Configuration {
+                        message: format!(
+                            "Function {function_name} names catalog '{catalog}', which the workflow does not use"
+                        ),
+                    }),
+                    None => Ok(None), // No catalogs defined
                 };
+            };
+            let source = CatalogSource::parse(&catalog_uri)?;
 
-                Some(url)
-            } else {
-                None
+            // A version range is pinned when the instance starts
+            let version = match ctx
+                .metadata
+                .resolved_versions
+                .get(&function_key(&reference))
+            {
+                Some(pinned) => pinned.clone(),
+                None if VersionReq::parse(reference.version).is_some() => {
+                    self.resolve_function_version(workflow, &reference).await?
+                }
+                None => reference.version.to_string(),
             };
 
-            match function_url {
-                Some(url) => url,
-                None => return Ok(None), // Not found in catalogs
-            }
+            // Build the manifest location based on the catalog structure
+            let manifest = match self.catalog_root(workflow, &source).await? {
+                Some(root) => Manifest::File(resolve_path(
+                    &root
+                        .path
+                        .join(reference.name)
+                        .join(&version)
+                        .join("function.yaml")
+                        .display()
+                        .to_string(),
+                    None,
+                )),
+                // HTTP catalogs follow the structure {catalog}/functions/{name}/{version}/function.yaml
+                None => Manifest::Url(format!(
+                    "{catalog_uri}/functions/{}/{version}/function.yaml",
+                    reference.name
+                )),
+            };
+            (manifest, reference.name, version)
         } else {
             // Not a catalog function reference
             return Ok(None);
         };
 
-        // Load the function definition
-        let function_content = if function_url.starts_with("file://") {
-            // Local file
-            let path =
-                function_url
-                    .strip_prefix("file://")
-                    .ok_or_else(|| Error::Configuration {
-                        message: format!("Invalid file:// URI: {function_url}"),
-                    })?;
-            tokio::fs::read_to_string(path).await.context(IoSnafu)?
-        } else {
-            // HTTP(S) URL
-            let response = reqwest::get(&function_url)
-                .await
-                .map_err(|e| Error::TaskExecution {
-                    message: format!("Failed to fetch catalog function from {function_url}: {e}"),
-                })?;
-
-            if !response.status().is_success() {
-                return Err(Error::TaskExecution {
+        let function_workflow = self.load_catalog_function(manifest, name, &version).await?;
+
+        // Execute the catalog function as a nested workflow with the provided inputs
+        let input_data = crate::ordering::to_canonical_value(with_params)?;
+
+        // Run the nested workflow (use Box::pin to avoid infinite-sized future)
+        let result =
+            Box::pin(self.run_instance((*function_workflow).clone(), None, input_data)).await?;
+
+        Ok(Some(result))
+    }
+
+    /// The workflow running a catalog function, fetched and parsed on first use
+    async fn load_catalog_function(
+        &self,
+        manifest: Manifest,
+        name: &str,
+        version: &str,
+    ) -> Result<Arc<WorkflowDefinition>> {
+        let key = manifest.key();
+        if let Some(function) = self.catalogs.functions.read().await.get(&key) {
+            return Ok(Arc::clone(function));
+        }
+
+        let function_content = match &manifest {
+            Manifest::File(resolved) => {
+                self.read_definition(&resolved.path, format!("catalog function {resolved}"))
+                    .await?
+            }
+            Manifest::Url(function_url) => self
+                .fetch_definition(function_url, "catalog function")
+                .await?
+                .ok_or_else(|| Error::TaskExecution {
                     message: format!(
-                        "Failed to fetch catalog function from {}: HTTP {}",
-                        function_url,
-                        response.status()
+                        "Failed to fetch catalog function from {function_url}: HTTP {}",
+                        reqwest::StatusCode::NOT_FOUND
                     ),
-                });
-            }
-
-            response.text().await.map_err(|e| Error::TaskExecution {
-                message: format!(
-                    "Failed to read catalog function response from {function_url}: {e}"
-                ),
-            })?
+                })?,
         };
 
-        // Parse the workflow definition
-        let function_workflow: WorkflowDefinition = serde_yaml::from_str(&function_content)
-            .map_err(|e| Error::Configuration {
-                message: format!("Failed to parse catalog function {function_name}: {e}"),
-            })?;
+        let function = parse_manifest(&function_content, name, version)?;
+        // Relative resources of a local function are next to its manifest
+        if let Manifest::File(resolved) = &manifest {
+            self.set_workflow_path(&function, &resolved.path).await;
+        }
+        let function = Arc::new(function);
+        self.catalogs
+            .functions
+            .write()
+            .await
+            .insert(key, Arc::clone(&function));
+        Ok(function)
+    }
+}
 
-        // Execute the catalog function as a nested workflow with the provided inputs
-        let input_data = serde_json::to_value(with_params)?;
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
 
-        // Run the nested workflow (use Box::pin to avoid infinite-sized future)
-        let result = Box::pin(self.run_instance(function_workflow, None, input_data)).await?;
+    use super::*;
 
-        Ok(Some(result))
+    #[test]
+    fn test_function_references_name_their_catalog_after_an_at() {
+        assert_eq!(
+            FunctionRef::parse("greet:^1.2@shared"),
+            Some(FunctionRef {
+                name: "greet",
+                version: "^1.2",
+                catalog: Some("shared"),
+            })
+        );
+        assert_eq!(
+            FunctionRef::parse("greet:1.0.0").map(|reference| reference.catalog),
+            Some(None)
+        );
+        assert_eq!(
+            FunctionRef::parse("https://example.com/function.yaml"),
+            None
+        );
+        assert_eq!(FunctionRef::parse("greet:1.0.0@"), None);
+        assert_eq!(FunctionRef::parse("http"), None);
+    }
+
+    #[test]
+    fn test_catalog_sources_are_told_apart_by_uri() {
+        assert_eq!(
+            CatalogSource::parse("git+https://example.com/catalog#v2").unwrap(),
+            CatalogSource::Git {
+                url: "https://example.com/catalog".to_string(),
+                reference: Some("v2".to_string()),
+            }
+        );
+        assert!(matches!(
+            CatalogSource::parse("https://example.com/catalog.git").unwrap(),
+            CatalogSource::Git {
+                reference: None,
+                ..
+            }
+        ));
+        assert_eq!(
+            CatalogSource::parse("https://example.com/catalog/").unwrap(),
+            CatalogSource::Http("https://example.com/catalog".to_string())
+        );
+        assert_eq!(
+            CatalogSource::parse("./functions").unwrap(),
+            CatalogSource::Dir("./functions".to_string())
+        );
+        assert!(CatalogSource::parse("s3://bucket/catalog").is_err());
+    }
+
+    #[test]
+    fn test_task_manifests_run_as_single_task_workflows() {
+        let workflow = parse_manifest("set:\n  sum: '${ .a + .b }'\n", "add", "1.0.0").unwrap();
+        assert_eq!(workflow.document.name, "add");
+        assert_eq!(workflow.document.version, "1.0.0");
+        assert!(
+            workflow
+                .do_
+                .entries
+                .iter()
+                .any(|entry| entry.contains_key("add"))
+        );
     }
 }
@@ -0,0 +1,535 @@
+//! Control of running workflow instances
+//!
+//! Every instance executed by the engine is tracked while it runs, so it can
+//! be cancelled or suspended from outside the task loop: through
+//! [`DurableEngine::cancel_instance`] and [`DurableEngine::suspend_instance`]
+//! in the same process, or by a `WorkflowCancelled` or `WorkflowSuspended`
+//! event persisted by another process sharing the same persistence provider.
+//! A suspended instance is continued with [`DurableEngine::resume_instance`],
+//! which replays its completed tasks from the event history.
+
+use chrono::Utc;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::{RwLock, watch};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::context::{Context, Reaper};
+use crate::persistence::PersistenceProvider;
+use crate::text::{MAX_ERROR_LEN, truncate};
+use crate::workflow::WorkflowEvent;
+
+use super::memory::RetainedContext;
+use super::{DurableEngine, Error, InstanceState, Result};
+
+/// How often running instances check persistence for control events recorded by other processes
+const EXTERNAL_CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often a resume checks whether the suspended run has stopped
+const STOPPING_RUN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long a resume waits for the suspended run to stop
+const STOPPING_RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long an interrupted or timed out task may take to stop the work its
+/// executor started, e.g. to remove a container, before it is abandoned
+pub(super) const CANCELLATION_GRACE: Duration = Duration::from_secs(5);
+
+/// Running instances, keyed by instance ID
+///
+/// A synchronous lock is used so that [`TrackedInstance`] can unregister an
+/// instance on drop, even when its run is aborted rather than returning.
+pub(super) type ActiveInstances = Arc<Mutex<HashMap<String, ActiveInstance>>>;
+
+/// Handles shared with the context of a running instance
+#[derive(Clone)]
+struct Controls {
+    cancelled: Arc<RwLock<bool>>,
+    cancellation_reason: Arc<RwLock<Option<String>>>,
+    suspended: Arc<RwLock<bool>>,
+    suspension_reason: Arc<RwLock<Option<String>>>,
+    /// Set to `true` to interrupt the task currently in flight
+    interrupt: Arc<watch::Sender<bool>>,
+    /// Stops the work executors started for the task in flight
+    cancellation: CancellationToken,
+}
+
+impl Controls {
+    async fn cancel(&self, reason: Option<String>) {
+        *self.cancelled.write().await = true;
+        *self.cancellation_reason.write().await = reason;
+        self.cancellation.cancel();
+        self.interrupt.send_replace(true);
+    }
+
+    async fn suspend(&self, reason: Option<String>) {
+        *self.suspended.write().await = true;
+        *self.suspension_reason.write().await = reason;
+        self.cancellation.cancel();
+        self.interrupt.send_replace(true);
+    }
+}
+
+pub(super) struct ActiveInstance {
+    controls: Controls,
+    /// Context state measured by memory reports
+    retained: RetainedContext,
+    /// Background task watching persistence for external cancellation and suspension
+    watcher: JoinHandle<()>,
+}
+
+impl Drop for ActiveInstance {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Unregisters a running instance when its run ends or is dropped
+pub(super) struct TrackedInstance {
+    active_instances: ActiveInstances,
+    instance_id: String,
+}
+
+impl Drop for TrackedInstance {
+    fn drop(&mut self) {
+        self.active_instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.instance_id);
+    }
+}
+
+impl DurableEngine {
+    /// Cancel a workflow instance
+    ///
+    /// If the instance is running in this engine, its cancellation flag is set
+    /// and the task in flight is interrupted. Otherwise the cancellation is only
+    /// recorded, which stops an instance running in another process that shares
+    /// the same persistence provider, and prevents a suspended instance from
+    /// being resumed. In both cases a `WorkflowCancelled` event is persisted.
+    /// A running instance is cancelling until its run has stopped; a pending
+    /// or suspended one is cancelled straight away.
+    ///
+    /// # Errors
+    /// Returns an error if the instance has no recorded events, has already
+    /// finished or is being cancelled, or the event cannot be persisted.
+    pub async fn cancel_instance(&self, instance_id: &str, reason: Option<String>) -> Result<()> {
+        // A run that is stopping for a suspension is handled like a suspended instance
+        if let Some(controls) = self.active_controls(instance_id)
+            && !*controls.suspended.read().await
+        {
+            if !*controls.cancelled.read().await {
+                self.transition(instance_id, InstanceState::Cancelling)
+                    .await?;
+                self.save_cancellation(instance_id, reason.clone()).await?;
+                controls.cancel(reason).await;
+            }
+            return Ok(());
+        }
+
+        self.transition_with(instance_id, |from| match from {
+            Some(InstanceState::Running | InstanceState::Cancelling) => InstanceState::Cancelling,
+            Some(_) | None => InstanceState::Cancelled,
+        })
+        .await?;
+        self.save_cancellation(instance_id, reason).await
+    }
+
+    /// Suspend a workflow instance
+    ///
+    /// If the instance is running in this engine, the task in flight is
+    /// interrupted and the run stops with [`Error::InstanceSuspended`].
+    /// Otherwise the suspension is only recorded, which stops an instance
+    /// running in another process that shares the same persistence provider.
+    /// In both cases a `WorkflowSuspended` event carrying the last checkpoint
+    /// is persisted. Suspending an already suspended instance does nothing.
+    ///
+    /// # Errors
+    /// Returns an error if the instance has no recorded events, is not
+    /// running, or the event cannot be persisted.
+    pub async fn suspend_instance(&self, instance_id: &str, reason: Option<String>) -> Result<()> {
+        if self.instance_state(instance_id).await? == InstanceState::Suspended {
+            return Ok(());
+        }
+        self.transition(instance_id, InstanceState::Suspended)
+            .await?;
+        self.save_suspension(instance_id, reason.clone()).await?;
+
+        if let Some(controls) = self.active_controls(instance_id) {
+            controls.suspend(reason).await;
+        }
+        Ok(())
+    }
+
+    /// Resume a suspended workflow instance and run it to completion
+    ///
+    /// The instance restarts from its last checkpoint: tasks completed before
+    /// the suspension are replayed from the event history rather than executed
+    /// again, and the task that was interrupted runs from the start. This works
+    /// across process restarts, as long as `workflow` is the definition the
+    /// instance was started with.
+    ///
+    /// # Errors
+    /// Returns an error if the instance is not suspended, or the resumed run fails.
+    pub async fn resume_instance(
+        &self,
+        workflow: WorkflowDefinition,
+        instance_id: &str,
+    ) -> Result<serde_json::Value> {
+        let state = self.instance_state(instance_id).await?;
+        if state != InstanceState::Suspended {
+            return Err(Error::IllegalTransition {
+                instance_id: instance_id.to_string(),
+                from: state,
+                to: InstanceState::Running,
+            });
+        }
+
+        // A run stopping for a suspension unregisters once its task is interrupted
+        let deadline = tokio::time::Instant::now() + STOPPING_RUN_TIMEOUT;
+        while self.active_controls(instance_id).is_some() {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(Error::Timeout {
+                    message: format!(
+                        "Suspended run of instance {instance_id} did not stop within {STOPPING_RUN_TIMEOUT:?}"
+                    ),
+                });
+            }
+            tokio::time::sleep(STOPPING_RUN_POLL_INTERVAL).await;
+        }
+
+        self.transition_from(
+            instance_id,
+            InstanceState::Suspended,
+            InstanceState::Running,
+        )
+        .await?;
+        self.persistence
+            .save_event(WorkflowEvent::WorkflowResumed {
+                instance_id: instance_id.to_string(),
+                timestamp: Utc::now(),
+            })
+            .await?;
+
+        self.resume(workflow, instance_id.to_string()).await
+    }
+
+    fn active_controls(&self, instance_id: &str) -> Option<Controls> {
+        self.active_instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(instance_id)
+            .map(|instance| instance.controls.clone())
+    }
+
+    /// The retained context state of each running instance, keyed by instance ID
+    pub(super) fn retained_contexts(&self) -> Vec<(String, RetainedContext)> {
+        self.active_instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(instance_id, instance)| (instance_id.clone(), instance.retained.clone()))
+            .collect()
+    }
+
+    /// The persisted events of an instance, which must have been started
+    pub(super) async fn recorded_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
+        let events = self.persistence.get_events(instance_id).await?;
+        if events.is_empty() {
+            return Err(Error::InstanceNotFound {
+                instance_id: instance_id.to_string(),
+            });
+        }
+        Ok(events)
+    }
+
+    async fn save_cancellation(&self, instance_id: &str, reason: Option<String>) -> Result<()> {
+        self.persistence
+            .save_event(WorkflowEvent::WorkflowCancelled {
+                instance_id: instance_id.to_string(),
+                reason,
+                timestamp: Utc::now(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Record the state a run ended in; a run stopped by a cancellation ends cancelled
+    pub(super) async fn save_outcome(&self, instance_id: &str, outcome: InstanceState) {
+        let result = self
+            .transition_with(instance_id, |from| match from {
+                Some(InstanceState::Cancelling | InstanceState::Cancelled) => {
+                    InstanceState::Cancelled
+                }
+                Some(_) | None => outcome,
+            })
+            .await;
+        match result {
+            // A refused admission leaves nothing to record
+            Ok(_) | Err(Error::InstanceNotFound { .. }) => {}
+            Err(e) => tracing::warn!("Failed to record state of {}: {}", instance_id, e),
+        }
+    }
+
+    /// Record that a run failed, unless the instance never started or has
+    /// already finished, e.g. because it was cancelled
+    pub(super) async fn save_failure(&self, instance_id: &str, error: &Error) {
+        let events = match self.persistence.get_events(instance_id).await {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::warn!("Failed to read events of {}: {}", instance_id, e);
+                return;
+            }
+        };
+        if events.is_empty() || has_finished(&events) {
+            return;
+        }
+
+        // Raised errors keep their JSON form, as in the events of an execution handle
+        let error = if let Error::TaskExecution { message } = error {
+            message.clone()
+        } else {
+            error.to_string()
+        };
+        if let Err(e) = self
+            .persistence
+            .save_event(WorkflowEvent::WorkflowFailed {
+                instance_id: instance_id.to_string(),
+                error: truncate(&error, MAX_ERROR_LEN).into_owned(),
+                timestamp: Utc::now(),
+            })
+            .await
+        {
+            tracing::warn!("Failed to persist failure of {}: {}", instance_id, e);
+        }
+    }
+
+    async fn save_suspension(&self, instance_id: &str, reason: Option<String>) -> Result<()> {
+        let checkpoint_data = self
+            .persistence
+            .get_checkpoint(instance_id)
+            .await?
+            .map_or(serde_json::Value::Null, |checkpoint| checkpoint.data);
+        self.persistence
+            .save_event(WorkflowEvent::WorkflowSuspended {
+                instance_id: instance_id.to_string(),
+                reason,
+                checkpoint_data,
+                timestamp: Utc::now(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Start tracking a running instance
+    ///
+    /// Returns a guard that stops tracking when dropped, and a receiver that
+    /// fires when the instance is cancelled or suspended.
+    pub(super) async fn track_instance(
+        &self,
+        ctx: &Context,
+    ) -> Result<(TrackedInstance, watch::Receiver<bool>)> {
+        let instance_id = ctx.metadata.instance_id.clone();
+        let (interrupt, receiver) = watch::channel(false);
+        let controls = Controls {
+            cancelled: ctx.state.cancelled.clone(),
+            cancellation_reason: ctx.state.cancellation_reason.clone(),
+            suspended: ctx.state.suspended.clone(),
+            suspension_reason: ctx.state.suspension_reason.clone(),
+            interrupt: Arc::new(interrupt),
+            cancellation: ctx.state.cancellation.clone(),
+        };
+
+        // Only control events recorded after this point apply to this run
+        let baseline = self.persistence.get_events(&instance_id).await?.len();
+        let watcher = tokio::spawn(watch_external_control(
+            self.persistence.clone(),
+            instance_id.clone(),
+            baseline,
+            controls.clone(),
+        ));
+
+        self.active_instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                instance_id.clone(),
+                ActiveInstance {
+                    controls,
+                    retained: RetainedContext::new(ctx),
+                    watcher,
+                },
+            );
+
+        let tracked = TrackedInstance {
+            active_instances: self.active_instances.clone(),
+            instance_id,
+        };
+        Ok((tracked, receiver))
+    }
+}
+
+/// Let an interrupted or timed out task stop the work its executor started,
+/// and record what was stopped in a `TaskReaped` event
+///
+/// The task is dropped if it has not stopped within [`CANCELLATION_GRACE`].
+pub(super) async fn reap_task<F: Future>(
+    persistence: &dyn PersistenceProvider,
+    instance_id: &str,
+    task_name: &str,
+    reason: &str,
+    reaper: &Reaper,
+    task: F,
+) {
+    let abandoned = tokio::time::timeout(CANCELLATION_GRACE, task)
+        .await
+        .is_err();
+    let resources = reaper.take();
+    if resources.is_empty() && !abandoned {
+        return;
+    }
+    if abandoned {
+        tracing::warn!(
+            "Task {} did not stop within {:?} and was abandoned",
+            task_name,
+            CANCELLATION_GRACE
+        );
+    }
+
+    if let Err(e) = persistence
+        .save_event(WorkflowEvent::TaskReaped {
+            instance_id: instance_id.to_string(),
+            task_name: task_name.to_string(),
+            reason: reason.to_string(),
+            resources,
+            abandoned,
+            timestamp: Utc::now(),
+        })
+        .await
+    {
+        tracing::warn!("Failed to persist reaping of task {}: {}", task_name, e);
+    }
+}
+
+/// Record that the task in flight was interrupted and build the resulting error
+pub(super) async fn task_interrupted<F: Future>(ctx: &Context, task_name: &str, task: F) -> Error {
+    let instance_id = ctx.metadata.instance_id.clone();
+    let suspended = ctx.is_suspended().await && !ctx.is_cancelled().await;
+
+    reap_task(
+        ctx.services.persistence.as_ref(),
+        &instance_id,
+        task_name,
+        if suspended { "suspended" } else { "cancelled" },
+        &ctx.state.reaper,
+        task,
+    )
+    .await;
+
+    if suspended {
+        let state = ctx.data().await;
+        if let Err(e) = ctx
+            .services
+            .persistence
+            .save_event(WorkflowEvent::TaskSuspended {
+                instance_id: instance_id.clone(),
+                task_name: task_name.to_string(),
+                state,
+                timestamp: Utc::now(),
+            })
+            .await
+        {
+            tracing::warn!("Failed to persist suspension of task {}: {}", task_name, e);
+        }
+        return Error::InstanceSuspended { instance_id };
+    }
+
+    let reason = ctx.state.cancellation_reason.read().await.clone();
+
+    if let Err(e) = ctx
+        .services
+        .persistence
+        .save_event(WorkflowEvent::TaskCancelled {
+            instance_id,
+            task_name: task_name.to_string(),
+            reason: reason.clone(),
+            timestamp: Utc::now(),
+        })
+        .await
+    {
+        tracing::warn!(
+            "Failed to persist cancellation of task {}: {}",
+            task_name,
+            e
+        );
+    }
+
+    Error::WorkflowExecution {
+        message: format!(
+            "Workflow cancelled: {}",
+            reason.unwrap_or_else(|| "No reason provided".to_string())
+        ),
+    }
+}
+
+/// Poll persistence for a `WorkflowCancelled` or `WorkflowSuspended` event recorded after `baseline`
+///
+/// Only the recorded state of the instance is read while it is running; its
+/// events are read once the state shows it was cancelled or suspended, or
+/// every time if the provider records no states.
+async fn watch_external_control(
+    persistence: Arc<dyn PersistenceProvider>,
+    instance_id: String,
+    baseline: usize,
+    controls: Controls,
+) {
+    loop {
+        tokio::time::sleep(EXTERNAL_CONTROL_POLL_INTERVAL).await;
+        if *controls.interrupt.borrow() {
+            return;
+        }
+
+        match persistence.get_state(&instance_id).await {
+            Ok(Some(InstanceState::Pending | InstanceState::Running)) | Err(_) => continue,
+            Ok(
+                Some(
+                    InstanceState::Suspended
+                    | InstanceState::Cancelling
+                    | InstanceState::Completed
+                    | InstanceState::Faulted
+                    | InstanceState::TimedOut
+                    | InstanceState::Cancelled,
+                )
+                | None,
+            ) => {}
+        }
+        let Ok(events) = persistence.get_events(&instance_id).await else {
+            continue;
+        };
+        for event in events.iter().skip(baseline) {
+            if let WorkflowEvent::WorkflowCancelled { reason, .. } = event {
+                controls.cancel(reason.clone()).await;
+                return;
+            }
+            if let WorkflowEvent::WorkflowSuspended { reason, .. } = event {
+                controls.suspend(reason.clone()).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Whether an instance's event history records that it completed, failed or was cancelled
+fn has_finished(events: &[WorkflowEvent]) -> bool {
+    events.iter().any(|event| {
+        matches!(
+            event,
+            WorkflowEvent::WorkflowCompleted { .. }
+                | WorkflowEvent::WorkflowFailed { .. }
+                | WorkflowEvent::WorkflowCancelled { .. }
+        )
+    })
+}
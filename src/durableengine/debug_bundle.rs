@@ -0,0 +1,256 @@
+//! Diagnostic bundles captured when an instance faults
+//!
+//! When an instance faults, the engine stores a [`DebugBundle`] alongside it:
+//! the error, the task it failed in and that task's definition, the context
+//! and task input at the time, the last events of the instance, and the
+//! runtime and platform it ran on. Values under keys that look like
+//! credentials are redacted before the bundle is stored, so it can be attached
+//! to a bug report as it is; `jackdaw debug-bundle <instance-id>` exports it.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{DurableEngine, Error, Result};
+use crate::context::Context;
+use crate::descriptors::RuntimeDescriptor;
+use crate::executor::WorkflowError;
+
+/// Events of the instance kept in a bundle, the most recent ones
+const RECENT_EVENTS: usize = 20;
+
+/// What redacted values are replaced with
+const REDACTED: &str = "<redacted>";
+
+/// Parts of keys whose values are redacted, compared without case or separators
+const SENSITIVE_KEYS: [&str; 10] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "apikey",
+    "authorization",
+    "authentication",
+    "credential",
+    "privatekey",
+    "cookie",
+];
+
+/// What was known about an instance when it faulted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugBundle {
+    pub instance_id: String,
+    /// The "namespace/name/version" key of the workflow
+    pub workflow_id: String,
+    /// The DSL version the workflow was written against
+    pub dsl_version: String,
+    pub captured_at: DateTime<Utc>,
+    pub error: WorkflowError,
+    /// The task the instance failed in
+    pub task_name: String,
+    /// Definition of that task, if it is found in the workflow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_definition: Option<Value>,
+    /// The workflow context when the instance faulted
+    pub context: Value,
+    /// Input of the task the instance failed in
+    pub task_input: Value,
+    /// The last events recorded for the instance, oldest first
+    pub recent_events: Vec<Value>,
+    pub runtime: RuntimeDescriptor,
+    pub os: String,
+    pub arch: String,
+}
+
+/// Replace the values under keys that look like credentials with [`REDACTED`]
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                if is_sensitive(key) {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key: String = key
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    SENSITIVE_KEYS
+        .iter()
+        .any(|sensitive| key.contains(sensitive))
+}
+
+/// The definition of the task called `name`, at any depth of `tasks`
+///
+/// Task lists are arrays of single-entry objects under `do` or `try`.
+fn find_task(tasks: &Value, name: &str) -> Option<Value> {
+    match tasks {
+        Value::Object(fields) => fields.iter().find_map(|(key, field)| {
+            if let ("do" | "try", Value::Array(entries)) = (key.as_str(), field)
+                && let Some(task) = entries.iter().find_map(|entry| entry.get(name))
+            {
+                return Some(task.clone());
+            }
+            find_task(field, name)
+        }),
+        Value::Array(items) => items.iter().find_map(|item| find_task(item, name)),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => None,
+    }
+}
+
+impl DurableEngine {
+    /// Store a debug bundle for an instance that faulted with `error`
+    ///
+    /// Errors that do not fault the instance, such as a suspension or a
+    /// cancellation, are ignored. A bundle that cannot be stored is logged and dropped; it never
+    /// changes the outcome of the instance.
+    pub(super) async fn capture_debug_bundle(&self, ctx: &Context, error: &Error) {
+        let instance_id = &ctx.metadata.instance_id;
+        if let Error::InstanceSuspended { .. } = error {
+            return;
+        }
+        if *ctx.state.cancelled.read().await {
+            return;
+        }
+        if let Error::IllegalTransition {
+            instance_id: refused,
+            ..
+        } = error
+            && refused == instance_id
+        {
+            return;
+        }
+
+        let bundle = match self.debug_bundle_of(ctx, error).await {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                tracing::warn!("Failed to capture debug bundle of {}: {}", instance_id, e);
+                return;
+            }
+        };
+        let saved = match serde_json::to_value(&bundle) {
+            // The instance's persistence also masks the values of its secrets
+            Ok(bundle) => ctx
+                .services
+                .persistence
+                .save_debug_bundle(instance_id, bundle)
+                .await
+                .map_err(Error::from),
+            Err(e) => Err(Error::from(e)),
+        };
+        if let Err(e) = saved {
+            tracing::warn!("Failed to store debug bundle of {}: {}", instance_id, e);
+        }
+    }
+
+    async fn debug_bundle_of(&self, ctx: &Context, error: &Error) -> Result<DebugBundle> {
+        let workflow = &ctx.metadata.workflow;
+        let task_name = ctx.state.current_task.read().await.clone();
+
+        let mut task_definition = find_task(&serde_json::to_value(workflow.as_ref())?, &task_name);
+        if let Some(definition) = &mut task_definition {
+            redact(definition);
+        }
+        let mut context = ctx.data().await;
+        redact(&mut context);
+        let mut task_input = ctx.task_input().await;
+        redact(&mut task_input);
+
+        let events = self
+            .persistence
+            .get_events(&ctx.metadata.instance_id)
+            .await?;
+        let recent_events = events
+            .iter()
+            .skip(events.len().saturating_sub(RECENT_EVENTS))
+            .map(|event| {
+                let mut event = serde_json::to_value(event)?;
+                redact(&mut event);
+                Ok::<_, Error>(event)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(DebugBundle {
+            instance_id: ctx.metadata.instance_id.clone(),
+            workflow_id: format!(
+                "{}/{}/{}",
+                workflow.document.namespace, workflow.document.name, workflow.document.version
+            ),
+            dsl_version: workflow.document.dsl.clone(),
+            captured_at: Utc::now(),
+            error: error.to_workflow_error(),
+            task_name,
+            task_definition,
+            context,
+            task_input,
+            recent_events,
+            runtime: ctx.metadata.runtime_descriptor.as_ref().clone(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        })
+    }
+
+    /// The debug bundle captured when an instance faulted, if any
+    ///
+    /// # Errors
+    /// Returns an error if the bundle cannot be read or is not a valid bundle.
+    pub async fn debug_bundle(&self, instance_id: &str) -> Result<Option<DebugBundle>> {
+        let bundle = self.persistence.get_debug_bundle(instance_id).await?;
+        Ok(bundle.map(serde_json::from_value).transpose()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_hides_credentials_at_any_depth() {
+        let mut value = json!({
+            "user": "ada",
+            "password": "hunter2",
+            "headers": { "Authorization": "Bearer abc", "Accept": "application/json" },
+            "accounts": [{ "api_key": "k-123", "id": 7 }],
+        });
+        redact(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "user": "ada",
+                "password": REDACTED,
+                "headers": { "Authorization": REDACTED, "Accept": "application/json" },
+                "accounts": [{ "api_key": REDACTED, "id": 7 }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_task_in_nested_lists() {
+        let workflow = json!({
+            "do": [
+                { "first": { "set": { "a": 1 } } },
+                { "guarded": { "try": [{ "charge": { "call": "http" } }], "catch": {} } },
+            ]
+        });
+        assert_eq!(
+            find_task(&workflow, "charge"),
+            Some(json!({ "call": "http" }))
+        );
+        assert_eq!(
+            find_task(&workflow, "first"),
+            Some(json!({ "set": { "a": 1 } }))
+        );
+        assert_eq!(find_task(&workflow, "missing"), None);
+    }
+}
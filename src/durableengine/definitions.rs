@@ -0,0 +1,96 @@
+//! Definitions the engine loads itself
+//!
+//! Sub-workflows found in workflow sources and the manifests of catalog
+//! functions are not loaded by the CLI, so the engine treats them as the CLI
+//! treats the workflow files it loads: each is checked against the engine's
+//! signature policy, with its detached signature next to it as `<file>.sig`
+//! or `<url>.sig`, and its `${{ env.X }}` placeholders are substituted.
+
+use snafu::prelude::*;
+use std::path::Path;
+
+use crate::signing::{SignaturePolicy, signature_path};
+
+use super::{DurableEngine, Error, ResourceReadSnafu, Result};
+
+impl DurableEngine {
+    /// Read the definition in the file at `path`, checked and interpolated
+    ///
+    /// `resource` names the definition in read errors.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, is not signed as the
+    /// signature policy requires, or has a placeholder that cannot be substituted.
+    pub(super) async fn read_definition(&self, path: &Path, resource: String) -> Result<String> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .context(ResourceReadSnafu { resource })?;
+        let signature = if self.verifier.policy() == SignaturePolicy::Off {
+            None
+        } else {
+            tokio::fs::read_to_string(signature_path(path)).await.ok()
+        };
+        self.trusted_definition(&path.display().to_string(), &content, signature.as_deref())
+    }
+
+    /// Fetch the definition at `url`, checked and interpolated; `None` if the
+    /// server has none
+    ///
+    /// `kind` names the definition in fetch errors, e.g. `catalog function`.
+    ///
+    /// # Errors
+    /// Returns an error if the definition cannot be fetched, is not signed as
+    /// the signature policy requires, or has a placeholder that cannot be
+    /// substituted.
+    pub(super) async fn fetch_definition(&self, url: &str, kind: &str) -> Result<Option<String>> {
+        let response = reqwest::get(url).await.map_err(|e| Error::TaskExecution {
+            message: format!("Failed to fetch {kind} from {url}: {e}"),
+        })?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(Error::TaskExecution {
+                message: format!(
+                    "Failed to fetch {kind} from {url}: HTTP {}",
+                    response.status()
+                ),
+            });
+        }
+        let content = response.text().await.map_err(|e| Error::TaskExecution {
+            message: format!("Failed to read {kind} response from {url}: {e}"),
+        })?;
+
+        // A missing signature is reported by the check, as for files
+        let signature = if self.verifier.policy() == SignaturePolicy::Off {
+            None
+        } else {
+            match reqwest::get(format!("{url}.sig")).await {
+                Ok(response) if response.status().is_success() => response.text().await.ok(),
+                Ok(_) | Err(_) => None,
+            }
+        };
+        self.trusted_definition(url, &content, signature.as_deref())
+            .map(Some)
+    }
+
+    /// Check `content` against the signature policy and substitute its
+    /// environment placeholders
+    fn trusted_definition(
+        &self,
+        subject: &str,
+        content: &str,
+        signature: Option<&str>,
+    ) -> Result<String> {
+        self.verifier
+            .verify(subject, content.as_bytes(), signature)
+            .map_err(|e| Error::Configuration {
+                message: e.to_string(),
+            })?;
+        crate::interpolation::interpolate_env(content, self.strict_env).map_err(|e| {
+            Error::Configuration {
+                message: format!("Failed to substitute environment variables in {subject}: {e}"),
+            }
+        })
+    }
+}
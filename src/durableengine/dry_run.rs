@@ -0,0 +1,212 @@
+//! Dry runs of workflows
+//!
+//! A dry run walks a workflow with an input the way an instance would, in a
+//! sandbox engine whose events stay apart from the engine's. Tasks that reach
+//! outside the engine (`call`, `run`, `emit`, `listen` and `wait`) pass their
+//! input through instead of running; everything else runs, so `if` conditions,
+//! `switch` cases and loops are evaluated against the data the input
+//! produces. The [`DryRunReport`] lists the tasks an instance would run, in
+//! order, with the inputs they would see.
+
+use async_trait::async_trait;
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use super::shadow::{ShadowStubs, has_side_effects};
+use super::{DurableEngine, Result};
+use crate::middleware::{self, TaskMiddleware, TaskRequest};
+use crate::output;
+use crate::task_ext::TaskDefinitionExt;
+use crate::workflow::WorkflowEvent;
+
+/// What a dry run found an instance would do at one task
+#[derive(Debug, Clone, PartialEq)]
+pub enum DryRunStep {
+    /// The task would run with `input`
+    Run {
+        task_name: String,
+        task_type: String,
+        /// The task's input, after `input.from` filtering, without the
+        /// engine's own fields
+        input: Value,
+        /// Whether the task reaches outside the engine, so the dry run passed
+        /// its input through instead of running it
+        stubbed: bool,
+    },
+    /// The task's `if` condition was false, so it would be skipped
+    Skipped {
+        task_name: String,
+        condition: String,
+    },
+    /// A case of a switch task matched
+    CaseMatched { task_name: String, case: String },
+}
+
+/// The outcome of a dry run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DryRunReport {
+    /// What the instance would do, in the order it would do it
+    pub steps: Vec<DryRunStep>,
+    /// The output of the workflow, given the stubbed tasks' outputs
+    pub output: Option<Value>,
+    /// Why the dry run stopped early, e.g. a `raise` task or an expression
+    /// that failed on a stubbed task's output
+    pub error: Option<String>,
+}
+
+impl DryRunReport {
+    /// The tasks that reach outside the engine and would run
+    #[must_use]
+    pub fn stubbed(&self) -> Vec<&str> {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                DryRunStep::Run {
+                    task_name,
+                    stubbed: true,
+                    ..
+                } => Some(task_name.as_str()),
+                DryRunStep::Run { .. }
+                | DryRunStep::Skipped { .. }
+                | DryRunStep::CaseMatched { .. } => None,
+            })
+            .collect()
+    }
+}
+
+/// A task as it was about to run, recorded by [`InputRecorder`]
+#[derive(Debug)]
+struct RecordedTask {
+    task_type: String,
+    input: Value,
+    stubbed: bool,
+}
+
+/// Middleware recording the input of every task of a dry run
+#[derive(Debug, Default)]
+struct InputRecorder {
+    recorded: Mutex<HashMap<String, VecDeque<RecordedTask>>>,
+}
+
+impl InputRecorder {
+    /// The next recorded run of the task named `task_name`
+    fn take(&self, task_name: &str) -> Option<RecordedTask> {
+        self.recorded
+            .lock()
+            .ok()
+            .and_then(|mut recorded| recorded.get_mut(task_name)?.pop_front())
+    }
+}
+
+#[async_trait]
+impl TaskMiddleware for InputRecorder {
+    async fn before_task(&self, request: &TaskRequest<'_>) -> middleware::Result<()> {
+        if let Ok(mut recorded) = self.recorded.lock() {
+            recorded
+                .entry(request.task_name.to_string())
+                .or_default()
+                .push_back(RecordedTask {
+                    task_type: request.task.type_name().to_string(),
+                    input: output::filter_internal_fields(request.input),
+                    stubbed: has_side_effects(request.task),
+                });
+        }
+        Ok(())
+    }
+}
+
+impl DurableEngine {
+    /// Walk `workflow` with `input` without running the tasks that reach
+    /// outside the engine
+    ///
+    /// Nothing is recorded in the engine's persistence. Middleware still
+    /// runs, so a task a policy would refuse ends the dry run with the
+    /// refusal. Call [`DurableEngine::set_workflow_path`] first for workflows
+    /// loaded from a file, so relative resources resolve as they would in a
+    /// real run.
+    ///
+    /// # Errors
+    /// Returns an error if the sandbox engine cannot be created or its events
+    /// cannot be read. Failures of the workflow itself are reported in
+    /// [`DryRunReport::error`].
+    pub async fn dry_run(
+        &self,
+        workflow: &WorkflowDefinition,
+        input: Value,
+    ) -> Result<DryRunReport> {
+        let recorder = Arc::new(InputRecorder::default());
+        let mut sandbox = self.sandbox()?;
+        sandbox.shadow_stubs = Some(Arc::new(ShadowStubs::new(&[])));
+        sandbox.middleware = Arc::new(
+            std::iter::once(Arc::clone(&recorder) as Arc<dyn TaskMiddleware>)
+                .chain(self.middleware.iter().cloned())
+                .collect(),
+        );
+
+        let instance_id = format!("dry-run-{}", uuid::Uuid::new_v4());
+        let run = sandbox
+            .run_instance(workflow.clone(), Some(instance_id.clone()), input)
+            .await;
+        let (output, error) = match run {
+            Ok(output) => (Some(output), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let mut steps = Vec::new();
+        for event in sandbox.persistence.get_events(&instance_id).await? {
+            match event {
+                WorkflowEvent::TaskStarted { task_name, .. } => {
+                    // Tasks whose input filter failed never reached the recorder
+                    if let Some(task) = recorder.take(&task_name) {
+                        steps.push(DryRunStep::Run {
+                            task_name,
+                            task_type: task.task_type,
+                            input: task.input,
+                            stubbed: task.stubbed,
+                        });
+                    }
+                }
+                WorkflowEvent::TaskSkipped {
+                    task_name,
+                    condition,
+                    ..
+                } => steps.push(DryRunStep::Skipped {
+                    task_name,
+                    condition,
+                }),
+                WorkflowEvent::SwitchCaseMatched {
+                    task_name, case, ..
+                } => steps.push(DryRunStep::CaseMatched { task_name, case }),
+                WorkflowEvent::WorkflowStarted { .. }
+                | WorkflowEvent::TaskEntered { .. }
+                | WorkflowEvent::TaskCreated { .. }
+                | WorkflowEvent::TaskRetried { .. }
+                | WorkflowEvent::TaskCompleted { .. }
+                | WorkflowEvent::WorkflowCompleted { .. }
+                | WorkflowEvent::WorkflowCorrelationStarted { .. }
+                | WorkflowEvent::WorkflowCorrelationCompleted { .. }
+                | WorkflowEvent::WorkflowFailed { .. }
+                | WorkflowEvent::WorkflowCancelled { .. }
+                | WorkflowEvent::WorkflowSuspended { .. }
+                | WorkflowEvent::WorkflowResumed { .. }
+                | WorkflowEvent::TaskCancelled { .. }
+                | WorkflowEvent::TaskSuspended { .. }
+                | WorkflowEvent::TaskResumed { .. }
+                | WorkflowEvent::TaskFaulted { .. }
+                | WorkflowEvent::TaskReaped { .. }
+                | WorkflowEvent::TaskCacheHit { .. }
+                | WorkflowEvent::ErrorCaught { .. }
+                | WorkflowEvent::WatchesEvaluated { .. }
+                | WorkflowEvent::ShadowCompared { .. } => {}
+            }
+        }
+
+        Ok(DryRunReport {
+            steps,
+            output,
+            error,
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use crate::context::Context;
+use crate::context::{Context, MergeStrategy};
 use crate::task_ext::TaskDefinitionExt;
 use serverless_workflow_core::models::task::TaskDefinition;
 
@@ -24,24 +24,16 @@ pub async fn apply_export_to_context(
     {
         // Evaluate export.as expression on the transformed task output
         // The result becomes the new context
-        let new_context = crate::expressions::evaluate_expression(expr_str, result)?;
-        *ctx.state.data.write().await = new_context;
+        let new_context =
+            crate::expressions::evaluate_expression(ctx.metadata.evaluation, expr_str, result)?;
+        ctx.merge_data(new_context, MergeStrategy::Replace).await;
         return Ok(());
     }
 
     // No explicit export.as - apply default behavior
-    // Default: merge the transformed task output into the existing context
-    let mut current_context = ctx.state.data.write().await;
-    if let serde_json::Value::Object(result_obj) = result {
-        if let Some(context_obj) = (*current_context).as_object_mut() {
-            for (key, value) in result_obj {
-                context_obj.insert(key.clone(), value.clone());
-            }
-        }
-    } else {
-        // If result is not an object, we cannot merge - replace the context entirely
-        *current_context = result.clone();
-    }
+    // Default: merge the transformed task output into the existing context,
+    // or replace the context if the output is not an object
+    ctx.merge_data(result.clone(), MergeStrategy::Shallow).await;
 
     Ok(())
 }
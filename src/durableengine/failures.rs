@@ -0,0 +1,300 @@
+//! Grouping of failed instances by what went wrong
+//!
+//! Each failure is fingerprinted by the task it stopped in, the type of its
+//! error and its message with the parts that differ between occurrences, such
+//! as IDs, numbers and quoted values, replaced by placeholders.
+//! [`DurableEngine::failure_clusters`] groups failed instances by fingerprint,
+//! so a hundred instances timing out in the same task show up as one cluster
+//! of a hundred.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::LazyLock;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::{DurableEngine, InstanceFilter, InstanceState, InstanceStatus, Result};
+
+/// Instance IDs kept as examples of a cluster
+const MAX_EXAMPLES: usize = 10;
+
+/// Parts of error messages that differ between occurrences of the same failure,
+/// and the placeholders they are replaced with, in the order they are applied
+#[allow(clippy::expect_used)]
+static RE_VARIABLE_PARTS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    [
+        (r#""[^"]*"|'[^']*'|`[^`]*`"#, "<str>"),
+        (
+            r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b",
+            "<uuid>",
+        ),
+        (
+            r"\b(?:0x)?[0-9a-fA-F]*[0-9][0-9a-fA-F]*[a-fA-F][0-9a-fA-F]*\b",
+            "<hex>",
+        ),
+        (r"\d+(?:\.\d+)?", "<n>"),
+        (r"\s+", " "),
+    ]
+    .into_iter()
+    .map(|(pattern, placeholder)| {
+        (
+            Regex::new(pattern).expect("hardcoded regex should be valid"),
+            placeholder,
+        )
+    })
+    .collect()
+});
+
+/// What identifies a failure, regardless of the instance it happened in
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ErrorFingerprint {
+    /// The task the instance stopped in
+    pub task_name: Option<String>,
+    /// The `type` of a raised error, or the category an error message starts
+    /// with, such as `Timeout` or `Executor error`
+    pub error_type: String,
+    /// The error message with its variable parts replaced by placeholders
+    pub message: String,
+}
+
+impl ErrorFingerprint {
+    /// Fingerprint an error recorded for an instance that stopped in `task_name`
+    #[must_use]
+    pub fn new(task_name: Option<&str>, error: &str) -> Self {
+        let (error_type, message) = split_error(error);
+        let message = RE_VARIABLE_PARTS
+            .iter()
+            .fold(message, |message, (pattern, placeholder)| {
+                pattern.replace_all(&message, *placeholder).into_owned()
+            });
+        Self {
+            task_name: task_name.map(str::to_string),
+            error_type,
+            message: message.trim().to_string(),
+        }
+    }
+
+    /// A short, stable ID of the fingerprint
+    #[must_use]
+    pub fn id(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            self.task_name.as_deref().unwrap_or_default(),
+            &self.error_type,
+            &self.message,
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        format!("{:x}", hasher.finalize())
+            .chars()
+            .take(12)
+            .collect()
+    }
+}
+
+/// The type and message of an error: raised errors are recorded as JSON
+/// objects, other errors as their display, led by their category
+fn split_error(error: &str) -> (String, String) {
+    if let Ok(serde_json::Value::Object(raised)) = serde_json::from_str(error) {
+        let field = |name: &str| {
+            raised
+                .get(name)
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string)
+        };
+        let message = [field("title"), field("detail")]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(": ");
+        return (field("type").unwrap_or_default(), message);
+    }
+
+    match error.split_once(": ") {
+        Some((category, message)) if !category.chars().any(|c| c.is_ascii_digit()) => {
+            (category.to_string(), message.to_string())
+        }
+        Some(_) | None => (String::new(), error.to_string()),
+    }
+}
+
+/// Failed instances that share a fingerprint
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureCluster {
+    /// ID of the fingerprint, see [`ErrorFingerprint::id`]
+    pub id: String,
+    #[serde(flatten)]
+    pub fingerprint: ErrorFingerprint,
+    pub count: usize,
+    /// Workflows the failed instances ran, by "namespace/name/version" key
+    pub workflows: BTreeSet<String>,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Some of the failed instances, the most recent first
+    pub instances: Vec<String>,
+}
+
+impl FailureCluster {
+    /// Group the faulted and timed out instances among `instances` by
+    /// fingerprint, the largest clusters first
+    #[must_use]
+    pub fn from_instances(instances: &[InstanceStatus]) -> Vec<Self> {
+        let mut failed: Vec<&InstanceStatus> = instances
+            .iter()
+            .filter(|instance| {
+                matches!(
+                    instance.state,
+                    InstanceState::Faulted | InstanceState::TimedOut
+                )
+            })
+            .collect();
+        failed.sort_by(|a, b| b.finished_at.cmp(&a.finished_at));
+
+        let mut clusters: HashMap<ErrorFingerprint, Self> = HashMap::new();
+        for instance in failed {
+            let fingerprint = ErrorFingerprint::new(
+                instance.current_task.as_deref(),
+                instance.error.as_deref().unwrap_or_default(),
+            );
+            let cluster = clusters.entry(fingerprint.clone()).or_insert_with(|| Self {
+                id: fingerprint.id(),
+                fingerprint,
+                count: 0,
+                workflows: BTreeSet::new(),
+                first_seen: None,
+                last_seen: instance.finished_at,
+                instances: Vec::new(),
+            });
+            cluster.count += 1;
+            cluster.workflows.extend(instance.workflow_id.clone());
+            // Instances are visited from the most recent failure back
+            if instance.finished_at.is_some() {
+                cluster.first_seen = instance.finished_at;
+            }
+            if cluster.instances.len() < MAX_EXAMPLES {
+                cluster.instances.push(instance.instance_id.clone());
+            }
+        }
+
+        let mut clusters: Vec<Self> = clusters.into_values().collect();
+        clusters.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| b.last_seen.cmp(&a.last_seen))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        clusters
+    }
+}
+
+impl DurableEngine {
+    /// Group the failed instances matching `filter` by the fingerprint of their error
+    ///
+    /// Set [`InstanceFilter::started_after`] to only look at recent failures.
+    /// Like [`DurableEngine::list_instances`], the instances are read from the
+    /// read provider, if one is configured.
+    ///
+    /// # Errors
+    /// Returns an error if the instances or their events cannot be read.
+    pub async fn failure_clusters(&self, filter: &InstanceFilter) -> Result<Vec<FailureCluster>> {
+        let instances = self.list_instances(filter).await?;
+        Ok(FailureCluster::from_instances(&instances))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use chrono::Duration;
+
+    fn failed(task: &str, error: &str, minutes_ago: i64) -> InstanceStatus {
+        let finished = Utc::now() - Duration::minutes(minutes_ago);
+        InstanceStatus {
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            workflow_id: Some("default/orders/1.0.0".to_string()),
+            state: InstanceState::Faulted,
+            current_task: Some(task.to_string()),
+            started_at: Some(finished),
+            updated_at: Some(finished),
+            finished_at: Some(finished),
+            output: None,
+            error: Some(error.to_string()),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_variable_parts() {
+        let first = ErrorFingerprint::new(
+            Some("charge"),
+            "Executor error: Request to 'https://pay/a1' failed after 30s for 0b7c6a52-3f0e-4a4e-9d7b-2c1f5b8e9a10",
+        );
+        let second = ErrorFingerprint::new(
+            Some("charge"),
+            "Executor error: Request to 'https://pay/b2' failed after 31s for 9f1d2e3c-0000-4a4e-9d7b-2c1f5b8e9a10",
+        );
+        assert_eq!(first, second);
+        assert_eq!(first.id(), second.id());
+        assert_eq!(first.error_type, "Executor error");
+        assert_eq!(
+            first.message,
+            "Request to <str> failed after <n>s for <uuid>"
+        );
+
+        // The same error in another task is another failure
+        let elsewhere = ErrorFingerprint::new(
+            Some("ship"),
+            "Executor error: Request to 'https://pay/a1' failed after 30s for 0b7c6a52-3f0e-4a4e-9d7b-2c1f5b8e9a10",
+        );
+        assert_ne!(first.id(), elsewhere.id());
+    }
+
+    #[test]
+    fn test_fingerprint_of_raised_error() {
+        let fingerprint = ErrorFingerprint::new(
+            Some("validate"),
+            r#"{"type":"https://example.com/errors/invalid","title":"Invalid order","status":400,"detail":"Order 1234 has no items","instance":"/do/0/validate"}"#,
+        );
+        assert_eq!(fingerprint.error_type, "https://example.com/errors/invalid");
+        assert_eq!(fingerprint.message, "Invalid order: Order <n> has no items");
+    }
+
+    #[test]
+    fn test_failures_are_clustered_by_fingerprint() {
+        let instances = vec![
+            failed("charge", "Timeout: Task timed out after 30s", 30),
+            failed("charge", "Timeout: Task timed out after 31s", 20),
+            failed("charge", "Timeout: Task timed out after 29s", 10),
+            failed("ship", "Executor error: Carrier unavailable", 5),
+            InstanceStatus {
+                state: InstanceState::Completed,
+                error: None,
+                ..failed("charge", "", 1)
+            },
+        ];
+
+        let clusters = FailureCluster::from_instances(&instances);
+        assert_eq!(clusters.len(), 2);
+        let timeouts = clusters.first().unwrap();
+        assert_eq!(timeouts.count, 3);
+        assert_eq!(timeouts.fingerprint.task_name.as_deref(), Some("charge"));
+        assert_eq!(timeouts.fingerprint.error_type, "Timeout");
+        assert_eq!(timeouts.fingerprint.message, "Task timed out after <n>s");
+        assert_eq!(timeouts.first_seen, instances.first().unwrap().finished_at);
+        assert_eq!(timeouts.last_seen, instances.get(2).unwrap().finished_at);
+        assert_eq!(
+            timeouts.instances.first(),
+            Some(&instances.get(2).unwrap().instance_id)
+        );
+        assert!(timeouts.workflows.contains("default/orders/1.0.0"));
+
+        let carrier = clusters.get(1).unwrap();
+        assert_eq!(carrier.count, 1);
+        assert_eq!(carrier.fingerprint.task_name.as_deref(), Some("ship"));
+    }
+}
@@ -0,0 +1,26 @@
+use serverless_workflow_core::models::task::TaskDefinition;
+
+use crate::context::Context;
+use crate::task_ext::TaskDefinitionExt;
+
+pub(super) use crate::graph::FlowDirective;
+
+/// The directive to follow after `task` completed
+///
+/// A target chosen while the task ran (a Switch case, or `end` reached
+/// in a nested list) takes precedence over the task's own `then`.
+pub(super) async fn after(task: &TaskDefinition, ctx: &Context) -> FlowDirective {
+    let chosen = ctx.state.next_task.write().await.take();
+    chosen
+        .as_deref()
+        .or_else(|| task.then())
+        .map_or(FlowDirective::Continue, FlowDirective::parse)
+}
+
+/// Stop every enclosing list and complete the workflow
+///
+/// Lists nested in a task only return to their caller, so `end` is handed up
+/// to the main loop as the next task of each enclosing task.
+pub(super) async fn propagate_end(ctx: &Context) {
+    *ctx.state.next_task.write().await = Some("end".to_string());
+}
@@ -1,124 +1,120 @@
-use petgraph::{graph::DiGraph, stable_graph::NodeIndex};
+use petgraph::stable_graph::NodeIndex;
 use serverless_workflow_core::models::task::TaskDefinition;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use std::collections::HashMap;
 
 use super::{Error, Result};
 
-/// Build an execution graph from a workflow definition
+pub use crate::graph::TaskGraph;
+pub(super) use crate::graph::next_in_sequence;
+
+/// Build the execution graph of a workflow, and check that this engine can
+/// run each of its call tasks
 ///
 /// Returns a tuple of (graph, ``task_name_to_node_index_map``)
 pub(super) fn build_graph(
     workflow: &WorkflowDefinition,
-) -> Result<(
-    DiGraph<(String, TaskDefinition), ()>,
-    HashMap<String, NodeIndex>,
-)> {
-    let mut graph = DiGraph::new();
-    let mut nodes = HashMap::new();
-    let mut task_names = Vec::new();
+) -> Result<(TaskGraph<'_>, HashMap<&str, NodeIndex>)> {
+    let built = crate::graph::build_graph(workflow)?;
+    validate_calls(workflow)?;
+    Ok(built)
+}
 
-    // Iterate over all task entries in the Map and preserve order
+/// Check the parameters of every call task, including nested ones, against the
+/// parameter type of its executor
+///
+/// Done while the graph is built, so malformed parameters are reported by
+/// `jackdaw validate` and fail an execution before any task runs.
+fn validate_calls(workflow: &WorkflowDefinition) -> Result<()> {
     for entry in &workflow.do_.entries {
         for (name, task) in entry {
-            let node = graph.add_node((name.clone(), task.clone()));
-            nodes.insert(name.clone(), node);
-            task_names.push(name.clone());
+            validate_task_calls(workflow, name, task)?;
         }
     }
+    Ok(())
+}
 
-    // Build explicit edges based on 'then' transitions
-    let mut has_explicit_transitions = false;
-    for entry in &workflow.do_.entries {
-        for (name, task) in entry {
-            let src = nodes.get(name).ok_or(Error::TaskExecution {
-                message: "Task not found".to_string(),
-            })?;
-            let transitions = get_task_transitions(task);
-            if !transitions.is_empty() {
-                has_explicit_transitions = true;
-                for target in transitions {
-                    if let Some(&dst) = nodes.get(&target) {
-                        graph.add_edge(*src, dst, ());
-                    }
+fn validate_task_calls(
+    workflow: &WorkflowDefinition,
+    name: &str,
+    task: &TaskDefinition,
+) -> Result<()> {
+    match task {
+        TaskDefinition::Call(call_task) => {
+            let mut params = call_task.with.clone().unwrap_or_default();
+            let mut call_type = call_task.call.as_str();
+            // Calls of user-defined functions use the function's call type and parameters
+            if let Some(TaskDefinition::Call(function)) = workflow
+                .use_
+                .as_ref()
+                .and_then(|use_| use_.functions.as_ref())
+                .and_then(|functions| functions.get(&call_task.call))
+            {
+                let mut merged = function.with.clone().unwrap_or_default();
+                merged.extend(params);
+                params = merged;
+                call_type = function.call.as_str();
+            }
+            if let Some(feature) = crate::providers::executors::disabled_feature(call_type) {
+                return Err(Error::Configuration {
+                    message: format!(
+                        "Task {name}: {call_type} calls need jackdaw built with the `{feature}` feature"
+                    ),
+                });
+            }
+            crate::providers::executors::validate_call_params(
+                call_type,
+                &serde_json::to_value(&params)?,
+            )
+            .map_err(|e| Error::Configuration {
+                message: format!("Task {name}: {e}"),
+            })
+        }
+        TaskDefinition::Do(t) => {
+            for entry in &t.do_.entries {
+                for (name, task) in entry {
+                    validate_task_calls(workflow, name, task)?;
                 }
             }
+            Ok(())
         }
-    }
-
-    // If no explicit transitions, create implicit sequential edges
-    if !has_explicit_transitions && task_names.len() > 1 {
-        for i in 0..task_names.len() - 1 {
-            let src_name = task_names.get(i).ok_or(Error::TaskExecution {
-                message: "Task not found".to_string(),
-            })?;
-            let dst_name = task_names.get(i + 1).ok_or(Error::TaskExecution {
-                message: "Task not found".to_string(),
-            })?;
-            let src = nodes.get(src_name).ok_or(Error::TaskExecution {
-                message: "Task not found".to_string(),
-            })?;
-            let dst = nodes.get(dst_name).ok_or(Error::TaskExecution {
-                message: "Task not found".to_string(),
-            })?;
-            graph.add_edge(*src, *dst, ());
+        TaskDefinition::For(t) => {
+            for entry in &t.do_.entries {
+                for (name, task) in entry {
+                    validate_task_calls(workflow, name, task)?;
+                }
+            }
+            Ok(())
         }
-    }
-
-    Ok((graph, nodes))
-}
-
-/// Extract all transition targets from a task definition
-///
-/// Returns a vector of task names that this task can transition to
-pub(super) fn get_task_transitions(task: &TaskDefinition) -> Vec<String> {
-    match task {
-        TaskDefinition::Call(t) => t
-            .common
-            .then
-            .as_ref()
-            .map(|s| vec![s.clone()])
-            .unwrap_or_default(),
-        TaskDefinition::Set(t) => t
-            .common
-            .then
-            .as_ref()
-            .map(|s| vec![s.clone()])
-            .unwrap_or_default(),
-        TaskDefinition::Fork(t) => t
-            .common
-            .then
-            .as_ref()
-            .map(|s| vec![s.clone()])
-            .unwrap_or_default(),
-        TaskDefinition::Switch(t) => {
-            let mut transitions = Vec::new();
-            for entry in &t.switch.entries {
-                for case in entry.values() {
-                    if let Some(then) = &case.then {
-                        transitions.push(then.clone());
+        TaskDefinition::Fork(t) => {
+            for entry in &t.fork.branches.entries {
+                for (name, task) in entry {
+                    validate_task_calls(workflow, name, task)?;
+                }
+            }
+            Ok(())
+        }
+        TaskDefinition::Try(t) => {
+            for entry in &t.try_.entries {
+                for (name, task) in entry {
+                    validate_task_calls(workflow, name, task)?;
+                }
+            }
+            if let Some(catch_tasks) = &t.catch.do_ {
+                for entry in &catch_tasks.entries {
+                    for (name, task) in entry {
+                        validate_task_calls(workflow, name, task)?;
                     }
                 }
             }
-            transitions
+            Ok(())
         }
-        TaskDefinition::Do(t) => t
-            .common
-            .then
-            .as_ref()
-            .map(|s| vec![s.clone()])
-            .unwrap_or_default(),
-        TaskDefinition::Emit(t) => t
-            .common
-            .then
-            .as_ref()
-            .map(|s| vec![s.clone()])
-            .unwrap_or_default(),
-        TaskDefinition::For(_)
+        TaskDefinition::Emit(_)
         | TaskDefinition::Listen(_)
         | TaskDefinition::Raise(_)
         | TaskDefinition::Run(_)
-        | TaskDefinition::Try(_)
-        | TaskDefinition::Wait(_) => vec![],
+        | TaskDefinition::Set(_)
+        | TaskDefinition::Switch(_)
+        | TaskDefinition::Wait(_) => Ok(()),
     }
 }
@@ -0,0 +1,94 @@
+//! Ownership of the instances an engine runs
+//!
+//! Before an instance runs, the engine takes its lease in persistence and
+//! renews it in the background until the run ends. If the process dies, the
+//! lease lapses once its TTL has passed and another process may resume the
+//! instance. Taking the lease over fences the old process off: writes it
+//! makes for the instance afterwards are refused rather than interleaved with
+//! the new owner's.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use super::{DurableEngine, Error, Result};
+use crate::persistence::{Lease, PersistenceProvider};
+
+/// How long an instance lease lasts without being renewed, unless configured
+pub const DEFAULT_LEASE_TTL: Duration = Duration::from_secs(30);
+
+/// A lease held while an instance runs, renewed until it is released
+pub(super) struct HeldLease {
+    lease: Lease,
+    persistence: Arc<dyn PersistenceProvider>,
+    heartbeat: JoinHandle<()>,
+}
+
+impl HeldLease {
+    /// Stop renewing the lease and give it up
+    pub(super) async fn release(self) {
+        self.heartbeat.abort();
+        if let Err(e) = self.persistence.release_lease(&self.lease).await {
+            tracing::warn!(
+                "Failed to release the lease on {}: {}",
+                self.lease.instance_id,
+                e
+            );
+        }
+    }
+}
+
+impl Drop for HeldLease {
+    fn drop(&mut self) {
+        // A run that is dropped leaves its lease to lapse
+        self.heartbeat.abort();
+    }
+}
+
+impl DurableEngine {
+    /// Take the lease of an instance for this engine and keep renewing it
+    ///
+    /// # Errors
+    /// Returns [`Error::InstanceLeased`] while another process holds the lease.
+    pub(super) async fn take_lease(&self, instance_id: &str) -> Result<HeldLease> {
+        let lease = self
+            .persistence
+            .acquire_lease(instance_id, &self.owner, self.lease_ttl)
+            .await?
+            .ok_or_else(|| Error::InstanceLeased {
+                instance_id: instance_id.to_string(),
+            })?;
+        let heartbeat = tokio::spawn(heartbeat(
+            self.persistence.clone(),
+            lease.clone(),
+            self.lease_ttl,
+        ));
+
+        Ok(HeldLease {
+            lease,
+            persistence: self.persistence.clone(),
+            heartbeat,
+        })
+    }
+}
+
+/// Renew a lease three times per TTL until another process takes it over
+async fn heartbeat(persistence: Arc<dyn PersistenceProvider>, lease: Lease, ttl: Duration) {
+    let mut interval = tokio::time::interval((ttl / 3).max(Duration::from_millis(1)));
+    // The first tick completes straight away
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match persistence
+            .acquire_lease(&lease.instance_id, &lease.owner, ttl)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                tracing::warn!("Lost the lease on {} to another process", lease.instance_id);
+                return;
+            }
+            Err(e) => tracing::warn!("Failed to renew the lease on {}: {}", lease.instance_id, e),
+        }
+    }
+}
@@ -5,8 +5,12 @@ use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::listeners::{EventSource, Listener, grpc::GrpcListener, http::HttpListener};
+use crate::events::{CloudEvent, InboundEvent, ReadMode};
+#[cfg(feature = "grpc")]
+use crate::listeners::grpc::{GrpcListener, compile_proto};
+use crate::listeners::{EventSource, Listener, ListenerAuth, http::HttpListener};
 use crate::providers::executors::{PythonExecutor, TypeScriptExecutor};
+use crate::telemetry::Telemetry;
 
 use super::{DurableEngine, Error, Result};
 
@@ -37,13 +41,30 @@ fn convert_path_params_to_axum(path: &str) -> String {
 }
 
 impl DurableEngine {
+    /// Deliver an event to the listen tasks of the instances running in this engine
+    ///
+    /// Listen tasks consume the event if it matches their `listen.to`
+    /// strategy; events that arrive while no listen task runs are dropped.
+    ///
+    /// # Errors
+    /// Returns an error if the event cannot be serialized
+    #[allow(dead_code)]
+    pub fn publish_event(&self, event: &CloudEvent) -> Result<()> {
+        let event = serde_json::to_value(event)?;
+        let _ = self.event_bus.send(InboundEvent {
+            event,
+            listener: None,
+        });
+        Ok(())
+    }
+
     /// Initialize all listeners from the workflow before task execution begins
     ///
     /// This scans the workflow for all Listen tasks, groups them by bind address,
     /// and starts all listeners together with their complete route tables
     pub(super) async fn initialize_listeners(&self, workflow: &WorkflowDefinition) -> Result<()> {
         // Collect all HTTP routes grouped by (bind_addr, openapi_path)
-        // Key: (bind_addr, openapi_path), Value: Vec of (path, task_name, handler)
+        // Key: (bind_addr, openapi_path), Value: Vec of (path, task_name, handler, read_mode, auth)
         let mut http_routes: HashMap<
             (String, String),
             Vec<(
@@ -54,6 +75,8 @@ impl DurableEngine {
                         + Send
                         + Sync,
                 >,
+                ReadMode,
+                Option<ListenerAuth>,
             )>,
         > = HashMap::new();
 
@@ -77,9 +100,18 @@ impl DurableEngine {
             for (task_name, task) in entry {
                 if let TaskDefinition::Listen(listen_task) = task {
                     // Extract event source and handler information
-                    let (source_value, schema_path_opt) =
-                        self.extract_listen_source(listen_task)?;
+                    let Some((source_value, schema_path_opt)) =
+                        self.extract_listen_source(listen_task)?
+                    else {
+                        continue;
+                    };
                     let event_source: EventSource = serde_json::from_value(source_value)?;
+                    let read_mode =
+                        ReadMode::parse(listen_task.listen.read.as_deref()).map_err(|e| {
+                            Error::Listener {
+                                message: format!("Task {task_name}: {e}"),
+                            }
+                        })?;
 
                     // Handle HTTP listeners
                     if event_source.uri.starts_with("http://")
@@ -116,22 +148,47 @@ impl DurableEngine {
                         let openapi_path = schema_path_opt.ok_or_else(|| Error::Listener {
                             message: "HTTP listener requires OpenAPI schema".to_string(),
                         })?;
+                        let openapi_path = self
+                            .resolve_resource(workflow, &openapi_path)
+                            .await
+                            .path
+                            .to_string_lossy()
+                            .into_owned();
 
                         // Create handler for this route
                         let handler = self.create_handler_from_listen_task(listen_task)?;
 
-                        // Get the read mode and wrap the handler to apply it
-                        let read_mode = listen_task.listen.read.as_deref().unwrap_or("envelope");
-                        let wrapped_handler = wrap_handler_with_read_mode(handler, read_mode);
+                        // Wrap the handler to apply the read mode
+                        let wrapped_handler = wrap_handler_with_telemetry(
+                            wrap_handler_with_delivery(
+                                wrap_handler_with_read_mode(handler, read_mode),
+                                self.event_bus.clone(),
+                                task_name,
+                            ),
+                            self.telemetry.clone(),
+                            "http",
+                            task_name,
+                        );
+
+                        let auth = self.listener_auth(workflow, &event_source).await?;
 
                         // Group by (bind_addr, openapi_path) - different specs can coexist on same port
                         http_routes
                             .entry((bind_addr.clone(), openapi_path.clone()))
                             .or_default()
-                            .push((path, task_name.clone(), wrapped_handler));
+                            .push((path, task_name.clone(), wrapped_handler, read_mode, auth));
                     }
                     // Handle gRPC listeners
                     else if event_source.uri.starts_with("grpc://") {
+                        if cfg!(not(feature = "grpc")) {
+                            return Err(Error::Listener {
+                                message: format!(
+                                    "Cannot listen on {}: jackdaw was built without the `grpc` feature",
+                                    event_source.uri
+                                ),
+                            });
+                        }
+
                         // Parse bind address and method from URI (e.g., grpc://localhost:50051/calculator.Calculator/Add)
                         let uri = &event_source.uri;
                         let without_scheme =
@@ -178,13 +235,28 @@ impl DurableEngine {
                         let proto_path = schema_path_opt.ok_or_else(|| Error::Listener {
                             message: "gRPC listener requires proto schema".to_string(),
                         })?;
+                        let proto = self.resolve_resource(workflow, &proto_path).await;
+                        if !proto.path.is_file() {
+                            return Err(Error::Listener {
+                                message: format!("Proto file {proto} does not exist"),
+                            });
+                        }
+                        let proto_path = proto.path.to_string_lossy().into_owned();
 
                         // Create handler for this method
                         let handler = self.create_handler_from_listen_task(listen_task)?;
 
-                        // Get the read mode and wrap the handler to apply it
-                        let read_mode = listen_task.listen.read.as_deref().unwrap_or("envelope");
-                        let wrapped_handler = wrap_handler_with_read_mode(handler, read_mode);
+                        // Wrap the handler to apply the read mode
+                        let wrapped_handler = wrap_handler_with_telemetry(
+                            wrap_handler_with_delivery(
+                                wrap_handler_with_read_mode(handler, read_mode),
+                                self.event_bus.clone(),
+                                task_name,
+                            ),
+                            self.telemetry.clone(),
+                            "grpc",
+                            task_name,
+                        );
 
                         // Group by (bind_addr, proto_path, service_name)
                         grpc_methods
@@ -202,17 +274,29 @@ impl DurableEngine {
         for ((bind_addr, _openapi_path), routes) in http_routes {
             // Build route handlers map
             let mut route_handlers = std::collections::HashMap::new();
-            for (path, task_name, handler) in routes {
+            let mut route_auth = Vec::new();
+            let mut route_read_modes = Vec::new();
+            for (path, task_name, handler, read_mode, auth) in routes {
                 // Convert OpenAPI-style path params {param} to Axum-style :param
                 let axum_path = convert_path_params_to_axum(&path);
                 route_handlers.insert(axum_path.clone(), handler);
                 println!(
                     "  Registering route {axum_path} (from {path}) for task {task_name} on {bind_addr}"
                 );
+                route_read_modes.push((axum_path.clone(), read_mode));
+                if let Some(auth) = auth {
+                    route_auth.push((axum_path, auth));
+                }
             }
 
             // Create and start the listener with all routes
-            let listener = HttpListener::new_multi_route(bind_addr.clone(), route_handlers)?;
+            let mut listener = HttpListener::new_multi_route(bind_addr.clone(), route_handlers)?;
+            for (path, auth) in route_auth {
+                listener = listener.with_auth(path, auth);
+            }
+            for (path, read_mode) in route_read_modes {
+                listener = listener.with_read_mode(path, read_mode);
+            }
             let listener_arc = Arc::new(listener);
             listener_arc.start().await?;
 
@@ -224,137 +308,125 @@ impl DurableEngine {
         }
 
         // Now create all gRPC listeners with their complete method tables
-        let mut grpc_listeners = self.grpc_listeners.write().await;
-
-        for ((bind_addr, proto_path, service_name), methods) in grpc_methods {
-            use prost_reflect::DynamicMessage;
-
-            // Compile proto file to get descriptors
-            let file_descriptor_set = protox::compile([proto_path.as_str()], ["."])?;
-            let pool =
-                prost_reflect::DescriptorPool::from_file_descriptor_set(file_descriptor_set)?;
+        #[cfg(feature = "grpc")]
+        {
+            let mut grpc_listeners = self.grpc_listeners.write().await;
 
-            // Get service descriptor
-            let service_descriptor =
-                pool.get_service_by_name(&service_name)
-                    .ok_or_else(|| Error::Listener {
-                        message: format!("Service {service_name} not found in proto file"),
-                    })?;
+            for ((bind_addr, proto_path, service_name), methods) in grpc_methods {
+                use prost_reflect::DynamicMessage;
 
-            // Build method handlers map - convert JSON handlers to DynamicMessage handlers
-            let mut method_handlers: std::collections::HashMap<
-                String,
-                Arc<
-                    dyn Fn(DynamicMessage) -> crate::listeners::Result<DynamicMessage>
-                        + Send
-                        + Sync,
-                >,
-            > = std::collections::HashMap::new();
+                // Compile proto file to get descriptors, unless the snapshot the
+                // engine was started from has them
+                let file_descriptor_set = match self.warm_start.proto_descriptors(&proto_path) {
+                    Some(file_descriptor_set) => file_descriptor_set,
+                    None => compile_proto(&proto_path)?,
+                };
+                let pool =
+                    prost_reflect::DescriptorPool::from_file_descriptor_set(file_descriptor_set)?;
+
+                // Get service descriptor
+                let service_descriptor =
+                    pool.get_service_by_name(&service_name)
+                        .ok_or_else(|| Error::Listener {
+                            message: format!("Service {service_name} not found in proto file"),
+                        })?;
 
-            for (method_name, task_name, json_handler) in methods {
-                println!(
-                    "  Registering gRPC method {service_name}/{method_name} for task {task_name} on {bind_addr}"
-                );
+                // Build method handlers map - convert JSON handlers to DynamicMessage handlers
+                let mut method_handlers: std::collections::HashMap<
+                    String,
+                    Arc<
+                        dyn Fn(DynamicMessage) -> crate::listeners::Result<DynamicMessage>
+                            + Send
+                            + Sync,
+                    >,
+                > = std::collections::HashMap::new();
+
+                for (method_name, task_name, json_handler) in methods {
+                    println!(
+                        "  Registering gRPC method {service_name}/{method_name} for task {task_name} on {bind_addr}"
+                    );
+
+                    // Get method descriptor for this method
+                    let method_descriptor = service_descriptor
+                        .methods()
+                        .find(|m| m.name() == method_name)
+                        .ok_or_else(|| Error::Listener {
+                            message: format!(
+                                "Method {method_name} not found in service {service_name}"
+                            ),
+                        })?;
 
-                // Get method descriptor for this method
-                let method_descriptor = service_descriptor
-                    .methods()
-                    .find(|m| m.name() == method_name)
-                    .ok_or_else(|| Error::Listener {
-                        message: format!(
-                            "Method {method_name} not found in service {service_name}"
-                        ),
-                    })?;
+                    let output_descriptor = method_descriptor.output();
 
-                let output_descriptor = method_descriptor.output();
+                    // Clone the Arc so each closure gets its own reference
+                    let json_handler_clone = json_handler.clone();
 
-                // Clone the Arc so each closure gets its own reference
-                let json_handler_clone = json_handler.clone();
+                    // Create a wrapper that converts DynamicMessage to JSON, calls the JSON handler, then converts back
+                    let wrapped_handler: Arc<
+                        dyn Fn(DynamicMessage) -> crate::listeners::Result<DynamicMessage>
+                            + Send
+                            + Sync,
+                    > = Arc::new(
+                        move |request_msg: DynamicMessage| -> crate::listeners::Result<DynamicMessage> {
+                            // Convert DynamicMessage to JSON
+                            let request_json = dynamic_message_to_json(&request_msg);
 
-                // Create a wrapper that converts DynamicMessage to JSON, calls the JSON handler, then converts back
-                let wrapped_handler: Arc<
-                    dyn Fn(DynamicMessage) -> crate::listeners::Result<DynamicMessage>
-                        + Send
-                        + Sync,
-                > = Arc::new(
-                    move |request_msg: DynamicMessage| -> crate::listeners::Result<DynamicMessage> {
-                        // Convert DynamicMessage to JSON
-                        let request_json = dynamic_message_to_json(&request_msg);
+                            // Call the JSON handler
+                            let response_json = json_handler_clone(request_json)?;
 
-                        // Call the JSON handler
-                        let response_json = json_handler_clone(request_json)?;
+                            // Convert JSON response back to DynamicMessage using the output descriptor
+                            let response_msg =
+                                json_to_dynamic_message(&response_json, &output_descriptor);
+                            Ok(response_msg)
+                        },
+                    );
 
-                        // Convert JSON response back to DynamicMessage using the output descriptor
-                        let response_msg =
-                            json_to_dynamic_message(&response_json, &output_descriptor);
-                        Ok(response_msg)
-                    },
-                );
+                    method_handlers.insert(method_name.clone(), wrapped_handler);
+                }
 
-                method_handlers.insert(method_name.clone(), wrapped_handler);
+                // Create and start the gRPC listener with all methods
+                let listener = GrpcListener::new_multi_method(
+                    bind_addr.clone(),
+                    &proto_path,
+                    &service_name,
+                    method_handlers,
+                )?;
+                let listener_arc = Arc::new(listener);
+                listener_arc.start().await?;
+
+                // Wait a bit for the server to start
+                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+                grpc_listeners.insert(bind_addr.clone(), listener_arc);
+                println!("  gRPC listener started on {bind_addr}");
             }
-
-            // Create and start the gRPC listener with all methods
-            let listener = GrpcListener::new_multi_method(
-                bind_addr.clone(),
-                &proto_path,
-                &service_name,
-                method_handlers,
-            )?;
-            let listener_arc = Arc::new(listener);
-            listener_arc.start().await?;
-
-            // Wait a bit for the server to start
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-            grpc_listeners.insert(bind_addr.clone(), listener_arc);
-            println!("  gRPC listener started on {bind_addr}");
         }
 
         Ok(())
     }
 
     /// Extract event source and ``OpenAPI`` path from a Listen task
+    ///
+    /// A filter whose `source` is an object configures the listener the task
+    /// receives events from. Tasks without one consume events from other
+    /// sources, e.g. `emit` tasks, and need no listener.
     #[allow(clippy::unused_self)]
-    fn extract_listen_source(
+    pub(super) fn extract_listen_source(
         &self,
         listen_task: &ListenTaskDefinition,
-    ) -> Result<(serde_json::Value, Option<String>)> {
-        let (_event_filter, _with_attrs, source_value) = if let Some(one_filter) =
-            &listen_task.listen.to.one
-        {
-            let with_attrs = one_filter
-                .with
-                .as_ref()
-                .ok_or_else(|| Error::Configuration {
-                    message: "Listen task requires 'with' attributes".to_string(),
-                })?;
-            let source_value = with_attrs
-                .get("source")
-                .ok_or_else(|| Error::Configuration {
-                    message: "Listen task requires 'source' in 'with' attributes".to_string(),
-                })?;
-            (one_filter, with_attrs, source_value)
-        } else if let Some(any_filters) = &listen_task.listen.to.any {
-            let first_filter = any_filters.first().ok_or_else(|| Error::Configuration {
-                message: "Listen task 'any' requires at least one event filter".to_string(),
-            })?;
-            let with_attrs = first_filter
-                .with
-                .as_ref()
-                .ok_or_else(|| Error::Configuration {
-                    message: "Listen task requires 'with' attributes".to_string(),
-                })?;
-            let source_value = with_attrs
-                .get("source")
-                .ok_or_else(|| Error::Configuration {
-                    message: "Listen task requires 'source' in 'with' attributes".to_string(),
-                })?;
-            (first_filter, with_attrs, source_value)
-        } else {
-            return Err(Error::Configuration {
-                message: "Listen task requires either 'one' or 'any' event filter".to_string(),
-            });
+    ) -> Result<Option<(serde_json::Value, Option<String>)>> {
+        let to = serde_json::to_value(&listen_task.listen.to)?;
+        let filters = to.get("one").into_iter().chain(
+            ["any", "all"]
+                .iter()
+                .filter_map(|strategy| to.get(*strategy).and_then(serde_json::Value::as_array))
+                .flatten(),
+        );
+        let Some(source_value) = filters
+            .filter_map(|filter| filter.pointer("/with/source"))
+            .find(|source| source.is_object())
+        else {
+            return Ok(None);
         };
 
         // Get OpenAPI schema path if present
@@ -364,7 +436,58 @@ impl DurableEngine {
             .as_ref()
             .map(|s| s.resource.endpoint.clone());
 
-        Ok((source_value.clone(), openapi_path))
+        Ok(Some((source_value.clone(), openapi_path)))
+    }
+
+    /// How the HTTP listener of a listen task authenticates requests
+    ///
+    /// The source's `authentication` takes precedence over the engine's
+    /// listener authentication. A scheme given as `use: <secret>` takes its
+    /// properties from a secret declared in `use.secrets`, holding them as a
+    /// JSON object.
+    async fn listener_auth(
+        &self,
+        workflow: &WorkflowDefinition,
+        source: &EventSource,
+    ) -> Result<Option<ListenerAuth>> {
+        let Some(policy) = &source.authentication else {
+            return Ok(self.listener_auth.as_deref().cloned());
+        };
+        let mut policy = policy.clone();
+        if let serde_json::Value::Object(schemes) = &mut policy {
+            for properties in schemes.values_mut() {
+                let Some(secret) = properties
+                    .get("use")
+                    .and_then(serde_json::Value::as_str)
+                    .map(str::to_string)
+                else {
+                    continue;
+                };
+                let declared = workflow
+                    .use_
+                    .as_ref()
+                    .and_then(|use_| use_.secrets.as_ref())
+                    .is_some_and(|secrets| secrets.contains(&secret));
+                if !declared {
+                    return Err(Error::Configuration {
+                        message: format!(
+                            "Listener authentication uses secret '{secret}', which is not declared in use.secrets"
+                        ),
+                    });
+                }
+                let value = self.secrets.require(&secret).await?;
+                *properties = serde_json::from_str(&value).map_err(|_| Error::Configuration {
+                    message: format!(
+                        "Secret '{secret}' does not hold the properties of a listener authentication as JSON"
+                    ),
+                })?;
+            }
+        }
+        serde_json::from_value(policy)
+            .map(Some)
+            .map_err(|e| Error::Configuration {
+                message: format!("Invalid listener authentication of {}: {e}", source.uri),
+            })
     }
 
     /// Create a handler function from a Listen task's foreach.do block
@@ -550,6 +673,7 @@ impl DurableEngine {
 }
 
 /// Convert a prost-reflect ``DynamicMessage`` to JSON
+#[cfg(feature = "grpc")]
 fn dynamic_message_to_json(msg: &prost_reflect::DynamicMessage) -> serde_json::Value {
     use prost_reflect::{ReflectMessage, Value};
 
@@ -580,6 +704,7 @@ fn dynamic_message_to_json(msg: &prost_reflect::DynamicMessage) -> serde_json::V
 }
 
 /// Convert JSON to a prost-reflect ``DynamicMessage`` using a message descriptor
+#[cfg(feature = "grpc")]
 #[allow(clippy::cast_possible_truncation)]
 fn json_to_dynamic_message(
     json: &serde_json::Value,
@@ -621,37 +746,40 @@ fn json_to_dynamic_message(
 /// Read modes:
 /// - `data`: Extract only the `data` field from CloudEvents
 /// - `envelope`: Pass the full CloudEvent (default)
-/// - `raw`: Pass the raw HTTP request body as-is
+/// - `raw`: Pass the raw HTTP request body as-is, which the HTTP listener
+///   leaves unparsed for routes in this mode
 fn wrap_handler_with_read_mode(
     handler: Arc<
         dyn Fn(serde_json::Value) -> crate::listeners::Result<serde_json::Value> + Send + Sync,
     >,
-    read_mode: &str,
+    read_mode: ReadMode,
 ) -> Arc<dyn Fn(serde_json::Value) -> crate::listeners::Result<serde_json::Value> + Send + Sync> {
-    let mode = read_mode.to_string();
-
     Arc::new(
         move |payload: serde_json::Value| -> crate::listeners::Result<serde_json::Value> {
-            let transformed_payload = match mode.as_str() {
-                "data" => {
-                    // Extract only the data field from CloudEvent
-                    if let Some(data) = payload.get("data") {
-                        data.clone()
-                    } else {
-                        payload
-                    }
-                }
-                "raw" => {
-                    // Pass through as-is for raw mode
-                    payload
-                }
-                _ => {
-                    // Default is "envelope" - pass full CloudEvent
-                    payload
-                }
-            };
+            handler(read_mode.read(payload))
+        },
+    )
+}
 
-            handler(transformed_payload)
+/// Wraps a handler so the events it receives are also delivered to the
+/// engine's listen tasks, attributed to the task whose listener received them
+fn wrap_handler_with_delivery(
+    handler: Arc<
+        dyn Fn(serde_json::Value) -> crate::listeners::Result<serde_json::Value> + Send + Sync,
+    >,
+    event_bus: tokio::sync::broadcast::Sender<InboundEvent>,
+    task_name: &str,
+) -> Arc<dyn Fn(serde_json::Value) -> crate::listeners::Result<serde_json::Value> + Send + Sync> {
+    let task_name = task_name.to_string();
+
+    Arc::new(
+        move |payload: serde_json::Value| -> crate::listeners::Result<serde_json::Value> {
+            // No listen task may be running, e.g. between two `until` rounds
+            let _ = event_bus.send(InboundEvent {
+                event: payload.clone(),
+                listener: Some(task_name.clone()),
+            });
+            handler(payload)
         },
     )
 }
@@ -666,3 +794,27 @@ fn indent_json(value: &serde_json::Value, indent: usize) -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+/// Wraps a handler so the requests it handles are counted by the engine's
+/// telemetry, if it has one
+fn wrap_handler_with_telemetry(
+    handler: Arc<
+        dyn Fn(serde_json::Value) -> crate::listeners::Result<serde_json::Value> + Send + Sync,
+    >,
+    telemetry: Option<Arc<Telemetry>>,
+    protocol: &'static str,
+    task_name: &str,
+) -> Arc<dyn Fn(serde_json::Value) -> crate::listeners::Result<serde_json::Value> + Send + Sync> {
+    let Some(telemetry) = telemetry else {
+        return handler;
+    };
+    let task_name = task_name.to_string();
+
+    Arc::new(
+        move |payload: serde_json::Value| -> crate::listeners::Result<serde_json::Value> {
+            let response = handler(payload);
+            telemetry.record_listener_request(protocol, &task_name, response.is_ok());
+            response
+        },
+    )
+}
@@ -0,0 +1,326 @@
+//! Run manifests recorded when instances start
+//!
+//! A run manifest pins down what an instance ran with: digests of the
+//! workflow definition and of the input, the engine version, the resolved
+//! configuration, and digests of the documents and sub-workflows the tasks
+//! reference. With manifests enabled, one is recorded under `manifest` in the
+//! metadata of the `WorkflowStarted` event of each instance, so two instances
+//! can be compared to tell whether they ran exactly the same thing.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::preflight::{FETCH_TIMEOUT, Requirement, requirements};
+use super::versions::VersionReq;
+use super::{DurableEngine, Result};
+use crate::bundle::content_digest;
+use crate::ordering::{canonical, to_canonical_value};
+use crate::workflow::WorkflowEvent;
+
+/// Key of the run manifest in the metadata of `WorkflowStarted` events
+pub(crate) const MANIFEST: &str = "manifest";
+
+/// What an instance ran with, recorded when it started
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunManifest {
+    /// Version of the engine that started the instance
+    pub engine_version: String,
+    /// The workflow, as "namespace/name/version"
+    pub workflow: String,
+    /// `sha256:<hex>` digest of the workflow definition
+    pub workflow_digest: String,
+    /// `sha256:<hex>` digest of the input
+    pub input_digest: String,
+    /// The resolved configuration the engine ran with
+    pub config: Value,
+    /// The documents and sub-workflows the tasks reference
+    pub resources: Vec<ResourceDigest>,
+}
+
+/// The digest of a resource a task references
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceDigest {
+    /// Name of the task referencing the resource
+    pub task: String,
+    /// The resource as written in the workflow
+    pub resource: String,
+    /// `sha256:<hex>` digest of the content, if it could be read
+    pub digest: Option<String>,
+    /// Why the content could not be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A field in which two run manifests differ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestDifference {
+    /// What differs, e.g. `inputDigest` or `config.cache_ttl`
+    pub field: String,
+    /// The value in the first manifest, if it has one
+    pub left: Option<String>,
+    /// The value in the second manifest, if it has one
+    pub right: Option<String>,
+}
+
+impl RunManifest {
+    /// The fields in which this manifest and `other` differ
+    ///
+    /// Configuration is compared key by key, and resources by task and
+    /// resource, so the result points at what changed between two runs.
+    #[must_use]
+    pub fn differences(&self, other: &Self) -> Vec<ManifestDifference> {
+        let mut differences = Vec::new();
+        let mut compare = |field: String, left: Option<String>, right: Option<String>| {
+            if left != right {
+                differences.push(ManifestDifference { field, left, right });
+            }
+        };
+
+        compare(
+            "engineVersion".to_string(),
+            Some(self.engine_version.clone()),
+            Some(other.engine_version.clone()),
+        );
+        compare(
+            "workflow".to_string(),
+            Some(self.workflow.clone()),
+            Some(other.workflow.clone()),
+        );
+        compare(
+            "workflowDigest".to_string(),
+            Some(self.workflow_digest.clone()),
+            Some(other.workflow_digest.clone()),
+        );
+        compare(
+            "inputDigest".to_string(),
+            Some(self.input_digest.clone()),
+            Some(other.input_digest.clone()),
+        );
+
+        let left = config_entries(&self.config);
+        let right = config_entries(&other.config);
+        for key in left.keys().chain(right.keys()).collect::<BTreeSet<_>>() {
+            compare(
+                format!("config.{key}"),
+                left.get(key).map(Value::to_string),
+                right.get(key).map(Value::to_string),
+            );
+        }
+
+        let left = resource_digests(&self.resources);
+        let right = resource_digests(&other.resources);
+        for key in left.keys().chain(right.keys()).collect::<BTreeSet<_>>() {
+            compare(
+                format!("resources.{}.{}", key.0, key.1),
+                left.get(key).cloned().flatten(),
+                right.get(key).cloned().flatten(),
+            );
+        }
+
+        differences
+    }
+}
+
+fn config_entries(config: &Value) -> BTreeMap<String, Value> {
+    match config {
+        Value::Object(map) => map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        Value::Null => BTreeMap::new(),
+        Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) => {
+            BTreeMap::from([(String::new(), config.clone())])
+        }
+    }
+}
+
+fn resource_digests(resources: &[ResourceDigest]) -> BTreeMap<(String, String), Option<String>> {
+    resources
+        .iter()
+        .map(|r| ((r.task.clone(), r.resource.clone()), r.digest.clone()))
+        .collect()
+}
+
+impl DurableEngine {
+    /// The run manifest recorded when an instance started
+    ///
+    /// Returns `None` if the instance started without manifests enabled.
+    ///
+    /// # Errors
+    /// Returns an error if the events of the instance cannot be read or the
+    /// recorded manifest is malformed.
+    pub async fn manifest(&self, instance_id: &str) -> Result<Option<RunManifest>> {
+        let events = self.reads.get_events(instance_id).await?;
+        let recorded = events.iter().find_map(|event| {
+            if let WorkflowEvent::WorkflowStarted { metadata, .. } = event {
+                metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.get(MANIFEST))
+                    .cloned()
+            } else {
+                None
+            }
+        });
+        Ok(recorded.map(serde_json::from_value).transpose()?)
+    }
+
+    /// Build the manifest of an instance of `workflow` starting with `input`
+    pub(super) async fn run_manifest(
+        &self,
+        workflow: &WorkflowDefinition,
+        input: &Value,
+        config: &Value,
+    ) -> Result<RunManifest> {
+        let mut resources = Vec::new();
+        for (task, requirement) in requirements(workflow) {
+            let (resource, content) = match requirement {
+                Requirement::Document(uri) => {
+                    let content = self.read_document(workflow, &uri).await;
+                    (uri, content)
+                }
+                Requirement::Workflow {
+                    namespace,
+                    name,
+                    version,
+                } => {
+                    let content = self.read_workflow(&namespace, &name, &version).await;
+                    (format!("{namespace}/{name}/{version}"), content)
+                }
+                // Only the container runtime knows the digest of an image
+                Requirement::Image(_) => continue,
+            };
+            let (digest, error) = match content {
+                Ok(content) => (Some(content_digest(&content)), None),
+                Err(error) => (None, Some(error)),
+            };
+            resources.push(ResourceDigest {
+                task,
+                resource,
+                digest,
+                error,
+            });
+        }
+
+        Ok(RunManifest {
+            engine_version: env!("CARGO_PKG_VERSION").to_string(),
+            workflow: format!(
+                "{}/{}/{}",
+                workflow.document.namespace, workflow.document.name, workflow.document.version
+            ),
+            workflow_digest: content_digest(&serde_json::to_vec(&to_canonical_value(workflow)?)?),
+            input_digest: content_digest(&serde_json::to_vec(&canonical(input))?),
+            config: config.clone(),
+            resources,
+        })
+    }
+
+    async fn read_document(
+        &self,
+        workflow: &WorkflowDefinition,
+        uri: &str,
+    ) -> std::result::Result<Vec<u8>, String> {
+        if uri.starts_with("http://") || uri.starts_with("https://") {
+            let client = reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let response = client
+                .get(uri)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| e.to_string())?;
+            return response
+                .bytes()
+                .await
+                .map(|bytes| bytes.to_vec())
+                .map_err(|e| e.to_string());
+        }
+
+        let resolved = self.resolve_resource(workflow, uri).await;
+        tokio::fs::read(&resolved.path)
+            .await
+            .map_err(|e| format!("{resolved}: {e}"))
+    }
+
+    async fn read_workflow(
+        &self,
+        namespace: &str,
+        name: &str,
+        version: &str,
+    ) -> std::result::Result<Vec<u8>, String> {
+        let version = if VersionReq::parse(version).is_some() {
+            self.resolve_workflow_version(namespace, name, version)
+                .await
+                .map_err(|e| e.to_string())?
+        } else {
+            version.to_string()
+        };
+        let definition = self
+            .find_workflow(namespace, name, version)
+            .await
+            .map_err(|e| e.to_string())?;
+        serde_json::to_value(definition)
+            .and_then(|definition| serde_json::to_vec(&definition))
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest(input_digest: &str, config: Value, digest: Option<&str>) -> RunManifest {
+        RunManifest {
+            engine_version: "0.1.1".to_string(),
+            workflow: "default/order/1.0.0".to_string(),
+            workflow_digest: "sha256:aa".to_string(),
+            input_digest: input_digest.to_string(),
+            config,
+            resources: vec![ResourceDigest {
+                task: "greet".to_string(),
+                resource: "file://./greeter.proto".to_string(),
+                digest: digest.map(str::to_string),
+                error: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_identical_manifests_have_no_differences() {
+        let a = manifest(
+            "sha256:01",
+            json!({ "cache_ttl": "PT1H" }),
+            Some("sha256:02"),
+        );
+        assert!(a.differences(&a.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_differences_name_the_changed_fields() {
+        let a = manifest(
+            "sha256:01",
+            json!({ "cache_ttl": "PT1H" }),
+            Some("sha256:02"),
+        );
+        let b = manifest(
+            "sha256:09",
+            json!({ "cache_ttl": "PT1H", "timeout": "PT5M" }),
+            Some("sha256:03"),
+        );
+        let fields: Vec<_> = a
+            .differences(&b)
+            .into_iter()
+            .map(|difference| difference.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "inputDigest",
+                "config.timeout",
+                "resources.greet.file://./greeter.proto"
+            ]
+        );
+    }
+}
@@ -0,0 +1,372 @@
+//! Memory held by a long-running engine
+//!
+//! [`DurableEngine::memory_report`] measures what the engine retains between
+//! tasks: the data and replay history of each running instance, the events
+//! buffered for listen tasks, the handlers of listeners and the entries of an
+//! in-memory cache. Sizes are those of the values serialized as JSON, which
+//! tracks growth well without walking the allocator. `jackdaw serve` exposes
+//! the report at `/debug/memory` and as gauges at `/metrics`.
+//!
+//! With the `jemalloc` feature the report also includes the allocator's own
+//! statistics, and [`dump_heap_profile`] writes a heap profile when jemalloc
+//! profiling is enabled with `MALLOC_CONF=prof:true`.
+
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::cache::CacheFootprint;
+use crate::context::Context;
+use crate::executionhistory::ExecutionHistory;
+use crate::telemetry::escape_label;
+
+use super::{DurableEngine, EVENT_BUS_CAPACITY, Result};
+
+/// Memory held by the engine, as measured by [`DurableEngine::memory_report`]
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryReport {
+    /// Running instances, largest first
+    pub instances: Vec<InstanceMemory>,
+    pub listeners: Vec<ListenerMemory>,
+    pub event_bus: EventBusMemory,
+    /// Entries of the cache, if it keeps them in memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheFootprint>,
+    /// Statistics of the allocator, with the `jemalloc` feature
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocator: Option<AllocatorStats>,
+}
+
+impl MemoryReport {
+    /// Bytes retained by all running instances
+    #[must_use]
+    pub fn retained_bytes(&self) -> usize {
+        self.instances.iter().map(InstanceMemory::total_bytes).sum()
+    }
+
+    /// The report as gauges in the Prometheus text format
+    #[must_use]
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        gauge(
+            &mut out,
+            "jackdaw_instances_running",
+            "Workflow instances running in this engine",
+            &[(String::new(), self.instances.len())],
+        );
+        gauge(
+            &mut out,
+            "jackdaw_instance_retained_bytes",
+            "Data and replay history retained by a running instance, as JSON",
+            &self
+                .instances
+                .iter()
+                .map(|instance| {
+                    (
+                        format!(
+                            "instance_id=\"{}\",workflow_id=\"{}\"",
+                            escape_label(&instance.instance_id),
+                            escape_label(&instance.workflow_id)
+                        ),
+                        instance.total_bytes(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+        gauge(
+            &mut out,
+            "jackdaw_listener_handlers",
+            "Routes or methods served by a listener",
+            &self
+                .listeners
+                .iter()
+                .map(|listener| {
+                    (
+                        format!(
+                            "address=\"{}\",protocol=\"{}\"",
+                            escape_label(&listener.address),
+                            listener.protocol
+                        ),
+                        listener.handlers,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+        gauge(
+            &mut out,
+            "jackdaw_event_bus_queued_events",
+            "Received events buffered for listen tasks",
+            &[(String::new(), self.event_bus.queued)],
+        );
+        if let Some(cache) = &self.cache {
+            gauge(
+                &mut out,
+                "jackdaw_cache_entries",
+                "Entries of the in-memory cache",
+                &[(String::new(), cache.entries)],
+            );
+            gauge(
+                &mut out,
+                "jackdaw_cache_bytes",
+                "Inputs and outputs held by the in-memory cache, as JSON",
+                &[(String::new(), cache.bytes)],
+            );
+        }
+        if let Some(allocator) = &self.allocator {
+            gauge(
+                &mut out,
+                "jackdaw_allocator_allocated_bytes",
+                "Bytes allocated by the application",
+                &[(String::new(), allocator.allocated)],
+            );
+            gauge(
+                &mut out,
+                "jackdaw_allocator_resident_bytes",
+                "Bytes in physically resident pages mapped by the allocator",
+                &[(String::new(), allocator.resident)],
+            );
+        }
+        out
+    }
+}
+
+/// Memory retained by a running instance
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceMemory {
+    pub instance_id: String,
+    /// The "namespace/name/version" key of the instance's workflow
+    pub workflow_id: String,
+    /// Size of the workflow data
+    pub data_bytes: usize,
+    /// Size of the input of the task in flight
+    pub task_input_bytes: usize,
+    /// Size of the results of tasks completed by previous runs, kept for replay
+    pub history_bytes: usize,
+}
+
+impl InstanceMemory {
+    #[must_use]
+    pub fn total_bytes(&self) -> usize {
+        self.data_bytes + self.task_input_bytes + self.history_bytes
+    }
+}
+
+/// Handlers registered on a listener started by a listen task
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenerMemory {
+    pub address: String,
+    /// `http` or `grpc`
+    pub protocol: &'static str,
+    pub handlers: usize,
+}
+
+/// Events received by listeners or emitted by workflows, kept for listen tasks
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EventBusMemory {
+    /// Events not yet seen by every subscribed listen task
+    pub queued: usize,
+    /// Events kept before the oldest are dropped
+    pub capacity: usize,
+    /// Listen tasks subscribed to the bus
+    pub subscribers: usize,
+}
+
+/// Statistics of the jemalloc allocator
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AllocatorStats {
+    /// Bytes allocated by the application
+    pub allocated: usize,
+    /// Bytes in physically resident pages mapped by the allocator
+    pub resident: usize,
+}
+
+/// The parts of a running instance's context that grow with its data
+#[derive(Clone)]
+pub(super) struct RetainedContext {
+    workflow_id: String,
+    data: Arc<RwLock<serde_json::Value>>,
+    task_input: Arc<RwLock<serde_json::Value>>,
+    history: Arc<ExecutionHistory>,
+}
+
+impl RetainedContext {
+    pub(super) fn new(ctx: &Context) -> Self {
+        let document = &ctx.metadata.workflow.document;
+        Self {
+            workflow_id: format!(
+                "{}/{}/{}",
+                document.namespace, document.name, document.version
+            ),
+            data: ctx.state.data.clone(),
+            task_input: ctx.state.task_input.clone(),
+            history: ctx.services.history.clone(),
+        }
+    }
+
+    async fn measure(&self, instance_id: String) -> InstanceMemory {
+        InstanceMemory {
+            instance_id,
+            workflow_id: self.workflow_id.clone(),
+            data_bytes: json_size(&*self.data.read().await),
+            task_input_bytes: json_size(&*self.task_input.read().await),
+            history_bytes: self.history.completed_results().map(json_size).sum(),
+        }
+    }
+}
+
+impl DurableEngine {
+    /// Measure the memory held by the engine
+    ///
+    /// # Errors
+    /// Returns an error if the cache cannot report its footprint.
+    pub async fn memory_report(&self) -> Result<MemoryReport> {
+        let mut instances = Vec::new();
+        for (instance_id, retained) in self.retained_contexts() {
+            instances.push(retained.measure(instance_id).await);
+        }
+        instances.sort_by_key(|instance| std::cmp::Reverse(instance.total_bytes()));
+
+        let mut listeners = Vec::new();
+        for (address, listener) in self.http_listeners.read().await.iter() {
+            listeners.push(ListenerMemory {
+                address: address.clone(),
+                protocol: "http",
+                handlers: listener.route_count().await,
+            });
+        }
+        #[cfg(feature = "grpc")]
+        for (address, listener) in self.grpc_listeners.read().await.iter() {
+            listeners.push(ListenerMemory {
+                address: address.clone(),
+                protocol: "grpc",
+                handlers: listener.method_count().await,
+            });
+        }
+
+        Ok(MemoryReport {
+            instances,
+            listeners,
+            event_bus: EventBusMemory {
+                queued: self.event_bus.len(),
+                capacity: EVENT_BUS_CAPACITY,
+                subscribers: self.event_bus.receiver_count(),
+            },
+            cache: self.cache.footprint().await?,
+            allocator: allocator_stats(),
+        })
+    }
+}
+
+/// Size of a value serialized as JSON, without allocating the serialization
+pub(crate) fn json_size(value: &serde_json::Value) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // Writing to the counter cannot fail, and a Value always serializes
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
+
+/// Append a gauge with one sample per `(labels, value)` pair
+fn gauge(out: &mut String, name: &str, help: &str, samples: &[(String, usize)]) {
+    use std::fmt::Write as _;
+
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{name} {value}");
+        } else {
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+#[cfg(feature = "jemalloc")]
+fn allocator_stats() -> Option<AllocatorStats> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // The statistics are cached until the epoch is advanced
+    epoch::advance().ok()?;
+    Some(AllocatorStats {
+        allocated: stats::allocated::read().ok()?,
+        resident: stats::resident::read().ok()?,
+    })
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn allocator_stats() -> Option<AllocatorStats> {
+    None
+}
+
+/// Write a jemalloc heap profile to `path`, for comparing with `jeprof`
+///
+/// Profiling must have been enabled when the process started, with
+/// `MALLOC_CONF=prof:true` (or `_RJEM_MALLOC_CONF` where jemalloc is prefixed).
+///
+/// # Errors
+/// Returns an error if profiling is not enabled or the profile cannot be written.
+#[cfg(feature = "jemalloc")]
+pub fn dump_heap_profile(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: prof.dump takes a NUL-terminated path, which outlives the call
+    unsafe { tikv_jemalloc_ctl::raw::write(b"prof.dump\0", path.as_ptr()) }
+        .map_err(|e| std::io::Error::other(format!("heap profiling is not enabled: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_size_matches_serialization() {
+        let value = serde_json::json!({ "name": "jackdaw", "tags": ["a", "b"], "n": 1.5 });
+        assert_eq!(
+            json_size(&value),
+            serde_json::to_vec(&value)
+                .map(|bytes| bytes.len())
+                .unwrap_or_default()
+        );
+    }
+
+    #[test]
+    fn test_prometheus_gauges() {
+        let report = MemoryReport {
+            instances: vec![InstanceMemory {
+                instance_id: "i-1".to_string(),
+                workflow_id: "default/orders/1.0.0".to_string(),
+                data_bytes: 100,
+                task_input_bytes: 20,
+                history_bytes: 3,
+            }],
+            listeners: Vec::new(),
+            event_bus: EventBusMemory {
+                queued: 2,
+                capacity: EVENT_BUS_CAPACITY,
+                subscribers: 1,
+            },
+            cache: None,
+            allocator: None,
+        };
+        let text = report.to_prometheus();
+        assert!(text.contains(
+            "jackdaw_instance_retained_bytes{instance_id=\"i-1\",workflow_id=\"default/orders/1.0.0\"} 123"
+        ));
+        assert!(text.contains("jackdaw_event_bus_queued_events 2"));
+        assert!(!text.contains("jackdaw_cache_entries"));
+    }
+}
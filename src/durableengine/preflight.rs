@@ -0,0 +1,387 @@
+//! Pre-flight checks of the resources a workflow references
+//!
+//! Before an instance starts, every external resource its tasks need can be
+//! checked at once: `OpenAPI`/`AsyncAPI` documents, proto files, script
+//! sources and WebAssembly modules must be readable, container images must be
+//! present or pullable, and sub-workflows must be registered. Problems are
+//! collected into a single report instead of surfacing one by one as the
+//! tasks that need them run.
+
+use serde_json::Value;
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::fmt;
+use std::time::Duration;
+
+use super::versions::VersionReq;
+use super::{DurableEngine, Error};
+use crate::providers::container;
+
+/// How long fetching a remote document may take before it counts as unreachable
+pub(super) const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Keys whose `endpoint` points at a document a task reads
+const RESOURCE_KEYS: &[&str] = &["source", "document", "proto", "resource", "module"];
+
+/// A resource a task references that is not available
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightProblem {
+    /// Name of the task referencing the resource
+    pub task: String,
+    /// The resource as written in the workflow
+    pub resource: String,
+    /// Why the resource is not available
+    pub message: String,
+}
+
+/// The outcome of checking every resource a workflow references
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreflightReport {
+    /// Number of resources that were checked
+    pub checked: usize,
+    /// The resources that are not available
+    pub problems: Vec<PreflightProblem>,
+}
+
+impl PreflightReport {
+    /// Whether every referenced resource is available
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn check(&mut self, task: &str, resource: &str, outcome: Result<(), String>) {
+        self.checked += 1;
+        if let Err(message) = outcome {
+            self.problems.push(PreflightProblem {
+                task: task.to_string(),
+                resource: resource.to_string(),
+                message,
+            });
+        }
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} referenced resource(s) unavailable",
+            self.problems.len(),
+            self.checked
+        )?;
+        for problem in &self.problems {
+            write!(
+                f,
+                "\n  task {}: {}: {}",
+                problem.task, problem.resource, problem.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What a task needs to be available before it runs
+#[derive(Debug)]
+pub(super) enum Requirement {
+    Document(String),
+    Image(String),
+    Workflow {
+        namespace: String,
+        name: String,
+        version: String,
+    },
+}
+
+impl DurableEngine {
+    /// Check that the resources `workflow` references are available
+    ///
+    /// Relative documents are resolved as the tasks would resolve them, so
+    /// call [`DurableEngine::set_workflow_path`] first for workflows loaded
+    /// from a file. References that are runtime expressions cannot be checked
+    /// ahead of time and are skipped.
+    pub async fn preflight(&self, workflow: &WorkflowDefinition) -> PreflightReport {
+        let mut report = PreflightReport::default();
+        let mut containers = None;
+        for (task, requirement) in requirements(workflow) {
+            match requirement {
+                Requirement::Document(uri) => {
+                    let outcome = self.check_document(workflow, &uri).await;
+                    report.check(&task, &uri, outcome);
+                }
+                Requirement::Image(image) => {
+                    // The container runtime is only connected to if a task needs it
+                    if containers.is_none() {
+                        containers = Some(
+                            container::connect(self.container_runtime, self.container_pool.clone())
+                                .await
+                                .map_err(|e| e.to_string()),
+                        );
+                    }
+                    let outcome = match containers.as_ref() {
+                        None => Ok(()),
+                        Some(Ok(provider)) => provider
+                            .check_image(&image)
+                            .await
+                            .map_err(|e| e.to_string()),
+                        Some(Err(message)) => Err(message.clone()),
+                    };
+                    report.check(&task, &image, outcome);
+                }
+                Requirement::Workflow {
+                    namespace,
+                    name,
+                    version,
+                } => {
+                    let outcome = self.check_workflow(&namespace, &name, &version).await;
+                    report.check(&task, &format!("{namespace}/{name}/{version}"), outcome);
+                }
+            }
+        }
+        report
+    }
+
+    async fn check_document(&self, workflow: &WorkflowDefinition, uri: &str) -> Result<(), String> {
+        if uri.starts_with("http://") || uri.starts_with("https://") {
+            let client = reqwest::Client::builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let response = client.get(uri).send().await.map_err(|e| e.to_string())?;
+            return if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(format!("HTTP {}", response.status()))
+            };
+        }
+
+        let resolved = self.resolve_resource(workflow, uri).await;
+        if resolved.path.is_file() {
+            Ok(())
+        } else {
+            Err(format!("{resolved} does not exist"))
+        }
+    }
+
+    async fn check_workflow(
+        &self,
+        namespace: &str,
+        name: &str,
+        version: &str,
+    ) -> Result<(), String> {
+        if VersionReq::parse(version).is_some() {
+            return self
+                .resolve_workflow_version(namespace, name, version)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string());
+        }
+        match self.find_workflow(namespace, name, version).await {
+            Ok(_) => Ok(()),
+            Err(Error::Configuration { .. }) => {
+                Err("not found in the workflow registry".to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// What the tasks of `workflow` need, by the name of the task needing it
+pub(super) fn requirements(workflow: &WorkflowDefinition) -> Vec<(String, Requirement)> {
+    let mut requirements = Vec::new();
+    collect_nested(workflow, &workflow.do_.entries, &mut requirements);
+    requirements
+}
+
+/// Collect the requirements of a task and of the tasks nested in it
+fn collect_task_requirements(
+    workflow: &WorkflowDefinition,
+    name: &str,
+    task: &TaskDefinition,
+    requirements: &mut Vec<(String, Requirement)>,
+) {
+    match task {
+        TaskDefinition::Call(call_task) => {
+            let mut found = Vec::new();
+            if let Ok(task) = serde_json::to_value(call_task) {
+                collect_documents(&task, None, &mut found);
+            }
+            // Calls of user-defined functions read the function's documents
+            if let Some(function) = workflow
+                .use_
+                .as_ref()
+                .and_then(|use_| use_.functions.as_ref())
+                .and_then(|functions| functions.get(&call_task.call))
+                && let Ok(function) = serde_json::to_value(function)
+            {
+                collect_documents(&function, None, &mut found);
+            }
+            requirements.extend(
+                found
+                    .into_iter()
+                    .map(|uri| (name.to_string(), Requirement::Document(uri))),
+            );
+        }
+        TaskDefinition::Run(run_task) => {
+            let Ok(task) = serde_json::to_value(run_task) else {
+                return;
+            };
+            let mut found = Vec::new();
+            collect_documents(&task, None, &mut found);
+            requirements.extend(
+                found
+                    .into_iter()
+                    .map(|uri| (name.to_string(), Requirement::Document(uri))),
+            );
+            if let Some(image) = task.pointer("/run/container/image").and_then(Value::as_str)
+                && !is_expression(image)
+            {
+                requirements.push((name.to_string(), Requirement::Image(image.to_string())));
+            }
+            if let Some(reference) = task.pointer("/run/workflow") {
+                let field = |field: &str| {
+                    reference
+                        .get(field)
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                };
+                if let (Some(namespace), Some(workflow_name), Some(version)) =
+                    (field("namespace"), field("name"), field("version"))
+                {
+                    requirements.push((
+                        name.to_string(),
+                        Requirement::Workflow {
+                            namespace,
+                            name: workflow_name,
+                            version,
+                        },
+                    ));
+                }
+            }
+        }
+        TaskDefinition::Do(t) => collect_nested(workflow, &t.do_.entries, requirements),
+        TaskDefinition::For(t) => collect_nested(workflow, &t.do_.entries, requirements),
+        TaskDefinition::Fork(t) => {
+            collect_nested(workflow, &t.fork.branches.entries, requirements);
+        }
+        TaskDefinition::Try(t) => {
+            collect_nested(workflow, &t.try_.entries, requirements);
+            if let Some(catch_tasks) = &t.catch.do_ {
+                collect_nested(workflow, &catch_tasks.entries, requirements);
+            }
+        }
+        TaskDefinition::Emit(_)
+        | TaskDefinition::Listen(_)
+        | TaskDefinition::Raise(_)
+        | TaskDefinition::Set(_)
+        | TaskDefinition::Switch(_)
+        | TaskDefinition::Wait(_) => {}
+    }
+}
+
+fn collect_nested<'a, E>(
+    workflow: &WorkflowDefinition,
+    entries: &'a [E],
+    requirements: &mut Vec<(String, Requirement)>,
+) where
+    &'a E: IntoIterator<Item = (&'a String, &'a TaskDefinition)>,
+{
+    for entry in entries {
+        for (name, task) in entry {
+            collect_task_requirements(workflow, name, task, requirements);
+        }
+    }
+}
+
+/// Collect the URIs of the documents referenced in a task
+fn collect_documents(value: &Value, parent: Option<&str>, found: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            let is_resource = parent.is_some_and(|key| RESOURCE_KEYS.contains(&key));
+            for (key, child) in map {
+                if is_resource && key == "endpoint" {
+                    let uri = match child {
+                        Value::String(uri) => Some(uri),
+                        Value::Object(endpoint) => match endpoint.get("uri") {
+                            Some(Value::String(uri)) => Some(uri),
+                            Some(_) | None => None,
+                        },
+                        Value::Null | Value::Bool(_) | Value::Number(_) | Value::Array(_) => None,
+                    };
+                    if let Some(uri) = uri.filter(|uri| !is_expression(uri)) {
+                        found.push(uri.clone());
+                    }
+                } else {
+                    collect_documents(child, Some(key), found);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_documents(item, None, found);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+fn is_expression(value: &str) -> bool {
+    value.trim_start().starts_with("${")
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_collect_documents_finds_endpoints_of_resource_keys() {
+        let task = json!({
+            "call": "openapi",
+            "with": {
+                "document": { "endpoint": "file://./petstore.yaml" },
+                "operationId": "getPet",
+                "parameters": { "endpoint": "not-a-document" }
+            }
+        });
+        let mut found = Vec::new();
+        collect_documents(&task, None, &mut found);
+        assert_eq!(found, vec!["file://./petstore.yaml"]);
+
+        let task = json!({
+            "call": "grpc",
+            "with": { "proto": { "endpoint": { "uri": "file://./greeter.proto" } } }
+        });
+        let mut found = Vec::new();
+        collect_documents(&task, None, &mut found);
+        assert_eq!(found, vec!["file://./greeter.proto"]);
+    }
+
+    #[test]
+    fn test_runtime_expressions_are_skipped() {
+        let task = json!({
+            "run": { "script": { "source": { "endpoint": "${ .script }" } } }
+        });
+        let mut found = Vec::new();
+        collect_documents(&task, None, &mut found);
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn test_report_lists_every_problem() {
+        let mut report = PreflightReport::default();
+        report.check("fetch", "file://./api.yaml", Ok(()));
+        report.check(
+            "greet",
+            "file://./greeter.proto",
+            Err("does not exist".to_string()),
+        );
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.to_string(),
+            "1 of 2 referenced resource(s) unavailable\n  task greet: file://./greeter.proto: does not exist"
+        );
+    }
+}
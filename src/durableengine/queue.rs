@@ -0,0 +1,125 @@
+//! Workflow starts queued for workers sharing one persistence store
+//!
+//! [`DurableEngine::enqueue`] records a start instead of running it, and
+//! `jackdaw worker` processes take queued starts with
+//! [`DurableEngine::run_next_queued`]. A worker claims a start by taking the
+//! lease of its instance, so no two workers run it at once, and keeps the
+//! lease while the instance runs. A start stays queued until its instance has
+//! run: if the worker dies, its lease lapses and another worker resumes the
+//! instance from its last checkpoint.
+
+use chrono::Utc;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+
+use super::{DurableEngine, Error, InstanceState, Result};
+use crate::persistence::QueuedStart;
+
+/// Queued starts looked at per attempt to claim one
+const CLAIM_BATCH: usize = 16;
+
+/// An instance a worker took from the queue and ran
+#[derive(Debug)]
+pub struct QueuedRun {
+    pub instance_id: String,
+    /// Output of the workflow, or why the run failed
+    pub output: Result<serde_json::Value>,
+}
+
+impl DurableEngine {
+    /// Queue a workflow for a worker to run, returning the new instance's ID
+    ///
+    /// The instance is pending until a worker claims it.
+    ///
+    /// # Errors
+    /// Returns an error if the workflow cannot be serialized or the
+    /// persistence provider does not support queued starts.
+    pub async fn enqueue(
+        &self,
+        workflow: &WorkflowDefinition,
+        input: serde_json::Value,
+    ) -> Result<String> {
+        let instance_id = uuid::Uuid::new_v4().to_string();
+        let workflow =
+            serde_json::to_value(workflow).map_err(|source| Error::Serialization { source })?;
+        self.transition(&instance_id, InstanceState::Pending)
+            .await?;
+
+        let start = QueuedStart {
+            instance_id: instance_id.clone(),
+            workflow,
+            input: self.number_mode.apply(input),
+            enqueued_at: Utc::now(),
+        };
+        if let Err(e) = self.persistence.enqueue_start(start).await {
+            // Nothing will ever run the instance
+            let _ = self.transition(&instance_id, InstanceState::Faulted).await;
+            return Err(e.into());
+        }
+        Ok(instance_id)
+    }
+
+    /// Claim the oldest queued start no other worker holds and run its instance
+    ///
+    /// Returns `None` when there is nothing to claim. A start whose instance
+    /// was run by a worker that stopped before removing it is removed without
+    /// running it again.
+    ///
+    /// # Errors
+    /// Returns an error if the queue cannot be read or updated. Failures of
+    /// the instance itself are returned in [`QueuedRun::output`].
+    pub async fn run_next_queued(&self) -> Result<Option<QueuedRun>> {
+        for start in self.persistence.queued_starts(CLAIM_BATCH).await? {
+            let instance_id = start.instance_id;
+            // Taking the lease is the claim; run_instance keeps renewing it
+            let Some(claim) = self
+                .persistence
+                .acquire_lease(&instance_id, &self.owner, self.lease_ttl)
+                .await?
+            else {
+                continue;
+            };
+
+            let state = self.instance_state(&instance_id).await.ok();
+            if !matches!(
+                state,
+                None | Some(InstanceState::Pending | InstanceState::Running)
+            ) {
+                self.persistence.remove_start(&instance_id).await?;
+                self.persistence.release_lease(&claim).await?;
+                continue;
+            }
+
+            let output = match serde_json::from_value::<WorkflowDefinition>(start.workflow) {
+                Ok(workflow) => {
+                    self.run_instance(workflow, Some(instance_id.clone()), start.input)
+                        .await
+                }
+                Err(source) => {
+                    self.transition(&instance_id, InstanceState::Faulted)
+                        .await?;
+                    self.persistence.release_lease(&claim).await?;
+                    Err(Error::Serialization { source })
+                }
+            };
+
+            // A worker that took the instance over removes the start when it is done
+            let taken_over = matches!(
+                &output,
+                Err(Error::InstanceLeased { .. }
+                    | Error::Persistence {
+                        source: crate::persistence::Error::LeaseLost { .. },
+                    })
+            );
+            if !taken_over {
+                self.persistence.remove_start(&instance_id).await?;
+            }
+
+            return Ok(Some(QueuedRun {
+                instance_id,
+                output,
+            }));
+        }
+
+        Ok(None)
+    }
+}
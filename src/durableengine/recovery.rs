@@ -0,0 +1,100 @@
+//! Recovery of instances left running by a process that stopped
+//!
+//! When a process dies mid-workflow, its instances keep the running state and
+//! the events they recorded, but nothing runs them again.
+//! [`DurableEngine::recover_incomplete`] finds them when an engine starts and
+//! resumes each from its last checkpoint, replaying the tasks it completed.
+//! An instance is only resumed once its lease has lapsed, so instances that
+//! another live process is running are left to it.
+
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::PoisonError;
+
+use super::{DurableEngine, Error, InstanceState, Result};
+
+/// An instance found without an outcome, and how resuming it went
+#[derive(Debug)]
+pub struct RecoveredRun {
+    pub instance_id: String,
+    /// Output of the workflow, or why the resumed run failed
+    pub output: Result<serde_json::Value>,
+}
+
+impl DurableEngine {
+    /// Resume the running instances of registered workflows that no process is running
+    ///
+    /// Instances whose workflow is not registered with the engine are left
+    /// running, with a warning; register them with
+    /// [`DurableEngine::register_workflow`] first. The instances are resumed
+    /// concurrently, and this returns once they have all finished.
+    ///
+    /// # Errors
+    /// Returns an error if the instances or their events cannot be read.
+    /// Failures of the instances themselves are returned in
+    /// [`RecoveredRun::output`].
+    pub async fn recover_incomplete(&self) -> Result<Vec<RecoveredRun>> {
+        let mut runs = Vec::new();
+        for instance_id in self.persistence.list_instance_ids().await? {
+            if self
+                .active_instances
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains_key(&instance_id)
+            {
+                continue;
+            }
+            let recorded = self.persistence.get_state(&instance_id).await?;
+            if recorded.is_some_and(|state| state != InstanceState::Running) {
+                continue;
+            }
+            let status = match self.live_status(&instance_id).await {
+                Ok(status) => status,
+                // Stopped before it recorded its start, so there is nothing to resume
+                Err(Error::InstanceNotFound { .. }) => continue,
+                Err(e) => return Err(e),
+            };
+            if recorded.unwrap_or(status.state) != InstanceState::Running {
+                continue;
+            }
+
+            let workflow = match &status.workflow_id {
+                Some(key) => self.get_registered_workflow(key).await,
+                None => None,
+            };
+            let Some(workflow) = workflow else {
+                tracing::warn!(
+                    "Cannot recover instance {}: workflow {} is not registered",
+                    instance_id,
+                    status.workflow_id.as_deref().unwrap_or("unknown")
+                );
+                continue;
+            };
+            runs.push(self.recover(workflow, instance_id));
+        }
+
+        Ok(futures::future::join_all(runs)
+            .await
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Resume an instance, or `None` if another process holds its lease
+    async fn recover(
+        &self,
+        workflow: WorkflowDefinition,
+        instance_id: String,
+    ) -> Option<RecoveredRun> {
+        tracing::info!("Recovering instance {}", instance_id);
+        let output = self
+            .run_instance(workflow, Some(instance_id.clone()), serde_json::json!({}))
+            .await;
+        if matches!(output, Err(Error::InstanceLeased { .. })) {
+            return None;
+        }
+        Some(RecoveredRun {
+            instance_id,
+            output,
+        })
+    }
+}
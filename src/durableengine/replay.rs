@@ -0,0 +1,330 @@
+//! Deterministic replay of a recorded instance
+//!
+//! [`DurableEngine::replay_instance`] runs a workflow definition again against
+//! the event history of an instance, without side effects: tasks that call
+//! out, run programs or wait take their recorded results, while `set`,
+//! `switch` and `raise` tasks, which only evaluate expressions, run again and
+//! must produce the results that were recorded. The replay stops at the first
+//! point where it departs from the recorded path, reporting it as a
+//! [`Divergence`]. This catches changes to a workflow definition that would
+//! break instances recorded with an earlier one.
+
+use serde::Serialize;
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::context::Context;
+use crate::executionhistory::ExecutionHistory;
+use crate::providers::cache::mem::InMemoryCache;
+use crate::providers::persistence::InMemoryPersistence;
+use crate::workflow::WorkflowEvent;
+
+use super::{DurableEngine, Error, Result};
+
+/// Where a replay departed from the recorded run of an instance
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Divergence {
+    /// Position of the task in the recorded path of top-level tasks
+    pub position: usize,
+    /// The task the recorded run went through at this position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    /// The task the replay went through instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at task #{}", self.position + 1)?;
+        match (&self.expected, &self.actual) {
+            (Some(expected), Some(actual)) if expected != actual => {
+                write!(f, " (expected '{expected}', ran '{actual}')")?;
+            }
+            (Some(task), Some(_)) | (Some(task), None) | (None, Some(task)) => {
+                write!(f, " ('{task}')")?;
+            }
+            (None, None) => {}
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Outcome of replaying an instance against a workflow definition
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayReport {
+    pub instance_id: String,
+    /// Tasks that took their recorded results instead of running
+    pub replayed: Vec<String>,
+    /// Tasks that ran again and produced their recorded results
+    pub reexecuted: Vec<String>,
+    /// The first point where the replay departed from the recorded run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub divergence: Option<Divergence>,
+    /// Output of the replay, if it ran to the end of the workflow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+}
+
+impl ReplayReport {
+    /// Whether the replay followed the recorded run
+    #[must_use]
+    pub fn is_deterministic(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// How a recorded run ended
+#[derive(Debug, Clone)]
+enum RecordedEnd {
+    Completed(serde_json::Value),
+    /// The task that faulted, if the failure was raised by one
+    Failed(Option<String>),
+    Unfinished,
+}
+
+/// Cursor over the recorded path, consulted before each top-level task
+#[derive(Debug)]
+pub(super) struct ReplayCheck {
+    recorded: VecDeque<(String, serde_json::Value)>,
+    end: RecordedEnd,
+    position: usize,
+    report: ReplayReport,
+}
+
+impl ReplayCheck {
+    fn new(instance_id: &str, events: &[WorkflowEvent]) -> Self {
+        let history = ExecutionHistory::new(events);
+        let mut faulted = None;
+        let mut end = RecordedEnd::Unfinished;
+        for event in events {
+            if let WorkflowEvent::TaskFaulted { task_name, .. } = event {
+                faulted = Some(task_name.clone());
+            } else if let WorkflowEvent::WorkflowCompleted { final_data, .. } = event {
+                end = RecordedEnd::Completed(final_data.clone());
+            } else if let WorkflowEvent::WorkflowFailed { .. } = event {
+                end = RecordedEnd::Failed(faulted.take());
+            }
+        }
+
+        Self {
+            recorded: history.completed_tasks().iter().cloned().collect(),
+            end,
+            position: 0,
+            report: ReplayReport {
+                instance_id: instance_id.to_string(),
+                replayed: Vec::new(),
+                reexecuted: Vec::new(),
+                divergence: None,
+                output: None,
+            },
+        }
+    }
+
+    /// Record a divergence and build the error that stops the replay
+    fn diverge(
+        &mut self,
+        expected: Option<&str>,
+        actual: Option<&str>,
+        reason: impl Into<String>,
+    ) -> Error {
+        let divergence = Divergence {
+            position: self.position,
+            expected: expected.map(str::to_string),
+            actual: actual.map(str::to_string),
+            reason: reason.into(),
+        };
+        let message = format!("Replay diverged {divergence}");
+        self.report.divergence = Some(divergence);
+        Error::WorkflowExecution { message }
+    }
+
+    /// Run a top-level task of the replay, or take its recorded result
+    pub(super) async fn replay_task(
+        &mut self,
+        engine: &DurableEngine,
+        task_name: &str,
+        task: &TaskDefinition,
+        ctx: &Context,
+    ) -> Result<serde_json::Value> {
+        let Some((expected, recorded)) = self.recorded.pop_front() else {
+            // An unfinished or failed run may stop in this task; so does the replay
+            let recorded_stop = match &self.end {
+                RecordedEnd::Failed(Some(faulted)) => faulted == task_name,
+                RecordedEnd::Unfinished => true,
+                RecordedEnd::Completed(_) | RecordedEnd::Failed(None) => false,
+            };
+            if recorded_stop {
+                return Err(Error::WorkflowExecution {
+                    message: format!("Replay reached the end of the recorded run at '{task_name}'"),
+                });
+            }
+            return Err(self.diverge(
+                None,
+                Some(task_name),
+                "the recorded run ended before this task",
+            ));
+        };
+        if expected != task_name {
+            return Err(self.diverge(
+                Some(expected.as_str()),
+                Some(task_name),
+                "the replay took a different path",
+            ));
+        }
+
+        let result = if reruns(task) {
+            let result = engine.exec_task(task_name, task, ctx).await.map_err(|e| {
+                self.diverge(
+                    Some(expected.as_str()),
+                    Some(task_name),
+                    format!("the task failed when run again: {e}"),
+                )
+            })?;
+            if result != recorded {
+                return Err(self.diverge(
+                    Some(expected.as_str()),
+                    Some(task_name),
+                    "the task produced a different result",
+                ));
+            }
+            self.report.reexecuted.push(task_name.to_string());
+            result
+        } else {
+            self.report.replayed.push(task_name.to_string());
+            recorded
+        };
+        self.position += 1;
+        Ok(result)
+    }
+
+    /// Compare the end of the replay with the end of the recorded run
+    fn finish(self, outcome: Result<serde_json::Value>) -> Result<ReplayReport> {
+        let mut report = self.report;
+        if report.divergence.is_some() {
+            return Ok(report);
+        }
+
+        let divergence = match (outcome, &self.end) {
+            (Ok(output), RecordedEnd::Completed(recorded)) => {
+                let divergence = if let Some((missing, _)) = self.recorded.front() {
+                    Some(Divergence {
+                        position: self.position,
+                        expected: Some(missing.clone()),
+                        actual: None,
+                        reason: "the replay ended before this task".to_string(),
+                    })
+                } else if output != *recorded {
+                    Some(Divergence {
+                        position: self.position,
+                        expected: None,
+                        actual: None,
+                        reason: "the workflow produced a different output".to_string(),
+                    })
+                } else {
+                    None
+                };
+                report.output = Some(output);
+                divergence
+            }
+            (Ok(output), RecordedEnd::Failed(_) | RecordedEnd::Unfinished) => {
+                report.output = Some(output);
+                Some(Divergence {
+                    position: self.position,
+                    expected: self.recorded.front().map(|(name, _)| name.clone()),
+                    actual: None,
+                    reason: "the replay completed but the recorded run did not".to_string(),
+                })
+            }
+            // Stopping where the recorded run stopped is not a divergence
+            (Err(_), RecordedEnd::Failed(_) | RecordedEnd::Unfinished)
+                if self.recorded.is_empty() =>
+            {
+                None
+            }
+            (
+                Err(e),
+                RecordedEnd::Completed(_) | RecordedEnd::Failed(_) | RecordedEnd::Unfinished,
+            ) => Some(Divergence {
+                position: self.position,
+                expected: self.recorded.front().map(|(name, _)| name.clone()),
+                actual: None,
+                reason: format!("the replay failed: {e}"),
+            }),
+        };
+        report.divergence = divergence;
+        Ok(report)
+    }
+}
+
+/// Whether a task only evaluates expressions, so replaying it runs it again
+fn reruns(task: &TaskDefinition) -> bool {
+    match task {
+        TaskDefinition::Set(_) | TaskDefinition::Switch(_) | TaskDefinition::Raise(_) => true,
+        TaskDefinition::Call(_)
+        | TaskDefinition::Do(_)
+        | TaskDefinition::Emit(_)
+        | TaskDefinition::For(_)
+        | TaskDefinition::Fork(_)
+        | TaskDefinition::Listen(_)
+        | TaskDefinition::Run(_)
+        | TaskDefinition::Try(_)
+        | TaskDefinition::Wait(_) => false,
+    }
+}
+
+impl DurableEngine {
+    /// Replay a recorded instance against `workflow` to check it is deterministic
+    ///
+    /// The workflow runs again in isolation from the instance's persisted
+    /// events, which are left untouched: tasks with side effects take their
+    /// recorded results, and tasks that only evaluate expressions run again
+    /// and are compared with their recorded results. The report names the
+    /// first task where the replay departs from the recorded run, if any.
+    ///
+    /// # Errors
+    /// Returns an error if no events are recorded for the instance or the
+    /// replay cannot be set up.
+    pub async fn replay_instance(
+        &self,
+        workflow: WorkflowDefinition,
+        instance_id: &str,
+    ) -> Result<ReplayReport> {
+        let events = self.recorded_events(instance_id).await?;
+        let history = ExecutionHistory::new(&events);
+        let initial_data = history.initial_data().cloned().unwrap_or_default();
+        let mut check = ReplayCheck::new(instance_id, &events);
+
+        // The replay records its own events, apart from the instance's
+        let mut sandbox = DurableEngine::new(
+            Arc::new(InMemoryPersistence::new()),
+            Arc::new(InMemoryCache::new()),
+        )?;
+        sandbox.base_dir = self.base_dir.clone();
+        sandbox.env_policy = Arc::clone(&self.env_policy);
+        sandbox.secrets = Arc::clone(&self.secrets);
+        sandbox.number_mode = self.number_mode;
+
+        let secrets = self.resolve_secrets(&workflow).await?;
+        let ctx = Context::new(
+            &workflow,
+            Arc::clone(&sandbox.persistence),
+            Arc::clone(&sandbox.cache),
+            Some(instance_id.to_string()),
+            initial_data,
+            // The replay runs the versions the recorded run was pinned to
+            history.started_metadata().cloned(),
+        )
+        .await?;
+        super::secrets::inject(&ctx, secrets).await;
+        let (_interrupter, interrupt) = tokio::sync::watch::channel(false);
+
+        let outcome = sandbox
+            .run_graph(&workflow, &ctx, interrupt, Some(&mut check))
+            .await;
+        check.finish(outcome)
+    }
+}
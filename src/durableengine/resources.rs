@@ -0,0 +1,200 @@
+//! Resolution of relative resource paths referenced by workflows
+//!
+//! Script sources, catalog files, proto files and `OpenAPI` schemas are resolved
+//! against the directory of the workflow file that references them (or an
+//! explicit base directory), so a workflow behaves the same regardless of the
+//! process working directory.
+
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::DurableEngine;
+
+/// The directory a relative resource path was resolved against
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PathAnchor {
+    /// The path was already absolute
+    Absolute,
+    /// Resolved against the configured base directory override
+    BaseDir(PathBuf),
+    /// Resolved against the directory containing the workflow file
+    WorkflowDir(PathBuf),
+    /// Resolved against the process working directory
+    CurrentDir,
+}
+
+/// A resource path together with a description of how it was resolved
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedPath {
+    /// The path as written in the workflow
+    pub(crate) raw: String,
+    /// The path to read from
+    pub(crate) path: PathBuf,
+    /// How `path` was derived from `raw`
+    pub(crate) anchor: PathAnchor,
+}
+
+impl fmt::Display for ResolvedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}'", self.raw)?;
+        match &self.anchor {
+            PathAnchor::Absolute => Ok(()),
+            PathAnchor::BaseDir(dir) => write!(
+                f,
+                " (resolved to '{}' relative to base directory '{}')",
+                self.path.display(),
+                dir.display()
+            ),
+            PathAnchor::WorkflowDir(dir) => write!(
+                f,
+                " (resolved to '{}' relative to workflow directory '{}')",
+                self.path.display(),
+                dir.display()
+            ),
+            PathAnchor::CurrentDir => write!(
+                f,
+                " (resolved to '{}' relative to the current directory)",
+                self.path.display()
+            ),
+        }
+    }
+}
+
+/// Resolve a resource path, accepting either a plain path or a `file://` URI
+///
+/// Relative paths are joined onto `anchor`. If the anchored path does not
+/// exist but the path relative to the working directory does, the latter is
+/// used so existing CWD-relative workflows keep working.
+#[must_use]
+pub(crate) fn resolve_path(raw: &str, anchor: Option<PathAnchor>) -> ResolvedPath {
+    let path_str = raw.strip_prefix("file://").unwrap_or(raw);
+    let path = Path::new(path_str);
+
+    if path.is_absolute() {
+        return ResolvedPath {
+            raw: raw.to_string(),
+            path: path.to_path_buf(),
+            anchor: PathAnchor::Absolute,
+        };
+    }
+
+    let dir = match &anchor {
+        Some(PathAnchor::BaseDir(dir) | PathAnchor::WorkflowDir(dir)) => Some(dir),
+        Some(PathAnchor::Absolute | PathAnchor::CurrentDir) | None => None,
+    };
+
+    if let (Some(dir), Some(anchor)) = (dir, anchor.clone()) {
+        let anchored = dir.join(path);
+        if anchored.exists() || !path.exists() {
+            return ResolvedPath {
+                raw: raw.to_string(),
+                path: anchored,
+                anchor,
+            };
+        }
+        tracing::warn!(
+            "'{}' was not found relative to '{}', falling back to the current directory",
+            raw,
+            dir.display()
+        );
+    }
+
+    ResolvedPath {
+        raw: raw.to_string(),
+        path: path.to_path_buf(),
+        anchor: PathAnchor::CurrentDir,
+    }
+}
+
+impl DurableEngine {
+    /// Record the file a workflow was loaded from
+    ///
+    /// Relative resource paths in the workflow (script sources, catalogs,
+    /// proto files and `OpenAPI` schemas) are resolved against this file's
+    /// directory unless a base directory override is configured.
+    pub async fn set_workflow_path(&self, workflow: &WorkflowDefinition, path: &Path) {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let key = format!(
+            "{}/{}/{}",
+            workflow.document.namespace, workflow.document.name, workflow.document.version
+        );
+        self.workflow_dirs.write().await.insert(key, dir);
+    }
+
+    /// Resolve a resource path referenced by `workflow`
+    pub(crate) async fn resolve_resource(
+        &self,
+        workflow: &WorkflowDefinition,
+        raw: &str,
+    ) -> ResolvedPath {
+        let anchor = if let Some(dir) = &self.base_dir {
+            Some(PathAnchor::BaseDir(dir.clone()))
+        } else {
+            let key = format!(
+                "{}/{}/{}",
+                workflow.document.namespace, workflow.document.name, workflow.document.version
+            );
+            self.workflow_dirs
+                .read()
+                .await
+                .get(&key)
+                .cloned()
+                .map(PathAnchor::WorkflowDir)
+        };
+        resolve_path(raw, anchor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    #[test]
+    fn test_absolute_path_is_unchanged() {
+        let resolved = resolve_path(
+            "file:///opt/scripts/run.py",
+            Some(PathAnchor::WorkflowDir(PathBuf::from("/workflows"))),
+        );
+        assert_eq!(resolved.path, PathBuf::from("/opt/scripts/run.py"));
+        assert_eq!(resolved.anchor, PathAnchor::Absolute);
+    }
+
+    #[test]
+    fn test_relative_path_anchored_to_workflow_dir() {
+        let dir = PathBuf::from("/nonexistent/workflows");
+        let resolved = resolve_path(
+            "file://./scripts/run.py",
+            Some(PathAnchor::WorkflowDir(dir.clone())),
+        );
+        assert_eq!(resolved.path, dir.join("./scripts/run.py"));
+        assert_eq!(resolved.anchor, PathAnchor::WorkflowDir(dir));
+        let message = resolved.to_string();
+        assert!(
+            message.contains("relative to workflow directory '/nonexistent/workflows'"),
+            "unexpected message: {message}"
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_current_dir_when_only_cwd_path_exists() {
+        let resolved = resolve_path(
+            "Cargo.toml",
+            Some(PathAnchor::BaseDir(PathBuf::from("/nonexistent"))),
+        );
+        assert_eq!(resolved.path, PathBuf::from("Cargo.toml"));
+        assert_eq!(resolved.anchor, PathAnchor::CurrentDir);
+    }
+
+    #[test]
+    fn test_no_anchor_resolves_against_current_dir() {
+        let resolved = resolve_path("spec.proto", None);
+        assert_eq!(resolved.path, PathBuf::from("spec.proto"));
+        assert_eq!(resolved.anchor, PathAnchor::CurrentDir);
+    }
+}
@@ -0,0 +1,254 @@
+/// Retry policies for `try` tasks, as defined by `catch.retry`
+use rand::Rng;
+use serde::Deserialize;
+use serverless_workflow_core::models::duration::OneOfDurationOrIso8601Expression;
+use serverless_workflow_core::models::task::ErrorCatcherDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration as StdDuration;
+
+use super::Error;
+use super::timeout::parse_duration;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Default multiplier applied to the delay after each attempt by exponential backoff
+const DEFAULT_EXPONENTIAL_FACTOR: f64 = 2.0;
+
+/// A retry policy, either inline in `catch.retry` or referenced from `use.retries`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RetryPolicy {
+    /// Only retry when this expression evaluates to `true`
+    pub when: Option<String>,
+
+    /// Do not retry when this expression evaluates to `true`
+    pub except_when: Option<String>,
+
+    /// Base delay between attempts
+    pub delay: Option<OneOfDurationOrIso8601Expression>,
+
+    #[serde(default)]
+    pub backoff: Backoff,
+
+    #[serde(default)]
+    pub limit: RetryLimit,
+
+    pub jitter: Option<Jitter>,
+}
+
+/// How the delay grows between attempts; constant when no strategy is set
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Backoff {
+    pub constant: Option<serde_json::Value>,
+    pub linear: Option<LinearBackoff>,
+    pub exponential: Option<ExponentialBackoff>,
+}
+
+/// Linear backoff: the delay grows by `increment` (the base delay by default) per retry
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct LinearBackoff {
+    pub increment: Option<OneOfDurationOrIso8601Expression>,
+}
+
+/// Exponential backoff: the delay is multiplied by `factor` (2 by default) per retry
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ExponentialBackoff {
+    pub factor: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct RetryLimit {
+    #[serde(default)]
+    pub attempt: AttemptLimit,
+
+    /// Maximum time spent retrying, measured from the first attempt
+    pub duration: Option<OneOfDurationOrIso8601Expression>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct AttemptLimit {
+    /// Maximum number of retries
+    pub count: Option<u32>,
+
+    /// Maximum duration of a single attempt
+    pub duration: Option<OneOfDurationOrIso8601Expression>,
+}
+
+/// Random duration added to each delay
+#[derive(Debug, Deserialize)]
+pub(crate) struct Jitter {
+    pub from: OneOfDurationOrIso8601Expression,
+    pub to: OneOfDurationOrIso8601Expression,
+}
+
+impl RetryPolicy {
+    /// Resolve the retry policy of a `catch`, following references to `use.retries`
+    pub(crate) fn resolve(
+        catch: &ErrorCatcherDefinition,
+        workflow: &WorkflowDefinition,
+    ) -> Result<Option<Self>> {
+        let catch = serde_json::to_value(catch)?;
+        let retry = match catch.get("retry") {
+            None | Some(serde_json::Value::Null) => return Ok(None),
+            Some(serde_json::Value::String(name)) => {
+                let pointer = format!(
+                    "/use/retries/{}",
+                    name.replace('~', "~0").replace('/', "~1")
+                );
+                serde_json::to_value(workflow)?
+                    .pointer(&pointer)
+                    .cloned()
+                    .ok_or_else(|| Error::Configuration {
+                        message: format!("Retry policy not found in use.retries: {name}"),
+                    })?
+            }
+            Some(retry) => retry.clone(),
+        };
+        Ok(Some(serde_json::from_value(retry)?))
+    }
+
+    /// Maximum duration of a single attempt, if limited
+    pub(crate) fn attempt_timeout(&self) -> Result<Option<StdDuration>> {
+        self.limit
+            .attempt
+            .duration
+            .as_ref()
+            .map(parse_duration)
+            .transpose()
+    }
+
+    /// Delay before retry number `retry` (starting at 1), or `None` once the
+    /// attempt count or total duration limit would be exceeded
+    pub(crate) fn next_delay(
+        &self,
+        retry: u32,
+        elapsed: StdDuration,
+    ) -> Result<Option<StdDuration>> {
+        if self.limit.attempt.count.is_some_and(|count| retry > count) {
+            return Ok(None);
+        }
+
+        let delay = self.backoff_delay(retry)?.saturating_add(self.jitter()?);
+
+        if let Some(limit) = &self.limit.duration
+            && elapsed + delay > parse_duration(limit)?
+        {
+            return Ok(None);
+        }
+        Ok(Some(delay))
+    }
+
+    /// Delay before retry number `retry`, without jitter
+    fn backoff_delay(&self, retry: u32) -> Result<StdDuration> {
+        let base = self
+            .delay
+            .as_ref()
+            .map(parse_duration)
+            .transpose()?
+            .unwrap_or_default();
+        let steps = retry.saturating_sub(1);
+
+        if let Some(exponential) = &self.backoff.exponential {
+            let factor = exponential.factor.unwrap_or(DEFAULT_EXPONENTIAL_FACTOR);
+            if !(factor.is_finite() && factor >= 1.0) {
+                return Err(Error::Configuration {
+                    message: format!("Exponential backoff factor must be at least 1, got {factor}"),
+                });
+            }
+            let exponent = i32::try_from(steps).unwrap_or(i32::MAX);
+            return Ok(base.mul_f64(factor.powi(exponent).min(f64::from(u32::MAX))));
+        }
+        if let Some(linear) = &self.backoff.linear {
+            let increment = match &linear.increment {
+                Some(increment) => parse_duration(increment)?,
+                None => base,
+            };
+            return Ok(base.saturating_add(increment.saturating_mul(steps)));
+        }
+        Ok(base)
+    }
+
+    /// A random duration within the jitter range, or zero without jitter
+    fn jitter(&self) -> Result<StdDuration> {
+        let Some(jitter) = &self.jitter else {
+            return Ok(StdDuration::ZERO);
+        };
+        let from = parse_duration(&jitter.from)?;
+        let to = parse_duration(&jitter.to)?;
+        if to <= from {
+            return Ok(from);
+        }
+        Ok(rand::thread_rng().gen_range(from..=to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    fn policy(yaml: &str) -> RetryPolicy {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_constant_backoff() {
+        let policy = policy("delay: { milliseconds: 100 }\nbackoff: { constant: {} }\n");
+        for retry in 1..=3 {
+            assert_eq!(
+                policy.next_delay(retry, StdDuration::ZERO).unwrap(),
+                Some(StdDuration::from_millis(100))
+            );
+        }
+    }
+
+    #[test]
+    fn test_linear_backoff() {
+        let policy = policy("delay: { milliseconds: 100 }\nbackoff: { linear: {} }\n");
+        assert_eq!(
+            policy.next_delay(3, StdDuration::ZERO).unwrap(),
+            Some(StdDuration::from_millis(300))
+        );
+    }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = policy("delay: PT0.1S\nbackoff: { exponential: { factor: 3 } }\n");
+        assert_eq!(
+            policy.next_delay(1, StdDuration::ZERO).unwrap(),
+            Some(StdDuration::from_millis(100))
+        );
+        assert_eq!(
+            policy.next_delay(3, StdDuration::ZERO).unwrap(),
+            Some(StdDuration::from_millis(900))
+        );
+    }
+
+    #[test]
+    fn test_attempt_and_duration_limits() {
+        let policy = policy(
+            "delay: { seconds: 1 }\nlimit:\n  attempt: { count: 2 }\n  duration: { seconds: 5 }\n",
+        );
+        assert!(policy.next_delay(2, StdDuration::ZERO).unwrap().is_some());
+        assert_eq!(policy.next_delay(3, StdDuration::ZERO).unwrap(), None);
+        assert_eq!(
+            policy
+                .next_delay(1, StdDuration::from_millis(4500))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_jitter_within_range() {
+        let policy = policy(
+            "delay: { milliseconds: 100 }\njitter: { from: { milliseconds: 10 }, to: { milliseconds: 20 } }\n",
+        );
+        let delay = policy.next_delay(1, StdDuration::ZERO).unwrap().unwrap();
+        assert!(delay >= StdDuration::from_millis(110), "{delay:?}");
+        assert!(delay <= StdDuration::from_millis(120), "{delay:?}");
+    }
+}
@@ -0,0 +1,162 @@
+//! JSON Schema validation of workflow and task input and output
+//!
+//! `input.schema` and `output.schema` hold a schema inline in `document` or
+//! reference one with `resource`. Input is validated before `input.from`
+//! transforms it and output after `output.as` has, as the DSL specifies. A
+//! mismatch fails with a validation error, which `try` tasks can catch.
+
+use serde::Serialize;
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+
+use super::{DurableEngine, Error, Result};
+
+/// At most this many mismatches are listed in a validation error
+const MAX_REPORTED_ERRORS: usize = 5;
+
+impl DurableEngine {
+    /// Validate `value` against the schema of an `input` or `output` definition
+    ///
+    /// Definitions without a schema accept any value. `subject` names what is
+    /// validated in the error, e.g. "Input of task 'charge'".
+    pub(super) async fn validate_schema(
+        &self,
+        workflow: &WorkflowDefinition,
+        definition: Option<&impl Serialize>,
+        value: &Value,
+        subject: &str,
+    ) -> Result<()> {
+        let Some(definition) = definition else {
+            return Ok(());
+        };
+        let definition = serde_json::to_value(definition)?;
+        let Some(schema) = definition.get("schema") else {
+            return Ok(());
+        };
+
+        let format = schema
+            .get("format")
+            .and_then(Value::as_str)
+            .unwrap_or("json");
+        if !format.eq_ignore_ascii_case("json") && !format.starts_with("json:") {
+            return Err(Error::Configuration {
+                message: format!("{subject} uses schema format '{format}'; only json is supported"),
+            });
+        }
+
+        let document = match (schema.get("document"), schema.pointer("/resource/endpoint")) {
+            (Some(document), _) => document.clone(),
+            (None, Some(endpoint)) => self.load_schema(workflow, endpoint).await?,
+            (None, None) => {
+                return Err(Error::Configuration {
+                    message: format!(
+                        "{subject} has a schema with neither a document nor a resource"
+                    ),
+                });
+            }
+        };
+
+        check(&document, value, subject)
+    }
+
+    /// Load a schema referenced by an external resource
+    async fn load_schema(&self, workflow: &WorkflowDefinition, endpoint: &Value) -> Result<Value> {
+        let uri = match endpoint {
+            Value::String(uri) => uri.as_str(),
+            Value::Object(endpoint) => endpoint
+                .get("uri")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::Array(_) => "",
+        };
+
+        let content = if uri.starts_with("http://") || uri.starts_with("https://") {
+            let response = reqwest::get(uri)
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| Error::Configuration {
+                    message: format!("Failed to fetch schema {uri}: {e}"),
+                })?;
+            response.text().await.map_err(|e| Error::Configuration {
+                message: format!("Failed to fetch schema {uri}: {e}"),
+            })?
+        } else {
+            let resolved = self.resolve_resource(workflow, uri).await;
+            tokio::fs::read_to_string(&resolved.path)
+                .await
+                .map_err(|source| Error::ResourceRead {
+                    resource: resolved.to_string(),
+                    source,
+                })?
+        };
+
+        // JSON is valid YAML, so YAML parsing covers both
+        serde_yaml::from_str(&content).map_err(|e| Error::Configuration {
+            message: format!("Schema {uri} is neither JSON nor YAML: {e}"),
+        })
+    }
+}
+
+/// Check `value` against the schema `document`
+fn check(document: &Value, value: &Value, subject: &str) -> Result<()> {
+    let validator = jsonschema::validator_for(document).map_err(|e| Error::Configuration {
+        message: format!("{subject} has an invalid schema: {e}"),
+    })?;
+
+    let mismatches: Vec<String> = validator
+        .iter_errors(value)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{path}: {error}")
+            }
+        })
+        .collect();
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "{subject} does not match its schema: {}",
+        mismatches
+            .iter()
+            .take(MAX_REPORTED_ERRORS)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("; ")
+    );
+    if mismatches.len() > MAX_REPORTED_ERRORS {
+        message.push_str(&format!(
+            " (and {} more)",
+            mismatches.len() - MAX_REPORTED_ERRORS
+        ));
+    }
+    Err(Error::SchemaValidation { message })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_lists_mismatches_with_their_path() {
+        let schema = json!({
+            "type": "object",
+            "required": ["orderId"],
+            "properties": { "quantity": { "type": "integer", "minimum": 1 } }
+        });
+
+        assert!(check(&schema, &json!({ "orderId": "a1", "quantity": 2 }), "Input").is_ok());
+
+        let error = check(&schema, &json!({ "quantity": 0 }), "Input").unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("Input does not match its schema"));
+        assert!(message.contains("orderId"));
+        assert!(message.contains("/quantity"));
+    }
+}
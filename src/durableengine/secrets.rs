@@ -0,0 +1,209 @@
+//! Secrets declared in `use.secrets`
+//!
+//! The secrets a workflow declares are read from the engine's secrets
+//! provider when an instance starts, or resumes, and placed in its data where
+//! expressions read them as `$secrets.NAME`. They are never persisted: the
+//! instance records its events and checkpoints through a
+//! [`RedactingPersistence`], which drops them from the data and masks their
+//! values wherever else they appear.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::ResultExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{DurableEngine, InstanceState, Result};
+use crate::context::Context;
+use crate::output;
+use crate::persistence::{
+    self, Change, Lease, PersistenceProvider, QueuedStart, RunUpdate, SerializationSnafu,
+    WorkflowRollup,
+};
+use crate::secrets::{Redactor, SECRETS_KEY};
+use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
+
+impl DurableEngine {
+    /// Read the secrets `workflow` declares in `use.secrets`
+    ///
+    /// # Errors
+    /// Fails if a declared secret does not exist or the provider cannot be read.
+    pub(super) async fn resolve_secrets(
+        &self,
+        workflow: &WorkflowDefinition,
+    ) -> Result<Map<String, Value>> {
+        let mut secrets = Map::new();
+        let declared = workflow
+            .use_
+            .as_ref()
+            .and_then(|use_| use_.secrets.as_ref());
+        for name in declared.into_iter().flatten() {
+            let value = self.secrets.require(name).await?;
+            secrets.insert(name.clone(), Value::String(value));
+        }
+        Ok(secrets)
+    }
+}
+
+/// Redacts the secrets of an instance from what it persists
+pub(super) fn redacting(
+    persistence: Arc<dyn PersistenceProvider>,
+    secrets: &Map<String, Value>,
+) -> Arc<dyn PersistenceProvider> {
+    if secrets.is_empty() {
+        return persistence;
+    }
+    let values: Vec<String> = secrets
+        .values()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect();
+    output::register_secrets(values.iter().cloned());
+    Arc::new(RedactingPersistence {
+        inner: persistence,
+        redactor: Redactor::new(values),
+    })
+}
+
+/// Place `secrets` in the data of `ctx`, where expressions read them as `$secrets`
+pub(super) async fn inject(ctx: &Context, secrets: Map<String, Value>) {
+    if secrets.is_empty() {
+        return;
+    }
+    ctx.inject(SECRETS_KEY, Value::Object(secrets)).await;
+}
+
+/// Persistence that masks secret values in the events and checkpoints it saves
+#[derive(Debug)]
+struct RedactingPersistence {
+    inner: Arc<dyn PersistenceProvider>,
+    redactor: Redactor,
+}
+
+impl RedactingPersistence {
+    /// `item` with the secrets masked, by way of its JSON form
+    fn redact<T: Serialize + DeserializeOwned>(&self, item: T) -> persistence::Result<T> {
+        let mut value = serde_json::to_value(item).context(SerializationSnafu)?;
+        self.redactor.redact(&mut value);
+        serde_json::from_value(value).context(SerializationSnafu)
+    }
+}
+
+#[async_trait]
+impl PersistenceProvider for RedactingPersistence {
+    async fn save_event(&self, event: WorkflowEvent) -> persistence::Result<()> {
+        self.inner.save_event(self.redact(event)?).await
+    }
+
+    async fn get_events(&self, instance_id: &str) -> persistence::Result<Vec<WorkflowEvent>> {
+        self.inner.get_events(instance_id).await
+    }
+
+    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> persistence::Result<()> {
+        self.inner.save_checkpoint(self.redact(checkpoint)?).await
+    }
+
+    async fn get_checkpoint(
+        &self,
+        instance_id: &str,
+    ) -> persistence::Result<Option<WorkflowCheckpoint>> {
+        self.inner.get_checkpoint(instance_id).await
+    }
+
+    async fn list_instance_ids(&self) -> persistence::Result<Vec<String>> {
+        self.inner.list_instance_ids().await
+    }
+
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> persistence::Result<()> {
+        self.inner
+            .commit_task(self.redact(event)?, self.redact(checkpoint)?)
+            .await
+    }
+
+    async fn get_state(&self, instance_id: &str) -> persistence::Result<Option<InstanceState>> {
+        self.inner.get_state(instance_id).await
+    }
+
+    async fn transition_state(
+        &self,
+        instance_id: &str,
+        from: Option<InstanceState>,
+        to: InstanceState,
+    ) -> persistence::Result<bool> {
+        self.inner.transition_state(instance_id, from, to).await
+    }
+
+    async fn acquire_lease(
+        &self,
+        instance_id: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> persistence::Result<Option<Lease>> {
+        self.inner.acquire_lease(instance_id, owner, ttl).await
+    }
+
+    async fn release_lease(&self, lease: &Lease) -> persistence::Result<()> {
+        self.inner.release_lease(lease).await
+    }
+
+    async fn changes(
+        &self,
+        after: u64,
+        instance_id: Option<&str>,
+        limit: usize,
+    ) -> persistence::Result<Vec<Change>> {
+        self.inner.changes(after, instance_id, limit).await
+    }
+
+    async fn enqueue_start(&self, start: QueuedStart) -> persistence::Result<()> {
+        self.inner.enqueue_start(start).await
+    }
+
+    async fn queued_starts(&self, limit: usize) -> persistence::Result<Vec<QueuedStart>> {
+        self.inner.queued_starts(limit).await
+    }
+
+    async fn remove_start(&self, instance_id: &str) -> persistence::Result<()> {
+        self.inner.remove_start(instance_id).await
+    }
+
+    async fn save_debug_bundle(
+        &self,
+        instance_id: &str,
+        mut bundle: Value,
+    ) -> persistence::Result<()> {
+        self.redactor.redact(&mut bundle);
+        self.inner.save_debug_bundle(instance_id, bundle).await
+    }
+
+    async fn get_debug_bundle(&self, instance_id: &str) -> persistence::Result<Option<Value>> {
+        self.inner.get_debug_bundle(instance_id).await
+    }
+
+    async fn update_rollup(
+        &self,
+        workflow_id: &str,
+        update: &RunUpdate,
+    ) -> persistence::Result<()> {
+        self.inner.update_rollup(workflow_id, update).await
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> persistence::Result<()> {
+        self.inner.replace_rollups(rollups).await
+    }
+
+    async fn workflow_rollups(&self) -> persistence::Result<Option<Vec<WorkflowRollup>>> {
+        self.inner.workflow_rollups().await
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.inner.is_degraded()
+    }
+}
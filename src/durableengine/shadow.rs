@@ -0,0 +1,439 @@
+//! Shadow runs of candidate workflow versions
+//!
+//! A candidate version registered with
+//! [`DurableEngineBuilder::with_shadow`](crate::DurableEngineBuilder::with_shadow)
+//! runs in the shadow of every instance of the workflow it replaces: once an
+//! instance completes, the candidate runs in the background with the same
+//! input in a sandbox engine whose events stay apart from the instance's. Depending on the
+//! [`ShadowMode`], the tasks of the candidate that have side effects take the
+//! results the instance recorded for them, or, for HTTP and OpenAPI calls,
+//! are sent to mock servers. Where the two outputs differ is recorded in a
+//! `ShadowCompared` event of the instance, so a new version can be checked
+//! against live traffic before it replaces the current one.
+
+use async_recursion::async_recursion;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::context::Context;
+use crate::providers::cache::mem::InMemoryCache;
+use crate::providers::persistence::InMemoryPersistence;
+use crate::workflow::{OutputDifference, WorkflowEvent};
+
+use super::{DurableEngine, Result};
+
+/// How long a shadow run may take before it is stopped and recorded as failed
+pub(super) const SHADOW_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How the tasks of a candidate with side effects run in its shadow runs,
+/// chosen with `shadow_mode`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShadowMode {
+    /// `call`, `run`, `emit`, `listen` and `wait` tasks take the results the
+    /// instance recorded for the tasks of the same name, or pass their input
+    /// through if it recorded none
+    #[default]
+    Stub,
+    /// `http` and `openapi` calls are sent to the mock servers set up with
+    /// [`DurableEngineBuilder::with_mock_route`](crate::DurableEngineBuilder::with_mock_route),
+    /// and fail if no mock answers them; the other tasks with side effects
+    /// take the recorded results as in [`ShadowMode::Stub`]
+    Sandbox,
+}
+
+impl std::fmt::Display for ShadowMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Stub => "stub",
+            Self::Sandbox => "sandbox",
+        })
+    }
+}
+
+impl std::str::FromStr for ShadowMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "stub" => Ok(Self::Stub),
+            "sandbox" => Ok(Self::Sandbox),
+            _ => Err(format!(
+                "unknown shadow mode '{name}', expected stub or sandbox"
+            )),
+        }
+    }
+}
+
+/// A candidate version run in the shadow of the workflow it replaces
+#[derive(Debug, Clone)]
+pub(super) struct Shadow {
+    pub(super) candidate: WorkflowDefinition,
+    pub(super) mode: ShadowMode,
+}
+
+impl Shadow {
+    /// Whether the candidate replaces `workflow`
+    fn shadows(&self, workflow: &WorkflowDefinition) -> bool {
+        let candidate = &self.candidate.document;
+        candidate.namespace == workflow.document.namespace
+            && candidate.name == workflow.document.name
+    }
+}
+
+/// The results an instance recorded, taken by the tasks of a shadow run
+#[derive(Debug, Default)]
+pub(super) struct ShadowStubs {
+    recorded: Mutex<HashMap<String, VecDeque<Value>>>,
+    stubbed: Mutex<Vec<String>>,
+    /// Whether calls that mock servers can answer run instead
+    mocked_calls: bool,
+}
+
+impl ShadowStubs {
+    pub(super) fn new(events: &[WorkflowEvent]) -> Self {
+        let mut recorded: HashMap<String, VecDeque<Value>> = HashMap::new();
+        for event in events {
+            if let WorkflowEvent::TaskCompleted {
+                task_name, result, ..
+            } = event
+            {
+                recorded
+                    .entry(task_name.clone())
+                    .or_default()
+                    .push_back(result.clone());
+            }
+        }
+        Self {
+            recorded: Mutex::new(recorded),
+            stubbed: Mutex::new(Vec::new()),
+            mocked_calls: false,
+        }
+    }
+
+    /// The same stubs, letting `http` and `openapi` calls run against mocks
+    pub(super) fn with_mocked_calls(mut self) -> Self {
+        self.mocked_calls = true;
+        self
+    }
+
+    /// The result a task with side effects takes instead of running
+    ///
+    /// Tasks that ran more than once, e.g. in loops, take their recorded
+    /// results in order. Returns `None` for tasks without side effects, and
+    /// for mocked calls if they run, which run as usual.
+    pub(super) async fn stub(
+        &self,
+        task_name: &str,
+        task: &TaskDefinition,
+        ctx: &Context,
+    ) -> Option<Value> {
+        if !has_side_effects(task)
+            || (self.mocked_calls && is_mockable_call(task, &ctx.metadata.workflow))
+        {
+            return None;
+        }
+        let recorded = self
+            .recorded
+            .lock()
+            .ok()
+            .and_then(|mut recorded| recorded.get_mut(task_name)?.pop_front());
+        if let Ok(mut stubbed) = self.stubbed.lock() {
+            stubbed.push(task_name.to_string());
+        }
+        match recorded {
+            Some(result) => Some(result),
+            None => Some(ctx.task_input().await),
+        }
+    }
+
+    fn stubbed(&self) -> Vec<String> {
+        self.stubbed
+            .lock()
+            .map(|stubbed| stubbed.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether running a task reaches outside the engine
+pub(super) fn has_side_effects(task: &TaskDefinition) -> bool {
+    match task {
+        TaskDefinition::Call(_)
+        | TaskDefinition::Emit(_)
+        | TaskDefinition::Listen(_)
+        | TaskDefinition::Run(_)
+        | TaskDefinition::Wait(_) => true,
+        TaskDefinition::Do(_)
+        | TaskDefinition::For(_)
+        | TaskDefinition::Fork(_)
+        | TaskDefinition::Raise(_)
+        | TaskDefinition::Set(_)
+        | TaskDefinition::Switch(_)
+        | TaskDefinition::Try(_) => false,
+    }
+}
+
+/// Whether a task is a call that mock servers can answer, sending its
+/// requests through the mock routes
+fn is_mockable_call(task: &TaskDefinition, workflow: &WorkflowDefinition) -> bool {
+    let TaskDefinition::Call(call_task) = task else {
+        return false;
+    };
+    // Calls of user-defined functions use the function's call type
+    let call_type = match workflow
+        .use_
+        .as_ref()
+        .and_then(|use_| use_.functions.as_ref())
+        .and_then(|functions| functions.get(&call_task.call))
+    {
+        Some(TaskDefinition::Call(function)) => function.call.as_str(),
+        _ => call_task.call.as_str(),
+    };
+    matches!(call_type, "http" | "openapi")
+}
+
+impl DurableEngine {
+    /// An engine that runs workflows as this one does, but records its events
+    /// apart from this engine's
+    pub(super) fn sandbox(&self) -> Result<DurableEngine> {
+        let mut sandbox = DurableEngine::new(
+            Arc::new(InMemoryPersistence::new()),
+            Arc::new(InMemoryCache::new()),
+        )?;
+        sandbox.executors = Arc::clone(&self.executors);
+        sandbox.workflow_registry = Arc::clone(&self.workflow_registry);
+        sandbox.workflow_dirs = Arc::clone(&self.workflow_dirs);
+        sandbox.base_dir = self.base_dir.clone();
+        sandbox.env_policy = Arc::clone(&self.env_policy);
+        sandbox.secrets = Arc::clone(&self.secrets);
+        sandbox.mock_routes = Arc::clone(&self.mock_routes);
+        sandbox.warm_start = Arc::clone(&self.warm_start);
+        sandbox.container_defaults = Arc::clone(&self.container_defaults);
+        sandbox.container_runtime = self.container_runtime;
+        sandbox.number_mode = self.number_mode;
+        Ok(sandbox)
+    }
+
+    /// Start the candidates that replace `workflow` in the shadow of a
+    /// completed instance
+    ///
+    /// The shadow runs go on in the background, each stopped after
+    /// [`SHADOW_TIMEOUT`]. Their failures are recorded in the comparison; they
+    /// never fail the instance.
+    pub(super) fn spawn_shadows(
+        &self,
+        workflow: &WorkflowDefinition,
+        instance_id: &str,
+        input: Value,
+        output: &Value,
+    ) {
+        for shadow in self
+            .shadows
+            .iter()
+            .filter(|shadow| shadow.shadows(workflow))
+        {
+            let engine = self.clone();
+            let shadow = shadow.clone();
+            let instance_id = instance_id.to_string();
+            let input = input.clone();
+            let output = output.clone();
+            let run = tokio::spawn(async move {
+                if let Err(e) = engine
+                    .run_shadow(&shadow, &instance_id, input, &output)
+                    .await
+                {
+                    tracing::warn!("Shadow run of instance {} failed: {}", instance_id, e);
+                }
+            });
+            if let Ok(mut runs) = self.shadow_runs.lock() {
+                runs.retain(|run| !run.is_finished());
+                runs.push(run);
+            }
+        }
+    }
+
+    /// Wait for the shadow runs going on in the background to finish
+    ///
+    /// Call this before a process that ran instances exits, or the
+    /// comparisons of their shadow runs may not be recorded.
+    pub async fn wait_for_shadows(&self) {
+        let runs = self
+            .shadow_runs
+            .lock()
+            .map(|mut runs| std::mem::take(&mut *runs))
+            .unwrap_or_default();
+        for run in runs {
+            let _ = run.await;
+        }
+    }
+
+    // The shadow run starts an instance, which may run shadows of its own
+    #[async_recursion]
+    async fn run_shadow(
+        &self,
+        shadow: &Shadow,
+        instance_id: &str,
+        input: Value,
+        output: &Value,
+    ) -> Result<()> {
+        // The shadow records its own events, apart from the instance's
+        let mut sandbox = self.sandbox()?;
+        let events = self.persistence.get_events(instance_id).await?;
+        let stubs = match shadow.mode {
+            ShadowMode::Stub => ShadowStubs::new(&events),
+            ShadowMode::Sandbox => {
+                // Calls that no mock answers are refused rather than repeated
+                sandbox.mock_routes = Arc::new(self.mock_routes.mocked_only());
+                ShadowStubs::new(&events).with_mocked_calls()
+            }
+        };
+        let stubs = Arc::new(stubs);
+        sandbox.shadow_stubs = Some(Arc::clone(&stubs));
+
+        let document = &shadow.candidate.document;
+        let candidate = format!(
+            "{}/{}/{}",
+            document.namespace, document.name, document.version
+        );
+        let run = tokio::time::timeout(
+            SHADOW_TIMEOUT,
+            sandbox.run_instance(
+                shadow.candidate.clone(),
+                Some(format!("{instance_id}-shadow")),
+                input,
+            ),
+        )
+        .await;
+        let (differences, error) = match run {
+            Ok(Ok(shadow_output)) => (differences(output, &shadow_output), None),
+            Ok(Err(e)) => (Vec::new(), Some(e.to_string())),
+            Err(_) => (
+                Vec::new(),
+                Some(format!("Shadow run timed out after {SHADOW_TIMEOUT:?}")),
+            ),
+        };
+
+        // Outputs may hold the secrets the workflow declares
+        let secrets = self.resolve_secrets(&shadow.candidate).await?;
+        super::secrets::redacting(Arc::clone(&self.persistence), &secrets)
+            .save_event(WorkflowEvent::ShadowCompared {
+                instance_id: instance_id.to_string(),
+                candidate,
+                differences,
+                error,
+                stubbed: stubs.stubbed(),
+                timestamp: chrono::Utc::now(),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// Where `right` differs from `left`, by JSON pointer
+///
+/// Objects are compared key by key and arrays item by item, so a difference
+/// points at the innermost value that changed.
+pub(crate) fn differences(left: &Value, right: &Value) -> Vec<OutputDifference> {
+    let mut found = Vec::new();
+    compare(String::new(), Some(left), Some(right), &mut found);
+    found
+}
+
+fn compare(
+    path: String,
+    left: Option<&Value>,
+    right: Option<&Value>,
+    found: &mut Vec<OutputDifference>,
+) {
+    match (left, right) {
+        (Some(Value::Object(left)), Some(Value::Object(right))) => {
+            for key in left.keys().chain(right.keys()).collect::<BTreeSet<_>>() {
+                compare(
+                    format!("{path}/{}", key.replace('~', "~0").replace('/', "~1")),
+                    left.get(key),
+                    right.get(key),
+                    found,
+                );
+            }
+        }
+        (Some(Value::Array(left)), Some(Value::Array(right))) => {
+            for index in 0..left.len().max(right.len()) {
+                compare(
+                    format!("{path}/{index}"),
+                    left.get(index),
+                    right.get(index),
+                    found,
+                );
+            }
+        }
+        (left, right) => {
+            if left != right {
+                found.push(OutputDifference {
+                    path,
+                    left: left.cloned(),
+                    right: right.cloned(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_equal_outputs_have_no_differences() {
+        let output = json!({ "order": { "id": 7, "items": [1, 2] } });
+        assert!(differences(&output, &output.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_differences_point_at_the_changed_values() {
+        let left = json!({ "order": { "total": 10, "items": [1, 2] }, "a/b": true });
+        let right = json!({ "order": { "total": 12, "items": [1] }, "note": "new" });
+        assert_eq!(
+            differences(&left, &right),
+            vec![
+                OutputDifference {
+                    path: "/a~1b".to_string(),
+                    left: Some(json!(true)),
+                    right: None,
+                },
+                OutputDifference {
+                    path: "/note".to_string(),
+                    left: None,
+                    right: Some(json!("new")),
+                },
+                OutputDifference {
+                    path: "/order/items/1".to_string(),
+                    left: Some(json!(2)),
+                    right: None,
+                },
+                OutputDifference {
+                    path: "/order/total".to_string(),
+                    left: Some(json!(10)),
+                    right: Some(json!(12)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outputs_of_different_types_differ_as_a_whole() {
+        assert_eq!(
+            differences(&json!([1]), &json!("done")),
+            vec![OutputDifference {
+                path: String::new(),
+                left: Some(json!([1])),
+                right: Some(json!("done")),
+            }]
+        );
+    }
+}
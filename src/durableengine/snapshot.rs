@@ -0,0 +1,395 @@
+//! Warm-start snapshots of an engine's setup
+//!
+//! An engine started in a fresh process parses the workflows it registers,
+//! preprocesses their expressions, fetches the `OpenAPI` documents their
+//! tasks call, compiles their proto files and probes the interpreters of its
+//! executors. Where processes are cold-started for a request, as on FaaS
+//! platforms, that setup is much of the request's latency.
+//! [`DurableEngine::snapshot`] records its results in an [`EngineSnapshot`],
+//! written to a file when the deployment is built; an engine built with the
+//! snapshot starts from them instead of doing the work again.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::executor::Health;
+use crate::expressions::{self, ExpressionLanguage, ExpressionMode};
+use crate::ordering::in_key_order;
+
+use super::{DurableEngine, Error, IoSnafu, ResourceReadSnafu, Result};
+
+/// Version of the snapshot format, raised whenever snapshots written before
+/// can no longer be read
+pub const SNAPSHOT_FORMAT: u32 = 1;
+
+/// The setup of an engine, recorded to start other engines from
+///
+/// A snapshot is only valid for the version of jackdaw that took it, and for
+/// the environment it was taken in: executors trust the recorded health of
+/// their interpreters, so take it in the image the engine runs in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineSnapshot {
+    /// Version of the snapshot format, see [`SNAPSHOT_FORMAT`]
+    pub format: u32,
+    /// Version of jackdaw that took the snapshot
+    pub jackdaw_version: String,
+    pub created_at: DateTime<Utc>,
+    /// The registered workflows
+    pub workflows: Vec<SnapshotWorkflow>,
+    /// The `${ }` expressions of the workflows, preprocessed
+    pub expressions: Vec<SnapshotExpression>,
+    /// The `OpenAPI` documents called by the workflows, parsed, by endpoint
+    pub openapi_documents: BTreeMap<String, Value>,
+    /// The proto files of gRPC calls and listeners, compiled, by path, as
+    /// base64 encoded `FileDescriptorSet`s
+    pub proto_descriptors: BTreeMap<String, String>,
+    /// The health of the executors, by call type
+    pub executor_health: BTreeMap<String, Health>,
+}
+
+/// A registered workflow recorded in a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotWorkflow {
+    pub definition: WorkflowDefinition,
+    /// Directory of the file the workflow was loaded from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+/// The source of a `${ }` expression and its preprocessed form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotExpression {
+    pub mode: ExpressionMode,
+    pub source: String,
+    pub preprocessed: String,
+}
+
+impl EngineSnapshot {
+    /// Read a snapshot written by [`EngineSnapshot::write`]
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or parsed, or the snapshot
+    /// was taken by another version of jackdaw.
+    pub fn read(path: &Path) -> Result<Self> {
+        let json = std::fs::read(path).context(ResourceReadSnafu {
+            resource: path.display().to_string(),
+        })?;
+        let snapshot: Self = serde_json::from_slice(&json)?;
+        snapshot.check_compatible()?;
+        Ok(snapshot)
+    }
+
+    /// Write the snapshot to `path`, as JSON
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec(self)?;
+        std::fs::write(path, json).context(IoSnafu)
+    }
+
+    /// Check that this version of jackdaw can start from the snapshot
+    fn check_compatible(&self) -> Result<()> {
+        let version = env!("CARGO_PKG_VERSION");
+        if self.format != SNAPSHOT_FORMAT || self.jackdaw_version != version {
+            return Err(Error::Configuration {
+                message: format!(
+                    "Snapshot was taken by jackdaw {} (format {}), not {version} (format {SNAPSHOT_FORMAT}); take it again",
+                    self.jackdaw_version, self.format
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Documents and descriptors restored from an [`EngineSnapshot`], which
+/// executors and listeners use instead of fetching and compiling them again
+#[derive(Debug, Default)]
+pub struct WarmStart {
+    openapi_documents: HashMap<String, Value>,
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    proto_descriptors: HashMap<String, Vec<u8>>,
+}
+
+impl WarmStart {
+    /// The parsed `OpenAPI` document at `endpoint`, if the snapshot has it
+    #[must_use]
+    pub fn openapi_document(&self, endpoint: &str) -> Option<&Value> {
+        self.openapi_documents.get(endpoint)
+    }
+
+    /// The compiled proto file at `path`, if the snapshot has it
+    #[cfg(feature = "grpc")]
+    #[must_use]
+    pub fn proto_descriptors(&self, path: &str) -> Option<prost_types::FileDescriptorSet> {
+        use prost::Message;
+
+        self.proto_descriptors
+            .get(path)
+            .and_then(|encoded| prost_types::FileDescriptorSet::decode(encoded.as_slice()).ok())
+    }
+}
+
+impl DurableEngine {
+    /// Record the setup of this engine, to start other engines from
+    ///
+    /// The snapshot holds the registered workflows and the directories they
+    /// were loaded from, their `${ }` expressions preprocessed, the `OpenAPI`
+    /// documents and proto files their tasks call and listen with, and the
+    /// health of the executors. A document that cannot be fetched or a proto
+    /// file that cannot be compiled is left out with a warning; engines
+    /// started from the snapshot fetch or compile it when it is used.
+    ///
+    /// # Errors
+    /// Returns an error if a registered workflow cannot be serialized.
+    pub async fn snapshot(&self) -> Result<EngineSnapshot> {
+        let registry = self.workflow_registry.read().await.clone();
+        let dirs = self.workflow_dirs.read().await.clone();
+
+        let mut workflows = Vec::new();
+        let mut preprocessed = Vec::new();
+        let mut seen = HashSet::new();
+        let mut openapi_documents = BTreeMap::new();
+        #[cfg_attr(not(feature = "grpc"), allow(unused_mut))]
+        let mut proto_descriptors = BTreeMap::new();
+        for (key, workflow) in in_key_order(&registry) {
+            let definition = serde_json::to_value(workflow)?;
+
+            // JavaScript expressions are not preprocessed
+            if let (Ok(ExpressionLanguage::Jq), Ok(mode)) = (
+                ExpressionLanguage::of_workflow(workflow),
+                ExpressionMode::of_workflow(workflow),
+            ) {
+                let mut sources = Vec::new();
+                expression_sources(&definition, &mut sources);
+                for source in sources {
+                    if seen.insert((mode, source.to_string())) {
+                        preprocessed.push(SnapshotExpression {
+                            mode,
+                            source: source.to_string(),
+                            preprocessed: expressions::preprocessed(mode, source).to_string(),
+                        });
+                    }
+                }
+            }
+
+            for endpoint in called_endpoints(&definition, "openapi", "document") {
+                if openapi_documents.contains_key(endpoint) {
+                    continue;
+                }
+                match self.warm_start.openapi_document(endpoint) {
+                    Some(document) => {
+                        openapi_documents.insert(endpoint.to_string(), document.clone());
+                    }
+                    None => match fetch_openapi_document(endpoint).await {
+                        Ok(document) => {
+                            openapi_documents.insert(endpoint.to_string(), document);
+                        }
+                        Err(e) => tracing::warn!(
+                            "Leaving OpenAPI document {endpoint} of {key} out of the snapshot: {e}"
+                        ),
+                    },
+                }
+            }
+
+            #[cfg(feature = "grpc")]
+            for proto_path in self.proto_paths(workflow, &definition).await? {
+                if proto_descriptors.contains_key(&proto_path) {
+                    continue;
+                }
+                let compiled = match self.warm_start.proto_descriptors(&proto_path) {
+                    Some(file_descriptor_set) => Ok(file_descriptor_set),
+                    None => crate::listeners::grpc::compile_proto(&proto_path),
+                };
+                match compiled {
+                    Ok(file_descriptor_set) => {
+                        let encoded = prost::Message::encode_to_vec(&file_descriptor_set);
+                        proto_descriptors.insert(proto_path, BASE64_STANDARD.encode(encoded));
+                    }
+                    Err(e) => tracing::warn!(
+                        "Leaving proto file {proto_path} of {key} out of the snapshot: {e}"
+                    ),
+                }
+            }
+
+            workflows.push(SnapshotWorkflow {
+                definition: workflow.clone(),
+                dir: dirs.get(key).cloned(),
+            });
+        }
+
+        Ok(EngineSnapshot {
+            format: SNAPSHOT_FORMAT,
+            jackdaw_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: Utc::now(),
+            workflows,
+            expressions: preprocessed,
+            openapi_documents,
+            proto_descriptors,
+            executor_health: self.check_executors().await,
+        })
+    }
+
+    /// Start this engine from `snapshot`, registering its workflows and
+    /// taking the rest of its setup
+    pub(super) fn restore_snapshot(&mut self, snapshot: EngineSnapshot) -> Result<()> {
+        snapshot.check_compatible()?;
+
+        let mut registry = HashMap::new();
+        let mut dirs = HashMap::new();
+        for SnapshotWorkflow { definition, dir } in snapshot.workflows {
+            let key = format!(
+                "{}/{}/{}",
+                definition.document.namespace,
+                definition.document.name,
+                definition.document.version
+            );
+            if let Some(dir) = dir {
+                dirs.insert(key.clone(), dir);
+            }
+            registry.insert(key, definition);
+        }
+        self.workflow_registry = Arc::new(RwLock::new(registry));
+        self.workflow_dirs = Arc::new(RwLock::new(dirs));
+
+        for expression in &snapshot.expressions {
+            expressions::intern_preprocessed(
+                expression.mode,
+                &expression.source,
+                &expression.preprocessed,
+            );
+        }
+
+        for (call_type, health) in snapshot.executor_health {
+            if let Some(executor) = self.executors.get(&call_type) {
+                executor.restore_health(health);
+            }
+        }
+
+        let proto_descriptors = snapshot
+            .proto_descriptors
+            .into_iter()
+            .map(|(path, encoded)| {
+                BASE64_STANDARD
+                    .decode(&encoded)
+                    .map(|decoded| (path.clone(), decoded))
+                    .map_err(|e| Error::Configuration {
+                        message: format!("Invalid descriptors of {path} in snapshot: {e}"),
+                    })
+            })
+            .collect::<Result<_>>()?;
+        self.warm_start = Arc::new(WarmStart {
+            openapi_documents: snapshot.openapi_documents.into_iter().collect(),
+            proto_descriptors,
+        });
+        Ok(())
+    }
+
+    /// The proto files of the gRPC calls and listeners of `workflow`, as the
+    /// executor and the listeners look them up
+    #[cfg(feature = "grpc")]
+    async fn proto_paths(
+        &self,
+        workflow: &WorkflowDefinition,
+        definition: &Value,
+    ) -> Result<Vec<String>> {
+        use serverless_workflow_core::models::task::TaskDefinition;
+
+        let mut paths: Vec<String> = called_endpoints(definition, "grpc", "proto")
+            .into_iter()
+            .map(|endpoint| {
+                endpoint
+                    .strip_prefix("file://")
+                    .unwrap_or(endpoint)
+                    .to_string()
+            })
+            .collect();
+        for (_, task) in workflow.do_.entries.iter().flatten() {
+            let TaskDefinition::Listen(listen_task) = task else {
+                continue;
+            };
+            let Some((source, Some(proto_path))) = self.extract_listen_source(listen_task)? else {
+                continue;
+            };
+            if source
+                .get("uri")
+                .and_then(Value::as_str)
+                .is_some_and(|uri| uri.starts_with("grpc://"))
+            {
+                let proto = self.resolve_resource(workflow, &proto_path).await;
+                paths.push(proto.path.to_string_lossy().into_owned());
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// The sources of the `${ }` expressions anywhere in `value`
+fn expression_sources<'a>(value: &'a Value, sources: &mut Vec<&'a str>) {
+    match value {
+        Value::String(text) => sources.extend(expressions::expression_source(text)),
+        Value::Array(items) => {
+            for item in items {
+                expression_sources(item, sources);
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values() {
+                expression_sources(item, sources);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// The endpoints of the `resource` argument of the `call: <call_type>` tasks
+/// anywhere in `definition`, leaving out those chosen by expressions
+fn called_endpoints<'a>(definition: &'a Value, call_type: &str, resource: &str) -> Vec<&'a str> {
+    let mut endpoints = Vec::new();
+    let mut pending = vec![definition];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Object(map) => {
+                if map.get("call").and_then(Value::as_str) == Some(call_type)
+                    && let Some(endpoint) = map
+                        .get("with")
+                        .and_then(|with| with.get(resource))
+                        .and_then(|resource| resource.get("endpoint"))
+                {
+                    let uri = endpoint
+                        .as_str()
+                        .or_else(|| endpoint.get("uri").and_then(Value::as_str));
+                    endpoints
+                        .extend(uri.filter(|uri| expressions::expression_source(uri).is_none()));
+                }
+                pending.extend(map.values());
+            }
+            Value::Array(items) => pending.extend(items),
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+        }
+    }
+    endpoints
+}
+
+/// Fetch and parse the `OpenAPI` document at `endpoint`
+async fn fetch_openapi_document(endpoint: &str) -> std::result::Result<Value, String> {
+    let text = reqwest::get(endpoint)
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    crate::providers::executors::parse_openapi_document(&text).map_err(|e| e.to_string())
+}
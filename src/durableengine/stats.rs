@@ -0,0 +1,505 @@
+//! Summaries of the runs of each workflow
+//!
+//! [`DurableEngine::workflow_stats`] reports a [`WorkflowStats`] per
+//! workflow: how many runs there were and how they ended, how long the
+//! completed ones took, and the tasks failed runs most often stopped in.
+//!
+//! Engines built with [`crate::DurableEngineBuilder`] keep a
+//! [`WorkflowRollup`] per workflow with the persistence provider, updated as
+//! each run starts and ends, so the summaries are read back without going
+//! through the recorded history, and cover the runs of every process sharing
+//! the store. Summaries of a time window, and of providers that keep no
+//! rollups, are computed from the recorded history instead.
+//! [`DurableEngine::rebuild_workflow_rollups`] recounts the rollups of a
+//! store written before they were kept.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use super::{DurableEngine, InstanceFilter, InstanceState, InstanceStatus, Result};
+use crate::observer::WorkflowObserver;
+use crate::persistence::{self, PersistenceProvider, RunOutcome, RunUpdate, WorkflowRollup};
+use crate::workflow::WorkflowEvent;
+
+/// Tasks reported in [`WorkflowStats::failing_tasks`]
+const MAX_FAILING_TASKS: usize = 5;
+
+/// How the runs of one workflow went
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorkflowStats {
+    /// The "namespace/name/version" key of the workflow
+    pub workflow_id: String,
+    pub runs: usize,
+    pub completed: usize,
+    /// Runs that faulted or timed out
+    pub failed: usize,
+    pub cancelled: usize,
+    /// Runs that have not finished yet, including suspended ones
+    pub in_flight: usize,
+    /// Share of the finished runs that completed, from 0 to 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success_rate: Option<f64>,
+    /// How long the completed runs took
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<DurationPercentiles>,
+    /// The tasks failed runs stopped in, the most frequent first
+    pub failing_tasks: Vec<TaskFailures>,
+}
+
+/// Percentiles of run durations, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DurationPercentiles {
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+/// How many failed runs stopped in a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskFailures {
+    pub task_name: String,
+    pub failures: usize,
+}
+
+impl WorkflowStats {
+    /// Summarize instances per workflow, sorted by workflow key
+    ///
+    /// Instances without a recorded workflow are left out.
+    #[must_use]
+    pub fn from_instances(instances: &[InstanceStatus]) -> Vec<Self> {
+        rollups(instances).iter().map(Self::from_rollup).collect()
+    }
+
+    /// Summarize the runs counted in a rollup
+    #[must_use]
+    pub fn from_rollup(rollup: &WorkflowRollup) -> Self {
+        let count = |n: u64| usize::try_from(n).unwrap_or(usize::MAX);
+        let runs = count(rollup.runs);
+        let completed = count(rollup.completed);
+        let failed = count(rollup.failed);
+        let cancelled = count(rollup.cancelled);
+        let finished = completed.saturating_add(failed).saturating_add(cancelled);
+        #[allow(clippy::cast_precision_loss)]
+        let success_rate = (finished > 0).then(|| completed as f64 / finished as f64);
+
+        let mut failing_tasks: Vec<TaskFailures> = rollup
+            .failing_tasks
+            .iter()
+            .map(|(task_name, failures)| TaskFailures {
+                task_name: task_name.clone(),
+                failures: count(*failures),
+            })
+            .collect();
+        failing_tasks.sort_by(|a, b| {
+            b.failures
+                .cmp(&a.failures)
+                .then_with(|| a.task_name.cmp(&b.task_name))
+        });
+        failing_tasks.truncate(MAX_FAILING_TASKS);
+
+        Self {
+            workflow_id: rollup.workflow_id.clone(),
+            runs,
+            completed,
+            failed,
+            cancelled,
+            in_flight: runs.saturating_sub(finished),
+            success_rate,
+            duration_ms: DurationPercentiles::of(rollup.durations_ms.iter().copied().collect()),
+            failing_tasks,
+        }
+    }
+
+    /// Whether these are the stats of `workflow`, given by name or by key
+    ///
+    /// A name matches every version of the workflow.
+    #[must_use]
+    pub fn is_workflow(&self, workflow: &str) -> bool {
+        self.workflow_id == workflow || self.workflow_id.split('/').nth(1) == Some(workflow)
+    }
+}
+
+/// Count instances into a rollup per workflow, sorted by workflow key
+fn rollups(instances: &[InstanceStatus]) -> Vec<WorkflowRollup> {
+    let mut rollups: BTreeMap<&str, WorkflowRollup> = BTreeMap::new();
+    for instance in instances {
+        let Some(workflow_id) = &instance.workflow_id else {
+            continue;
+        };
+        let rollup = rollups
+            .entry(workflow_id)
+            .or_insert_with(|| WorkflowRollup::new(workflow_id));
+        rollup.apply(&RunUpdate::Started);
+        if instance.state.is_finished() {
+            let duration_ms = instance
+                .started_at
+                .zip(instance.finished_at)
+                .map(|(started, finished)| (finished - started).num_milliseconds());
+            rollup.apply(&RunUpdate::Finished {
+                outcome: outcome(
+                    instance.state,
+                    instance.current_task.as_deref(),
+                    duration_ms,
+                ),
+                previous: None,
+            });
+        }
+    }
+    rollups.into_values().collect()
+}
+
+/// How a run that ended in `state`, in `task` after `duration_ms`, is counted
+///
+/// Only completed runs keep their duration, and failed runs their task.
+fn outcome(state: InstanceState, task: Option<&str>, duration_ms: Option<i64>) -> RunOutcome {
+    let failed = state == InstanceState::Faulted || state == InstanceState::TimedOut;
+    RunOutcome {
+        state,
+        duration_ms: duration_ms
+            .filter(|_| state == InstanceState::Completed)
+            .map(|ms| u64::try_from(ms).unwrap_or_default()),
+        failed_task: task.filter(|_| failed).map(str::to_string),
+    }
+}
+
+/// How the run of an instance ended, and how it had ended before, from its events
+///
+/// `None` unless the last terminal event changes how the run is counted:
+/// completed and cancelled runs stay that way, while a run that faulted and
+/// was run again is counted by how it ended last.
+fn finished(events: &[WorkflowEvent]) -> Option<(String, RunUpdate)> {
+    let mut workflow_id = None;
+    let mut current_task = None;
+    let mut outcomes = Vec::new();
+    for event in events {
+        match event {
+            WorkflowEvent::WorkflowStarted {
+                workflow_id: id, ..
+            } => workflow_id = Some(id.clone()),
+            WorkflowEvent::TaskEntered { task_name, .. } => current_task = Some(task_name.clone()),
+            WorkflowEvent::TaskCompleted { task_name, .. } => {
+                if current_task.as_ref() == Some(task_name) {
+                    current_task = None;
+                }
+            }
+            WorkflowEvent::WorkflowCompleted { duration_ms, .. } => {
+                outcomes.push(outcome(InstanceState::Completed, None, Some(*duration_ms)));
+            }
+            WorkflowEvent::WorkflowFailed { .. } => {
+                outcomes.push(outcome(
+                    InstanceState::Faulted,
+                    current_task.as_deref(),
+                    None,
+                ));
+            }
+            WorkflowEvent::WorkflowCancelled { .. } => {
+                outcomes.push(outcome(InstanceState::Cancelled, None, None));
+            }
+            WorkflowEvent::TaskCreated { .. }
+            | WorkflowEvent::TaskStarted { .. }
+            | WorkflowEvent::TaskRetried { .. }
+            | WorkflowEvent::WorkflowCorrelationStarted { .. }
+            | WorkflowEvent::WorkflowCorrelationCompleted { .. }
+            | WorkflowEvent::WorkflowSuspended { .. }
+            | WorkflowEvent::WorkflowResumed { .. }
+            | WorkflowEvent::TaskCancelled { .. }
+            | WorkflowEvent::TaskSuspended { .. }
+            | WorkflowEvent::TaskResumed { .. }
+            | WorkflowEvent::TaskFaulted { .. }
+            | WorkflowEvent::TaskReaped { .. }
+            | WorkflowEvent::TaskCacheHit { .. }
+            | WorkflowEvent::TaskSkipped { .. }
+            | WorkflowEvent::SwitchCaseMatched { .. }
+            | WorkflowEvent::ErrorCaught { .. }
+            | WorkflowEvent::WatchesEvaluated { .. }
+            | WorkflowEvent::ShadowCompared { .. } => {}
+        }
+    }
+
+    let last = outcomes.pop()?;
+    let previous = outcomes.pop();
+    if previous
+        .as_ref()
+        .is_some_and(|previous| previous.state != InstanceState::Faulted)
+    {
+        return None;
+    }
+    Some((
+        workflow_id?,
+        RunUpdate::Finished {
+            outcome: last,
+            previous,
+        },
+    ))
+}
+
+/// Keeps the rollup of each workflow up to date as runs start and end
+#[derive(Debug)]
+pub(crate) struct RunRollups {
+    persistence: Arc<dyn PersistenceProvider>,
+}
+
+impl RunRollups {
+    pub(crate) fn new(persistence: Arc<dyn PersistenceProvider>) -> Self {
+        Self { persistence }
+    }
+
+    async fn record(&self, event: &WorkflowEvent) -> persistence::Result<()> {
+        if let WorkflowEvent::WorkflowStarted { workflow_id, .. } = event {
+            return self
+                .persistence
+                .update_rollup(workflow_id, &RunUpdate::Started)
+                .await;
+        }
+        if !matches!(
+            event,
+            WorkflowEvent::WorkflowCompleted { .. }
+                | WorkflowEvent::WorkflowFailed { .. }
+                | WorkflowEvent::WorkflowCancelled { .. }
+        ) {
+            return Ok(());
+        }
+
+        // The task a failed run stopped in, and whether it ended before, are
+        // in the history of this instance only
+        let events = self.persistence.get_events(event.instance_id()).await?;
+        match finished(&events) {
+            Some((workflow_id, update)) => {
+                self.persistence.update_rollup(&workflow_id, &update).await
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl WorkflowObserver for RunRollups {
+    async fn on_event(&self, event: &WorkflowEvent) {
+        if let Err(e) = self.record(event).await {
+            tracing::warn!(
+                "Failed to update the rollup for {}: {}",
+                event.instance_id(),
+                e
+            );
+        }
+    }
+}
+
+impl DurationPercentiles {
+    /// Nearest-rank percentiles of `durations`, or `None` if there are none
+    fn of(mut durations: Vec<u64>) -> Option<Self> {
+        durations.sort_unstable();
+        let rank = |percentile: usize| {
+            let index = (durations.len() * percentile)
+                .div_ceil(100)
+                .saturating_sub(1);
+            durations.get(index).copied()
+        };
+        Some(Self {
+            p50: rank(50)?,
+            p95: rank(95)?,
+            p99: rank(99)?,
+            max: durations.last().copied()?,
+        })
+    }
+}
+
+impl DurableEngine {
+    /// Summarize the runs of each workflow among the instances matching `filter`
+    ///
+    /// Without a state, time window or limit in `filter`, the summaries are
+    /// read from the rollups the persistence provider keeps, if it keeps any.
+    /// Otherwise, set [`InstanceFilter::started_after`] for a rolling window,
+    /// e.g. the runs started in the last day; like
+    /// [`DurableEngine::list_instances`], the instances are then read from
+    /// the read provider, if one is configured, and so are the rollups.
+    ///
+    /// # Errors
+    /// Returns an error if the rollups, or the instances and their events,
+    /// cannot be read.
+    pub async fn workflow_stats(&self, filter: &InstanceFilter) -> Result<Vec<WorkflowStats>> {
+        if filter.state.is_none()
+            && filter.started_after.is_none()
+            && filter.started_before.is_none()
+            && filter.limit.is_none()
+            && let Some(rollups) = self.reads.workflow_rollups().await?
+        {
+            return Ok(rollups
+                .iter()
+                .filter(|rollup| {
+                    filter
+                        .workflow_id
+                        .as_ref()
+                        .is_none_or(|id| &rollup.workflow_id == id)
+                })
+                .map(WorkflowStats::from_rollup)
+                .collect());
+        }
+
+        let instances = self.list_instances(filter).await?;
+        Ok(WorkflowStats::from_instances(&instances))
+    }
+
+    /// Recount the rollup of every workflow from the recorded history
+    ///
+    /// Rollups only count the runs that started and ended while they were
+    /// kept, so a store written by an earlier version of jackdaw, or by an
+    /// engine not built with [`crate::DurableEngineBuilder`], needs them
+    /// recounted once.
+    ///
+    /// # Errors
+    /// Returns an error if the instances cannot be read or the rollups cannot
+    /// be stored.
+    pub async fn rebuild_workflow_rollups(&self) -> Result<()> {
+        let instances = self.list_instances(&InstanceFilter::default()).await?;
+        self.persistence
+            .replace_rollups(rollups(&instances))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn instance(state: InstanceState, ms: i64, task: Option<&str>) -> InstanceStatus {
+        let started = Utc::now();
+        InstanceStatus {
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            workflow_id: Some("default/orders/1.0.0".to_string()),
+            state,
+            current_task: task.map(str::to_string),
+            started_at: Some(started),
+            updated_at: Some(started + Duration::milliseconds(ms)),
+            finished_at: state
+                .is_finished()
+                .then(|| started + Duration::milliseconds(ms)),
+            output: None,
+            error: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_stats_summarize_runs_per_workflow() {
+        let mut instances: Vec<InstanceStatus> = (1..=20)
+            .map(|i| instance(InstanceState::Completed, i * 100, None))
+            .collect();
+        instances.push(instance(InstanceState::Faulted, 50, Some("charge")));
+        instances.push(instance(InstanceState::Faulted, 50, Some("charge")));
+        instances.push(instance(InstanceState::TimedOut, 50, Some("ship")));
+        instances.push(instance(InstanceState::Cancelled, 50, None));
+        instances.push(instance(InstanceState::Running, 50, Some("charge")));
+        let mut other = instance(InstanceState::Completed, 10, None);
+        other.workflow_id = Some("default/refunds/1.0.0".to_string());
+        instances.push(other);
+
+        let stats = WorkflowStats::from_instances(&instances);
+        assert_eq!(stats.len(), 2);
+        let orders = stats.first().unwrap();
+        assert_eq!(orders.workflow_id, "default/orders/1.0.0");
+        assert_eq!(orders.runs, 25);
+        assert_eq!(orders.completed, 20);
+        assert_eq!(orders.failed, 3);
+        assert_eq!(orders.cancelled, 1);
+        assert_eq!(orders.in_flight, 1);
+        assert_eq!(orders.success_rate, Some(20.0 / 24.0));
+        assert_eq!(
+            orders.duration_ms,
+            Some(DurationPercentiles {
+                p50: 1000,
+                p95: 1900,
+                p99: 2000,
+                max: 2000,
+            })
+        );
+        assert_eq!(
+            orders.failing_tasks,
+            vec![
+                TaskFailures {
+                    task_name: "charge".to_string(),
+                    failures: 2,
+                },
+                TaskFailures {
+                    task_name: "ship".to_string(),
+                    failures: 1,
+                },
+            ]
+        );
+
+        let refunds = stats.get(1).unwrap();
+        assert_eq!(refunds.runs, 1);
+        assert_eq!(refunds.success_rate, Some(1.0));
+        assert!(refunds.failing_tasks.is_empty());
+    }
+
+    #[test]
+    fn test_rerun_of_a_faulted_run_replaces_its_failure() {
+        let at = Utc::now();
+        let mut events = vec![
+            WorkflowEvent::WorkflowStarted {
+                instance_id: "a".to_string(),
+                workflow_id: "default/orders/1.0.0".to_string(),
+                timestamp: at,
+                initial_data: serde_json::json!({}),
+                metadata: None,
+            },
+            WorkflowEvent::TaskEntered {
+                instance_id: "a".to_string(),
+                task_name: "charge".to_string(),
+                timestamp: at,
+            },
+            WorkflowEvent::WorkflowFailed {
+                instance_id: "a".to_string(),
+                error: "declined".to_string(),
+                timestamp: at,
+            },
+        ];
+        let mut rollup = WorkflowRollup::new("default/orders/1.0.0");
+        rollup.apply(&RunUpdate::Started);
+        let (workflow_id, update) = finished(&events).unwrap();
+        assert_eq!(workflow_id, "default/orders/1.0.0");
+        rollup.apply(&update);
+        let stats = WorkflowStats::from_rollup(&rollup);
+        assert_eq!((stats.runs, stats.failed, stats.completed), (1, 1, 0));
+        assert_eq!(stats.failing_tasks.first().unwrap().task_name, "charge");
+
+        events.push(WorkflowEvent::WorkflowCompleted {
+            instance_id: "a".to_string(),
+            final_data: serde_json::json!({}),
+            timestamp: at,
+            duration_ms: 40,
+        });
+        rollup.apply(&finished(&events).unwrap().1);
+        let stats = WorkflowStats::from_rollup(&rollup);
+        assert_eq!((stats.runs, stats.failed, stats.completed), (1, 0, 1));
+        assert!(stats.failing_tasks.is_empty());
+        assert_eq!(stats.duration_ms.unwrap().max, 40);
+
+        // A completed run is not counted again
+        events.push(WorkflowEvent::WorkflowCancelled {
+            instance_id: "a".to_string(),
+            reason: None,
+            timestamp: at,
+        });
+        assert!(finished(&events).is_none());
+    }
+
+    #[test]
+    fn test_stats_without_finished_runs() {
+        let stats =
+            WorkflowStats::from_instances(&[instance(InstanceState::Running, 0, Some("a"))]);
+        let stats = stats.first().unwrap();
+        assert_eq!(stats.in_flight, 1);
+        assert_eq!(stats.success_rate, None);
+        assert_eq!(stats.duration_ms, None);
+    }
+}
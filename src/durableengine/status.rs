@@ -0,0 +1,602 @@
+//! Status of workflow instances
+//!
+//! Each instance moves through the states of [`InstanceState`], and the
+//! engine records every transition with the persistence provider, rejecting
+//! the ones the state machine does not allow, such as resuming a completed
+//! instance or cancelling a faulted one. [`DurableEngine::instance_state`]
+//! reads the recorded state directly; [`DurableEngine::get_instance_status`]
+//! and [`DurableEngine::list_instances`] also fold the events persisted for
+//! an instance into an [`InstanceStatus`], so instances started by another
+//! process, or before a restart, are reported the same way as instances
+//! running in this engine. Instances without a recorded state, such as those
+//! kept by a provider that does not record states, take the state their
+//! events imply.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::persistence::{Change, InstanceState, PersistenceProvider};
+use crate::workflow::WorkflowEvent;
+
+use super::{DurableEngine, Error, Result};
+
+/// State of a workflow instance as recorded in its event history
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InstanceStatus {
+    pub instance_id: String,
+    /// The "namespace/name/version" key of the instance's workflow
+    pub workflow_id: Option<String>,
+    pub state: InstanceState,
+    /// The top-level task in flight, the task a suspended instance resumes
+    /// from, or the task a failed or cancelled instance stopped in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_task: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the latest event was recorded
+    pub updated_at: Option<DateTime<Utc>>,
+    /// When the instance completed, failed or was cancelled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Metadata recorded when the instance started, such as bundle provenance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl InstanceStatus {
+    /// Fold an instance's events, in the order they were recorded
+    #[must_use]
+    pub fn from_events(instance_id: &str, events: &[WorkflowEvent]) -> Self {
+        let mut status = Self {
+            instance_id: instance_id.to_string(),
+            workflow_id: None,
+            state: InstanceState::Running,
+            current_task: None,
+            started_at: None,
+            updated_at: None,
+            finished_at: None,
+            output: None,
+            error: None,
+            metadata: None,
+        };
+
+        for event in events {
+            status.updated_at = Some(event.timestamp());
+            match event {
+                WorkflowEvent::WorkflowStarted {
+                    workflow_id,
+                    timestamp,
+                    metadata,
+                    ..
+                } => {
+                    status.workflow_id = Some(workflow_id.clone());
+                    status.started_at.get_or_insert(*timestamp);
+                    status.metadata.clone_from(metadata);
+                }
+                WorkflowEvent::TaskEntered { task_name, .. } => {
+                    status.current_task = Some(task_name.clone());
+                }
+                WorkflowEvent::TaskCompleted { task_name, .. } => {
+                    if status.current_task.as_ref() == Some(task_name) {
+                        status.current_task = None;
+                    }
+                }
+                WorkflowEvent::WorkflowCompleted {
+                    final_data,
+                    timestamp,
+                    ..
+                } => {
+                    status.finish(InstanceState::Completed, *timestamp);
+                    status.current_task = None;
+                    status.output = Some(final_data.clone());
+                }
+                WorkflowEvent::WorkflowFailed {
+                    error, timestamp, ..
+                } => {
+                    status.finish(InstanceState::Faulted, *timestamp);
+                    status.error = Some(error.clone());
+                }
+                WorkflowEvent::WorkflowCancelled { timestamp, .. } => {
+                    status.finish(InstanceState::Cancelled, *timestamp);
+                }
+                WorkflowEvent::WorkflowSuspended { .. } => {
+                    if !status.state.is_finished() {
+                        status.state = InstanceState::Suspended;
+                    }
+                }
+                WorkflowEvent::WorkflowResumed { .. } => {
+                    if !status.state.is_finished() {
+                        status.state = InstanceState::Running;
+                    }
+                }
+                WorkflowEvent::TaskCreated { .. }
+                | WorkflowEvent::TaskStarted { .. }
+                | WorkflowEvent::TaskRetried { .. }
+                | WorkflowEvent::WorkflowCorrelationStarted { .. }
+                | WorkflowEvent::WorkflowCorrelationCompleted { .. }
+                | WorkflowEvent::TaskCancelled { .. }
+                | WorkflowEvent::TaskSuspended { .. }
+                | WorkflowEvent::TaskResumed { .. }
+                | WorkflowEvent::TaskFaulted { .. }
+                | WorkflowEvent::TaskReaped { .. }
+                | WorkflowEvent::TaskCacheHit { .. }
+                | WorkflowEvent::TaskSkipped { .. }
+                | WorkflowEvent::SwitchCaseMatched { .. }
+                | WorkflowEvent::ErrorCaught { .. }
+                | WorkflowEvent::WatchesEvaluated { .. }
+                | WorkflowEvent::ShadowCompared { .. } => {}
+            }
+        }
+
+        status
+    }
+
+    /// Record the first terminal event; a run interrupted by a cancellation
+    /// may record events after it
+    fn finish(&mut self, state: InstanceState, timestamp: DateTime<Utc>) {
+        if !self.state.is_finished() {
+            self.state = state;
+            self.finished_at = Some(timestamp);
+        }
+    }
+}
+
+/// Criteria for [`DurableEngine::list_instances`]; unset fields match every instance
+#[derive(Debug, Clone, Default)]
+pub struct InstanceFilter {
+    pub state: Option<InstanceState>,
+    pub workflow_id: Option<String>,
+    /// Only instances started at or after this time
+    pub started_after: Option<DateTime<Utc>>,
+    /// Only instances started before this time
+    pub started_before: Option<DateTime<Utc>>,
+    /// Return at most this many instances, the earliest started first
+    pub limit: Option<usize>,
+}
+
+impl InstanceFilter {
+    #[must_use]
+    pub fn with_state(mut self, state: InstanceState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    #[must_use]
+    pub fn with_workflow_id(mut self, workflow_id: impl Into<String>) -> Self {
+        self.workflow_id = Some(workflow_id.into());
+        self
+    }
+
+    /// Whether an instance meets every criterion of the filter
+    #[must_use]
+    pub fn matches(&self, status: &InstanceStatus) -> bool {
+        self.state.is_none_or(|state| status.state == state)
+            && self
+                .workflow_id
+                .as_ref()
+                .is_none_or(|id| status.workflow_id.as_ref() == Some(id))
+            && self
+                .started_after
+                .is_none_or(|after| status.started_at.is_some_and(|at| at >= after))
+            && self
+                .started_before
+                .is_none_or(|before| status.started_at.is_some_and(|at| at < before))
+    }
+}
+
+impl DurableEngine {
+    /// Get the state of a workflow instance
+    ///
+    /// Reads the state recorded by the persistence provider, without reading
+    /// the instance's events unless no state has been recorded for it.
+    ///
+    /// # Errors
+    /// Returns an error if the instance has no recorded state or events, or
+    /// they cannot be read.
+    pub async fn instance_state(&self, instance_id: &str) -> Result<InstanceState> {
+        if let Some(state) = self.persistence.get_state(instance_id).await? {
+            return Ok(state);
+        }
+        let events = self.recorded_events(instance_id).await?;
+        Ok(InstanceStatus::from_events(instance_id, &events).state)
+    }
+
+    /// Get the status of a workflow instance from its persisted events
+    ///
+    /// The status is read from the read provider, if one is configured; see
+    /// [`crate::DurableEngineBuilder::with_read_persistence`].
+    ///
+    /// # Errors
+    /// Returns an error if the instance has no recorded events, or the events
+    /// cannot be read.
+    pub async fn get_instance_status(&self, instance_id: &str) -> Result<InstanceStatus> {
+        status_in(self.reads.as_ref(), instance_id).await
+    }
+
+    /// The status of an instance as the persistence provider has it, for
+    /// decisions about a run in progress
+    pub(super) async fn live_status(&self, instance_id: &str) -> Result<InstanceStatus> {
+        status_in(self.persistence.as_ref(), instance_id).await
+    }
+
+    /// List the instances recorded by the persistence provider that match `filter`
+    ///
+    /// Instances are sorted by start time, the earliest first. Like
+    /// [`DurableEngine::get_instance_status`], the instances are read from the
+    /// read provider, if one is configured.
+    ///
+    /// # Errors
+    /// Returns an error if the instances or their events cannot be read.
+    pub async fn list_instances(&self, filter: &InstanceFilter) -> Result<Vec<InstanceStatus>> {
+        let mut instances = Vec::new();
+        for instance_id in self.reads.list_instance_ids().await? {
+            let recorded = self.reads.get_state(&instance_id).await?;
+            // The recorded state rules instances out without reading their events
+            if let (Some(wanted), Some(state)) = (filter.state, recorded)
+                && wanted != state
+            {
+                continue;
+            }
+            let events = self.reads.get_events(&instance_id).await?;
+            let mut status = InstanceStatus::from_events(&instance_id, &events);
+            if let Some(state) = recorded {
+                status.state = state;
+            }
+            if filter.matches(&status) {
+                instances.push(status);
+            }
+        }
+
+        instances.sort_by(|a, b| {
+            a.started_at
+                .cmp(&b.started_at)
+                .then_with(|| a.instance_id.cmp(&b.instance_id))
+        });
+        if let Some(limit) = filter.limit {
+            instances.truncate(limit);
+        }
+        Ok(instances)
+    }
+
+    /// Up to `limit` recorded events after offset `after`, oldest first
+    ///
+    /// Reads the change feed of the read provider, if one is configured;
+    /// `instance_id` narrows it to one instance. See
+    /// [`PersistenceProvider::changes`] for how offsets are ordered.
+    ///
+    /// # Errors
+    /// Returns an error if the provider does not keep a change feed, or it
+    /// cannot be read.
+    pub async fn changes(
+        &self,
+        after: u64,
+        instance_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        Ok(self.reads.changes(after, instance_id, limit).await?)
+    }
+
+    /// Move an instance to `next`, recording the new state
+    ///
+    /// Returns the state the instance was in, or `None` for an instance that
+    /// has not been recorded yet, which may only become pending or running.
+    ///
+    /// # Errors
+    /// Returns [`Error::IllegalTransition`] if the state machine does not
+    /// allow the transition, or an error if the state cannot be read or recorded.
+    pub(super) async fn transition(
+        &self,
+        instance_id: &str,
+        next: InstanceState,
+    ) -> Result<Option<InstanceState>> {
+        self.transition_with(instance_id, |_| next).await
+    }
+
+    /// Move an instance to the state `next` picks for its current state
+    ///
+    /// The provider only records the new state if the instance is still in
+    /// the state it was checked in, so a transition made concurrently, e.g.
+    /// by another process, is checked again.
+    pub(super) async fn transition_with(
+        &self,
+        instance_id: &str,
+        next: impl Fn(Option<InstanceState>) -> InstanceState + Send + Sync,
+    ) -> Result<Option<InstanceState>> {
+        loop {
+            let (recorded, current) = self.current_state(instance_id).await?;
+            let to = next(current);
+            match current {
+                Some(from) if !from.can_transition_to(to) => {
+                    return Err(Error::IllegalTransition {
+                        instance_id: instance_id.to_string(),
+                        from,
+                        to,
+                    });
+                }
+                None if !matches!(to, InstanceState::Pending | InstanceState::Running) => {
+                    return Err(Error::InstanceNotFound {
+                        instance_id: instance_id.to_string(),
+                    });
+                }
+                Some(_) | None => {}
+            }
+
+            if self
+                .persistence
+                .transition_state(instance_id, recorded, to)
+                .await?
+            {
+                return Ok(current);
+            }
+        }
+    }
+
+    /// Move an instance from `from` to `to`, refusing if it is in any other state
+    pub(super) async fn transition_from(
+        &self,
+        instance_id: &str,
+        from: InstanceState,
+        to: InstanceState,
+    ) -> Result<()> {
+        loop {
+            let (recorded, current) = self.current_state(instance_id).await?;
+            match current {
+                Some(state) if state == from => {}
+                Some(state) => {
+                    return Err(Error::IllegalTransition {
+                        instance_id: instance_id.to_string(),
+                        from: state,
+                        to,
+                    });
+                }
+                None => {
+                    return Err(Error::InstanceNotFound {
+                        instance_id: instance_id.to_string(),
+                    });
+                }
+            }
+
+            if self
+                .persistence
+                .transition_state(instance_id, recorded, to)
+                .await?
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The recorded state of an instance, and the state it is in: the recorded
+    /// state, or the one its events imply, or `None` if it has neither
+    async fn current_state(
+        &self,
+        instance_id: &str,
+    ) -> Result<(Option<InstanceState>, Option<InstanceState>)> {
+        if let Some(state) = self.persistence.get_state(instance_id).await? {
+            return Ok((Some(state), Some(state)));
+        }
+        let events = self.persistence.get_events(instance_id).await?;
+        let implied =
+            (!events.is_empty()).then(|| InstanceStatus::from_events(instance_id, &events).state);
+        Ok((None, implied))
+    }
+}
+
+/// The status of an instance from the events and state recorded in `persistence`
+async fn status_in(
+    persistence: &dyn PersistenceProvider,
+    instance_id: &str,
+) -> Result<InstanceStatus> {
+    let events = persistence.get_events(instance_id).await?;
+    if events.is_empty() {
+        return Err(Error::InstanceNotFound {
+            instance_id: instance_id.to_string(),
+        });
+    }
+    let mut status = InstanceStatus::from_events(instance_id, &events);
+    if let Some(state) = persistence.get_state(instance_id).await? {
+        status.state = state;
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+
+    fn started(at: DateTime<Utc>) -> WorkflowEvent {
+        WorkflowEvent::WorkflowStarted {
+            instance_id: "i".to_string(),
+            workflow_id: "default/greet/1.0.0".to_string(),
+            timestamp: at,
+            initial_data: json!({}),
+            metadata: None,
+        }
+    }
+
+    fn entered(task_name: &str, at: DateTime<Utc>) -> WorkflowEvent {
+        WorkflowEvent::TaskEntered {
+            instance_id: "i".to_string(),
+            task_name: task_name.to_string(),
+            timestamp: at,
+        }
+    }
+
+    fn completed(task_name: &str, at: DateTime<Utc>) -> WorkflowEvent {
+        WorkflowEvent::TaskCompleted {
+            instance_id: "i".to_string(),
+            task_name: task_name.to_string(),
+            result: json!({}),
+            timestamp: at,
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_running_instance_reports_task_in_flight() {
+        let t0 = Utc::now();
+        let events = vec![
+            started(t0),
+            entered("first", t0),
+            completed("first", t0 + Duration::seconds(1)),
+            entered("second", t0 + Duration::seconds(2)),
+        ];
+
+        let status = InstanceStatus::from_events("i", &events);
+        assert_eq!(status.state, InstanceState::Running);
+        assert_eq!(status.workflow_id.as_deref(), Some("default/greet/1.0.0"));
+        assert_eq!(status.current_task.as_deref(), Some("second"));
+        assert_eq!(status.started_at, Some(t0));
+        assert_eq!(status.updated_at, Some(t0 + Duration::seconds(2)));
+        assert_eq!(status.finished_at, None);
+    }
+
+    #[test]
+    fn test_completed_instance() {
+        let t0 = Utc::now();
+        let events = vec![
+            started(t0),
+            entered("only", t0),
+            completed("only", t0),
+            WorkflowEvent::WorkflowCompleted {
+                instance_id: "i".to_string(),
+                final_data: json!({ "done": true }),
+                timestamp: t0 + Duration::seconds(3),
+                duration_ms: 3000,
+            },
+        ];
+
+        let status = InstanceStatus::from_events("i", &events);
+        assert_eq!(status.state, InstanceState::Completed);
+        assert_eq!(status.current_task, None);
+        assert_eq!(status.output, Some(json!({ "done": true })));
+        assert_eq!(status.finished_at, Some(t0 + Duration::seconds(3)));
+    }
+
+    #[test]
+    fn test_suspension_and_resumption() {
+        let t0 = Utc::now();
+        let mut events = vec![
+            started(t0),
+            entered("wait", t0),
+            WorkflowEvent::WorkflowSuspended {
+                instance_id: "i".to_string(),
+                reason: None,
+                checkpoint_data: json!({}),
+                timestamp: t0,
+            },
+        ];
+        let status = InstanceStatus::from_events("i", &events);
+        assert_eq!(status.state, InstanceState::Suspended);
+        assert_eq!(status.current_task.as_deref(), Some("wait"));
+
+        events.push(WorkflowEvent::WorkflowResumed {
+            instance_id: "i".to_string(),
+            timestamp: t0,
+        });
+        let status = InstanceStatus::from_events("i", &events);
+        assert_eq!(status.state, InstanceState::Running);
+    }
+
+    #[test]
+    fn test_first_terminal_event_wins() {
+        let t0 = Utc::now();
+        let events = vec![
+            started(t0),
+            entered("wait", t0),
+            WorkflowEvent::WorkflowCancelled {
+                instance_id: "i".to_string(),
+                reason: Some("stop".to_string()),
+                timestamp: t0 + Duration::seconds(1),
+            },
+            WorkflowEvent::TaskCancelled {
+                instance_id: "i".to_string(),
+                task_name: "wait".to_string(),
+                reason: Some("stop".to_string()),
+                timestamp: t0 + Duration::seconds(2),
+            },
+            WorkflowEvent::WorkflowFailed {
+                instance_id: "i".to_string(),
+                error: "Workflow cancelled".to_string(),
+                timestamp: t0 + Duration::seconds(2),
+            },
+        ];
+
+        let status = InstanceStatus::from_events("i", &events);
+        assert_eq!(status.state, InstanceState::Cancelled);
+        assert_eq!(status.finished_at, Some(t0 + Duration::seconds(1)));
+        assert_eq!(status.current_task.as_deref(), Some("wait"));
+    }
+
+    #[test]
+    fn test_transitions() {
+        assert!(InstanceState::Pending.can_transition_to(InstanceState::Running));
+        assert!(InstanceState::Running.can_transition_to(InstanceState::Cancelling));
+        assert!(InstanceState::Cancelling.can_transition_to(InstanceState::Cancelled));
+        assert!(InstanceState::Suspended.can_transition_to(InstanceState::Running));
+        assert!(InstanceState::Faulted.can_transition_to(InstanceState::Running));
+        assert!(!InstanceState::Completed.can_transition_to(InstanceState::Running));
+        assert!(!InstanceState::Faulted.can_transition_to(InstanceState::Cancelled));
+        assert!(!InstanceState::Cancelled.can_transition_to(InstanceState::Running));
+        assert!(!InstanceState::Pending.can_transition_to(InstanceState::Suspended));
+        assert!(!InstanceState::Cancelling.can_transition_to(InstanceState::Completed));
+    }
+
+    #[test]
+    fn test_state_names_round_trip() {
+        for state in [
+            InstanceState::Pending,
+            InstanceState::Cancelling,
+            InstanceState::TimedOut,
+        ] {
+            assert_eq!(state.as_str().parse::<InstanceState>().unwrap(), state);
+            assert_eq!(serde_json::to_value(state).unwrap(), json!(state.as_str()));
+        }
+        assert!("unknown".parse::<InstanceState>().is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let t0 = Utc::now();
+        let status = InstanceStatus::from_events("i", &[started(t0)]);
+
+        assert!(InstanceFilter::default().matches(&status));
+        assert!(
+            InstanceFilter::default()
+                .with_state(InstanceState::Running)
+                .with_workflow_id("default/greet/1.0.0")
+                .matches(&status)
+        );
+        assert!(
+            !InstanceFilter::default()
+                .with_state(InstanceState::Completed)
+                .matches(&status)
+        );
+        assert!(
+            !InstanceFilter::default()
+                .with_workflow_id("default/other/1.0.0")
+                .matches(&status)
+        );
+
+        let window = InstanceFilter {
+            started_after: Some(t0),
+            started_before: Some(t0 + Duration::seconds(1)),
+            ..InstanceFilter::default()
+        };
+        assert!(window.matches(&status));
+        let later = InstanceFilter {
+            started_after: Some(t0 + Duration::seconds(1)),
+            ..InstanceFilter::default()
+        };
+        assert!(!later.matches(&status));
+    }
+}
@@ -0,0 +1,294 @@
+//! Sub-workflows found by reference, and how deep they may call each other
+//!
+//! A `run.workflow` task names the workflow it runs by namespace, name and
+//! version (or range of versions). Workflows registered with the engine are
+//! found first; any other is looked up in the engine's workflow sources
+//! (`with_workflow_source`, or `workflow_sources` in the configuration):
+//!
+//! - local directories and files, which are scanned for workflows the first
+//!   time a workflow is not registered;
+//! - git repositories (`git+https://...#branch` and the other URIs catalogs
+//!   accept), cloned into the catalog directory and scanned under `workflows/`;
+//! - HTTP servers, where a version is the `workflow.yaml` at
+//!   `workflows/{namespace}/{name}/{version}/`. Servers cannot list the
+//!   versions they publish, so ranges only match those of the other sources.
+//!
+//! A workflow found in a source is checked against the engine's signature
+//! policy and has its `${{ env.X }}` placeholders substituted, as the workflow
+//! files the CLI loads do, and is registered on first use. Each nested
+//! instance records the workflows calling it under `callChain` in the
+//! metadata of its `WorkflowStarted` event, and a call nested deeper than the
+//! maximum call depth (`with_max_call_depth`, [`DEFAULT_MAX_CALL_DEPTH`] by
+//! default) fails. Workflows may call themselves, for instance to walk a
+//! tree, so a recursive call is only an error when it goes too deep; the
+//! error then names the cycle of workflows calling each other.
+
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::OnceCell;
+
+use crate::context::Context;
+
+use super::catalog::CatalogSource;
+use super::resources::resolve_path;
+use super::{DurableEngine, Error, Result};
+
+/// Key of the workflows calling an instance in the metadata of `WorkflowStarted` events
+pub(crate) const CALL_CHAIN: &str = "callChain";
+
+/// How deep workflows may call each other unless configured otherwise
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 16;
+
+/// Where the engine looks for workflows that are not registered
+#[derive(Debug, Default)]
+pub(crate) struct WorkflowSources {
+    /// Paths and catalog URIs, in the order they are searched
+    sources: Vec<String>,
+    /// Files of the workflows of local and git sources by key, found the
+    /// first time a workflow is not registered
+    index: OnceCell<HashMap<String, PathBuf>>,
+}
+
+impl WorkflowSources {
+    pub(crate) fn new(sources: Vec<String>) -> Self {
+        Self {
+            sources,
+            index: OnceCell::new(),
+        }
+    }
+}
+
+/// The `namespace/name/version` key of a workflow
+pub(crate) fn workflow_id(workflow: &WorkflowDefinition) -> String {
+    format!(
+        "{}/{}/{}",
+        workflow.document.namespace, workflow.document.name, workflow.document.version
+    )
+}
+
+fn parse_workflow(content: &str, location: &str) -> Result<WorkflowDefinition> {
+    serde_yaml::from_str(content).map_err(|e| Error::Configuration {
+        message: format!("Failed to parse workflow {location}: {e}"),
+    })
+}
+
+/// Add the workflows of the files under `path` to `index`, keeping the
+/// first file found for a key
+fn scan(path: &Path, index: &mut HashMap<String, PathBuf>) {
+    if path.is_dir() {
+        let Ok(entries) = std::fs::read_dir(path) else {
+            tracing::warn!("Cannot read workflow source {}", path.display());
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name().is_none_or(|name| name != ".git"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            scan(&path, index);
+        }
+        return;
+    }
+    if !path
+        .extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml")
+    {
+        return;
+    }
+    let Some(key) = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_yaml::from_str::<serde_json::Value>(&content).ok())
+        .filter(|document| document.get("do").is_some())
+        .and_then(|document| {
+            let document = document.get("document")?;
+            Some(format!(
+                "{}/{}/{}",
+                document.get("namespace")?.as_str()?,
+                document.get("name")?.as_str()?,
+                document.get("version")?.as_str()?
+            ))
+        })
+    else {
+        return;
+    };
+    index.entry(key).or_insert_with(|| path.to_path_buf());
+}
+
+impl DurableEngine {
+    /// The workflow `namespace/name/version`, registered on first use if it
+    /// is found in a workflow source
+    ///
+    /// # Errors
+    /// Returns an error if no source has the workflow, or it cannot be read.
+    pub(super) async fn find_workflow(
+        &self,
+        namespace: &str,
+        name: &str,
+        version: &str,
+    ) -> Result<WorkflowDefinition> {
+        let key = format!("{namespace}/{name}/{version}");
+        if let Some(workflow) = self.workflow_registry.read().await.get(&key) {
+            return Ok(workflow.clone());
+        }
+
+        let workflow = match self.workflow_index().await?.get(&key) {
+            Some(path) => {
+                let content = self
+                    .read_definition(path, format!("workflow {}", path.display()))
+                    .await?;
+                let workflow = parse_workflow(&content, &path.display().to_string())?;
+                // Relative resources of the workflow are next to its file
+                self.set_workflow_path(&workflow, path).await;
+                Some(workflow)
+            }
+            None => self.fetch_workflow(namespace, name, version).await?,
+        };
+        let workflow = workflow
+            .filter(|workflow| workflow_id(workflow) == key)
+            .ok_or_else(|| Error::Configuration {
+                message: format!("Workflow not found in registry: {key}"),
+            })?;
+        tracing::info!("Loaded workflow {key} from the workflow sources");
+        self.register_workflow(workflow.clone()).await?;
+        Ok(workflow)
+    }
+
+    /// The versions of `namespace/name` registered or found in the local
+    /// and git workflow sources
+    ///
+    /// # Errors
+    /// Returns an error if a git source cannot be cloned.
+    pub(super) async fn workflow_versions(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Vec<String>> {
+        let prefix = format!("{namespace}/{name}/");
+        let mut versions: Vec<String> = self
+            .workflow_registry
+            .read()
+            .await
+            .keys()
+            .filter_map(|key| key.strip_prefix(&prefix))
+            .map(str::to_string)
+            .collect();
+        if !self.subworkflows.sources.is_empty() {
+            for version in self
+                .workflow_index()
+                .await?
+                .keys()
+                .filter_map(|key| key.strip_prefix(&prefix))
+            {
+                if !versions.iter().any(|known| known == version) {
+                    versions.push(version.to_string());
+                }
+            }
+        }
+        Ok(versions)
+    }
+
+    /// The workflows of the local and git sources, scanned on first use
+    async fn workflow_index(&self) -> Result<&HashMap<String, PathBuf>> {
+        self.subworkflows
+            .index
+            .get_or_try_init(|| async {
+                let mut index = HashMap::new();
+                for source in &self.subworkflows.sources {
+                    match CatalogSource::parse(source)? {
+                        CatalogSource::Dir(dir) => scan(&resolve_path(&dir, None).path, &mut index),
+                        CatalogSource::Git { url, reference } => {
+                            let checkout = self.clone_catalog(&url, reference.as_deref()).await?;
+                            scan(&checkout.join("workflows"), &mut index);
+                        }
+                        CatalogSource::Http(_) => {}
+                    }
+                }
+                Ok(index)
+            })
+            .await
+    }
+
+    /// Fetch a workflow from the first HTTP source that publishes it
+    async fn fetch_workflow(
+        &self,
+        namespace: &str,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<WorkflowDefinition>> {
+        for source in &self.subworkflows.sources {
+            let CatalogSource::Http(base) = CatalogSource::parse(source)? else {
+                continue;
+            };
+            let url = format!("{base}/workflows/{namespace}/{name}/{version}/workflow.yaml");
+            let Some(content) = self.fetch_definition(&url, "workflow").await? else {
+                continue;
+            };
+            return parse_workflow(&content, &url).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// The workflows calling `callee` when the instance of `ctx` calls it
+    ///
+    /// # Errors
+    /// Returns an error if the call is nested deeper than the maximum call depth.
+    pub(super) fn call_chain(
+        &self,
+        ctx: &Context,
+        callee: &WorkflowDefinition,
+    ) -> Result<Vec<String>> {
+        let mut chain = ctx.metadata.call_chain.to_vec();
+        chain.push(workflow_id(&ctx.metadata.workflow));
+        if chain.len() <= self.max_call_depth {
+            return Ok(chain);
+        }
+
+        let callee = workflow_id(callee);
+        let message = match chain
+            .iter()
+            .rposition(|caller| *caller == callee)
+            .and_then(|start| chain.get(start..))
+        {
+            Some(cycle) => format!(
+                "Recursive workflow call {} -> {callee} exceeds the maximum call depth of {}",
+                cycle.join(" -> "),
+                self.max_call_depth
+            ),
+            None => format!(
+                "Workflow call {} -> {callee} exceeds the maximum call depth of {}",
+                chain.join(" -> "),
+                self.max_call_depth
+            ),
+        };
+        Err(Error::WorkflowExecution { message })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_scan_indexes_workflows_by_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("billing");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(
+            nested.join("invoice.yaml"),
+            "document:\n  dsl: '1.0.2'\n  namespace: billing\n  name: invoice\n  version: '1.2.0'\ndo:\n  - done:\n      set:\n        done: true\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("values.yaml"), "replicas: 3\n").unwrap();
+
+        let mut index = HashMap::new();
+        scan(dir.path(), &mut index);
+        assert_eq!(
+            index.keys().collect::<Vec<_>>(),
+            vec!["billing/invoice/1.2.0"]
+        );
+    }
+}
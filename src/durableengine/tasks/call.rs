@@ -1,9 +1,14 @@
 use chrono::Utc;
 use std::collections::HashMap;
+use tracing::Instrument;
 
-use crate::cache::{CacheEntry, compute_cache_key};
+use crate::cache::CacheEntry;
 use crate::context::Context;
+use crate::executor::Executor;
 use crate::output;
+use crate::profile;
+use crate::providers::executors::disabled_feature;
+use crate::workflow::WorkflowEvent;
 
 use super::super::{DurableEngine, Result};
 
@@ -17,9 +22,10 @@ pub async fn exec_call_task(
     let with_params = call_task.with.clone().unwrap_or_default();
 
     // Evaluate expressions in with parameters
-    let current_data = ctx.state.data.read().await.clone();
+    let current_data = ctx.data().await;
     let evaluated_with_params_value = crate::expressions::evaluate_value_with_input(
-        &serde_json::to_value(&with_params)?,
+        ctx.metadata.evaluation,
+        &crate::ordering::to_canonical_value(&with_params)?,
         &current_data,
         &ctx.metadata.initial_input,
     )?;
@@ -29,23 +35,35 @@ pub async fn exec_call_task(
         serde_json::from_value(evaluated_with_params_value.clone())?;
 
     let params = evaluated_with_params_value.clone();
-    let cache_key = compute_cache_key(task_name, &params);
-
-    if let Some(cached) = ctx.services.cache.get(&cache_key).await? {
-        output::format_cache_hit(
-            task_name,
-            &cache_key,
-            Some(&cached.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()),
-        );
-        return Ok(cached.output);
-    }
+    let cache_key = super::cache_key(task_name, call_task, &params, &current_data, ctx)?;
+
+    if let Some(cache_key) = &cache_key {
+        if let Some(cached) = ctx.services.cache.get(cache_key).await? {
+            output::format_cache_hit(
+                task_name,
+                cache_key,
+                Some(&cached.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()),
+            );
+            ctx.services
+                .persistence
+                .save_event(WorkflowEvent::TaskCacheHit {
+                    instance_id: ctx.metadata.instance_id.clone(),
+                    task_name: task_name.to_string(),
+                    cache_key: cache_key.clone(),
+                    cached_at: cached.timestamp,
+                    timestamp: Utc::now(),
+                })
+                .await?;
+            return Ok(cached.output);
+        }
 
-    output::format_cache_miss(task_name, &cache_key);
+        output::format_cache_miss(task_name, cache_key);
+    }
 
     // Note: TaskStarted event is now emitted centrally in exec_task()
 
     // Resolve the function definition from workflow.use_.functions
-    // If not found, check catalogs
+    // If not found, check catalogs, then the registered Rust functions
     // If still not found, assume it's a built-in protocol (http, grpc, etc.)
     let function_name = &call_task.call;
 
@@ -86,28 +104,50 @@ pub async fn exec_call_task(
 
             let executor = engine.executors.get(call_type.as_str()).ok_or(
                 super::super::Error::TaskExecution {
-                    message: format!("No executor for call type: {call_type}"),
+                    message: no_executor_message(call_type),
                 },
             )?;
 
-            let final_params = serde_json::to_value(&merged_params)?;
-            executor.exec(task_name, &final_params, ctx, None).await?
+            let mut final_params = crate::ordering::to_canonical_value(&merged_params)?;
+            resolve_call_resources(engine, call_type, &mut final_params, ctx).await;
+            executor
+                .exec(task_name, &final_params, ctx, None, &ctx.state.cancellation)
+                .instrument(profile::executor_span(call_type))
+                .await?
         } else if let Some(catalog_result) = engine
             .try_load_catalog_function(function_name, &evaluated_with_params, ctx)
             .await?
         {
             // Catalog function - execute as nested workflow
             catalog_result
+        } else if !engine.executors.contains_key(function_name.as_str())
+            && let Some(native) = engine
+                .native_functions()
+                .filter(|native| native.contains(function_name))
+        {
+            // Rust function registered by the embedding application
+            let arguments = (!evaluated_with_params.is_empty())
+                .then(|| crate::ordering::to_canonical_value(&evaluated_with_params))
+                .transpose()?;
+            let params = serde_json::json!({ "function": function_name, "arguments": arguments });
+            native
+                .exec(task_name, &params, ctx, None, &ctx.state.cancellation)
+                .instrument(profile::executor_span("native"))
+                .await?
         } else {
             // Built-in protocol
             let executor = engine.executors.get(function_name.as_str()).ok_or(
                 super::super::Error::TaskExecution {
-                    message: format!("No executor for call type: {function_name}"),
+                    message: no_executor_message(function_name),
                 },
             )?;
 
-            let final_params = serde_json::to_value(&evaluated_with_params)?;
-            executor.exec(task_name, &final_params, ctx, None).await?
+            let mut final_params = crate::ordering::to_canonical_value(&evaluated_with_params)?;
+            resolve_call_resources(engine, function_name, &mut final_params, ctx).await;
+            executor
+                .exec(task_name, &final_params, ctx, None, &ctx.state.cancellation)
+                .instrument(profile::executor_span(function_name))
+                .await?
         };
 
     let mut result = function_result;
@@ -116,7 +156,7 @@ pub async fn exec_call_task(
     if let Some(output_config) = &call_task.common.output
         && let Some(as_value) = &output_config.as_
     {
-        let task_input = ctx.state.task_input.read().await.clone();
+        let task_input = ctx.task_input().await;
 
         // Handle both string (expression) and object (field mapping) forms
         if let Some(expr_str) = as_value.as_str() {
@@ -133,6 +173,7 @@ pub async fn exec_call_task(
             for (key, value) in as_obj {
                 if let Some(expr_str) = value.as_str() {
                     let evaluated = crate::expressions::evaluate_expression_with_input(
+                        ctx.metadata.evaluation,
                         expr_str,
                         &result,
                         &task_input,
@@ -144,13 +185,63 @@ pub async fn exec_call_task(
         }
     }
 
-    let cache_entry = CacheEntry {
-        key: cache_key.clone(),
-        inputs: params,
-        output: result.clone(),
-        timestamp: Utc::now(),
-    };
-    ctx.services.cache.set(cache_entry).await?;
+    if let Some(key) = cache_key {
+        let cache_entry = CacheEntry {
+            key,
+            inputs: params,
+            output: result.clone(),
+            timestamp: Utc::now(),
+            expires_at: super::cache_expiry(engine, call_task)?,
+        };
+        ctx.services.cache.set(cache_entry).await?;
+    }
 
     Ok(result)
 }
+
+/// Why no executor handles `call_type`, naming the cargo feature that
+/// provides one if this build was compiled without it
+fn no_executor_message(call_type: &str) -> String {
+    match disabled_feature(call_type) {
+        Some(feature) => format!(
+            "No executor for call type: {call_type} (jackdaw was built without the `{feature}` feature)"
+        ),
+        None => format!("No executor for call type: {call_type}"),
+    }
+}
+
+/// Resolve local files referenced by a call's parameters against the workflow file
+///
+/// Executors only see the call parameters, so relative paths such as a gRPC
+/// proto file, an AsyncAPI document or a WebAssembly module are rewritten here, where the
+/// workflow's location is known.
+async fn resolve_call_resources(
+    engine: &DurableEngine,
+    call_type: &str,
+    params: &mut serde_json::Value,
+    ctx: &Context,
+) {
+    let pointer = match call_type {
+        "grpc" => "/proto/endpoint",
+        "asyncapi" => "/document/endpoint",
+        "wasm" => "/module/endpoint",
+        _ => return,
+    };
+    let Some(endpoint) = params.pointer_mut(pointer) else {
+        return;
+    };
+    let target = match endpoint {
+        serde_json::Value::Object(endpoint) => endpoint.get_mut("uri"),
+        serde_json::Value::Null
+        | serde_json::Value::Bool(_)
+        | serde_json::Value::Number(_)
+        | serde_json::Value::String(_)
+        | serde_json::Value::Array(_) => Some(endpoint),
+    };
+    if let Some(serde_json::Value::String(uri)) = target
+        && (uri.starts_with("file://") || !uri.contains("://"))
+    {
+        let resolved = engine.resolve_resource(&ctx.metadata.workflow, uri).await;
+        *uri = resolved.path.to_string_lossy().into_owned();
+    }
+}
@@ -1,49 +1,54 @@
-use chrono::Utc;
-
 use crate::context::Context;
+use crate::events::{CloudEvent, InboundEvent};
 
 use super::super::{DurableEngine, Result};
 
-/// Execute an Emit task - emits ``CloudEvents`` to the workflow context
+/// Execute an Emit task - publishes a ``CloudEvent`` to the engine's event sinks
+///
+/// The event's attributes are evaluated against the current context and
+/// wrapped in a CloudEvents 1.0 envelope. Events without a `source` are
+/// attributed to the workflow. The event is also delivered to the engine's
+/// running listen tasks.
 pub async fn exec_emit_task(
-    _engine: &DurableEngine,
+    engine: &DurableEngine,
     _task_name: &str,
     emit_task: &serverless_workflow_core::models::task::EmitTaskDefinition,
     ctx: &Context,
 ) -> Result<serde_json::Value> {
     // Get current context data for expression evaluation
-    let current_data = ctx.state.data.read().await.clone();
-
-    // Evaluate the event attributes
-    let mut event_data = serde_json::Map::new();
-
-    // CloudEvents standard fields
-    // Generate a unique ID for the event
-    event_data.insert(
-        "id".to_string(),
-        serde_json::json!(uuid::Uuid::new_v4().to_string()),
-    );
-
-    // CloudEvents spec version
-    event_data.insert("specversion".to_string(), serde_json::json!("1.0"));
-
-    // Add timestamp
-    event_data.insert(
-        "time".to_string(),
-        serde_json::json!(Utc::now().to_rfc3339()),
-    );
+    let current_data = ctx.data().await;
 
     // Process the 'with' attributes from the event definition
-    for (key, value) in &emit_task.emit.event.with {
+    let mut attributes = serde_json::Map::new();
+    for (key, value) in crate::ordering::in_key_order(&emit_task.emit.event.with) {
         let evaluated_value = crate::expressions::evaluate_value_with_input(
+            ctx.metadata.evaluation,
             value,
             &current_data,
             &ctx.metadata.initial_input,
         )?;
-        event_data.insert(key.clone(), evaluated_value);
+        attributes.insert(key.clone(), evaluated_value);
+    }
+
+    let document = &ctx.metadata.workflow.document;
+    let default_source = format!(
+        "/{}/{}/{}",
+        document.namespace, document.name, document.version
+    );
+    let event = CloudEvent::from_attributes(attributes, &default_source)?;
+
+    for sink in engine.event_sinks.iter() {
+        sink.publish(&event).await?;
     }
 
-    let result = serde_json::Value::Object(event_data);
+    let result = serde_json::to_value(&event)?;
+
+    // Listen tasks of running instances may be waiting for the event; it is
+    // fine if none are
+    let _ = engine.event_bus.send(InboundEvent {
+        event: result.clone(),
+        listener: None,
+    });
 
     // Merge each field of the event into the context (not nested under task name)
     if let serde_json::Value::Object(map) = &result {
@@ -1,26 +1,59 @@
-use crate::context::Context;
+use futures::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use serverless_workflow_core::models::task::ForTaskDefinition;
+
+use crate::context::{Context, MergeStrategy, field_pointer};
 
 use super::super::{DurableEngine, Error, Result};
 
+/// Number of iterations run at once by parallel loops that set no limit, when
+/// the engine sets none either
+pub(crate) const DEFAULT_FOR_PARALLELISM: usize = 4;
+
+/// How a loop runs its iterations, read from `metadata.for`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ForOptions {
+    /// Run iterations concurrently, or one after another when `false`
+    parallel: Option<bool>,
+    /// Maximum number of iterations running at once; implies `parallel`
+    max_parallelism: Option<usize>,
+}
+
 /// Execute a For task - iterates over a collection and executes tasks for each item
+///
+/// Iterations run one after another unless `metadata.for` asks for the loop
+/// to run in parallel.
 pub async fn exec_for_task(
     engine: &DurableEngine,
     _task_name: &str,
-    for_task: &serverless_workflow_core::models::task::ForTaskDefinition,
+    for_task: &ForTaskDefinition,
     ctx: &Context,
 ) -> Result<serde_json::Value> {
     // Get current context data
-    let current_data = ctx.state.data.read().await.clone();
+    let current_data = ctx.data().await;
 
     // Evaluate the 'in' expression to get the collection to iterate over
     let collection_expr = &for_task.for_.in_;
-    let collection = crate::expressions::evaluate_jq(collection_expr, &current_data)?;
+    let collection = if collection_expr.trim().starts_with("${") {
+        crate::expressions::evaluate_expression(
+            ctx.metadata.evaluation,
+            collection_expr,
+            &current_data,
+        )?
+    } else {
+        crate::expressions::evaluate_jq(collection_expr, &current_data)?
+    };
 
     // Get the collection as an array
     let items = collection.as_array().ok_or(Error::TaskExecution {
         message: format!("For loop 'in' expression must evaluate to an array, got: {collection:?}"),
     })?;
 
+    if let Some(workers) = parallelism(engine, for_task)? {
+        return exec_parallel(engine, for_task, items, workers, ctx).await;
+    }
+
     // Get the iteration variable name (e.g., "color")
     let item_var = &for_task.for_.each;
 
@@ -31,48 +64,129 @@ pub async fn exec_for_task(
 
     // Iterate over the collection
     for (index, item) in items.iter().enumerate() {
-        // Get current accumulated state (includes updates from previous iterations)
-        let accumulated_data = ctx.state.data.read().await.clone();
+        // Inject iteration variables into the current state, which includes
+        // updates from previous iterations
+        if ctx.view_data().await.is_object() {
+            // Store the item and index as variables (without $ prefix, jq will handle $ reference)
+            let mut variables = serde_json::Map::new();
+            variables.insert(item_var.clone(), item.clone());
+            variables.insert(index_var.to_string(), serde_json::json!(index));
+            ctx.merge_data(variables.into(), MergeStrategy::Shallow)
+                .await;
+        }
+
+        // Execute the do tasks for this iteration
+        last_result = exec_iteration(engine, for_task, ctx).await?;
+
+        // Remove iteration variables but keep accumulated changes
+        ctx.remove(&field_pointer(item_var)).await?;
+        ctx.remove(&field_pointer(index_var)).await?;
+    }
+
+    // For task returns the last subtask's result
+    Ok(last_result)
+}
+
+/// Run the iterations of a loop concurrently, at most `workers` at once
+///
+/// Each iteration works on its own copy of the data, so changes made by one
+/// are not seen by the others or kept after the loop. Like a loop run in
+/// order, the loop returns the result of the last iteration of the collection,
+/// whichever finishes last. When an iteration fails, the iterations still
+/// running are cancelled.
+async fn exec_parallel(
+    engine: &DurableEngine,
+    for_task: &ForTaskDefinition,
+    items: &[serde_json::Value],
+    workers: usize,
+    ctx: &Context,
+) -> Result<serde_json::Value> {
+    let item_var = &for_task.for_.each;
+    let index_var = for_task.for_.at.as_deref().unwrap_or("index");
+    let data = ctx.data().await;
+    let task_input = ctx.task_input().await;
+
+    // Cancelled with the instance, or when an iteration fails
+    let loop_ctx = ctx.with_child_cancellation();
 
-        // Inject iteration variables into the current state
-        let mut iteration_data = accumulated_data;
+    let iterations = items.iter().enumerate().map(|(index, item)| {
+        let mut iteration_data = data.clone();
         if let Some(obj) = iteration_data.as_object_mut() {
-            // Store the item and index as variables (without $ prefix, jq will handle $ reference)
             obj.insert(item_var.clone(), item.clone());
             obj.insert(index_var.to_string(), serde_json::json!(index));
         }
-
-        // Update context with iteration variables
-        {
-            let mut data_guard = ctx.state.data.write().await;
-            *data_guard = iteration_data;
+        let iteration_ctx = loop_ctx.with_own_data(iteration_data, task_input.clone());
+        async move {
+            let result = exec_iteration(engine, for_task, &iteration_ctx).await?;
+            Ok::<_, Error>((index, result))
         }
+    });
+
+    // Unordered, so a failure is seen as soon as it happens
+    let results: Result<Vec<(usize, serde_json::Value)>> = futures::stream::iter(iterations)
+        .buffer_unordered(workers)
+        .try_collect()
+        .await;
+    let results = match results {
+        Ok(results) => results,
+        Err(e) => {
+            // Stop what the iterations still running had started
+            loop_ctx.state.cancellation.cancel();
+            return Err(e);
+        }
+    };
+    Ok(results
+        .into_iter()
+        .max_by_key(|(index, _)| *index)
+        .map_or(serde_json::Value::Null, |(_, result)| result))
+}
 
-        // Execute the do tasks for this iteration
-        for entry in &for_task.do_.entries {
-            for (subtask_name, subtask) in entry {
-                let result = Box::pin(engine.exec_task(subtask_name, subtask, ctx)).await?;
+/// Run the `do` tasks of one iteration, returning the last task's result
+async fn exec_iteration(
+    engine: &DurableEngine,
+    for_task: &ForTaskDefinition,
+    ctx: &Context,
+) -> Result<serde_json::Value> {
+    let mut last_result = serde_json::Value::Null;
+    for entry in &for_task.do_.entries {
+        for (subtask_name, subtask) in entry {
+            let result = Box::pin(engine.exec_task(subtask_name, subtask, ctx)).await?;
 
-                // Update task_input for the next subtask
-                *ctx.state.task_input.write().await = result.clone();
+            // Update task_input for the next subtask
+            ctx.set_task_input(result.clone()).await;
 
-                // Handle export.as for subtasks (same logic as main execution loop)
-                super::super::export::apply_export_to_context(subtask, &result, ctx).await?;
+            // Handle export.as for subtasks (same logic as main execution loop)
+            super::super::export::apply_export_to_context(subtask, &result, ctx).await?;
 
-                last_result = result;
-            }
+            last_result = result;
         }
+    }
+    Ok(last_result)
+}
 
-        // Remove iteration variables but keep accumulated changes
-        {
-            let mut data_guard = ctx.state.data.write().await;
-            if let Some(obj) = data_guard.as_object_mut() {
-                obj.remove(item_var);
-                obj.remove(index_var);
-            }
+/// How many iterations of the loop run at once, or `None` to run them in order
+fn parallelism(engine: &DurableEngine, for_task: &ForTaskDefinition) -> Result<Option<usize>> {
+    let task = serde_json::to_value(for_task)?;
+    let options: ForOptions = match task.pointer("/metadata/for") {
+        Some(options) => {
+            serde_json::from_value(options.clone()).map_err(|e| Error::Configuration {
+                message: format!("Invalid metadata.for: {e}"),
+            })?
         }
-    }
+        None => ForOptions::default(),
+    };
 
-    // For task returns the last subtask's result
-    Ok(last_result)
+    if options.max_parallelism == Some(0) {
+        return Err(Error::Configuration {
+            message: "metadata.for.maxParallelism must be at least 1".to_string(),
+        });
+    }
+    Ok(match (options.parallel, options.max_parallelism) {
+        (Some(false), _) => None,
+        (Some(true) | None, Some(workers)) => Some(workers),
+        (Some(true), None) => Some(engine.for_parallelism.unwrap_or(DEFAULT_FOR_PARALLELISM)),
+        // The iterations of a parallel loop do not see each other's data
+        // changes, so only loops that ask for it run in parallel
+        (None, None) => None,
+    })
 }
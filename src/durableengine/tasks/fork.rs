@@ -1,44 +1,94 @@
+use serde::Deserialize;
+use serverless_workflow_core::models::task::{ForkTaskDefinition, TaskDefinition};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Semaphore;
 
 use crate::context::Context;
+use crate::expressions::BRANCHES_KEY;
 
 use super::super::{DurableEngine, Error, Result};
 
+/// How a fork runs its branches, read from `metadata.fork`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ForkOptions {
+    /// Maximum number of branches running at once
+    max_parallelism: Option<usize>,
+    /// Priority of branches by name; branches with a higher one start first
+    #[serde(default)]
+    priorities: HashMap<String, i64>,
+    /// Order of the fields of the fork's output
+    #[serde(default)]
+    order: BranchOrder,
+}
+
+/// Order of the fields of a fork's output, one per branch
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BranchOrder {
+    /// The order the branches are declared in
+    #[default]
+    Declared,
+    /// The order the branches start in: by priority, then as declared
+    Priority,
+    /// The order the branches complete in
+    Completion,
+}
+
+/// A branch of a fork, with its position among the declared branches
+struct Branch<'a> {
+    index: usize,
+    name: &'a String,
+    task: &'a TaskDefinition,
+}
+
 /// Execute a Fork task - parallel execution of branches with optional compete mode
+///
+/// Branches start together unless `metadata.fork.maxParallelism` or the
+/// engine's fork limit bounds how many run at once, in which case those
+/// with a higher priority in `metadata.fork.priorities` start first. The
+/// results of the branches are kept for later tasks as `$branches`.
 pub async fn exec_fork_task(
     engine: &DurableEngine,
     _task_name: &str,
-    fork_task: &serverless_workflow_core::models::task::ForkTaskDefinition,
+    fork_task: &ForkTaskDefinition,
     ctx: &Context,
 ) -> Result<serde_json::Value> {
+    let options = fork_options(fork_task)?;
+    let branches = start_order(fork_task, &options)?;
+    let limit = options.max_parallelism.map(Semaphore::new);
+    let limit = limit.as_ref();
+
     // Check if compete mode is enabled - use different future types
     if fork_task.fork.compete {
         // In compete mode, use boxed futures for select_all (requires Unpin)
         let mut branch_futures = Vec::new();
         let engine = Arc::new(engine);
 
-        let mut branch_index = 0;
-        for entry in &fork_task.fork.branches.entries {
-            for (branch_name, branch_task) in entry {
-                let branch_name = branch_name.clone();
-                let branch_task = branch_task.clone();
-                let mut ctx = ctx.clone();
-                ctx.state.task_index = Some(branch_index);
-                let engine = Arc::clone(&engine);
-
-                let future = Box::pin(async move {
-                    let result = engine.exec_task(&branch_name, &branch_task, &ctx).await?;
-                    Ok::<_, Error>((branch_name, result))
-                });
-                branch_futures.push(future);
-                branch_index += 1;
-            }
+        for branch in &branches {
+            let branch_name = branch.name.clone();
+            let branch_task = branch.task.clone();
+            let mut ctx = ctx.clone();
+            ctx.state.task_index = Some(branch.index);
+            let engine = Arc::clone(&engine);
+
+            let future = Box::pin(async move {
+                let result = run_branch(&engine, limit, &branch_name, &branch_task, ctx).await?;
+                Ok::<_, Error>((branch_name, result))
+            });
+            branch_futures.push(future);
         }
 
         if !branch_futures.is_empty() {
+            // Branches done at the same time go to the one started first
             let (result, _index, _remaining) = futures::future::select_all(branch_futures).await;
-            let (_branch_name, branch_result) = result?;
+            let (branch_name, branch_result) = result?;
+            let mut winner = serde_json::Map::new();
+            winner.insert(branch_name, branch_result.clone());
+            ctx.inject(BRANCHES_KEY, serde_json::Value::Object(winner))
+                .await;
             // In compete mode, return only the winning branch's result
             return Ok(branch_result);
         }
@@ -48,34 +98,134 @@ pub async fn exec_fork_task(
     } else {
         // In normal mode, plain futures work fine with join_all
         let mut branch_futures = Vec::new();
-        let mut results = HashMap::new();
         let engine = Arc::new(engine);
+        let completed = Arc::new(AtomicUsize::new(0));
+        let order = options.order;
+
+        for (start, branch) in branches.iter().enumerate() {
+            let branch_name = branch.name.clone();
+            let branch_task = branch.task.clone();
+            let index = branch.index;
+            let mut ctx = ctx.clone();
+            ctx.state.task_index = Some(index);
+            let engine = Arc::clone(&engine);
+            let completed = Arc::clone(&completed);
 
-        let mut branch_index = 0;
-        for entry in &fork_task.fork.branches.entries {
-            for (branch_name, branch_task) in entry {
-                let branch_name = branch_name.clone();
-                let branch_task = branch_task.clone();
-                let mut ctx = ctx.clone();
-                ctx.state.task_index = Some(branch_index);
-                let engine = Arc::clone(&engine);
-
-                let future = async move {
-                    let result = engine.exec_task(&branch_name, &branch_task, &ctx).await?;
-                    Ok::<_, Error>((branch_name, result))
+            let future = async move {
+                let result = run_branch(&engine, limit, &branch_name, &branch_task, ctx).await;
+                let position = match order {
+                    BranchOrder::Declared => index,
+                    BranchOrder::Priority => start,
+                    BranchOrder::Completion => completed.fetch_add(1, Ordering::SeqCst),
                 };
-                branch_futures.push(future);
-                branch_index += 1;
-            }
+                (index, position, branch_name, result)
+            };
+            branch_futures.push(future);
         }
 
-        let branch_results = futures::future::join_all(branch_futures).await;
+        let mut branch_results = futures::future::join_all(branch_futures).await;
 
-        for result in branch_results {
-            let (branch_name, branch_result) = result?;
-            results.insert(branch_name, branch_result);
+        // The first failed branch, as declared, fails the fork
+        branch_results.sort_by_key(|(index, ..)| *index);
+        let mut outputs = Vec::with_capacity(branch_results.len());
+        for (_index, position, branch_name, result) in branch_results {
+            outputs.push((position, branch_name, result?));
         }
+        outputs.sort_by_key(|(position, ..)| *position);
+
+        // One field per branch, in the order `metadata.fork.order` selects
+        let results: serde_json::Map<_, _> = outputs
+            .into_iter()
+            .map(|(_position, branch_name, result)| (branch_name, result))
+            .collect();
+        ctx.inject(BRANCHES_KEY, serde_json::Value::Object(results.clone()))
+            .await;
 
-        Ok(serde_json::to_value(&results)?)
+        Ok(serde_json::Value::Object(results))
     }
 }
+
+/// Run a branch once it may start
+///
+/// The branch waits for a permit of the fork's own limit, then for one of the
+/// engine's, unless an enclosing branch already holds one.
+async fn run_branch(
+    engine: &DurableEngine,
+    limit: Option<&Semaphore>,
+    branch_name: &str,
+    branch_task: &TaskDefinition,
+    mut ctx: Context,
+) -> Result<serde_json::Value> {
+    // The semaphores are never closed, so acquiring a permit cannot fail
+    let _fork_permit = match limit {
+        Some(limit) => limit.acquire().await.ok(),
+        None => None,
+    };
+    let _engine_permit = match &engine.fork_permits {
+        Some(permits) if !ctx.state.holds_fork_permit => {
+            ctx.state.holds_fork_permit = true;
+            permits.acquire().await.ok()
+        }
+        Some(_) | None => None,
+    };
+    engine.exec_task(branch_name, branch_task, &ctx).await
+}
+
+/// How the fork runs its branches, from `metadata.fork`
+fn fork_options(fork_task: &ForkTaskDefinition) -> Result<ForkOptions> {
+    let task = serde_json::to_value(fork_task)?;
+    let Some(options) = task.pointer("/metadata/fork") else {
+        return Ok(ForkOptions::default());
+    };
+    if let Some(limit) = options.get("maxParallelism")
+        && limit.as_u64().is_none_or(|limit| limit == 0)
+    {
+        return Err(Error::Configuration {
+            message: format!(
+                "metadata.fork.maxParallelism must be a positive integer, got {limit}"
+            ),
+        });
+    }
+    serde_json::from_value(options.clone()).map_err(|e| Error::Configuration {
+        message: format!("Invalid metadata.fork: {e}"),
+    })
+}
+
+/// The branches of the fork in the order they start: by priority, highest
+/// first, then in the order they are declared
+fn start_order<'a>(
+    fork_task: &'a ForkTaskDefinition,
+    options: &ForkOptions,
+) -> Result<Vec<Branch<'a>>> {
+    let mut branches: Vec<Branch<'a>> = fork_task
+        .fork
+        .branches
+        .entries
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, (name, task))| Branch { index, name, task })
+        .collect();
+
+    let mut unknown: Vec<&str> = options
+        .priorities
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !branches.iter().any(|branch| branch.name == name))
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort();
+        return Err(Error::Configuration {
+            message: format!(
+                "metadata.fork.priorities names branches the fork does not have: {}",
+                unknown.join(", ")
+            ),
+        });
+    }
+
+    // A stable sort keeps branches of the same priority in declaration order
+    branches.sort_by_key(|branch| {
+        std::cmp::Reverse(options.priorities.get(branch.name).copied().unwrap_or(0))
+    });
+    Ok(branches)
+}
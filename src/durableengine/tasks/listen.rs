@@ -0,0 +1,360 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::context::Context;
+use crate::events::{InboundEvent, ReadMode};
+use crate::expressions::Evaluation;
+
+use super::super::{DurableEngine, Error, Result};
+
+/// Execute a Listen task - consumes events according to its `listen.to` strategy
+///
+/// Events are those received by the workflow's listeners, emitted by `emit`
+/// tasks, or published with [`DurableEngine::publish_event`] while the task
+/// runs. The task completes when its strategy is satisfied:
+///
+/// - `one`: the first event matching the filter
+/// - `all`: one event matching each filter, in any order
+/// - `any`: the first event matching any filter (every event if there are no
+///   filters), or, with `until`, every matching event until the condition holds
+///
+/// `until` is either a runtime expression, evaluated against the array of
+/// events consumed so far after each event, or another strategy, whose events
+/// end the task without being consumed. The task returns the consumed events,
+/// shaped by `listen.read` (see [`ReadMode`]): `data` returns only the
+/// events' data, `envelope` (the default) the whole events and `raw` the
+/// events as they arrived.
+pub async fn exec_listen_task(
+    engine: &DurableEngine,
+    task_name: &str,
+    listen_task: &serverless_workflow_core::models::task::ListenTaskDefinition,
+    ctx: &Context,
+) -> Result<Value> {
+    let strategy: Strategy = serde_json::from_value(serde_json::to_value(&listen_task.listen.to)?)
+        .map_err(|e| Error::Configuration {
+            message: format!("Task {task_name}: invalid listen.to: {e}"),
+        })?;
+    let evaluation = ctx.metadata.evaluation;
+    let mut consumer = Consumer::new(&strategy, task_name, evaluation)?;
+    let mut until = match strategy.until.as_deref() {
+        None => Until::None,
+        Some(UntilDefinition::Expression(expr)) => Until::Expression(expr.clone()),
+        Some(UntilDefinition::Strategy(until)) => {
+            Until::Strategy(Box::new(Consumer::new(until, task_name, evaluation)?))
+        }
+    };
+    let read_mode =
+        ReadMode::parse(listen_task.listen.read.as_deref()).map_err(|e| Error::Configuration {
+            message: format!("Task {task_name}: {e}"),
+        })?;
+
+    let mut events = engine.event_bus.subscribe();
+    let mut consumed = Vec::new();
+    loop {
+        let inbound = tokio::select! {
+            received = events.recv() => match received {
+                Ok(inbound) => inbound,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Listen task {task_name} missed {missed} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => {
+                    return Err(Error::TaskExecution {
+                        message: format!("Listen task {task_name}: event bus closed"),
+                    });
+                }
+            },
+            () = ctx.state.cancellation.cancelled() => {
+                return Err(Error::TaskExecution {
+                    message: format!("Listen task {task_name} was cancelled"),
+                });
+            }
+        };
+
+        if let Until::Strategy(until) = &mut until
+            && until.offer(&inbound, task_name)
+        {
+            if until.is_complete() {
+                break;
+            }
+            continue;
+        }
+
+        if !consumer.offer(&inbound, task_name) {
+            continue;
+        }
+        consumed.push(read_mode.read(inbound.event));
+
+        let done = match (&consumer.mode, &until) {
+            (Mode::One | Mode::All, _) | (Mode::Any, Until::None) => consumer.is_complete(),
+            (Mode::Any, Until::Expression(expr)) => {
+                let events = Value::Array(consumed.clone());
+                crate::expressions::evaluate_expression(evaluation, expr, &events)?.as_bool()
+                    == Some(true)
+            }
+            (Mode::Any, Until::Strategy(_)) => false,
+        };
+        if done {
+            break;
+        }
+    }
+
+    Ok(Value::Array(consumed))
+}
+
+/// The `listen.to` definition, read from its serialized form
+#[derive(Debug, Default, Deserialize)]
+struct Strategy {
+    all: Option<Vec<Filter>>,
+    any: Option<Vec<Filter>>,
+    one: Option<Filter>,
+    until: Option<Box<UntilDefinition>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum UntilDefinition {
+    Expression(String),
+    Strategy(Strategy),
+}
+
+/// Attributes an event must have to be consumed
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Filter {
+    #[serde(default)]
+    with: Map<String, Value>,
+}
+
+impl Filter {
+    /// Whether an event has every attribute of the filter
+    ///
+    /// String values are runtime expressions, evaluated against the event's
+    /// attribute and matching if they yield `true`, or regular expressions the
+    /// whole attribute must match. A `source` object configures the listener
+    /// the task receives events from, and matches the events it received.
+    fn matches(&self, inbound: &InboundEvent, task_name: &str, evaluation: Evaluation) -> bool {
+        self.with.iter().all(|(attribute, expected)| {
+            if attribute == "source" && expected.is_object() {
+                return inbound.listener.as_deref() == Some(task_name);
+            }
+            let Some(actual) = inbound.event.get(attribute) else {
+                return false;
+            };
+            match (expected, actual) {
+                (Value::String(expr), _) if expr.trim_start().starts_with("${") => {
+                    crate::expressions::evaluate_expression(evaluation, expr, actual)
+                        .is_ok_and(|result| result.as_bool() == Some(true))
+                }
+                (Value::String(pattern), Value::String(actual)) => {
+                    regex::Regex::new(&format!("^(?:{pattern})$"))
+                        .map_or(pattern == actual, |re| re.is_match(actual))
+                }
+                (expected, actual) => expected == actual,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    All,
+    Any,
+    One,
+}
+
+/// Progress of a consumption strategy over the events received so far
+#[derive(Debug)]
+struct Consumer {
+    mode: Mode,
+    filters: Vec<Filter>,
+    /// Filters that have consumed an event
+    matched: Vec<bool>,
+    /// Number of events consumed
+    consumed: usize,
+    /// How the expressions of the filters are evaluated
+    evaluation: Evaluation,
+}
+
+impl Consumer {
+    fn new(strategy: &Strategy, task_name: &str, evaluation: Evaluation) -> Result<Self> {
+        let (mode, filters) = match (&strategy.all, &strategy.any, &strategy.one) {
+            (Some(all), None, None) => (Mode::All, all.clone()),
+            (None, Some(any), None) => (Mode::Any, any.clone()),
+            (None, None, Some(one)) => (Mode::One, vec![one.clone()]),
+            _ => {
+                return Err(Error::Configuration {
+                    message: format!(
+                        "Task {task_name}: listen.to must define exactly one of 'all', 'any' or 'one'"
+                    ),
+                });
+            }
+        };
+        Ok(Self {
+            matched: vec![false; filters.len()],
+            mode,
+            filters,
+            consumed: 0,
+            evaluation,
+        })
+    }
+
+    /// Offer an event to the strategy, returning whether it was consumed
+    fn offer(&mut self, inbound: &InboundEvent, task_name: &str) -> bool {
+        let consumed =
+            match self.mode {
+                Mode::Any => {
+                    self.filters.is_empty()
+                        || self
+                            .filters
+                            .iter()
+                            .any(|f| f.matches(inbound, task_name, self.evaluation))
+                }
+                Mode::All | Mode::One => {
+                    let evaluation = self.evaluation;
+                    let pending = self.filters.iter().zip(self.matched.iter_mut()).find(
+                        |(filter, matched)| {
+                            !**matched && filter.matches(inbound, task_name, evaluation)
+                        },
+                    );
+                    match pending {
+                        Some((_, matched)) => {
+                            *matched = true;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+            };
+        if consumed {
+            self.consumed += 1;
+        }
+        consumed
+    }
+
+    /// Whether the strategy has consumed the events it waits for
+    fn is_complete(&self) -> bool {
+        match self.mode {
+            Mode::Any => self.consumed > 0,
+            Mode::All | Mode::One => self.matched.iter().all(|matched| *matched),
+        }
+    }
+}
+
+/// When an `any` strategy stops consuming events
+enum Until {
+    /// After the first event
+    None,
+    /// When the expression yields `true` for the events consumed so far
+    Expression(String),
+    /// When this strategy is satisfied
+    Strategy(Box<Consumer>),
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use serde_json::json;
+
+    fn strategy(value: Value) -> Strategy {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn event(value: Value) -> InboundEvent {
+        InboundEvent {
+            event: value,
+            listener: None,
+        }
+    }
+
+    #[test]
+    fn test_filter_matching() {
+        let filter: Filter = serde_json::from_value(json!({
+            "with": { "type": "com\\.example\\.order\\..*", "source": "/shop", "priority": 1 }
+        }))
+        .unwrap();
+        let placed =
+            event(json!({ "type": "com.example.order.placed", "source": "/shop", "priority": 1 }));
+        assert!(filter.matches(&placed, "wait", Evaluation::default()));
+        let other =
+            event(json!({ "type": "com.example.user.created", "source": "/shop", "priority": 1 }));
+        assert!(!filter.matches(&other, "wait", Evaluation::default()));
+
+        let filter: Filter =
+            serde_json::from_value(json!({ "with": { "priority": "${ . > 5 }" } })).unwrap();
+        assert!(filter.matches(
+            &event(json!({ "priority": 7 })),
+            "wait",
+            Evaluation::default()
+        ));
+        assert!(!filter.matches(
+            &event(json!({ "priority": 3 })),
+            "wait",
+            Evaluation::default()
+        ));
+    }
+
+    #[test]
+    fn test_listener_source_matches_its_events() {
+        let filter: Filter = serde_json::from_value(json!({
+            "with": { "source": { "uri": "http://localhost:8081/webhook" } }
+        }))
+        .unwrap();
+        let received = InboundEvent {
+            event: json!({ "type": "t" }),
+            listener: Some("wait".to_string()),
+        };
+        assert!(filter.matches(&received, "wait", Evaluation::default()));
+        assert!(!filter.matches(&received, "other", Evaluation::default()));
+        assert!(!filter.matches(
+            &event(json!({ "type": "t" })),
+            "wait",
+            Evaluation::default()
+        ));
+    }
+
+    #[test]
+    fn test_all_waits_for_every_filter() {
+        let mut consumer = Consumer::new(
+            &strategy(json!({ "all": [{ "with": { "type": "a" } }, { "with": { "type": "b" } }] })),
+            "wait",
+            Evaluation::default(),
+        )
+        .unwrap();
+        assert!(consumer.offer(&event(json!({ "type": "b" })), "wait"));
+        assert!(!consumer.offer(&event(json!({ "type": "b" })), "wait"));
+        assert!(!consumer.is_complete());
+        assert!(consumer.offer(&event(json!({ "type": "a" })), "wait"));
+        assert!(consumer.is_complete());
+    }
+
+    #[test]
+    fn test_any_without_filters_consumes_every_event() {
+        let mut consumer = Consumer::new(
+            &strategy(json!({ "any": [] })),
+            "wait",
+            Evaluation::default(),
+        )
+        .unwrap();
+        assert!(!consumer.is_complete());
+        assert!(consumer.offer(&event(json!({ "type": "x" })), "wait"));
+        assert!(consumer.is_complete());
+    }
+
+    #[test]
+    fn test_strategy_requires_one_kind() {
+        assert!(Consumer::new(&strategy(json!({})), "wait", Evaluation::default()).is_err());
+        assert!(
+            Consumer::new(
+                &strategy(json!({ "one": { "with": {} }, "any": [] })),
+                "wait",
+                Evaluation::default()
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,9 +1,11 @@
 use serverless_workflow_core::models::task::TaskDefinition;
 
-use crate::context::Context;
+use crate::context::{Context, MergeStrategy};
+use crate::middleware::TaskRequest;
 use crate::output;
 use crate::task_ext::TaskDefinitionExt;
 
+use super::flow::FlowDirective;
 use super::{DurableEngine, Result};
 
 // Submodules for individual task types
@@ -11,6 +13,7 @@ mod call;
 mod emit;
 mod for_loop;
 mod fork;
+mod listen;
 mod raise;
 mod run;
 mod switch;
@@ -22,6 +25,7 @@ pub use call::exec_call_task;
 pub use emit::exec_emit_task;
 pub use for_loop::exec_for_task;
 pub use fork::exec_fork_task;
+pub use listen::exec_listen_task;
 pub use raise::exec_raise_task;
 pub use run::exec_run_task;
 pub use switch::exec_switch_task;
@@ -30,6 +34,13 @@ pub use wait::exec_wait_task;
 
 impl DurableEngine {
     /// Main task execution dispatcher
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "task",
+        skip_all,
+        fields(task = task_name)
+    )]
     pub(super) async fn exec_task(
         &self,
         task_name: &str,
@@ -70,6 +81,23 @@ impl DurableEngine {
             });
         }
 
+        // A task whose `if` condition is false is skipped, passing its input through
+        if let Some(condition) = task.condition()
+            && !self.evaluate_condition(condition, ctx).await?
+        {
+            ctx.services
+                .persistence
+                .save_event(crate::workflow::WorkflowEvent::TaskSkipped {
+                    instance_id: ctx.metadata.instance_id.clone(),
+                    task_name: task_name.to_string(),
+                    condition: condition.to_string(),
+                    timestamp: chrono::Utc::now(),
+                })
+                .await?;
+            output::format_task_condition_false(task_name, condition);
+            return Ok(ctx.task_input().await);
+        }
+
         // Emit task.created.v1 event
         ctx.services
             .persistence
@@ -98,16 +126,46 @@ impl DurableEngine {
         output::format_task_start(task_name, task.type_name());
 
         // Show current context
-        let current_context = ctx.state.data.read().await.clone();
+        let current_context = ctx.data().await;
         output::format_task_context(&current_context);
 
         // Apply input filtering if specified
-        let _has_input_filter = self.apply_input_filter(task, ctx).await?;
+        let _has_input_filter = self.apply_input_filter(task_name, task, ctx).await?;
 
         // Show input after filtering
-        let input_data = ctx.state.data.read().await.clone();
+        let input_data = ctx.data().await;
         output::format_task_input(&input_data);
 
+        // Let middleware (e.g. admission policies) refuse the task
+        for middleware in self.middleware.iter() {
+            middleware
+                .before_task(&TaskRequest {
+                    instance_id: &ctx.metadata.instance_id,
+                    workflow: &ctx.metadata.workflow,
+                    task_name,
+                    task,
+                    input: &input_data,
+                })
+                .await?;
+        }
+
+        // A shadow run takes the results the instance recorded for tasks with side effects
+        if let Some(stubs) = &self.shadow_stubs
+            && let Some(result) = stubs.stub(task_name, task, ctx).await
+        {
+            return Ok(result);
+        }
+
+        // A task with a timeout gets its own cancellation token, so that its
+        // executor can be stopped when it times out without cancelling the instance
+        let timeout_ctx;
+        let ctx = if task.timeout().is_some() {
+            timeout_ctx = ctx.with_child_cancellation();
+            &timeout_ctx
+        } else {
+            ctx
+        };
+
         // Execute the task
         // Note: We don't restore the original context after input filtering
         // because task outputs (via ctx.merge) should be preserved
@@ -151,12 +209,25 @@ impl DurableEngine {
         };
 
         // Apply task-level timeout if specified
-        if let Some(timeout_def) = task.timeout() {
+        let output = if let Some(timeout_def) = task.timeout() {
             let timeout_duration = super::timeout::parse_timeout_duration(timeout_def)?;
 
-            match tokio::time::timeout(timeout_duration, task_execution_future).await {
+            let mut task_execution_future = std::pin::pin!(task_execution_future);
+            match tokio::time::timeout(timeout_duration, &mut task_execution_future).await {
                 Ok(result) => result,
                 Err(_) => {
+                    // Stop the work the executor started, as if the task were cancelled
+                    ctx.state.cancellation.cancel();
+                    super::control::reap_task(
+                        ctx.services.persistence.as_ref(),
+                        &ctx.metadata.instance_id,
+                        task_name,
+                        "timeout",
+                        &ctx.state.reaper,
+                        task_execution_future,
+                    )
+                    .await;
+
                     // Task timed out - emit TaskFaulted event
                     ctx.services
                         .persistence
@@ -182,31 +253,79 @@ impl DurableEngine {
         } else {
             // No timeout specified, execute normally
             task_execution_future.await
-        }
+        }?;
+        // Results of calls and scripts come with numbers as they were written
+        let output = self.number_mode.apply(output);
+
+        // The output is validated after `output.as` has transformed it
+        self.validate_schema(
+            &ctx.metadata.workflow,
+            task.output(),
+            &output,
+            &format!("Output of task '{task_name}'"),
+        )
+        .await?;
+        Ok(output)
+    }
+
+    /// Evaluate a task's `if` condition against the current context
+    ///
+    /// Like switch cases, `null` and `false` are false and any other value is true.
+    async fn evaluate_condition(&self, condition: &str, ctx: &Context) -> Result<bool> {
+        let current_data = ctx.data().await;
+        let result = if condition.trim().starts_with("${") {
+            let task_input = ctx.task_input().await;
+            crate::expressions::evaluate_expression_with_input(
+                ctx.metadata.evaluation,
+                condition,
+                &current_data,
+                &task_input,
+            )?
+        } else {
+            crate::expressions::evaluate_jq(condition, &current_data)?
+        };
+        Ok(!matches!(
+            result,
+            serde_json::Value::Null | serde_json::Value::Bool(false)
+        ))
     }
 
-    /// Apply input filter to task
+    /// Validate the input of a task against its schema and apply its input filter
     pub(super) async fn apply_input_filter(
         &self,
+        task_name: &str,
         task: &TaskDefinition,
         ctx: &Context,
     ) -> Result<bool> {
+        // The input is validated before `input.from` transforms it
+        let current_data = ctx.data().await;
+        self.validate_schema(
+            &ctx.metadata.workflow,
+            task.input(),
+            &current_data,
+            &format!("Input of task '{task_name}'"),
+        )
+        .await?;
+
         if let Some(input) = task.input()
             && let Some(from_expr) = &input.from
             && let Some(expr_str) = from_expr.as_str()
         {
-            let current_data = ctx.state.data.read().await.clone();
             // Input filtering can use either:
             // 1. Wrapped expressions: ${ .field } (newer CTK examples)
             // 2. Bare JQ expressions: .field (older examples)
             let filtered = if expr_str.trim().starts_with("${") {
                 // Wrapped expression - use evaluate_expression which handles ${ } syntax
-                crate::expressions::evaluate_expression(expr_str, &current_data)?
+                crate::expressions::evaluate_expression(
+                    ctx.metadata.evaluation,
+                    expr_str,
+                    &current_data,
+                )?
             } else {
                 // Bare JQ expression - use evaluate_jq directly
                 crate::expressions::evaluate_jq(expr_str, &current_data)?
             };
-            *ctx.state.data.write().await = filtered;
+            ctx.merge_data(filtered, MergeStrategy::Replace).await;
             return Ok(true);
         }
 
@@ -214,6 +333,67 @@ impl DurableEngine {
     }
 }
 
+/// The key the result of `task` is cached under, or `None` if the task opts
+/// out of caching with `metadata.cache: false`
+///
+/// A task's `metadata.cacheKey` is evaluated against its input, and its result
+/// is hashed instead of `params`, so tasks can share results across inputs that
+/// differ in ways that do not matter.
+fn cache_key(
+    task_name: &str,
+    task: &impl serde::Serialize,
+    params: &serde_json::Value,
+    input: &serde_json::Value,
+    ctx: &Context,
+) -> Result<Option<String>> {
+    let task = serde_json::to_value(task)?;
+    match task.pointer("/metadata/cache") {
+        Some(serde_json::Value::Bool(false)) => return Ok(None),
+        Some(serde_json::Value::Bool(true)) | None => {}
+        Some(cache) => {
+            return Err(super::Error::Configuration {
+                message: format!("metadata.cache must be true or false, got {cache}"),
+            });
+        }
+    }
+    match task.pointer("/metadata/cacheKey") {
+        Some(serde_json::Value::String(expr)) => {
+            let key = crate::expressions::evaluate_expression_with_input(
+                ctx.metadata.evaluation,
+                expr,
+                input,
+                &ctx.metadata.initial_input,
+            )?;
+            Ok(Some(crate::cache::compute_cache_key(task_name, &key)))
+        }
+        Some(key) => Err(super::Error::Configuration {
+            message: format!("metadata.cacheKey must be an expression, got {key}"),
+        }),
+        None => Ok(Some(crate::cache::compute_cache_key(task_name, params))),
+    }
+}
+
+/// When the cached result of `task`, written now, expires
+///
+/// A task's `metadata.cacheTtl`, an ISO 8601 duration, takes precedence over
+/// the engine's TTL.
+fn cache_expiry(
+    engine: &DurableEngine,
+    task: &impl serde::Serialize,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    let task = serde_json::to_value(task)?;
+    let ttl = match task.pointer("/metadata/cacheTtl") {
+        Some(serde_json::Value::String(ttl)) => Some(super::timeout::parse_iso8601_duration(ttl)?),
+        Some(ttl) => {
+            return Err(super::Error::Configuration {
+                message: format!("metadata.cacheTtl must be an ISO 8601 duration, got {ttl}"),
+            });
+        }
+        None => engine.cache_ttl,
+    };
+    Ok(crate::cache::expires_at(ttl))
+}
+
 /// Execute a Set task - sets variables in the context
 async fn exec_set_task(
     _engine: &DurableEngine,
@@ -224,16 +404,18 @@ async fn exec_set_task(
     use serverless_workflow_core::models::task::SetValue;
 
     // Get current context data for expression evaluation
-    let current_data = ctx.state.data.read().await.clone();
+    let current_data = ctx.data().await;
 
-    let task_input = ctx.state.task_input.read().await.clone();
+    let task_input = ctx.task_input().await;
 
     match &set_task.set {
         SetValue::Map(map) => {
-            // Handle map of key-value pairs - evaluate each value
+            // Handle map of key-value pairs - evaluate each value. The map has
+            // no document order left, so the fields are set in key order
             let mut result_map = serde_json::Map::new();
-            for (key, value) in map {
+            for (key, value) in crate::ordering::in_key_order(map) {
                 let evaluated_value = crate::expressions::evaluate_value_with_input(
+                    ctx.metadata.evaluation,
                     value,
                     &current_data,
                     &task_input,
@@ -245,6 +427,7 @@ async fn exec_set_task(
         SetValue::Expression(expr) => {
             // Handle runtime expression - evaluate it and return the result
             let evaluated_value = crate::expressions::evaluate_expression_with_input(
+                ctx.metadata.evaluation,
                 expr,
                 &current_data,
                 &task_input,
@@ -255,6 +438,9 @@ async fn exec_set_task(
 }
 
 /// Execute a Do task - sequential execution of subtasks
+///
+/// Subtasks follow their `then` directives within the block: `exit` leaves
+/// it, and `end` leaves it and every enclosing task to complete the workflow.
 async fn exec_do_task(
     engine: &DurableEngine,
     _task_name: &str,
@@ -262,71 +448,39 @@ async fn exec_do_task(
     ctx: &Context,
 ) -> Result<serde_json::Value> {
     let mut last_result = serde_json::Value::Null;
+    let subtasks: Vec<_> = do_task.do_.entries.iter().flatten().collect();
 
-    // Execute subtasks sequentially in order
-    for entry in &do_task.do_.entries {
-        for (subtask_name, subtask) in entry {
-            // Box the recursive call to avoid infinite sized future
-            let result = Box::pin(engine.exec_task(subtask_name, subtask, ctx)).await?;
+    // Execute subtasks in order, unless a directive jumps elsewhere
+    let mut index = 0;
+    while let Some((subtask_name, subtask)) = subtasks.get(index) {
+        // Box the recursive call to avoid infinite sized future
+        let result = Box::pin(engine.exec_task(subtask_name, subtask, ctx)).await?;
 
-            // Update task_input for the next subtask
-            *ctx.state.task_input.write().await = result.clone();
+        // Update task_input for the next subtask
+        ctx.set_task_input(result.clone()).await;
 
-            // Handle export.as for subtasks (same logic as main execution loop)
-            super::export::apply_export_to_context(subtask, &result, ctx).await?;
+        // Handle export.as for subtasks (same logic as main execution loop)
+        super::export::apply_export_to_context(subtask, &result, ctx).await?;
+        engine.evaluate_watches(subtask_name, ctx).await?;
 
-            last_result = result;
-        }
-    }
+        last_result = result;
 
-    // Do task returns the last subtask's result
-    Ok(last_result)
-}
-
-/// Execute a Listen task - listeners are initialized at workflow startup
-#[allow(clippy::unnecessary_wraps)]
-async fn exec_listen_task(
-    _engine: &DurableEngine,
-    _task_name: &str,
-    listen_task: &serverless_workflow_core::models::task::ListenTaskDefinition,
-    ctx: &Context,
-) -> Result<serde_json::Value> {
-    // Listen tasks are initialized at workflow startup via initialize_listeners()
-    // The listener is already running in the background.
-    // According to the DSL spec, if 'until' is specified with eventConsumptionStrategy 'any',
-    // we must keep listening until the condition evaluates to true.
-    // If until evaluates to false, we block indefinitely to keep the workflow alive.
-
-    let listen_def = &listen_task.listen;
-    if let Some(until_box) = &listen_def.to.until {
-        use serverless_workflow_core::models::event::OneOfEventConsumptionStrategyDefinitionOrExpression;
-
-        // Check if until is an expression (not a strategy with events)
-        if let OneOfEventConsumptionStrategyDefinitionOrExpression::Expression(until_expr) =
-            until_box.as_ref()
-        {
-            // Evaluate the until expression
-            let current_data = ctx.state.data.read().await.clone();
-            let until_value = crate::expressions::evaluate_expression(until_expr, &current_data)?;
-
-            // If until evaluates to false, block indefinitely
-            // This keeps the workflow (and container) alive while background listeners process events
-            if let Some(false) = until_value.as_bool() {
-                eprintln!("DEBUG: Blocking forever because until = false");
-                use tokio::time::Duration;
-                loop {
-                    tokio::time::sleep(Duration::from_secs(3600)).await;
-                }
-            } else {
-                eprintln!(
-                    "DEBUG: NOT blocking, until_value.as_bool() = {:?}",
-                    until_value.as_bool()
-                );
+        index = match super::flow::after(subtask, ctx).await {
+            FlowDirective::Continue => index + 1,
+            FlowDirective::Exit => break,
+            FlowDirective::End => {
+                super::flow::propagate_end(ctx).await;
+                break;
             }
-        }
-        // Note: If until is a Strategy (not an Expression), we don't block here
-        // because the strategy defines events that trigger completion, not a boolean condition
+            FlowDirective::Goto(next_name) => subtasks
+                .iter()
+                .position(|(name, _)| **name == next_name)
+                .ok_or(super::Error::TaskExecution {
+                    message: format!("Next task not found: {next_name}"),
+                })?,
+        };
     }
 
-    Ok(serde_json::json!({"status": "already_listening"}))
+    // Do task returns the last subtask's result
+    Ok(last_result)
 }
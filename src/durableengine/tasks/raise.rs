@@ -1,4 +1,5 @@
 use crate::context::Context;
+use crate::executor::WorkflowError;
 
 use super::super::{DurableEngine, Error, Result};
 
@@ -26,21 +27,9 @@ pub async fn exec_raise_task(
         "type": error_def.type_,
         "title": error_def.title,
         "status": error_def.status,
+        "detail": error_def.detail,
     });
 
-    // Add optional fields if present
-    if let Some(detail) = &error_def.detail {
-        error_obj
-            .as_object_mut()
-            .ok_or_else(|| Error::Configuration {
-                message: "Error object is not a valid JSON object".to_string(),
-            })?
-            .insert(
-                "detail".to_string(),
-                serde_json::Value::String(detail.clone()),
-            );
-    }
-
     // Add the instance field - this should be the path to the task in the workflow
     // The path format is /do/index/taskName
     let task_path = format!("/do/0/{task_name}");
@@ -51,11 +40,9 @@ pub async fn exec_raise_task(
         })?
         .insert("instance".to_string(), serde_json::Value::String(task_path));
 
-    // Serialize the error to a JSON string for the error message
-    let error_json = serde_json::to_string(&error_obj)?;
-
-    // Return an error with the JSON-serialized error object
-    Err(Error::TaskExecution {
-        message: error_json,
-    })
+    let error: WorkflowError =
+        serde_json::from_value(error_obj).map_err(|e| Error::Configuration {
+            message: format!("Invalid error raised by task {task_name}: {e}"),
+        })?;
+    Err(Error::Raised { error })
 }
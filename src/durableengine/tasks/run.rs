@@ -1,15 +1,25 @@
 use chrono::Utc;
 use snafu::prelude::*;
+use std::collections::HashMap;
 use std::process::Stdio;
+use tracing::Instrument;
 
-use crate::cache::{CacheEntry, compute_cache_key};
-use crate::container::{ContainerConfig, ContainerProvider};
+use crate::cache::CacheEntry;
+use crate::container::{
+    ContainerConfig, ContainerOptions, ContainerProvider, ImageBuild, parse_compose,
+};
 use crate::context::Context;
 use crate::output;
-use crate::providers::container::DockerProvider;
+use crate::process::{self, ProcessGroup};
+use crate::profile;
+use crate::providers::container;
+use crate::task_env::TaskEnv;
 use crate::task_output::TaskOutputStreamer;
+use crate::text::{MAX_DISPLAY_LEN, truncate};
+use crate::workflow::WorkflowEvent;
 
-use super::super::{DurableEngine, Error, IoSnafu, Result};
+use super::super::versions;
+use super::super::{DurableEngine, Error, ResourceReadSnafu, Result};
 
 /// Execute a Run task - runs workflows, scripts, containers, or shell commands
 pub async fn exec_run_task(
@@ -20,9 +30,10 @@ pub async fn exec_run_task(
 ) -> Result<serde_json::Value> {
     // Evaluate expressions in the run task definition before computing cache key
     // This ensures that expressions like $workflow.id are evaluated to their actual values
-    let current_data = ctx.state.data.read().await.clone();
+    let current_data = ctx.data().await;
     let params = serde_json::to_value(&run_task.run)?;
     let evaluated_params = crate::expressions::evaluate_value_with_input(
+        ctx.metadata.evaluation,
         &params,
         &current_data,
         &ctx.metadata.initial_input,
@@ -35,45 +46,68 @@ pub async fn exec_run_task(
         "input": current_data
     });
 
-    let cache_key = compute_cache_key(task_name, &cache_params);
+    let cache_key = super::cache_key(task_name, run_task, &cache_params, &current_data, ctx)?;
+
+    if let Some(cache_key) = &cache_key {
+        if let Some(cached) = ctx.services.cache.get(cache_key).await? {
+            output::format_cache_hit(
+                task_name,
+                cache_key,
+                Some(&cached.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()),
+            );
+            ctx.services
+                .persistence
+                .save_event(WorkflowEvent::TaskCacheHit {
+                    instance_id: ctx.metadata.instance_id.clone(),
+                    task_name: task_name.to_string(),
+                    cache_key: cache_key.clone(),
+                    cached_at: cached.timestamp,
+                    timestamp: Utc::now(),
+                })
+                .await?;
+            return Ok(cached.output);
+        }
 
-    if let Some(cached) = ctx.services.cache.get(&cache_key).await? {
-        output::format_cache_hit(
-            task_name,
-            &cache_key,
-            Some(&cached.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()),
-        );
-        return Ok(cached.output);
+        output::format_cache_miss(task_name, cache_key);
     }
 
-    output::format_cache_miss(task_name, &cache_key);
-
     // Note: TaskStarted event is now emitted centrally in exec_task()
 
     // Check what type of run task this is
     let result = if let Some(workflow_def) = run_task.run.workflow.as_ref() {
-        // Workflow execution
-        let workflow_key = format!(
-            "{}/{}/{}",
-            workflow_def.namespace, workflow_def.name, workflow_def.version
+        // Workflow execution; a version range is pinned when the instance starts
+        let range_key = versions::workflow_key(
+            &workflow_def.namespace,
+            &workflow_def.name,
+            &workflow_def.version,
         );
+        let version = match ctx.metadata.resolved_versions.get(&range_key) {
+            Some(pinned) => pinned.clone(),
+            None if versions::VersionReq::parse(&workflow_def.version).is_some() => {
+                engine
+                    .resolve_workflow_version(
+                        &workflow_def.namespace,
+                        &workflow_def.name,
+                        &workflow_def.version,
+                    )
+                    .await?
+            }
+            None => workflow_def.version.clone(),
+        };
 
-        // Look up workflow from registry
-        let registry = engine.workflow_registry.read().await;
-        let workflow = registry
-            .get(&workflow_key)
-            .ok_or_else(|| Error::Configuration {
-                message: format!("Workflow not found in registry: {workflow_key}"),
-            })?
-            .clone();
-        drop(registry);
+        // Look up workflow from registry, or load it from the workflow sources
+        let workflow = engine
+            .find_workflow(&workflow_def.namespace, &workflow_def.name, &version)
+            .await?;
+        let call_chain = engine.call_chain(ctx, &workflow)?;
 
         // Get input data for the nested workflow
         let input_data = workflow_def.input.clone().unwrap_or(serde_json::json!({}));
 
         // Evaluate input data against current context
-        let current_data = ctx.state.data.read().await.clone();
+        let current_data = ctx.data().await;
         let evaluated_input = crate::expressions::evaluate_value_with_input(
+            ctx.metadata.evaluation,
             &input_data,
             &current_data,
             &ctx.metadata.initial_input,
@@ -83,7 +117,12 @@ pub async fn exec_run_task(
         // Generate a new instance ID for the nested workflow
         let instance_id = uuid::Uuid::new_v4().to_string();
         let final_data = engine
-            .run_instance(workflow, Some(instance_id.clone()), evaluated_input)
+            .run_called_instance(
+                workflow,
+                Some(instance_id.clone()),
+                evaluated_input,
+                &call_chain,
+            )
             .await?;
 
         // Wait for completion if await is true (default)
@@ -98,9 +137,10 @@ pub async fn exec_run_task(
         let language = script.language.to_lowercase();
 
         // Display script parameters instead of generic input
-        let current_data = ctx.state.data.read().await.clone();
+        let current_data = ctx.data().await;
         let stdin_display = script.stdin.as_ref().and_then(|s| {
             crate::expressions::evaluate_value_with_input(
+                ctx.metadata.evaluation,
                 &serde_json::Value::String(s.clone()),
                 &current_data,
                 &ctx.metadata.initial_input,
@@ -111,6 +151,7 @@ pub async fn exec_run_task(
 
         let arguments_display = script.arguments.as_ref().and_then(|args| {
             crate::expressions::evaluate_value_with_input(
+                ctx.metadata.evaluation,
                 &serde_json::to_value(args).ok()?,
                 &current_data,
                 &ctx.metadata.initial_input,
@@ -122,6 +163,7 @@ pub async fn exec_run_task(
             let mut evaluated_env = serde_json::Map::new();
             for (key, value) in env {
                 if let Some(evaluated) = crate::expressions::evaluate_value_with_input(
+                    ctx.metadata.evaluation,
                     &serde_json::Value::String(value.clone()),
                     &current_data,
                     &ctx.metadata.initial_input,
@@ -163,16 +205,15 @@ pub async fn exec_run_task(
             };
 
             if source_uri.starts_with("file://") {
-                // Load from local file
-                let file_path =
-                    source_uri
-                        .strip_prefix("file://")
-                        .ok_or_else(|| Error::Configuration {
-                            message: format!("Invalid file URI format: {source_uri}"),
-                        })?;
-                tokio::fs::read_to_string(file_path)
+                // Load from local file, relative to the workflow file
+                let resolved = engine
+                    .resolve_resource(&ctx.metadata.workflow, source_uri)
+                    .await;
+                tokio::fs::read_to_string(&resolved.path)
                     .await
-                    .context(IoSnafu)?
+                    .context(ResourceReadSnafu {
+                        resource: format!("script source {resolved}"),
+                    })?
             } else if source_uri.starts_with("http://") || source_uri.starts_with("https://") {
                 // Load from HTTP(S)
                 let response =
@@ -210,10 +251,11 @@ pub async fn exec_run_task(
         };
 
         // Get script arguments if provided and evaluate them against context
-        let current_data = ctx.state.data.read().await.clone();
+        let current_data = ctx.data().await;
         let arguments = if let Some(args) = script.arguments.as_ref() {
             crate::expressions::evaluate_value_with_input(
-                &serde_json::to_value(args)?,
+                ctx.metadata.evaluation,
+                &crate::ordering::to_canonical_value(args)?,
                 &current_data,
                 &ctx.metadata.initial_input,
             )?
@@ -225,6 +267,7 @@ pub async fn exec_run_task(
         // Get stdin if provided and evaluate it
         let stdin = if let Some(stdin_str) = script.stdin.as_ref() {
             let evaluated = crate::expressions::evaluate_value_with_input(
+                ctx.metadata.evaluation,
                 &serde_json::Value::String(stdin_str.clone()),
                 &current_data,
                 &ctx.metadata.initial_input,
@@ -235,66 +278,70 @@ pub async fn exec_run_task(
         };
 
         // Get environment variables if provided and evaluate them
-        let environment = if let Some(env) = script.environment.as_ref() {
-            let mut evaluated_env = serde_json::Map::new();
+        let mut environment = task_environment(engine, run_task).await?;
+        if let Some(env) = script.environment.as_ref() {
             for (key, value) in env {
                 let evaluated = crate::expressions::evaluate_value_with_input(
+                    ctx.metadata.evaluation,
                     &serde_json::Value::String(value.clone()),
                     &current_data,
                     &ctx.metadata.initial_input,
                 )?;
                 if let Some(s) = evaluated.as_str() {
-                    evaluated_env.insert(key.clone(), serde_json::Value::String(s.to_string()));
+                    environment.insert(key.clone(), s.to_string());
                 }
             }
-            Some(serde_json::Value::Object(evaluated_env))
-        } else {
-            None
-        };
+        }
 
         // Execute script with stdin, arguments, and environment
         let mut script_params = serde_json::json!({
             "script": script_code,
-            "arguments": arguments
+            "arguments": arguments,
+            "environment": environment,
+            "clearEnvironment": true
         });
 
         if let (Some(stdin_val), Some(obj)) = (stdin, script_params.as_object_mut()) {
             obj.insert("stdin".to_string(), serde_json::Value::String(stdin_val));
         }
 
-        if let (Some(env_val), Some(obj)) = (environment, script_params.as_object_mut()) {
-            obj.insert("environment".to_string(), env_val);
-        }
-
-        // Create streamer for real-time output streaming (before execution)
+        // Create streamer for real-time output streaming (before execution),
+        // if the executor can stream
+        let streams = executor.capabilities().supports_streaming;
         let task_index = ctx.state.task_index.unwrap_or(0);
-        let streamer = TaskOutputStreamer::new(task_name.to_string(), task_index);
-
-        // Pass streamer directly to executor for real-time streaming
-        let script_result = executor
-            .exec(task_name, &script_params, ctx, Some(streamer))
+        let streamer = streams.then(|| TaskOutputStreamer::new(task_name.to_string(), task_index));
+
+        let mut script_result = executor
+            .exec(
+                task_name,
+                &script_params,
+                ctx,
+                streamer,
+                &ctx.state.cancellation,
+            )
+            .instrument(profile::executor_span(&language))
             .await?;
 
-        // Output has already been streamed in real-time by the executor!
+        // Streamed output has already been printed by the executor!
         // Mark it as streamed so we don't print it again
-        let mut result_with_marker = script_result.clone();
-        if let Some(obj) = result_with_marker.as_object_mut() {
+        if streams && let Some(obj) = script_result.as_object_mut() {
             obj.insert("__streamed".to_string(), serde_json::Value::Bool(true));
         }
 
-        result_with_marker
+        script_result
     } else if let Some(shell) = run_task.run.shell.as_ref() {
         // Shell command execution
         let command = &shell.command;
         let args = shell.arguments.as_deref().unwrap_or(&[]);
 
         // Evaluate arguments against current context
-        let current_data = ctx.state.data.read().await.clone();
+        let current_data = ctx.data().await;
         let evaluated_args: Vec<String> = args
             .iter()
             .map(|arg| {
                 // Try to evaluate as expression, fall back to literal string
                 match crate::expressions::evaluate_value_with_input(
+                    ctx.metadata.evaluation,
                     &serde_json::Value::String(arg.clone()),
                     &current_data,
                     &ctx.metadata.initial_input,
@@ -309,27 +356,48 @@ pub async fn exec_run_task(
         let task_index = ctx.state.task_index.unwrap_or(0);
         let streamer = TaskOutputStreamer::new(task_name.to_string(), task_index);
 
-        // Execute shell command with piped stdout/stderr for streaming
-        let child = tokio::process::Command::new(command)
+        let environment = task_environment(engine, run_task).await?;
+
+        // Execute shell command with piped stdout/stderr for streaming, in a
+        // process group of its own so whatever it starts is killed with it
+        let mut shell_command = tokio::process::Command::new(command);
+        shell_command
             .args(&evaluated_args)
+            .env_clear()
+            .envs(&environment)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| Error::TaskExecution {
-                message: format!("Failed to execute command '{command}': {e}"),
-            })?;
+            .kill_on_drop(true);
+        process::isolate(&mut shell_command);
+        let child = shell_command.spawn().map_err(|e| Error::TaskExecution {
+            message: format!("Failed to execute command '{command}': {e}"),
+        })?;
+        // Dropping the group on an error path kills the whole group
+        let group = ProcessGroup::new(&child, command.as_str());
 
-        // Stream output in real-time
-        let (stdout, stderr, exit_code) =
-            streamer
+        // Stream output in real-time; dropping the stream kills the command
+        // if the task is cancelled
+        let (stdout, stderr, exit_code) = tokio::select! {
+            output = streamer
                 .stream_process_output(child)
-                .await
-                .map_err(|e| Error::TaskExecution {
+                .instrument(profile::executor_span("shell")) => {
+                output.map_err(|e| Error::TaskExecution {
                     message: format!("Failed to stream command output: {e}"),
-                })?;
+                })?
+            }
+            () = ctx.state.cancellation.cancelled() => {
+                ctx.state.reaper.record(group.kill());
+                return Err(Error::TaskExecution {
+                    message: format!("Command '{command}' was killed: task cancelled"),
+                });
+            }
+        };
+        group.release();
 
         // Check exit status
         if exit_code != 0 {
+            let stdout = truncate(&stdout, MAX_DISPLAY_LEN);
+            let stderr = truncate(&stderr, MAX_DISPLAY_LEN);
             return Err(Error::TaskExecution {
                 message: format!(
                     "Command '{command}' failed with exit code {exit_code}\nstdout: {stdout}\nstderr: {stderr}"
@@ -350,12 +418,13 @@ pub async fn exec_run_task(
         let command = container.command.as_deref().unwrap_or("sh");
 
         // Evaluate arguments against current context
-        let current_data = ctx.state.data.read().await.clone();
+        let current_data = ctx.data().await;
         let evaluated_args: Vec<String> = if let Some(args) = container.arguments.as_ref() {
             args.iter()
                 .map(|arg| {
                     // Try to evaluate as expression, fall back to literal string
                     match crate::expressions::evaluate_value_with_input(
+                        ctx.metadata.evaluation,
                         &serde_json::Value::String(arg.clone()),
                         &current_data,
                         &ctx.metadata.initial_input,
@@ -372,6 +441,7 @@ pub async fn exec_run_task(
         // Evaluate stdin if provided
         let stdin_data = if let Some(stdin_str) = container.stdin.as_ref() {
             let evaluated = crate::expressions::evaluate_value_with_input(
+                ctx.metadata.evaluation,
                 &serde_json::Value::String(stdin_str.clone()),
                 &current_data,
                 &ctx.metadata.initial_input,
@@ -391,10 +461,11 @@ pub async fn exec_run_task(
         cmd_with_args.extend(evaluated_args);
 
         // Evaluate environment variables if provided
-        let environment = if let Some(env) = container.environment.as_ref() {
-            let mut evaluated_env = std::collections::HashMap::new();
+        let mut evaluated_env = task_environment(engine, run_task).await?;
+        if let Some(env) = container.environment.as_ref() {
             for (key, value) in env {
                 let evaluated = crate::expressions::evaluate_value_with_input(
+                    ctx.metadata.evaluation,
                     &serde_json::Value::String(value.clone()),
                     &current_data,
                     &ctx.metadata.initial_input,
@@ -411,10 +482,8 @@ pub async fn exec_run_task(
                 };
                 evaluated_env.insert(key.clone(), value_str);
             }
-            Some(evaluated_env)
-        } else {
-            None
-        };
+        }
+        let environment = (!evaluated_env.is_empty()).then_some(evaluated_env);
 
         // Evaluate volumes if provided
         let volumes = if let Some(vols) = container.volumes.as_ref() {
@@ -422,11 +491,13 @@ pub async fn exec_run_task(
             for (key, value) in vols {
                 // Evaluate both host path and container path for expressions
                 let evaluated_key = crate::expressions::evaluate_value_with_input(
+                    ctx.metadata.evaluation,
                     &serde_json::Value::String(key.clone()),
                     &current_data,
                     &ctx.metadata.initial_input,
                 )?;
                 let evaluated_value = crate::expressions::evaluate_value_with_input(
+                    ctx.metadata.evaluation,
                     &serde_json::Value::String(value.clone()),
                     &current_data,
                     &ctx.metadata.initial_input,
@@ -445,28 +516,89 @@ pub async fn exec_run_task(
         // Ports don't need expression evaluation (they're numbers)
         let ports = container.ports.clone();
 
-        // Create container provider (Docker for now, could be configurable later)
-        let provider = DockerProvider::new().map_err(|e| Error::TaskExecution {
-            message: format!("Failed to create container provider: {e}"),
-        })?;
+        let provider = container::connect(engine.container_runtime, engine.container_pool.clone())
+            .await
+            .map_err(|e| Error::TaskExecution {
+                message: format!("Failed to create container provider: {e}"),
+            })?;
 
         // Execute container
-        let config = ContainerConfig {
+        let mut config = ContainerConfig {
             image: image.clone(),
             command: cmd_with_args,
             stdin: stdin_data,
             environment,
-            working_dir: None,
             volumes,
             ports,
+            cancellation: ctx.state.cancellation.clone(),
+            reaper: ctx.state.reaper.clone(),
+            ..ContainerConfig::default()
         };
-
-        let result = provider
-            .execute(config)
-            .await
+        let options = ContainerOptions::from_task(&serde_json::to_value(run_task)?)?
+            .or(&engine.container_defaults);
+        options
+            .apply(&mut config)
             .map_err(|e| Error::TaskExecution {
-                message: format!("Container execution failed: {e}"),
+                message: e.to_string(),
+            })?;
+
+        // Build the image first if the task asks for it, tagging it with the task's image
+        let image_digest = if let Some(build) = &options.build {
+            let context = engine
+                .resolve_resource(&ctx.metadata.workflow, &build.context)
+                .await;
+            let build = ImageBuild {
+                context: context.path.to_string_lossy().into_owned(),
+                ..build.clone()
+            };
+            let streamer =
+                TaskOutputStreamer::new(task_name.to_string(), ctx.state.task_index.unwrap_or(0));
+            let digest = provider
+                .build_image(&build, image, Some(&streamer))
+                .await
+                .map_err(|e| Error::TaskExecution {
+                    message: e.to_string(),
+                })?;
+            config.skip_pull = true;
+            Some(digest)
+        } else {
+            None
+        };
+
+        // Services from the compose file, then inline services, which replace
+        // compose services of the same name
+        let mut services = std::collections::BTreeMap::new();
+        if let Some(compose) = &options.compose {
+            let resolved = engine
+                .resolve_resource(&ctx.metadata.workflow, compose)
+                .await;
+            let yaml =
+                tokio::fs::read_to_string(&resolved.path)
+                    .await
+                    .context(ResourceReadSnafu {
+                        resource: format!("compose file {resolved}"),
+                    })?;
+            services = parse_compose(&yaml).map_err(|e| Error::TaskExecution {
+                message: e.to_string(),
             })?;
+        }
+        services.extend(options.services.clone().unwrap_or_default());
+
+        let span = profile::executor_span("container");
+        let result = if services.is_empty() {
+            provider.execute(config).instrument(span).await
+        } else {
+            let ready_timeout = options.ready_timeout().map_err(|e| Error::TaskExecution {
+                message: e.to_string(),
+            })?;
+            provider
+                .execute_with_services(&services, ready_timeout, config)
+                .instrument(span)
+                .await
+        }
+        .map_err(|e| Error::TaskExecution {
+            message: format!("Container execution failed: {e}"),
+        })?;
 
         // Check exit status
         if result.exit_code != 0 {
@@ -480,10 +612,21 @@ pub async fn exec_run_task(
 
         // Return just stdout as a string on success
         // Try to parse as JSON first, fall back to plain string
-        if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&result.stdout) {
-            json_value
-        } else {
-            serde_json::Value::String(result.stdout)
+        let output =
+            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&result.stdout) {
+                json_value
+            } else {
+                serde_json::Value::String(result.stdout)
+            };
+
+        // Tasks that build their image also report which image they ran
+        match image_digest {
+            Some(digest) => serde_json::json!({
+                "image": image,
+                "digest": digest,
+                "output": output,
+            }),
+            None => output,
         }
     } else {
         // Other run types not yet implemented
@@ -495,7 +638,7 @@ pub async fn exec_run_task(
     if let Some(output_config) = &run_task.common.output
         && let Some(as_value) = &output_config.as_
     {
-        let task_input = ctx.state.task_input.read().await.clone();
+        let task_input = ctx.task_input().await;
 
         // Handle both string (expression) and object (field mapping) forms
         if let Some(expr_str) = as_value.as_str() {
@@ -512,6 +655,7 @@ pub async fn exec_run_task(
             for (key, value) in as_obj {
                 if let Some(expr_str) = value.as_str() {
                     let evaluated = crate::expressions::evaluate_expression_with_input(
+                        ctx.metadata.evaluation,
                         expr_str,
                         &final_result,
                         &task_input,
@@ -523,13 +667,28 @@ pub async fn exec_run_task(
         }
     }
 
-    let cache_entry = CacheEntry {
-        key: cache_key.clone(),
-        inputs: evaluated_params,
-        output: final_result.clone(),
-        timestamp: Utc::now(),
-    };
-    ctx.services.cache.set(cache_entry).await?;
+    if let Some(key) = cache_key {
+        let cache_entry = CacheEntry {
+            key,
+            inputs: evaluated_params,
+            output: final_result.clone(),
+            timestamp: Utc::now(),
+            expires_at: super::cache_expiry(engine, run_task)?,
+        };
+        ctx.services.cache.set(cache_entry).await?;
+    }
 
     Ok(final_result)
 }
+
+/// Environment of a script, shell or container task: the process variables allowed
+/// by the engine's policy, followed by the secrets requested in `metadata.env`
+async fn task_environment(
+    engine: &DurableEngine,
+    run_task: &serverless_workflow_core::models::task::RunTaskDefinition,
+) -> Result<HashMap<String, String>> {
+    let task_env = TaskEnv::from_task(&serde_json::to_value(run_task)?)?;
+    let mut environment = engine.env_policy.inherited(&task_env);
+    environment.extend(task_env.secrets(engine.secrets.as_ref()).await?);
+    Ok(environment)
+}
@@ -1,24 +1,35 @@
+use chrono::Utc;
+
 use crate::context::Context;
+use crate::workflow::WorkflowEvent;
 
 use super::super::{DurableEngine, Result};
 
 /// Execute a Switch task - conditional branching based on evaluated expressions
 pub async fn exec_switch_task(
     _engine: &DurableEngine,
-    _task_name: &str,
+    task_name: &str,
     switch_task: &serverless_workflow_core::models::task::SwitchTaskDefinition,
     ctx: &Context,
 ) -> Result<serde_json::Value> {
     // Get current context data
-    let current_data = ctx.state.data.read().await.clone();
+    let current_data = ctx.data().await;
 
     // Evaluate each case in order
     for entry in &switch_task.switch.entries {
-        for case_def in entry.values() {
+        for (case_name, case_def) in entry {
             // If there's a 'when' condition, evaluate it
             let matches = if let Some(when_expr) = &case_def.when {
                 // Evaluate the condition expression
-                let result = crate::expressions::evaluate_jq(when_expr, &current_data)?;
+                let result = if when_expr.trim().starts_with("${") {
+                    crate::expressions::evaluate_expression(
+                        ctx.metadata.evaluation,
+                        when_expr,
+                        &current_data,
+                    )?
+                } else {
+                    crate::expressions::evaluate_jq(when_expr, &current_data)?
+                };
 
                 // Check if the result is truthy
                 match result {
@@ -35,11 +46,21 @@ pub async fn exec_switch_task(
             };
 
             if matches {
+                ctx.services
+                    .persistence
+                    .save_event(WorkflowEvent::SwitchCaseMatched {
+                        instance_id: ctx.metadata.instance_id.clone(),
+                        task_name: task_name.to_string(),
+                        case: case_name.clone(),
+                        timestamp: Utc::now(),
+                    })
+                    .await?;
+
                 // Set the next task to the matched case's 'then' target
                 if let Some(then_target) = &case_def.then {
                     *ctx.state.next_task.write().await = Some(then_target.clone());
                 }
-                return Ok(ctx.state.task_input.read().await.clone());
+                return Ok(ctx.task_input().await);
             }
         }
     }
@@ -48,5 +69,5 @@ pub async fn exec_switch_task(
     if let Some(then_target) = &switch_task.common.then {
         *ctx.state.next_task.write().await = Some(then_target.clone());
     }
-    Ok(ctx.state.task_input.read().await.clone())
+    Ok(ctx.task_input().await)
 }
@@ -1,119 +1,234 @@
+use chrono::Utc;
+use std::time::Instant;
+
 use crate::context::Context;
+use crate::executor::{ErrorKind, WorkflowError};
+use crate::workflow::WorkflowEvent;
 
-use super::super::{DurableEngine, Result};
+use super::super::retry::RetryPolicy;
+use super::super::{DurableEngine, Error, Result};
 
 /// Execute a Try task - error handling with catch blocks
-// If it's about too many arguments:
+///
+/// An error is caught when it matches `catch.errors` and the `catch.when` and
+/// `catch.exceptWhen` conditions; it is then bound in the context under the
+/// name given by `catch.as`, `error` by default.
+///
+/// When the caught error matches `catch.retry`, the whole `try` block runs
+/// again after the policy's delay, recording a `TaskRetried` event per retry.
+/// Once the policy is exhausted the error is handled by `catch.do`.
 pub async fn exec_try_task(
     engine: &DurableEngine,
     task_name: &str,
     try_task: &serverless_workflow_core::models::task::TryTaskDefinition,
     ctx: &Context,
 ) -> Result<serde_json::Value> {
-    // Execute the tasks in the try block
-    let mut last_result = serde_json::Value::Null;
+    let retry_policy = RetryPolicy::resolve(&try_task.catch, &ctx.metadata.workflow)?;
+    let attempt_timeout = match &retry_policy {
+        Some(policy) => policy.attempt_timeout()?,
+        None => None,
+    };
+
+    // Every attempt starts from the input the try task was given
+    let attempt_input = ctx.task_input().await;
+    let first_attempt = Instant::now();
+    let mut retries: u32 = 0;
 
-    for entry in &try_task.try_.entries {
-        for (subtask_name, subtask) in entry {
-            println!("    Executing try subtask: {subtask_name}");
+    loop {
+        // Execute the tasks in the try block
+        let mut last_result = serde_json::Value::Null;
+        let attempt = async {
+            for entry in &try_task.try_.entries {
+                for (subtask_name, subtask) in entry {
+                    println!("    Executing try subtask: {subtask_name}");
+
+                    // Box the async call to avoid infinite recursion
+                    let result = Box::pin(engine.exec_task(subtask_name, subtask, ctx))
+                        .await
+                        .map_err(|e| (subtask_name.as_str(), e))?;
 
-            // Box the async call to avoid infinite recursion
-            let exec_future = engine.exec_task(subtask_name, subtask, ctx);
-            match Box::pin(exec_future).await {
-                Ok(result) => {
                     // Update task_input for the next subtask
-                    *ctx.state.task_input.write().await = result.clone();
+                    ctx.set_task_input(result.clone()).await;
 
                     // Handle export.as for subtasks (same logic as main execution loop)
-                    super::super::export::apply_export_to_context(subtask, &result, ctx).await?;
+                    super::super::export::apply_export_to_context(subtask, &result, ctx)
+                        .await
+                        .map_err(|e| (subtask_name.as_str(), e))?;
 
                     last_result = result;
                 }
-                Err(e) => {
-                    // An error occurred - check if it should be caught
-
-                    // Try to parse the error as JSON to check if it matches the filter
-                    // The error might be wrapped in "Executor error: Execution error: {json}"
-                    let error_str = e.to_string();
-                    let error_obj: serde_json::Value =
-                        // First try to parse the whole string as JSON
-                        if let Ok(parsed) = serde_json::from_str(&error_str) {
-                            parsed
-                        } else {
-                            // Try to extract JSON from wrapped error messages
-                            // Look for patterns like "Executor error: Execution error: {json}"
-                            let json_start = error_str.find('{');
-                            let json_end = error_str.rfind('}');
-
-                            if let (Some(start), Some(end)) = (json_start, json_end) {
-                                if let Ok(parsed) = serde_json::from_str(&error_str[start..=end]) {
-                                    parsed
-                                } else {
-                                    // If extraction failed, create a generic error object
-                                    serde_json::json!({
-                                        "type": "https://serverlessworkflow.io/dsl/errors/types/runtime",
-                                        "status": 500,
-                                        "title": "Runtime Error",
-                                        "detail": error_str,
-                                        "instance": format!("/do/0/{}/try/0/{}", task_name, subtask_name)
-                                    })
-                                }
-                            } else {
-                                // No JSON found, create a generic error object
-                                serde_json::json!({
-                                    "type": "https://serverlessworkflow.io/dsl/errors/types/runtime",
-                                    "status": 500,
-                                    "title": "Runtime Error",
-                                    "detail": error_str,
-                                    "instance": format!("/do/0/{}/try/0/{}", task_name, subtask_name)
-                                })
-                            }
-                        };
-
-                    // Check if the error matches the catch filter
-                    let should_catch = should_catch_error(&error_obj, &try_task.catch);
-
-                    if should_catch {
-                        // Store the error in context using the specified variable name
-                        let error_var_name = try_task.catch.as_.as_deref().unwrap_or("error");
-                        ctx.merge(error_var_name, error_obj.clone()).await;
-
-                        // Execute the catch handler tasks if defined
-                        if let Some(ref catch_tasks) = try_task.catch.do_ {
-                            for catch_entry in &catch_tasks.entries {
-                                for (catch_task_name, catch_task) in catch_entry {
-                                    // Box the async call to avoid infinite recursion
-                                    let exec_future =
-                                        engine.exec_task(catch_task_name, catch_task, ctx);
-                                    let catch_result = Box::pin(exec_future).await?;
-
-                                    // Update task_input for the next subtask
-                                    *ctx.state.task_input.write().await = catch_result.clone();
-
-                                    // Handle export.as for catch handler subtasks
-                                    super::super::export::apply_export_to_context(
-                                        catch_task,
-                                        &catch_result,
-                                        ctx,
-                                    )
-                                    .await?;
-
-                                    last_result = catch_result;
-                                }
-                            }
-                        }
-
-                        // Try task returns the last catch handler result
-                        return Ok(last_result);
-                    }
-                    // Error doesn't match the filter, propagate it
-                    return Err(e);
+            }
+            Ok::<_, (&str, Error)>(())
+        };
+
+        let outcome = match attempt_timeout {
+            Some(limit) => tokio::time::timeout(limit, attempt)
+                .await
+                .unwrap_or_else(|_| {
+                    Err((
+                        task_name,
+                        Error::Timeout {
+                            message: format!(
+                                "Attempt of try task '{task_name}' exceeded {limit:?}"
+                            ),
+                        },
+                    ))
+                }),
+            None => attempt.await,
+        };
+
+        let (subtask_name, e) = match outcome {
+            Ok(()) => return Ok(last_result),
+            Err(failure) => failure,
+        };
+
+        // An error occurred - check if it should be caught
+        let error_obj = error_object(&e, task_name, subtask_name);
+        let error_var_name = try_task.catch.as_.as_deref().unwrap_or("error");
+        if !should_catch_error(&error_obj, &try_task.catch)
+            || !catch_conditions_hold(&try_task.catch, error_var_name, &error_obj, ctx).await?
+        {
+            // Error doesn't match the filter, propagate it
+            return Err(e);
+        }
+
+        ctx.services
+            .persistence
+            .save_event(WorkflowEvent::ErrorCaught {
+                instance_id: ctx.metadata.instance_id.clone(),
+                task_name: task_name.to_string(),
+                error: error_obj.clone(),
+                timestamp: Utc::now(),
+            })
+            .await?;
+
+        // Store the error in context using the specified variable name
+        ctx.merge(error_var_name, error_obj.clone()).await;
+
+        if let Some(policy) = &retry_policy
+            && should_retry(policy, &e, ctx).await?
+            && let Some(delay) = policy.next_delay(retries + 1, first_attempt.elapsed())?
+        {
+            retries += 1;
+            ctx.services
+                .persistence
+                .save_event(WorkflowEvent::TaskRetried {
+                    instance_id: ctx.metadata.instance_id.clone(),
+                    task_name: task_name.to_string(),
+                    attempt: retries,
+                    timestamp: Utc::now(),
+                })
+                .await?;
+            println!("    Retrying try task {task_name} in {delay:?} (retry {retries})");
+
+            tokio::time::sleep(delay).await;
+            ctx.set_task_input(attempt_input.clone()).await;
+            continue;
+        }
+
+        // Execute the catch handler tasks if defined
+        if let Some(ref catch_tasks) = try_task.catch.do_ {
+            for catch_entry in &catch_tasks.entries {
+                for (catch_task_name, catch_task) in catch_entry {
+                    // Box the async call to avoid infinite recursion
+                    let exec_future = engine.exec_task(catch_task_name, catch_task, ctx);
+                    let catch_result = Box::pin(exec_future).await?;
+
+                    // Update task_input for the next subtask
+                    ctx.set_task_input(catch_result.clone()).await;
+
+                    // Handle export.as for catch handler subtasks
+                    super::super::export::apply_export_to_context(catch_task, &catch_result, ctx)
+                        .await?;
+
+                    last_result = catch_result;
                 }
             }
         }
+
+        // Try task returns the last catch handler result
+        return Ok(last_result);
     }
+}
 
-    Ok(last_result)
+/// Build the error object exposed to catch filters and handlers
+///
+/// This is the error as the workflow sees it, see [`Error::to_workflow_error`],
+/// located in the `try` block if it does not say where it occurred.
+/// Categorized errors also carry their `category` and whether they are
+/// `retryable`.
+fn error_object(error: &Error, task_name: &str, subtask_name: &str) -> serde_json::Value {
+    let mut workflow_error = error.to_workflow_error();
+    if workflow_error.instance.is_none() {
+        workflow_error.instance = Some(format!("/do/0/{task_name}/try/0/{subtask_name}"));
+    }
+    let mut error_obj = serde_json::to_value(&workflow_error).unwrap_or_default();
+
+    if let (Some(kind), Some(fields)) = (error.kind(), error_obj.as_object_mut()) {
+        fields.insert("category".to_string(), kind.name().into());
+        fields.insert("retryable".to_string(), kind.is_retryable().into());
+    }
+    error_obj
+}
+
+/// Evaluate the retry policy's `when` / `exceptWhen` conditions against the context
+///
+/// A policy without conditions retries every caught error that is not known
+/// to be permanent, such as a script failure or a request rejected with 404.
+async fn should_retry(policy: &RetryPolicy, error: &Error, ctx: &Context) -> Result<bool> {
+    if policy.when.is_none() && policy.except_when.is_none() {
+        return Ok(error.kind().is_none_or(ErrorKind::is_retryable));
+    }
+    let data = ctx.data().await;
+    if let Some(when) = &policy.when
+        && crate::expressions::evaluate_expression(ctx.metadata.evaluation, when, &data)?
+            != serde_json::Value::Bool(true)
+    {
+        return Ok(false);
+    }
+    if let Some(except_when) = &policy.except_when
+        && crate::expressions::evaluate_expression(ctx.metadata.evaluation, except_when, &data)?
+            == serde_json::Value::Bool(true)
+    {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Evaluate `catch.when` and `catch.exceptWhen` against the context, with the
+/// error bound to the variable named by `catch.as`
+///
+/// The error is caught if `when` is true and `exceptWhen` is not; both can
+/// refer to the error as a variable, e.g. `${ $error.status >= 500 }`.
+async fn catch_conditions_hold(
+    catch_def: &serverless_workflow_core::models::task::ErrorCatcherDefinition,
+    error_var_name: &str,
+    error: &serde_json::Value,
+    ctx: &Context,
+) -> Result<bool> {
+    if catch_def.when.is_none() && catch_def.except_when.is_none() {
+        return Ok(true);
+    }
+    let mut data = ctx.data().await;
+    if let Some(fields) = data.as_object_mut() {
+        fields.insert(error_var_name.to_string(), error.clone());
+    } else {
+        data = serde_json::json!({ error_var_name: error });
+    }
+
+    if let Some(when) = &catch_def.when
+        && crate::expressions::evaluate_expression(ctx.metadata.evaluation, when, &data)?
+            != serde_json::Value::Bool(true)
+    {
+        return Ok(false);
+    }
+    if let Some(except_when) = &catch_def.except_when
+        && crate::expressions::evaluate_expression(ctx.metadata.evaluation, except_when, &data)?
+            == serde_json::Value::Bool(true)
+    {
+        return Ok(false);
+    }
+    Ok(true)
 }
 
 /// Check if an error should be caught based on the catch definition
@@ -136,6 +251,15 @@ fn should_catch_error(
         let actual_value = error.get(key);
 
         match actual_value {
+            // Standard types match however the specification version spells them
+            Some(serde_json::Value::String(actual)) if key == "type" => {
+                let Some(expected) = expected_value.as_str() else {
+                    return false;
+                };
+                if !WorkflowError::types_match(actual, expected) {
+                    return false;
+                }
+            }
             Some(actual) => {
                 // Compare values - need to handle different types
                 if !values_match(expected_value, actual) {
@@ -15,7 +15,10 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// - PT1M30S (1 minute 30 seconds)
 /// - PT0.5S (0.5 seconds = 500ms)
 /// - PT0.05M (0.05 minutes = 3 seconds)
-fn parse_iso8601_duration(iso_str: &str) -> Result<StdDuration> {
+///
+/// # Errors
+/// Returns an error if the string is not an ISO 8601 duration.
+pub fn parse_iso8601_duration(iso_str: &str) -> Result<StdDuration> {
     let trimmed = iso_str.trim();
 
     if !trimmed.starts_with('P') {
@@ -80,18 +83,23 @@ fn parse_iso8601_duration(iso_str: &str) -> Result<StdDuration> {
     Ok(StdDuration::from_millis(total_ms as u64))
 }
 
+/// Parse a DSL duration (inline or ISO 8601) into a std::time::Duration
+pub(crate) fn parse_duration(duration: &OneOfDurationOrIso8601Expression) -> Result<StdDuration> {
+    match duration {
+        OneOfDurationOrIso8601Expression::Duration(d) => {
+            let millis = d.total_milliseconds();
+            Ok(StdDuration::from_millis(millis))
+        }
+        OneOfDurationOrIso8601Expression::Iso8601Expression(iso_str) => {
+            parse_iso8601_duration(iso_str)
+        }
+    }
+}
+
 /// Parse a timeout definition into a std::time::Duration
 pub fn parse_timeout_duration(timeout: &OneOfTimeoutDefinitionOrReference) -> Result<StdDuration> {
     match timeout {
-        OneOfTimeoutDefinitionOrReference::Timeout(def) => match &def.after {
-            OneOfDurationOrIso8601Expression::Duration(d) => {
-                let millis = d.total_milliseconds();
-                Ok(StdDuration::from_millis(millis))
-            }
-            OneOfDurationOrIso8601Expression::Iso8601Expression(iso_str) => {
-                parse_iso8601_duration(iso_str)
-            }
-        },
+        OneOfTimeoutDefinitionOrReference::Timeout(def) => parse_duration(&def.after),
         OneOfTimeoutDefinitionOrReference::Reference(_ref_str) => {
             // TODO: Support timeout references from workflow.timeouts map
             Err(Error::Configuration {
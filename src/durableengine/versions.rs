@@ -0,0 +1,565 @@
+//! Version ranges in sub-workflow and catalog function references
+//!
+//! A `run.workflow` reference or a `name:version` catalog function call may
+//! name a range of versions instead of a single one, such as `^1.2`, `~2.0`
+//! or `>=1.2, <1.5`. When an instance starts, each range it references is
+//! resolved to the highest matching version registered with the engine (for
+//! workflows) or published in the catalog (for functions), and the versions
+//! chosen are recorded under `resolvedVersions` in the metadata of its
+//! `WorkflowStarted` event. The instance, including when it is resumed or
+//! replayed, runs the recorded versions even after newer ones are published.
+//!
+//! A version is a range if it contains an operator (`^ ~ = < > ,`) or a
+//! wildcard (`*`, `x`); anything else, such as `1.0.0` or `latest`, is
+//! looked up as written.
+
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use super::catalog::{CatalogSource, FunctionRef, catalog_uri};
+use super::manifest::MANIFEST;
+use super::subworkflows::CALL_CHAIN;
+use super::{DurableEngine, Error, Result};
+use crate::workflow::WorkflowEvent;
+
+/// Key of the resolved versions in the metadata of `WorkflowStarted` events
+pub(crate) const RESOLVED_VERSIONS: &str = "resolvedVersions";
+
+/// A `MAJOR.MINOR.PATCH` version
+///
+/// Versions with a pre-release or build suffix are not matched by ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl Version {
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().split('.').map(str::parse::<u64>);
+        let version = Self {
+            major: parts.next()?.ok()?,
+            minor: parts.next()?.ok()?,
+            patch: parts.next()?.ok()?,
+        };
+        parts.next().is_none().then_some(version)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+}
+
+/// One comparison of a range, such as `>=1.2`; missing parts are wildcards
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    major: u64,
+    minor: Option<u64>,
+    patch: Option<u64>,
+}
+
+impl Comparator {
+    fn parse(comparator: &str) -> Option<Self> {
+        let comparator = comparator.trim();
+        let (op, version) = [
+            (">=", Op::GreaterEq),
+            ("<=", Op::LessEq),
+            (">", Op::Greater),
+            ("<", Op::Less),
+            ("=", Op::Exact),
+            ("~", Op::Tilde),
+            ("^", Op::Caret),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| comparator.strip_prefix(prefix).map(|rest| (op, rest)))
+        .unwrap_or((Op::Exact, comparator));
+
+        let mut parts = version.trim().split('.').map(|part| match part {
+            "*" | "x" | "X" => Ok(None),
+            part => part.parse::<u64>().map(Some),
+        });
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().transpose().ok()?.flatten();
+        let patch = parts.next().transpose().ok()?.flatten();
+        if parts.next().is_some() || (minor.is_none() && patch.is_some()) {
+            return None;
+        }
+        match major {
+            Some(major) => Some(Self {
+                op,
+                major,
+                minor,
+                patch,
+            }),
+            // `*` matches every version
+            None if op == Op::Exact && minor.is_none() => Some(Self {
+                op: Op::GreaterEq,
+                major: 0,
+                minor: None,
+                patch: None,
+            }),
+            None => None,
+        }
+    }
+
+    /// The lowest version the comparator names, missing parts taken as 0
+    fn floor(&self) -> Version {
+        Version {
+            major: self.major,
+            minor: self.minor.unwrap_or(0),
+            patch: self.patch.unwrap_or(0),
+        }
+    }
+
+    /// How `version` compares with the parts the comparator names
+    fn compare(&self, version: &Version) -> Ordering {
+        version
+            .major
+            .cmp(&self.major)
+            .then_with(|| {
+                self.minor
+                    .map_or(Ordering::Equal, |m| version.minor.cmp(&m))
+            })
+            .then_with(|| {
+                self.patch
+                    .map_or(Ordering::Equal, |p| version.patch.cmp(&p))
+            })
+    }
+
+    fn matches(&self, version: &Version) -> bool {
+        match self.op {
+            Op::Exact => self.compare(version) == Ordering::Equal,
+            Op::Greater => self.compare(version) == Ordering::Greater,
+            Op::GreaterEq => *version >= self.floor(),
+            Op::Less => *version < self.floor(),
+            Op::LessEq => self.compare(version) != Ordering::Greater,
+            // ~1.2.3 and ~1.2 allow patch updates, ~1 minor updates
+            Op::Tilde => {
+                *version >= self.floor()
+                    && version.major == self.major
+                    && self.minor.is_none_or(|minor| version.minor == minor)
+            }
+            // ^ allows updates that do not change the leftmost non-zero part
+            Op::Caret => {
+                if *version < self.floor() || version.major != self.major {
+                    return false;
+                }
+                match (self.major, self.minor, self.patch) {
+                    (0, Some(0), Some(patch)) => version.minor == 0 && version.patch == patch,
+                    (0, Some(minor), _) => version.minor == minor,
+                    _ => true,
+                }
+            }
+        }
+    }
+}
+
+/// A range of versions, such as `^1.2` or `>=1.2, <1.5`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+impl VersionReq {
+    /// Parse `version` if it is a range rather than a single version
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let is_range = version.contains(['^', '~', '=', '<', '>', ',', '*'])
+            || version
+                .split('.')
+                .any(|part| part.trim().eq_ignore_ascii_case("x"));
+        if !is_range {
+            return None;
+        }
+        let comparators = version
+            .split(',')
+            .map(Comparator::parse)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { comparators })
+    }
+
+    pub(crate) fn matches(&self, version: &Version) -> bool {
+        self.comparators
+            .iter()
+            .all(|comparator| comparator.matches(version))
+    }
+
+    /// The highest of `versions` in the range, as written
+    pub(crate) fn best_match<'a>(
+        &self,
+        versions: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&'a str> {
+        versions
+            .into_iter()
+            .filter_map(|raw| Version::parse(raw).map(|version| (version, raw)))
+            .filter(|(version, _)| self.matches(version))
+            .max_by_key(|(version, _)| *version)
+            .map(|(_, raw)| raw)
+    }
+}
+
+/// Key of a sub-workflow reference in the resolved versions
+pub(crate) fn workflow_key(namespace: &str, name: &str, range: &str) -> String {
+    format!("workflow:{namespace}/{name}@{range}")
+}
+
+/// Key of a catalog function reference in the resolved versions
+pub(crate) fn function_key(reference: &FunctionRef<'_>) -> String {
+    let FunctionRef {
+        name,
+        version: range,
+        catalog,
+    } = reference;
+    match catalog {
+        Some(catalog) => format!("function:{name}@{range}@{catalog}"),
+        None => format!("function:{name}@{range}"),
+    }
+}
+
+/// A sub-workflow or catalog function referenced by a version range
+struct RangeRef {
+    /// Namespace of a sub-workflow, `None` for a catalog function
+    namespace: Option<String>,
+    name: String,
+    range: String,
+    /// Catalog a function reference names
+    catalog: Option<String>,
+}
+
+/// The version ranges `tasks` references, by their key in the resolved versions
+fn referenced_ranges(tasks: &Value, functions: &[String], found: &mut BTreeMap<String, RangeRef>) {
+    match tasks {
+        Value::Object(fields) => {
+            if let Some(Value::Object(workflow)) =
+                fields.get("run").and_then(|run| run.get("workflow"))
+                && let (Some(namespace), Some(name), Some(range)) = (
+                    workflow.get("namespace").and_then(Value::as_str),
+                    workflow.get("name").and_then(Value::as_str),
+                    workflow.get("version").and_then(Value::as_str),
+                )
+                && VersionReq::parse(range).is_some()
+            {
+                found.insert(
+                    workflow_key(namespace, name, range),
+                    RangeRef {
+                        namespace: Some(namespace.to_string()),
+                        name: name.to_string(),
+                        range: range.to_string(),
+                        catalog: None,
+                    },
+                );
+            }
+            if let Some(Value::String(call)) = fields.get("call")
+                && !functions.contains(call)
+                && let Some(reference) = FunctionRef::parse(call)
+                && VersionReq::parse(reference.version).is_some()
+            {
+                found.insert(
+                    function_key(&reference),
+                    RangeRef {
+                        namespace: None,
+                        name: reference.name.to_string(),
+                        range: reference.version.to_string(),
+                        catalog: reference.catalog.map(str::to_string),
+                    },
+                );
+            }
+            fields
+                .values()
+                .for_each(|field| referenced_ranges(field, functions, found));
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| referenced_ranges(item, functions, found)),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+impl DurableEngine {
+    /// The metadata to record when an instance of `workflow` starts
+    ///
+    /// Adds the versions the ranges of the workflow resolve to, the workflows
+    /// calling the instance and, if manifests are recorded, the run manifest
+    /// of the instance, unless the instance has already started and keeps
+    /// what it was pinned to.
+    ///
+    /// # Errors
+    /// Returns an error if no published version matches a range.
+    pub(super) async fn start_metadata(
+        &self,
+        workflow: &WorkflowDefinition,
+        instance_id: &str,
+        input: &Value,
+        call_chain: &[String],
+    ) -> Result<Option<serde_json::Map<String, Value>>> {
+        let metadata = self.workflow_metadata(workflow).await;
+        let started = self
+            .persistence
+            .get_events(instance_id)
+            .await?
+            .iter()
+            .any(|event| matches!(event, WorkflowEvent::WorkflowStarted { .. }));
+        if started {
+            return Ok(metadata);
+        }
+
+        let resolved = self.resolve_versions(workflow).await?;
+        if resolved.is_empty() && self.manifest_config.is_none() && call_chain.is_empty() {
+            return Ok(metadata);
+        }
+        let mut metadata = metadata.unwrap_or_default();
+        if !resolved.is_empty() {
+            metadata.insert(RESOLVED_VERSIONS.to_string(), Value::Object(resolved));
+        }
+        if !call_chain.is_empty() {
+            metadata.insert(CALL_CHAIN.to_string(), serde_json::to_value(call_chain)?);
+        }
+        if let Some(config) = &self.manifest_config {
+            let manifest = self.run_manifest(workflow, input, config).await?;
+            metadata.insert(MANIFEST.to_string(), serde_json::to_value(manifest)?);
+        }
+        Ok(Some(metadata))
+    }
+
+    /// Resolve the version ranges `workflow` references
+    ///
+    /// Returns the version chosen for each range by its key, see
+    /// [`workflow_key`] and [`function_key`].
+    ///
+    /// # Errors
+    /// Returns an error if no published version matches a range.
+    pub(super) async fn resolve_versions(
+        &self,
+        workflow: &WorkflowDefinition,
+    ) -> Result<serde_json::Map<String, Value>> {
+        let functions: Vec<String> = workflow
+            .use_
+            .as_ref()
+            .and_then(|use_| use_.functions.as_ref())
+            .map(|functions| functions.keys().cloned().collect())
+            .unwrap_or_default();
+        let mut ranges = BTreeMap::new();
+        referenced_ranges(
+            &serde_json::to_value(&workflow.do_)?,
+            &functions,
+            &mut ranges,
+        );
+        // Without a catalog, `name:version` calls are not catalog functions,
+        // unless they name one
+        if catalog_uri(workflow, None).is_none() {
+            ranges.retain(|_, reference| {
+                reference.namespace.is_some() || reference.catalog.is_some()
+            });
+        }
+
+        let mut resolved = serde_json::Map::new();
+        for (
+            key,
+            RangeRef {
+                namespace,
+                name,
+                range,
+                catalog,
+            },
+        ) in ranges
+        {
+            let version = match namespace {
+                Some(namespace) => {
+                    self.resolve_workflow_version(&namespace, &name, &range)
+                        .await?
+                }
+                None => {
+                    let reference = FunctionRef {
+                        name: &name,
+                        version: &range,
+                        catalog: catalog.as_deref(),
+                    };
+                    self.resolve_function_version(workflow, &reference).await?
+                }
+            };
+            resolved.insert(key, Value::String(version));
+        }
+        Ok(resolved)
+    }
+
+    /// The highest version of a workflow in `range`, registered or in the
+    /// engine's workflow sources
+    pub(super) async fn resolve_workflow_version(
+        &self,
+        namespace: &str,
+        name: &str,
+        range: &str,
+    ) -> Result<String> {
+        let req = parse_range(range)?;
+        let versions = self.workflow_versions(namespace, name).await?;
+        req.best_match(versions.iter().map(String::as_str))
+            .map(str::to_string)
+            .ok_or_else(|| {
+                let versions: Vec<&str> = versions.iter().map(String::as_str).collect();
+                no_match(&format!("{namespace}/{name}"), range, &versions)
+            })
+    }
+
+    /// The highest version of a catalog function in `range`
+    ///
+    /// Only local and git catalogs can list the versions they publish: each
+    /// version of a function is a directory under the function's directory.
+    pub(super) async fn resolve_function_version(
+        &self,
+        workflow: &WorkflowDefinition,
+        reference: &FunctionRef<'_>,
+    ) -> Result<String> {
+        let FunctionRef {
+            name,
+            version: range,
+            catalog,
+        } = *reference;
+        let req = parse_range(range)?;
+        let Some(uri) = catalog_uri(workflow, catalog) else {
+            return Err(Error::Configuration {
+                message: match catalog {
+                    Some(catalog) => format!(
+                        "Function {name}:{range} names catalog '{catalog}', which the workflow does not use"
+                    ),
+                    None => format!(
+                        "Function {name}:{range} names a version range, but the workflow uses no catalog"
+                    ),
+                },
+            });
+        };
+        let Some(root) = self
+            .catalog_root(workflow, &CatalogSource::parse(&uri)?)
+            .await?
+        else {
+            return Err(Error::Configuration {
+                message: format!(
+                    "Cannot resolve {name}:{range}: {uri} is an HTTP catalog, which does not list its versions"
+                ),
+            });
+        };
+
+        let mut versions = Vec::new();
+        if let Ok(mut entries) = tokio::fs::read_dir(root.path.join(name)).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                if let Some(version) = entry.file_name().to_str() {
+                    versions.push(version.to_string());
+                }
+            }
+        }
+        req.best_match(versions.iter().map(String::as_str))
+            .map(str::to_string)
+            .ok_or_else(|| {
+                let versions: Vec<&str> = versions.iter().map(String::as_str).collect();
+                no_match(name, range, &versions)
+            })
+    }
+}
+
+fn parse_range(range: &str) -> Result<VersionReq> {
+    VersionReq::parse(range).ok_or_else(|| Error::Configuration {
+        message: format!("Invalid version range: {range}"),
+    })
+}
+
+fn no_match(reference: &str, range: &str, versions: &[&str]) -> Error {
+    let mut versions = versions.to_vec();
+    versions.sort_by_key(|version| Version::parse(version));
+    Error::Configuration {
+        message: format!(
+            "No version of {reference} matches {range} (available: {})",
+            if versions.is_empty() {
+                "none".to_string()
+            } else {
+                versions.join(", ")
+            }
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    fn matches(range: &str, version: &str) -> bool {
+        VersionReq::parse(range)
+            .unwrap()
+            .matches(&Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn test_plain_versions_are_not_ranges() {
+        assert_eq!(VersionReq::parse("1.0.0"), None);
+        assert_eq!(VersionReq::parse("1.2"), None);
+        assert_eq!(VersionReq::parse("latest"), None);
+        assert!(VersionReq::parse("^1.2").is_some());
+        assert!(VersionReq::parse("1.x").is_some());
+    }
+
+    #[test]
+    fn test_caret_and_tilde_ranges() {
+        assert!(matches("^1.2", "1.2.0"));
+        assert!(matches("^1.2", "1.9.3"));
+        assert!(!matches("^1.2", "1.1.9"));
+        assert!(!matches("^1.2", "2.0.0"));
+        assert!(matches("^0.2.1", "0.2.5"));
+        assert!(!matches("^0.2.1", "0.3.0"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+
+        assert!(matches("~2.0", "2.0.7"));
+        assert!(!matches("~2.0", "2.1.0"));
+        assert!(matches("~2", "2.5.0"));
+        assert!(!matches("~1.2.3", "1.2.2"));
+    }
+
+    #[test]
+    fn test_comparisons_and_wildcards() {
+        assert!(matches(">=1.2, <1.5", "1.4.9"));
+        assert!(!matches(">=1.2, <1.5", "1.5.0"));
+        assert!(matches(">1.2", "1.3.0"));
+        assert!(!matches(">1.2", "1.2.9"));
+        assert!(matches("<=1.2", "1.2.9"));
+        assert!(matches("1.x", "1.7.0"));
+        assert!(!matches("1.x", "2.0.0"));
+        assert!(matches("*", "0.0.1"));
+        assert!(matches("=1.2.3", "1.2.3"));
+    }
+
+    #[test]
+    fn test_best_match_picks_highest_version_as_written() {
+        let req = VersionReq::parse("^1.0").unwrap();
+        assert_eq!(
+            req.best_match(["1.0.0", "1.10.0", "1.9.0", "2.0.0", "1.11.0-beta"]),
+            Some("1.10.0")
+        );
+        assert_eq!(req.best_match(["0.9.0", "2.0.0"]), None);
+    }
+
+    #[test]
+    fn test_referenced_ranges() {
+        let tasks = json!([
+            { "child": { "run": { "workflow": { "namespace": "default", "name": "child", "version": "^1.0" } } } },
+            { "pinned": { "run": { "workflow": { "namespace": "default", "name": "child", "version": "1.0.0" } } } },
+            { "greet": { "call": "greet:~2.1" } },
+            { "local": { "call": "local:^1" } },
+        ]);
+        let mut found = BTreeMap::new();
+        referenced_ranges(&tasks, &["local:^1".to_string()], &mut found);
+        assert_eq!(
+            found.keys().collect::<Vec<_>>(),
+            ["function:greet@~2.1", "workflow:default/child@^1.0"]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Watch expressions evaluated after every task
+//!
+//! Watch expressions follow a few values of the workflow data, such as the
+//! status of an order, as the instance runs. Their values are printed and
+//! recorded in the instance history after each task completes.
+
+use serde_json::{Map, Value};
+
+use super::{DurableEngine, Result};
+use crate::context::Context;
+use crate::expressions::Evaluation;
+use crate::output;
+use crate::workflow::WorkflowEvent;
+
+impl DurableEngine {
+    /// Evaluate the watch expressions after `task_name` completed
+    ///
+    /// An expression that fails to evaluate is reported as `null` rather than
+    /// failing the task, since watching must not change how an instance runs.
+    pub(super) async fn evaluate_watches(&self, task_name: &str, ctx: &Context) -> Result<()> {
+        if self.watch_exprs.is_empty() {
+            return Ok(());
+        }
+
+        let data = ctx.data().await;
+        let task_input = ctx.task_input().await;
+        let mut values = Map::new();
+        for expr in self.watch_exprs.iter() {
+            let value =
+                evaluate(ctx.metadata.evaluation, expr, &data, &task_input).unwrap_or_else(|e| {
+                    tracing::warn!(expression = %expr, error = %e, "Watch expression failed");
+                    Value::Null
+                });
+            values.insert(expr.clone(), value);
+        }
+
+        output::format_watches(task_name, &values);
+        ctx.services
+            .persistence
+            .save_event(WorkflowEvent::WatchesEvaluated {
+                instance_id: ctx.metadata.instance_id.clone(),
+                task_name: task_name.to_string(),
+                values,
+                timestamp: chrono::Utc::now(),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+fn evaluate(
+    evaluation: Evaluation,
+    expr: &str,
+    data: &Value,
+    task_input: &Value,
+) -> crate::expressions::Result<Value> {
+    if expr.trim().starts_with("${") {
+        crate::expressions::evaluate_expression_with_input(evaluation, expr, data, task_input)
+    } else {
+        crate::expressions::evaluate_jq(expr, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_evaluate_accepts_bare_and_wrapped_expressions() {
+        let data = json!({ "order": { "status": "shipped" } });
+        assert_eq!(
+            evaluate(Evaluation::default(), ".order.status", &data, &json!({})).unwrap(),
+            json!("shipped")
+        );
+        assert_eq!(
+            evaluate(
+                Evaluation::default(),
+                "${ .order.status }",
+                &data,
+                &json!({})
+            )
+            .unwrap(),
+            json!("shipped")
+        );
+    }
+}
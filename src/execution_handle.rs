@@ -174,8 +174,8 @@ impl ExecutionHandle {
 
     /// Cancel the workflow execution
     ///
-    /// This sends a cancellation signal to the workflow. The cancellation is graceful -
-    /// the workflow will stop after the current task completes.
+    /// This sends a cancellation signal to the workflow. The task in flight is
+    /// interrupted and a `WorkflowCancelled` event is persisted and emitted.
     ///
     /// This method is essential for stopping perpetual workflows that run indefinitely.
     ///
@@ -223,6 +223,7 @@ impl ExecutionHandle {
     /// Returns an error if:
     /// - The workflow fails
     /// - The workflow is cancelled
+    /// - The workflow is suspended
     /// - The timeout is exceeded
     /// - The event stream closes unexpectedly
     ///
@@ -258,6 +259,11 @@ impl ExecutionHandle {
                         message: format!("Workflow cancelled: {}", reason.unwrap_or_default()),
                     });
                 }
+                WorkflowEvent::WorkflowSuspended { reason, .. } => {
+                    return Err(Error::WorkflowExecution {
+                        message: format!("Workflow suspended: {}", reason.unwrap_or_default()),
+                    });
+                }
                 WorkflowEvent::WorkflowStarted { .. }
                 | WorkflowEvent::WorkflowCorrelationStarted { .. }
                 | WorkflowEvent::WorkflowCorrelationCompleted { .. }
@@ -267,12 +273,18 @@ impl ExecutionHandle {
                 WorkflowEvent::TaskEntered { .. }
                 | WorkflowEvent::TaskCreated { .. }
                 | WorkflowEvent::TaskRetried { .. }
-                | WorkflowEvent::WorkflowSuspended { .. }
                 | WorkflowEvent::WorkflowResumed { .. }
                 | WorkflowEvent::TaskCancelled { .. }
                 | WorkflowEvent::TaskSuspended { .. }
                 | WorkflowEvent::TaskResumed { .. }
-                | WorkflowEvent::TaskFaulted { .. } => {}
+                | WorkflowEvent::TaskFaulted { .. }
+                | WorkflowEvent::TaskReaped { .. }
+                | WorkflowEvent::TaskCacheHit { .. }
+                | WorkflowEvent::TaskSkipped { .. }
+                | WorkflowEvent::SwitchCaseMatched { .. }
+                | WorkflowEvent::ErrorCaught { .. }
+                | WorkflowEvent::WatchesEvaluated { .. }
+                | WorkflowEvent::ShadowCompared { .. } => {}
             }
 
             if start.elapsed() > timeout {
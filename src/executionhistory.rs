@@ -1,29 +1,457 @@
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 
 use crate::workflow::WorkflowEvent;
 
 #[derive(Clone)]
 pub struct ExecutionHistory {
     completed_tasks: HashMap<String, serde_json::Value>,
+    /// Tasks completed by previous runs, in execution order, with their results
+    completion_order: Vec<(String, serde_json::Value)>,
+    /// Task entered after the last completion that never completed
+    interrupted_task: Option<String>,
+    /// Input the instance was started with
+    initial_data: Option<serde_json::Value>,
+    /// Metadata recorded when the instance was started
+    started_metadata: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 impl ExecutionHistory {
     #[must_use]
     pub fn new(events: &[WorkflowEvent]) -> Self {
         let mut completed_tasks = HashMap::new();
+        let mut completion_order = Vec::new();
+        let mut interrupted_task = None;
+        let mut initial_data = None;
+        let mut started_metadata = None;
         for event in events {
-            if let WorkflowEvent::TaskCompleted {
-                task_name, result, ..
-            } = event
-            {
-                completed_tasks.insert(task_name.clone(), result.clone());
+            match event {
+                WorkflowEvent::WorkflowStarted {
+                    initial_data: data,
+                    metadata,
+                    ..
+                } => {
+                    if initial_data.is_none() {
+                        initial_data = Some(data.clone());
+                        started_metadata.clone_from(metadata);
+                    }
+                }
+                WorkflowEvent::TaskEntered { task_name, .. } => {
+                    interrupted_task = Some(task_name.clone());
+                }
+                WorkflowEvent::TaskCompleted {
+                    task_name, result, ..
+                } => {
+                    completed_tasks.insert(task_name.clone(), result.clone());
+                    completion_order.push((task_name.clone(), result.clone()));
+                    interrupted_task = None;
+                }
+                WorkflowEvent::TaskCreated { .. }
+                | WorkflowEvent::TaskStarted { .. }
+                | WorkflowEvent::TaskRetried { .. }
+                | WorkflowEvent::WorkflowCompleted { .. }
+                | WorkflowEvent::WorkflowCorrelationStarted { .. }
+                | WorkflowEvent::WorkflowCorrelationCompleted { .. }
+                | WorkflowEvent::WorkflowFailed { .. }
+                | WorkflowEvent::WorkflowCancelled { .. }
+                | WorkflowEvent::WorkflowSuspended { .. }
+                | WorkflowEvent::WorkflowResumed { .. }
+                | WorkflowEvent::TaskCancelled { .. }
+                | WorkflowEvent::TaskSuspended { .. }
+                | WorkflowEvent::TaskResumed { .. }
+                | WorkflowEvent::TaskFaulted { .. }
+                | WorkflowEvent::TaskReaped { .. }
+                | WorkflowEvent::TaskCacheHit { .. }
+                | WorkflowEvent::TaskSkipped { .. }
+                | WorkflowEvent::SwitchCaseMatched { .. }
+                | WorkflowEvent::ErrorCaught { .. }
+                | WorkflowEvent::WatchesEvaluated { .. }
+                | WorkflowEvent::ShadowCompared { .. } => {}
             }
         }
-        Self { completed_tasks }
+        Self {
+            completed_tasks,
+            completion_order,
+            interrupted_task,
+            initial_data,
+            started_metadata,
+        }
     }
 
     #[must_use]
     pub fn is_task_completed(&self, task_name: &str) -> Option<&serde_json::Value> {
         self.completed_tasks.get(task_name)
     }
+
+    /// The input recorded when the instance was started
+    #[must_use]
+    pub fn initial_data(&self) -> Option<&serde_json::Value> {
+        self.initial_data.as_ref()
+    }
+
+    /// The metadata recorded when the instance was started
+    #[must_use]
+    pub fn started_metadata(&self) -> Option<&serde_json::Map<String, serde_json::Value>> {
+        self.started_metadata.as_ref()
+    }
+
+    /// Results of the tasks completed by previous runs, kept to skip them on replay
+    pub fn completed_results(&self) -> impl Iterator<Item = &serde_json::Value> {
+        self.completed_tasks.values()
+    }
+
+    /// Tasks completed by previous runs, in execution order, with their results
+    #[must_use]
+    pub fn completed_tasks(&self) -> &[(String, serde_json::Value)] {
+        &self.completion_order
+    }
+
+    /// Start replaying the recorded path of a previous run
+    #[must_use]
+    pub fn replay(&self) -> Replay {
+        Replay {
+            completed: self.completion_order.iter().cloned().collect(),
+            interrupted: self.interrupted_task.clone(),
+        }
+    }
+}
+
+/// Cursor over the tasks a previous run of an instance went through
+///
+/// Completed tasks are replayed from their recorded results instead of being
+/// executed again, following the same path (including branches chosen by
+/// `switch` tasks) until the task that was in flight when the run stopped.
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    completed: VecDeque<(String, serde_json::Value)>,
+    interrupted: Option<String>,
+}
+
+impl Replay {
+    /// Whether every recorded task has been replayed
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.completed.is_empty()
+    }
+
+    /// The next task on the recorded path, if the path records one
+    #[must_use]
+    pub fn next_task(&self) -> Option<&str> {
+        self.completed
+            .front()
+            .map(|(name, _)| name.as_str())
+            .or(self.interrupted.as_deref())
+    }
+
+    /// Take the recorded result of `task_name` if it is the next completed task
+    pub fn take(&mut self, task_name: &str) -> Option<serde_json::Value> {
+        if self
+            .completed
+            .front()
+            .is_some_and(|(name, _)| name == task_name)
+        {
+            self.completed.pop_front().map(|(_, result)| result)
+        } else {
+            None
+        }
+    }
+}
+
+/// One event in the timeline of an instance
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Kind of event, such as `task.completed`
+    pub event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    /// The retry attempt, error, reason or cache key recorded with the event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl TimelineEntry {
+    fn new(event: &WorkflowEvent, kind: &'static str) -> Self {
+        Self {
+            timestamp: event.timestamp(),
+            event: kind,
+            task: None,
+            duration_ms: None,
+            detail: None,
+        }
+    }
+
+    fn task(mut self, task_name: &str) -> Self {
+        self.task = Some(task_name.to_string());
+        self
+    }
+
+    fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Describe an instance's events, in the order they were recorded
+#[must_use]
+pub fn timeline(events: &[WorkflowEvent]) -> Vec<TimelineEntry> {
+    events
+        .iter()
+        .map(|event| match event {
+            WorkflowEvent::WorkflowStarted { workflow_id, .. } => {
+                TimelineEntry::new(event, "workflow.started").detail(workflow_id.as_str())
+            }
+            WorkflowEvent::WorkflowCompleted { duration_ms, .. } => TimelineEntry {
+                duration_ms: Some(*duration_ms),
+                ..TimelineEntry::new(event, "workflow.completed")
+            },
+            WorkflowEvent::WorkflowFailed { error, .. } => {
+                TimelineEntry::new(event, "workflow.faulted").detail(error.as_str())
+            }
+            WorkflowEvent::WorkflowCancelled { reason, .. } => TimelineEntry {
+                detail: reason.clone(),
+                ..TimelineEntry::new(event, "workflow.cancelled")
+            },
+            WorkflowEvent::WorkflowSuspended { reason, .. } => TimelineEntry {
+                detail: reason.clone(),
+                ..TimelineEntry::new(event, "workflow.suspended")
+            },
+            WorkflowEvent::WorkflowResumed { .. } => TimelineEntry::new(event, "workflow.resumed"),
+            WorkflowEvent::WorkflowCorrelationStarted { .. } => {
+                TimelineEntry::new(event, "workflow.correlation.started")
+            }
+            WorkflowEvent::WorkflowCorrelationCompleted {
+                correlation_context,
+                ..
+            } => TimelineEntry::new(event, "workflow.correlation.completed")
+                .detail(correlation_context.as_str()),
+            WorkflowEvent::TaskEntered { task_name, .. } => {
+                TimelineEntry::new(event, "task.entered").task(task_name)
+            }
+            WorkflowEvent::TaskCreated {
+                task_name,
+                task_type,
+                ..
+            } => TimelineEntry::new(event, "task.created")
+                .task(task_name)
+                .detail(task_type.as_str()),
+            WorkflowEvent::TaskStarted { task_name, .. } => {
+                TimelineEntry::new(event, "task.started").task(task_name)
+            }
+            WorkflowEvent::TaskCacheHit {
+                task_name,
+                cache_key,
+                ..
+            } => TimelineEntry::new(event, "task.cache.hit")
+                .task(task_name)
+                .detail(cache_key.as_str()),
+            WorkflowEvent::TaskSkipped {
+                task_name,
+                condition,
+                ..
+            } => TimelineEntry::new(event, "task.skipped")
+                .task(task_name)
+                .detail(condition.as_str()),
+            WorkflowEvent::TaskRetried {
+                task_name, attempt, ..
+            } => TimelineEntry::new(event, "task.retried")
+                .task(task_name)
+                .detail(format!("attempt {attempt}")),
+            WorkflowEvent::TaskCompleted {
+                task_name,
+                duration_ms,
+                ..
+            } => TimelineEntry {
+                duration_ms: Some(*duration_ms),
+                ..TimelineEntry::new(event, "task.completed").task(task_name)
+            },
+            WorkflowEvent::TaskFaulted {
+                task_name, error, ..
+            } => TimelineEntry::new(event, "task.faulted")
+                .task(task_name)
+                .detail(error.as_str()),
+            WorkflowEvent::TaskCancelled {
+                task_name, reason, ..
+            } => TimelineEntry {
+                detail: reason.clone(),
+                ..TimelineEntry::new(event, "task.cancelled").task(task_name)
+            },
+            WorkflowEvent::TaskSuspended { task_name, .. } => {
+                TimelineEntry::new(event, "task.suspended").task(task_name)
+            }
+            WorkflowEvent::TaskResumed { task_name, .. } => {
+                TimelineEntry::new(event, "task.resumed").task(task_name)
+            }
+            WorkflowEvent::SwitchCaseMatched {
+                task_name, case, ..
+            } => TimelineEntry::new(event, "task.switch.matched")
+                .task(task_name)
+                .detail(case.as_str()),
+            WorkflowEvent::ErrorCaught {
+                task_name, error, ..
+            } => TimelineEntry::new(event, "task.error.caught")
+                .task(task_name)
+                .detail(
+                    error
+                        .get("title")
+                        .and_then(serde_json::Value::as_str)
+                        .unwrap_or("error"),
+                ),
+            WorkflowEvent::TaskReaped {
+                task_name,
+                reason,
+                resources,
+                abandoned,
+                ..
+            } => {
+                let mut detail = format!("{reason}, {} stopped", resources.len());
+                if *abandoned {
+                    detail.push_str(", abandoned");
+                }
+                TimelineEntry::new(event, "task.reaped")
+                    .task(task_name)
+                    .detail(detail)
+            }
+            WorkflowEvent::WatchesEvaluated {
+                task_name, values, ..
+            } => {
+                let detail = values
+                    .iter()
+                    .map(|(expr, value)| format!("{expr} = {value}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                TimelineEntry::new(event, "task.watched")
+                    .task(task_name)
+                    .detail(detail)
+            }
+            WorkflowEvent::ShadowCompared {
+                candidate,
+                differences,
+                error,
+                ..
+            } => {
+                let detail = match error {
+                    Some(error) => format!("{candidate} failed: {error}"),
+                    None => format!("{candidate}, {} difference(s)", differences.len()),
+                };
+                TimelineEntry::new(event, "workflow.shadowed").detail(detail)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn entered(task_name: &str) -> WorkflowEvent {
+        WorkflowEvent::TaskEntered {
+            instance_id: "i".to_string(),
+            task_name: task_name.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn completed(task_name: &str, result: serde_json::Value) -> WorkflowEvent {
+        WorkflowEvent::TaskCompleted {
+            instance_id: "i".to_string(),
+            task_name: task_name.to_string(),
+            result,
+            timestamp: Utc::now(),
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_replay_follows_recorded_path_to_interrupted_task() {
+        let history = ExecutionHistory::new(&[
+            WorkflowEvent::WorkflowStarted {
+                instance_id: "i".to_string(),
+                workflow_id: "wf".to_string(),
+                timestamp: Utc::now(),
+                initial_data: json!({"n": 1}),
+                metadata: None,
+            },
+            entered("check"),
+            completed("check", json!({"n": 1})),
+            entered("branchB"),
+            completed("branchB", json!({"n": 2})),
+            entered("finish"),
+        ]);
+        assert_eq!(history.initial_data(), Some(&json!({"n": 1})));
+
+        let mut replay = history.replay();
+        assert_eq!(replay.next_task(), Some("check"));
+        assert_eq!(replay.take("branchB"), None);
+        assert_eq!(replay.take("check"), Some(json!({"n": 1})));
+        assert_eq!(replay.next_task(), Some("branchB"));
+        assert_eq!(replay.take("branchB"), Some(json!({"n": 2})));
+        assert!(replay.is_finished());
+        assert_eq!(replay.next_task(), Some("finish"));
+        assert_eq!(replay.take("finish"), None);
+    }
+
+    #[test]
+    fn test_timeline_describes_events_in_order() {
+        let entries = timeline(&[
+            entered("fetch"),
+            WorkflowEvent::TaskCacheHit {
+                instance_id: "i".to_string(),
+                task_name: "fetch".to_string(),
+                cache_key: "abc123".to_string(),
+                cached_at: Utc::now(),
+                timestamp: Utc::now(),
+            },
+            completed("fetch", json!({})),
+            WorkflowEvent::TaskRetried {
+                instance_id: "i".to_string(),
+                task_name: "send".to_string(),
+                attempt: 2,
+                timestamp: Utc::now(),
+            },
+            WorkflowEvent::TaskFaulted {
+                instance_id: "i".to_string(),
+                task_name: "send".to_string(),
+                error: "Connection refused".to_string(),
+                timestamp: Utc::now(),
+            },
+        ]);
+
+        let kinds: Vec<&str> = entries.iter().map(|entry| entry.event).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "task.entered",
+                "task.cache.hit",
+                "task.completed",
+                "task.retried",
+                "task.faulted"
+            ]
+        );
+        let hit = entries.get(1).unwrap();
+        assert_eq!(hit.task.as_deref(), Some("fetch"));
+        assert_eq!(hit.detail.as_deref(), Some("abc123"));
+        assert_eq!(entries.get(2).unwrap().duration_ms, Some(0));
+        assert_eq!(entries.get(3).unwrap().detail.as_deref(), Some("attempt 2"));
+        assert_eq!(
+            entries.get(4).unwrap().detail.as_deref(),
+            Some("Connection refused")
+        );
+    }
+
+    #[test]
+    fn test_replay_of_new_instance_is_empty() {
+        let replay = ExecutionHistory::new(&[]).replay();
+        assert!(replay.is_finished());
+        assert_eq!(replay.next_task(), None);
+    }
 }
@@ -1,7 +1,10 @@
 use crate::context::Context;
 use crate::task_output::TaskOutputStreamer;
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -10,21 +13,517 @@ pub enum Error {
 
     #[snafu(display("Task error: {message}"))]
     Task { message: String },
+
+    #[snafu(display("Invalid parameters for {call_type}: {message}"))]
+    InvalidParams { call_type: String, message: String },
+
+    #[snafu(display("Cancelled: {message}"))]
+    Cancelled { message: String },
+
+    /// A failure of the work a task started, categorized by what went wrong
+    #[snafu(display("Execution error: {message}"))]
+    Failed { kind: ErrorKind, message: String },
+
+    /// A failure the executor described as a workflow error, e.g. a request
+    /// the remote service rejected
+    #[snafu(display("Execution error: {error}"))]
+    Raised {
+        kind: ErrorKind,
+        error: WorkflowError,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// What went wrong, if the executor categorized the failure
+    #[must_use]
+    pub fn kind(&self) -> Option<ErrorKind> {
+        if let Self::Failed { kind, .. } | Self::Raised { kind, .. } = self {
+            Some(*kind)
+        } else {
+            None
+        }
+    }
+
+    /// The workflow error the executor described the failure with, if any
+    #[must_use]
+    pub fn workflow_error(&self) -> Option<&WorkflowError> {
+        if let Self::Raised { error, .. } = self {
+            Some(error)
+        } else {
+            None
+        }
+    }
+
+    /// Whether running the task again may succeed
+    ///
+    /// Uncategorized failures are not known to be retryable.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_some_and(ErrorKind::is_retryable)
+    }
+}
+
+/// Category of a failed task, which tells whether trying again may succeed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The remote service could not be reached or the connection broke
+    Network,
+    /// The remote service or the program did not answer in time
+    Timeout,
+    /// The remote service rejected the request with a 4xx status
+    Client { status: u16 },
+    /// The remote service failed with a 5xx status
+    Server { status: u16 },
+    /// The script or function ran and failed
+    Script,
+    /// The task could not be run, e.g. because an interpreter is missing
+    Infrastructure,
+}
+
+impl ErrorKind {
+    /// Categorize an HTTP status that is not a success
+    #[must_use]
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            408 => Self::Timeout,
+            400..=499 => Self::Client { status },
+            _ => Self::Server { status },
+        }
+    }
+
+    /// Categorize a failed HTTP request
+    #[must_use]
+    pub fn from_request(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Self::Timeout
+        } else if let Some(status) = error.status() {
+            Self::from_status(status.as_u16())
+        } else {
+            Self::Network
+        }
+    }
+
+    /// Whether a failure of this kind is transient
+    ///
+    /// Requests rejected by the client-side statuses are not retried, except
+    /// when the service asked to slow down (429).
+    #[must_use]
+    pub fn is_retryable(self) -> bool {
+        match self {
+            Self::Network | Self::Timeout | Self::Server { .. } => true,
+            Self::Client { status } => status == 429,
+            Self::Script | Self::Infrastructure => false,
+        }
+    }
+
+    /// Name of the category, as exposed to `catch` filters
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::Timeout => "timeout",
+            Self::Client { .. } => "client",
+            Self::Server { .. } => "server",
+            Self::Script => "script",
+            Self::Infrastructure => "infrastructure",
+        }
+    }
+
+    /// The workflow error type of a failure of this kind
+    #[must_use]
+    pub fn error_type(self) -> &'static str {
+        match self {
+            Self::Network | Self::Client { .. } | Self::Server { .. } => {
+                WorkflowError::COMMUNICATION
+            }
+            Self::Timeout => WorkflowError::TIMEOUT,
+            Self::Script | Self::Infrastructure => WorkflowError::RUNTIME,
+        }
+    }
+
+    /// The status reported with a failure of this kind
+    #[must_use]
+    pub fn status(self) -> u16 {
+        match self {
+            Self::Client { status } | Self::Server { status } => status,
+            Self::Timeout => 408,
+            Self::Network => 503,
+            Self::Script | Self::Infrastructure => 500,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Prefix of the standard error types
+const STANDARD_TYPE_PREFIX: &str = "https://serverlessworkflow.io/dsl/errors/types/";
+
+/// Prefix of the standard error types as the 1.0.0 specification spells them,
+/// followed by the specification version and `/errors/`
+const SPEC_TYPE_PREFIX: &str = "https://serverlessworkflow.io/spec/";
+
+/// An error as the Serverless Workflow specification describes it
+///
+/// Raised errors, failed calls and other faults are all described this way,
+/// so `catch.errors.with` filters can match any of their fields. The display
+/// is the JSON object, which is also how failed instances record it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkflowError {
+    /// URI identifying the type of the error
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// The status code of the error, e.g. the HTTP status of a failed request
+    pub status: u16,
+    /// A short summary of the type of the error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// What went wrong in this occurrence of the error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// JSON pointer to the component of the workflow the error occurred in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+}
+
+impl WorkflowError {
+    /// The workflow definition is invalid
+    pub const CONFIGURATION: &'static str =
+        "https://serverlessworkflow.io/dsl/errors/types/configuration";
+    /// Data does not match the schema it should follow
+    #[allow(dead_code)]
+    pub const VALIDATION: &'static str =
+        "https://serverlessworkflow.io/dsl/errors/types/validation";
+    /// A runtime expression could not be evaluated
+    pub const EXPRESSION: &'static str =
+        "https://serverlessworkflow.io/dsl/errors/types/expression";
+    /// Authenticating with a service failed
+    pub const AUTHENTICATION: &'static str =
+        "https://serverlessworkflow.io/dsl/errors/types/authentication";
+    /// The workflow is not allowed to do something
+    pub const AUTHORIZATION: &'static str =
+        "https://serverlessworkflow.io/dsl/errors/types/authorization";
+    /// A task or the workflow ran out of time
+    pub const TIMEOUT: &'static str = "https://serverlessworkflow.io/dsl/errors/types/timeout";
+    /// Communicating with a service failed
+    pub const COMMUNICATION: &'static str =
+        "https://serverlessworkflow.io/dsl/errors/types/communication";
+    /// Anything else that went wrong while running the workflow
+    pub const RUNTIME: &'static str = "https://serverlessworkflow.io/dsl/errors/types/runtime";
+
+    /// An error of `type_` with `status`, titled `title`
+    #[must_use]
+    pub fn new(type_: &str, status: u16, title: impl Into<String>) -> Self {
+        Self {
+            type_: type_.to_string(),
+            status,
+            title: Some(title.into()),
+            detail: None,
+            instance: None,
+        }
+    }
+
+    /// Set what went wrong in this occurrence of the error
+    #[must_use]
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Set the component of the workflow the error occurred in
+    #[must_use]
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Whether two error type URIs name the same type
+    ///
+    /// The standard types can be given as the DSL reference spells them, e.g.
+    /// `https://serverlessworkflow.io/dsl/errors/types/timeout`, or as the
+    /// 1.0.0 specification does, e.g.
+    /// `https://serverlessworkflow.io/spec/1.0.0/errors/timeout`.
+    #[must_use]
+    pub fn types_match(a: &str, b: &str) -> bool {
+        normalize_type(a) == normalize_type(b)
+    }
+}
+
+/// The DSL spelling of a standard error type, or the type itself
+fn normalize_type(type_: &str) -> std::borrow::Cow<'_, str> {
+    let standard = type_
+        .strip_prefix(SPEC_TYPE_PREFIX)
+        .and_then(|rest| rest.split_once("/errors/"))
+        .map(|(_, name)| name);
+    match standard {
+        Some(name) if !name.contains('/') => format!("{STANDARD_TYPE_PREFIX}{name}").into(),
+        Some(_) | None => type_.into(),
+    }
+}
+
+impl std::fmt::Display for WorkflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| std::fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+/// Optional behaviour of an executor, which the engine adapts to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Output is printed through the task's streamer while the task runs
+    pub supports_streaming: bool,
+    /// The work `exec` started, e.g. a child process or a request, is stopped
+    /// promptly when the cancellation token fires
+    #[allow(dead_code)]
+    pub supports_cancellation: bool,
+}
+
+/// Whether an executor can run tasks, as reported by [`Executor::health_check`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Health {
+    /// Version of the program the executor runs tasks with, such as an interpreter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Dependencies that could not be found
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+impl Health {
+    /// Whether nothing the executor depends on is missing
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// An endpoint given either as a URI or as an object with a `uri` field
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Endpoint {
+    Uri(String),
+    Definition {
+        uri: String,
+        #[serde(default)]
+        authentication: Option<serde_json::Value>,
+    },
+}
+
+impl Endpoint {
+    #[must_use]
+    pub fn uri(&self) -> &str {
+        match self {
+            Self::Uri(uri) | Self::Definition { uri, .. } => uri,
+        }
+    }
+
+    /// The authentication policy given with the endpoint, if any
+    #[must_use]
+    pub fn authentication(&self) -> Option<&serde_json::Value> {
+        match self {
+            Self::Uri(_) => None,
+            Self::Definition { authentication, .. } => authentication.as_ref(),
+        }
+    }
+}
+
+/// Deserialize the evaluated parameters of a task into an executor's parameter type
+///
+/// # Errors
+///
+/// Returns an error naming `call_type` if the parameters do not match `P`
+pub fn parse_params<P: DeserializeOwned>(call_type: &str, params: &serde_json::Value) -> Result<P> {
+    serde_json::from_value(params.clone()).map_err(|e| Error::InvalidParams {
+        call_type: call_type.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// Check a task's parameters before it runs, e.g. when the workflow graph is built
+///
+/// Parameters are evaluated only when the task runs, so parameters that
+/// contain runtime expressions are accepted even if they do not deserialize
+/// yet: an expression may stand for a number or an object.
+///
+/// # Errors
+///
+/// Returns an error if parameters without expressions do not match `P`
+pub fn validate_params<P: DeserializeOwned>(
+    call_type: &str,
+    params: &serde_json::Value,
+) -> Result<()> {
+    match parse_params::<P>(call_type, params) {
+        Ok(_) => Ok(()),
+        Err(_) if contains_expression(params) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn contains_expression(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::String(s) => s.contains("${"),
+        serde_json::Value::Array(items) => items.iter().any(contains_expression),
+        serde_json::Value::Object(map) => map.values().any(contains_expression),
+        serde_json::Value::Null | serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {
+            false
+        }
+    }
+}
+
 #[async_trait]
 pub trait Executor: Send + Sync {
+    /// Run a task
+    ///
+    /// `cancel` fires when the instance is cancelled or suspended, or the task
+    /// times out. Executors that support cancellation stop the work they
+    /// started and return [`Error::Cancelled`].
     async fn exec(
         &self,
         task_name: &str,
         params: &serde_json::Value,
         ctx: &Context,
         streamer: Option<TaskOutputStreamer>,
+        cancel: &CancellationToken,
     ) -> Result<serde_json::Value>;
 
+    /// What this executor supports beyond running a task to completion
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Check that the programs this executor depends on are available
+    ///
+    /// Executors that run tasks with an external program, such as an
+    /// interpreter, report its version or that it is missing. The check runs
+    /// once per executor and its result is reused.
+    async fn health_check(&self) -> Health {
+        Health::default()
+    }
+
+    /// Take the result of a [`Executor::health_check`] made by an earlier
+    /// process, such as the one that took an engine snapshot
+    ///
+    /// Executors that check once and reuse the result use `health` instead of
+    /// checking again; the others ignore it.
+    fn restore_health(&self, _health: Health) {}
+
     /// Downcast to concrete type for special handling
     #[allow(dead_code)]
     fn as_any(&self) -> &dyn std::any::Any;
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Params {
+        #[allow(dead_code)]
+        endpoint: Endpoint,
+        #[allow(dead_code)]
+        port: u16,
+    }
+
+    #[test]
+    fn test_endpoint_forms() {
+        let uri: Endpoint = serde_json::from_value(json!("http://a")).unwrap();
+        assert_eq!(uri.uri(), "http://a");
+        assert!(uri.authentication().is_none());
+
+        let definition: Endpoint = serde_json::from_value(json!({
+            "uri": "http://b",
+            "authentication": { "basic": { "username": "u", "password": "p" } }
+        }))
+        .unwrap();
+        assert_eq!(definition.uri(), "http://b");
+        assert!(definition.authentication().is_some());
+    }
+
+    #[test]
+    fn test_validate_params_allows_expressions() {
+        assert!(validate_params::<Params>("test", &json!({ "endpoint": "a", "port": 1 })).is_ok());
+        assert!(validate_params::<Params>("test", &json!({ "endpoint": "a" })).is_err());
+        assert!(
+            validate_params::<Params>("test", &json!({ "endpoint": "a", "port": "x" })).is_err()
+        );
+        assert!(
+            validate_params::<Params>("test", &json!({ "endpoint": "a", "port": "${ .port }" }))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_error_kind_from_status() {
+        assert_eq!(
+            ErrorKind::from_status(404),
+            ErrorKind::Client { status: 404 }
+        );
+        assert_eq!(ErrorKind::from_status(408), ErrorKind::Timeout);
+        assert_eq!(
+            ErrorKind::from_status(503),
+            ErrorKind::Server { status: 503 }
+        );
+
+        assert!(!ErrorKind::from_status(404).is_retryable());
+        assert!(ErrorKind::from_status(429).is_retryable());
+        assert!(ErrorKind::from_status(503).is_retryable());
+        assert!(!ErrorKind::Script.is_retryable());
+
+        let error = Error::Failed {
+            kind: ErrorKind::Network,
+            message: "connection refused".to_string(),
+        };
+        assert_eq!(error.kind(), Some(ErrorKind::Network));
+        assert!(error.is_retryable());
+        assert!(
+            !Error::Execution {
+                message: "unknown".to_string()
+            }
+            .is_retryable()
+        );
+    }
+
+    #[test]
+    fn test_workflow_error_matches_spec_types() {
+        let error = WorkflowError::new(WorkflowError::COMMUNICATION, 503, "Communication Error")
+            .with_detail("connection refused");
+        assert!(WorkflowError::types_match(
+            &error.type_,
+            "https://serverlessworkflow.io/spec/1.0.0/errors/communication"
+        ));
+        assert!(!WorkflowError::types_match(
+            &error.type_,
+            "https://serverlessworkflow.io/spec/1.0.0/errors/runtime"
+        ));
+        assert!(WorkflowError::types_match(
+            "https://example.com/errors/rejected",
+            "https://example.com/errors/rejected"
+        ));
+        assert!(!WorkflowError::types_match(
+            "https://example.com/errors/rejected",
+            WorkflowError::RUNTIME
+        ));
+
+        let json: serde_json::Value = serde_json::from_str(&error.to_string()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": WorkflowError::COMMUNICATION,
+                "status": 503,
+                "title": "Communication Error",
+                "detail": "connection refused",
+            })
+        );
+    }
+}
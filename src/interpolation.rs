@@ -0,0 +1,302 @@
+//! Load-time environment variable interpolation for workflow documents
+//!
+//! Placeholders of the form `${{ env.NAME }}` are substituted when a workflow
+//! is loaded, so endpoints, image names and paths can adapt to the
+//! environment. The document is parsed first and placeholders are replaced
+//! only inside its string scalars, so a variable's value can never add keys
+//! or tasks to the workflow, whatever YAML it contains. They are unrelated to
+//! runtime JQ expressions (`${ ... }`), which are left untouched.
+
+use regex::{Captures, Regex};
+use serde_yaml::Value;
+use snafu::prelude::*;
+use std::sync::LazyLock;
+
+/// Regex for `${{ env.NAME }}` placeholders
+#[allow(clippy::expect_used)]
+static RE_ENV_PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\$\{\{\s*env\.([A-Za-z_][A-Za-z0-9_]*)\s*\}\}")
+        .expect("hardcoded regex should be valid")
+});
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Environment variable '{name}' is not set (referenced on line {line})"))]
+    MissingVariable { name: String, line: usize },
+
+    #[snafu(display("Failed to parse the document: {source}"))]
+    Parse { source: serde_yaml::Error },
+
+    #[snafu(display("Failed to write the interpolated document: {source}"))]
+    Serialize { source: serde_yaml::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Substitute `${{ env.NAME }}` placeholders in a YAML document using the
+/// process environment
+///
+/// In strict mode a reference to an unset variable is an error; otherwise the
+/// placeholder is replaced with an empty string and a warning is logged.
+///
+/// # Errors
+///
+/// Returns an error if the document is not valid YAML, or in strict mode if a
+/// referenced variable is not set.
+pub fn interpolate_env(source: &str, strict: bool) -> Result<String> {
+    interpolate_with(source, strict, |name| std::env::var(name).ok())
+}
+
+/// Substitute `${{ env.NAME }}` placeholders in a YAML document using a
+/// custom variable lookup
+///
+/// Returns the document as YAML again. A string scalar that is a single
+/// placeholder takes the type its value reads as, so `port: ${{ env.PORT }}`
+/// stays a number; a value that reads as a mapping or a sequence stays a
+/// string.
+///
+/// # Errors
+///
+/// Returns an error if the document is not valid YAML, or in strict mode if
+/// `lookup` returns `None` for a referenced variable.
+pub fn interpolate_with<F>(source: &str, strict: bool, lookup: F) -> Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if !RE_ENV_PLACEHOLDER.is_match(source) {
+        return Ok(source.to_string());
+    }
+
+    let mut document: Value = serde_yaml::from_str(source).context(ParseSnafu)?;
+    let mut missing: Option<Error> = None;
+    substitute(&mut document, &mut |name| {
+        resolve(source, strict, &lookup, &mut missing, name)
+    });
+    match missing {
+        Some(err) => Err(err),
+        None => serde_yaml::to_string(&document).context(SerializeSnafu),
+    }
+}
+
+/// Substitute `${{ env.NAME }}` placeholders in a single string, such as a
+/// scalar already read from a document, using the process environment
+///
+/// # Errors
+///
+/// Returns an error in strict mode if a referenced variable is not set.
+pub fn interpolate_text(text: &str, strict: bool) -> Result<String> {
+    let lookup = |name: &str| std::env::var(name).ok();
+    let mut missing: Option<Error> = None;
+    let result = replace(text, &mut |name| {
+        resolve(text, strict, &lookup, &mut missing, name)
+    });
+    match missing {
+        Some(err) => Err(err),
+        None => Ok(result),
+    }
+}
+
+/// Names of all environment variables referenced by `${{ env.NAME }}` placeholders
+#[must_use]
+pub fn referenced_env_vars(source: &str) -> Vec<String> {
+    let mut names: Vec<String> = RE_ENV_PLACEHOLDER
+        .captures_iter(source)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str().to_string()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Substitute the placeholders of every string scalar and mapping key in `value`
+fn substitute(value: &mut Value, resolve: &mut dyn FnMut(&str) -> String) {
+    match value {
+        Value::String(text) => {
+            if let Some(resolved) = resolve_scalar(text, resolve) {
+                *value = resolved;
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                substitute(item, resolve);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (mut key, mut item) in std::mem::take(mapping) {
+                substitute(&mut key, resolve);
+                substitute(&mut item, resolve);
+                mapping.insert(key, item);
+            }
+        }
+        Value::Tagged(tagged) => substitute(&mut tagged.value, resolve),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// The value of a string scalar with its placeholders substituted, or `None`
+/// if it has none
+fn resolve_scalar(text: &str, resolve: &mut dyn FnMut(&str) -> String) -> Option<Value> {
+    let whole = RE_ENV_PLACEHOLDER.find(text)?.as_str() == text;
+    let replaced = replace(text, resolve);
+    if whole
+        && let Ok(value @ (Value::Null | Value::Bool(_) | Value::Number(_))) =
+            serde_yaml::from_str(&replaced)
+    {
+        return Some(value);
+    }
+    Some(Value::String(replaced))
+}
+
+fn replace(text: &str, resolve: &mut dyn FnMut(&str) -> String) -> String {
+    RE_ENV_PLACEHOLDER
+        .replace_all(text, |caps: &Captures| {
+            resolve(caps.get(1).map_or("", |m| m.as_str()))
+        })
+        .into_owned()
+}
+
+/// The value of variable `name`, or an empty string if it is not set
+///
+/// An unset variable is recorded in `missing` in strict mode, and logged
+/// otherwise, with the line of `source` it is first referenced on.
+fn resolve<F>(
+    source: &str,
+    strict: bool,
+    lookup: &F,
+    missing: &mut Option<Error>,
+    name: &str,
+) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(value) = lookup(name) {
+        return value;
+    }
+
+    let line = RE_ENV_PLACEHOLDER
+        .captures_iter(source)
+        .find(|caps| caps.get(1).is_some_and(|m| m.as_str() == name))
+        .and_then(|caps| caps.get(0))
+        .map_or(0, |m| line_number(source, m.start()));
+    if strict {
+        missing.get_or_insert_with(|| Error::MissingVariable {
+            name: name.to_string(),
+            line,
+        });
+    } else {
+        tracing::warn!(
+            "Environment variable '{}' is not set (line {}), substituting an empty string",
+            name,
+            line
+        );
+    }
+    String::new()
+}
+
+fn line_number(source: &str, offset: usize) -> usize {
+    source
+        .get(..offset)
+        .map_or(0, |prefix| prefix.matches('\n').count())
+        + 1
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "REGISTRY" => Some("ghcr.io/acme".to_string()),
+            "PORT" => Some("8080".to_string()),
+            "INJECTED" => Some("x\nadmin: true\ntasks: [rm]".to_string()),
+            _ => None,
+        }
+    }
+
+    fn yaml(source: &str) -> Value {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn test_substitutes_placeholders() {
+        let source = "image: ${{ env.REGISTRY }}/app\nendpoint: http://localhost:${{env.PORT}}/api";
+        let result = interpolate_with(source, true, lookup).unwrap();
+        assert_eq!(
+            yaml(&result),
+            yaml("image: ghcr.io/acme/app\nendpoint: http://localhost:8080/api")
+        );
+    }
+
+    #[test]
+    fn test_single_placeholder_keeps_scalar_type() {
+        let result = interpolate_with("port: ${{ env.PORT }}", true, lookup).unwrap();
+        assert_eq!(yaml(&result), yaml("port: 8080"));
+    }
+
+    #[test]
+    fn test_values_cannot_inject_yaml() {
+        let source = "name: ${{ env.INJECTED }}\nlabel: a-${{ env.INJECTED }}";
+        let result = interpolate_with(source, true, lookup).unwrap();
+        let document = yaml(&result);
+        let mapping = document.as_mapping().unwrap();
+        assert_eq!(mapping.len(), 2);
+        assert_eq!(
+            mapping.get("name").unwrap(),
+            &Value::String("x\nadmin: true\ntasks: [rm]".to_string())
+        );
+        assert_eq!(
+            mapping.get("label").unwrap(),
+            &Value::String("a-x\nadmin: true\ntasks: [rm]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_leaves_jq_expressions_alone() {
+        let source = "set:\n  a: ${ .foo }\n  b: \"${ {x: .y} }\"\n  c: ${{ env.PORT }}";
+        let result = interpolate_with(source, true, lookup).unwrap();
+        assert_eq!(
+            yaml(&result),
+            yaml("set:\n  a: ${ .foo }\n  b: \"${ {x: .y} }\"\n  c: 8080")
+        );
+    }
+
+    #[test]
+    fn test_documents_without_placeholders_are_returned_as_is() {
+        let source = "# not parsed\nset: { a: 1 }\n";
+        assert_eq!(interpolate_with(source, true, lookup).unwrap(), source);
+    }
+
+    #[test]
+    fn test_strict_mode_reports_missing_variable() {
+        let source = "document:\n  name: test\nimage: ${{ env.MISSING }}";
+        let err = interpolate_with(source, true, lookup).unwrap_err();
+        let Error::MissingVariable { name, line } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(name, "MISSING");
+        assert_eq!(line, 3);
+    }
+
+    #[test]
+    fn test_lax_mode_substitutes_empty_string() {
+        let result = interpolate_with("path: /data/${{ env.MISSING }}", false, lookup).unwrap();
+        assert_eq!(yaml(&result), yaml("path: /data/"));
+    }
+
+    #[test]
+    fn test_interpolate_text_substitutes_a_single_string() {
+        let result = interpolate_text("jackdaw-${{ env.JACKDAW_TEST_UNSET }}", false).unwrap();
+        assert_eq!(result, "jackdaw-");
+        assert!(interpolate_text("${{ env.JACKDAW_TEST_UNSET }}", true).is_err());
+    }
+
+    #[test]
+    fn test_referenced_env_vars() {
+        let source = "a: ${{ env.B }}\nb: ${{ env.A }}\nc: ${{ env.B }}";
+        assert_eq!(referenced_env_vars(source), vec!["A", "B"]);
+    }
+}
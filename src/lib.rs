@@ -22,6 +22,27 @@
 //! - [`listeners`] - Event listeners (HTTP, gRPC)
 //! - [`workflow`] - Workflow parsing and validation
 //!
+//! [`graph`], [`workflow`], [`persistence`], [`cache`], [`secrets`], [`events`],
+//! [`expressions`], [`descriptors`] and [`task_ext`] are re-exported from the
+//! `jackdaw-core` crate, which custom runtimes and providers can depend on
+//! alone.
+//!
+//! ## Public API
+//!
+//! The supported API, which follows semver and is checked with
+//! `cargo semver-checks` before every release, is:
+//!
+//! - the engine: [`builder`], [`durableengine`], [`execution_handle`],
+//!   [`observer`], [`middleware`] and [`telemetry`]
+//! - the providers: [`providers`], and the traits they implement in
+//!   [`persistence`], [`cache`], [`secrets`] and [`events`]
+//! - custom executors and listeners: [`executor`], [`context`] and [`listeners`]
+//! - the modules of `jackdaw-core`, and [`config`]
+//!
+//! [`prelude`] re-exports the types most programs need. The remaining
+//! modules support the `jackdaw` command line and may change in any release;
+//! the ones without a use outside of it are hidden from the documentation.
+//!
 //! ## Example Usage
 //!
 //! ```rust,no_run
@@ -101,24 +122,46 @@
 //!
 //! See [`config::JackdawConfig`] for available options.
 
+#[cfg(feature = "bdd")]
+pub mod bdd;
 pub mod builder;
-pub mod cache;
+pub mod bundle;
 pub mod config;
 pub mod container;
 pub mod context;
-pub mod descriptors;
+pub mod contract;
+pub mod coverage;
 pub mod durableengine;
 pub mod execution_handle;
+#[doc(hidden)]
 pub mod executionhistory;
 pub mod executor;
-pub mod expressions;
+pub mod interpolation;
 pub mod listeners;
+pub mod middleware;
+pub mod mock;
+pub mod observer;
+pub mod oci;
+#[doc(hidden)]
 pub mod output;
-pub mod persistence;
+pub mod prelude;
+#[doc(hidden)]
+pub mod process;
+pub mod profile;
 pub mod providers;
-pub mod task_ext;
+pub mod signing;
+#[doc(hidden)]
+pub mod task_env;
+#[doc(hidden)]
 pub mod task_output;
-pub mod workflow;
+pub mod telemetry;
+pub mod testing;
+
+// The engine core, see the `jackdaw-core` crate
+pub use jackdaw_core::{
+    cache, descriptors, events, expressions, graph, numbers, ordering, persistence, secrets,
+    task_ext, text, workflow,
+};
 
 // Re-export commonly used types for convenience
 pub use builder::DurableEngineBuilder;
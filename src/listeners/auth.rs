@@ -0,0 +1,290 @@
+//! Authentication of the requests HTTP listeners receive
+//!
+//! A listener route accepts any request unless it is given a [`ListenerAuth`]:
+//! a static bearer token, a username and password checked with basic
+//! authentication, or JSON Web Tokens, as issued by OAuth2 and OIDC
+//! providers, whose signature is checked against the keys published at a
+//! JWKS URL. Rejected requests get a `401` with a `WWW-Authenticate` header
+//! and never reach the workflow.
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// How long keys fetched from a JWKS URL are used before they are fetched again
+const JWKS_TTL: Duration = Duration::from_secs(300);
+
+/// Keys are fetched again for a token signed with an unknown key at most this often
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(30);
+
+/// How a listener authenticates the requests it receives, set in the
+/// `authentication` of a listen task's source or in `listener_auth`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum ListenerAuth {
+    /// Requests must carry `Authorization: Bearer <token>`
+    Bearer { token: String },
+    /// Requests must carry these credentials with basic authentication
+    Basic { username: String, password: String },
+    /// Requests must carry a bearer JSON Web Token signed with a key
+    /// published at `jwks_uri`, and issued by `issuer` for `audience` if set
+    #[serde(alias = "oidc")]
+    Jwt {
+        #[serde(rename = "jwksUri")]
+        jwks_uri: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        issuer: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        audience: Option<String>,
+    },
+}
+
+/// Checks requests against a [`ListenerAuth`], caching the keys of a JWKS URL
+#[derive(Debug)]
+pub struct Authenticator {
+    auth: ListenerAuth,
+    client: reqwest::Client,
+    keys: RwLock<Option<(Instant, JwkSet)>>,
+}
+
+impl Authenticator {
+    /// Check requests against `auth`
+    #[must_use]
+    pub fn new(auth: ListenerAuth) -> Self {
+        Self {
+            auth,
+            client: reqwest::Client::new(),
+            keys: RwLock::new(None),
+        }
+    }
+
+    /// Check the credentials in the headers of a request
+    ///
+    /// # Errors
+    /// Returns why the request is rejected.
+    pub async fn check(&self, headers: &HeaderMap) -> Result<(), String> {
+        let authorization = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| "missing Authorization header".to_string())?;
+        match &self.auth {
+            ListenerAuth::Bearer { token } => {
+                let presented = bearer(authorization)?;
+                if constant_time_eq(presented.as_bytes(), token.as_bytes()) {
+                    Ok(())
+                } else {
+                    Err("invalid bearer token".to_string())
+                }
+            }
+            ListenerAuth::Basic { username, password } => {
+                let encoded = authorization
+                    .strip_prefix("Basic ")
+                    .ok_or_else(|| "expected basic credentials".to_string())?;
+                let decoded = BASE64_STANDARD
+                    .decode(encoded.trim())
+                    .map_err(|_| "malformed basic credentials".to_string())?;
+                let expected = format!("{username}:{password}");
+                if constant_time_eq(&decoded, expected.as_bytes()) {
+                    Ok(())
+                } else {
+                    Err("invalid username or password".to_string())
+                }
+            }
+            ListenerAuth::Jwt {
+                jwks_uri,
+                issuer,
+                audience,
+            } => {
+                let token = bearer(authorization)?;
+                self.check_jwt(token, jwks_uri, issuer.as_deref(), audience.as_deref())
+                    .await
+            }
+        }
+    }
+
+    async fn check_jwt(
+        &self,
+        token: &str,
+        jwks_uri: &str,
+        issuer: Option<&str>,
+        audience: Option<&str>,
+    ) -> Result<(), String> {
+        let header =
+            jsonwebtoken::decode_header(token).map_err(|e| format!("malformed token: {e}"))?;
+        let key = self.key(jwks_uri, header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation)
+            .map(|_| ())
+            .map_err(|e| format!("invalid token: {e}"))
+    }
+
+    /// The key a token was signed with, by its key ID
+    ///
+    /// Keys are fetched again once they are stale, or when a token names a
+    /// key that is not known yet, since providers rotate their keys.
+    async fn key(&self, jwks_uri: &str, kid: Option<&str>) -> Result<DecodingKey, String> {
+        let cached = self.keys.read().await.clone();
+        let keys = match cached {
+            Some((fetched, keys))
+                if fetched.elapsed() < JWKS_TTL
+                    && (find(&keys, kid).is_some() || fetched.elapsed() < JWKS_MIN_REFRESH) =>
+            {
+                keys
+            }
+            Some(_) | None => {
+                let keys = self.fetch_keys(jwks_uri).await?;
+                *self.keys.write().await = Some((Instant::now(), keys.clone()));
+                keys
+            }
+        };
+        let jwk = find(&keys, kid).ok_or_else(|| "token signed with an unknown key".to_string())?;
+        DecodingKey::from_jwk(jwk).map_err(|e| format!("unusable key: {e}"))
+    }
+
+    async fn fetch_keys(&self, jwks_uri: &str) -> Result<JwkSet, String> {
+        let response = self
+            .client
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| format!("cannot fetch signing keys: {e}"))?;
+        response
+            .json()
+            .await
+            .map_err(|e| format!("malformed signing keys: {e}"))
+    }
+}
+
+/// The key with ID `kid`, or the only key if the token names none
+fn find<'a>(keys: &'a JwkSet, kid: Option<&str>) -> Option<&'a jsonwebtoken::jwk::Jwk> {
+    match kid {
+        Some(kid) => keys.find(kid),
+        None => match keys.keys.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        },
+    }
+}
+
+fn bearer(authorization: &str) -> Result<&str, String> {
+    authorization
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .ok_or_else(|| "expected a bearer token".to_string())
+}
+
+/// Compare secrets in time that does not depend on where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Middleware rejecting the requests an [`Authenticator`] does not accept
+pub(crate) async fn authorize(
+    State(authenticator): State<Arc<Authenticator>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match authenticator.check(request.headers()).await {
+        Ok(()) => next.run(request).await,
+        Err(message) => {
+            tracing::debug!("Rejected request to {}: {}", request.uri(), message);
+            let challenge = match &authenticator.auth {
+                ListenerAuth::Basic { .. } => "Basic realm=\"jackdaw\"",
+                ListenerAuth::Bearer { .. } | ListenerAuth::Jwt { .. } => {
+                    "Bearer realm=\"jackdaw\""
+                }
+            };
+            let mut response = (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response();
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(challenge),
+            );
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(authorization).unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_must_match() {
+        let authenticator = Authenticator::new(ListenerAuth::Bearer {
+            token: "s3cr3t".to_string(),
+        });
+        assert!(authenticator.check(&headers("Bearer s3cr3t")).await.is_ok());
+        assert!(authenticator.check(&headers("Bearer guess")).await.is_err());
+        assert!(authenticator.check(&HeaderMap::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_basic_credentials_must_match() {
+        let authenticator = Authenticator::new(ListenerAuth::Basic {
+            username: "ops".to_string(),
+            password: "hunter2".to_string(),
+        });
+        let valid = format!("Basic {}", BASE64_STANDARD.encode("ops:hunter2"));
+        let invalid = format!("Basic {}", BASE64_STANDARD.encode("ops:hunter3"));
+        assert!(authenticator.check(&headers(&valid)).await.is_ok());
+        assert!(authenticator.check(&headers(&invalid)).await.is_err());
+        assert!(
+            authenticator
+                .check(&headers("Bearer hunter2"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_listener_auth_parses_from_yaml() {
+        let auth: ListenerAuth = serde_yaml::from_str(
+            "oidc:\n  jwksUri: https://idp.example.com/jwks\n  audience: orders\n",
+        )
+        .unwrap();
+        assert_eq!(
+            auth,
+            ListenerAuth::Jwt {
+                jwks_uri: "https://idp.example.com/jwks".to_string(),
+                issuer: None,
+                audience: Some("orders".to_string()),
+            }
+        );
+    }
+}
@@ -6,6 +6,7 @@ use hyper::service::Service as HyperService;
 use prost::Message;
 use prost_reflect::{DescriptorPool, DynamicMessage, ServiceDescriptor};
 use prost_types::FileDescriptorSet;
+use std::path::Path;
 use std::sync::Arc;
 use std::task::{Context as TaskContext, Poll};
 use tokio::sync::RwLock;
@@ -16,6 +17,18 @@ use tower::Service as TowerService;
 // Type alias for boxed body (tonic 0.14+ made BoxBody private)
 type BoxBody = UnsyncBoxBody<Bytes, Status>;
 
+/// Compile a proto file, resolving its imports relative to the file's own directory
+///
+/// # Errors
+/// Returns an error if the proto file or one of its imports cannot be compiled.
+pub fn compile_proto(proto_path: &str) -> Result<FileDescriptorSet> {
+    let include = Path::new(proto_path)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    Ok(protox::compile([proto_path], [include])?)
+}
+
 /// gRPC listener for handling proto-based service requests
 pub struct GrpcListener {
     /// Bind address (e.g., "localhost:50051")
@@ -101,7 +114,7 @@ impl GrpcListener {
         >,
     ) -> Result<Self> {
         // Compile proto file and build descriptor pool
-        let file_descriptor_set = protox::compile([proto_path], ["."])?;
+        let file_descriptor_set = compile_proto(proto_path)?;
         let mut buf = Vec::new();
         file_descriptor_set.encode(&mut buf)?;
         let descriptor_pool = DescriptorPool::decode(buf.as_slice())?;
@@ -133,6 +146,11 @@ impl GrpcListener {
             shutdown_tx: Arc::new(RwLock::new(None)),
         })
     }
+
+    /// Number of methods the listener serves
+    pub async fn method_count(&self) -> usize {
+        self.method_handlers.read().await.len()
+    }
 }
 
 #[async_trait]
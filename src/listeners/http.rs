@@ -1,22 +1,29 @@
+use super::auth::{Authenticator, ListenerAuth, authorize};
 use super::{Listener, Result};
 use async_trait::async_trait;
 use axum::{
     Json, Router,
     body::Body,
     extract::Request,
-    http::StatusCode,
+    http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
     response::IntoResponse,
     routing::{MethodRouter, any},
 };
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::events::ReadMode;
+
 /// Create a method router that handles all HTTP methods (GET, POST, PUT, DELETE, PATCH, etc.)
 ///
-/// For requests with bodies (POST, PUT, PATCH), extracts JSON payload.
+/// For requests with bodies (POST, PUT, PATCH), extracts the payload as
+/// [`body_payload`] reads it in the route's read mode.
 /// For requests without bodies (GET, DELETE), extracts path parameters.
 fn create_method_router(
     handler: Arc<dyn Fn(serde_json::Value) -> Result<serde_json::Value> + Send + Sync>,
+    read_mode: ReadMode,
 ) -> MethodRouter {
     any(move |request: Request<Body>| {
         let handler = handler.clone();
@@ -55,7 +62,7 @@ fn create_method_router(
 
                 serde_json::Value::Object(params)
             } else {
-                // For POST/PUT/PATCH, extract JSON body
+                // For POST/PUT/PATCH, extract the body
                 let bytes = match axum::body::to_bytes(body, usize::MAX).await {
                     Ok(b) => b,
                     Err(e) => {
@@ -69,20 +76,14 @@ fn create_method_router(
                     }
                 };
 
-                if bytes.is_empty() {
-                    serde_json::json!({})
-                } else {
-                    match serde_json::from_slice(&bytes) {
-                        Ok(json) => json,
-                        Err(e) => {
-                            return (
-                                StatusCode::BAD_REQUEST,
-                                Json(serde_json::json!({
-                                    "error": format!("Invalid JSON: {}", e)
-                                })),
-                            )
-                                .into_response();
-                        }
+                match body_payload(&parts.headers, &bytes, read_mode) {
+                    Ok(payload) => payload,
+                    Err(message) => {
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            Json(serde_json::json!({ "error": message })),
+                        )
+                            .into_response();
                     }
                 }
             };
@@ -105,6 +106,68 @@ fn create_method_router(
     })
 }
 
+/// The payload of a request with a body, read in `read_mode`
+///
+/// In `raw` mode the body is passed as it arrived: as JSON if it is JSON,
+/// otherwise as a string, in base64 if it is not UTF-8 text. In the other
+/// modes the body is read as a CloudEvent: a request in the binary content
+/// mode, whose context attributes are `ce-` headers, becomes the event's
+/// envelope, and any other body must be JSON.
+fn body_payload(
+    headers: &HeaderMap,
+    body: &[u8],
+    read_mode: ReadMode,
+) -> std::result::Result<serde_json::Value, String> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    match read_mode {
+        ReadMode::Raw => Ok(body_data(None, body).1),
+        ReadMode::Data | ReadMode::Envelope if headers.contains_key("ce-specversion") => {
+            let mut envelope = serde_json::Map::new();
+            for (name, value) in headers {
+                if let Some(attribute) = name.as_str().strip_prefix("ce-")
+                    && let Ok(value) = value.to_str()
+                {
+                    envelope.insert(attribute.to_string(), serde_json::json!(value));
+                }
+            }
+            if let Some(content_type) = content_type {
+                envelope.insert(
+                    "datacontenttype".to_string(),
+                    serde_json::json!(content_type),
+                );
+            }
+            if !body.is_empty() {
+                let (attribute, data) = body_data(content_type, body);
+                envelope.insert(attribute.to_string(), data);
+            }
+            Ok(serde_json::Value::Object(envelope))
+        }
+        ReadMode::Data | ReadMode::Envelope if body.is_empty() => Ok(serde_json::json!({})),
+        ReadMode::Data | ReadMode::Envelope => {
+            serde_json::from_slice(body).map_err(|e| format!("Invalid JSON: {e}"))
+        }
+    }
+}
+
+/// The data a body carries and the CloudEvents attribute it belongs in:
+/// `data` for JSON (unless `content_type` says otherwise) and text, or
+/// `data_base64` for other bytes
+fn body_data(content_type: Option<&str>, body: &[u8]) -> (&'static str, serde_json::Value) {
+    let json = content_type.is_none_or(|content_type| content_type.contains("json"));
+    if json && let Ok(data) = serde_json::from_slice(body) {
+        return ("data", data);
+    }
+    match std::str::from_utf8(body) {
+        Ok(text) => ("data", serde_json::json!(text)),
+        Err(_) => (
+            "data_base64",
+            serde_json::json!(BASE64_STANDARD.encode(body)),
+        ),
+    }
+}
+
 /// HTTP/OpenAPI listener for handling REST requests
 pub struct HttpListener {
     /// Bind address (e.g., "localhost:8080")
@@ -122,6 +185,14 @@ pub struct HttpListener {
         >,
     >,
 
+    /// How the requests to each route are authenticated, by path; routes
+    /// without an entry accept any request
+    route_auth: std::collections::HashMap<String, Arc<Authenticator>>,
+
+    /// How the requests to each route are read, by path; routes without an
+    /// entry read them as CloudEvent envelopes
+    route_read_modes: std::collections::HashMap<String, ReadMode>,
+
     /// Server handle for shutdown
     shutdown_tx: Arc<RwLock<Option<tokio::sync::oneshot::Sender<()>>>>,
 
@@ -164,10 +235,32 @@ impl HttpListener {
         Ok(Self {
             bind_addr,
             route_handlers: Arc::new(RwLock::new(route_handlers)),
+            route_auth: std::collections::HashMap::new(),
+            route_read_modes: std::collections::HashMap::new(),
             shutdown_tx: Arc::new(RwLock::new(None)),
             server_handle: Arc::new(RwLock::new(None)),
         })
     }
+
+    /// Authenticate the requests to the route at `path` with `auth`
+    #[must_use]
+    pub fn with_auth(mut self, path: impl Into<String>, auth: ListenerAuth) -> Self {
+        self.route_auth
+            .insert(path.into(), Arc::new(Authenticator::new(auth)));
+        self
+    }
+
+    /// Read the requests to the route at `path` in `read_mode`
+    #[must_use]
+    pub fn with_read_mode(mut self, path: impl Into<String>, read_mode: ReadMode) -> Self {
+        self.route_read_modes.insert(path.into(), read_mode);
+        self
+    }
+
+    /// Number of routes the listener serves
+    pub async fn route_count(&self) -> usize {
+        self.route_handlers.read().await.len()
+    }
 }
 
 #[async_trait]
@@ -207,8 +300,19 @@ impl Listener for HttpListener {
 
                 // Create a unified handler that supports all HTTP methods
                 // For GET/DELETE: extract path params and use empty body
-                // For POST/PUT/PATCH: use JSON body
-                let method_router = create_method_router(handler_clone);
+                // For POST/PUT/PATCH: use the body, read in the route's read mode
+                let read_mode = self
+                    .route_read_modes
+                    .get(&path_str)
+                    .copied()
+                    .unwrap_or_default();
+                let mut method_router = create_method_router(handler_clone, read_mode);
+                if let Some(authenticator) = self.route_auth.get(&path_str) {
+                    method_router = method_router.layer(axum::middleware::from_fn_with_state(
+                        authenticator.clone(),
+                        authorize,
+                    ));
+                }
 
                 app = app.route(&path_str, method_router);
             }
@@ -292,6 +396,8 @@ impl std::fmt::Debug for HttpListener {
             .field("bind_addr", &self.bind_addr)
             .field("openapi_spec", &"<OpenAPI spec>")
             .field("route_handlers", &"<function handlers>")
+            .field("route_auth", &self.route_auth.keys().collect::<Vec<_>>())
+            .field("route_read_modes", &self.route_read_modes)
             .field("shutdown_tx", &"<shutdown sender>")
             .field("server_handle", &"<server task>")
             .finish()
@@ -3,9 +3,12 @@ use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 use std::sync::Arc;
 
+pub mod auth;
+#[cfg(feature = "grpc")]
 pub mod grpc;
 pub mod http;
 
+pub use auth::ListenerAuth;
 // pub use grpc::GrpcListener;
 pub use http::HttpListener;
 
@@ -44,6 +47,7 @@ impl From<serde_yaml::Error> for Error {
     }
 }
 
+#[cfg(feature = "grpc")]
 impl From<protox::Error> for Error {
     fn from(source: protox::Error) -> Self {
         Error::Listener {
@@ -52,6 +56,7 @@ impl From<protox::Error> for Error {
     }
 }
 
+#[cfg(feature = "grpc")]
 impl From<prost::EncodeError> for Error {
     fn from(source: prost::EncodeError) -> Self {
         Error::Listener {
@@ -60,6 +65,7 @@ impl From<prost::EncodeError> for Error {
     }
 }
 
+#[cfg(feature = "grpc")]
 impl From<prost_reflect::DescriptorError> for Error {
     fn from(source: prost_reflect::DescriptorError) -> Self {
         Error::Listener {
@@ -92,6 +98,11 @@ pub struct EventSource {
     /// Schema definition for the event
     #[serde(skip_serializing_if = "Option::is_none")]
     pub schema: Option<SchemaReference>,
+
+    /// How HTTP listeners authenticate requests, as a [`ListenerAuth`] whose
+    /// scheme may take its properties from a secret with `use: <secret>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authentication: Option<serde_json::Value>,
 }
 
 /// Schema reference for event validation
@@ -0,0 +1,77 @@
+//! Hooks that run around workflow instances and tasks
+//!
+//! A [`TaskMiddleware`] is consulted before an instance starts and before each
+//! task executes, and can refuse either by returning [`Error::Denied`]. The
+//! engine fails the instance (or task) with that error without running it.
+//! Middleware is registered with
+//! [`DurableEngineBuilder::with_middleware`](crate::DurableEngineBuilder::with_middleware).
+
+use async_trait::async_trait;
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+
+pub mod policy;
+
+pub use policy::PolicyMiddleware;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Policy '{policy}' denied {subject}: {reason}"))]
+    Denied {
+        /// Name of the policy that refused
+        policy: String,
+        /// What was refused, e.g. "task 'build'" or "workflow 'ns/name/1.0.0'"
+        subject: String,
+        /// Why it was refused
+        reason: String,
+    },
+
+    #[snafu(display("Policy '{policy}' could not be evaluated: {message}"))]
+    Evaluation { policy: String, message: String },
+
+    #[snafu(display("Middleware error: {message}"))]
+    Middleware { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A workflow instance about to start
+#[derive(Debug, Clone, Copy)]
+pub struct InstanceRequest<'a> {
+    pub instance_id: &'a str,
+    pub workflow: &'a WorkflowDefinition,
+    pub input: &'a serde_json::Value,
+}
+
+/// A task about to execute
+#[derive(Debug, Clone, Copy)]
+pub struct TaskRequest<'a> {
+    pub instance_id: &'a str,
+    pub workflow: &'a WorkflowDefinition,
+    pub task_name: &'a str,
+    pub task: &'a TaskDefinition,
+    /// The task's input, after `input.from` filtering
+    pub input: &'a serde_json::Value,
+}
+
+/// Hook consulted before instances start and tasks execute
+#[async_trait]
+pub trait TaskMiddleware: Send + Sync + std::fmt::Debug {
+    /// Called before an instance runs its first task
+    ///
+    /// # Errors
+    /// Returns an error to refuse the instance.
+    async fn before_instance(&self, _request: &InstanceRequest<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before a task executes
+    ///
+    /// # Errors
+    /// Returns an error to refuse the task.
+    async fn before_task(&self, _request: &TaskRequest<'_>) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+//! Admission policies written as CEL expressions
+//!
+//! A policy file lists rules, each with a `deny` expression in the
+//! [Common Expression Language](https://cel.dev) evaluated against the
+//! instance or task being admitted. When the expression yields `true` the
+//! instance or task is refused with the rule's `message`.
+//!
+//! ```yaml
+//! policies:
+//!   - name: no-latest-images
+//!     on: task
+//!     taskTypes: [run]
+//!     deny: 'has(task.definition.run.container) && task.definition.run.container.image.endsWith(":latest")'
+//!     message: Container images must be pinned to a version
+//!   - name: approved-http-domains
+//!     on: task
+//!     taskTypes: [call]
+//!     deny: >-
+//!       task.definition.call == "http" &&
+//!       !task.definition.with.endpoint.startsWith("https://api.example.com/")
+//!     message: HTTP calls are limited to api.example.com
+//! ```
+//!
+//! Instance rules (`on: instance`) see the variables `instanceId`, `workflow`
+//! and `input`, where `workflow` is the full workflow definition. Task rules
+//! see `instanceId`, `workflow` as `{ namespace, name, version }`, `task` as
+//! `{ name, type, definition }` and `input`, where `type` is the lowercase
+//! task type. Expressions are compiled once, when the policy file is loaded,
+//! and one that does not yield a boolean refuses what it was evaluated for.
+
+use async_trait::async_trait;
+use cel_interpreter::{Context, Program, Value as CelValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use snafu::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{DeniedSnafu, InstanceRequest, Result, TaskMiddleware, TaskRequest};
+use crate::task_ext::TaskDefinitionExt;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum LoadError {
+    #[snafu(display("Failed to read policy file '{}': {source}", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to parse policy file '{}': {source}", path.display()))]
+    Parse {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[snafu(display("Invalid deny expression of policy '{policy}' in '{}': {message}", path.display()))]
+    Compile {
+        path: PathBuf,
+        policy: String,
+        message: String,
+    },
+}
+
+/// When a rule is evaluated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyTarget {
+    /// Before an instance starts
+    Instance,
+    /// Before each task executes
+    Task,
+}
+
+/// A single admission rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PolicyRule {
+    /// Name reported when the rule denies
+    pub name: String,
+
+    /// Whether the rule applies to instances or tasks
+    pub on: PolicyTarget,
+
+    /// Task types the rule applies to (e.g. `run`, `call`); all types if empty
+    #[serde(default)]
+    pub task_types: Vec<String>,
+
+    /// CEL expression that yields `true` to deny
+    pub deny: String,
+
+    /// Reason given when the rule denies
+    pub message: Option<String>,
+}
+
+/// The contents of a policy file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicySet {
+    #[serde(default)]
+    pub policies: Vec<PolicyRule>,
+}
+
+/// A rule with its `deny` expression compiled once, when the rule is loaded
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    rule: PolicyRule,
+    /// The compiled expression, or why it does not compile
+    program: std::result::Result<Arc<Program>, String>,
+}
+
+/// [`TaskMiddleware`] that enforces a [`PolicySet`]
+#[derive(Debug, Clone, Default)]
+pub struct PolicyMiddleware {
+    rules: Vec<CompiledRule>,
+}
+
+impl PolicyMiddleware {
+    /// Compile the `deny` expression of every rule
+    ///
+    /// A rule whose expression does not compile refuses whatever it is
+    /// evaluated for; use [`PolicyMiddleware::load`] to reject it up front.
+    #[must_use]
+    pub fn new(policies: PolicySet) -> Self {
+        let rules = policies
+            .policies
+            .into_iter()
+            .map(|rule| {
+                let program = Program::compile(rule.deny.trim())
+                    .map(Arc::new)
+                    .map_err(|e| e.to_string());
+                CompiledRule { rule, program }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Load policies from a YAML (or JSON) file
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or parsed, or a rule's
+    /// `deny` expression is not valid CEL.
+    pub fn load(path: &Path) -> std::result::Result<Self, LoadError> {
+        let yaml = std::fs::read_to_string(path).context(ReadSnafu { path })?;
+        let policies: PolicySet = serde_yaml::from_str(&yaml).context(ParseSnafu { path })?;
+        let middleware = Self::new(policies);
+        for compiled in &middleware.rules {
+            if let Err(message) = &compiled.program {
+                return CompileSnafu {
+                    path,
+                    policy: compiled.rule.name.as_str(),
+                    message: message.as_str(),
+                }
+                .fail();
+            }
+        }
+        Ok(middleware)
+    }
+
+    /// Evaluate every rule for `target` against `document`, failing on the first denial
+    fn evaluate(
+        &self,
+        target: PolicyTarget,
+        task_type: Option<&str>,
+        subject: &str,
+        document: &Value,
+    ) -> Result<()> {
+        let rules = self.rules.iter().filter(|compiled| {
+            let rule = &compiled.rule;
+            rule.on == target
+                && (rule.task_types.is_empty()
+                    || task_type.is_some_and(|t| rule.task_types.iter().any(|r| r == t)))
+        });
+
+        for CompiledRule { rule, program } in rules {
+            let denied = program
+                .clone()
+                .and_then(|program| denies(&program, document))
+                .map_err(|message| super::Error::Evaluation {
+                    policy: rule.name.clone(),
+                    message,
+                })?;
+
+            if denied {
+                return DeniedSnafu {
+                    policy: rule.name.clone(),
+                    subject,
+                    reason: rule
+                        .message
+                        .clone()
+                        .unwrap_or_else(|| "denied by policy".to_string()),
+                }
+                .fail();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Evaluate a compiled `deny` expression with the fields of `document` as its variables
+fn denies(program: &Program, document: &Value) -> std::result::Result<bool, String> {
+    let mut context = Context::default();
+    if let Value::Object(fields) = document {
+        for (name, value) in fields {
+            context
+                .add_variable(name.as_str(), value)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let result = program.execute(&context).map_err(|e| e.to_string())?;
+    let CelValue::Bool(denied) = result else {
+        return Err(format!("expected true or false, got {result:?}"));
+    };
+    Ok(denied)
+}
+
+#[async_trait]
+impl TaskMiddleware for PolicyMiddleware {
+    async fn before_instance(&self, request: &InstanceRequest<'_>) -> Result<()> {
+        let document = &request.workflow.document;
+        let subject = format!(
+            "workflow '{}/{}/{}'",
+            document.namespace, document.name, document.version
+        );
+        let workflow =
+            serde_json::to_value(request.workflow).map_err(|e| super::Error::Middleware {
+                message: format!("Failed to serialize workflow: {e}"),
+            })?;
+        let document = json!({
+            "instanceId": request.instance_id,
+            "workflow": workflow,
+            "input": request.input,
+        });
+        self.evaluate(PolicyTarget::Instance, None, &subject, &document)
+    }
+
+    async fn before_task(&self, request: &TaskRequest<'_>) -> Result<()> {
+        let task_type = request.task.type_name().to_lowercase();
+        let definition =
+            serde_json::to_value(request.task).map_err(|e| super::Error::Middleware {
+                message: format!("Failed to serialize task '{}': {e}", request.task_name),
+            })?;
+        let workflow = &request.workflow.document;
+        let document = json!({
+            "instanceId": request.instance_id,
+            "workflow": {
+                "namespace": workflow.namespace,
+                "name": workflow.name,
+                "version": workflow.version,
+            },
+            "task": {
+                "name": request.task_name,
+                "type": task_type,
+                "definition": definition,
+            },
+            "input": request.input,
+        });
+        let subject = format!("task '{}'", request.task_name);
+        self.evaluate(PolicyTarget::Task, Some(&task_type), &subject, &document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    const POLICIES: &str = r#"
+policies:
+  - name: no-latest-images
+    on: task
+    taskTypes: [run]
+    deny: 'has(task.definition.run.container) && task.definition.run.container.image.endsWith(":latest")'
+    message: Container images must be pinned to a version
+  - name: require-input
+    on: instance
+    deny: '!has(input.tenant)'
+"#;
+
+    fn middleware() -> PolicyMiddleware {
+        PolicyMiddleware::new(serde_yaml::from_str(POLICIES).unwrap())
+    }
+
+    fn task_document(image: &str) -> Value {
+        json!({
+            "task": {
+                "name": "build",
+                "type": "run",
+                "definition": { "run": { "container": { "image": image } } },
+            },
+        })
+    }
+
+    #[test]
+    fn test_task_rule_denies_matching_task() {
+        let err = middleware()
+            .evaluate(
+                PolicyTarget::Task,
+                Some("run"),
+                "task 'build'",
+                &task_document("alpine:latest"),
+            )
+            .unwrap_err();
+        let super::super::Error::Denied {
+            policy,
+            subject,
+            reason,
+        } = err
+        else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(policy, "no-latest-images");
+        assert_eq!(subject, "task 'build'");
+        assert_eq!(reason, "Container images must be pinned to a version");
+    }
+
+    #[test]
+    fn test_task_rule_allows_other_tasks() {
+        let middleware = middleware();
+        let document = task_document("alpine:3.20");
+        assert!(
+            middleware
+                .evaluate(PolicyTarget::Task, Some("run"), "task", &document)
+                .is_ok()
+        );
+        let document = task_document("alpine:latest");
+        assert!(
+            middleware
+                .evaluate(PolicyTarget::Task, Some("call"), "task", &document)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_instance_rule() {
+        let middleware = middleware();
+        let allowed = json!({ "input": { "tenant": "acme" } });
+        assert!(
+            middleware
+                .evaluate(PolicyTarget::Instance, None, "workflow", &allowed)
+                .is_ok()
+        );
+        let denied = json!({ "input": {} });
+        assert!(
+            middleware
+                .evaluate(PolicyTarget::Instance, None, "workflow", &denied)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_rule_must_yield_a_boolean() {
+        let middleware = PolicyMiddleware::new(
+            serde_yaml::from_str(
+                "policies:\n  - name: typo\n    on: instance\n    deny: 'input'\n",
+            )
+            .unwrap(),
+        );
+        let err = middleware
+            .evaluate(
+                PolicyTarget::Instance,
+                None,
+                "workflow",
+                &json!({ "input": {} }),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, super::super::Error::Evaluation { ref policy, .. } if policy == "typo"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_expression_refuses_when_evaluated() {
+        let middleware = PolicyMiddleware::new(
+            serde_yaml::from_str(
+                "policies:\n  - name: broken\n    on: instance\n    deny: 'input =='\n",
+            )
+            .unwrap(),
+        );
+        let err = middleware
+            .evaluate(
+                PolicyTarget::Instance,
+                None,
+                "workflow",
+                &json!({ "input": {} }),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(err, super::super::Error::Evaluation { ref policy, .. } if policy == "broken"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_expression_rejected_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policies.yaml");
+        std::fs::write(
+            &path,
+            "policies:\n  - name: broken\n    on: task\n    deny: 'task.name =='\n",
+        )
+        .unwrap();
+        let err = PolicyMiddleware::load(&path).unwrap_err();
+        assert!(
+            matches!(err, LoadError::Compile { ref policy, .. } if policy == "broken"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_rejected() {
+        let result: std::result::Result<PolicySet, _> = serde_yaml::from_str(
+            "policies:\n  - name: x\n    on: task\n    deny: 'true'\n    when: x\n",
+        );
+        assert!(result.is_err());
+    }
+}
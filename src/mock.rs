@@ -0,0 +1,720 @@
+//! Mock servers for the APIs described by OpenAPI documents
+//!
+//! A [`MockApi`] answers every operation of an OpenAPI (3.x) or Swagger (2.0)
+//! document with the example of its success response, or with a value built
+//! from the response schema when the document has no example. Workflows that
+//! call the API can then be developed without reaching it: `jackdaw mock`
+//! serves a document on its own, and `--mock` on `jackdaw run` starts a mock
+//! server for each document and routes the calls made to the API's server URL
+//! to it through [`MockRoutes`].
+
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use snafu::prelude::*;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How deep schemas are followed when building a response from them
+const MAX_SCHEMA_DEPTH: usize = 8;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Failed to read OpenAPI document '{}': {source}", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Invalid OpenAPI document '{}': {message}", path.display()))]
+    Document { path: PathBuf, message: String },
+
+    #[snafu(display("Failed to bind mock server to {address}: {source}"))]
+    Bind {
+        address: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("No mock server answers calls to {url}"))]
+    Unmocked { url: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A segment of an operation's path template
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    /// A `{parameter}`, matching any segment
+    Parameter,
+}
+
+/// An operation of the API and its canned response
+#[derive(Debug, Clone)]
+pub struct MockOperation {
+    /// HTTP method, in upper case
+    pub method: String,
+    /// Path template, as written in the document
+    pub path: String,
+    pub status: u16,
+    /// Response body, if the response has one
+    pub body: Option<serde_json::Value>,
+    segments: Vec<Segment>,
+}
+
+/// The operations of an OpenAPI document, answered with canned responses
+#[derive(Debug, Clone)]
+pub struct MockApi {
+    /// Base URL of the real API, if the document names an absolute one
+    server_url: Option<String>,
+    /// Path prefix of the operations, such as `/v1`
+    base_path: String,
+    operations: Vec<MockOperation>,
+}
+
+impl MockApi {
+    /// Load an OpenAPI document, in JSON or YAML
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or is not an OpenAPI document.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).context(ReadSnafu { path })?;
+        let document: serde_json::Value = serde_json::from_str(&text)
+            .or_else(|_| serde_yaml::from_str(&text))
+            .map_err(|e| Error::Document {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+        Self::from_document(&document).map_err(|message| Error::Document {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Build the mock of the API an OpenAPI document describes
+    ///
+    /// # Errors
+    /// Returns a description of the problem if the document has no paths.
+    pub fn from_document(document: &serde_json::Value) -> std::result::Result<Self, String> {
+        let paths = document
+            .get("paths")
+            .and_then(serde_json::Value::as_object)
+            .ok_or_else(|| "the document has no paths".to_string())?;
+        let (server_url, base_path) = server(document);
+
+        let mut operations = Vec::new();
+        for (path, item) in paths {
+            let Some(item) = item.as_object() else {
+                continue;
+            };
+            for (method, operation) in item {
+                let method = method.to_ascii_uppercase();
+                if !matches!(
+                    method.as_str(),
+                    "GET" | "PUT" | "POST" | "DELETE" | "OPTIONS" | "HEAD" | "PATCH" | "TRACE"
+                ) {
+                    continue;
+                }
+                let (status, body) = canned_response(document, operation);
+                operations.push(MockOperation {
+                    method,
+                    path: path.clone(),
+                    status,
+                    body,
+                    segments: parse_template(path),
+                });
+            }
+        }
+
+        Ok(Self {
+            server_url,
+            base_path,
+            operations,
+        })
+    }
+
+    /// Base URL of the real API, which `--mock` routes to the mock server
+    #[must_use]
+    pub fn server_url(&self) -> Option<&str> {
+        self.server_url.as_deref()
+    }
+
+    /// Path prefix of the operations, such as `/v1`
+    #[must_use]
+    pub fn base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    #[must_use]
+    pub fn operations(&self) -> &[MockOperation] {
+        &self.operations
+    }
+
+    /// The operation answering a request, if any
+    #[must_use]
+    pub fn find(&self, method: &str, path: &str) -> Option<&MockOperation> {
+        // Paths are matched with or without the base path
+        let relative = path
+            .strip_prefix(self.base_path.as_str())
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            .unwrap_or(path);
+        let segments: Vec<&str> = relative.split('/').filter(|s| !s.is_empty()).collect();
+
+        self.operations
+            .iter()
+            .filter(|operation| operation.method.eq_ignore_ascii_case(method))
+            .filter(|operation| {
+                operation.segments.len() == segments.len()
+                    && operation
+                        .segments
+                        .iter()
+                        .zip(&segments)
+                        .all(|(template, segment)| match template {
+                            Segment::Literal(literal) => literal == segment,
+                            Segment::Parameter => true,
+                        })
+            })
+            // Literal segments win over parameters, as `/pets/mine` over `/pets/{id}`
+            .max_by_key(|operation| {
+                operation
+                    .segments
+                    .iter()
+                    .filter(|segment| matches!(segment, Segment::Literal(_)))
+                    .count()
+            })
+    }
+
+    /// Serve the mock on `address` until the returned server is dropped or stopped
+    ///
+    /// # Errors
+    /// Returns an error if the address cannot be bound.
+    pub async fn start(self, address: &str) -> Result<MockServer> {
+        let listener = tokio::net::TcpListener::bind(address)
+            .await
+            .context(BindSnafu { address })?;
+        let local_addr = listener.local_addr().context(BindSnafu { address })?;
+
+        let app = Router::new().fallback(respond).with_state(Arc::new(self));
+        let (shutdown, stopped) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            let served = axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(async {
+                    stopped.await.ok();
+                })
+                .await;
+            if let Err(e) = served {
+                tracing::error!("Mock server error: {}", e);
+            }
+        });
+
+        Ok(MockServer {
+            local_addr,
+            shutdown: Some(shutdown),
+            task,
+        })
+    }
+}
+
+/// A running mock server
+#[derive(Debug)]
+pub struct MockServer {
+    local_addr: SocketAddr,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// URL of the server, such as `http://127.0.0.1:8081`
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.local_addr())
+    }
+
+    /// Stop accepting requests and wait for the server to finish
+    pub async fn stop(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Base URLs of APIs whose calls are sent to mock servers instead
+#[derive(Debug, Clone, Default)]
+pub struct MockRoutes {
+    /// Base URL of each API and the URL of its mock
+    routes: Vec<(String, String)>,
+    /// Whether calls to APIs without a mock are refused instead of sent
+    mocked_only: bool,
+}
+
+impl MockRoutes {
+    /// Send calls to URLs starting with `base_url` to `target` instead
+    pub fn add(&mut self, base_url: impl Into<String>, target: impl Into<String>) {
+        let base_url: String = base_url.into();
+        let target: String = target.into();
+        self.routes.push((
+            base_url.trim_end_matches('/').to_string(),
+            target.trim_end_matches('/').to_string(),
+        ));
+    }
+
+    /// The same routes, refusing calls to APIs without a mock
+    #[must_use]
+    pub fn mocked_only(&self) -> Self {
+        Self {
+            routes: self.routes.clone(),
+            mocked_only: true,
+        }
+    }
+
+    /// The URL a call to `url` is sent to
+    #[must_use]
+    pub fn route(&self, url: &str) -> String {
+        self.mock(url).unwrap_or_else(|| url.to_string())
+    }
+
+    /// The URL a call to `url` is sent to, if it may be sent
+    ///
+    /// # Errors
+    /// Returns an error if the routes only allow mocked calls and no mock
+    /// server answers `url`.
+    pub fn try_route(&self, url: &str) -> Result<String> {
+        match self.mock(url) {
+            Some(target) => Ok(target),
+            None if self.mocked_only => UnmockedSnafu { url }.fail(),
+            None => Ok(url.to_string()),
+        }
+    }
+
+    /// The URL of the mock that answers calls to `url`
+    fn mock(&self, url: &str) -> Option<String> {
+        for (base_url, target) in &self.routes {
+            if let Some(rest) = url.strip_prefix(base_url.as_str())
+                && (rest.is_empty() || rest.starts_with(['/', '?', '#']))
+            {
+                tracing::debug!("Routing {} to mock server {}", url, target);
+                return Some(format!("{target}{rest}"));
+            }
+        }
+        None
+    }
+}
+
+/// Answer a request with the canned response of its operation
+async fn respond(State(api): State<Arc<MockApi>>, request: Request) -> Response {
+    let method = request.method().as_str().to_string();
+    let path = request.uri().path().to_string();
+    let Some(operation) = api.find(&method, &path) else {
+        return (
+            StatusCode::NOT_FOUND,
+            axum::Json(serde_json::json!({
+                "error": format!("No operation for {method} {path} in the mocked document")
+            })),
+        )
+            .into_response();
+    };
+
+    let status = StatusCode::from_u16(operation.status).unwrap_or(StatusCode::OK);
+    match &operation.body {
+        Some(body) => (status, axum::Json(body.clone())).into_response(),
+        None => status.into_response(),
+    }
+}
+
+/// Base URL of the real API and the path prefix of its operations
+fn server(document: &serde_json::Value) -> (Option<String>, String) {
+    // OpenAPI 3.x names its servers
+    if let Some(url) = document
+        .pointer("/servers/0/url")
+        .and_then(serde_json::Value::as_str)
+    {
+        let url = url.trim_end_matches('/');
+        return match url.split_once("://") {
+            Some((_, rest)) => {
+                let base_path = rest
+                    .find('/')
+                    .and_then(|start| rest.get(start..))
+                    .unwrap_or_default();
+                (Some(url.to_string()), base_path.to_string())
+            }
+            None => (None, url.to_string()),
+        };
+    }
+
+    // Swagger 2.0 splits the URL into host, base path and schemes
+    let base_path = document
+        .get("basePath")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .to_string();
+    let server_url = document
+        .get("host")
+        .and_then(serde_json::Value::as_str)
+        .map(|host| {
+            let scheme = document
+                .pointer("/schemes/0")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or("https");
+            format!("{scheme}://{host}{base_path}")
+        });
+    (server_url, base_path)
+}
+
+fn parse_template(path: &str) -> Vec<Segment> {
+    path.split('/')
+        .filter(|s| !s.is_empty())
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                Segment::Parameter
+            } else {
+                Segment::Literal(segment.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Status and body of the success response of an operation
+fn canned_response(
+    document: &serde_json::Value,
+    operation: &serde_json::Value,
+) -> (u16, Option<serde_json::Value>) {
+    let responses = operation
+        .get("responses")
+        .and_then(serde_json::Value::as_object);
+    let chosen = responses.and_then(|responses| {
+        let success = responses
+            .iter()
+            .filter_map(|(code, response)| Some((code.parse::<u16>().ok()?, response)))
+            .filter(|(code, _)| (200..300).contains(code))
+            .min_by_key(|(code, _)| *code);
+        success.or_else(|| responses.get("default").map(|response| (200, response)))
+    });
+    let Some((status, response)) = chosen else {
+        return (200, None);
+    };
+    let response = resolve(document, response, 0);
+    (status, example(document, response))
+}
+
+/// The example body of a response, or one built from its schema
+fn example(
+    document: &serde_json::Value,
+    response: &serde_json::Value,
+) -> Option<serde_json::Value> {
+    // OpenAPI 3.x: examples per media type, JSON preferred
+    if let Some(content) = response
+        .get("content")
+        .and_then(serde_json::Value::as_object)
+    {
+        let media = content
+            .iter()
+            .find(|(media_type, _)| media_type.contains("json"))
+            .or_else(|| content.iter().next())
+            .map(|(_, media)| media)?;
+        if let Some(example) = media.get("example") {
+            return Some(example.clone());
+        }
+        if let Some(example) = media
+            .get("examples")
+            .and_then(serde_json::Value::as_object)
+            .and_then(|examples| examples.values().next())
+        {
+            let example = resolve(document, example, 0);
+            return Some(
+                example
+                    .get("value")
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null),
+            );
+        }
+        return media
+            .get("schema")
+            .map(|schema| from_schema(document, schema, 0));
+    }
+
+    // Swagger 2.0: examples keyed by media type, and a single schema
+    if let Some(example) = response
+        .get("examples")
+        .and_then(serde_json::Value::as_object)
+        .and_then(|examples| {
+            examples
+                .iter()
+                .find(|(media_type, _)| media_type.contains("json"))
+                .or_else(|| examples.iter().next())
+        })
+        .map(|(_, example)| example)
+    {
+        return Some(example.clone());
+    }
+    response
+        .get("schema")
+        .map(|schema| from_schema(document, schema, 0))
+}
+
+/// Follow a `$ref` within the document
+pub(crate) fn resolve<'a>(
+    document: &'a serde_json::Value,
+    value: &'a serde_json::Value,
+    depth: usize,
+) -> &'a serde_json::Value {
+    match value.get("$ref").and_then(serde_json::Value::as_str) {
+        Some(reference) if depth < MAX_SCHEMA_DEPTH => reference
+            .strip_prefix('#')
+            .and_then(|pointer| document.pointer(pointer))
+            .map_or(value, |target| resolve(document, target, depth + 1)),
+        Some(_) | None => value,
+    }
+}
+
+/// A value conforming to a schema, from its examples or its types
+fn from_schema(
+    document: &serde_json::Value,
+    schema: &serde_json::Value,
+    depth: usize,
+) -> serde_json::Value {
+    if depth > MAX_SCHEMA_DEPTH {
+        return serde_json::Value::Null;
+    }
+    let schema = resolve(document, schema, 0);
+    if let Some(example) = schema.get("example").or_else(|| schema.get("default")) {
+        return example.clone();
+    }
+    if let Some(first) = schema
+        .get("enum")
+        .and_then(serde_json::Value::as_array)
+        .and_then(|values| values.first())
+    {
+        return first.clone();
+    }
+    if let Some(first) = ["allOf", "oneOf", "anyOf"].iter().find_map(|key| {
+        schema
+            .get(*key)
+            .and_then(serde_json::Value::as_array)
+            .filter(|schemas| !schemas.is_empty())
+            .map(|schemas| (*key, schemas))
+    }) {
+        return match first {
+            // Every schema applies, so their objects are merged
+            ("allOf", schemas) => {
+                let mut merged = serde_json::Map::new();
+                for schema in schemas {
+                    if let serde_json::Value::Object(object) =
+                        from_schema(document, schema, depth + 1)
+                    {
+                        merged.extend(object);
+                    }
+                }
+                serde_json::Value::Object(merged)
+            }
+            (_, schemas) => schemas.first().map_or(serde_json::Value::Null, |schema| {
+                from_schema(document, schema, depth + 1)
+            }),
+        };
+    }
+
+    let kind = schema
+        .get("type")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or(if schema.get("properties").is_some() {
+            "object"
+        } else {
+            ""
+        });
+    match kind {
+        "object" => {
+            let properties = schema
+                .get("properties")
+                .and_then(serde_json::Value::as_object)
+                .map(|properties| {
+                    properties
+                        .iter()
+                        .map(|(name, property)| {
+                            (name.clone(), from_schema(document, property, depth + 1))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            serde_json::Value::Object(properties)
+        }
+        "array" => {
+            let item = schema
+                .get("items")
+                .map_or(serde_json::Value::Null, |items| {
+                    from_schema(document, items, depth + 1)
+                });
+            serde_json::json!([item])
+        }
+        "string" => {
+            let text = match schema.get("format").and_then(serde_json::Value::as_str) {
+                Some("date-time") => "1970-01-01T00:00:00Z",
+                Some("date") => "1970-01-01",
+                Some("uuid") => "00000000-0000-0000-0000-000000000000",
+                Some("email") => "user@example.com",
+                Some("uri" | "url") => "https://example.com",
+                Some(_) | None => "string",
+            };
+            serde_json::json!(text)
+        }
+        "integer" => serde_json::json!(0),
+        "number" => serde_json::json!(0.0),
+        "boolean" => serde_json::json!(true),
+        _ => serde_json::Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn petstore() -> MockApi {
+        MockApi::from_document(&serde_json::json!({
+            "openapi": "3.0.0",
+            "servers": [{ "url": "https://petstore.example.com/v1" }],
+            "paths": {
+                "/pets": {
+                    "get": {
+                        "responses": {
+                            "200": {
+                                "content": {
+                                    "application/json": {
+                                        "schema": {
+                                            "type": "array",
+                                            "items": { "$ref": "#/components/schemas/Pet" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "post": { "responses": { "201": { "description": "Created" } } }
+                },
+                "/pets/{petId}": {
+                    "get": {
+                        "responses": {
+                            "200": {
+                                "content": {
+                                    "application/json": {
+                                        "example": { "id": 7, "name": "Rex" }
+                                    }
+                                }
+                            },
+                            "404": { "description": "Not found" }
+                        }
+                    }
+                },
+                "/pets/mine": {
+                    "get": { "responses": { "default": { "description": "Mine" } } }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Pet": {
+                        "type": "object",
+                        "properties": {
+                            "id": { "type": "integer" },
+                            "name": { "type": "string", "example": "Fido" },
+                            "status": { "type": "string", "enum": ["available", "sold"] }
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_server_url_and_base_path() {
+        let api = petstore();
+        assert_eq!(api.server_url(), Some("https://petstore.example.com/v1"));
+        assert_eq!(api.base_path(), "/v1");
+    }
+
+    #[test]
+    fn test_response_built_from_schema() {
+        let api = petstore();
+        let operation = api.find("GET", "/v1/pets").unwrap();
+        assert_eq!(operation.status, 200);
+        assert_eq!(
+            operation.body,
+            Some(serde_json::json!([{ "id": 0, "name": "Fido", "status": "available" }]))
+        );
+    }
+
+    #[test]
+    fn test_response_example_and_path_parameters() {
+        let api = petstore();
+        let operation = api.find("get", "/pets/42").unwrap();
+        assert_eq!(operation.path, "/pets/{petId}");
+        assert_eq!(
+            operation.body,
+            Some(serde_json::json!({ "id": 7, "name": "Rex" }))
+        );
+        assert_eq!(api.find("GET", "/v1/pets/mine").unwrap().path, "/pets/mine");
+    }
+
+    #[test]
+    fn test_success_status_without_body() {
+        let api = petstore();
+        let operation = api.find("POST", "/v1/pets").unwrap();
+        assert_eq!(operation.status, 201);
+        assert_eq!(operation.body, None);
+        assert!(api.find("DELETE", "/v1/pets").is_none());
+    }
+
+    #[test]
+    fn test_routes_rewrite_base_url() {
+        let mut routes = MockRoutes::default();
+        routes.add(
+            "https://petstore.example.com/v1/",
+            "http://127.0.0.1:8081/v1",
+        );
+        assert_eq!(
+            routes.route("https://petstore.example.com/v1/pets?limit=1"),
+            "http://127.0.0.1:8081/v1/pets?limit=1"
+        );
+        assert_eq!(
+            routes.route("https://petstore.example.com/v10/pets"),
+            "https://petstore.example.com/v10/pets"
+        );
+    }
+
+    #[test]
+    fn test_mocked_only_routes_refuse_unmocked_calls() {
+        let mut routes = MockRoutes::default();
+        routes.add(
+            "https://petstore.example.com/v1",
+            "http://127.0.0.1:8081/v1",
+        );
+        let routes = routes.mocked_only();
+        assert_eq!(
+            routes
+                .try_route("https://petstore.example.com/v1/pets")
+                .unwrap(),
+            "http://127.0.0.1:8081/v1/pets"
+        );
+        assert!(matches!(
+            routes.try_route("https://billing.example.com/charge"),
+            Err(Error::Unmocked { .. })
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+//! Live observation of the events a workflow instance records
+//!
+//! A [`WorkflowObserver`] passed to
+//! [`DurableEngine::start_with_observer`](crate::durableengine::DurableEngine::start_with_observer)
+//! is called with every event the instance records, as it is recorded, so an
+//! application can render progress without polling persistence. A
+//! [`tokio::sync::mpsc::Sender`] is an observer that forwards the events to
+//! its receiver.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::durableengine::InstanceState;
+use crate::persistence::{
+    Change, Lease, PersistenceProvider, QueuedStart, Result, RunUpdate, WorkflowRollup,
+};
+use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
+
+/// Receives the events of a workflow instance as they are recorded
+///
+/// The instance waits for the observer before it continues, so
+/// implementations should hand slow work off rather than doing it inline.
+#[async_trait]
+pub trait WorkflowObserver: Send + Sync + std::fmt::Debug {
+    /// Called after an event has been recorded
+    async fn on_event(&self, event: &WorkflowEvent);
+}
+
+/// Forwards events to the channel's receiver
+///
+/// Events are dropped once the receiver is closed; while the channel is
+/// full, the instance waits for the receiver to catch up.
+#[async_trait]
+impl WorkflowObserver for mpsc::Sender<WorkflowEvent> {
+    async fn on_event(&self, event: &WorkflowEvent) {
+        let _ = self.send(event.clone()).await;
+    }
+}
+
+/// Persistence that reports each saved event to an observer
+#[derive(Debug)]
+pub(crate) struct ObservedPersistence {
+    inner: Arc<dyn PersistenceProvider>,
+    observer: Arc<dyn WorkflowObserver>,
+}
+
+impl ObservedPersistence {
+    pub(crate) fn new(
+        inner: Arc<dyn PersistenceProvider>,
+        observer: Arc<dyn WorkflowObserver>,
+    ) -> Self {
+        Self { inner, observer }
+    }
+}
+
+#[async_trait]
+impl PersistenceProvider for ObservedPersistence {
+    async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
+        self.inner.save_event(event.clone()).await?;
+        self.observer.on_event(&event).await;
+        Ok(())
+    }
+
+    async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
+        self.inner.get_events(instance_id).await
+    }
+
+    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
+        self.inner.save_checkpoint(checkpoint).await
+    }
+
+    async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
+        self.inner.get_checkpoint(instance_id).await
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        self.inner.list_instance_ids().await
+    }
+
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> Result<()> {
+        self.inner.commit_task(event.clone(), checkpoint).await?;
+        self.observer.on_event(&event).await;
+        Ok(())
+    }
+
+    async fn get_state(&self, instance_id: &str) -> Result<Option<InstanceState>> {
+        self.inner.get_state(instance_id).await
+    }
+
+    async fn transition_state(
+        &self,
+        instance_id: &str,
+        from: Option<InstanceState>,
+        to: InstanceState,
+    ) -> Result<bool> {
+        self.inner.transition_state(instance_id, from, to).await
+    }
+
+    async fn acquire_lease(
+        &self,
+        instance_id: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<Option<Lease>> {
+        self.inner.acquire_lease(instance_id, owner, ttl).await
+    }
+
+    async fn release_lease(&self, lease: &Lease) -> Result<()> {
+        self.inner.release_lease(lease).await
+    }
+
+    async fn changes(
+        &self,
+        after: u64,
+        instance_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        self.inner.changes(after, instance_id, limit).await
+    }
+
+    async fn enqueue_start(&self, start: QueuedStart) -> Result<()> {
+        self.inner.enqueue_start(start).await
+    }
+
+    async fn queued_starts(&self, limit: usize) -> Result<Vec<QueuedStart>> {
+        self.inner.queued_starts(limit).await
+    }
+
+    async fn remove_start(&self, instance_id: &str) -> Result<()> {
+        self.inner.remove_start(instance_id).await
+    }
+
+    async fn save_debug_bundle(&self, instance_id: &str, bundle: serde_json::Value) -> Result<()> {
+        self.inner.save_debug_bundle(instance_id, bundle).await
+    }
+
+    async fn get_debug_bundle(&self, instance_id: &str) -> Result<Option<serde_json::Value>> {
+        self.inner.get_debug_bundle(instance_id).await
+    }
+
+    async fn update_rollup(&self, workflow_id: &str, update: &RunUpdate) -> Result<()> {
+        self.inner.update_rollup(workflow_id, update).await
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> Result<()> {
+        self.inner.replace_rollups(rollups).await
+    }
+
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        self.inner.workflow_rollups().await
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.inner.is_degraded()
+    }
+}
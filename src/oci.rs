@@ -0,0 +1,769 @@
+//! Distribution of workflow bundles as OCI artifacts
+//!
+//! Bundles are pushed to, and pulled from, any registry implementing the OCI
+//! distribution API (GHCR, Docker Hub, ECR, Harbor, `registry:2`, ...). An
+//! artifact is an OCI image manifest whose config blob is the bundle manifest
+//! and whose single layer is the bundle archive, so registries, signing tools
+//! and scanners handle workflows like any other artifact.
+
+use reqwest::StatusCode;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, LOCATION, WWW_AUTHENTICATE};
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+use crate::bundle::content_digest;
+use crate::text::{MAX_EXCERPT_LEN, truncate};
+
+/// Media type of OCI image manifests
+pub const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// Artifact type identifying jackdaw workflow bundles
+pub const ARTIFACT_TYPE: &str = "application/vnd.jackdaw.workflow.bundle.v1";
+
+/// Media type of the config blob, which holds the bundle manifest
+pub const CONFIG_MEDIA_TYPE: &str = "application/vnd.jackdaw.workflow.config.v1+json";
+
+/// Media type of the layer holding the bundle archive
+pub const BUNDLE_MEDIA_TYPE: &str = "application/vnd.jackdaw.workflow.bundle.v1.tar";
+
+/// Layer annotation carrying the detached signature of the bundle archive
+pub const SIGNATURE_ANNOTATION: &str = "dev.jackdaw.bundle.signature";
+
+/// Environment variable holding the registry username
+pub const USERNAME_ENV: &str = "JACKDAW_REGISTRY_USERNAME";
+
+/// Environment variable holding the registry password or token
+pub const PASSWORD_ENV: &str = "JACKDAW_REGISTRY_PASSWORD";
+
+/// Registry used for references without a registry host
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// Host serving the distribution API for [`DEFAULT_REGISTRY`]
+const DEFAULT_REGISTRY_API: &str = "registry-1.docker.io";
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Invalid OCI reference '{reference}': {message}"))]
+    InvalidReference { reference: String, message: String },
+
+    #[snafu(display("Registry request failed: {source}"))]
+    Http { source: reqwest::Error },
+
+    #[snafu(display("Registry returned {status} for {url}: {message}"))]
+    Registry {
+        url: String,
+        status: u16,
+        message: String,
+    },
+
+    #[snafu(display("Registry did not return an upload location for {url}"))]
+    MissingUploadLocation { url: String },
+
+    #[snafu(display("Failed to obtain a registry token from {realm}: {message}"))]
+    Token { realm: String, message: String },
+
+    #[snafu(display("Invalid OCI manifest for {reference}: {source}"))]
+    Manifest {
+        reference: String,
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("{reference} is not a workflow bundle (no {BUNDLE_MEDIA_TYPE} layer)"))]
+    NotABundle { reference: String },
+
+    #[snafu(display("Digest mismatch for {what}: expected {expected}, got {actual}"))]
+    DigestMismatch {
+        what: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A parsed artifact reference, e.g. `ghcr.io/org/workflow:1.2.0`
+///
+/// An `oci://` prefix is accepted and ignored. References without a registry
+/// host resolve to Docker Hub, and references without a tag or digest to
+/// `latest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub registry: String,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl Reference {
+    /// Parse a reference of the form `[registry/]repository[:tag][@digest]`
+    ///
+    /// # Errors
+    /// Returns an error if the repository is missing or contains invalid characters.
+    pub fn parse(reference: &str) -> Result<Self> {
+        let invalid = |message: &str| Error::InvalidReference {
+            reference: reference.to_string(),
+            message: message.to_string(),
+        };
+
+        let rest = reference.strip_prefix("oci://").unwrap_or(reference);
+        let (rest, digest) = match rest.split_once('@') {
+            Some((rest, digest)) => {
+                if !digest.starts_with("sha256:") {
+                    return Err(invalid("only sha256 digests are supported"));
+                }
+                (rest, Some(digest.to_string()))
+            }
+            None => (rest, None),
+        };
+
+        // A ':' after the last '/' separates the tag; one before it belongs to a registry port
+        let (name, tag) = match rest.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, Some(tag.to_string())),
+            Some(_) | None => (rest, None),
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, path))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_string(), path.to_string())
+            }
+            Some(_) => (DEFAULT_REGISTRY.to_string(), name.to_string()),
+            None => (DEFAULT_REGISTRY.to_string(), format!("library/{name}")),
+        };
+
+        if repository.is_empty() || repository.ends_with('/') || name.is_empty() {
+            return Err(invalid("missing repository"));
+        }
+        if !repository.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-' | '/')
+        }) {
+            return Err(invalid(
+                "repository may only contain lowercase letters, digits, '.', '_', '-' and '/'",
+            ));
+        }
+        if tag.as_deref().is_some_and(str::is_empty) {
+            return Err(invalid("empty tag"));
+        }
+
+        Ok(Self {
+            registry,
+            repository,
+            tag,
+            digest,
+        })
+    }
+
+    /// The tag or digest to request the manifest by
+    #[must_use]
+    pub fn target(&self) -> &str {
+        self.digest
+            .as_deref()
+            .or(self.tag.as_deref())
+            .unwrap_or("latest")
+    }
+
+    /// Host serving the distribution API for this reference's registry
+    fn api_host(&self) -> &str {
+        if self.registry == DEFAULT_REGISTRY {
+            DEFAULT_REGISTRY_API
+        } else {
+            &self.registry
+        }
+    }
+
+    /// Whether the registry is on the local machine and spoken to over plain HTTP
+    fn is_local(&self) -> bool {
+        let host = self
+            .registry
+            .rsplit_once(':')
+            .map_or(self.registry.as_str(), |(host, _)| host);
+        matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.registry, self.repository)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{tag}")?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{digest}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Registry username and password (or access token)
+#[derive(Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Credentials {
+    /// Credentials from [`USERNAME_ENV`] and [`PASSWORD_ENV`], if both are set
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            username: std::env::var(USERNAME_ENV).ok()?,
+            password: std::env::var(PASSWORD_ENV).ok()?,
+        })
+    }
+}
+
+/// A content descriptor within an OCI manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Descriptor {
+    fn for_content(media_type: &str, data: &[u8]) -> Self {
+        Self {
+            media_type: media_type.to_string(),
+            digest: content_digest(data),
+            size: data.len() as u64,
+            annotations: BTreeMap::new(),
+        }
+    }
+}
+
+/// An OCI image manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// A bundle downloaded from a registry
+#[derive(Debug)]
+pub struct PulledBundle {
+    /// The bundle archive
+    pub bundle: Vec<u8>,
+    /// Digest of the artifact manifest, which identifies the artifact
+    pub manifest_digest: String,
+    /// Digest of the bundle archive
+    pub bundle_digest: String,
+    /// Detached signature of the bundle archive, if one was pushed with it
+    pub signature: Option<String>,
+}
+
+/// How requests to the registry are authenticated
+#[derive(Debug, Clone)]
+enum Auth {
+    Anonymous,
+    Basic,
+    Bearer(String),
+}
+
+/// An authentication challenge from a `WWW-Authenticate` header
+#[derive(Debug, PartialEq, Eq)]
+enum Challenge {
+    Basic,
+    Bearer {
+        realm: String,
+        service: Option<String>,
+        scope: Option<String>,
+    },
+}
+
+/// Minimal client for the OCI distribution API
+#[derive(Debug)]
+pub struct OciClient {
+    http: reqwest::Client,
+    credentials: Option<Credentials>,
+    plain_http: bool,
+    auth: Mutex<Auth>,
+}
+
+impl OciClient {
+    #[must_use]
+    pub fn new(credentials: Option<Credentials>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            credentials,
+            plain_http: false,
+            auth: Mutex::new(Auth::Anonymous),
+        }
+    }
+
+    /// Talk to the registry over plain HTTP instead of HTTPS
+    ///
+    /// Registries on `localhost` always use plain HTTP.
+    #[must_use]
+    pub fn with_plain_http(mut self, plain_http: bool) -> Self {
+        self.plain_http = plain_http;
+        self
+    }
+
+    /// Push a bundle archive as an artifact
+    ///
+    /// `config` is the bundle manifest serialized as JSON. A detached
+    /// `signature` of the archive is attached as a layer annotation. Returns
+    /// the digest of the pushed artifact manifest.
+    ///
+    /// # Errors
+    /// Returns an error if the reference has no tag, or the registry rejects
+    /// an upload or the manifest.
+    pub async fn push(
+        &self,
+        reference: &Reference,
+        bundle: &[u8],
+        config: &[u8],
+        signature: Option<&str>,
+    ) -> Result<String> {
+        let Some(tag) = &reference.tag else {
+            return InvalidReferenceSnafu {
+                reference: reference.to_string(),
+                message: "a tag is required to push",
+            }
+            .fail();
+        };
+
+        let config_descriptor = Descriptor::for_content(CONFIG_MEDIA_TYPE, config);
+        let mut layer = Descriptor::for_content(BUNDLE_MEDIA_TYPE, bundle);
+        layer.annotations.insert(
+            "org.opencontainers.image.title".to_string(),
+            "bundle.tar".to_string(),
+        );
+        if let Some(signature) = signature {
+            layer.annotations.insert(
+                SIGNATURE_ANNOTATION.to_string(),
+                signature.trim().to_string(),
+            );
+        }
+
+        self.upload_blob(reference, config, &config_descriptor.digest)
+            .await?;
+        self.upload_blob(reference, bundle, &layer.digest).await?;
+
+        let manifest = Manifest {
+            schema_version: 2,
+            media_type: Some(MANIFEST_MEDIA_TYPE.to_string()),
+            artifact_type: Some(ARTIFACT_TYPE.to_string()),
+            config: config_descriptor,
+            layers: vec![layer],
+            annotations: BTreeMap::from([(
+                "org.opencontainers.image.created".to_string(),
+                chrono::Utc::now().to_rfc3339(),
+            )]),
+        };
+        let body = serde_json::to_vec(&manifest).context(ManifestSnafu {
+            reference: reference.to_string(),
+        })?;
+        let digest = content_digest(&body);
+
+        let url = format!("{}/manifests/{tag}", self.repository_url(reference));
+        let request = self
+            .http
+            .put(&url)
+            .header(CONTENT_TYPE, MANIFEST_MEDIA_TYPE)
+            .body(body);
+        ensure_success(self.send(reference, request).await?, &url).await?;
+
+        Ok(digest)
+    }
+
+    /// Pull a bundle archive, verifying the digests of the manifest and layer
+    ///
+    /// # Errors
+    /// Returns an error if the artifact cannot be fetched, is not a workflow
+    /// bundle, or its content does not match its digests.
+    pub async fn pull(&self, reference: &Reference) -> Result<PulledBundle> {
+        let url = format!(
+            "{}/manifests/{}",
+            self.repository_url(reference),
+            reference.target()
+        );
+        let request = self.http.get(&url).header(ACCEPT, MANIFEST_MEDIA_TYPE);
+        let response = ensure_success(self.send(reference, request).await?, &url).await?;
+        let body = response.bytes().await.context(HttpSnafu)?;
+
+        let manifest_digest = content_digest(&body);
+        if let Some(expected) = &reference.digest {
+            ensure!(
+                *expected == manifest_digest,
+                DigestMismatchSnafu {
+                    what: format!("manifest of {reference}"),
+                    expected: expected.clone(),
+                    actual: manifest_digest,
+                }
+            );
+        }
+
+        let manifest: Manifest = serde_json::from_slice(&body).context(ManifestSnafu {
+            reference: reference.to_string(),
+        })?;
+        let layer = manifest
+            .layers
+            .iter()
+            .find(|layer| layer.media_type == BUNDLE_MEDIA_TYPE)
+            .context(NotABundleSnafu {
+                reference: reference.to_string(),
+            })?;
+
+        let url = format!("{}/blobs/{}", self.repository_url(reference), layer.digest);
+        let response =
+            ensure_success(self.send(reference, self.http.get(&url)).await?, &url).await?;
+        let bundle = response.bytes().await.context(HttpSnafu)?.to_vec();
+
+        let bundle_digest = content_digest(&bundle);
+        ensure!(
+            bundle_digest == layer.digest,
+            DigestMismatchSnafu {
+                what: format!("bundle layer of {reference}"),
+                expected: layer.digest.clone(),
+                actual: bundle_digest,
+            }
+        );
+
+        Ok(PulledBundle {
+            bundle,
+            manifest_digest,
+            bundle_digest,
+            signature: layer.annotations.get(SIGNATURE_ANNOTATION).cloned(),
+        })
+    }
+
+    /// Upload a blob unless the repository already has it
+    async fn upload_blob(&self, reference: &Reference, data: &[u8], digest: &str) -> Result<()> {
+        let repository_url = self.repository_url(reference);
+
+        let exists = self
+            .send(
+                reference,
+                self.http.head(format!("{repository_url}/blobs/{digest}")),
+            )
+            .await?;
+        if exists.status().is_success() {
+            return Ok(());
+        }
+
+        let url = format!("{repository_url}/blobs/uploads/");
+        let response =
+            ensure_success(self.send(reference, self.http.post(&url)).await?, &url).await?;
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .context(MissingUploadLocationSnafu { url: url.clone() })?;
+
+        // The upload location may be relative to the registry
+        let location = if location.starts_with('/') {
+            format!("{}{location}", self.base_url(reference))
+        } else {
+            location.to_string()
+        };
+        let separator = if location.contains('?') { '&' } else { '?' };
+        let url = format!("{location}{separator}digest={}", digest.replace(':', "%3A"));
+
+        let request = self
+            .http
+            .put(&url)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(data.to_vec());
+        ensure_success(self.send(reference, request).await?, &url).await?;
+        Ok(())
+    }
+
+    /// Send a request, authenticating and retrying once if the registry asks for it
+    async fn send(
+        &self,
+        reference: &Reference,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let retry = request.try_clone();
+        let response = self.authorize(request).send().await.context(HttpSnafu)?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(retry) = retry else {
+            return Ok(response);
+        };
+
+        let auth = match challenge(response.headers()) {
+            Some(Challenge::Bearer {
+                realm,
+                service,
+                scope,
+            }) => {
+                let scope =
+                    scope.unwrap_or_else(|| format!("repository:{}:pull", reference.repository));
+                Auth::Bearer(self.fetch_token(&realm, service.as_deref(), &scope).await?)
+            }
+            Some(Challenge::Basic) if self.credentials.is_some() => Auth::Basic,
+            Some(Challenge::Basic) | None => return Ok(response),
+        };
+        *self.auth.lock().unwrap_or_else(PoisonError::into_inner) = auth;
+
+        self.authorize(retry).send().await.context(HttpSnafu)
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let auth = self
+            .auth
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        match (auth, &self.credentials) {
+            (Auth::Bearer(token), _) => request.bearer_auth(token),
+            (Auth::Basic, Some(credentials)) => {
+                request.basic_auth(&credentials.username, Some(&credentials.password))
+            }
+            (Auth::Basic | Auth::Anonymous, _) => request,
+        }
+    }
+
+    /// Exchange the credentials (or nothing, for anonymous pulls) for a bearer token
+    async fn fetch_token(&self, realm: &str, service: Option<&str>, scope: &str) -> Result<String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+
+        let mut query = vec![("scope", scope)];
+        if let Some(service) = service {
+            query.push(("service", service));
+        }
+        let mut request = self.http.get(realm).query(&query);
+        if let Some(credentials) = &self.credentials {
+            request = request.basic_auth(&credentials.username, Some(&credentials.password));
+        }
+
+        let response = request.send().await.context(HttpSnafu)?;
+        if !response.status().is_success() {
+            return TokenSnafu {
+                realm,
+                message: format!("status {}", response.status()),
+            }
+            .fail();
+        }
+        let body: TokenResponse = response.json().await.context(HttpSnafu)?;
+        body.token.or(body.access_token).context(TokenSnafu {
+            realm,
+            message: "response contained no token",
+        })
+    }
+
+    fn base_url(&self, reference: &Reference) -> String {
+        let scheme = if self.plain_http || reference.is_local() {
+            "http"
+        } else {
+            "https"
+        };
+        format!("{scheme}://{}", reference.api_host())
+    }
+
+    fn repository_url(&self, reference: &Reference) -> String {
+        format!("{}/v2/{}", self.base_url(reference), reference.repository)
+    }
+}
+
+/// Turn an unsuccessful response into an error carrying the registry's message
+async fn ensure_success(response: reqwest::Response, url: &str) -> Result<reqwest::Response> {
+    #[derive(Deserialize)]
+    struct RegistryErrors {
+        errors: Vec<RegistryError>,
+    }
+    #[derive(Deserialize)]
+    struct RegistryError {
+        code: String,
+        message: Option<String>,
+    }
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<RegistryErrors>(&body) {
+        Ok(parsed) if !parsed.errors.is_empty() => parsed
+            .errors
+            .iter()
+            .map(|e| match &e.message {
+                Some(message) => format!("{}: {message}", e.code),
+                None => e.code.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join("; "),
+        Ok(_) | Err(_) => truncate(&body, MAX_EXCERPT_LEN).into_owned(),
+    };
+
+    RegistrySnafu {
+        url,
+        status: status.as_u16(),
+        message,
+    }
+    .fail()
+}
+
+/// Parse the `WWW-Authenticate` challenge of a 401 response
+fn challenge(headers: &HeaderMap) -> Option<Challenge> {
+    let header = headers.get(WWW_AUTHENTICATE)?.to_str().ok()?;
+    parse_challenge(header)
+}
+
+fn parse_challenge(header: &str) -> Option<Challenge> {
+    let (scheme, params) = header.trim().split_once(' ').unwrap_or((header.trim(), ""));
+    if scheme.eq_ignore_ascii_case("basic") {
+        return Some(Challenge::Basic);
+    }
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+
+    // Parameters are comma separated, but quoted values (such as scopes) may contain commas
+    let mut values = BTreeMap::new();
+    let mut rest = params.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .to_ascii_lowercase();
+        let (value, after) = if let Some(quoted) = after.strip_prefix('"') {
+            quoted.split_once('"').unwrap_or((quoted, ""))
+        } else {
+            after.split_once(',').unwrap_or((after, ""))
+        };
+        values.insert(key, value.to_string());
+        rest = after.trim_start_matches(',').trim();
+    }
+
+    Some(Challenge::Bearer {
+        realm: values.remove("realm")?,
+        service: values.remove("service"),
+        scope: values.remove("scope"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    #[test]
+    fn test_parse_full_reference() {
+        let reference = Reference::parse("ghcr.io/org/wf:1.2.0").unwrap();
+        assert_eq!(reference.registry, "ghcr.io");
+        assert_eq!(reference.repository, "org/wf");
+        assert_eq!(reference.tag.as_deref(), Some("1.2.0"));
+        assert_eq!(reference.target(), "1.2.0");
+        assert_eq!(reference.to_string(), "ghcr.io/org/wf:1.2.0");
+    }
+
+    #[test]
+    fn test_parse_reference_with_port_and_digest() {
+        let digest = format!("sha256:{}", "a".repeat(64));
+        let reference =
+            Reference::parse(&format!("oci://localhost:5000/workflows/etl@{digest}")).unwrap();
+        assert_eq!(reference.registry, "localhost:5000");
+        assert_eq!(reference.repository, "workflows/etl");
+        assert_eq!(reference.tag, None);
+        assert_eq!(reference.target(), digest);
+        assert!(reference.is_local());
+    }
+
+    #[test]
+    fn test_parse_docker_hub_reference() {
+        let reference = Reference::parse("wf").unwrap();
+        assert_eq!(reference.registry, "docker.io");
+        assert_eq!(reference.repository, "library/wf");
+        assert_eq!(reference.target(), "latest");
+        assert_eq!(reference.api_host(), "registry-1.docker.io");
+
+        let reference = Reference::parse("org/wf:dev").unwrap();
+        assert_eq!(reference.repository, "org/wf");
+        assert_eq!(reference.tag.as_deref(), Some("dev"));
+    }
+
+    #[test]
+    fn test_parse_invalid_references() {
+        assert!(Reference::parse("ghcr.io/Org/WF:1").is_err());
+        assert!(Reference::parse("ghcr.io/org/wf:").is_err());
+        assert!(Reference::parse("ghcr.io/org/wf@md5:abc").is_err());
+        assert!(Reference::parse("").is_err());
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        let challenge = parse_challenge(
+            r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:org/wf:pull,push""#,
+        );
+        assert_eq!(
+            challenge,
+            Some(Challenge::Bearer {
+                realm: "https://ghcr.io/token".to_string(),
+                service: Some("ghcr.io".to_string()),
+                scope: Some("repository:org/wf:pull,push".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_challenge(r#"Basic realm="Registry""#),
+            Some(Challenge::Basic)
+        );
+        assert_eq!(parse_challenge("Bearer service=\"x\""), None);
+    }
+
+    #[test]
+    fn test_manifest_serializes_as_oci_artifact() {
+        let manifest = Manifest {
+            schema_version: 2,
+            media_type: Some(MANIFEST_MEDIA_TYPE.to_string()),
+            artifact_type: Some(ARTIFACT_TYPE.to_string()),
+            config: Descriptor::for_content(CONFIG_MEDIA_TYPE, b"{}"),
+            layers: vec![Descriptor::for_content(BUNDLE_MEDIA_TYPE, b"bundle")],
+            annotations: BTreeMap::new(),
+        };
+        let json = serde_json::to_value(&manifest).unwrap();
+        assert_eq!(json.pointer("/schemaVersion"), Some(&serde_json::json!(2)));
+        assert_eq!(
+            json.pointer("/artifactType"),
+            Some(&serde_json::json!(ARTIFACT_TYPE))
+        );
+        assert_eq!(json.pointer("/config/size"), Some(&serde_json::json!(2)));
+        assert_eq!(
+            json.pointer("/layers/0/mediaType"),
+            Some(&serde_json::json!(BUNDLE_MEDIA_TYPE))
+        );
+        assert!(
+            json.pointer("/layers/0/digest")
+                .and_then(serde_json::Value::as_str)
+                .unwrap()
+                .starts_with("sha256:")
+        );
+    }
+}
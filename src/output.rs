@@ -4,11 +4,19 @@
 
 use console::style;
 use serde_json::Value;
+use std::borrow::Cow;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock};
+
+use crate::secrets::Redactor;
+use crate::text::{MAX_DISPLAY_LEN, truncate};
 
 /// Global debug mode flag
 static DEBUG_MODE: AtomicBool = AtomicBool::new(false);
 
+/// Secret values masked in everything printed
+static REDACTOR: RwLock<Option<Redactor>> = RwLock::new(None);
+
 /// Enable debug mode to show detailed execution information
 pub fn set_debug_mode(enabled: bool) {
     DEBUG_MODE.store(enabled, Ordering::Relaxed);
@@ -19,6 +27,54 @@ pub fn is_debug_mode() -> bool {
     DEBUG_MODE.load(Ordering::Relaxed)
 }
 
+/// Mask `values` in everything printed from now on
+///
+/// Called with the secrets of each instance when it starts; values
+/// registered by earlier instances stay masked.
+pub fn register_secrets(values: impl IntoIterator<Item = String>) {
+    let mut redactor = REDACTOR.write().unwrap_or_else(PoisonError::into_inner);
+    let merged = match redactor.as_ref() {
+        Some(known) => known.merged(values),
+        None => Redactor::new(values),
+    };
+    *redactor = Some(merged);
+}
+
+/// `value` with the registered secret values masked
+#[must_use]
+pub fn redact_secrets(value: &Value) -> Value {
+    let mut value = value.clone();
+    if let Some(redactor) = REDACTOR
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        redactor.redact(&mut value);
+    }
+    value
+}
+
+/// `text` with the registered secret values masked
+fn redact_text(text: &str) -> Cow<'_, str> {
+    match REDACTOR
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        Some(redactor) if !redactor.is_empty() => Cow::Owned(redactor.redact_str(text)),
+        Some(_) | None => Cow::Borrowed(text),
+    }
+}
+
+/// `text` with the registered secret values masked, cut to a size that
+/// fits in the console
+///
+/// Secrets are masked before the text is cut, so that a cut can never leave
+/// the start of a secret unmasked.
+fn shown(text: &str) -> String {
+    truncate(&redact_text(text), MAX_DISPLAY_LEN).into_owned()
+}
+
 /// Format a workflow start message
 pub fn format_workflow_start(workflow_name: &str, instance_id: &str) {
     if !is_debug_mode() {
@@ -68,6 +124,7 @@ pub fn format_context(title: &str, context: &Value) {
     }
     println!("\n{}", style(title).bold());
     println!("{}", "┄".repeat(80));
+    let context = &redact_secrets(context);
     if let Some(obj) = context.as_object() {
         if obj.is_empty() {
             println!("  {}", style("(empty)").dim());
@@ -114,7 +171,7 @@ pub fn format_workflow_output(output: &Value, duration_ms: i64) {
     );
     println!("{}", "─".repeat(80));
 
-    let filtered = filter_internal_fields(output);
+    let filtered = redact_secrets(&filter_internal_fields(output));
 
     // Only show output section if there's meaningful data to display
     // Skip if it's just stdout/stderr/exitCode (which was already streamed)
@@ -166,6 +223,34 @@ pub fn format_task_skipped(task_name: &str) {
     );
 }
 
+/// Format task not run because its `if` condition is false
+pub fn format_task_condition_false(task_name: &str, condition: &str) {
+    if !is_debug_mode() {
+        return;
+    }
+    println!(
+        "  {} {}",
+        style("⤼").yellow(),
+        style(format!("Skipping '{task_name}' (if {condition} is false)")).yellow()
+    );
+}
+
+/// Format the values of the watch expressions after a task completed
+///
+/// Printed whether or not debug mode is on, since watches are requested
+/// explicitly. They go to stderr to keep the workflow output on stdout clean.
+pub fn format_watches(task_name: &str, values: &serde_json::Map<String, Value>) {
+    for (expr, value) in values {
+        eprintln!(
+            "  {} {} {} = {}",
+            style("◉").magenta(),
+            style(task_name).dim(),
+            style(expr).magenta(),
+            redact_secrets(value)
+        );
+    }
+}
+
 /// Format cache hit
 pub fn format_cache_hit(_task_name: &str, key: &str, timestamp: Option<&str>) {
     if !is_debug_mode() {
@@ -193,6 +278,41 @@ pub fn format_cache_miss(_task_name: &str, key: &str) {
     println!("    {} {}", style("Key:").yellow(), style(key).yellow());
 }
 
+/// Format how well the cache saved work, at the end of a run
+pub fn format_cache_stats(stats: &crate::cache::CacheStats) {
+    let hit_rate = stats.hit_rate().map_or_else(
+        || "no lookups".to_string(),
+        |rate| format!("{:.0}% hit rate", rate * 100.0),
+    );
+    println!(
+        "{} {} {} hits, {} misses ({}); {} entries, {} stored",
+        style("→").cyan(),
+        style("Cache:").bold(),
+        stats.hits,
+        stats.misses,
+        hit_rate,
+        stats.entries,
+        format_bytes(stats.bytes)
+    );
+}
+
+/// Format a byte count with a binary unit, e.g. `1.5 KiB`
+#[must_use]
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS.get(unit).unwrap_or(&"TiB"))
+}
+
 /// Filter out internal descriptor fields from display
 #[must_use]
 pub fn filter_internal_fields(value: &Value) -> Value {
@@ -215,7 +335,7 @@ pub fn format_task_context(context: &Value) {
     }
     println!("  {}", style("Context").white());
     println!("  {}", "·".repeat(78));
-    let filtered = filter_internal_fields(context);
+    let filtered = redact_secrets(&filter_internal_fields(context));
     if let Some(obj) = filtered.as_object() {
         if obj.is_empty() {
             println!("    {}", style("(empty)").dim());
@@ -237,7 +357,7 @@ pub fn format_task_input(input: &Value) {
     }
     println!("  {}", style("Input").cyan());
     println!("  {}", "·".repeat(78));
-    let filtered = filter_internal_fields(input);
+    let filtered = redact_secrets(&filter_internal_fields(input));
     if let Some(obj) = filtered.as_object() {
         if obj.is_empty() {
             println!("    {}", style("(empty)").dim());
@@ -270,7 +390,7 @@ pub fn format_run_task_params(
         println!(
             "  {} {}",
             style("Stdin:").cyan(),
-            style(format!("\"{stdin_val}\"")).cyan()
+            style(format!("\"{}\"", redact_text(stdin_val))).cyan()
         );
     }
 
@@ -281,7 +401,7 @@ pub fn format_run_task_params(
         println!("  {}", style("Arguments:").cyan());
         for arg in arr {
             if let Some(s) = arg.as_str() {
-                println!("    {}", style(format!("- {s}")).cyan());
+                println!("    {}", style(format!("- {}", redact_text(s))).cyan());
             }
         }
     }
@@ -296,7 +416,7 @@ pub fn format_run_task_params(
                 println!(
                     "    {} {}",
                     style(format!("{key}:")).cyan(),
-                    style(s).cyan()
+                    style(redact_text(s)).cyan()
                 );
             }
         }
@@ -326,7 +446,7 @@ pub fn format_task_output(output: &Value, duration_ms: i64) {
     );
     println!("  {}", "·".repeat(78));
 
-    let filtered = filter_internal_fields(output);
+    let filtered = redact_secrets(&filter_internal_fields(output));
 
     // For streamed output, show stdout/stderr based on exit code
     if output
@@ -346,7 +466,10 @@ pub fn format_task_output(output: &Value, duration_ms: i64) {
                     if stdout.is_empty() {
                         println!("    {}", style("(empty)").dim());
                     } else {
-                        println!("    {}", style(format!("\"{stdout}\"")).green());
+                        println!(
+                            "    {}",
+                            style(format!("\"{}\"", truncate(stdout, MAX_DISPLAY_LEN))).green()
+                        );
                     }
                 } else {
                     println!("    {}", style("(empty)").dim());
@@ -359,7 +482,10 @@ pub fn format_task_output(output: &Value, duration_ms: i64) {
                     .filter(|s| !s.is_empty())
                 {
                     println!("    {}", style("stdout:").green());
-                    println!("      {}", style(format!("\"{stdout}\"")).green());
+                    println!(
+                        "      {}",
+                        style(format!("\"{}\"", truncate(stdout, MAX_DISPLAY_LEN))).green()
+                    );
                 }
                 if let Some(stderr) = obj
                     .get("stderr")
@@ -367,7 +493,10 @@ pub fn format_task_output(output: &Value, duration_ms: i64) {
                     .filter(|s| !s.is_empty())
                 {
                     println!("    {}", style("stderr:").green());
-                    println!("      {}", style(format!("\"{stderr}\"")).green());
+                    println!(
+                        "      {}",
+                        style(format!("\"{}\"", truncate(stderr, MAX_DISPLAY_LEN))).green()
+                    );
                 }
             }
         }
@@ -396,14 +525,14 @@ pub fn format_task_logs(stdout: Option<&str>, stderr: Option<&str>) {
     }
     if let Some(out) = stdout.filter(|s| !s.trim().is_empty()) {
         println!("  {}", style("Stdout").dim());
-        for line in out.lines() {
+        for line in shown(out).lines() {
             println!("    {}", style(line).dim());
         }
     }
 
     if let Some(err) = stderr.filter(|e| !e.trim().is_empty()) {
         println!("  {}", style("Stderr").yellow());
-        for line in err.lines() {
+        for line in shown(err).lines() {
             println!("    {}", style(line).yellow());
         }
     }
@@ -431,7 +560,11 @@ pub fn format_task_error(task_name: &str, error: &str) {
         style("✗").red().bold(),
         style(format!("Failed '{task_name}'")).red().bold()
     );
-    println!("    {} {}", style("Error:").red(), style(error).red());
+    println!(
+        "    {} {}",
+        style("Error:").red(),
+        style(shown(error)).red()
+    );
 }
 
 /// Format fork branch execution
@@ -464,6 +597,7 @@ pub fn format_branch_start(branch_name: &str, task_type: &str) {
 /// Helper: Indent JSON output
 fn indent_json(value: &Value, indent: usize) -> String {
     let json_str = serde_json::to_string_pretty(value).unwrap_or_else(|_| "{}".to_string());
+    let json_str = truncate(&json_str, MAX_DISPLAY_LEN);
     let indent_str = " ".repeat(indent);
     json_str
         .lines()
@@ -475,6 +609,7 @@ fn indent_json(value: &Value, indent: usize) -> String {
 /// Helper: Colorize JSON output
 fn colorize_json(value: &Value, indent: usize, color: &str) -> String {
     let json_str = serde_json::to_string_pretty(value).unwrap_or_else(|_| "{}".to_string());
+    let json_str = truncate(&json_str, MAX_DISPLAY_LEN);
     let indent_str = " ".repeat(indent);
     let styled_lines: Vec<String> = json_str
         .lines()
@@ -0,0 +1,32 @@
+//! The types most programs embedding jackdaw use
+//!
+//! ```rust,no_run
+//! use jackdaw::prelude::*;
+//! use std::time::Duration;
+//!
+//! # async fn example(workflow: WorkflowDefinition) -> Result<(), Box<dyn std::error::Error>> {
+//! let engine = DurableEngineBuilder::new().build()?;
+//! let output = engine
+//!     .execute(workflow, serde_json::json!({}))
+//!     .await?
+//!     .wait_for_completion(Duration::from_secs(30))
+//!     .await?;
+//! println!("{output}");
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::builder::DurableEngineBuilder;
+pub use crate::cache::{CacheEntry, CacheProvider};
+pub use crate::context::Context;
+pub use crate::durableengine::{DurableEngine, InstanceFilter, InstanceState, InstanceStatus};
+pub use crate::events::{CloudEvent, EventSink};
+pub use crate::execution_handle::ExecutionHandle;
+pub use crate::executor::{Executor, WorkflowError};
+pub use crate::listeners::Listener;
+pub use crate::middleware::TaskMiddleware;
+pub use crate::observer::WorkflowObserver;
+pub use crate::persistence::PersistenceProvider;
+pub use crate::secrets::SecretsProvider;
+pub use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
+pub use serverless_workflow_core::models::workflow::WorkflowDefinition;
@@ -0,0 +1,233 @@
+//! Process groups for shell and script tasks
+//!
+//! Commands spawned by shell and script tasks run in a process group of their
+//! own, so the processes they start in turn can be killed along with them.
+//! `kill_on_drop` only reaches the command itself; a [`ProcessGroup`] kills
+//! the whole group when a task is cancelled, times out or fails.
+//!
+//! Groups whose command exited while processes it started kept running are
+//! remembered, and [`reap_orphans`] kills them when the engine shuts down.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use tokio::process::{Child, Command};
+
+use crate::workflow::ReapedResource;
+
+/// Process groups left running by commands that have exited, keyed by group ID
+static ORPHANS: LazyLock<Mutex<HashMap<u32, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Run `command` in a new process group led by the spawned process
+pub fn isolate(command: &mut Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// The process group of a spawned command
+///
+/// Dropping the group kills every process in it, so work started by a task
+/// does not outlive the task on error paths. Call [`ProcessGroup::release`]
+/// once the command has exited normally.
+#[derive(Debug)]
+pub struct ProcessGroup {
+    id: Option<u32>,
+    command: String,
+}
+
+impl ProcessGroup {
+    /// Track the process group of `child`, spawned from a command passed to [`isolate`]
+    #[must_use]
+    pub fn new(child: &Child, command: impl Into<String>) -> Self {
+        let id = child.id();
+        if let (Some(id), Ok(mut orphans)) = (id, ORPHANS.lock()) {
+            // The ID of a group that has since died can be reused
+            orphans.remove(&id);
+        }
+        Self {
+            id,
+            command: command.into(),
+        }
+    }
+
+    /// Kill every process in the group
+    #[must_use]
+    pub fn kill(mut self) -> ReapedResource {
+        let id = self.id.take();
+        if let Some(id) = id {
+            kill_group(id);
+        }
+        ReapedResource::Process {
+            pid: id,
+            command: self.command.clone(),
+        }
+    }
+
+    /// Let the group go after its command exited
+    ///
+    /// Processes the command left running are killed by [`reap_orphans`].
+    pub fn release(mut self) {
+        let Some(id) = self.id.take() else {
+            return;
+        };
+        if !is_alive(id) {
+            return;
+        }
+        tracing::debug!(
+            "Command '{}' exited leaving processes in group {} running",
+            self.command,
+            id
+        );
+        if let Ok(mut orphans) = ORPHANS.lock() {
+            orphans.insert(id, self.command.clone());
+        }
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            kill_group(id);
+        }
+    }
+}
+
+/// Kill the processes left running by shell and script tasks whose command exited
+///
+/// Call this when the engine shuts down. Returns the process groups that
+/// were killed.
+pub fn reap_orphans() -> Vec<ReapedResource> {
+    let orphans: Vec<(u32, String)> = match ORPHANS.lock() {
+        Ok(mut orphans) => orphans.drain().collect(),
+        Err(_) => return Vec::new(),
+    };
+
+    let mut reaped = Vec::new();
+    for (id, command) in orphans {
+        if !is_alive(id) {
+            continue;
+        }
+        tracing::warn!(
+            "Killing processes left running by command '{}' (process group {})",
+            command,
+            id
+        );
+        kill_group(id);
+        reaped.push(ReapedResource::Process {
+            pid: Some(id),
+            command,
+        });
+    }
+    reaped
+}
+
+#[cfg(unix)]
+fn signal_group(id: u32, signal: libc::c_int) -> bool {
+    let Ok(pgid) = libc::pid_t::try_from(id) else {
+        return false;
+    };
+    // SAFETY: killpg only sends a signal; it has no memory-safety requirements
+    unsafe { libc::killpg(pgid, signal) == 0 }
+}
+
+#[cfg(unix)]
+fn kill_group(id: u32) {
+    signal_group(id, libc::SIGKILL);
+}
+
+#[cfg(unix)]
+fn is_alive(id: u32) -> bool {
+    signal_group(id, 0)
+}
+
+#[cfg(not(unix))]
+fn kill_group(_id: u32) {}
+
+#[cfg(not(unix))]
+fn is_alive(_id: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use std::process::Stdio;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    /// Spawn `script` in its own process group with stdout piped
+    ///
+    /// Every process it starts inherits the pipe, so reading stdout to the
+    /// end waits until all of them have died.
+    fn spawn(script: &str) -> Child {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", script])
+            .stdout(Stdio::piped())
+            .kill_on_drop(true);
+        isolate(&mut command);
+        command.spawn().unwrap()
+    }
+
+    async fn wait_for_group_exit(child: &mut Child) {
+        let mut stdout = child.stdout.take().unwrap();
+        let mut output = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), stdout.read_to_end(&mut output))
+            .await
+            .expect("processes in the group should have been killed")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dropping_group_kills_grandchildren() {
+        let mut child = spawn("sleep 30 & wait");
+        let group = ProcessGroup::new(&child, "sh");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        drop(group);
+        wait_for_group_exit(&mut child).await;
+    }
+
+    #[tokio::test]
+    async fn test_kill_reports_the_group() {
+        let mut child = spawn("sleep 30 & wait");
+        let group = ProcessGroup::new(&child, "sh");
+        let id = child.id();
+
+        let reaped = group.kill();
+        assert_eq!(
+            reaped,
+            ReapedResource::Process {
+                pid: id,
+                command: "sh".to_string()
+            }
+        );
+        wait_for_group_exit(&mut child).await;
+    }
+
+    #[tokio::test]
+    async fn test_released_group_with_orphans_is_reaped() {
+        let mut child = spawn("sleep 30 &");
+        let group = ProcessGroup::new(&child, "orphaning");
+        let id = child.id().unwrap();
+        child.wait().await.unwrap();
+
+        group.release();
+        assert!(ORPHANS.lock().unwrap().contains_key(&id));
+
+        let reaped = reap_orphans();
+        assert!(reaped.contains(&ReapedResource::Process {
+            pid: Some(id),
+            command: "orphaning".to_string()
+        }));
+        wait_for_group_exit(&mut child).await;
+    }
+}
@@ -0,0 +1,48 @@
+//! Chrome trace profiles of workflow runs
+//!
+//! The engine opens `trace`-level spans with the target `jackdaw::profile`
+//! around its internals: each task, building the workflow graph, evaluating
+//! expressions, persistence writes and executor calls. `jackdaw run
+//! --profile-out trace.json` writes them as a Chrome trace, which
+//! `chrome://tracing` or <https://ui.perfetto.dev> show as a timeline of where
+//! a run spent its time. Library users can add [`chrome_layer`] to their own
+//! tracing subscriber.
+
+use std::path::Path;
+use tracing::{Level, Span, Subscriber};
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard, TraceStyle};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Target of the profiling spans
+pub const TARGET: &str = "jackdaw::profile";
+
+/// A layer writing the profiling spans to `path` as a Chrome trace
+///
+/// The trace is complete once the returned guard is dropped.
+///
+/// # Errors
+///
+/// Returns an error if `path` cannot be created
+pub fn chrome_layer<S>(path: &Path) -> std::io::Result<(impl Layer<S>, FlushGuard)>
+where
+    S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync,
+{
+    let file = std::fs::File::create(path)?;
+    let (layer, guard) = ChromeLayerBuilder::new()
+        .writer(file)
+        // Tasks are futures, so spans are drawn from creation to close rather
+        // than per poll on a thread
+        .trace_style(TraceStyle::Async)
+        .include_args(true)
+        .build();
+    let filter = Targets::new().with_target(TARGET, Level::TRACE);
+    Ok((layer.with_filter(filter), guard))
+}
+
+/// The span of a call to the executor or container provider named `executor`
+#[must_use]
+pub fn executor_span(executor: &str) -> Span {
+    tracing::trace_span!(target: TARGET, "executor", executor)
+}
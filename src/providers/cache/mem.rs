@@ -1,4 +1,5 @@
-use crate::cache::{CacheEntry, CacheProvider, Result};
+use crate::cache::{CacheCounters, CacheEntry, CacheFootprint, CacheProvider, CacheStats, Result};
+use crate::durableengine::memory::json_size;
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
@@ -6,12 +7,14 @@ use std::sync::{Arc, Mutex};
 #[derive(Debug)]
 pub struct InMemoryCache {
     store: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    counters: CacheCounters,
 }
 
 impl Default for InMemoryCache {
     fn default() -> Self {
         Self {
             store: Arc::new(Mutex::new(HashMap::new())),
+            counters: CacheCounters::default(),
         }
     }
 }
@@ -31,7 +34,8 @@ impl CacheProvider for InMemoryCache {
             .store
             .lock()
             .unwrap_or_else(std::sync::PoisonError::into_inner);
-        Ok(store.get(key).cloned())
+        let entry = store.get(key).filter(|entry| !entry.is_expired()).cloned();
+        Ok(self.counters.record(entry))
     }
 
     async fn set(&self, entry: CacheEntry) -> Result<()> {
@@ -51,4 +55,47 @@ impl CacheProvider for InMemoryCache {
         store.remove(key);
         Ok(())
     }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        let mut store = self
+            .store
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let before = store.len();
+        store.retain(|_, entry| !entry.is_expired());
+        Ok(u64::try_from(before - store.len()).unwrap_or(u64::MAX))
+    }
+
+    async fn purge_all(&self) -> Result<u64> {
+        let mut store = self
+            .store
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let removed = store.len();
+        store.clear();
+        Ok(u64::try_from(removed).unwrap_or(u64::MAX))
+    }
+
+    async fn footprint(&self) -> Result<Option<CacheFootprint>> {
+        let store = self
+            .store
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let bytes = store
+            .values()
+            .map(|entry| json_size(&entry.inputs) + json_size(&entry.output))
+            .sum();
+        Ok(Some(CacheFootprint {
+            entries: store.len(),
+            bytes,
+        }))
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let footprint = self.footprint().await?.unwrap_or_default();
+        Ok(self.counters.stats(
+            u64::try_from(footprint.entries).unwrap_or(u64::MAX),
+            u64::try_from(footprint.bytes).unwrap_or(u64::MAX),
+        ))
+    }
 }
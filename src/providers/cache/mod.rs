@@ -1,10 +1,17 @@
 pub mod mem;
+#[cfg(feature = "postgres")]
 pub mod postgres;
 pub mod redb;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "sqlite")]
 pub mod sqlite;
 
-#[allow(unused_imports)]
+pub use self::mem::InMemoryCache;
+#[cfg(feature = "postgres")]
 pub use self::postgres::PostgresCache;
 pub use self::redb::RedbCache;
-#[allow(unused_imports)]
+#[cfg(feature = "redis")]
+pub use self::redis::RedisCache;
+#[cfg(feature = "sqlite")]
 pub use self::sqlite::SqliteCache;
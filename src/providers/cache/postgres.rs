@@ -1,10 +1,11 @@
-use crate::cache::{CacheEntry, CacheProvider, Error, Result};
+use crate::cache::{CacheCounters, CacheEntry, CacheProvider, CacheStats, Error, Result};
 use async_trait::async_trait;
 use sqlx::postgres::{PgPool, PgPoolOptions};
 
 #[derive(Debug)]
 pub struct PostgresCache {
     pool: PgPool,
+    counters: CacheCounters,
 }
 
 #[allow(dead_code)]
@@ -46,7 +47,10 @@ impl PostgresCache {
                 })?;
         }
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            counters: CacheCounters::default(),
+        })
     }
 
     /// Create a new ``PostgreSQL`` cache with custom pool options
@@ -66,57 +70,64 @@ impl PostgresCache {
                 })?;
         }
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            counters: CacheCounters::default(),
+        })
     }
 }
 
 #[async_trait]
 impl CacheProvider for PostgresCache {
     async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
-        let result =
-            sqlx::query_as::<
-                _,
-                (
-                    String,
-                    serde_json::Value,
-                    serde_json::Value,
-                    chrono::DateTime<chrono::Utc>,
-                ),
-            >("SELECT key, inputs, output, timestamp FROM cache_entries WHERE key = $1")
-            .bind(key)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| Error::Database {
-                message: format!("Failed to get cache entry: {e}"),
-            })?;
+        let result = sqlx::query_as::<
+            _,
+            (
+                String,
+                serde_json::Value,
+                serde_json::Value,
+                chrono::DateTime<chrono::Utc>,
+                Option<chrono::DateTime<chrono::Utc>>,
+            ),
+        >(
+            "SELECT key, inputs, output, timestamp, expires_at FROM cache_entries \
+                 WHERE key = $1 AND (expires_at IS NULL OR expires_at > NOW())",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to get cache entry: {e}"),
+        })?;
 
-        match result {
-            Some((key, inputs, output, timestamp)) => Ok(Some(CacheEntry {
-                key,
-                inputs,
-                output,
-                timestamp,
-            })),
-            None => Ok(None),
-        }
+        let entry = result.map(|(key, inputs, output, timestamp, expires_at)| CacheEntry {
+            key,
+            inputs,
+            output,
+            timestamp,
+            expires_at,
+        });
+        Ok(self.counters.record(entry))
     }
 
     async fn set(&self, entry: CacheEntry) -> Result<()> {
         sqlx::query(
             r"
-            INSERT INTO cache_entries (key, inputs, output, timestamp)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO cache_entries (key, inputs, output, timestamp, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
             ON CONFLICT (key)
             DO UPDATE SET
                 inputs = EXCLUDED.inputs,
                 output = EXCLUDED.output,
-                timestamp = EXCLUDED.timestamp
+                timestamp = EXCLUDED.timestamp,
+                expires_at = EXCLUDED.expires_at
             ",
         )
         .bind(&entry.key)
         .bind(&entry.inputs)
         .bind(&entry.output)
         .bind(entry.timestamp)
+        .bind(entry.expires_at)
         .execute(&self.pool)
         .await
         .map_err(|e| Error::Database {
@@ -137,6 +148,57 @@ impl CacheProvider for PostgresCache {
 
         Ok(())
     }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM cache_entries WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to purge expired cache entries: {e}"),
+            })?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn purge_all(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM cache_entries")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to purge cache entries: {e}"),
+            })?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn compact(&self) -> Result<()> {
+        // Plain VACUUM makes the space of deleted rows reusable without locking the table
+        sqlx::query("VACUUM cache_entries")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to compact cache: {e}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let (entries, bytes) = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*), COALESCE(SUM(OCTET_LENGTH(inputs::text) + OCTET_LENGTH(output::text)), 0)::BIGINT \
+             FROM cache_entries",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to read cache statistics: {e}"),
+        })?;
+
+        Ok(self.counters.stats(
+            u64::try_from(entries).unwrap_or_default(),
+            u64::try_from(bytes).unwrap_or_default(),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -216,6 +278,7 @@ mod tests {
             inputs: serde_json::json!({"param": "value"}),
             output: serde_json::json!({"result": "success"}),
             timestamp: Utc::now(),
+            expires_at: None,
         };
 
         cache.set(entry.clone()).await.unwrap();
@@ -244,6 +307,7 @@ mod tests {
             inputs: serde_json::json!({"v": 1}),
             output: serde_json::json!({"r": 1}),
             timestamp: Utc::now(),
+            expires_at: None,
         };
 
         cache.set(entry1).await.unwrap();
@@ -254,6 +318,7 @@ mod tests {
             inputs: serde_json::json!({"v": 2}),
             output: serde_json::json!({"r": 2}),
             timestamp: Utc::now(),
+            expires_at: None,
         };
 
         cache.set(entry2).await.unwrap();
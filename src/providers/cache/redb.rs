@@ -1,4 +1,7 @@
-use crate::cache::{CacheEntry, CacheProvider, Error, Result, SerializationSnafu};
+use crate::cache::{
+    CacheCounters, CacheEntry, CacheProvider, CacheStats, Error, Result, SerializationSnafu,
+};
+use crate::durableengine::memory::json_size;
 use async_trait::async_trait;
 use snafu::prelude::*;
 use std::sync::Arc;
@@ -8,6 +11,7 @@ const CACHE_TABLE: redb::TableDefinition<&str, &[u8]> = redb::TableDefinition::n
 #[derive(Debug)]
 pub struct RedbCache {
     db: Arc<redb::Database>,
+    counters: CacheCounters,
 }
 
 impl RedbCache {
@@ -30,7 +34,55 @@ impl RedbCache {
         write_txn.commit().map_err(|e| Error::Database {
             message: format!("Failed to commit transaction: {e}"),
         })?;
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            counters: CacheCounters::default(),
+        })
+    }
+
+    /// Remove the entries for which `remove` returns true, returning how many were removed
+    async fn remove_where(&self, remove: fn(&CacheEntry) -> bool) -> Result<u64> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || -> Result<u64> {
+            let write_txn = db.begin_write().map_err(|e| Error::Database {
+                message: format!("Failed to begin write transaction: {e}"),
+            })?;
+            let removed = {
+                let mut table = write_txn
+                    .open_table(CACHE_TABLE)
+                    .map_err(|e| Error::Database {
+                        message: format!("Failed to open cache table: {e}"),
+                    })?;
+                let mut keys = Vec::new();
+                let range = table.range::<&str>(..).map_err(|e| Error::Database {
+                    message: format!("Failed to create range: {e}"),
+                })?;
+                for item in range {
+                    let (key, value) = item.map_err(|e| Error::Database {
+                        message: format!("Failed to read item: {e}"),
+                    })?;
+                    let entry: CacheEntry =
+                        serde_json::from_slice(value.value()).context(SerializationSnafu)?;
+                    if remove(&entry) {
+                        keys.push(key.value().to_string());
+                    }
+                }
+                for key in &keys {
+                    table.remove(key.as_str()).map_err(|e| Error::Database {
+                        message: format!("Failed to remove value: {e}"),
+                    })?;
+                }
+                keys.len()
+            };
+            write_txn.commit().map_err(|e| Error::Database {
+                message: format!("Failed to commit transaction: {e}"),
+            })?;
+            Ok(u64::try_from(removed).unwrap_or(u64::MAX))
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
     }
 }
 
@@ -39,7 +91,7 @@ impl CacheProvider for RedbCache {
     async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
         let db = self.db.clone();
         let key = key.to_string();
-        tokio::task::spawn_blocking(move || -> Result<Option<CacheEntry>> {
+        let entry = tokio::task::spawn_blocking(move || -> Result<Option<CacheEntry>> {
             let read_txn = db.begin_read().map_err(|e| Error::Database {
                 message: format!("Failed to begin read transaction: {e}"),
             })?;
@@ -53,7 +105,7 @@ impl CacheProvider for RedbCache {
             })? {
                 let entry: CacheEntry =
                     serde_json::from_slice(value.value()).context(SerializationSnafu)?;
-                Ok(Some(entry))
+                Ok((!entry.is_expired()).then_some(entry))
             } else {
                 Ok(None)
             }
@@ -61,7 +113,8 @@ impl CacheProvider for RedbCache {
         .await
         .map_err(|e| Error::Database {
             message: format!("Task join error: {e}"),
-        })?
+        })??;
+        Ok(self.counters.record(entry))
     }
 
     async fn set(&self, entry: CacheEntry) -> Result<()> {
@@ -121,4 +174,49 @@ impl CacheProvider for RedbCache {
             message: format!("Task join error: {e}"),
         })?
     }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        self.remove_where(CacheEntry::is_expired).await
+    }
+
+    async fn purge_all(&self) -> Result<u64> {
+        self.remove_where(|_| true).await
+    }
+
+    // redb reuses the pages of removed entries; shrinking the file needs
+    // exclusive access to the database, which is shared with persistence
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let db = self.db.clone();
+        let (entries, bytes) = tokio::task::spawn_blocking(move || -> Result<(u64, u64)> {
+            let read_txn = db.begin_read().map_err(|e| Error::Database {
+                message: format!("Failed to begin read transaction: {e}"),
+            })?;
+            let table = read_txn
+                .open_table(CACHE_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open cache table: {e}"),
+                })?;
+            let range = table.range::<&str>(..).map_err(|e| Error::Database {
+                message: format!("Failed to create range: {e}"),
+            })?;
+            let (mut entries, mut bytes) = (0_u64, 0_u64);
+            for item in range {
+                let (_, value) = item.map_err(|e| Error::Database {
+                    message: format!("Failed to read item: {e}"),
+                })?;
+                let entry: CacheEntry =
+                    serde_json::from_slice(value.value()).context(SerializationSnafu)?;
+                entries += 1;
+                bytes += u64::try_from(json_size(&entry.inputs) + json_size(&entry.output))
+                    .unwrap_or(u64::MAX);
+            }
+            Ok((entries, bytes))
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })??;
+        Ok(self.counters.stats(entries, bytes))
+    }
 }
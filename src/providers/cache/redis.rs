@@ -0,0 +1,267 @@
+use crate::cache::{
+    CacheCounters, CacheEntry, CacheProvider, CacheStats, Error, Result, SerializationSnafu,
+};
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, SetExpiry, SetOptions};
+use snafu::prelude::*;
+
+/// Prefix of the keys cache entries are stored under
+const KEY_PREFIX: &str = "jackdaw:cache:";
+
+/// Number of keys sent to Redis per command when scanning the whole cache
+const BATCH_SIZE: usize = 500;
+
+/// Cache provider backed by a Redis server
+///
+/// Entries are stored as JSON under `jackdaw:cache:<key>`, so several jackdaw
+/// workers pointed at the same server share one cache. Entries with a TTL are
+/// given the same expiry in Redis, which removes them on its own.
+#[allow(dead_code)]
+pub struct RedisCache {
+    connection: ConnectionManager,
+    counters: CacheCounters,
+}
+
+impl std::fmt::Debug for RedisCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisCache")
+            .field("counters", &self.counters)
+            .finish_non_exhaustive()
+    }
+}
+
+#[allow(dead_code)]
+impl RedisCache {
+    /// Create a new Redis cache provider
+    ///
+    /// # Arguments
+    /// * `url` - Redis connection string (e.g., `redis://localhost:6379/0`)
+    ///
+    /// # Errors
+    /// Returns an error if the URL is invalid or the server cannot be reached.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jackdaw::providers::cache::RedisCache;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cache = RedisCache::new("redis://localhost:6379").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn new(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url).map_err(|e| Error::Database {
+            message: format!("Invalid Redis URL: {e}"),
+        })?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to connect to Redis: {e}"),
+            })?;
+
+        Ok(Self {
+            connection,
+            counters: CacheCounters::default(),
+        })
+    }
+
+    /// Keys of every entry currently in the cache
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut connection = self.connection.clone();
+        let mut iter: redis::AsyncIter<'_, String> = connection
+            .scan_match(format!("{KEY_PREFIX}*"))
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to scan cache entries: {e}"),
+            })?;
+
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+}
+
+/// Redis key an entry is stored under
+fn entry_key(key: &str) -> String {
+    format!("{KEY_PREFIX}{key}")
+}
+
+#[async_trait]
+impl CacheProvider for RedisCache {
+    async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let mut connection = self.connection.clone();
+        let value: Option<String> =
+            connection
+                .get(entry_key(key))
+                .await
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to get cache entry: {e}"),
+                })?;
+
+        let entry = value
+            .map(|value| serde_json::from_str::<CacheEntry>(&value).context(SerializationSnafu))
+            .transpose()?
+            .filter(|entry| !entry.is_expired());
+        Ok(self.counters.record(entry))
+    }
+
+    async fn set(&self, entry: CacheEntry) -> Result<()> {
+        let value = serde_json::to_string(&entry).context(SerializationSnafu)?;
+        let mut options = SetOptions::default();
+        if let Some(expires_at) = entry.expires_at {
+            // An expiry already in the past makes Redis drop the entry straight away
+            let millis = u64::try_from(expires_at.timestamp_millis()).unwrap_or(1);
+            options = options.with_expiration(SetExpiry::PXAT(millis.max(1)));
+        }
+
+        let mut connection = self.connection.clone();
+        connection
+            .set_options::<_, _, ()>(entry_key(&entry.key), value, options)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to set cache entry: {e}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn invalidate(&self, key: &str) -> Result<()> {
+        let mut connection = self.connection.clone();
+        connection
+            .del::<_, ()>(entry_key(key))
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to invalidate cache entry: {e}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        // Redis removes expired entries itself, so there is never anything to purge
+        Ok(0)
+    }
+
+    async fn purge_all(&self) -> Result<u64> {
+        let keys = self.keys().await?;
+        let mut connection = self.connection.clone();
+        let mut removed = 0;
+        for batch in keys.chunks(BATCH_SIZE) {
+            removed += connection
+                .del::<_, u64>(batch)
+                .await
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to purge cache entries: {e}"),
+                })?;
+        }
+
+        Ok(removed)
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let keys = self.keys().await?;
+        let mut connection = self.connection.clone();
+        let mut bytes = 0;
+        for batch in keys.chunks(BATCH_SIZE) {
+            let mut pipe = redis::pipe();
+            for key in batch {
+                pipe.strlen(key);
+            }
+            let lengths: Vec<u64> =
+                pipe.query_async(&mut connection)
+                    .await
+                    .map_err(|e| Error::Database {
+                        message: format!("Failed to read cache statistics: {e}"),
+                    })?;
+            bytes += lengths.iter().sum::<u64>();
+        }
+
+        Ok(self
+            .counters
+            .stats(u64::try_from(keys.len()).unwrap_or(u64::MAX), bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use chrono::{Duration, Utc};
+    use serial_test::serial;
+    use testcontainers::{GenericImage, runners::AsyncRunner};
+
+    async fn setup_redis_container() -> (testcontainers::ContainerAsync<GenericImage>, String) {
+        use testcontainers::core::{ContainerPort, WaitFor};
+
+        let container = GenericImage::new("redis", "7-alpine")
+            .with_exposed_port(ContainerPort::Tcp(6379))
+            .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+            .start()
+            .await
+            .expect("Failed to start redis container");
+        let port = container
+            .get_host_port_ipv4(ContainerPort::Tcp(6379))
+            .await
+            .expect("Failed to get port");
+
+        (container, format!("redis://localhost:{port}"))
+    }
+
+    fn entry(key: &str, expires_at: Option<chrono::DateTime<Utc>>) -> CacheEntry {
+        CacheEntry {
+            key: key.to_string(),
+            inputs: serde_json::json!({"param": "value"}),
+            output: serde_json::json!({"result": "success"}),
+            timestamp: Utc::now(),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_redis_cache_basic_operations() {
+        let (_container, url) = setup_redis_container().await;
+        let cache = RedisCache::new(&url).await.unwrap();
+
+        cache.set(entry("test_key", None)).await.unwrap();
+
+        let retrieved = cache.get("test_key").await.unwrap().unwrap();
+        assert_eq!(retrieved.inputs, serde_json::json!({"param": "value"}));
+        assert_eq!(retrieved.output, serde_json::json!({"result": "success"}));
+
+        cache.invalidate("test_key").await.unwrap();
+        assert!(cache.get("test_key").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_redis_cache_expiry_and_purge() {
+        let (_container, url) = setup_redis_container().await;
+        let cache = RedisCache::new(&url).await.unwrap();
+
+        cache
+            .set(entry("expired", Some(Utc::now() - Duration::seconds(1))))
+            .await
+            .unwrap();
+        cache
+            .set(entry("fresh", Some(Utc::now() + Duration::hours(1))))
+            .await
+            .unwrap();
+        cache.set(entry("forever", None)).await.unwrap();
+
+        assert!(cache.get("expired").await.unwrap().is_none());
+        assert!(cache.get("fresh").await.unwrap().is_some());
+
+        let stats = cache.stats().await.unwrap();
+        assert_eq!(stats.entries, 2);
+        assert!(stats.bytes > 0);
+
+        assert_eq!(cache.purge_all().await.unwrap(), 2);
+        assert!(cache.get("forever").await.unwrap().is_none());
+    }
+}
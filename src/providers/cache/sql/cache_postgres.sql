@@ -6,11 +6,18 @@ CREATE TABLE IF NOT EXISTS cache_entries (
     key TEXT PRIMARY KEY NOT NULL,
     inputs JSONB NOT NULL,
     output JSONB NOT NULL,
-    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ
 );
 
+-- Caches created before entries had a TTL
+ALTER TABLE cache_entries ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
 -- Index for potential TTL-based cleanup or timestamp queries
 CREATE INDEX IF NOT EXISTS idx_cache_timestamp ON cache_entries(timestamp);
 
+-- Index for purging expired entries
+CREATE INDEX IF NOT EXISTS idx_cache_expires_at ON cache_entries(expires_at);
+
 -- GIN index for efficient JSONB queries on inputs (optional, for future enhancements)
 CREATE INDEX IF NOT EXISTS idx_cache_inputs ON cache_entries USING GIN (inputs);
@@ -5,7 +5,8 @@ CREATE TABLE IF NOT EXISTS cache_entries (
     key TEXT PRIMARY KEY NOT NULL,
     inputs TEXT NOT NULL,           -- JSON serialized
     output TEXT NOT NULL,            -- JSON serialized
-    timestamp DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+    timestamp DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TEXT                  -- RFC 3339 in UTC, NULL if the entry never expires
 );
 
 -- Index for potential TTL-based cleanup or timestamp queries
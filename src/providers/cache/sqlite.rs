@@ -1,5 +1,8 @@
-use crate::cache::{CacheEntry, CacheProvider, Error, Result, SerializationSnafu};
+use crate::cache::{
+    CacheCounters, CacheEntry, CacheProvider, CacheStats, Error, Result, SerializationSnafu,
+};
 use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
 use snafu::prelude::*;
 use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
 
@@ -7,6 +10,7 @@ use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
 #[allow(dead_code)]
 pub struct SqliteCache {
     pool: SqlitePool,
+    counters: CacheCounters,
 }
 
 #[allow(dead_code)]
@@ -36,15 +40,7 @@ impl SqliteCache {
                 message: format!("Failed to connect to SQLite: {e}"),
             })?;
 
-        // Initialize schema
-        sqlx::query(include_str!("./sql/cache_sqlite.sql"))
-            .execute(&pool)
-            .await
-            .map_err(|e| Error::Database {
-                message: format!("Failed to execute schema: {e}"),
-            })?;
-
-        Ok(Self { pool })
+        Self::with_pool(pool).await
     }
 
     /// Create a new ``SQLite`` cache with custom pool options
@@ -60,17 +56,51 @@ impl SqliteCache {
                 message: format!("Failed to execute schema: {e}"),
             })?;
 
-        Ok(Self { pool })
+        // Caches created before entries had a TTL lack the expiry column
+        let (has_expiry,) = sqlx::query_as::<_, (bool,)>(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('cache_entries') WHERE name = 'expires_at'",
+        )
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to inspect schema: {e}"),
+        })?;
+        if !has_expiry {
+            sqlx::query("ALTER TABLE cache_entries ADD COLUMN expires_at TEXT")
+                .execute(&pool)
+                .await
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to migrate schema: {e}"),
+                })?;
+        }
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_cache_expires_at ON cache_entries(expires_at)")
+            .execute(&pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to execute schema: {e}"),
+            })?;
+
+        Ok(Self {
+            pool,
+            counters: CacheCounters::default(),
+        })
     }
 }
 
+/// Format an expiry so that expiries compare as strings in SQL
+fn expiry_string(expires_at: DateTime<Utc>) -> String {
+    expires_at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
 #[async_trait]
 impl CacheProvider for SqliteCache {
     async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
-        let result = sqlx::query_as::<_, (String, String, String, String)>(
-            "SELECT key, inputs, output, timestamp FROM cache_entries WHERE key = ?",
+        let result = sqlx::query_as::<_, (String, String, String, String, Option<String>)>(
+            "SELECT key, inputs, output, timestamp, expires_at FROM cache_entries \
+             WHERE key = ? AND (expires_at IS NULL OR expires_at > ?)",
         )
         .bind(key)
+        .bind(expiry_string(Utc::now()))
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| Error::Database {
@@ -78,7 +108,7 @@ impl CacheProvider for SqliteCache {
         })?;
 
         match result {
-            Some((key, inputs_json, output_json, timestamp_str)) => {
+            Some((key, inputs_json, output_json, timestamp_str, expires_at_str)) => {
                 let inputs = serde_json::from_str(&inputs_json).context(SerializationSnafu)?;
                 let output = serde_json::from_str(&output_json).context(SerializationSnafu)?;
                 let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
@@ -86,15 +116,24 @@ impl CacheProvider for SqliteCache {
                         message: format!("Failed to parse timestamp: {e}"),
                     })?
                     .with_timezone(&chrono::Utc);
+                let expires_at = expires_at_str
+                    .map(|expires_at| {
+                        DateTime::parse_from_rfc3339(&expires_at).map_err(|e| Error::Database {
+                            message: format!("Failed to parse expiry: {e}"),
+                        })
+                    })
+                    .transpose()?
+                    .map(|expires_at| expires_at.with_timezone(&Utc));
 
-                Ok(Some(CacheEntry {
+                Ok(self.counters.record(Some(CacheEntry {
                     key,
                     inputs,
                     output,
                     timestamp,
-                }))
+                    expires_at,
+                })))
             }
-            None => Ok(None),
+            None => Ok(self.counters.record(None)),
         }
     }
 
@@ -104,12 +143,13 @@ impl CacheProvider for SqliteCache {
         let timestamp_str = entry.timestamp.to_rfc3339();
 
         sqlx::query(
-            "INSERT OR REPLACE INTO cache_entries (key, inputs, output, timestamp) VALUES (?, ?, ?, ?)"
+            "INSERT OR REPLACE INTO cache_entries (key, inputs, output, timestamp, expires_at) VALUES (?, ?, ?, ?, ?)"
         )
         .bind(&entry.key)
         .bind(&inputs_json)
         .bind(&output_json)
         .bind(&timestamp_str)
+        .bind(entry.expires_at.map(expiry_string))
         .execute(&self.pool)
         .await
         .map_err(|e| Error::Database { message: format!("Failed to set cache entry: {e}") })?;
@@ -128,6 +168,56 @@ impl CacheProvider for SqliteCache {
 
         Ok(())
     }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM cache_entries WHERE expires_at <= ?")
+            .bind(expiry_string(Utc::now()))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to purge expired cache entries: {e}"),
+            })?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn purge_all(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM cache_entries")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to purge cache entries: {e}"),
+            })?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn compact(&self) -> Result<()> {
+        sqlx::query("VACUUM")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to compact cache: {e}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let (entries, bytes) = sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*), COALESCE(SUM(LENGTH(inputs) + LENGTH(output)), 0) FROM cache_entries",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to read cache statistics: {e}"),
+        })?;
+
+        Ok(self.counters.stats(
+            u64::try_from(entries).unwrap_or_default(),
+            u64::try_from(bytes).unwrap_or_default(),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -137,7 +227,6 @@ mod tests {
     #![allow(clippy::panic)]
 
     use super::*;
-    use chrono::Utc;
 
     #[tokio::test]
     async fn test_sqlite_cache_basic_operations() {
@@ -149,6 +238,7 @@ mod tests {
             inputs: serde_json::json!({"param": "value"}),
             output: serde_json::json!({"result": "success"}),
             timestamp: Utc::now(),
+            expires_at: None,
         };
 
         cache.set(entry.clone()).await.unwrap();
@@ -175,6 +265,7 @@ mod tests {
             inputs: serde_json::json!({"v": 1}),
             output: serde_json::json!({"r": 1}),
             timestamp: Utc::now(),
+            expires_at: None,
         };
 
         cache.set(entry1).await.unwrap();
@@ -185,6 +276,7 @@ mod tests {
             inputs: serde_json::json!({"v": 2}),
             output: serde_json::json!({"r": 2}),
             timestamp: Utc::now(),
+            expires_at: None,
         };
 
         cache.set(entry2).await.unwrap();
@@ -193,6 +285,64 @@ mod tests {
         assert_eq!(retrieved.output, serde_json::json!({"r": 2}));
     }
 
+    #[tokio::test]
+    async fn test_sqlite_cache_expiry() {
+        let cache = SqliteCache::new(":memory:").await.unwrap();
+
+        let entry = |key: &str, expires_at| CacheEntry {
+            key: key.to_string(),
+            inputs: serde_json::json!({}),
+            output: serde_json::json!({"r": key}),
+            timestamp: Utc::now(),
+            expires_at,
+        };
+        cache
+            .set(entry(
+                "expired",
+                Some(Utc::now() - chrono::Duration::seconds(1)),
+            ))
+            .await
+            .unwrap();
+        cache
+            .set(entry(
+                "fresh",
+                Some(Utc::now() + chrono::Duration::hours(1)),
+            ))
+            .await
+            .unwrap();
+        cache.set(entry("forever", None)).await.unwrap();
+
+        assert!(cache.get("expired").await.unwrap().is_none());
+        let fresh = cache.get("fresh").await.unwrap().unwrap();
+        assert!(fresh.expires_at.is_some());
+        assert!(cache.get("forever").await.unwrap().is_some());
+
+        assert_eq!(cache.purge_expired().await.unwrap(), 1);
+        assert_eq!(cache.purge_all().await.unwrap(), 2);
+        assert!(cache.get("forever").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_cache_stats() {
+        let cache = SqliteCache::new(":memory:").await.unwrap();
+        cache
+            .set(CacheEntry {
+                key: "k".to_string(),
+                inputs: serde_json::json!({"v": 1}),
+                output: serde_json::json!([1, 2]),
+                timestamp: Utc::now(),
+                expires_at: None,
+            })
+            .await
+            .unwrap();
+        assert!(cache.get("k").await.unwrap().is_some());
+        assert!(cache.get("other").await.unwrap().is_none());
+
+        let stats = cache.stats().await.unwrap();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!((stats.entries, stats.bytes), (1, 12));
+    }
+
     #[tokio::test]
     async fn test_sqlite_cache_get_nonexistent() {
         let cache = SqliteCache::new(":memory:").await.unwrap();
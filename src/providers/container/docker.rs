@@ -1,20 +1,40 @@
-use crate::container::{ContainerConfig, ContainerProvider, ContainerResult, Error, Result};
+use crate::container::{
+    ContainerConfig, ContainerProvider, ContainerResult, Error, ImageBuild, Result, ServiceSpec,
+};
+use crate::context::Reaper;
+use crate::task_output::TaskOutputStreamer;
+use crate::workflow::ReapedResource;
 use async_trait::async_trait;
 use bollard::Docker;
 use bollard::container::{
-    AttachContainerOptions, Config, RemoveContainerOptions, StartContainerOptions,
+    AttachContainerOptions, Config, LogOutput, NetworkingConfig, RemoveContainerOptions,
+    StartContainerOptions,
 };
-use bollard::image::CreateImageOptions;
-use bollard::models::{HostConfig, PortBinding};
-use futures::StreamExt;
-use std::collections::HashMap;
-use tokio::io::AsyncWriteExt;
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::image::{BuildImageOptions, CreateImageOptions};
+use bollard::models::{EndpointSettings, HostConfig, PortBinding};
+use bollard::network::CreateNetworkOptions;
+use futures::{Stream, StreamExt};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::WarmPool;
+
+/// Command run by warm containers to stay alive until they are removed
+const KEEP_ALIVE_SCRIPT: &str = "trap 'exit 0' TERM; while :; do sleep 3600 & wait; done";
+
+/// Delay between runs of a service's ready command
+const READY_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Docker container provider using bollard
 #[derive(Debug, Clone)]
 pub struct DockerProvider {
     /// Docker client
     docker: Docker,
+    /// Idle containers reused by tasks with `keep_warm` set
+    pool: Option<Arc<WarmPool>>,
 }
 
 impl DockerProvider {
@@ -28,80 +48,27 @@ impl DockerProvider {
             message: format!("Failed to connect to Docker daemon: {e}"),
         })?;
 
-        Ok(Self { docker })
+        Ok(Self { docker, pool: None })
     }
 
     /// Create a new Docker provider with a custom Docker client
     #[must_use]
-    #[allow(dead_code)]
     pub fn with_docker(docker: Docker) -> Self {
-        Self { docker }
+        Self { docker, pool: None }
     }
-}
-
-#[async_trait]
-impl ContainerProvider for DockerProvider {
-    async fn execute(&self, config: ContainerConfig) -> Result<ContainerResult> {
-        // Prepare command - if command is empty, use default shell
-        let cmd = if config.command.is_empty() {
-            vec!["/bin/sh".to_string(), "-c".to_string()]
-        } else {
-            config.command
-        };
-
-        // Prepare environment variables
-        let env: Option<Vec<String>> = config
-            .environment
-            .as_ref()
-            .map(|env_map| env_map.iter().map(|(k, v)| format!("{k}={v}")).collect());
-
-        // Prepare volumes (bind mounts) with SELinux relabeling for compatibility
-        // Using binds with :z suffix for proper SELinux support on Fedora/RHEL/CentOS
-        let binds: Option<Vec<String>> = config.volumes.as_ref().map(|vols| {
-            vols.iter()
-                .map(|(host_path, container_path)| {
-                    // Add :z for SELinux relabeling (shared volume label)
-                    // This allows the container to write to the host directory
-                    format!("{}:{}:z", host_path, container_path)
-                })
-                .collect()
-        });
 
-        // Prepare port bindings
-        let (exposed_ports, port_bindings) = if let Some(ports) = config.ports.as_ref() {
-            let mut exposed = HashMap::new();
-            let mut bindings = HashMap::new();
-
-            for (container_port, host_port) in ports {
-                // Exposed ports format: "8080/tcp" -> {}
-                let port_key = format!("{container_port}/tcp");
-                exposed.insert(port_key.clone(), HashMap::new());
-
-                // Port bindings format: "8080/tcp" -> [{"HostPort": "8080"}]
-                bindings.insert(
-                    port_key,
-                    Some(vec![PortBinding {
-                        host_ip: None,
-                        host_port: Some(host_port.to_string()),
-                    }]),
-                );
-            }
-
-            (Some(exposed), Some(bindings))
-        } else {
-            (None, None)
-        };
+    /// Keep containers of tasks with `keep_warm` set running in `pool` between executions
+    #[must_use]
+    pub fn with_pool(mut self, pool: Arc<WarmPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
 
-        // Create host configuration for volumes and ports
-        let host_config = if binds.is_some() || port_bindings.is_some() {
-            Some(HostConfig {
-                binds,
-                port_bindings,
-                ..Default::default()
-            })
-        } else {
-            None
-        };
+    /// Pull the image if it is not present, honouring the pull timeout
+    async fn pull_image(&self, config: &ContainerConfig) -> Result<()> {
+        if config.skip_pull {
+            return Ok(());
+        }
 
         let image_parts: Vec<&str> = config.image.split(':').collect();
         let (image_name, image_tag) = match (image_parts.first(), image_parts.get(1)) {
@@ -121,30 +88,83 @@ impl ContainerProvider for DockerProvider {
             .create_image(Some(create_image_options), None, None);
 
         // Process pull stream (this will pull the image if not present, or be a no-op if cached)
-        while let Some(pull_result) = pull_stream.next().await {
-            pull_result.map_err(|e| Error::ImagePull {
-                message: format!("Failed to pull image {}: {}", config.image, e),
-            })?;
+        let pull = async {
+            while let Some(pull_result) = pull_stream.next().await {
+                pull_result.map_err(|e| Error::ImagePull {
+                    message: format!("Failed to pull image {}: {}", config.image, e),
+                })?;
+            }
+            Ok(())
+        };
+        match config.pull_timeout {
+            Some(timeout) => {
+                tokio::time::timeout(timeout, pull)
+                    .await
+                    .map_err(|_| Error::ImagePull {
+                        message: format!(
+                            "Timed out pulling image {} after {timeout:?}",
+                            config.image
+                        ),
+                    })?
+            }
+            None => pull.await,
         }
+    }
 
-        // Create container configuration
+    /// Run a task in an idle container from the pool, starting one if none is available
+    async fn execute_warm(
+        &self,
+        pool: &WarmPool,
+        config: ContainerConfig,
+    ) -> Result<ContainerResult> {
+        let key = warm_key(&config);
+        let (container, expired) = pool.checkout(&key);
+        self.remove_all(expired).await;
+
+        let container_id = match container {
+            Some(id) => id,
+            None => self.start_warm(&config).await?,
+        };
+
+        let cancellation = config.cancellation.clone();
+        let reaper = config.reaper.clone();
+        let image = config.image.clone();
+        let result = tokio::select! {
+            result = self.exec(&container_id, config) => result,
+            () = cancellation.cancelled() => Err(reaped(&reaper, &container_id, &image)),
+        };
+        match result {
+            Ok(result) => {
+                self.remove_all(pool.checkin(&key, container_id)).await;
+                Ok(result)
+            }
+            Err(e) => {
+                // The container may be broken or still running a cancelled
+                // command; never hand it out again
+                self.remove_all(vec![container_id]).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Start a container that idles until commands are executed in it
+    async fn start_warm(&self, config: &ContainerConfig) -> Result<String> {
+        self.pull_image(config).await?;
+
+        let (exposed_ports, host_config) = host_config(config);
         let container_config = Config {
             image: Some(config.image.clone()),
-            cmd: Some(cmd),
-            env,
-            working_dir: config.working_dir.clone(),
-            attach_stdin: Some(config.stdin.is_some()),
-            attach_stdout: Some(true),
-            attach_stderr: Some(true),
-            open_stdin: Some(config.stdin.is_some()),
-            stdin_once: Some(config.stdin.is_some()),
-            tty: Some(false),
+            entrypoint: Some(vec!["/bin/sh".to_string(), "-c".to_string()]),
+            cmd: Some(vec![KEEP_ALIVE_SCRIPT.to_string()]),
+            labels: Some(HashMap::from([(
+                "jackdaw.pool".to_string(),
+                "warm".to_string(),
+            )])),
             exposed_ports,
-            host_config,
+            host_config: Some(host_config),
             ..Default::default()
         };
 
-        // Create container
         let container = self
             .docker
             .create_container::<String, String>(None, container_config)
@@ -153,8 +173,174 @@ impl ContainerProvider for DockerProvider {
                 message: format!("Failed to create container: {e}"),
             })?;
 
-        let container_id = container.id.clone();
+        if let Err(e) = self
+            .docker
+            .start_container(&container.id, None::<StartContainerOptions<String>>)
+            .await
+        {
+            self.remove_all(vec![container.id]).await;
+            return Err(Error::Start {
+                message: format!("Failed to start container: {e}"),
+            });
+        }
+
+        Ok(container.id)
+    }
+
+    /// Execute the task's command in a running container
+    async fn exec(&self, container_id: &str, config: ContainerConfig) -> Result<ContainerResult> {
+        let exec = self
+            .docker
+            .create_exec(
+                container_id,
+                CreateExecOptions {
+                    cmd: Some(command(&config)),
+                    env: environment(&config),
+                    working_dir: config.working_dir.clone(),
+                    user: config.user.clone(),
+                    attach_stdin: Some(config.stdin.is_some()),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| Error::Execution {
+                message: format!("Failed to create exec in container: {e}"),
+            })?;
+
+        let StartExecResults::Attached { output, input } = self
+            .docker
+            .start_exec(&exec.id, None)
+            .await
+            .map_err(|e| Error::Start {
+                message: format!("Failed to start exec in container: {e}"),
+            })?
+        else {
+            return Err(Error::Attach {
+                message: "Exec started detached".to_string(),
+            });
+        };
+
+        write_stdin(input, config.stdin.as_deref()).await?;
+        let (stdout, stderr) = collect_output(output).await?;
+
+        let inspect = self
+            .docker
+            .inspect_exec(&exec.id)
+            .await
+            .map_err(|e| Error::Inspect {
+                message: format!("Failed to inspect exec: {e}"),
+            })?;
+
+        Ok(ContainerResult {
+            stdout,
+            stderr,
+            exit_code: inspect.exit_code.unwrap_or(0),
+        })
+    }
+
+    /// Create and start a service container attached to `network` under its name
+    ///
+    /// The ID of the created container is pushed to `started` before it is
+    /// started, so it is removed even if starting fails.
+    async fn start_service(
+        &self,
+        network: &str,
+        name: &str,
+        service: &ServiceSpec,
+        pull_timeout: Option<Duration>,
+        started: &mut Vec<String>,
+    ) -> Result<()> {
+        self.pull_image(&ContainerConfig {
+            image: service.image.clone(),
+            pull_timeout,
+            ..ContainerConfig::default()
+        })
+        .await?;
+
+        let container_config = Config {
+            image: Some(service.image.clone()),
+            cmd: service.command.clone(),
+            env: Some(
+                service
+                    .environment
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect(),
+            ),
+            labels: Some(HashMap::from([(
+                "jackdaw.network".to_string(),
+                network.to_string(),
+            )])),
+            host_config: Some(HostConfig {
+                network_mode: Some(network.to_string()),
+                ..Default::default()
+            }),
+            networking_config: Some(NetworkingConfig {
+                endpoints_config: HashMap::from([(
+                    network.to_string(),
+                    EndpointSettings {
+                        aliases: Some(vec![name.to_string()]),
+                        ..Default::default()
+                    },
+                )]),
+            }),
+            ..Default::default()
+        };
+
+        let container = self
+            .docker
+            .create_container::<String, String>(None, container_config)
+            .await
+            .map_err(|e| Error::Creation {
+                message: format!("Failed to create service {name}: {e}"),
+            })?;
+        started.push(container.id.clone());
 
+        self.docker
+            .start_container(&container.id, None::<StartContainerOptions<String>>)
+            .await
+            .map_err(|e| Error::Start {
+                message: format!("Failed to start service {name}: {e}"),
+            })
+    }
+
+    /// Run a service's ready command until it succeeds or the deadline passes
+    async fn wait_ready(
+        &self,
+        container_id: &str,
+        name: &str,
+        ready: &str,
+        deadline: tokio::time::Instant,
+    ) -> Result<()> {
+        let check = ContainerConfig {
+            command: vec!["/bin/sh".to_string(), "-c".to_string(), ready.to_string()],
+            ..ContainerConfig::default()
+        };
+        loop {
+            // A service that is still starting may not accept execs yet
+            let last = match self.exec(container_id, check.clone()).await {
+                Ok(result) if result.exit_code == 0 => return Ok(()),
+                Ok(result) => format!("exit code {}: {}", result.exit_code, result.stderr.trim()),
+                Err(e) => e.to_string(),
+            };
+            if tokio::time::Instant::now() + READY_POLL_INTERVAL > deadline {
+                return Err(Error::Service {
+                    message: format!("Service {name} did not become ready ({ready}): {last}"),
+                });
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Attach to a created container, start it and wait for it to exit,
+    /// returning its output and exit code
+    async fn run_attached(
+        &self,
+        container_id: &str,
+        config: &ContainerConfig,
+    ) -> Result<(String, String, i64)> {
         // Attach to container before starting it
         let attach_options = AttachContainerOptions::<String> {
             stdin: Some(config.stdin.is_some()),
@@ -167,7 +353,7 @@ impl ContainerProvider for DockerProvider {
 
         let attach_result = self
             .docker
-            .attach_container(&container_id, Some(attach_options))
+            .attach_container(container_id, Some(attach_options))
             .await
             .map_err(|e| Error::Attach {
                 message: format!("Failed to attach to container: {e}"),
@@ -175,63 +361,103 @@ impl ContainerProvider for DockerProvider {
 
         // Start container
         self.docker
-            .start_container(&container_id, None::<StartContainerOptions<String>>)
+            .start_container(container_id, None::<StartContainerOptions<String>>)
             .await
             .map_err(|e| Error::Start {
                 message: format!("Failed to start container: {e}"),
             })?;
 
         // Write stdin if provided
-        let bollard::container::AttachContainerResults {
-            mut output,
-            mut input,
-        } = attach_result;
-
-        if let Some(stdin_str) = config.stdin.as_ref() {
-            input
-                .write_all(stdin_str.as_bytes())
-                .await
-                .map_err(|e| Error::Io {
-                    message: format!("Failed to write stdin: {e}"),
-                })?;
-            input.shutdown().await.map_err(|e| Error::Io {
-                message: format!("Failed to close stdin: {e}"),
-            })?;
-        }
+        let bollard::container::AttachContainerResults { output, input } = attach_result;
+        write_stdin(input, config.stdin.as_deref()).await?;
 
         // Collect output
-        let mut stdout_buffer = String::new();
-        let mut stderr_buffer = String::new();
-
-        while let Some(output_result) = output.next().await {
-            let output_chunk = output_result.map_err(|e| Error::Io {
-                message: format!("Failed to read output: {e}"),
-            })?;
-
-            match output_chunk {
-                bollard::container::LogOutput::StdOut { message } => {
-                    stdout_buffer.push_str(&String::from_utf8_lossy(&message));
-                }
-                bollard::container::LogOutput::StdErr { message } => {
-                    stderr_buffer.push_str(&String::from_utf8_lossy(&message));
-                }
-                bollard::container::LogOutput::StdIn { .. } => {}
-                bollard::container::LogOutput::Console { message } => {
-                    stdout_buffer.push_str(&String::from_utf8_lossy(&message));
-                }
-            }
-        }
+        let (stdout_buffer, stderr_buffer) = collect_output(output).await?;
 
         // Get exit code from container inspection
         let inspect = self
             .docker
-            .inspect_container(&container_id, None)
+            .inspect_container(container_id, None)
             .await
             .map_err(|e| Error::Inspect {
                 message: format!("Failed to inspect container: {e}"),
             })?;
 
         let exit_code = inspect.state.and_then(|s| s.exit_code).unwrap_or(0);
+        Ok((stdout_buffer, stderr_buffer, exit_code))
+    }
+
+    /// Force-remove containers, ignoring failures (they may already be gone)
+    async fn remove_all(&self, container_ids: Vec<String>) {
+        for container_id in container_ids {
+            let remove_options = RemoveContainerOptions {
+                force: true,
+                ..Default::default()
+            };
+            let _ = self
+                .docker
+                .remove_container(&container_id, Some(remove_options))
+                .await;
+        }
+    }
+}
+
+#[async_trait]
+impl ContainerProvider for DockerProvider {
+    async fn execute(&self, config: ContainerConfig) -> Result<ContainerResult> {
+        if config.keep_warm
+            && let Some(pool) = &self.pool
+        {
+            return self.execute_warm(pool, config).await;
+        }
+
+        self.pull_image(&config).await?;
+
+        let (exposed_ports, host_config) = host_config(&config);
+
+        // Create container configuration
+        let container_config = Config {
+            image: Some(config.image.clone()),
+            cmd: Some(command(&config)),
+            env: environment(&config),
+            working_dir: config.working_dir.clone(),
+            user: config.user.clone(),
+            attach_stdin: Some(config.stdin.is_some()),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            open_stdin: Some(config.stdin.is_some()),
+            stdin_once: Some(config.stdin.is_some()),
+            tty: Some(false),
+            exposed_ports,
+            host_config: Some(host_config),
+            ..Default::default()
+        };
+
+        // Create container
+        let container = self
+            .docker
+            .create_container::<String, String>(None, container_config)
+            .await
+            .map_err(|e| Error::Creation {
+                message: format!("Failed to create container: {e}"),
+            })?;
+
+        let container_id = container.id.clone();
+
+        // Run the container, removing it if the task is cancelled meanwhile
+        let run = tokio::select! {
+            run = self.run_attached(&container_id, &config) => run,
+            () = config.cancellation.cancelled() => {
+                Err(reaped(&config.reaper, &container_id, &config.image))
+            }
+        };
+        let (stdout_buffer, stderr_buffer, exit_code) = match run {
+            Ok(run) => run,
+            Err(e) => {
+                self.remove_all(vec![container_id]).await;
+                return Err(e);
+            }
+        };
 
         // Remove container
         let remove_options = RemoveContainerOptions {
@@ -252,6 +478,318 @@ impl ContainerProvider for DockerProvider {
             exit_code,
         })
     }
+
+    async fn execute_with_services(
+        &self,
+        services: &BTreeMap<String, ServiceSpec>,
+        ready_timeout: Duration,
+        mut config: ContainerConfig,
+    ) -> Result<ContainerResult> {
+        let network = format!("jackdaw-{}", uuid::Uuid::new_v4().simple());
+        self.docker
+            .create_network(CreateNetworkOptions {
+                name: network.clone(),
+                labels: HashMap::from([("jackdaw.network".to_string(), network.clone())]),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| Error::Network {
+                message: format!("Failed to create network {network}: {e}"),
+            })?;
+
+        let cancellation = config.cancellation.clone();
+        let reaper = config.reaper.clone();
+        let mut started = Vec::new();
+        let result = async {
+            let start = async {
+                for (name, service) in services {
+                    self.start_service(&network, name, service, config.pull_timeout, &mut started)
+                        .await?;
+                }
+
+                let deadline = tokio::time::Instant::now() + ready_timeout;
+                for ((name, service), container_id) in services.iter().zip(&started) {
+                    if let Some(ready) = &service.ready {
+                        self.wait_ready(container_id, name, ready, deadline).await?;
+                    }
+                }
+                Ok(())
+            };
+            // The main container stops itself if the task is cancelled later on
+            tokio::select! {
+                start = start => start?,
+                () = config.cancellation.cancelled() => return Err(cancelled(&config.image)),
+            }
+
+            // Warm containers are not attached to the group's network
+            config.network_mode = Some(network.clone());
+            config.keep_warm = false;
+            self.execute(config).await
+        }
+        .await;
+
+        if cancellation.is_cancelled() {
+            for (service, container_id) in services.values().zip(&started) {
+                reaper.record(ReapedResource::Container {
+                    id: container_id.clone(),
+                    image: service.image.clone(),
+                });
+            }
+        }
+        self.remove_all(started).await;
+        let _ = self.docker.remove_network(&network).await;
+        result
+    }
+
+    async fn build_image(
+        &self,
+        build: &ImageBuild,
+        tag: &str,
+        streamer: Option<&TaskOutputStreamer>,
+    ) -> Result<String> {
+        let context = build.context.clone();
+        let archive = tokio::task::spawn_blocking(move || context_archive(&context))
+            .await
+            .map_err(|e| Error::Build {
+                message: format!("Failed to archive build context: {e}"),
+            })??;
+
+        let options = BuildImageOptions {
+            dockerfile: build
+                .dockerfile
+                .clone()
+                .unwrap_or_else(|| "Dockerfile".to_string()),
+            t: tag.to_string(),
+            buildargs: build.args.clone(),
+            target: build.target.clone().unwrap_or_default(),
+            rm: true,
+            ..Default::default()
+        };
+
+        let mut build_stream =
+            self.docker
+                .build_image(options, None, Some(bytes::Bytes::from(archive)));
+
+        let mut image_id = None;
+        while let Some(build_result) = build_stream.next().await {
+            let info = build_result.map_err(|e| Error::Build {
+                message: format!("Failed to build image {tag}: {e}"),
+            })?;
+            if let Some(error) = info.error {
+                return Err(Error::Build {
+                    message: format!("Failed to build image {tag}: {error}"),
+                });
+            }
+            if let (Some(output), Some(streamer)) = (info.stream.as_deref(), streamer) {
+                for line in output.lines().filter(|line| !line.trim().is_empty()) {
+                    streamer.print_stdout(line).await;
+                }
+            }
+            if let Some(id) = info.aux.and_then(|aux| aux.id) {
+                image_id = Some(id);
+            }
+        }
+
+        match image_id {
+            Some(id) => Ok(id),
+            None => self
+                .docker
+                .inspect_image(tag)
+                .await
+                .ok()
+                .and_then(|image| image.id)
+                .ok_or_else(|| Error::Build {
+                    message: format!("Build of image {tag} did not produce an image"),
+                }),
+        }
+    }
+
+    async fn check_image(&self, image: &str) -> Result<()> {
+        if self.docker.inspect_image(image).await.is_ok() {
+            return Ok(());
+        }
+        // Not present locally; ask its registry without pulling the layers
+        self.docker
+            .inspect_registry_image(image, None)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::ImagePull {
+                message: format!("Image {image} is not present and cannot be pulled: {e}"),
+            })
+    }
+
+    async fn drain_pool(&self) {
+        if let Some(pool) = &self.pool {
+            self.remove_all(pool.drain()).await;
+        }
+    }
+}
+
+/// Record that a task's container is removed because it was cancelled, and
+/// build the resulting error
+fn reaped(reaper: &Reaper, container_id: &str, image: &str) -> Error {
+    reaper.record(ReapedResource::Container {
+        id: container_id.to_string(),
+        image: image.to_string(),
+    });
+    cancelled(image)
+}
+
+/// The error returned when a task's container is removed because it was cancelled
+fn cancelled(image: &str) -> Error {
+    Error::Cancelled {
+        message: format!("container of image {image} was removed"),
+    }
+}
+
+/// Archive a build context directory as the tar stream Docker expects
+fn context_archive(context: &str) -> Result<Vec<u8>> {
+    let mut archive = tar::Builder::new(Vec::new());
+    archive.follow_symlinks(false);
+    archive
+        .append_dir_all(".", context)
+        .and_then(|()| archive.finish())
+        .map_err(|e| Error::Build {
+            message: format!("Failed to archive build context {context}: {e}"),
+        })?;
+    archive.into_inner().map_err(|e| Error::Build {
+        message: format!("Failed to archive build context {context}: {e}"),
+    })
+}
+
+/// The command to run - if command is empty, use default shell
+fn command(config: &ContainerConfig) -> Vec<String> {
+    if config.command.is_empty() {
+        vec!["/bin/sh".to_string(), "-c".to_string()]
+    } else {
+        config.command.clone()
+    }
+}
+
+/// Environment variables in Docker's `KEY=value` form
+fn environment(config: &ContainerConfig) -> Option<Vec<String>> {
+    config
+        .environment
+        .as_ref()
+        .map(|env_map| env_map.iter().map(|(k, v)| format!("{k}={v}")).collect())
+}
+
+/// Exposed ports and host configuration for volumes, ports, networking and resource limits
+fn host_config(config: &ContainerConfig) -> (Option<HashMap<String, HashMap<(), ()>>>, HostConfig) {
+    // Prepare volumes (bind mounts) with SELinux relabeling for compatibility
+    // Using binds with :z suffix for proper SELinux support on Fedora/RHEL/CentOS
+    let binds: Option<Vec<String>> = config.volumes.as_ref().map(|vols| {
+        vols.iter()
+            .map(|(host_path, container_path)| {
+                // Add :z for SELinux relabeling (shared volume label)
+                // This allows the container to write to the host directory
+                format!("{}:{}:z", host_path, container_path)
+            })
+            .collect()
+    });
+
+    // Prepare port bindings
+    let (exposed_ports, port_bindings) = if let Some(ports) = config.ports.as_ref() {
+        let mut exposed = HashMap::new();
+        let mut bindings = HashMap::new();
+
+        for (container_port, host_port) in ports {
+            // Exposed ports format: "8080/tcp" -> {}
+            let port_key = format!("{container_port}/tcp");
+            exposed.insert(port_key.clone(), HashMap::new());
+
+            // Port bindings format: "8080/tcp" -> [{"HostPort": "8080"}]
+            bindings.insert(
+                port_key,
+                Some(vec![PortBinding {
+                    host_ip: None,
+                    host_port: Some(host_port.to_string()),
+                }]),
+            );
+        }
+
+        (Some(exposed), Some(bindings))
+    } else {
+        (None, None)
+    };
+
+    // Docker expresses CPU limits in billionths of a CPU
+    #[allow(clippy::cast_possible_truncation)]
+    let nano_cpus = config.cpus.map(|cpus| (cpus * 1e9) as i64);
+
+    let host_config = HostConfig {
+        binds,
+        port_bindings,
+        network_mode: config.network_mode.clone(),
+        nano_cpus,
+        memory: config.memory,
+        ..Default::default()
+    };
+
+    (exposed_ports, host_config)
+}
+
+/// Pool key of a container: the settings fixed when it is created
+///
+/// Environment, working directory and user are set per execution, so
+/// containers that differ only in those can be shared.
+fn warm_key(config: &ContainerConfig) -> String {
+    let mut volumes: Vec<_> = config.volumes.iter().flatten().collect();
+    volumes.sort();
+    let mut ports: Vec<_> = config.ports.iter().flatten().collect();
+    ports.sort();
+    format!(
+        "{}|{:?}|{:?}|{:?}|{volumes:?}|{ports:?}",
+        config.image, config.network_mode, config.cpus, config.memory
+    )
+}
+
+/// Write stdin to the container, if provided, and close it
+async fn write_stdin(
+    mut input: std::pin::Pin<Box<dyn AsyncWrite + Send>>,
+    stdin: Option<&str>,
+) -> Result<()> {
+    if let Some(stdin_str) = stdin {
+        input
+            .write_all(stdin_str.as_bytes())
+            .await
+            .map_err(|e| Error::Io {
+                message: format!("Failed to write stdin: {e}"),
+            })?;
+        input.shutdown().await.map_err(|e| Error::Io {
+            message: format!("Failed to close stdin: {e}"),
+        })?;
+    }
+    Ok(())
+}
+
+/// Collect stdout and stderr from an attached container or exec
+async fn collect_output(
+    mut output: impl Stream<Item = std::result::Result<LogOutput, bollard::errors::Error>> + Unpin,
+) -> Result<(String, String)> {
+    let mut stdout_buffer = String::new();
+    let mut stderr_buffer = String::new();
+
+    while let Some(output_result) = output.next().await {
+        let output_chunk = output_result.map_err(|e| Error::Io {
+            message: format!("Failed to read output: {e}"),
+        })?;
+
+        match output_chunk {
+            LogOutput::StdOut { message } => {
+                stdout_buffer.push_str(&String::from_utf8_lossy(&message));
+            }
+            LogOutput::StdErr { message } => {
+                stderr_buffer.push_str(&String::from_utf8_lossy(&message));
+            }
+            LogOutput::StdIn { .. } => {}
+            LogOutput::Console { message } => {
+                stdout_buffer.push_str(&String::from_utf8_lossy(&message));
+            }
+        }
+    }
+
+    Ok((stdout_buffer, stderr_buffer))
 }
 
 #[cfg(test)]
@@ -261,6 +799,7 @@ mod tests {
     #![allow(clippy::panic)]
 
     use super::*;
+    use tokio_util::sync::CancellationToken;
 
     #[tokio::test]
     async fn test_docker_execute_simple() {
@@ -282,6 +821,7 @@ mod tests {
             working_dir: None,
             volumes: None,
             ports: None,
+            ..ContainerConfig::default()
         };
 
         let result = provider.execute(config).await;
@@ -312,6 +852,7 @@ mod tests {
             working_dir: None,
             volumes: None,
             ports: None,
+            ..ContainerConfig::default()
         };
 
         let result = provider.execute(config).await;
@@ -349,6 +890,7 @@ mod tests {
             working_dir: None,
             volumes: None,
             ports: None,
+            ..ContainerConfig::default()
         };
 
         let result = provider.execute(config).await;
@@ -358,4 +900,216 @@ mod tests {
         assert_eq!(result.exit_code, 0);
         assert!(result.stdout.contains("test_value"));
     }
+
+    #[tokio::test]
+    async fn test_docker_execute_with_workdir_and_user() {
+        let provider = DockerProvider::new();
+
+        // Skip test if Docker is not available
+        if provider.is_err() {
+            eprintln!("Skipping test: Docker not available");
+            return;
+        }
+
+        let provider = provider.unwrap();
+
+        let config = ContainerConfig {
+            image: "alpine".to_string(),
+            command: vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                "pwd && id -u".to_string(),
+            ],
+            working_dir: Some("/tmp".to_string()),
+            user: Some("1000:1000".to_string()),
+            network_mode: Some("none".to_string()),
+            memory: Some(64 * 1024 * 1024),
+            ..ContainerConfig::default()
+        };
+
+        let result = provider.execute(config).await;
+        assert!(result.is_ok());
+
+        let result = result.unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(
+            result.stdout.lines().collect::<Vec<_>>(),
+            vec!["/tmp", "1000"]
+        );
+    }
+
+    #[test]
+    fn test_warm_key_ignores_per_execution_settings() {
+        let config = ContainerConfig {
+            image: "alpine".to_string(),
+            volumes: Some(HashMap::from([("/a".to_string(), "/b".to_string())])),
+            ..ContainerConfig::default()
+        };
+        let other = ContainerConfig {
+            command: vec!["ls".to_string()],
+            working_dir: Some("/tmp".to_string()),
+            environment: Some(HashMap::from([("A".to_string(), "1".to_string())])),
+            ..config.clone()
+        };
+        assert_eq!(warm_key(&config), warm_key(&other));
+
+        let limited = ContainerConfig {
+            memory: Some(1024),
+            ..config.clone()
+        };
+        assert_ne!(warm_key(&config), warm_key(&limited));
+    }
+
+    #[tokio::test]
+    async fn test_docker_warm_container_is_reused() {
+        let provider = DockerProvider::new();
+
+        // Skip test if Docker is not available
+        if provider.is_err() {
+            eprintln!("Skipping test: Docker not available");
+            return;
+        }
+
+        let pool = Arc::new(WarmPool::default());
+        let provider = provider.unwrap().with_pool(pool.clone());
+
+        let config = ContainerConfig {
+            image: "alpine".to_string(),
+            command: vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                "hostname".to_string(),
+            ],
+            keep_warm: true,
+            ..ContainerConfig::default()
+        };
+
+        let first = provider.execute(config.clone()).await;
+        if first.is_err() {
+            eprintln!("Skipping test: Docker not available");
+            return;
+        }
+        let second = provider.execute(config).await.unwrap();
+        assert_eq!(first.unwrap().stdout, second.stdout);
+
+        provider.drain_pool().await;
+        assert!(pool.drain().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_docker_execute_with_services() {
+        let provider = DockerProvider::new();
+
+        // Skip test if Docker is not available
+        if provider.is_err() {
+            eprintln!("Skipping test: Docker not available");
+            return;
+        }
+
+        let provider = provider.unwrap();
+
+        let services = BTreeMap::from([(
+            "db".to_string(),
+            ServiceSpec {
+                image: "alpine".to_string(),
+                command: Some(vec![
+                    "/bin/sh".to_string(),
+                    "-c".to_string(),
+                    "touch /ready && sleep 60".to_string(),
+                ]),
+                ready: Some("test -f /ready".to_string()),
+                ..ServiceSpec::default()
+            },
+        )]);
+        let config = ContainerConfig {
+            image: "alpine".to_string(),
+            command: vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                "ping -c 1 -W 2 db > /dev/null && echo reachable".to_string(),
+            ],
+            ..ContainerConfig::default()
+        };
+
+        let result = provider
+            .execute_with_services(&services, Duration::from_secs(30), config)
+            .await;
+        if result.is_err() {
+            eprintln!("Skipping test: Docker not available");
+            return;
+        }
+
+        let result = result.unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert!(result.stdout.contains("reachable"));
+    }
+
+    #[tokio::test]
+    async fn test_docker_cancelled_container_is_removed() {
+        let provider = DockerProvider::new();
+
+        // Skip test if Docker is not available
+        if provider.is_err() {
+            eprintln!("Skipping test: Docker not available");
+            return;
+        }
+
+        let provider = provider.unwrap();
+
+        let probe = ContainerConfig {
+            image: "alpine".to_string(),
+            command: vec!["true".to_string()],
+            ..ContainerConfig::default()
+        };
+        if provider.execute(probe).await.is_err() {
+            eprintln!("Skipping test: Docker not available");
+            return;
+        }
+
+        let cancellation = CancellationToken::new();
+        let reaper = Reaper::default();
+        let config = ContainerConfig {
+            image: "alpine".to_string(),
+            command: vec!["sleep".to_string(), "30".to_string()],
+            cancellation: cancellation.clone(),
+            reaper: reaper.clone(),
+            ..ContainerConfig::default()
+        };
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            cancellation.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let result = provider.execute(config).await;
+        assert!(matches!(result, Err(Error::Cancelled { .. })));
+        assert!(started.elapsed() < Duration::from_secs(20));
+
+        // The removed container is reported
+        let reaped = reaper.take();
+        let Some(ReapedResource::Container { id, image }) = reaped.first() else {
+            panic!("expected a reaped container, got {reaped:?}");
+        };
+        assert_eq!(image, "alpine");
+        assert!(provider.docker.inspect_container(id, None).await.is_err());
+    }
+
+    #[test]
+    fn test_context_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Dockerfile"), "FROM alpine\n").unwrap();
+        let archive = context_archive(dir.path().to_str().unwrap()).unwrap();
+        let mut entries = tar::Archive::new(archive.as_slice());
+        let names: Vec<String> = entries
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert!(
+            names.iter().any(|name| name.ends_with("Dockerfile")),
+            "{names:?}"
+        );
+
+        assert!(context_archive("/does/not/exist").is_err());
+    }
 }
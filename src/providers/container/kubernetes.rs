@@ -0,0 +1,414 @@
+use crate::container::{ContainerConfig, ContainerProvider, ContainerResult, Error, Result};
+use crate::context::Reaper;
+use crate::workflow::ReapedResource;
+use async_trait::async_trait;
+use futures::AsyncReadExt;
+use k8s_openapi::api::batch::v1::{Job, JobSpec};
+use k8s_openapi::api::core::v1::{
+    Container, ContainerState, EnvVar, Pod, PodSpec, PodTemplateSpec, ResourceRequirements,
+    SecurityContext,
+};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::Client;
+use kube::api::{Api, AttachParams, DeleteParams, ListParams, LogParams, PostParams};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Name of the container running the task in a Job's pod
+const CONTAINER_NAME: &str = "task";
+
+/// Label marking the Jobs jackdaw creates
+const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+
+/// Delay between checks of a Job's pod
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Seconds a finished Job is kept if it could not be deleted
+const FINISHED_TTL_SECS: i32 = 300;
+
+/// Kubernetes container provider, running each container task as a Job
+///
+/// The cluster and namespace are those of the current kubeconfig context
+/// (`KUBECONFIG` or `~/.kube/config`), or of the pod jackdaw runs in when it
+/// runs in a cluster. Output is read from the pod's log, which merges stdout
+/// and stderr, so everything the container prints is returned as stdout.
+#[derive(Clone)]
+pub struct KubernetesProvider {
+    client: Client,
+    namespace: String,
+}
+
+impl std::fmt::Debug for KubernetesProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KubernetesProvider")
+            .field("namespace", &self.namespace)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KubernetesProvider {
+    /// Create a new Kubernetes provider from kubeconfig or in-cluster credentials
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no kubeconfig or in-cluster configuration is found,
+    /// or the client cannot be created
+    pub async fn new() -> Result<Self> {
+        let client = Client::try_default().await.map_err(|e| Error::Provider {
+            message: format!("Failed to connect to Kubernetes: {e}"),
+        })?;
+        let namespace = client.default_namespace().to_string();
+        Ok(Self { client, namespace })
+    }
+
+    /// Wait until the Job's container runs or has finished, returning its pod's name
+    async fn wait_started(&self, job: &str, config: &ContainerConfig) -> Result<String> {
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
+        let selector = ListParams::default().labels(&format!("job-name={job}"));
+        let deadline = config
+            .pull_timeout
+            .map(|timeout| tokio::time::Instant::now() + timeout);
+        loop {
+            let list = pods.list(&selector).await.map_err(|e| Error::Start {
+                message: format!("Failed to find the pod of job {job}: {e}"),
+            })?;
+            if let Some(pod) = list.items.first()
+                && let Some(name) = pod.metadata.name.clone()
+                && let Some(state) = container_state(pod)
+            {
+                if state.running.is_some() || state.terminated.is_some() {
+                    return Ok(name);
+                }
+                if let Some(waiting) = state.waiting
+                    && matches!(
+                        waiting.reason.as_deref(),
+                        Some("ErrImagePull" | "ImagePullBackOff" | "InvalidImageName")
+                    )
+                {
+                    return Err(Error::ImagePull {
+                        message: format!(
+                            "Failed to pull image {}: {}",
+                            config.image,
+                            waiting.message.unwrap_or_default()
+                        ),
+                    });
+                }
+            }
+            if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                return Err(Error::ImagePull {
+                    message: format!("Timed out waiting for the pod of job {job} to start"),
+                });
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Write `stdin` to the container and close it
+    async fn write_stdin(&self, pod: &str, stdin: &str) -> Result<()> {
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
+        let params = AttachParams::default()
+            .container(CONTAINER_NAME)
+            .stdin(true)
+            .stdout(false)
+            .stderr(false);
+        let mut attached = pods.attach(pod, &params).await.map_err(|e| Error::Attach {
+            message: format!("Failed to attach to pod {pod}: {e}"),
+        })?;
+        let mut writer = attached.stdin().ok_or_else(|| Error::Attach {
+            message: format!("Pod {pod} has no stdin"),
+        })?;
+        let write = async {
+            writer.write_all(stdin.as_bytes()).await?;
+            writer.shutdown().await
+        };
+        write.await.map_err(|e| Error::Io {
+            message: format!("Failed to write to stdin: {e}"),
+        })?;
+        // Detaching closes the container's stdin, as it is attached once
+        attached.abort();
+        Ok(())
+    }
+
+    /// Follow the pod's log until the container exits, then read its exit code
+    async fn wait_finished(&self, pod: &str) -> Result<(String, i64)> {
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &self.namespace);
+        let params = LogParams {
+            container: Some(CONTAINER_NAME.to_string()),
+            follow: true,
+            ..LogParams::default()
+        };
+        let mut output = String::new();
+        pods.log_stream(pod, &params)
+            .await
+            .map_err(|e| Error::Attach {
+                message: format!("Failed to read the log of pod {pod}: {e}"),
+            })?
+            .read_to_string(&mut output)
+            .await
+            .map_err(|e| Error::Io {
+                message: format!("Failed to read the log of pod {pod}: {e}"),
+            })?;
+
+        // The log can end shortly before the pod's status shows the exit code
+        loop {
+            let current = pods.get(pod).await.map_err(|e| Error::Inspect {
+                message: format!("Failed to inspect pod {pod}: {e}"),
+            })?;
+            if let Some(terminated) = container_state(&current).and_then(|state| state.terminated) {
+                return Ok((output, i64::from(terminated.exit_code)));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn run(&self, job: &str, config: &ContainerConfig) -> Result<ContainerResult> {
+        let pod = self.wait_started(job, config).await?;
+        if let Some(stdin) = &config.stdin {
+            self.write_stdin(&pod, stdin).await?;
+        }
+        let (stdout, exit_code) = self.wait_finished(&pod).await?;
+        Ok(ContainerResult {
+            stdout,
+            stderr: String::new(),
+            exit_code,
+        })
+    }
+
+    /// Delete a Job and its pod, ignoring failures (it may already be gone)
+    async fn delete(&self, job: &str) {
+        let jobs: Api<Job> = Api::namespaced(self.client.clone(), &self.namespace);
+        let _ = jobs.delete(job, &DeleteParams::background()).await;
+    }
+}
+
+#[async_trait]
+impl ContainerProvider for KubernetesProvider {
+    async fn execute(&self, config: ContainerConfig) -> Result<ContainerResult> {
+        let name = format!("jackdaw-{}", uuid::Uuid::new_v4().simple());
+        let job = job_spec(&name, &config)?;
+        let jobs: Api<Job> = Api::namespaced(self.client.clone(), &self.namespace);
+        jobs.create(&PostParams::default(), &job)
+            .await
+            .map_err(|e| Error::Creation {
+                message: format!("Failed to create job {name}: {e}"),
+            })?;
+
+        // Run the job, deleting it if the task is cancelled meanwhile
+        let result = tokio::select! {
+            result = self.run(&name, &config) => result,
+            () = config.cancellation.cancelled() => {
+                Err(reaped(&config.reaper, &name, &config.image))
+            }
+        };
+        self.delete(&name).await;
+        result
+    }
+}
+
+/// The Job running a container task
+///
+/// # Errors
+///
+/// Returns an error for settings a Job cannot honour: volumes and ports of
+/// the host, a network mode, or a user or group that is not numeric
+fn job_spec(name: &str, config: &ContainerConfig) -> Result<Job> {
+    if config
+        .volumes
+        .as_ref()
+        .is_some_and(|volumes| !volumes.is_empty())
+    {
+        return Err(Error::Options {
+            message: "volumes are not supported by the kubernetes provider".to_string(),
+        });
+    }
+    if config.ports.as_ref().is_some_and(|ports| !ports.is_empty()) {
+        return Err(Error::Options {
+            message: "ports are not supported by the kubernetes provider".to_string(),
+        });
+    }
+    if config.network_mode.is_some() {
+        return Err(Error::Options {
+            message: "network modes are not supported by the kubernetes provider".to_string(),
+        });
+    }
+
+    let labels = BTreeMap::from([
+        (MANAGED_BY_LABEL.to_string(), "jackdaw".to_string()),
+        ("jackdaw.job".to_string(), name.to_string()),
+    ]);
+    let mut limits = BTreeMap::new();
+    if let Some(cpus) = config.cpus {
+        limits.insert("cpu".to_string(), Quantity(cpus.to_string()));
+    }
+    if let Some(memory) = config.memory {
+        limits.insert("memory".to_string(), Quantity(memory.to_string()));
+    }
+
+    let container = Container {
+        name: CONTAINER_NAME.to_string(),
+        image: Some(config.image.clone()),
+        // Without a command the image's entrypoint and command run
+        command: (!config.command.is_empty()).then(|| config.command.clone()),
+        env: config.environment.as_ref().map(|environment| {
+            let mut env: Vec<EnvVar> = environment
+                .iter()
+                .map(|(name, value)| EnvVar {
+                    name: name.clone(),
+                    value: Some(value.clone()),
+                    ..EnvVar::default()
+                })
+                .collect();
+            env.sort_by(|a, b| a.name.cmp(&b.name));
+            env
+        }),
+        working_dir: config.working_dir.clone(),
+        stdin: Some(config.stdin.is_some()),
+        stdin_once: Some(config.stdin.is_some()),
+        image_pull_policy: config.skip_pull.then(|| "IfNotPresent".to_string()),
+        resources: (!limits.is_empty()).then(|| ResourceRequirements {
+            limits: Some(limits),
+            ..ResourceRequirements::default()
+        }),
+        security_context: config.user.as_deref().map(security_context).transpose()?,
+        ..Container::default()
+    };
+
+    Ok(Job {
+        metadata: ObjectMeta {
+            name: Some(name.to_string()),
+            labels: Some(labels.clone()),
+            ..ObjectMeta::default()
+        },
+        spec: Some(JobSpec {
+            backoff_limit: Some(0),
+            ttl_seconds_after_finished: Some(FINISHED_TTL_SECS),
+            template: PodTemplateSpec {
+                metadata: Some(ObjectMeta {
+                    labels: Some(labels),
+                    ..ObjectMeta::default()
+                }),
+                spec: Some(PodSpec {
+                    restart_policy: Some("Never".to_string()),
+                    containers: vec![container],
+                    ..PodSpec::default()
+                }),
+            },
+            ..JobSpec::default()
+        }),
+        ..Job::default()
+    })
+}
+
+/// The security context running the container as `user` (`UID` or `UID:GID`)
+fn security_context(user: &str) -> Result<SecurityContext> {
+    let parse = |id: &str| {
+        id.parse::<i64>().map_err(|_| Error::Options {
+            message: format!("the kubernetes provider needs a numeric user and group, got {user}"),
+        })
+    };
+    let (uid, gid) = match user.split_once(':') {
+        Some((uid, gid)) => (parse(uid)?, Some(parse(gid)?)),
+        None => (parse(user)?, None),
+    };
+    Ok(SecurityContext {
+        run_as_user: Some(uid),
+        run_as_group: gid,
+        ..SecurityContext::default()
+    })
+}
+
+/// State of the task's container in a Job's pod
+fn container_state(pod: &Pod) -> Option<ContainerState> {
+    pod.status
+        .as_ref()?
+        .container_statuses
+        .as_ref()?
+        .iter()
+        .find(|status| status.name == CONTAINER_NAME)?
+        .state
+        .clone()
+}
+
+/// Record that a task's Job is deleted because it was cancelled, and build
+/// the resulting error
+fn reaped(reaper: &Reaper, job: &str, image: &str) -> Error {
+    reaper.record(ReapedResource::Container {
+        id: job.to_string(),
+        image: image.to_string(),
+    });
+    Error::Cancelled {
+        message: format!("job {job} of image {image} was deleted"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_job_spec() {
+        let config = ContainerConfig {
+            image: "alpine".to_string(),
+            command: vec!["echo".to_string(), "hello".to_string()],
+            stdin: Some("input".to_string()),
+            environment: Some(HashMap::from([("GREETING".to_string(), "hi".to_string())])),
+            working_dir: Some("/work".to_string()),
+            user: Some("1000:2000".to_string()),
+            cpus: Some(0.5),
+            memory: Some(536_870_912),
+            ..ContainerConfig::default()
+        };
+        let job = job_spec("jackdaw-test", &config).unwrap();
+
+        let spec = job.spec.unwrap();
+        assert_eq!(spec.backoff_limit, Some(0));
+        let pod = spec.template.spec.unwrap();
+        assert_eq!(pod.restart_policy.as_deref(), Some("Never"));
+        let container = pod.containers.first().unwrap();
+        assert_eq!(container.image.as_deref(), Some("alpine"));
+        assert_eq!(
+            container.command,
+            Some(vec!["echo".to_string(), "hello".to_string()])
+        );
+        assert_eq!(container.stdin_once, Some(true));
+        assert_eq!(container.working_dir.as_deref(), Some("/work"));
+        let env = container.env.as_ref().unwrap();
+        assert_eq!(env.first().map(|var| var.name.as_str()), Some("GREETING"));
+        let security = container.security_context.as_ref().unwrap();
+        assert_eq!(security.run_as_user, Some(1000));
+        assert_eq!(security.run_as_group, Some(2000));
+        let limits = container
+            .resources
+            .as_ref()
+            .and_then(|resources| resources.limits.as_ref())
+            .unwrap();
+        assert_eq!(limits.get("cpu"), Some(&Quantity("0.5".to_string())));
+        assert_eq!(
+            limits.get("memory"),
+            Some(&Quantity("536870912".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_job_spec_rejects_host_settings() {
+        let volumes = ContainerConfig {
+            image: "alpine".to_string(),
+            volumes: Some(HashMap::from([("/tmp".to_string(), "/data".to_string())])),
+            ..ContainerConfig::default()
+        };
+        assert!(job_spec("jackdaw-test", &volumes).is_err());
+
+        let user = ContainerConfig {
+            image: "alpine".to_string(),
+            user: Some("nobody".to_string()),
+            ..ContainerConfig::default()
+        };
+        assert!(job_spec("jackdaw-test", &user).is_err());
+    }
+}
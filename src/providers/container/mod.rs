@@ -1,3 +1,50 @@
+#[cfg(feature = "docker")]
 pub mod docker;
+#[cfg(feature = "kubernetes")]
+pub mod kubernetes;
+#[cfg(feature = "docker")]
+pub mod podman;
+pub mod pool;
 
+#[cfg(feature = "docker")]
 pub use docker::DockerProvider;
+#[cfg(feature = "kubernetes")]
+pub use kubernetes::KubernetesProvider;
+#[cfg(feature = "docker")]
+pub use podman::PodmanProvider;
+pub use pool::WarmPool;
+
+use crate::container::{ContainerProvider, ContainerRuntime, Error, Result};
+use std::sync::Arc;
+
+/// Connect to `runtime`, keeping warm containers in `pool`
+///
+/// # Errors
+///
+/// Returns an error if the runtime cannot be reached, or is not supported
+pub async fn connect(
+    runtime: ContainerRuntime,
+    #[cfg_attr(not(feature = "docker"), allow(unused_variables))] pool: Arc<WarmPool>,
+) -> Result<Box<dyn ContainerProvider>> {
+    match runtime {
+        #[cfg(feature = "docker")]
+        ContainerRuntime::Docker => Ok(Box::new(DockerProvider::new()?.with_pool(pool))),
+        #[cfg(feature = "docker")]
+        ContainerRuntime::Podman => Ok(Box::new(PodmanProvider::new()?.with_pool(pool))),
+        #[cfg(not(feature = "docker"))]
+        ContainerRuntime::Docker | ContainerRuntime::Podman => Err(disabled(runtime, "docker")),
+        // Jobs are not kept between tasks, so there is no warm pool
+        #[cfg(feature = "kubernetes")]
+        ContainerRuntime::Kubernetes => Ok(Box::new(KubernetesProvider::new().await?)),
+        #[cfg(not(feature = "kubernetes"))]
+        ContainerRuntime::Kubernetes => Err(disabled(runtime, "kubernetes")),
+    }
+}
+
+/// The error of a runtime whose provider this build was compiled without
+#[cfg(not(all(feature = "docker", feature = "kubernetes")))]
+fn disabled(runtime: ContainerRuntime, feature: &str) -> Error {
+    Error::Provider {
+        message: format!("the {runtime} provider needs jackdaw built with the `{feature}` feature"),
+    }
+}
@@ -0,0 +1,108 @@
+use crate::container::{
+    ContainerConfig, ContainerProvider, ContainerResult, Error, ImageBuild, Result, ServiceSpec,
+};
+use crate::task_output::TaskOutputStreamer;
+use async_trait::async_trait;
+use bollard::Docker;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{DockerProvider, WarmPool};
+
+/// Seconds to wait for an answer from the Podman API
+const API_TIMEOUT_SECS: u64 = 120;
+
+/// Podman container provider
+///
+/// Podman serves a Docker-compatible API on a socket (started with
+/// `podman system service` or the `podman.socket` systemd unit), so tasks
+/// run through the same client as with Docker.
+#[derive(Debug, Clone)]
+pub struct PodmanProvider(DockerProvider);
+
+impl PodmanProvider {
+    /// Create a new Podman provider
+    ///
+    /// The socket is taken from `CONTAINER_HOST` if set, else the rootless
+    /// socket under `XDG_RUNTIME_DIR` if it exists, else the system socket
+    /// `/run/podman/podman.sock`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `CONTAINER_HOST` is not a Unix socket URL, or the
+    /// client cannot be created
+    pub fn new() -> Result<Self> {
+        let socket = socket_path()?;
+        let docker = Docker::connect_with_unix(
+            &socket.to_string_lossy(),
+            API_TIMEOUT_SECS,
+            bollard::API_DEFAULT_VERSION,
+        )
+        .map_err(|e| Error::Provider {
+            message: format!("Failed to connect to Podman at {}: {e}", socket.display()),
+        })?;
+        Ok(Self(DockerProvider::with_docker(docker)))
+    }
+
+    /// Keep containers of tasks with `keep_warm` set running in `pool` between executions
+    #[must_use]
+    pub fn with_pool(self, pool: Arc<WarmPool>) -> Self {
+        Self(self.0.with_pool(pool))
+    }
+}
+
+/// Path of the Podman API socket
+fn socket_path() -> Result<PathBuf> {
+    if let Ok(host) = std::env::var("CONTAINER_HOST") {
+        return host
+            .strip_prefix("unix://")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::Provider {
+                message: format!("CONTAINER_HOST must be a unix:// socket URL, got {host}"),
+            });
+    }
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        let rootless = PathBuf::from(runtime_dir).join("podman/podman.sock");
+        if rootless.exists() {
+            return Ok(rootless);
+        }
+    }
+    Ok(PathBuf::from("/run/podman/podman.sock"))
+}
+
+#[async_trait]
+impl ContainerProvider for PodmanProvider {
+    async fn execute(&self, config: ContainerConfig) -> Result<ContainerResult> {
+        self.0.execute(config).await
+    }
+
+    async fn build_image(
+        &self,
+        build: &ImageBuild,
+        tag: &str,
+        streamer: Option<&TaskOutputStreamer>,
+    ) -> Result<String> {
+        self.0.build_image(build, tag, streamer).await
+    }
+
+    async fn execute_with_services(
+        &self,
+        services: &BTreeMap<String, ServiceSpec>,
+        ready_timeout: Duration,
+        config: ContainerConfig,
+    ) -> Result<ContainerResult> {
+        self.0
+            .execute_with_services(services, ready_timeout, config)
+            .await
+    }
+
+    async fn check_image(&self, image: &str) -> Result<()> {
+        self.0.check_image(image).await
+    }
+
+    async fn drain_pool(&self) {
+        self.0.drain_pool().await;
+    }
+}
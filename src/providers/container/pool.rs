@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Number of idle containers kept when no size is configured
+pub const DEFAULT_POOL_SIZE: usize = 4;
+
+/// How long a container stays idle before it is removed, when no TTL is configured
+pub const DEFAULT_IDLE_TTL: Duration = Duration::from_secs(300);
+
+/// Idle containers kept running between executions of container tasks
+///
+/// Containers are grouped by a key describing how they were created (image,
+/// network, limits, mounts), so a container is only reused by tasks that
+/// would have created an identical one. The pool only tracks container IDs;
+/// starting, stopping and removing containers is left to the provider, which
+/// removes every ID the pool hands back as evicted.
+#[derive(Debug)]
+pub struct WarmPool {
+    /// Maximum number of idle containers, across all keys
+    max_size: usize,
+    /// How long a container may stay idle before it is evicted
+    idle_ttl: Duration,
+    idle: Mutex<HashMap<String, Vec<IdleContainer>>>,
+}
+
+#[derive(Debug)]
+struct IdleContainer {
+    id: String,
+    since: Instant,
+}
+
+impl Default for WarmPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_POOL_SIZE, DEFAULT_IDLE_TTL)
+    }
+}
+
+impl WarmPool {
+    #[must_use]
+    pub fn new(max_size: usize, idle_ttl: Duration) -> Self {
+        Self {
+            max_size,
+            idle_ttl,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take an idle container created with `key`
+    ///
+    /// Also returns the containers that have been idle for longer than the
+    /// TTL, which the caller should remove.
+    pub fn checkout(&self, key: &str) -> (Option<String>, Vec<String>) {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        let evicted = self.evict_expired(&mut idle);
+        let container = idle
+            .get_mut(key)
+            .and_then(Vec::pop)
+            .map(|container| container.id);
+        (container, evicted)
+    }
+
+    /// Return a container to the pool after an execution
+    ///
+    /// Returns the containers the caller should remove: those that expired,
+    /// and `id` itself if the pool is full.
+    pub fn checkin(&self, key: &str, id: String) -> Vec<String> {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut evicted = self.evict_expired(&mut idle);
+        if idle.values().map(Vec::len).sum::<usize>() < self.max_size {
+            idle.entry(key.to_string())
+                .or_default()
+                .push(IdleContainer {
+                    id,
+                    since: Instant::now(),
+                });
+        } else {
+            evicted.push(id);
+        }
+        evicted
+    }
+
+    /// Remove every idle container from the pool, returning their IDs
+    pub fn drain(&self) -> Vec<String> {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        idle.drain()
+            .flat_map(|(_, containers)| containers.into_iter().map(|c| c.id))
+            .collect()
+    }
+
+    fn evict_expired(&self, idle: &mut HashMap<String, Vec<IdleContainer>>) -> Vec<String> {
+        let mut evicted = Vec::new();
+        for containers in idle.values_mut() {
+            containers.retain(|container| {
+                let expired = container.since.elapsed() >= self.idle_ttl;
+                if expired {
+                    evicted.push(container.id.clone());
+                }
+                !expired
+            });
+        }
+        idle.retain(|_, containers| !containers.is_empty());
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    #[test]
+    fn test_checkout_reuses_matching_container() {
+        let pool = WarmPool::default();
+        assert!(pool.checkin("alpine", "a".to_string()).is_empty());
+        assert_eq!(pool.checkout("python"), (None, Vec::new()));
+        assert_eq!(pool.checkout("alpine"), (Some("a".to_string()), Vec::new()));
+        assert_eq!(pool.checkout("alpine"), (None, Vec::new()));
+    }
+
+    #[test]
+    fn test_full_pool_evicts_returned_container() {
+        let pool = WarmPool::new(1, DEFAULT_IDLE_TTL);
+        assert!(pool.checkin("alpine", "a".to_string()).is_empty());
+        assert_eq!(pool.checkin("alpine", "b".to_string()), vec!["b"]);
+        assert_eq!(pool.drain(), vec!["a"]);
+        assert!(pool.drain().is_empty());
+    }
+
+    #[test]
+    fn test_idle_containers_expire() {
+        let pool = WarmPool::new(DEFAULT_POOL_SIZE, Duration::ZERO);
+        assert!(pool.checkin("alpine", "a".to_string()).is_empty());
+        assert_eq!(pool.checkout("alpine"), (None, vec!["a".to_string()]));
+    }
+}
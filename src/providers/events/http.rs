@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+
+use crate::events::{CloudEvent, Error, EventSink, Result};
+
+/// Content type of CloudEvents sent in structured mode
+const STRUCTURED_CONTENT_TYPE: &str = "application/cloudevents+json";
+
+/// Event sink that POSTs each event to an HTTP endpoint in structured mode
+#[derive(Debug, Clone)]
+pub struct HttpSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpSink {
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for HttpSink {
+    async fn publish(&self, event: &CloudEvent) -> Result<()> {
+        let body = serde_json::to_vec(event).map_err(|e| Error::Publish {
+            sink: self.url.clone(),
+            message: e.to_string(),
+        })?;
+        self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, STRUCTURED_CONTENT_TYPE)
+            .body(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| Error::Publish {
+                sink: self.url.clone(),
+                message: e.to_string(),
+            })?;
+        Ok(())
+    }
+}
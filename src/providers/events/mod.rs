@@ -0,0 +1,68 @@
+pub mod http;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod stdout;
+
+pub use http::HttpSink;
+#[cfg(feature = "mqtt")]
+pub use mqtt::MqttSink;
+pub use stdout::StdoutSink;
+
+use std::sync::Arc;
+
+use crate::events::{Error, EventSink, Result};
+
+/// Create the event sink described by a URI
+///
+/// - `stdout` prints each event as a line of JSON
+/// - `http://...` and `https://...` POST each event in structured mode
+/// - `mqtt://host[:port]/topic` publishes each event to an MQTT topic
+///
+/// # Errors
+///
+/// Returns an error if the URI does not describe a supported sink
+pub fn sink_from_uri(uri: &str) -> Result<Arc<dyn EventSink>> {
+    if uri == "stdout" {
+        return Ok(Arc::new(StdoutSink));
+    }
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        return Ok(Arc::new(HttpSink::new(uri)));
+    }
+    #[cfg(feature = "mqtt")]
+    if uri.starts_with("mqtt://") {
+        return Ok(Arc::new(MqttSink::from_uri(uri)?));
+    }
+    #[cfg(not(feature = "mqtt"))]
+    if uri.starts_with("mqtt://") {
+        return Err(Error::DisabledSink {
+            uri: uri.to_string(),
+            feature: "mqtt".to_string(),
+        });
+    }
+    Err(Error::UnsupportedSink {
+        uri: uri.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    #[test]
+    fn test_sink_from_uri() {
+        assert!(sink_from_uri("stdout").is_ok());
+        assert!(sink_from_uri("https://events.example.com/ingest").is_ok());
+        assert!(sink_from_uri("kafka://localhost:9092/events").is_err());
+    }
+
+    #[cfg(feature = "mqtt")]
+    #[test]
+    fn test_sink_from_mqtt_uri() {
+        assert!(sink_from_uri("mqtt://localhost:1883/workflows/events").is_ok());
+        assert!(sink_from_uri("mqtt://localhost").is_err());
+    }
+}
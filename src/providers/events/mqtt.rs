@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::time::Duration;
+
+use crate::events::{CloudEvent, Error, EventSink, Result};
+
+/// Port used when an `mqtt://` URI does not specify one
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+/// Event sink that publishes each event to an MQTT topic
+///
+/// Events are published in structured mode (the JSON event format) with
+/// at-least-once delivery. A connection is opened per event.
+#[derive(Debug, Clone)]
+pub struct MqttSink {
+    host: String,
+    port: u16,
+    topic: String,
+}
+
+impl MqttSink {
+    #[must_use]
+    pub fn new(host: impl Into<String>, port: u16, topic: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            topic: topic.into(),
+        }
+    }
+
+    /// Create a sink from a `mqtt://host[:port]/topic` URI
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URI has no topic or an invalid port
+    pub fn from_uri(uri: &str) -> Result<Self> {
+        let invalid = |message: &str| Error::UnsupportedSink {
+            uri: format!("{uri} ({message})"),
+        };
+        let rest = uri
+            .strip_prefix("mqtt://")
+            .ok_or_else(|| invalid("expected mqtt://"))?;
+        let (address, topic) = rest
+            .split_once('/')
+            .filter(|(_, topic)| !topic.is_empty())
+            .ok_or_else(|| invalid("missing topic"))?;
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse::<u16>().map_err(|_| invalid("invalid port"))?,
+            ),
+            None => (address, DEFAULT_MQTT_PORT),
+        };
+        Ok(Self::new(host, port, topic))
+    }
+
+    fn sink(&self) -> String {
+        format!("mqtt://{}:{}/{}", self.host, self.port, self.topic)
+    }
+}
+
+#[async_trait]
+impl EventSink for MqttSink {
+    async fn publish(&self, event: &CloudEvent) -> Result<()> {
+        let publish_error = |message: String| Error::Publish {
+            sink: self.sink(),
+            message,
+        };
+        let payload = serde_json::to_vec(event).map_err(|e| publish_error(e.to_string()))?;
+
+        let mut options = MqttOptions::new(
+            format!("jackdaw-{}", uuid::Uuid::new_v4()),
+            self.host.clone(),
+            self.port,
+        );
+        options.set_keep_alive(Duration::from_secs(5));
+        let (client, mut eventloop) = AsyncClient::new(options, 10);
+        client
+            .publish(&self.topic, QoS::AtLeastOnce, false, payload)
+            .await
+            .map_err(|e| publish_error(e.to_string()))?;
+
+        loop {
+            let notification = eventloop
+                .poll()
+                .await
+                .map_err(|e| publish_error(e.to_string()))?;
+            if let Event::Incoming(Packet::PubAck(_)) = notification {
+                break;
+            }
+        }
+        let _ = client.disconnect().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    #[test]
+    fn test_from_uri() {
+        let sink = MqttSink::from_uri("mqtt://broker:1884/workflows/events").unwrap();
+        assert_eq!(sink.host, "broker");
+        assert_eq!(sink.port, 1884);
+        assert_eq!(sink.topic, "workflows/events");
+
+        let sink = MqttSink::from_uri("mqtt://broker/events").unwrap();
+        assert_eq!(sink.port, DEFAULT_MQTT_PORT);
+
+        assert!(MqttSink::from_uri("mqtt://broker:x/events").is_err());
+        assert!(MqttSink::from_uri("mqtt://broker/").is_err());
+    }
+}
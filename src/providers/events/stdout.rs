@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+
+use crate::events::{CloudEvent, Error, EventSink, Result};
+use crate::task_output::OUTPUT_LOCK;
+
+/// Event sink that prints each event to stdout as a line of JSON
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+#[async_trait]
+impl EventSink for StdoutSink {
+    async fn publish(&self, event: &CloudEvent) -> Result<()> {
+        let line = serde_json::to_string(event).map_err(|e| Error::Publish {
+            sink: "stdout".to_string(),
+            message: e.to_string(),
+        })?;
+        let _lock = OUTPUT_LOCK.lock().await;
+        println!("{line}");
+        Ok(())
+    }
+}
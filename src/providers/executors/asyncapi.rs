@@ -0,0 +1,590 @@
+use crate::context::Context;
+use crate::executor::{Capabilities, Endpoint, Error, ErrorKind, Executor, Result, parse_params};
+use crate::workflow::ReapedResource;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use serverless_workflow_core::models::duration::OneOfDurationOrIso8601Expression;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Port used for `mqtt` servers that do not specify one
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+/// Executor for `call: asyncapi` tasks
+///
+/// The operation named in `with.operation` is looked up in the AsyncAPI
+/// document (2.x or 3.0), along with its channel and server. A `send`
+/// operation publishes `with.message.payload` to the channel; a `receive`
+/// operation subscribes to it and returns the messages consumed, as set by
+/// `with.subscription.consume`. Servers using the `mqtt` protocol are supported.
+pub struct AsyncApiExecutor(pub reqwest::Client);
+
+/// Parameters of `call: asyncapi` tasks
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsyncApiParams {
+    pub document: AsyncApiDocument,
+    /// ID of the operation to perform
+    pub operation: String,
+    #[serde(default)]
+    pub server: AsyncApiServer,
+    /// Message to send, for `send` operations
+    pub message: Option<Value>,
+    /// How many messages to consume, for `receive` operations
+    pub subscription: Option<AsyncApiSubscription>,
+    pub authentication: Option<Value>,
+}
+
+/// The AsyncAPI document describing the operation
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsyncApiDocument {
+    pub endpoint: Endpoint,
+}
+
+/// The server to connect to, when the document lists several
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AsyncApiServer {
+    pub name: Option<String>,
+    /// Values of the server URL's `{variables}`
+    #[serde(default)]
+    pub variables: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsyncApiSubscription {
+    pub consume: Option<AsyncApiConsume>,
+}
+
+/// Consume `amount` messages, or messages for a duration, or both
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AsyncApiConsume {
+    pub amount: Option<u64>,
+    #[serde(rename = "for")]
+    pub for_: Option<OneOfDurationOrIso8601Expression>,
+}
+
+/// Whether the workflow sends to or receives from the channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Send,
+    Receive,
+}
+
+/// A broker from the document's `servers`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Server {
+    protocol: String,
+    host: String,
+    port: Option<u16>,
+}
+
+/// An operation resolved against the document's channels and servers
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ResolvedOperation {
+    action: Action,
+    /// Channel address, e.g. an MQTT topic
+    channel: String,
+    server: Server,
+}
+
+#[async_trait]
+impl Executor for AsyncApiExecutor {
+    async fn exec(
+        &self,
+        _task_name: &str,
+        params: &Value,
+        ctx: &Context,
+        _streamer: Option<crate::task_output::TaskOutputStreamer>,
+        cancel: &CancellationToken,
+    ) -> Result<Value> {
+        let params: AsyncApiParams = parse_params("asyncapi", params)?;
+        let document_endpoint = params.document.endpoint.uri();
+        let operation_id = params.operation.as_str();
+        let server_name = params.server.name.as_deref();
+        let variables = &params.server.variables;
+
+        let document = self.load_document(document_endpoint).await?;
+        let operation = resolve_operation(&document, operation_id, server_name, variables)?;
+
+        if operation.server.protocol != "mqtt" {
+            return Err(Error::Execution {
+                message: format!(
+                    "Unsupported AsyncAPI protocol '{}', supported: mqtt",
+                    operation.server.protocol
+                ),
+            });
+        }
+
+        let mut options = MqttOptions::new(
+            format!("jackdaw-{}", uuid::Uuid::new_v4()),
+            operation.server.host.clone(),
+            operation.server.port.unwrap_or(DEFAULT_MQTT_PORT),
+        );
+        options.set_keep_alive(Duration::from_secs(5));
+        if let Some(basic) = params
+            .authentication
+            .as_ref()
+            .and_then(|auth| auth.get("basic"))
+        {
+            let username = basic.get("username").and_then(Value::as_str);
+            let password = basic.get("password").and_then(Value::as_str);
+            if let (Some(username), Some(password)) = (username, password) {
+                options.set_credentials(username, password);
+            }
+        }
+
+        println!(
+            "  AsyncAPI call: {operation_id} on '{}' at {}",
+            operation.channel, operation.server.host
+        );
+
+        match operation.action {
+            Action::Send => {
+                let message = params.message.unwrap_or(Value::Null);
+                let payload = message.get("payload").cloned().unwrap_or(Value::Null);
+                tokio::select! {
+                    result = publish(options, &operation.channel, &payload) => result?,
+                    () = cancel.cancelled() => return Err(cancelled(ctx, &operation, operation_id)),
+                }
+                Ok(message)
+            }
+            Action::Receive => {
+                let consume = params
+                    .subscription
+                    .and_then(|subscription| subscription.consume)
+                    .unwrap_or_default();
+                let amount = consume.amount;
+                let window = consume
+                    .for_
+                    .map(|d| crate::durableengine::timeout::parse_duration(&d))
+                    .transpose()
+                    .map_err(|e| Error::Execution {
+                        message: format!("Invalid subscription.consume.for: {e}"),
+                    })?;
+                let amount = match (amount, window) {
+                    (None, None) => Some(1),
+                    (amount, _) => amount,
+                };
+                tokio::select! {
+                    result = subscribe(options, &operation.channel, amount, window) => result,
+                    () = cancel.cancelled() => Err(cancelled(ctx, &operation, operation_id)),
+                }
+            }
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: false,
+            supports_cancellation: true,
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl AsyncApiExecutor {
+    /// Load an AsyncAPI document (YAML or JSON) from a file or HTTP(S) URL
+    async fn load_document(&self, endpoint: &str) -> Result<Value> {
+        let text = if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+            self.0
+                .get(endpoint)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| Error::Execution {
+                    message: format!("Failed to fetch AsyncAPI document: {e}"),
+                })?
+                .text()
+                .await
+                .map_err(|e| Error::Execution {
+                    message: format!("Failed to read AsyncAPI document: {e}"),
+                })?
+        } else {
+            let path = endpoint.strip_prefix("file://").unwrap_or(endpoint);
+            tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| Error::Execution {
+                    message: format!("Failed to read AsyncAPI document {path}: {e}"),
+                })?
+        };
+
+        serde_yaml::from_str(&text).map_err(|e| Error::Execution {
+            message: format!("Failed to parse AsyncAPI document: {e}"),
+        })
+    }
+}
+
+/// The error returned when a task is cancelled while publishing or receiving
+fn cancelled(ctx: &Context, operation: &ResolvedOperation, operation_id: &str) -> Error {
+    ctx.state.reaper.record(ReapedResource::Request {
+        target: format!("mqtt://{}/{}", operation.server.host, operation.channel),
+    });
+    Error::Cancelled {
+        message: format!("AsyncAPI operation {operation_id} was aborted"),
+    }
+}
+
+/// Find an operation, its channel address and the server to use
+fn resolve_operation(
+    document: &Value,
+    operation_id: &str,
+    server_name: Option<&str>,
+    variables: &Map<String, Value>,
+) -> Result<ResolvedOperation> {
+    let version = document
+        .get("asyncapi")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+
+    let (action, channel) = if version.starts_with('2') {
+        resolve_v2_operation(document, operation_id)?
+    } else {
+        resolve_v3_operation(document, operation_id)?
+    };
+
+    let servers = document
+        .get("servers")
+        .and_then(Value::as_object)
+        .ok_or(Error::Execution {
+            message: "AsyncAPI document defines no servers".to_string(),
+        })?;
+    let (name, server) = match server_name {
+        Some(name) => servers
+            .get_key_value(name)
+            .ok_or_else(|| Error::Execution {
+                message: format!("Server '{name}' not found in AsyncAPI document"),
+            })?,
+        None => servers.iter().next().ok_or(Error::Execution {
+            message: "AsyncAPI document defines no servers".to_string(),
+        })?,
+    };
+
+    Ok(ResolvedOperation {
+        action,
+        channel,
+        server: parse_server(name, server, variables)?,
+    })
+}
+
+/// AsyncAPI 3.0: `operations.<id>` names its action and references its channel
+fn resolve_v3_operation(document: &Value, operation_id: &str) -> Result<(Action, String)> {
+    let operation = document
+        .pointer(&format!("/operations/{}", escape_pointer(operation_id)))
+        .ok_or_else(|| Error::Execution {
+            message: format!("Operation '{operation_id}' not found in AsyncAPI document"),
+        })?;
+
+    let action = match operation.get("action").and_then(Value::as_str) {
+        Some("send") => Action::Send,
+        Some("receive") => Action::Receive,
+        other => {
+            return Err(Error::Execution {
+                message: format!("Operation '{operation_id}' has invalid action {other:?}"),
+            });
+        }
+    };
+
+    let reference = operation
+        .pointer("/channel/$ref")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::Execution {
+            message: format!("Operation '{operation_id}' does not reference a channel"),
+        })?;
+    let channel_pointer = reference.strip_prefix('#').unwrap_or(reference);
+    let channel = document
+        .pointer(channel_pointer)
+        .ok_or_else(|| Error::Execution {
+            message: format!("Channel {reference} not found in AsyncAPI document"),
+        })?;
+
+    // A channel without an address is addressed by its key
+    let address = match channel.get("address").and_then(Value::as_str) {
+        Some(address) => address.to_string(),
+        None => channel_pointer
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .replace("~1", "/")
+            .replace("~0", "~"),
+    };
+    Ok((action, address))
+}
+
+/// AsyncAPI 2.x: channels hold `publish` and `subscribe` operations
+///
+/// Operations are described from the application's point of view: the
+/// workflow sends to `publish` channels and receives from `subscribe` ones.
+fn resolve_v2_operation(document: &Value, operation_id: &str) -> Result<(Action, String)> {
+    let channels = document
+        .get("channels")
+        .and_then(Value::as_object)
+        .ok_or(Error::Execution {
+            message: "AsyncAPI document defines no channels".to_string(),
+        })?;
+
+    for (address, channel) in channels {
+        for (kind, action) in [("publish", Action::Send), ("subscribe", Action::Receive)] {
+            let id = channel
+                .get(kind)
+                .and_then(|op| op.get("operationId"))
+                .and_then(Value::as_str);
+            if id == Some(operation_id) {
+                return Ok((action, address.clone()));
+            }
+        }
+    }
+
+    Err(Error::Execution {
+        message: format!("Operation '{operation_id}' not found in AsyncAPI document"),
+    })
+}
+
+/// Read a server's protocol, host and port, substituting `{variables}`
+///
+/// Values passed in `with.server.variables` take precedence over the defaults
+/// declared by the server.
+fn parse_server(name: &str, server: &Value, variables: &Map<String, Value>) -> Result<Server> {
+    let protocol = server
+        .get("protocol")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::Execution {
+            message: format!("Server '{name}' has no protocol"),
+        })?
+        .to_lowercase();
+
+    // 3.0 servers have a `host`, 2.x servers a `url`
+    let mut address = server
+        .get("host")
+        .or_else(|| server.get("url"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::Execution {
+            message: format!("Server '{name}' has no host"),
+        })?
+        .to_string();
+
+    if let Some(declared) = server.get("variables").and_then(Value::as_object) {
+        for (variable, definition) in declared {
+            let value = variables
+                .get(variable)
+                .or_else(|| definition.get("default"))
+                .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string));
+            if let Some(value) = value {
+                address = address.replace(&format!("{{{variable}}}"), &value);
+            }
+        }
+    }
+
+    let address = address
+        .split_once("://")
+        .map_or(address.as_str(), |(_, rest)| rest);
+    let address = address.split('/').next().unwrap_or(address);
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port.parse::<u16>().map_err(|_| Error::Execution {
+                message: format!("Server '{name}' has invalid port '{port}'"),
+            })?;
+            (host, Some(port))
+        }
+        None => (address, None),
+    };
+
+    Ok(Server {
+        protocol,
+        host: host.to_string(),
+        port,
+    })
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Publish a payload to an MQTT topic and wait for the broker to acknowledge it
+async fn publish(options: MqttOptions, topic: &str, payload: &Value) -> Result<()> {
+    let bytes = match payload {
+        Value::String(s) => s.clone().into_bytes(),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::Array(_) | Value::Object(_) => {
+            serde_json::to_vec(payload).map_err(|e| Error::Execution {
+                message: format!("Failed to serialize message payload: {e}"),
+            })?
+        }
+    };
+
+    let (client, mut eventloop) = AsyncClient::new(options, 10);
+    client
+        .publish(topic, QoS::AtLeastOnce, false, bytes)
+        .await
+        .map_err(|e| Error::Execution {
+            message: format!("Failed to publish to '{topic}': {e}"),
+        })?;
+
+    loop {
+        let event = eventloop.poll().await.map_err(|e| Error::Failed {
+            kind: ErrorKind::Network,
+            message: format!("MQTT connection error: {e}"),
+        })?;
+        if let Event::Incoming(Packet::PubAck(_)) = event {
+            break;
+        }
+    }
+    let _ = client.disconnect().await;
+    Ok(())
+}
+
+/// Subscribe to an MQTT topic and collect messages until `amount` have arrived
+/// or `window` has elapsed
+async fn subscribe(
+    options: MqttOptions,
+    topic: &str,
+    amount: Option<u64>,
+    window: Option<Duration>,
+) -> Result<Value> {
+    let (client, mut eventloop) = AsyncClient::new(options, 10);
+    client
+        .subscribe(topic, QoS::AtLeastOnce)
+        .await
+        .map_err(|e| Error::Execution {
+            message: format!("Failed to subscribe to '{topic}': {e}"),
+        })?;
+
+    let mut messages = Vec::new();
+    let collect = async {
+        while amount.is_none_or(|amount| (messages.len() as u64) < amount) {
+            let event = eventloop.poll().await.map_err(|e| Error::Failed {
+                kind: ErrorKind::Network,
+                message: format!("MQTT connection error: {e}"),
+            })?;
+            if let Event::Incoming(Packet::Publish(publish)) = event {
+                let message = serde_json::from_slice(&publish.payload).unwrap_or_else(|_| {
+                    Value::String(String::from_utf8_lossy(&publish.payload).into_owned())
+                });
+                messages.push(message);
+            }
+        }
+        Ok::<_, Error>(())
+    };
+
+    match window {
+        // Stopping at the end of the window is not an error
+        Some(window) => {
+            if let Ok(result) = tokio::time::timeout(window, collect).await {
+                result?;
+            }
+        }
+        None => collect.await?,
+    }
+
+    let _ = client.disconnect().await;
+    Ok(Value::Array(messages))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    const V3_DOCUMENT: &str = r#"
+asyncapi: 3.0.0
+info: { title: Orders, version: 1.0.0 }
+servers:
+  production:
+    host: 'broker.{region}.example.com:1884'
+    protocol: mqtt
+    variables:
+      region: { default: eu }
+channels:
+  orderPlaced:
+    address: orders/placed
+  orderShipped: {}
+operations:
+  placeOrder:
+    action: send
+    channel: { $ref: '#/channels/orderPlaced' }
+  onOrderShipped:
+    action: receive
+    channel: { $ref: '#/channels/orderShipped' }
+"#;
+
+    const V2_DOCUMENT: &str = r#"
+asyncapi: 2.6.0
+info: { title: Sensors, version: 1.0.0 }
+servers:
+  local:
+    url: mqtt://localhost
+    protocol: mqtt
+channels:
+  sensors/temperature:
+    subscribe:
+      operationId: readTemperature
+  sensors/commands:
+    publish:
+      operationId: sendCommand
+"#;
+
+    fn document(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_v3_operation() {
+        let doc = document(V3_DOCUMENT);
+        let operation = resolve_operation(&doc, "placeOrder", None, &Map::new()).unwrap();
+        assert_eq!(
+            operation,
+            ResolvedOperation {
+                action: Action::Send,
+                channel: "orders/placed".to_string(),
+                server: Server {
+                    protocol: "mqtt".to_string(),
+                    host: "broker.eu.example.com".to_string(),
+                    port: Some(1884),
+                },
+            }
+        );
+
+        let operation = resolve_operation(&doc, "onOrderShipped", None, &Map::new()).unwrap();
+        assert_eq!(operation.action, Action::Receive);
+        assert_eq!(operation.channel, "orderShipped");
+    }
+
+    #[test]
+    fn test_server_variables_override_defaults() {
+        let doc = document(V3_DOCUMENT);
+        let variables = serde_json::json!({ "region": "us" });
+        let operation = resolve_operation(
+            &doc,
+            "placeOrder",
+            Some("production"),
+            variables.as_object().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(operation.server.host, "broker.us.example.com");
+    }
+
+    #[test]
+    fn test_resolve_v2_operation() {
+        let doc = document(V2_DOCUMENT);
+        let operation = resolve_operation(&doc, "readTemperature", None, &Map::new()).unwrap();
+        assert_eq!(operation.action, Action::Receive);
+        assert_eq!(operation.channel, "sensors/temperature");
+        assert_eq!(operation.server.host, "localhost");
+        assert_eq!(operation.server.port, None);
+
+        let operation = resolve_operation(&doc, "sendCommand", None, &Map::new()).unwrap();
+        assert_eq!(operation.action, Action::Send);
+    }
+
+    #[test]
+    fn test_unknown_operation_and_server() {
+        let doc = document(V2_DOCUMENT);
+        assert!(resolve_operation(&doc, "missing", None, &Map::new()).is_err());
+        assert!(resolve_operation(&doc, "sendCommand", Some("prod"), &Map::new()).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Authentication of HTTP and OpenAPI calls
+//!
+//! A call authenticates with a policy given inline, or with `use: <name>`
+//! referring to one defined in the workflow's `use.authentications`. The
+//! schemes are `basic`, `bearer`, `apiKey` and `oauth2` with the
+//! `client_credentials` or `password` grant. A scheme given as `use: <secret>`
+//! takes its properties from a secret declared in `use.secrets`, holding them
+//! as a JSON object.
+//!
+//! OAuth2 access tokens are cached by token endpoint, client and scopes for as
+//! long as they are valid, and refreshed with their refresh token, if the
+//! authority issued one, once they expire.
+
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::context::Context;
+use crate::executor::{Error, ErrorKind, Result, WorkflowError};
+use crate::secrets::SECRETS_KEY;
+
+/// Token endpoint of an OAuth2 authority that does not set `endpoints.token`
+const DEFAULT_TOKEN_PATH: &str = "/oauth2/token";
+
+/// Tokens are refreshed this long before they expire, so none expires in flight
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// OAuth2 access tokens by the grant they were issued for
+static TOKENS: LazyLock<Mutex<HashMap<String, CachedToken>>> = LazyLock::new(Mutex::default);
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Option<Instant>,
+}
+
+impl CachedToken {
+    fn is_valid(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| Instant::now() + EXPIRY_MARGIN < expires_at)
+    }
+}
+
+/// Credentials a request is sent with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Credentials {
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer(String),
+    ApiKey {
+        name: String,
+        value: String,
+        in_query: bool,
+    },
+}
+
+impl Credentials {
+    /// Add the credentials to `request`
+    pub(crate) fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Basic { username, password } => request.basic_auth(username, Some(password)),
+            Self::Bearer(token) => request.bearer_auth(token),
+            Self::ApiKey {
+                name,
+                value,
+                in_query: true,
+            } => request.query(&[(name, value)]),
+            Self::ApiKey {
+                name,
+                value,
+                in_query: false,
+            } => request.header(name.as_str(), value.as_str()),
+        }
+    }
+}
+
+/// Properties of the `oauth2` scheme
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OAuth2Policy {
+    authority: String,
+    #[serde(default = "default_grant")]
+    grant: String,
+    #[serde(default)]
+    client: OAuth2Client,
+    #[serde(default)]
+    scopes: Vec<String>,
+    #[serde(default)]
+    audiences: Vec<String>,
+    username: Option<String>,
+    password: Option<String>,
+    #[serde(default)]
+    endpoints: OAuth2Endpoints,
+}
+
+fn default_grant() -> String {
+    "client_credentials".to_string()
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct OAuth2Client {
+    id: Option<String>,
+    secret: Option<String>,
+    /// `client_secret_post` (default) or `client_secret_basic`
+    authentication: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct OAuth2Endpoints {
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+/// Resolve the authentication policy of a call into the credentials to send
+///
+/// # Errors
+///
+/// Returns an error if the policy refers to an undefined authentication or
+/// secret, uses an unsupported scheme, or an OAuth2 token cannot be obtained.
+pub(crate) async fn credentials(
+    client: &reqwest::Client,
+    policy: &Value,
+    ctx: &Context,
+) -> Result<Credentials> {
+    let policy = resolve_reference(policy, ctx)?;
+    let data = ctx.data().await;
+    let policy = crate::expressions::evaluate_value_with_input(
+        ctx.metadata.evaluation,
+        &policy,
+        &data,
+        &ctx.metadata.initial_input,
+    )
+    .map_err(|e| Error::Execution {
+        message: format!("Failed to evaluate authentication policy: {e}"),
+    })?;
+    let Value::Object(schemes) = &policy else {
+        return Err(config_error("an authentication policy must be an object"));
+    };
+    // Policies defined in `use.authentications` list the schemes they do not use as null
+    let Some((scheme, properties)) = schemes.iter().find(|(_, properties)| !properties.is_null())
+    else {
+        return Err(config_error("the authentication policy names no scheme"));
+    };
+    let properties = secret_properties(properties, &data)?;
+    let field = |name: &str| {
+        properties
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| config_error(&format!("{scheme} authentication requires '{name}'")))
+    };
+
+    match scheme.as_str() {
+        "basic" => Ok(Credentials::Basic {
+            username: field("username")?,
+            password: field("password")?,
+        }),
+        "bearer" => Ok(Credentials::Bearer(field("token")?)),
+        "apiKey" => Ok(Credentials::ApiKey {
+            name: field("name").unwrap_or_else(|_| "X-API-Key".to_string()),
+            value: field("key")?,
+            in_query: properties.get("in").and_then(Value::as_str) == Some("query"),
+        }),
+        "oauth2" => {
+            let policy: OAuth2Policy = serde_json::from_value(without_nulls(properties.clone()))
+                .map_err(|e| config_error(&format!("invalid oauth2 authentication: {e}")))?;
+            Ok(Credentials::Bearer(
+                access_token(client, &policy, ctx).await?,
+            ))
+        }
+        other => Err(config_error(&format!(
+            "unsupported authentication scheme '{other}', expected basic, bearer, apiKey or oauth2"
+        ))),
+    }
+}
+
+/// The policy `use: <name>` refers to in `use.authentications`, or `policy` itself
+fn resolve_reference(policy: &Value, ctx: &Context) -> Result<Value> {
+    let Some(name) = policy.get("use").and_then(Value::as_str) else {
+        return Ok(policy.clone());
+    };
+    let authentications = ctx
+        .metadata
+        .workflow
+        .use_
+        .as_ref()
+        .and_then(|use_| serde_json::to_value(&use_.authentications).ok());
+    authentications
+        .as_ref()
+        .and_then(|authentications| authentications.get(name))
+        .cloned()
+        .ok_or_else(|| {
+            config_error(&format!(
+                "authentication '{name}' is not defined in use.authentications"
+            ))
+        })
+}
+
+/// The properties of a scheme, read from a secret if given as `use: <secret>`
+fn secret_properties(properties: &Value, data: &Value) -> Result<Value> {
+    let Some(secret) = properties.get("use").and_then(Value::as_str) else {
+        return Ok(properties.clone());
+    };
+    let value = data
+        .get(SECRETS_KEY)
+        .and_then(|secrets| secrets.get(secret))
+        .and_then(Value::as_str)
+        .ok_or_else(|| {
+            config_error(&format!("secret '{secret}' is not declared in use.secrets"))
+        })?;
+    serde_json::from_str(value).map_err(|_| {
+        config_error(&format!(
+            "secret '{secret}' does not hold the properties of an authentication as JSON"
+        ))
+    })
+}
+
+/// An access token for `policy`, from the cache if one is still valid
+async fn access_token(
+    client: &reqwest::Client,
+    policy: &OAuth2Policy,
+    ctx: &Context,
+) -> Result<String> {
+    let token_endpoint = super::route(
+        &ctx.services,
+        &format!(
+            "{}{}",
+            policy.authority.trim_end_matches('/'),
+            policy
+                .endpoints
+                .token
+                .as_deref()
+                .unwrap_or(DEFAULT_TOKEN_PATH)
+        ),
+    )?;
+    let key = format!(
+        "{token_endpoint}|{}|{}|{}|{}|{}",
+        policy.grant,
+        policy.client.id.as_deref().unwrap_or_default(),
+        policy.username.as_deref().unwrap_or_default(),
+        policy.scopes.join(" "),
+        policy.audiences.join(" ")
+    );
+
+    let cached = TOKENS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+        .cloned();
+    let token = match cached {
+        Some(token) if token.is_valid() => return Ok(token.access_token),
+        Some(CachedToken {
+            refresh_token: Some(refresh_token),
+            ..
+        }) => {
+            let form = vec![
+                ("grant_type", "refresh_token".to_string()),
+                ("refresh_token", refresh_token.clone()),
+            ];
+            // A rejected refresh token falls back to the original grant
+            match request_token(client, &token_endpoint, policy, form).await {
+                Ok(token) => CachedToken {
+                    refresh_token: token.refresh_token.or(Some(refresh_token)),
+                    ..token
+                },
+                Err(_) => {
+                    request_token(client, &token_endpoint, policy, grant_form(policy)?).await?
+                }
+            }
+        }
+        Some(_) | None => {
+            request_token(client, &token_endpoint, policy, grant_form(policy)?).await?
+        }
+    };
+
+    let access_token = token.access_token.clone();
+    TOKENS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, token);
+    Ok(access_token)
+}
+
+/// The form requesting a token with the policy's grant
+fn grant_form(policy: &OAuth2Policy) -> Result<Vec<(&'static str, String)>> {
+    let mut form = vec![("grant_type", policy.grant.clone())];
+    match policy.grant.as_str() {
+        "client_credentials" => {}
+        "password" => {
+            let (Some(username), Some(password)) = (&policy.username, &policy.password) else {
+                return Err(config_error(
+                    "the oauth2 password grant requires 'username' and 'password'",
+                ));
+            };
+            form.push(("username", username.clone()));
+            form.push(("password", password.clone()));
+        }
+        other => {
+            return Err(config_error(&format!(
+                "unsupported oauth2 grant '{other}', expected client_credentials or password"
+            )));
+        }
+    }
+    if !policy.scopes.is_empty() {
+        form.push(("scope", policy.scopes.join(" ")));
+    }
+    if !policy.audiences.is_empty() {
+        form.push(("audience", policy.audiences.join(" ")));
+    }
+    Ok(form)
+}
+
+/// Request a token from the token endpoint, authenticating the client
+async fn request_token(
+    client: &reqwest::Client,
+    token_endpoint: &str,
+    policy: &OAuth2Policy,
+    mut form: Vec<(&'static str, String)>,
+) -> Result<CachedToken> {
+    let mut request = client.post(token_endpoint);
+    match (&policy.client.id, policy.client.authentication.as_deref()) {
+        (Some(id), Some("client_secret_basic")) => {
+            request = request.basic_auth(id, policy.client.secret.as_ref());
+        }
+        (Some(id), _) => {
+            form.push(("client_id", id.clone()));
+            if let Some(secret) = &policy.client.secret {
+                form.push(("client_secret", secret.clone()));
+            }
+        }
+        (None, _) => {}
+    }
+
+    let response = request
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| Error::Failed {
+            kind: ErrorKind::from_request(&e),
+            message: format!("Failed to request an OAuth2 token from {token_endpoint}: {e}"),
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Raised {
+            kind: ErrorKind::from_status(status.as_u16()),
+            error: WorkflowError::new(WorkflowError::AUTHENTICATION, 401, "Authentication Error")
+                .with_detail(format!(
+                    "{token_endpoint} refused to issue a token with status {status}"
+                )),
+        });
+    }
+    let token: TokenResponse = response.json().await.map_err(|e| Error::Execution {
+        message: format!("Invalid OAuth2 token response from {token_endpoint}: {e}"),
+    })?;
+
+    Ok(CachedToken {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        expires_at: token
+            .expires_in
+            .map(|seconds| Instant::now() + Duration::from_secs(seconds)),
+    })
+}
+
+/// `value` without the fields that are null
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, field)| !field.is_null())
+                .map(|(key, field)| (key, without_nulls(field)))
+                .collect(),
+        ),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) => {
+            value
+        }
+    }
+}
+
+fn config_error(message: &str) -> Error {
+    Error::Raised {
+        kind: ErrorKind::Client { status: 400 },
+        error: WorkflowError::new(WorkflowError::CONFIGURATION, 400, "Configuration Error")
+            .with_detail(message.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_grant_form_includes_scopes_and_password() {
+        let policy: OAuth2Policy = serde_json::from_value(json!({
+            "authority": "https://auth.example.com",
+            "grant": "password",
+            "username": "alice",
+            "password": "pa55",
+            "scopes": ["orders:read", "orders:write"]
+        }))
+        .unwrap();
+        let form = grant_form(&policy).unwrap();
+        assert!(form.contains(&("grant_type", "password".to_string())));
+        assert!(form.contains(&("username", "alice".to_string())));
+        assert!(form.contains(&("scope", "orders:read orders:write".to_string())));
+
+        let policy = OAuth2Policy {
+            grant: "device_code".to_string(),
+            ..policy
+        };
+        assert!(grant_form(&policy).is_err());
+    }
+
+    #[test]
+    fn test_expired_tokens_are_not_valid() {
+        let token = |expires_in: u64| CachedToken {
+            access_token: "t".to_string(),
+            refresh_token: None,
+            expires_at: Some(Instant::now() + Duration::from_secs(expires_in)),
+        };
+        assert!(token(3600).is_valid());
+        assert!(!token(5).is_valid());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::context::Context;
+use crate::executor::{
+    Capabilities, Endpoint, Error, ErrorKind, Executor, Result, WorkflowError, parse_params,
+};
+use crate::workflow::ReapedResource;
+use async_trait::async_trait;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, SerializeOptions};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::{Code, Status};
+
+/// Executor for `call: grpc` tasks
+///
+/// The proto file named in `with.proto.endpoint` is compiled with protox, the
+/// `with.arguments` are converted to the method's request message, and the
+/// response message is returned as JSON using the proto field names.
+#[derive(Default)]
+pub struct GrpcExecutor {
+    /// Compiled proto files, keyed by path
+    pools: Mutex<HashMap<String, DescriptorPool>>,
+}
+
+/// Parameters of `call: grpc` tasks
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrpcParams {
+    pub proto: ProtoParams,
+    pub service: GrpcService,
+    /// Name of the unary method to call
+    pub method: String,
+    /// Fields of the request message
+    pub arguments: Option<serde_json::Value>,
+}
+
+/// The proto file describing the service
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProtoParams {
+    pub endpoint: Endpoint,
+}
+
+/// The service to call and where it listens
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrpcService {
+    /// Fully qualified service name, e.g. `calculator.Calculator`
+    pub name: String,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl GrpcExecutor {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile a proto file, reusing the descriptors of earlier calls or of
+    /// the snapshot the engine was started from
+    fn descriptor_pool(&self, proto_path: &str, ctx: &Context) -> Result<DescriptorPool> {
+        if let Some(pool) = self
+            .pools
+            .lock()
+            .ok()
+            .and_then(|pools| pools.get(proto_path).cloned())
+        {
+            return Ok(pool);
+        }
+
+        let file_descriptor_set = match ctx.services.warm.proto_descriptors(proto_path) {
+            Some(file_descriptor_set) => file_descriptor_set,
+            None => {
+                crate::listeners::grpc::compile_proto(proto_path).map_err(|e| Error::Execution {
+                    message: format!("Failed to compile proto file {proto_path}: {e}"),
+                })?
+            }
+        };
+        let pool = DescriptorPool::from_file_descriptor_set(file_descriptor_set).map_err(|e| {
+            Error::Execution {
+                message: format!("Invalid descriptors in proto file {proto_path}: {e}"),
+            }
+        })?;
+
+        if let Ok(mut pools) = self.pools.lock() {
+            pools.insert(proto_path.to_string(), pool.clone());
+        }
+        Ok(pool)
+    }
+}
+
+#[async_trait]
+impl Executor for GrpcExecutor {
+    async fn exec(
+        &self,
+        task_name: &str,
+        params: &serde_json::Value,
+        ctx: &Context,
+        _streamer: Option<crate::task_output::TaskOutputStreamer>,
+        cancel: &CancellationToken,
+    ) -> Result<serde_json::Value> {
+        let params: GrpcParams = parse_params("grpc", params)?;
+        let proto_endpoint = params.proto.endpoint.uri();
+        let proto_path = proto_endpoint
+            .strip_prefix("file://")
+            .unwrap_or(proto_endpoint);
+        let service_name = params.service.name.as_str();
+        let host = params.service.host.as_str();
+        let port = params.service.port;
+        let method_name = params.method.as_str();
+        let arguments = params.arguments.unwrap_or(serde_json::json!({}));
+
+        let pool = self.descriptor_pool(proto_path, ctx)?;
+        let service = pool
+            .get_service_by_name(service_name)
+            .ok_or_else(|| Error::Execution {
+                message: format!("Service {service_name} not found in {proto_path}"),
+            })?;
+        let method = service
+            .methods()
+            .find(|m| m.name() == method_name)
+            .ok_or_else(|| Error::Execution {
+                message: format!("Method {method_name} not found in service {service_name}"),
+            })?;
+        if method.is_client_streaming() || method.is_server_streaming() {
+            return Err(Error::Execution {
+                message: format!("Streaming method {service_name}/{method_name} is not supported"),
+            });
+        }
+
+        // Build the request message from the JSON arguments
+        let request = DynamicMessage::deserialize(method.input(), arguments).map_err(|e| {
+            Error::Execution {
+                message: format!("Arguments do not match {}: {e}", method.input().full_name()),
+            }
+        })?;
+
+        let address = match port {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        let uri = if address.contains("://") {
+            address
+        } else {
+            format!("http://{address}")
+        };
+        let path = format!("/{}/{}", service.full_name(), method.name())
+            .parse::<http::uri::PathAndQuery>()
+            .map_err(|e| Error::Execution {
+                message: format!("Invalid gRPC method path: {e}"),
+            })?;
+
+        println!("  gRPC call: {service_name}/{method_name} at {uri}");
+
+        let codec = DynamicCodec {
+            output: method.output(),
+        };
+        let call = async {
+            let channel = tonic::transport::Endpoint::from_shared(uri.clone())
+                .map_err(|e| Status::invalid_argument(format!("Invalid endpoint {uri}: {e}")))?
+                .connect()
+                .await
+                .map_err(|e| Status::unavailable(format!("Failed to connect to {uri}: {e}")))?;
+            let mut client = tonic::client::Grpc::new(channel);
+            client
+                .ready()
+                .await
+                .map_err(|e| Status::unavailable(format!("Service not ready: {e}")))?;
+            client
+                .unary(tonic::Request::new(request), path, codec)
+                .await
+        };
+        // Dropping the call aborts it if the task is cancelled
+        let response = tokio::select! {
+            response = call => response,
+            () = cancel.cancelled() => {
+                ctx.state.reaper.record(ReapedResource::Request {
+                    target: format!("{uri}/{service_name}/{method_name}"),
+                });
+                return Err(Error::Cancelled {
+                    message: format!("gRPC call {service_name}/{method_name} was aborted"),
+                });
+            }
+        };
+
+        match response {
+            Ok(response) => response
+                .into_inner()
+                .serialize_with_options(
+                    serde_json::value::Serializer,
+                    &SerializeOptions::new().use_proto_field_name(true),
+                )
+                .map_err(|e| Error::Execution {
+                    message: format!("Failed to convert gRPC response to JSON: {e}"),
+                }),
+            Err(status) => {
+                let error = WorkflowError::new(
+                    WorkflowError::COMMUNICATION,
+                    http_status(status.code()),
+                    format!("gRPC {:?} Error", status.code()),
+                )
+                .with_detail(format!(
+                    "{service_name}/{method_name} call to {uri} failed: {}",
+                    status.message()
+                ))
+                .with_instance(format!(
+                    "/do/0/{}/try/0/{}",
+                    ctx.state.current_task.read().await,
+                    task_name
+                ));
+                Err(Error::Raised {
+                    kind: error_kind(status.code()),
+                    error,
+                })
+            }
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: false,
+            supports_cancellation: true,
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// The HTTP status reported in workflow errors for a gRPC status code
+fn http_status(code: Code) -> u16 {
+    match code {
+        Code::Ok => 200,
+        Code::Cancelled => 499,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => 400,
+        Code::Unauthenticated => 401,
+        Code::PermissionDenied => 403,
+        Code::NotFound => 404,
+        Code::AlreadyExists | Code::Aborted => 409,
+        Code::ResourceExhausted => 429,
+        Code::Unimplemented => 501,
+        Code::Unavailable => 503,
+        Code::DeadlineExceeded => 504,
+        Code::Unknown | Code::Internal | Code::DataLoss => 500,
+    }
+}
+
+/// Categorize a failed call by its status code
+fn error_kind(code: Code) -> ErrorKind {
+    match code {
+        Code::Unavailable => ErrorKind::Network,
+        Code::DeadlineExceeded => ErrorKind::Timeout,
+        Code::Ok
+        | Code::Cancelled
+        | Code::InvalidArgument
+        | Code::FailedPrecondition
+        | Code::OutOfRange
+        | Code::Unauthenticated
+        | Code::PermissionDenied
+        | Code::NotFound
+        | Code::AlreadyExists
+        | Code::Aborted
+        | Code::ResourceExhausted
+        | Code::Unimplemented
+        | Code::Unknown
+        | Code::Internal
+        | Code::DataLoss => ErrorKind::from_status(http_status(code)),
+    }
+}
+
+/// Codec sending and receiving `DynamicMessage`s
+struct DynamicCodec {
+    output: MessageDescriptor,
+}
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.output.clone())
+    }
+}
+
+struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(
+        &mut self,
+        item: Self::Item,
+        dst: &mut EncodeBuf<'_>,
+    ) -> std::result::Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|e| Status::internal(format!("Failed to encode request: {e}")))
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(
+        &mut self,
+        src: &mut DecodeBuf<'_>,
+    ) -> std::result::Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|e| Status::internal(format!("Failed to decode response: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    const CALCULATOR_PROTO: &str = "tests/fixtures/listeners/specs/calculator.proto";
+
+    #[test]
+    fn test_descriptor_pool_is_reused() {
+        let executor = GrpcExecutor::new();
+        let pool = executor.descriptor_pool(CALCULATOR_PROTO).unwrap();
+        assert!(pool.get_service_by_name("calculator.Calculator").is_some());
+        assert_eq!(executor.pools.lock().unwrap().len(), 1);
+        executor.descriptor_pool(CALCULATOR_PROTO).unwrap();
+        assert_eq!(executor.pools.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_arguments_become_request_message() {
+        let pool = GrpcExecutor::new()
+            .descriptor_pool(CALCULATOR_PROTO)
+            .unwrap();
+        let input = pool.get_message_by_name("calculator.AddRequest").unwrap();
+        let request =
+            DynamicMessage::deserialize(input.clone(), serde_json::json!({ "a": 2, "b": 3 }))
+                .unwrap();
+        let json = request
+            .serialize_with_options(
+                serde_json::value::Serializer,
+                &SerializeOptions::new().use_proto_field_name(true),
+            )
+            .unwrap();
+        assert_eq!(json, serde_json::json!({ "a": 2, "b": 3 }));
+        assert!(DynamicMessage::deserialize(input, serde_json::json!({ "c": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_http_status() {
+        assert_eq!(http_status(Code::NotFound), 404);
+        assert_eq!(http_status(Code::Unavailable), 503);
+        assert_eq!(http_status(Code::Internal), 500);
+    }
+}
@@ -0,0 +1,340 @@
+//! HTTP caching of GET responses for the REST and OpenAPI executors
+//!
+//! Responses that carry validators (`ETag`, `Last-Modified`) or a freshness
+//! lifetime (`Cache-Control: max-age`, `Expires`) are kept in the engine's
+//! cache provider, below the task cache. A response that is still fresh
+//! answers the next GET of the same URL without a request; a stale one is
+//! revalidated with `If-None-Match`/`If-Modified-Since`, and a
+//! `304 Not Modified` reply reuses it.
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::header::{
+    AGE, AUTHORIZATION, CACHE_CONTROL, ETAG, EXPIRES, HeaderMap, HeaderName, HeaderValue,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, VARY,
+};
+use reqwest::{Client, Method, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{CacheEntry, CacheProvider, compute_cache_key};
+
+/// A response read in full
+#[derive(Debug, Clone)]
+pub(crate) struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Read the body of a response
+    pub(crate) async fn read(response: reqwest::Response) -> reqwest::Result<Self> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+        Ok(Self {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// A response kept for later GETs of its URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    /// Until when the response can be used without asking the server
+    fresh_until: Option<DateTime<Utc>>,
+}
+
+impl StoredResponse {
+    fn header(&self, name: &HeaderName) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name.as_str()))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn to_response(&self) -> HttpResponse {
+        let headers = self
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect();
+        HttpResponse {
+            status: StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK),
+            headers,
+            body: self.body.clone(),
+        }
+    }
+}
+
+/// Send a request, answering or revalidating GETs from the HTTP cache
+///
+/// Requests other than GET are sent as they are. The cache is an
+/// optimization: failures to read or write it are logged, and the request
+/// goes to the server.
+pub(crate) async fn send(
+    client: &Client,
+    mut request: Request,
+    cache: &dyn CacheProvider,
+) -> reqwest::Result<HttpResponse> {
+    if request.method() != Method::GET {
+        return HttpResponse::read(client.execute(request).await?).await;
+    }
+
+    let key = cache_key(&request);
+    let stored = match cache.get(&key).await {
+        Ok(entry) => entry.and_then(|entry| serde_json::from_value(entry.output).ok()),
+        Err(e) => {
+            tracing::warn!("Failed to read HTTP cache for {}: {}", request.url(), e);
+            None
+        }
+    };
+
+    if let Some(stored) = &stored {
+        if stored.fresh_until.is_some_and(|until| until > Utc::now()) {
+            tracing::debug!("Using fresh cached response for {}", request.url());
+            return Ok(stored.to_response());
+        }
+        add_validators(&mut request, stored);
+    }
+
+    let url = request.url().to_string();
+    let response = HttpResponse::read(client.execute(request).await?).await?;
+
+    let (response, to_store) = match stored {
+        Some(mut stored) if response.status == StatusCode::NOT_MODIFIED => {
+            tracing::debug!("Cached response for {url} was not modified");
+            // The 304 carries updated caching headers for the stored response
+            for name in [CACHE_CONTROL, EXPIRES, ETAG, LAST_MODIFIED] {
+                if let Some(value) = response.headers.get(&name).and_then(|v| v.to_str().ok()) {
+                    stored
+                        .headers
+                        .retain(|(key, _)| !key.eq_ignore_ascii_case(name.as_str()));
+                    stored
+                        .headers
+                        .push((name.as_str().to_string(), value.to_string()));
+                }
+            }
+            let revalidated = stored.to_response();
+            stored.fresh_until = fresh_until(&revalidated.headers);
+            (revalidated, Some(stored))
+        }
+        Some(_) | None => {
+            let to_store = storable(&response).then(|| StoredResponse {
+                status: response.status.as_u16(),
+                headers: response
+                    .headers
+                    .iter()
+                    // The age is only known when a response is received
+                    .filter(|(name, _)| **name != AGE)
+                    .filter_map(|(name, value)| {
+                        Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+                    })
+                    .collect(),
+                body: response.body.clone(),
+                fresh_until: fresh_until(&response.headers),
+            });
+            (response, to_store)
+        }
+    };
+
+    if let Some(stored) = to_store {
+        let entry = serde_json::to_value(&stored).map(|output| CacheEntry {
+            key,
+            inputs: serde_json::json!({ "method": "GET", "uri": url }),
+            output,
+            timestamp: Utc::now(),
+            // Stale responses are kept to be revalidated
+            expires_at: None,
+        });
+        let result = match entry {
+            Ok(entry) => cache.set(entry).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to write HTTP cache for {url}: {e}");
+        }
+    }
+    Ok(response)
+}
+
+/// Key of the cached response to a request
+///
+/// Responses to requests with different credentials are kept apart; the key
+/// is a hash, so the credentials are not stored.
+fn cache_key(request: &Request) -> String {
+    let authorization = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    compute_cache_key(
+        "http",
+        &serde_json::json!({
+            "method": "GET",
+            "uri": request.url().as_str(),
+            "authorization": authorization,
+        }),
+    )
+}
+
+/// Make the request conditional on the stored response having changed
+fn add_validators(request: &mut Request, stored: &StoredResponse) {
+    let validators = [
+        (IF_NONE_MATCH, stored.header(&ETAG)),
+        (IF_MODIFIED_SINCE, stored.header(&LAST_MODIFIED)),
+    ];
+    for (name, value) in validators {
+        if let Some(value) = value.and_then(|value| HeaderValue::from_str(value).ok()) {
+            request.headers_mut().insert(name, value);
+        }
+    }
+}
+
+/// Whether a response can be stored, following its `Cache-Control` and `Vary`
+fn storable(response: &HttpResponse) -> bool {
+    if response.status != StatusCode::OK {
+        return false;
+    }
+    let directives = cache_control(&response.headers);
+    if directives.iter().any(|(name, _)| name == "no-store") {
+        return false;
+    }
+    if response
+        .headers
+        .get(VARY)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|vary| vary.trim() == "*")
+    {
+        return false;
+    }
+    // Without a validator or a lifetime, the response could never be reused
+    response.headers.contains_key(ETAG)
+        || response.headers.contains_key(LAST_MODIFIED)
+        || fresh_until(&response.headers).is_some()
+}
+
+/// Until when a response can be used without revalidation, if at all
+fn fresh_until(headers: &HeaderMap) -> Option<DateTime<Utc>> {
+    let directives = cache_control(headers);
+    if directives.iter().any(|(name, _)| name == "no-cache") {
+        return None;
+    }
+
+    let now = Utc::now();
+    if let Some(max_age) = directives
+        .iter()
+        .find(|(name, _)| name == "max-age")
+        .and_then(|(_, value)| value.as_deref()?.parse::<i64>().ok())
+    {
+        // Time the response already spent in caches along the way
+        let age = headers
+            .get(AGE)
+            .and_then(|value| value.to_str().ok()?.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        // Lifetimes beyond a century are as good as forever
+        let lifetime = max_age.saturating_sub(age).min(i64::from(u32::MAX));
+        return Some(now + Duration::seconds(lifetime));
+    }
+
+    headers
+        .get(EXPIRES)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|expires| DateTime::parse_from_rfc2822(expires).ok())
+        .map(|expires| expires.with_timezone(&Utc))
+}
+
+/// The directives of the `Cache-Control` headers, with their values
+fn cache_control(headers: &HeaderMap) -> Vec<(String, Option<String>)> {
+    headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name, Some(value.trim().trim_matches('"').to_string())),
+                None => (directive, None),
+            };
+            (name.trim().to_ascii_lowercase(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_max_age_sets_freshness() {
+        let until = fresh_until(&headers(&[("cache-control", "public, max-age=60")])).unwrap();
+        let remaining = until - Utc::now();
+        assert!(remaining > Duration::seconds(55) && remaining <= Duration::seconds(60));
+    }
+
+    #[test]
+    fn test_age_shortens_freshness() {
+        let until =
+            fresh_until(&headers(&[("cache-control", "max-age=60"), ("age", "50")])).unwrap();
+        assert!(until - Utc::now() <= Duration::seconds(10));
+    }
+
+    #[test]
+    fn test_no_cache_always_revalidates() {
+        assert!(fresh_until(&headers(&[("cache-control", "no-cache, max-age=60")])).is_none());
+    }
+
+    #[test]
+    fn test_expires_sets_freshness() {
+        let until = fresh_until(&headers(&[("expires", "Wed, 21 Oct 2099 07:28:00 GMT")]));
+        assert_eq!(
+            until.unwrap().to_rfc3339(),
+            "2099-10-21T07:28:00+00:00".to_string()
+        );
+    }
+
+    #[test]
+    fn test_storable_responses() {
+        let response = |status: StatusCode, pairs: &[(&'static str, &'static str)]| HttpResponse {
+            status,
+            headers: headers(pairs),
+            body: String::new(),
+        };
+        assert!(storable(&response(StatusCode::OK, &[("etag", "\"v1\"")])));
+        assert!(!storable(&response(StatusCode::OK, &[])));
+        assert!(!storable(&response(
+            StatusCode::OK,
+            &[("etag", "\"v1\""), ("cache-control", "no-store")]
+        )));
+        assert!(!storable(&response(
+            StatusCode::OK,
+            &[("etag", "\"v1\""), ("vary", "*")]
+        )));
+        assert!(!storable(&response(
+            StatusCode::NOT_FOUND,
+            &[("etag", "\"v1\"")]
+        )));
+    }
+}
@@ -1,9 +1,157 @@
+#[cfg(feature = "mqtt")]
+mod asyncapi;
+mod auth;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod http_cache;
+mod native;
 mod node;
 mod openapi;
 mod python;
 mod rest;
+#[cfg(feature = "wasm")]
+mod wasm;
 
+#[cfg(feature = "mqtt")]
+pub use asyncapi::{AsyncApiExecutor, AsyncApiParams};
+#[cfg(feature = "grpc")]
+pub use grpc::{GrpcExecutor, GrpcParams};
+pub use native::{NativeFunction, NativeFunctionExecutor, NativeParams};
 pub use node::NodeExecutor as TypeScriptExecutor;
-pub use openapi::OpenApiExecutor;
+pub(crate) use openapi::parse_openapi_document;
+pub use openapi::{OpenApiExecutor, OpenApiParams};
 pub use python::PythonExtExecutor as PythonExecutor;
-pub use rest::RestExecutor;
+pub use rest::{HttpParams, RestExecutor};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmExecutor, WasmModule, WasmParams};
+
+use crate::context::ExecutionServices;
+use crate::executor::{Error, ErrorKind, Health, Result, validate_params};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Parameters of the script executors, built by `run: script` tasks
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptParams {
+    /// Source code of the script (`code` is accepted for compatibility)
+    #[serde(alias = "code")]
+    pub script: String,
+    pub stdin: Option<String>,
+    /// Command-line arguments, as evaluated from the task
+    pub arguments: Option<Vec<serde_json::Value>>,
+    pub environment: Option<HashMap<String, String>>,
+    /// Start from an empty environment instead of inheriting this process's
+    #[serde(default)]
+    pub clear_environment: bool,
+}
+
+impl ScriptParams {
+    /// The arguments passed on the command line: the string arguments, in order
+    #[must_use]
+    pub fn command_line(&self) -> Option<Vec<String>> {
+        self.arguments.as_ref().map(|arguments| {
+            arguments
+                .iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+    }
+}
+
+/// Check the `with` parameters of a call against the executor's parameter type
+///
+/// Call types without typed parameters, such as custom functions, are not checked.
+///
+/// # Errors
+///
+/// Returns an error if the parameters cannot be used by the executor
+pub fn validate_call_params(call_type: &str, params: &serde_json::Value) -> Result<()> {
+    match call_type {
+        "http" | "rest" => validate_params::<HttpParams>(call_type, params),
+        "openapi" => validate_params::<OpenApiParams>(call_type, params),
+        #[cfg(feature = "grpc")]
+        "grpc" => validate_params::<GrpcParams>(call_type, params),
+        #[cfg(feature = "mqtt")]
+        "asyncapi" => validate_params::<AsyncApiParams>(call_type, params),
+        #[cfg(feature = "wasm")]
+        "wasm" => validate_params::<WasmParams>(call_type, params),
+        "native" => validate_params::<NativeParams>(call_type, params),
+        _ => Ok(()),
+    }
+}
+
+/// The cargo feature providing the executor of `call_type`, if this build
+/// was compiled without it
+#[must_use]
+pub fn disabled_feature(call_type: &str) -> Option<&'static str> {
+    match call_type {
+        "grpc" if cfg!(not(feature = "grpc")) => Some("grpc"),
+        "asyncapi" if cfg!(not(feature = "mqtt")) => Some("mqtt"),
+        "wasm" if cfg!(not(feature = "wasm")) => Some("wasm"),
+        _ => None,
+    }
+}
+
+/// Find the version of an interpreter by running it with `--version`
+pub(crate) async fn probe_interpreter(path: &str) -> Health {
+    let output = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            // Older interpreters print their version to stderr
+            let version = [&output.stdout, &output.stderr]
+                .into_iter()
+                .map(|stream| String::from_utf8_lossy(stream).trim().to_string())
+                .find(|text| !text.is_empty());
+            Health {
+                version,
+                missing: Vec::new(),
+            }
+        }
+        Ok(output) => Health {
+            version: None,
+            missing: vec![format!("'{path} --version' exited with {}", output.status)],
+        },
+        Err(e) => Health {
+            version: None,
+            missing: vec![format!("'{path}' could not be run: {e}")],
+        },
+    }
+}
+
+/// The URL a call to `url` is sent to, e.g. the mock server answering it
+///
+/// # Errors
+///
+/// Returns an error if only mocked calls may be sent and no mock answers `url`
+pub(crate) fn route(services: &ExecutionServices, url: &str) -> Result<String> {
+    services.mocks.try_route(url).map_err(|e| Error::Failed {
+        kind: ErrorKind::Infrastructure,
+        message: e.to_string(),
+    })
+}
+
+/// Fail with the dependencies a script executor is missing
+///
+/// # Errors
+///
+/// Returns an error naming the missing dependencies unless `health` is ready
+pub(crate) fn ensure_ready(language: &str, health: &Health) -> Result<()> {
+    if health.is_ready() {
+        return Ok(());
+    }
+    Err(Error::Failed {
+        kind: ErrorKind::Infrastructure,
+        message: format!(
+            "{language} executor is unavailable: {}. Run `jackdaw doctor` to check executor dependencies",
+            health.missing.join(", ")
+        ),
+    })
+}
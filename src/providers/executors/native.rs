@@ -0,0 +1,112 @@
+//! Executor for Rust functions registered by library users
+//!
+//! Functions registered with
+//! [`DurableEngine::register_function`](crate::durableengine::DurableEngine::register_function)
+//! run in-process when a task calls them by name (`call: fraud-check`), with
+//! no process to spawn. A function receives the call's `with` arguments, or
+//! the task input if there are none, and its output is the task's output.
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio_util::sync::CancellationToken;
+
+use crate::context::Context;
+use crate::executor::{Capabilities, Error, ErrorKind, Executor, Result, parse_params};
+use crate::output;
+
+/// A registered function: its input to its output, or an error message
+pub type NativeFunction = Arc<
+    dyn Fn(serde_json::Value) -> BoxFuture<'static, std::result::Result<serde_json::Value, String>>
+        + Send
+        + Sync,
+>;
+
+/// Executor for `call: native` tasks, and calls of registered functions by name
+#[derive(Default)]
+pub struct NativeFunctionExecutor {
+    functions: RwLock<HashMap<String, NativeFunction>>,
+}
+
+/// Parameters of `call: native` tasks
+#[derive(Debug, Clone, Deserialize)]
+pub struct NativeParams {
+    /// Name the function was registered under
+    pub function: String,
+    /// Input of the function; the task input if omitted
+    pub arguments: Option<serde_json::Value>,
+}
+
+impl NativeFunctionExecutor {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a function, replacing any registered under the same name
+    pub fn register(&self, name: impl Into<String>, function: NativeFunction) {
+        if let Ok(mut functions) = self.functions.write() {
+            functions.insert(name.into(), function);
+        }
+    }
+
+    /// Whether a function is registered under `name`
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions
+            .read()
+            .is_ok_and(|functions| functions.contains_key(name))
+    }
+
+    fn get(&self, name: &str) -> Option<NativeFunction> {
+        self.functions
+            .read()
+            .ok()
+            .and_then(|functions| functions.get(name).cloned())
+    }
+}
+
+#[async_trait]
+impl Executor for NativeFunctionExecutor {
+    async fn exec(
+        &self,
+        task_name: &str,
+        params: &serde_json::Value,
+        ctx: &Context,
+        _streamer: Option<crate::task_output::TaskOutputStreamer>,
+        cancel: &CancellationToken,
+    ) -> Result<serde_json::Value> {
+        let params: NativeParams = parse_params("native", params)?;
+        let function = self.get(&params.function).ok_or_else(|| Error::Execution {
+            message: format!("No function registered as {}", params.function),
+        })?;
+
+        let input = match params.arguments {
+            Some(arguments) => arguments,
+            None => output::filter_internal_fields(&ctx.view_task_input().await),
+        };
+
+        tokio::select! {
+            result = function(input) => result.map_err(|message| Error::Failed {
+                kind: ErrorKind::Script,
+                message: format!("Function {} of {task_name} failed: {message}", params.function),
+            }),
+            () = cancel.cancelled() => Err(Error::Cancelled {
+                message: format!("Function {} was stopped", params.function),
+            }),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: false,
+            supports_cancellation: true,
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -10,19 +10,28 @@
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::process::Stdio;
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::OnceCell;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    context::Context,
-    executor::{Error, Executor, Result},
+    context::{Context, Reaper},
+    executor::{Capabilities, Error, ErrorKind, Executor, Health, Result, parse_params},
+    process::{self, ProcessGroup},
     task_output::TaskOutputStreamer,
 };
 
+use super::{ScriptParams, ensure_ready, probe_interpreter};
+
 /// Node.js executor that uses the system `node` binary
+#[derive(Clone)]
 pub struct NodeExecutor {
     /// Path to the Node.js binary (defaults to "node")
     node_path: String,
+    /// Result of checking the interpreter, shared by clones
+    health: Arc<OnceCell<Health>>,
 }
 
 impl Default for NodeExecutor {
@@ -35,16 +44,16 @@ impl NodeExecutor {
     /// Create a new external Node.js executor with default node binary
     #[must_use]
     pub fn new() -> Self {
-        Self {
-            node_path: "node".to_string(),
-        }
+        Self::with_node_path("node".to_string())
     }
 
     /// Create a new external Node.js executor with a custom Node.js binary path
     #[must_use]
-    #[allow(dead_code)]
     pub fn with_node_path(node_path: String) -> Self {
-        Self { node_path }
+        Self {
+            node_path,
+            health: Arc::new(OnceCell::new()),
+        }
     }
 
     /// Execute a JavaScript function (used by listeners)
@@ -114,13 +123,25 @@ impl NodeExecutor {
 
         // Execute synchronously by spawning a blocking thread with its own runtime
         // This works even when called from within an async context
-        let node_path = self.node_path.clone();
+        let executor = self.clone();
         std::thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().map_err(|e| Error::Execution {
                 message: format!("Failed to create tokio runtime: {e}"),
             })?;
-            let executor = NodeExecutor { node_path };
-            rt.block_on(async { executor.exec_script(&script, None, None, None, None).await })
+            rt.block_on(async {
+                executor
+                    .exec_script(
+                        &script,
+                        None,
+                        None,
+                        None,
+                        false,
+                        None,
+                        &CancellationToken::new(),
+                        &Reaper::default(),
+                    )
+                    .await
+            })
         })
         .join()
         .map_err(|_| Error::Execution {
@@ -135,7 +156,10 @@ impl NodeExecutor {
     /// * `stdin` - Optional stdin data to pass to the script
     /// * `arguments` - Optional command-line arguments
     /// * `environment` - Optional environment variables
+    /// * `clear_env` - Start from an empty environment instead of inheriting this process's
     /// * `streamer` - Optional output streamer for real-time output
+    /// * `cancel` - Kills the script when it fires
+    /// * `reaper` - Records the killed script
     ///
     /// # Errors
     /// Returns an error if:
@@ -148,7 +172,10 @@ impl NodeExecutor {
         stdin: Option<&str>,
         arguments: Option<&[String]>,
         environment: Option<&HashMap<String, String>>,
+        clear_env: bool,
         streamer: Option<TaskOutputStreamer>,
+        cancel: &CancellationToken,
+        reaper: &Reaper,
     ) -> Result<serde_json::Value> {
         // When using -e, Node.js doesn't include a script path in argv,
         // so argv[1] is the first argument instead of argv[2].
@@ -160,8 +187,14 @@ impl NodeExecutor {
             script.to_string()
         };
 
+        // A missing interpreter fails every script the same way, without
+        // spawning it each time
+        ensure_ready("Node.js", &self.health_check().await)?;
+
         // Build command
         let mut cmd = Command::new(&self.node_path);
+        // The script is killed if the task is cancelled and its future dropped
+        cmd.kill_on_drop(true);
         cmd.arg("--input-type=module") // Treat input as ESM module
             .arg("-e") // Execute code from command line
             .arg(&wrapped_script);
@@ -176,16 +209,23 @@ impl NodeExecutor {
             .stderr(Stdio::piped());
 
         // Set environment variables if provided
+        if clear_env {
+            cmd.env_clear();
+        }
         if let Some(env) = environment {
             for (key, value) in env {
                 cmd.env(key, value);
             }
         }
 
-        // Spawn the process
-        let mut child = cmd.spawn().map_err(|e| Error::Execution {
+        // Spawn the process in a process group of its own, killed on error
+        // paths along with anything the script started
+        process::isolate(&mut cmd);
+        let mut child = cmd.spawn().map_err(|e| Error::Failed {
+            kind: ErrorKind::Infrastructure,
             message: format!("Failed to spawn Node.js process: {e}"),
         })?;
+        let group = ProcessGroup::new(&child, self.node_path.as_str());
 
         // Handle stdin if provided
         if let Some(stdin_data) = stdin {
@@ -243,10 +283,20 @@ impl NodeExecutor {
             })
         };
 
-        // Wait for process to complete
-        let status = child.wait().await.map_err(|e| Error::Execution {
-            message: format!("Failed to wait for Node.js process: {e}"),
-        })?;
+        // Wait for process to complete, killing it if the task is cancelled
+        let status = tokio::select! {
+            status = child.wait() => status.map_err(|e| Error::Execution {
+                message: format!("Failed to wait for Node.js process: {e}"),
+            })?,
+            () = cancel.cancelled() => {
+                reaper.record(group.kill());
+                let _ = child.kill().await;
+                return Err(Error::Cancelled {
+                    message: "Node.js script was killed".to_string(),
+                });
+            }
+        };
+        group.release();
 
         // Collect output
         let stdout_lines = stdout_task.await.map_err(|e| Error::Execution {
@@ -264,7 +314,8 @@ impl NodeExecutor {
 
         // Check exit code
         if exit_code != 0 {
-            return Err(Error::Execution {
+            return Err(Error::Failed {
+                kind: ErrorKind::Script,
                 message: format!(
                     "Node.js script failed with exit code {exit_code}\nstdout: {stdout_str}\nstderr: {stderr_str}"
                 ),
@@ -287,48 +338,44 @@ impl Executor for NodeExecutor {
         &self,
         _task_name: &str,
         params: &serde_json::Value,
-        _ctx: &Context,
+        ctx: &Context,
         streamer: Option<TaskOutputStreamer>,
+        cancel: &CancellationToken,
     ) -> Result<serde_json::Value> {
-        // Support both 'script' (from run task) and 'code' (legacy) parameters
-        let script = params
-            .get("script")
-            .or_else(|| params.get("code"))
-            .and_then(|c| c.as_str())
-            .ok_or(Error::Execution {
-                message: "Missing 'script' or 'code' parameter for JavaScript script execution"
-                    .to_string(),
-            })?;
-
-        let stdin = params.get("stdin").and_then(|s| s.as_str());
-
-        let arguments: Option<Vec<String>> = params.get("arguments").and_then(|args| {
-            args.as_array().map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-        });
-
-        let environment: Option<HashMap<String, String>> =
-            params.get("environment").and_then(|env| {
-                env.as_object().map(|obj| {
-                    obj.iter()
-                        .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
-                        .collect()
-                })
-            });
+        let params: ScriptParams = parse_params("javascript", params)?;
+        let arguments = params.command_line();
 
         self.exec_script(
-            script,
-            stdin,
+            &params.script,
+            params.stdin.as_deref(),
             arguments.as_deref(),
-            environment.as_ref(),
+            params.environment.as_ref(),
+            params.clear_environment,
             streamer,
+            cancel,
+            &ctx.state.reaper,
         )
         .await
     }
 
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: true,
+            supports_cancellation: true,
+        }
+    }
+
+    async fn health_check(&self) -> Health {
+        self.health
+            .get_or_init(|| probe_interpreter(&self.node_path))
+            .await
+            .clone()
+    }
+
+    fn restore_health(&self, health: Health) {
+        let _ = self.health.set(health);
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -1,11 +1,59 @@
-use crate::context::Context;
-use crate::executor::{Error, Executor, Result};
+use crate::context::{Context, ExecutionServices};
+use crate::executor::{
+    Capabilities, Endpoint, Error, ErrorKind, Executor, Result, WorkflowError, parse_params,
+};
+use crate::text::{MAX_EXCERPT_LEN, truncate};
+use crate::workflow::ReapedResource;
 use async_trait::async_trait;
 use openapiv3::{OpenAPI, ParameterKind, ReferenceOr, VersionedOpenAPI};
 use reqwest::Url;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+use super::auth::{self, Credentials};
+use super::http_cache::{self, HttpResponse};
 
 pub struct OpenApiExecutor(pub reqwest::Client);
 
+/// Parameters of `call: openapi` tasks
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenApiParams {
+    pub document: OpenApiDocument,
+    pub operation_id: String,
+    /// Path, query and body parameters of the operation
+    #[serde(default = "empty_object")]
+    pub parameters: serde_json::Value,
+    /// `content` (default) for the response body, or `response` for the full response
+    #[serde(default = "default_output")]
+    pub output: String,
+    /// Authentication policy the operation is called with
+    pub authentication: Option<serde_json::Value>,
+}
+
+/// The OpenAPI document describing the operation
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenApiDocument {
+    pub endpoint: Endpoint,
+}
+
+/// Parse an OpenAPI or Swagger document, in JSON or YAML
+pub(crate) fn parse_openapi_document(spec_text: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(spec_text)
+        .or_else(|_| serde_yaml::from_str(spec_text))
+        .map_err(|e| Error::Execution {
+            message: format!("Failed to parse spec as JSON or YAML: {e}"),
+        })
+}
+
+fn empty_object() -> serde_json::Value {
+    serde_json::json!({})
+}
+
+fn default_output() -> String {
+    "content".to_string()
+}
+
 #[async_trait]
 impl Executor for OpenApiExecutor {
     async fn exec(
@@ -14,66 +62,90 @@ impl Executor for OpenApiExecutor {
         params: &serde_json::Value,
         ctx: &Context,
         _streamer: Option<crate::task_output::TaskOutputStreamer>,
+        cancel: &CancellationToken,
     ) -> Result<serde_json::Value> {
-        // Extract document endpoint
-        let doc_endpoint = params
-            .get("document")
-            .and_then(|d| d.get("endpoint"))
-            .and_then(|e| e.as_str())
-            .ok_or(Error::Execution {
-                message: "No document endpoint specified".to_string(),
-            })?;
-
-        // Extract operation ID
-        let operation_id =
-            params
-                .get("operationId")
-                .and_then(|o| o.as_str())
-                .ok_or(Error::Execution {
-                    message: "No operationId specified".to_string(),
-                })?;
-
-        // Extract and evaluate parameters
-        let parameters_raw = params
-            .get("parameters")
-            .cloned()
-            .unwrap_or(serde_json::json!({}));
-        let parameters = evaluate_parameters(&parameters_raw, ctx).await?;
+        let params: OpenApiParams = parse_params("openapi", params)?;
+
+        // Dropping the call aborts its requests if the task is cancelled
+        tokio::select! {
+            result = self.call(task_name, &params, ctx) => result,
+            () = cancel.cancelled() => {
+                ctx.state.reaper.record(ReapedResource::Request {
+                    target: format!("{} {}", params.document.endpoint.uri(), params.operation_id),
+                });
+                Err(Error::Cancelled {
+                    message: format!("OpenAPI call {} was aborted", params.operation_id),
+                })
+            }
+        }
+    }
 
-        // Check for output mode (default is "content")
-        let output_mode = params
-            .get("output")
-            .and_then(|v| v.as_str())
-            .unwrap_or("content");
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: false,
+            supports_cancellation: true,
+        }
+    }
 
-        println!("  OpenAPI call: {operation_id} at {doc_endpoint}");
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
 
-        // Fetch the OpenAPI spec
-        let spec_text = self
+impl OpenApiExecutor {
+    /// Fetch the OpenAPI document, with the credentials of its endpoint if it
+    /// has any
+    async fn fetch_document(
+        &self,
+        document: &OpenApiDocument,
+        ctx: &Context,
+    ) -> Result<serde_json::Value> {
+        let mut request = self
             .0
-            .get(doc_endpoint)
-            .send()
-            .await
-            .map_err(|e| Error::Execution {
-                message: format!("Failed to fetch OpenAPI spec: {e}"),
-            })?
-            .text()
-            .await
-            .map_err(|e| Error::Execution {
-                message: format!("Failed to read spec text: {e}"),
-            })?;
+            .get(super::route(&ctx.services, document.endpoint.uri())?);
+        if let Some(policy) = document.endpoint.authentication() {
+            request = auth::credentials(&self.0, policy, ctx)
+                .await?
+                .apply(request);
+        }
+        let request = request.build();
+        let spec_text = match request {
+            Ok(request) => http_cache::send(&self.0, request, ctx.services.cache.as_ref()).await,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| Error::Execution {
+            message: format!("Failed to fetch OpenAPI spec: {e}"),
+        })?
+        .body;
 
-        println!(
-            "  Fetched spec (first 200 chars): {}",
-            &spec_text.chars().take(200).collect::<String>()
-        );
+        println!("  Fetched spec: {}", truncate(&spec_text, MAX_EXCERPT_LEN));
 
-        // Parse as JSON value first to check version
-        let spec_value: serde_json::Value = serde_json::from_str(&spec_text)
-            .or_else(|_| serde_yaml::from_str(&spec_text))
-            .map_err(|e| Error::Execution {
-                message: format!("Failed to parse spec as JSON or YAML: {e}"),
-            })?;
+        parse_openapi_document(&spec_text)
+    }
+
+    /// Fetch the OpenAPI document and call the operation
+    async fn call(
+        &self,
+        task_name: &str,
+        params: &OpenApiParams,
+        ctx: &Context,
+    ) -> Result<serde_json::Value> {
+        let doc_endpoint = params.document.endpoint.uri();
+        let operation_id = params.operation_id.as_str();
+        let parameters = evaluate_parameters(&params.parameters, ctx).await?;
+        let output_mode = params.output.as_str();
+        let credentials = match &params.authentication {
+            Some(policy) => Some(auth::credentials(&self.0, policy, ctx).await?),
+            None => None,
+        };
+
+        println!("  OpenAPI call: {operation_id} at {doc_endpoint}");
+
+        // Use the spec restored from a snapshot, or fetch it
+        let spec_value = match ctx.services.warm.openapi_document(doc_endpoint) {
+            Some(spec_value) => spec_value.clone(),
+            None => self.fetch_document(&params.document, ctx).await?,
+        };
 
         // Check if it's a Swagger 2.0 spec and convert it manually
         if spec_value
@@ -84,12 +156,14 @@ impl Executor for OpenApiExecutor {
             println!("  Detected Swagger 2.0 spec, converting to OpenAPI 3.x");
             return execute_swagger_v2_spec(
                 &self.0,
+                &ctx.services,
                 task_name,
                 operation_id,
                 &parameters,
                 &spec_value,
                 output_mode,
                 doc_endpoint,
+                credentials.as_ref(),
             )
             .await;
         }
@@ -107,29 +181,29 @@ impl Executor for OpenApiExecutor {
 
         execute_openapi_v3_spec(
             &self.0,
+            &ctx.services,
             task_name,
             operation_id,
             &parameters,
             &spec,
             output_mode,
             doc_endpoint,
+            credentials.as_ref(),
         )
         .await
     }
-
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
 }
 
 async fn execute_swagger_v2_spec(
     client: &reqwest::Client,
+    services: &ExecutionServices,
     task_name: &str,
     operation_id: &str,
     parameters: &serde_json::Value,
     spec_value: &serde_json::Value,
     output_mode: &str,
     doc_endpoint: &str,
+    credentials: Option<&Credentials>,
 ) -> Result<serde_json::Value> {
     // Find operation by operationId in the spec
     let paths = spec_value
@@ -230,80 +304,33 @@ async fn execute_swagger_v2_spec(
     println!("  Request: {} {}", method.to_uppercase(), url);
 
     // Make the HTTP request
-    let response = match method.to_uppercase().as_str() {
-        "GET" => client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::Execution {
-                message: format!("Request failed: {e}"),
-            })?,
-        "POST" => {
-            let body = parameters
-                .get("body")
-                .cloned()
-                .unwrap_or(serde_json::json!({}));
-            client
-                .post(&url)
-                .json(&body)
-                .send()
-                .await
-                .map_err(|e| Error::Execution {
-                    message: format!("Request failed: {e}"),
-                })?
-        }
-        "PUT" => {
-            let body = parameters
-                .get("body")
-                .cloned()
-                .unwrap_or(serde_json::json!({}));
-            client
-                .put(&url)
-                .json(&body)
-                .send()
-                .await
-                .map_err(|e| Error::Execution {
-                    message: format!("Request failed: {e}"),
-                })?
-        }
-        "DELETE" => client
-            .delete(&url)
-            .send()
-            .await
-            .map_err(|e| Error::Execution {
-                message: format!("Request failed: {e}"),
-            })?,
-        _ => {
-            return Err(Error::Execution {
-                message: format!("Unsupported HTTP method: {method}"),
-            });
-        }
-    };
+    let response = send_operation(client, services, method, &url, parameters, credentials).await?;
 
-    let status = response.status();
-    let headers = response.headers().clone();
+    let status = response.status;
+    let headers = response.headers;
 
     println!("  Response status: {status}");
 
     if !status.is_success() {
-        let error_obj = serde_json::json!({
-            "type": "https://serverlessworkflow.io/dsl/errors/types/communication",
-            "status": status.as_u16(),
-            "title": format!("HTTP {} Error", status.as_u16()),
-            "detail": format!("{} request to {} failed with status {}", method.to_uppercase(), url, status),
-            "instance": format!("/do/0/{}", task_name)
-        });
-        return Err(Error::Execution {
-            message: serde_json::to_string(&error_obj).map_err(|e| Error::Execution {
-                message: format!("Failed to serialize error: {e}"),
-            })?,
+        let error = WorkflowError::new(
+            WorkflowError::COMMUNICATION,
+            status.as_u16(),
+            format!("HTTP {} Error", status.as_u16()),
+        )
+        .with_detail(format!(
+            "{} request to {} failed with status {}",
+            method.to_uppercase(),
+            url,
+            status
+        ))
+        .with_instance(format!("/do/0/{task_name}"));
+        return Err(Error::Raised {
+            kind: ErrorKind::from_status(status.as_u16()),
+            error,
         });
     }
 
-    // Get response body
-    let body_text = response.text().await.map_err(|e| Error::Execution {
-        message: format!("Failed to read response body: {e}"),
-    })?;
+    let body_text = response.body;
 
     // Try to parse as JSON
     let content_type = headers
@@ -343,12 +370,14 @@ async fn execute_swagger_v2_spec(
 
 async fn execute_openapi_v3_spec(
     client: &reqwest::Client,
+    services: &ExecutionServices,
     task_name: &str,
     operation_id: &str,
     parameters: &serde_json::Value,
     spec: &OpenAPI,
     output_mode: &str,
     doc_endpoint: &str,
+    credentials: Option<&Credentials>,
 ) -> Result<serde_json::Value> {
     // Find operation by operationId
     let (path_pattern, method, operation) =
@@ -410,80 +439,33 @@ async fn execute_openapi_v3_spec(
     println!("  Request: {} {}", method.to_uppercase(), url);
 
     // Make the HTTP request
-    let response = match method.to_uppercase().as_str() {
-        "GET" => client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| Error::Execution {
-                message: format!("Request failed: {e}"),
-            })?,
-        "POST" => {
-            let body = parameters
-                .get("body")
-                .cloned()
-                .unwrap_or(serde_json::json!({}));
-            client
-                .post(&url)
-                .json(&body)
-                .send()
-                .await
-                .map_err(|e| Error::Execution {
-                    message: format!("Request failed: {e}"),
-                })?
-        }
-        "PUT" => {
-            let body = parameters
-                .get("body")
-                .cloned()
-                .unwrap_or(serde_json::json!({}));
-            client
-                .put(&url)
-                .json(&body)
-                .send()
-                .await
-                .map_err(|e| Error::Execution {
-                    message: format!("Request failed: {e}"),
-                })?
-        }
-        "DELETE" => client
-            .delete(&url)
-            .send()
-            .await
-            .map_err(|e| Error::Execution {
-                message: format!("Request failed: {e}"),
-            })?,
-        _ => {
-            return Err(Error::Execution {
-                message: format!("Unsupported HTTP method: {method}"),
-            });
-        }
-    };
+    let response = send_operation(client, services, method, &url, parameters, credentials).await?;
 
-    let status = response.status();
-    let headers = response.headers().clone();
+    let status = response.status;
+    let headers = response.headers;
 
     println!("  Response status: {status}");
 
     if !status.is_success() {
-        let error_obj = serde_json::json!({
-            "type": "https://serverlessworkflow.io/dsl/errors/types/communication",
-            "status": status.as_u16(),
-            "title": format!("HTTP {} Error", status.as_u16()),
-            "detail": format!("{} request to {} failed with status {}", method.to_uppercase(), url, status),
-            "instance": format!("/do/0/{}", task_name)
-        });
-        return Err(Error::Execution {
-            message: serde_json::to_string(&error_obj).map_err(|e| Error::Execution {
-                message: format!("Failed to serialize error: {e}"),
-            })?,
+        let error = WorkflowError::new(
+            WorkflowError::COMMUNICATION,
+            status.as_u16(),
+            format!("HTTP {} Error", status.as_u16()),
+        )
+        .with_detail(format!(
+            "{} request to {} failed with status {}",
+            method.to_uppercase(),
+            url,
+            status
+        ))
+        .with_instance(format!("/do/0/{task_name}"));
+        return Err(Error::Raised {
+            kind: ErrorKind::from_status(status.as_u16()),
+            error,
         });
     }
 
-    // Get response body
-    let body_text = response.text().await.map_err(|e| Error::Execution {
-        message: format!("Failed to read response body: {e}"),
-    })?;
+    let body_text = response.body;
 
     // Try to parse as JSON
     let content_type = headers
@@ -521,6 +503,48 @@ async fn execute_openapi_v3_spec(
     Ok(result)
 }
 
+/// Send the request of an operation through the mock routes and the HTTP cache
+async fn send_operation(
+    client: &reqwest::Client,
+    services: &ExecutionServices,
+    method: &str,
+    url: &str,
+    parameters: &serde_json::Value,
+    credentials: Option<&Credentials>,
+) -> Result<HttpResponse> {
+    let body = || {
+        parameters
+            .get("body")
+            .cloned()
+            .unwrap_or(serde_json::json!({}))
+    };
+    let url = super::route(services, url)?;
+    let mut request = match method.to_uppercase().as_str() {
+        "GET" => client.get(&url),
+        "POST" => client.post(&url).json(&body()),
+        "PUT" => client.put(&url).json(&body()),
+        "DELETE" => client.delete(&url),
+        _ => {
+            return Err(Error::Execution {
+                message: format!("Unsupported HTTP method: {method}"),
+            });
+        }
+    };
+
+    if let Some(credentials) = credentials {
+        request = credentials.apply(request);
+    }
+
+    let response = match request.build() {
+        Ok(request) => http_cache::send(client, request, services.cache.as_ref()).await,
+        Err(e) => Err(e),
+    };
+    response.map_err(|e| Error::Failed {
+        kind: ErrorKind::from_request(&e),
+        message: format!("Request failed: {e}"),
+    })
+}
+
 fn find_operation<'a>(
     spec: &'a OpenAPI,
     operation_id: &str,
@@ -563,10 +587,11 @@ async fn evaluate_parameters(
 ) -> Result<serde_json::Value> {
     if let Some(obj) = parameters.as_object() {
         let mut result = serde_json::Map::new();
-        let current_data = ctx.state.data.read().await.clone();
+        let current_data = ctx.data().await;
 
         for (key, value) in obj {
             let evaluated = crate::expressions::evaluate_value_with_input(
+                ctx.metadata.evaluation,
                 value,
                 &current_data,
                 &ctx.metadata.initial_input,
@@ -10,15 +10,21 @@
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::process::Stdio;
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::OnceCell;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    context::Context,
-    executor::{Error, Executor, Result},
+    context::{Context, Reaper},
+    executor::{Capabilities, Error, ErrorKind, Executor, Health, Result, parse_params},
+    process::{self, ProcessGroup},
     task_output::TaskOutputStreamer,
 };
 
+use super::{ScriptParams, ensure_ready, probe_interpreter};
+
 /// A reference to a Python function for the external executor
 /// This stores the module and function name for later execution
 #[derive(Clone, Debug)]
@@ -28,9 +34,12 @@ pub struct PythonFunctionRef {
 }
 
 /// Python executor that uses the system `python` binary
+#[derive(Clone)]
 pub struct PythonExtExecutor {
     /// Path to the Python binary (defaults to "python3")
     python_path: String,
+    /// Result of checking the interpreter, shared by clones
+    health: Arc<OnceCell<Health>>,
 }
 
 impl Default for PythonExtExecutor {
@@ -43,16 +52,16 @@ impl PythonExtExecutor {
     /// Create a new external Python executor with default python3 binary
     #[must_use]
     pub fn new() -> Self {
-        Self {
-            python_path: "python".to_string(),
-        }
+        Self::with_python_path("python".to_string())
     }
 
     /// Create a new external Python executor with a custom Python binary path
     #[must_use]
-    #[allow(dead_code)]
     pub fn with_python_path(python_path: String) -> Self {
-        Self { python_path }
+        Self {
+            python_path,
+            health: Arc::new(OnceCell::new()),
+        }
     }
 
     /// Load a Python function reference for later execution
@@ -172,13 +181,25 @@ except Exception as e:
 
         // Execute synchronously by spawning a blocking thread with its own runtime
         // This works even when called from within an async context
-        let python_path = self.python_path.clone();
+        let executor = self.clone();
         std::thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().map_err(|e| Error::Execution {
                 message: format!("Failed to create tokio runtime: {e}"),
             })?;
-            let executor = PythonExtExecutor { python_path };
-            rt.block_on(async { executor.exec_script(&script, None, None, None, None).await })
+            rt.block_on(async {
+                executor
+                    .exec_script(
+                        &script,
+                        None,
+                        None,
+                        None,
+                        false,
+                        None,
+                        &CancellationToken::new(),
+                        &Reaper::default(),
+                    )
+                    .await
+            })
         })
         .join()
         .map_err(|_| Error::Execution {
@@ -193,7 +214,10 @@ except Exception as e:
     /// * `stdin` - Optional stdin data to pass to the script
     /// * `arguments` - Optional command-line arguments
     /// * `environment` - Optional environment variables
+    /// * `clear_env` - Start from an empty environment instead of inheriting this process's
     /// * `streamer` - Optional output streamer for real-time output
+    /// * `cancel` - Kills the script when it fires
+    /// * `reaper` - Records the killed script
     ///
     /// # Errors
     /// Returns an error if:
@@ -206,10 +230,19 @@ except Exception as e:
         stdin: Option<&str>,
         arguments: Option<&[String]>,
         environment: Option<&HashMap<String, String>>,
+        clear_env: bool,
         streamer: Option<TaskOutputStreamer>,
+        cancel: &CancellationToken,
+        reaper: &Reaper,
     ) -> Result<serde_json::Value> {
+        // A missing interpreter fails every script the same way, without
+        // spawning it each time
+        ensure_ready("Python", &self.health_check().await)?;
+
         // Build command
         let mut cmd = Command::new(&self.python_path);
+        // The script is killed if the task is cancelled and its future dropped
+        cmd.kill_on_drop(true);
         cmd.arg("-c") // Execute code from command line
             .arg(script)
             .stdin(Stdio::piped())
@@ -222,16 +255,23 @@ except Exception as e:
         }
 
         // Set environment variables if provided
+        if clear_env {
+            cmd.env_clear();
+        }
         if let Some(env) = environment {
             for (key, value) in env {
                 cmd.env(key, value);
             }
         }
 
-        // Spawn the process
-        let mut child = cmd.spawn().map_err(|e| Error::Execution {
+        // Spawn the process in a process group of its own, killed on error
+        // paths along with anything the script started
+        process::isolate(&mut cmd);
+        let mut child = cmd.spawn().map_err(|e| Error::Failed {
+            kind: ErrorKind::Infrastructure,
             message: format!("Failed to spawn Python process: {e}"),
         })?;
+        let group = ProcessGroup::new(&child, self.python_path.as_str());
 
         // Handle stdin if provided
         if let Some(stdin_data) = stdin {
@@ -289,10 +329,20 @@ except Exception as e:
             })
         };
 
-        // Wait for process to complete
-        let status = child.wait().await.map_err(|e| Error::Execution {
-            message: format!("Failed to wait for Python process: {e}"),
-        })?;
+        // Wait for process to complete, killing it if the task is cancelled
+        let status = tokio::select! {
+            status = child.wait() => status.map_err(|e| Error::Execution {
+                message: format!("Failed to wait for Python process: {e}"),
+            })?,
+            () = cancel.cancelled() => {
+                reaper.record(group.kill());
+                let _ = child.kill().await;
+                return Err(Error::Cancelled {
+                    message: "Python script was killed".to_string(),
+                });
+            }
+        };
+        group.release();
 
         // Collect output
         let stdout_lines = stdout_task.await.map_err(|e| Error::Execution {
@@ -310,7 +360,8 @@ except Exception as e:
 
         // Check exit code
         if exit_code != 0 {
-            return Err(Error::Execution {
+            return Err(Error::Failed {
+                kind: ErrorKind::Script,
                 message: format!(
                     "Python script failed with exit code {exit_code}\nstdout: {stdout_str}\nstderr: {stderr_str}"
                 ),
@@ -333,51 +384,48 @@ impl Executor for PythonExtExecutor {
         &self,
         _task_name: &str,
         params: &serde_json::Value,
-        _ctx: &Context,
+        ctx: &Context,
         streamer: Option<TaskOutputStreamer>,
+        cancel: &CancellationToken,
     ) -> Result<serde_json::Value> {
         // For external executor, we only support script execution
         // Module-based calls are not supported as they require the pyo3 embedded interpreter
         // Support both 'script' (from run task) and 'code' (legacy) parameters
 
-        let script = params
-            .get("script")
-            .or_else(|| params.get("code"))
-            .and_then(|c| c.as_str())
-            .ok_or(Error::Execution {
-                message: "Missing 'script' or 'code' parameter for Python script execution"
-                    .to_string(),
-            })?;
-
-        let stdin = params.get("stdin").and_then(|s| s.as_str());
-
-        let arguments: Option<Vec<String>> = params.get("arguments").and_then(|args| {
-            args.as_array().map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-        });
-
-        let environment: Option<HashMap<String, String>> =
-            params.get("environment").and_then(|env| {
-                env.as_object().map(|obj| {
-                    obj.iter()
-                        .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
-                        .collect()
-                })
-            });
+        let params: ScriptParams = parse_params("python", params)?;
+        let arguments = params.command_line();
 
         self.exec_script(
-            script,
-            stdin,
+            &params.script,
+            params.stdin.as_deref(),
             arguments.as_deref(),
-            environment.as_ref(),
+            params.environment.as_ref(),
+            params.clear_environment,
             streamer,
+            cancel,
+            &ctx.state.reaper,
         )
         .await
     }
 
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: true,
+            supports_cancellation: true,
+        }
+    }
+
+    async fn health_check(&self) -> Health {
+        self.health
+            .get_or_init(|| probe_interpreter(&self.python_path))
+            .await
+            .clone()
+    }
+
+    fn restore_health(&self, health: Health) {
+        let _ = self.health.set(health);
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
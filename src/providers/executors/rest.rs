@@ -1,9 +1,45 @@
 use crate::context::Context;
-use crate::executor::{Error, Executor, Result};
+use crate::executor::{
+    Capabilities, Endpoint, Error, ErrorKind, Executor, Result, WorkflowError, parse_params,
+};
+use crate::workflow::ReapedResource;
+
+use super::{auth, http_cache};
 use async_trait::async_trait;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
 
 pub struct RestExecutor(pub reqwest::Client);
 
+/// Parameters of `call: http` tasks
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpParams {
+    /// HTTP method, `get` by default
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub endpoint: Endpoint,
+    /// Request body, sent as JSON for `post`, `put` and `patch`
+    pub body: Option<serde_json::Value>,
+    /// `content` (default) for the response body, or `response` for the full response
+    #[serde(default = "default_output")]
+    pub output: String,
+    /// Whether redirects are followed, `true` by default
+    #[serde(default = "default_redirect")]
+    pub redirect: bool,
+}
+
+fn default_method() -> String {
+    "get".to_string()
+}
+
+fn default_output() -> String {
+    "content".to_string()
+}
+
+fn default_redirect() -> bool {
+    true
+}
+
 #[async_trait]
 impl Executor for RestExecutor {
     async fn exec(
@@ -12,56 +48,16 @@ impl Executor for RestExecutor {
         params: &serde_json::Value,
         ctx: &Context,
         _streamer: Option<crate::task_output::TaskOutputStreamer>,
+        cancel: &CancellationToken,
     ) -> Result<serde_json::Value> {
-        // Extract endpoint - can be a string or an object with 'uri' field
-        let (endpoint_str, auth_config) = if let Some(endpoint_val) = params.get("endpoint") {
-            match endpoint_val {
-                serde_json::Value::String(s) => (s.clone(), None),
-                serde_json::Value::Object(obj) => {
-                    let uri = obj
-                        .get("uri")
-                        .and_then(|v| v.as_str())
-                        .ok_or(Error::Execution {
-                            message: "No 'uri' field in endpoint object".to_string(),
-                        })?
-                        .to_string();
-                    let auth = obj.get("authentication").cloned();
-                    (uri, auth)
-                }
-                serde_json::Value::Null
-                | serde_json::Value::Bool(_)
-                | serde_json::Value::Number(_)
-                | serde_json::Value::Array(_) => {
-                    return Err(Error::Execution {
-                        message: "Invalid endpoint format".to_string(),
-                    });
-                }
-            }
-        } else {
-            return Err(Error::Execution {
-                message: "No endpoint specified".to_string(),
-            });
-        };
+        let params: HttpParams = parse_params("http", params)?;
 
         // Interpolate path parameters from context if needed
-        let endpoint = interpolate_uri(&endpoint_str, ctx).await?;
-
-        let method = params
-            .get("method")
-            .and_then(|v| v.as_str())
-            .unwrap_or("get");
-
-        // Check for output mode (default is "content")
-        let output_mode = params
-            .get("output")
-            .and_then(|v| v.as_str())
-            .unwrap_or("content");
-
-        // Check for redirect setting (default is true - follow redirects)
-        let follow_redirects = params
-            .get("redirect")
-            .and_then(serde_json::Value::as_bool)
-            .unwrap_or(true);
+        let endpoint = interpolate_uri(params.endpoint.uri(), ctx).await?;
+        let endpoint = super::route(&ctx.services, &endpoint)?;
+        let method = params.method.as_str();
+        let output_mode = params.output.as_str();
+        let follow_redirects = params.redirect;
 
         // Create appropriate client based on redirect policy
         let client = if follow_redirects {
@@ -87,24 +83,44 @@ impl Executor for RestExecutor {
         };
 
         // Add authentication if specified
-        if let Some(auth) = auth_config {
-            request_builder = apply_authentication(request_builder, &auth, ctx).await?;
+        if let Some(auth) = params.endpoint.authentication() {
+            request_builder = auth::credentials(&self.0, auth, ctx)
+                .await?
+                .apply(request_builder);
         }
 
         // Add body for POST/PUT requests
         if (method == "post" || method == "put" || method == "patch")
-            && let Some(body) = params.get("body")
+            && let Some(body) = &params.body
         {
             request_builder = request_builder.json(body);
         }
 
-        // Send the request
-        let res = request_builder.send().await;
+        // Send the request through the HTTP cache, aborting it if the task is cancelled
+        let send = async {
+            http_cache::send(
+                &client,
+                request_builder.build()?,
+                ctx.services.cache.as_ref(),
+            )
+            .await
+        };
+        let res = tokio::select! {
+            res = send => res,
+            () = cancel.cancelled() => {
+                ctx.state.reaper.record(ReapedResource::Request {
+                    target: format!("{} {endpoint}", method.to_uppercase()),
+                });
+                return Err(Error::Cancelled {
+                    message: format!("{} request to {endpoint} was aborted", method.to_uppercase()),
+                });
+            }
+        };
 
         match res {
             Ok(response) => {
-                let status = response.status();
-                let headers = response.headers().clone();
+                let status = response.status;
+                let headers = response.headers;
 
                 // Check if the response indicates an error
                 // When redirects are disabled, 3xx responses are valid and should be returned
@@ -112,29 +128,29 @@ impl Executor for RestExecutor {
                 let treat_as_error = !status.is_success() && (!is_redirect || follow_redirects);
 
                 if treat_as_error {
-                    // Create a structured error object
-                    let error_obj = serde_json::json!({
-                        "type": "https://serverlessworkflow.io/dsl/errors/types/communication",
-                        "status": status.as_u16(),
-                        "title": format!("HTTP {} Error", status.as_u16()),
-                        "detail": format!("{} request to {} failed with status {}", method.to_uppercase(), endpoint, status),
-                        "instance": format!("/do/0/{}/try/0/{}", ctx.state.current_task.read().await, task_name)
-                    });
-
-                    // Return error as JSON string
-                    return Err(Error::Execution {
-                        message: serde_json::to_string(&error_obj).map_err(|e| {
-                            Error::Execution {
-                                message: format!("Failed to serialize error: {e}"),
-                            }
-                        })?,
+                    let error = WorkflowError::new(
+                        WorkflowError::COMMUNICATION,
+                        status.as_u16(),
+                        format!("HTTP {} Error", status.as_u16()),
+                    )
+                    .with_detail(format!(
+                        "{} request to {} failed with status {}",
+                        method.to_uppercase(),
+                        endpoint,
+                        status
+                    ))
+                    .with_instance(format!(
+                        "/do/0/{}/try/0/{}",
+                        ctx.state.current_task.read().await,
+                        task_name
+                    ));
+                    return Err(Error::Raised {
+                        kind: ErrorKind::from_status(status.as_u16()),
+                        error,
                     });
                 }
 
-                // Get response body
-                let body_text = response.text().await.map_err(|e| Error::Execution {
-                    message: format!("Failed to read response body: {e}"),
-                })?;
+                let body_text = response.body;
 
                 // Try to parse as JSON if content-type is application/json
                 let content_type = headers
@@ -175,82 +191,36 @@ impl Executor for RestExecutor {
             }
             Err(e) => {
                 // Network or other error
-                let error_obj = serde_json::json!({
-                    "type": "https://serverlessworkflow.io/dsl/errors/types/communication",
-                    "status": 500,
-                    "title": "Communication Error",
-                    "detail": e.to_string(),
-                    "instance": format!("/do/0/{}/try/0/{}", ctx.state.current_task.read().await, task_name)
-                });
-
-                Err(Error::Execution {
-                    message: serde_json::to_string(&error_obj).map_err(|e| Error::Execution {
-                        message: format!("Failed to serialize error: {e}"),
-                    })?,
-                })
+                let kind = ErrorKind::from_request(&e);
+                let error =
+                    WorkflowError::new(kind.error_type(), kind.status(), "Communication Error")
+                        .with_detail(e.to_string())
+                        .with_instance(format!(
+                            "/do/0/{}/try/0/{}",
+                            ctx.state.current_task.read().await,
+                            task_name
+                        ));
+                Err(Error::Raised { kind, error })
             }
         }
     }
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: false,
+            supports_cancellation: true,
+        }
     }
-}
 
-async fn apply_authentication(
-    request: reqwest::RequestBuilder,
-    auth_config: &serde_json::Value,
-    ctx: &Context,
-) -> Result<reqwest::RequestBuilder> {
-    // Handle basic authentication
-    if let Some(basic) = auth_config.get("basic") {
-        let username = if let Some(username_expr) = basic.get("username") {
-            // Evaluate expression
-            let current_data = ctx.state.data.read().await.clone();
-            let evaluated = crate::expressions::evaluate_value_with_input(
-                username_expr,
-                &current_data,
-                &ctx.metadata.initial_input,
-            )
-            .map_err(|e| Error::Execution {
-                message: format!("Failed to evaluate username expression: {e}"),
-            })?;
-            evaluated.as_str().unwrap_or("").to_string()
-        } else {
-            return Err(Error::Execution {
-                message: "No username in basic auth".to_string(),
-            });
-        };
-
-        let password = if let Some(password_expr) = basic.get("password") {
-            // Evaluate expression
-            let current_data = ctx.state.data.read().await.clone();
-            let evaluated = crate::expressions::evaluate_value_with_input(
-                password_expr,
-                &current_data,
-                &ctx.metadata.initial_input,
-            )
-            .map_err(|e| Error::Execution {
-                message: format!("Failed to evaluate password expression: {e}"),
-            })?;
-            evaluated.as_str().unwrap_or("").to_string()
-        } else {
-            return Err(Error::Execution {
-                message: "No password in basic auth".to_string(),
-            });
-        };
-
-        Ok(request.basic_auth(username, Some(password)))
-    } else {
-        // Other auth types not yet implemented
-        Ok(request)
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
     }
 }
 
 async fn interpolate_uri(uri: &str, ctx: &Context) -> Result<String> {
     // Simple URI interpolation - replace {paramName} with values from context
     let mut result = uri.to_string();
-    let data = ctx.state.data.read().await;
+    let data = ctx.view_data().await;
 
     // Find all {paramName} patterns and replace them
     let re = regex::Regex::new(r"\{([^}]+)\}").map_err(|e| Error::Execution {
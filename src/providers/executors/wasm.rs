@@ -0,0 +1,235 @@
+//! Executor for `call: wasm` tasks
+//!
+//! The module named in `with.module.endpoint` is a WASI command (a `.wasm`
+//! file exporting `_start`, as built for `wasm32-wasip1`). It runs sandboxed
+//! in wasmtime: the task's input is written to its stdin as JSON, and what it
+//! prints to stdout is the task's output, parsed as JSON when it is JSON. The
+//! module sees no files, network or host environment, only the `arguments`
+//! and `environment` given with the call.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use wasmtime::{Config, Engine, Linker, Module, Store};
+use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{I32Exit, WasiCtxBuilder};
+
+use crate::context::Context;
+use crate::executor::{Capabilities, Endpoint, Error, ErrorKind, Executor, Result, parse_params};
+use crate::output;
+use crate::text::{MAX_DISPLAY_LEN, truncate};
+
+/// Most bytes kept of what a module prints to stdout or stderr
+const MAX_OUTPUT_BYTES: usize = 16 * 1024 * 1024;
+
+/// How often running modules yield, so cancellation is noticed
+const YIELD_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Executor for `call: wasm` tasks
+#[derive(Default)]
+pub struct WasmExecutor {
+    /// The wasmtime engine, created with the first call
+    engine: OnceLock<std::result::Result<Engine, String>>,
+    /// Compiled modules, keyed by path
+    modules: Mutex<HashMap<String, Module>>,
+}
+
+/// Parameters of `call: wasm` tasks
+#[derive(Debug, Clone, Deserialize)]
+pub struct WasmParams {
+    pub module: WasmModule,
+    /// Value written to stdin as JSON; the task input if omitted
+    pub input: Option<serde_json::Value>,
+    /// Command-line arguments, after the module name
+    #[serde(default)]
+    pub arguments: Vec<String>,
+    /// Environment variables the module sees
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+}
+
+/// The WebAssembly module to run
+#[derive(Debug, Clone, Deserialize)]
+pub struct WasmModule {
+    pub endpoint: Endpoint,
+}
+
+impl WasmExecutor {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn engine(&self) -> Result<Engine> {
+        self.engine
+            .get_or_init(|| {
+                let mut config = Config::new();
+                config.async_support(true);
+                config.epoch_interruption(true);
+                Engine::new(&config).map_err(|e| e.to_string())
+            })
+            .clone()
+            .map_err(|message| Error::Failed {
+                kind: ErrorKind::Infrastructure,
+                message: format!("Failed to create the WebAssembly engine: {message}"),
+            })
+    }
+
+    /// Compile a module, reusing the compilations of earlier calls
+    fn module(&self, engine: &Engine, path: &str) -> Result<Module> {
+        if let Some(module) = self
+            .modules
+            .lock()
+            .ok()
+            .and_then(|modules| modules.get(path).cloned())
+        {
+            return Ok(module);
+        }
+
+        let module = Module::from_file(engine, path).map_err(|e| Error::Execution {
+            message: format!("Failed to load WebAssembly module {path}: {e}"),
+        })?;
+        if let Ok(mut modules) = self.modules.lock() {
+            modules.insert(path.to_string(), module.clone());
+        }
+        Ok(module)
+    }
+
+    /// Run a module to completion, returning its exit code, stdout and stderr
+    async fn run(
+        engine: &Engine,
+        module: &Module,
+        name: &str,
+        params: &WasmParams,
+        stdin: Vec<u8>,
+    ) -> wasmtime::Result<(i32, Vec<u8>, Vec<u8>)> {
+        let mut linker: Linker<WasiP1Ctx> = Linker::new(engine);
+        preview1::add_to_linker_async(&mut linker, |ctx| ctx)?;
+
+        let stdout = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+        let stderr = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+        let mut args = vec![name.to_string()];
+        args.extend(params.arguments.iter().cloned());
+        let envs: Vec<(String, String)> = params
+            .environment
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let wasi = WasiCtxBuilder::new()
+            .stdin(MemoryInputPipe::new(stdin))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .args(&args)
+            .envs(&envs)
+            .build_p1();
+
+        let mut store = Store::new(engine, wasi);
+        // Yield to the runtime at every epoch tick, so a cancelled call can be dropped
+        store.epoch_deadline_async_yield_and_update(1);
+
+        let instance = linker.instantiate_async(&mut store, module).await?;
+        let start = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+        let exit_code = match start.call_async(&mut store, ()).await {
+            Ok(()) => 0,
+            Err(e) => match e.downcast_ref::<I32Exit>() {
+                Some(exit) => exit.0,
+                None => return Err(e),
+            },
+        };
+        Ok((
+            exit_code,
+            stdout.contents().to_vec(),
+            stderr.contents().to_vec(),
+        ))
+    }
+}
+
+#[async_trait]
+impl Executor for WasmExecutor {
+    async fn exec(
+        &self,
+        task_name: &str,
+        params: &serde_json::Value,
+        ctx: &Context,
+        _streamer: Option<crate::task_output::TaskOutputStreamer>,
+        cancel: &CancellationToken,
+    ) -> Result<serde_json::Value> {
+        let params: WasmParams = parse_params("wasm", params)?;
+        let endpoint = params.module.endpoint.uri();
+        let path = endpoint.strip_prefix("file://").unwrap_or(endpoint);
+
+        let input = match &params.input {
+            Some(input) => input.clone(),
+            None => output::filter_internal_fields(&ctx.view_task_input().await),
+        };
+        let stdin = serde_json::to_vec(&input).map_err(|e| Error::Execution {
+            message: format!("Failed to serialize input of {task_name}: {e}"),
+        })?;
+
+        let engine = self.engine()?;
+        let module = self.module(&engine, path)?;
+        println!("  WebAssembly call: {path}");
+
+        // Advance the engine's epoch while the module runs, making it yield
+        let ticker = {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(YIELD_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    engine.increment_epoch();
+                }
+            })
+        };
+        // Dropping the run stops the module if the task is cancelled
+        let result = tokio::select! {
+            result = Self::run(&engine, &module, path, &params, stdin) => result,
+            () = cancel.cancelled() => {
+                ticker.abort();
+                return Err(Error::Cancelled {
+                    message: format!("WebAssembly module {path} was stopped"),
+                });
+            }
+        };
+        ticker.abort();
+
+        let (exit_code, stdout, stderr) = result.map_err(|e| Error::Failed {
+            kind: ErrorKind::Script,
+            message: format!("WebAssembly module {path} trapped: {e:#}"),
+        })?;
+        let stdout = String::from_utf8_lossy(&stdout);
+        let stderr = String::from_utf8_lossy(&stderr);
+        if exit_code != 0 {
+            let stdout = truncate(&stdout, MAX_DISPLAY_LEN);
+            let stderr = truncate(&stderr, MAX_DISPLAY_LEN);
+            return Err(Error::Failed {
+                kind: ErrorKind::Script,
+                message: format!(
+                    "WebAssembly module {path} failed with exit code {exit_code}\nstdout: {stdout}\nstderr: {stderr}"
+                ),
+            });
+        }
+
+        let stdout = stdout.trim();
+        if stdout.is_empty() {
+            return Ok(serde_json::Value::Null);
+        }
+        Ok(serde_json::from_str(stdout)
+            .unwrap_or_else(|_| serde_json::Value::String(stdout.to_string())))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            supports_streaming: false,
+            supports_cancellation: true,
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
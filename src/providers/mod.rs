@@ -1,5 +1,7 @@
 pub mod cache;
 pub mod container;
+pub mod events;
 pub mod executors;
 pub mod persistence;
+pub mod secrets;
 pub mod visualization;
@@ -1,13 +1,19 @@
-use crate::persistence::{PersistenceProvider, Result};
+use crate::durableengine::InstanceState;
+use crate::persistence::{Change, PersistenceProvider, Result, RunUpdate, WorkflowRollup};
 use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
 pub struct InMemoryPersistence {
     events: Arc<Mutex<HashMap<String, Vec<WorkflowEvent>>>>,
     checkpoints: Arc<Mutex<HashMap<String, WorkflowCheckpoint>>>,
+    states: Arc<Mutex<HashMap<String, InstanceState>>>,
+    /// Instance and index of each event, in the order the events were saved
+    feed: Arc<Mutex<Vec<(String, usize)>>>,
+    debug_bundles: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    rollups: Arc<Mutex<BTreeMap<String, WorkflowRollup>>>,
 }
 
 impl Default for InMemoryPersistence {
@@ -15,6 +21,10 @@ impl Default for InMemoryPersistence {
         Self {
             events: Arc::new(Mutex::new(HashMap::new())),
             checkpoints: Arc::new(Mutex::new(HashMap::new())),
+            states: Arc::new(Mutex::new(HashMap::new())),
+            feed: Arc::new(Mutex::new(Vec::new())),
+            debug_bundles: Arc::new(Mutex::new(HashMap::new())),
+            rollups: Arc::new(Mutex::new(BTreeMap::new())),
         }
     }
 }
@@ -24,18 +34,34 @@ impl InMemoryPersistence {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Append an event to its instance's history and to the change feed
+    fn append(&self, events: &mut HashMap<String, Vec<WorkflowEvent>>, event: WorkflowEvent) {
+        let instance_id = event.instance_id().to_string();
+        let history = events.entry(instance_id.clone()).or_default();
+        self.feed
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push((instance_id, history.len()));
+        history.push(event);
+    }
 }
 
 #[async_trait]
 impl PersistenceProvider for InMemoryPersistence {
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_event",
+        skip_all
+    )]
     async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
-        let instance_id = event.instance_id().to_string();
         let mut events = self
             .events
             .lock()
             .unwrap_or_else(std::sync::PoisonError::into_inner);
 
-        events.entry(instance_id).or_default().push(event);
+        self.append(&mut events, event);
 
         Ok(())
     }
@@ -49,6 +75,12 @@ impl PersistenceProvider for InMemoryPersistence {
         Ok(events.get(instance_id).cloned().unwrap_or_default())
     }
 
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_checkpoint",
+        skip_all
+    )]
     async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
         let instance_id = checkpoint.instance_id.clone();
         let mut checkpoints = self
@@ -61,6 +93,27 @@ impl PersistenceProvider for InMemoryPersistence {
         Ok(())
     }
 
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> Result<()> {
+        // Holding both locks keeps readers from seeing one write without the other
+        let mut events = self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut checkpoints = self
+            .checkpoints
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        self.append(&mut events, event);
+        checkpoints.insert(checkpoint.instance_id.clone(), checkpoint);
+
+        Ok(())
+    }
+
     async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
         let checkpoints = self
             .checkpoints
@@ -69,4 +122,114 @@ impl PersistenceProvider for InMemoryPersistence {
 
         Ok(checkpoints.get(instance_id).cloned())
     }
+
+    async fn get_state(&self, instance_id: &str) -> Result<Option<InstanceState>> {
+        let states = self
+            .states
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        Ok(states.get(instance_id).copied())
+    }
+
+    async fn transition_state(
+        &self,
+        instance_id: &str,
+        from: Option<InstanceState>,
+        to: InstanceState,
+    ) -> Result<bool> {
+        let mut states = self
+            .states
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if states.get(instance_id).copied() != from {
+            return Ok(false);
+        }
+        states.insert(instance_id.to_string(), to);
+        Ok(true)
+    }
+
+    async fn changes(
+        &self,
+        after: u64,
+        instance_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        let events = self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let feed = self
+            .feed
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        Ok(feed
+            .iter()
+            .zip(1_u64..)
+            .skip(usize::try_from(after).unwrap_or(usize::MAX))
+            .filter(|((id, _), _)| instance_id.is_none_or(|wanted| wanted == id))
+            .filter_map(|((id, index), offset)| {
+                let event = events.get(id)?.get(*index)?.clone();
+                Some(Change { offset, event })
+            })
+            .take(limit)
+            .collect())
+    }
+
+    async fn save_debug_bundle(&self, instance_id: &str, bundle: serde_json::Value) -> Result<()> {
+        self.debug_bundles
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(instance_id.to_string(), bundle);
+        Ok(())
+    }
+
+    async fn get_debug_bundle(&self, instance_id: &str) -> Result<Option<serde_json::Value>> {
+        Ok(self
+            .debug_bundles
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(instance_id)
+            .cloned())
+    }
+
+    async fn update_rollup(&self, workflow_id: &str, update: &RunUpdate) -> Result<()> {
+        self.rollups
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(workflow_id.to_string())
+            .or_insert_with(|| WorkflowRollup::new(workflow_id))
+            .apply(update);
+        Ok(())
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> Result<()> {
+        *self
+            .rollups
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = rollups
+            .into_iter()
+            .map(|rollup| (rollup.workflow_id.clone(), rollup))
+            .collect();
+        Ok(())
+    }
+
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        let rollups = self
+            .rollups
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        Ok((!rollups.is_empty()).then(|| rollups.values().cloned().collect()))
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        let events = self
+            .events
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        Ok(events.keys().cloned().collect())
+    }
 }
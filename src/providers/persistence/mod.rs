@@ -1,11 +1,19 @@
 pub mod mem;
+#[cfg(feature = "object-store")]
+pub mod object_store;
+#[cfg(feature = "postgres")]
 pub mod postgres;
 pub mod redb;
+pub mod resilient;
+#[cfg(feature = "sqlite")]
 pub mod sqlite;
 
 pub use self::mem::InMemoryPersistence;
-#[allow(unused_imports)]
+#[cfg(feature = "object-store")]
+pub use self::object_store::ObjectStorePersistence;
+#[cfg(feature = "postgres")]
 pub use self::postgres::PostgresPersistence;
 pub use self::redb::RedbPersistence;
-#[allow(unused_imports)]
+pub use self::resilient::{ResilienceOptions, ResilientPersistence};
+#[cfg(feature = "sqlite")]
 pub use self::sqlite::SqlitePersistence;
@@ -0,0 +1,316 @@
+//! Persistence in an object store, such as S3, GCS or MinIO
+//!
+//! Each instance keeps its history under `<prefix>/instances/<instance_id>/`:
+//! `events.jsonl` holds its events, one per line in the order they were
+//! recorded, and `checkpoint.json` its latest checkpoint. Object stores cannot
+//! append to an object, so an event is added by rewriting the history, on
+//! stores that support conditional writes only if it has not changed since it
+//! was read.
+//!
+//! Instance states and leases are not stored; the state of an instance is the
+//! one its events imply.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutMode, PutPayload, UpdateVersion};
+use snafu::prelude::*;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::persistence::{Error, PersistenceProvider, Result, SerializationSnafu};
+use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
+
+/// Object holding an instance's events
+const EVENTS_FILE: &str = "events.jsonl";
+
+/// Object holding an instance's latest checkpoint
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// Times an event is appended again after another writer changed the history
+const APPEND_ATTEMPTS: usize = 5;
+
+#[derive(Debug)]
+pub struct ObjectStorePersistence {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    /// Serializes the rewrites of event histories made by this process
+    appends: Mutex<()>,
+}
+
+impl ObjectStorePersistence {
+    /// Create a persistence provider storing instances under `prefix` in `store`
+    #[must_use]
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: Path) -> Self {
+        Self {
+            store,
+            prefix,
+            appends: Mutex::new(()),
+        }
+    }
+
+    /// Create a persistence provider from an object store URL
+    ///
+    /// The path of the URL is the prefix instances are stored under.
+    /// Credentials and endpoints are read from the variables the store's
+    /// client understands, such as `AWS_ACCESS_KEY_ID`, or `AWS_ENDPOINT` and
+    /// `AWS_ALLOW_HTTP` for MinIO.
+    ///
+    /// # Arguments
+    /// * `url` - Object store URL (e.g., `s3://bucket/jackdaw`, `gs://bucket/jackdaw` or `file:///var/lib/jackdaw`)
+    ///
+    /// # Errors
+    /// Returns an error if the URL is invalid or names an unsupported store.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use jackdaw::providers::persistence::ObjectStorePersistence;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let persistence = ObjectStorePersistence::from_url("s3://workflows/jackdaw")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_url(url: &str) -> Result<Self> {
+        let parsed = url::Url::parse(url).map_err(|e| Error::Persistence {
+            message: format!("Invalid object store URL '{url}': {e}"),
+        })?;
+        let options = std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value));
+        let (store, prefix) =
+            object_store::parse_url_opts(&parsed, options).map_err(|e| Error::Database {
+                message: format!("Failed to open object store: {e}"),
+            })?;
+
+        Ok(Self::new(Arc::from(store), prefix))
+    }
+
+    /// Path of one of an instance's objects
+    fn instance_path(&self, instance_id: &str, file: &str) -> Path {
+        self.prefix
+            .child("instances")
+            .child(instance_id)
+            .child(file)
+    }
+
+    /// Contents and version of an object, or `None` if it does not exist
+    async fn read(&self, path: &Path) -> Result<Option<(Bytes, UpdateVersion)>> {
+        let result = match self.store.get(path).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => {
+                return Err(Error::Database {
+                    message: format!("Failed to read {path}: {e}"),
+                });
+            }
+        };
+        let version = UpdateVersion {
+            e_tag: result.meta.e_tag.clone(),
+            version: result.meta.version.clone(),
+        };
+        let bytes = result.bytes().await.map_err(|e| Error::Database {
+            message: format!("Failed to read {path}: {e}"),
+        })?;
+
+        Ok(Some((bytes, version)))
+    }
+
+    /// Write an object, overwriting it on stores without conditional writes
+    async fn write(&self, path: &Path, bytes: Vec<u8>, mode: PutMode) -> object_store::Result<()> {
+        let payload = PutPayload::from(bytes);
+        match self
+            .store
+            .put_opts(path, payload.clone(), mode.into())
+            .await
+        {
+            Err(object_store::Error::NotImplemented) => {
+                self.store.put(path, payload).await.map(|_| ())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+}
+
+#[async_trait]
+impl PersistenceProvider for ObjectStorePersistence {
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_event",
+        skip_all
+    )]
+    async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
+        let path = self.instance_path(event.instance_id(), EVENTS_FILE);
+        let mut line = serde_json::to_vec(&event).context(SerializationSnafu)?;
+        line.push(b'\n');
+
+        let _appending = self.appends.lock().await;
+        for _ in 0..APPEND_ATTEMPTS {
+            let (mut history, mode) = match self.read(&path).await? {
+                Some((bytes, version)) => (bytes.to_vec(), PutMode::Update(version)),
+                None => (Vec::new(), PutMode::Create),
+            };
+            history.extend_from_slice(&line);
+            match self.write(&path, history, mode).await {
+                Ok(()) => return Ok(()),
+                // Another process wrote to the history since it was read
+                Err(
+                    object_store::Error::Precondition { .. }
+                    | object_store::Error::AlreadyExists { .. },
+                ) => {}
+                Err(e) => {
+                    return Err(Error::Database {
+                        message: format!("Failed to save event: {e}"),
+                    });
+                }
+            }
+        }
+
+        Err(Error::Persistence {
+            message: format!(
+                "Events of {} kept changing while an event was saved",
+                event.instance_id()
+            ),
+        })
+    }
+
+    async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
+        let Some((bytes, _)) = self
+            .read(&self.instance_path(instance_id, EVENTS_FILE))
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+
+        bytes
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).context(SerializationSnafu))
+            .collect()
+    }
+
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_checkpoint",
+        skip_all
+    )]
+    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
+        let path = self.instance_path(&checkpoint.instance_id, CHECKPOINT_FILE);
+        let value = serde_json::to_vec(&checkpoint).context(SerializationSnafu)?;
+        self.write(&path, value, PutMode::Overwrite)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to save checkpoint: {e}"),
+            })
+    }
+
+    async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
+        self.read(&self.instance_path(instance_id, CHECKPOINT_FILE))
+            .await?
+            .map(|(bytes, _)| serde_json::from_slice(&bytes).context(SerializationSnafu))
+            .transpose()
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        let instances = self
+            .store
+            .list_with_delimiter(Some(&self.prefix.child("instances")))
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to list instances: {e}"),
+            })?;
+
+        Ok(instances
+            .common_prefixes
+            .iter()
+            .filter_map(|path| path.filename().map(str::to_string))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::wildcard_enum_match_arm)]
+
+    use super::*;
+    use chrono::Utc;
+    use object_store::memory::InMemory;
+
+    fn task_started(instance_id: &str, task_name: &str) -> WorkflowEvent {
+        WorkflowEvent::TaskStarted {
+            instance_id: instance_id.to_string(),
+            task_name: task_name.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_object_store_persistence_events_and_checkpoints() {
+        let store = Arc::new(InMemory::new());
+        let persistence = ObjectStorePersistence::new(store.clone(), Path::from("jackdaw"));
+
+        persistence
+            .save_event(task_started("test-instance-1", "first"))
+            .await
+            .unwrap();
+        persistence
+            .save_event(task_started("test-instance-1", "second"))
+            .await
+            .unwrap();
+        persistence
+            .save_event(task_started("test-instance-2", "first"))
+            .await
+            .unwrap();
+
+        let events = persistence.get_events("test-instance-1").await.unwrap();
+        let names: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                WorkflowEvent::TaskStarted { task_name, .. } => Some(task_name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["first", "second"]);
+        assert!(persistence.get_events("missing").await.unwrap().is_empty());
+
+        // The history is one line per event
+        let history = store
+            .get(&Path::from(
+                "jackdaw/instances/test-instance-1/events.jsonl",
+            ))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(history.split(|byte| *byte == b'\n').count(), 3);
+
+        persistence
+            .save_checkpoint(WorkflowCheckpoint {
+                instance_id: "test-instance-1".to_string(),
+                current_task: "second".to_string(),
+                data: serde_json::json!({"count": 2}),
+                timestamp: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let checkpoint = persistence
+            .get_checkpoint("test-instance-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(checkpoint.current_task, "second");
+        assert!(
+            persistence
+                .get_checkpoint("test-instance-2")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let mut ids = persistence.list_instance_ids().await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["test-instance-1", "test-instance-2"]);
+    }
+}
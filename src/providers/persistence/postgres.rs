@@ -1,13 +1,22 @@
-use crate::persistence::{Error, PersistenceProvider, Result, SerializationSnafu};
+use crate::durableengine::InstanceState;
+use crate::persistence::{
+    Change, Error, FencingTokens, Lease, PersistenceProvider, QueuedStart, Result, RunUpdate,
+    SerializationSnafu, WorkflowRollup, check_fence,
+};
 use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
 use async_trait::async_trait;
 use snafu::prelude::*;
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions};
+use std::time::Duration;
+
+/// Advisory lock held while an event is appended, until its transaction ends
+const CHANGE_FEED_LOCK: i64 = 0x6a61_636b_6461_77;
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct PostgresPersistence {
     pool: PgPool,
+    fencing: FencingTokens,
 }
 
 #[allow(dead_code)]
@@ -49,7 +58,10 @@ impl PostgresPersistence {
                 })?;
         }
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            fencing: FencingTokens::default(),
+        })
     }
 
     /// Create a new ``PostgreSQL`` persistence provider with custom pool options
@@ -69,47 +81,101 @@ impl PostgresPersistence {
                 })?;
         }
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            fencing: FencingTokens::default(),
+        })
     }
 
-    /// Get the event type name for a ``WorkflowEvent``
-    fn get_event_type(event: &WorkflowEvent) -> &'static str {
-        match event {
-            WorkflowEvent::WorkflowStarted { .. } => "WorkflowStarted",
-            WorkflowEvent::TaskEntered { .. } => "TaskEntered",
-            WorkflowEvent::TaskCreated { .. } => "TaskCreated",
-            WorkflowEvent::TaskStarted { .. } => "TaskStarted",
-            WorkflowEvent::TaskRetried { .. } => "TaskRetried",
-            WorkflowEvent::TaskCompleted { .. } => "TaskCompleted",
-            WorkflowEvent::WorkflowCompleted { .. } => "WorkflowCompleted",
-            WorkflowEvent::WorkflowCorrelationStarted { .. } => "WorkflowCorrelationStarted",
-            WorkflowEvent::WorkflowCorrelationCompleted { .. } => "WorkflowCorrelationCompleted",
-            WorkflowEvent::WorkflowFailed { .. } => "WorkflowFailed",
-            WorkflowEvent::WorkflowCancelled { .. } => "WorkflowCancelled",
-            WorkflowEvent::WorkflowSuspended { .. } => "WorkflowSuspended",
-            WorkflowEvent::WorkflowResumed { .. } => "WorkflowResumed",
-            WorkflowEvent::TaskCancelled { .. } => "TaskCancelled",
-            WorkflowEvent::TaskSuspended { .. } => "TaskSuspended",
-            WorkflowEvent::TaskResumed { .. } => "TaskResumed",
-            WorkflowEvent::TaskFaulted { .. } => "TaskFaulted",
-        }
+    /// Connect to a read replica of a ``PostgreSQL`` persistence database
+    ///
+    /// The schema is left to the primary, and writes through the provider
+    /// are refused by the replica. Use it with
+    /// [`crate::DurableEngineBuilder::with_read_persistence`].
+    ///
+    /// # Arguments
+    /// * `database_url` - ``PostgreSQL`` connection string of the replica
+    ///
+    /// # Errors
+    /// Returns an error if the database connection fails.
+    pub async fn replica(database_url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(database_url)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to connect to PostgreSQL replica: {e}"),
+            })?;
+
+        Ok(Self {
+            pool,
+            fencing: FencingTokens::default(),
+        })
     }
-}
 
-#[async_trait]
-impl PersistenceProvider for PostgresPersistence {
-    async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
+    /// Begin a transaction
+    async fn begin(&self) -> Result<sqlx::Transaction<'_, sqlx::Postgres>> {
+        self.pool.begin().await.map_err(|e| Error::Database {
+            message: format!("Failed to begin transaction: {e}"),
+        })
+    }
+
+    /// Commit a transaction
+    async fn commit(tx: sqlx::Transaction<'_, sqlx::Postgres>) -> Result<()> {
+        tx.commit().await.map_err(|e| Error::Database {
+            message: format!("Failed to commit transaction: {e}"),
+        })
+    }
+
+    /// Refuse writes for an instance on `conn` once its lease has been taken over
+    ///
+    /// The lease row stays locked until the transaction ends, so it cannot be
+    /// taken over before the write is committed.
+    async fn check_fence(&self, conn: &mut PgConnection, instance_id: &str) -> Result<()> {
+        let Some(token) = self.fencing.token(instance_id) else {
+            return Ok(());
+        };
+        let stored = sqlx::query_scalar::<_, i64>(
+            "SELECT token FROM workflow_instance_leases WHERE instance_id = $1 FOR SHARE",
+        )
+        .bind(instance_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to check instance lease: {e}"),
+        })?;
+
+        check_fence(
+            instance_id,
+            token,
+            stored.and_then(|stored| u64::try_from(stored).ok()),
+        )
+    }
+
+    /// Append an event to the instance's history on `conn`
+    async fn insert_event(conn: &mut PgConnection, event: &WorkflowEvent) -> Result<()> {
         let instance_id = event.instance_id().to_string();
-        let event_type = Self::get_event_type(&event);
-        let event_data = serde_json::to_value(&event).context(SerializationSnafu)?;
+        let event_type = Self::get_event_type(event);
+        let event_data = serde_json::to_value(event).context(SerializationSnafu)?;
         let timestamp = chrono::Utc::now();
 
+        // Event IDs are the offsets of the change feed. Taking them one
+        // transaction at a time makes them commit in order, so a consumer
+        // never sees an event before one with a lower ID that commits later.
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(CHANGE_FEED_LOCK)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to lock the change feed: {e}"),
+            })?;
+
         // Get the next sequence number for this instance
         let sequence_number: i64 = sqlx::query_scalar(
             "SELECT COALESCE(MAX(sequence_number), -1) + 1 FROM workflow_events WHERE instance_id = $1"
         )
         .bind(&instance_id)
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *conn)
         .await
         .map_err(|e| Error::Database { message: format!("Failed to get sequence number: {e}") })?;
 
@@ -121,33 +187,18 @@ impl PersistenceProvider for PostgresPersistence {
         .bind(&event_data)
         .bind(timestamp)
         .bind(sequence_number)
-        .execute(&self.pool)
+        .execute(&mut *conn)
         .await
         .map_err(|e| Error::Database { message: format!("Failed to save event: {e}") })?;
 
         Ok(())
     }
 
-    async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
-        let rows = sqlx::query_as::<_, (serde_json::Value,)>(
-            "SELECT event_data FROM workflow_events WHERE instance_id = $1 ORDER BY sequence_number ASC"
-        )
-        .bind(instance_id)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::Database { message: format!("Failed to get events: {e}") })?;
-
-        let mut events = Vec::new();
-        for (event_data,) in rows {
-            let event: WorkflowEvent =
-                serde_json::from_value(event_data).context(SerializationSnafu)?;
-            events.push(event);
-        }
-
-        Ok(events)
-    }
-
-    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
+    /// Replace the instance's checkpoint on `conn`
+    async fn upsert_checkpoint(
+        conn: &mut PgConnection,
+        checkpoint: &WorkflowCheckpoint,
+    ) -> Result<()> {
         let data_json = serde_json::to_value(&checkpoint.data).context(SerializationSnafu)?;
 
         sqlx::query(
@@ -165,7 +216,7 @@ impl PersistenceProvider for PostgresPersistence {
         .bind(&checkpoint.current_task)
         .bind(&data_json)
         .bind(checkpoint.timestamp)
-        .execute(&self.pool)
+        .execute(&mut *conn)
         .await
         .map_err(|e| Error::Database {
             message: format!("Failed to save checkpoint: {e}"),
@@ -174,6 +225,96 @@ impl PersistenceProvider for PostgresPersistence {
         Ok(())
     }
 
+    /// Get the event type name for a ``WorkflowEvent``
+    fn get_event_type(event: &WorkflowEvent) -> &'static str {
+        match event {
+            WorkflowEvent::WorkflowStarted { .. } => "WorkflowStarted",
+            WorkflowEvent::TaskEntered { .. } => "TaskEntered",
+            WorkflowEvent::TaskCreated { .. } => "TaskCreated",
+            WorkflowEvent::TaskStarted { .. } => "TaskStarted",
+            WorkflowEvent::TaskRetried { .. } => "TaskRetried",
+            WorkflowEvent::TaskCompleted { .. } => "TaskCompleted",
+            WorkflowEvent::WorkflowCompleted { .. } => "WorkflowCompleted",
+            WorkflowEvent::WorkflowCorrelationStarted { .. } => "WorkflowCorrelationStarted",
+            WorkflowEvent::WorkflowCorrelationCompleted { .. } => "WorkflowCorrelationCompleted",
+            WorkflowEvent::WorkflowFailed { .. } => "WorkflowFailed",
+            WorkflowEvent::WorkflowCancelled { .. } => "WorkflowCancelled",
+            WorkflowEvent::WorkflowSuspended { .. } => "WorkflowSuspended",
+            WorkflowEvent::WorkflowResumed { .. } => "WorkflowResumed",
+            WorkflowEvent::TaskCancelled { .. } => "TaskCancelled",
+            WorkflowEvent::TaskSuspended { .. } => "TaskSuspended",
+            WorkflowEvent::TaskResumed { .. } => "TaskResumed",
+            WorkflowEvent::TaskFaulted { .. } => "TaskFaulted",
+            WorkflowEvent::TaskReaped { .. } => "TaskReaped",
+            WorkflowEvent::TaskCacheHit { .. } => "TaskCacheHit",
+            WorkflowEvent::TaskSkipped { .. } => "TaskSkipped",
+            WorkflowEvent::SwitchCaseMatched { .. } => "SwitchCaseMatched",
+            WorkflowEvent::ErrorCaught { .. } => "ErrorCaught",
+            WorkflowEvent::WatchesEvaluated { .. } => "WatchesEvaluated",
+            WorkflowEvent::ShadowCompared { .. } => "ShadowCompared",
+        }
+    }
+}
+
+#[async_trait]
+impl PersistenceProvider for PostgresPersistence {
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_event",
+        skip_all
+    )]
+    async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, event.instance_id()).await?;
+        Self::insert_event(&mut tx, &event).await?;
+        Self::commit(tx).await
+    }
+
+    async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
+        let rows = sqlx::query_as::<_, (serde_json::Value,)>(
+            "SELECT event_data FROM workflow_events WHERE instance_id = $1 ORDER BY sequence_number ASC"
+        )
+        .bind(instance_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database { message: format!("Failed to get events: {e}") })?;
+
+        let mut events = Vec::new();
+        for (event_data,) in rows {
+            let event: WorkflowEvent =
+                serde_json::from_value(event_data).context(SerializationSnafu)?;
+            events.push(event);
+        }
+
+        Ok(events)
+    }
+
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_checkpoint",
+        skip_all
+    )]
+    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, &checkpoint.instance_id).await?;
+        Self::upsert_checkpoint(&mut tx, &checkpoint).await?;
+        Self::commit(tx).await
+    }
+
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> Result<()> {
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, event.instance_id()).await?;
+        Self::insert_event(&mut tx, &event).await?;
+        Self::upsert_checkpoint(&mut tx, &checkpoint).await?;
+        Self::commit(tx).await
+    }
+
     async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
         let result = sqlx::query_as::<_, (String, String, serde_json::Value, chrono::DateTime<chrono::Utc>)>(
             "SELECT instance_id, current_task, data, timestamp FROM workflow_checkpoints WHERE instance_id = $1"
@@ -193,6 +334,355 @@ impl PersistenceProvider for PostgresPersistence {
             None => Ok(None),
         }
     }
+
+    async fn get_state(&self, instance_id: &str) -> Result<Option<InstanceState>> {
+        let state = sqlx::query_scalar::<_, String>(
+            "SELECT state FROM workflow_instance_states WHERE instance_id = $1",
+        )
+        .bind(instance_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to get instance state: {e}"),
+        })?;
+
+        state
+            .map(|state| {
+                state
+                    .parse()
+                    .map_err(|message| Error::Persistence { message })
+            })
+            .transpose()
+    }
+
+    async fn transition_state(
+        &self,
+        instance_id: &str,
+        from: Option<InstanceState>,
+        to: InstanceState,
+    ) -> Result<bool> {
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, instance_id).await?;
+
+        // Only the first state of an instance is inserted; later ones must
+        // match the state they were checked against
+        let result = match from {
+            None => sqlx::query(
+                "INSERT INTO workflow_instance_states (instance_id, state, updated_at) VALUES ($1, $2, $3) ON CONFLICT (instance_id) DO NOTHING",
+            )
+            .bind(instance_id)
+            .bind(to.as_str())
+            .bind(chrono::Utc::now())
+            .execute(&mut *tx)
+            .await,
+            Some(from) => sqlx::query(
+                "UPDATE workflow_instance_states SET state = $1, updated_at = $2 WHERE instance_id = $3 AND state = $4",
+            )
+            .bind(to.as_str())
+            .bind(chrono::Utc::now())
+            .bind(instance_id)
+            .bind(from.as_str())
+            .execute(&mut *tx)
+            .await,
+        }
+        .map_err(|e| Error::Database {
+            message: format!("Failed to save instance state: {e}"),
+        })?;
+
+        Self::commit(tx).await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn acquire_lease(
+        &self,
+        instance_id: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<Option<Lease>> {
+        // The token only changes hands with the lease, and another owner's
+        // lease is only taken once it has lapsed by the database's clock
+        let ttl_secs = ttl.as_secs_f64();
+        let lease = sqlx::query_as::<_, (i64, chrono::DateTime<chrono::Utc>)>(
+            r"
+            INSERT INTO workflow_instance_leases (instance_id, owner, token, expires_at)
+            VALUES ($1, $2, 1, NOW() + make_interval(secs => $3))
+            ON CONFLICT (instance_id)
+            DO UPDATE SET
+                token = CASE
+                    WHEN workflow_instance_leases.owner = EXCLUDED.owner
+                    THEN workflow_instance_leases.token
+                    ELSE workflow_instance_leases.token + 1
+                END,
+                owner = EXCLUDED.owner,
+                expires_at = EXCLUDED.expires_at
+            WHERE workflow_instance_leases.owner = EXCLUDED.owner
+                OR workflow_instance_leases.expires_at <= NOW()
+            RETURNING token, expires_at
+            ",
+        )
+        .bind(instance_id)
+        .bind(owner)
+        .bind(ttl_secs)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to acquire instance lease: {e}"),
+        })?;
+
+        let lease = lease.map(|(token, expires_at)| Lease {
+            instance_id: instance_id.to_string(),
+            owner: owner.to_string(),
+            token: u64::try_from(token).unwrap_or_default(),
+            expires_at,
+        });
+        if let Some(lease) = &lease {
+            self.fencing.hold(lease);
+        }
+        Ok(lease)
+    }
+
+    async fn release_lease(&self, lease: &Lease) -> Result<()> {
+        // The lease is kept, lapsed, so the next owner's token is still newer
+        sqlx::query(
+            "UPDATE workflow_instance_leases SET expires_at = NOW() WHERE instance_id = $1 AND owner = $2 AND token = $3",
+        )
+        .bind(&lease.instance_id)
+        .bind(&lease.owner)
+        .bind(i64::try_from(lease.token).unwrap_or(i64::MAX))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to release instance lease: {e}"),
+        })?;
+
+        self.fencing.release(lease);
+        Ok(())
+    }
+
+    async fn changes(
+        &self,
+        after: u64,
+        instance_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        let rows = sqlx::query_as::<_, (i64, serde_json::Value)>(
+            "SELECT id, event_data FROM workflow_events WHERE id > $1 AND ($2::TEXT IS NULL OR instance_id = $2) ORDER BY id LIMIT $3",
+        )
+        .bind(i64::try_from(after).unwrap_or(i64::MAX))
+        .bind(instance_id)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to read change feed: {e}"),
+        })?;
+
+        rows.into_iter()
+            .map(|(id, event_data)| {
+                Ok(Change {
+                    offset: u64::try_from(id).unwrap_or_default(),
+                    event: serde_json::from_value(event_data).context(SerializationSnafu)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn enqueue_start(&self, start: QueuedStart) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO workflow_queue (instance_id, workflow, input, enqueued_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&start.instance_id)
+        .bind(&start.workflow)
+        .bind(&start.input)
+        .bind(start.enqueued_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to queue workflow start: {e}"),
+        })?;
+
+        Ok(())
+    }
+
+    async fn queued_starts(&self, limit: usize) -> Result<Vec<QueuedStart>> {
+        // Starts whose instance is running under a live lease are skipped
+        let rows = sqlx::query_as::<
+            _,
+            (
+                String,
+                serde_json::Value,
+                serde_json::Value,
+                chrono::DateTime<chrono::Utc>,
+            ),
+        >(
+            r"
+            SELECT q.instance_id, q.workflow, q.input, q.enqueued_at
+            FROM workflow_queue q
+            LEFT JOIN workflow_instance_leases l ON l.instance_id = q.instance_id
+            WHERE l.instance_id IS NULL OR l.expires_at <= NOW()
+            ORDER BY q.enqueued_at, q.instance_id
+            LIMIT $1
+            ",
+        )
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to list queued workflow starts: {e}"),
+        })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(instance_id, workflow, input, enqueued_at)| QueuedStart {
+                instance_id,
+                workflow,
+                input,
+                enqueued_at,
+            })
+            .collect())
+    }
+
+    async fn remove_start(&self, instance_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM workflow_queue WHERE instance_id = $1")
+            .bind(instance_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to remove queued workflow start: {e}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn save_debug_bundle(&self, instance_id: &str, bundle: serde_json::Value) -> Result<()> {
+        sqlx::query(
+            r"
+            INSERT INTO workflow_debug_bundles (instance_id, bundle, created_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (instance_id)
+            DO UPDATE SET bundle = EXCLUDED.bundle, created_at = EXCLUDED.created_at
+            ",
+        )
+        .bind(instance_id)
+        .bind(&bundle)
+        .bind(chrono::Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to save debug bundle: {e}"),
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_debug_bundle(&self, instance_id: &str) -> Result<Option<serde_json::Value>> {
+        sqlx::query_scalar::<_, serde_json::Value>(
+            "SELECT bundle FROM workflow_debug_bundles WHERE instance_id = $1",
+        )
+        .bind(instance_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to get debug bundle: {e}"),
+        })
+    }
+
+    async fn update_rollup(&self, workflow_id: &str, update: &RunUpdate) -> Result<()> {
+        let mut tx = self.begin().await?;
+
+        // The row is locked until the transaction ends, so concurrent updates
+        // of the same rollup wait for each other
+        let empty =
+            serde_json::to_value(WorkflowRollup::new(workflow_id)).context(SerializationSnafu)?;
+        sqlx::query(
+            "INSERT INTO workflow_rollups (workflow_id, rollup) VALUES ($1, $2) ON CONFLICT (workflow_id) DO NOTHING",
+        )
+        .bind(workflow_id)
+        .bind(&empty)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to save rollup: {e}"),
+        })?;
+
+        let stored = sqlx::query_scalar::<_, serde_json::Value>(
+            "SELECT rollup FROM workflow_rollups WHERE workflow_id = $1 FOR UPDATE",
+        )
+        .bind(workflow_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to get rollup: {e}"),
+        })?;
+        let mut rollup: WorkflowRollup =
+            serde_json::from_value(stored).context(SerializationSnafu)?;
+        rollup.apply(update);
+        let rollup = serde_json::to_value(&rollup).context(SerializationSnafu)?;
+
+        sqlx::query("UPDATE workflow_rollups SET rollup = $1 WHERE workflow_id = $2")
+            .bind(&rollup)
+            .bind(workflow_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to save rollup: {e}"),
+            })?;
+
+        Self::commit(tx).await
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> Result<()> {
+        let mut tx = self.begin().await?;
+        sqlx::query("DELETE FROM workflow_rollups")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to clear rollups: {e}"),
+            })?;
+        for rollup in &rollups {
+            let value = serde_json::to_value(rollup).context(SerializationSnafu)?;
+            sqlx::query("INSERT INTO workflow_rollups (workflow_id, rollup) VALUES ($1, $2)")
+                .bind(&rollup.workflow_id)
+                .bind(&value)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to save rollup: {e}"),
+                })?;
+        }
+        Self::commit(tx).await
+    }
+
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        let rows = sqlx::query_scalar::<_, serde_json::Value>(
+            "SELECT rollup FROM workflow_rollups ORDER BY workflow_id",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to list rollups: {e}"),
+        })?;
+
+        let rollups = rows
+            .into_iter()
+            .map(|rollup| serde_json::from_value(rollup).context(SerializationSnafu))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((!rollups.is_empty()).then_some(rollups))
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query_as::<_, (String,)>(
+            "SELECT instance_id FROM workflow_events GROUP BY instance_id ORDER BY MIN(id)",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to list instances: {e}"),
+        })?;
+
+        Ok(rows.into_iter().map(|(instance_id,)| instance_id).collect())
+    }
 }
 
 #[cfg(test)]
@@ -275,6 +765,7 @@ mod tests {
             workflow_id: "workflow1".to_string(),
             timestamp: Utc::now(),
             initial_data: serde_json::json!({"input": "data"}),
+            metadata: None,
         };
 
         let event2 = WorkflowEvent::TaskStarted {
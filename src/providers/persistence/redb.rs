@@ -3,7 +3,10 @@ use chrono::Utc;
 use snafu::prelude::*;
 use std::sync::Arc;
 
-use crate::persistence::{Error, PersistenceProvider, Result, SerializationSnafu};
+use crate::durableengine::InstanceState;
+use crate::persistence::{
+    Error, PersistenceProvider, Result, RunUpdate, SerializationSnafu, WorkflowRollup,
+};
 use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
 
 #[derive(Debug)]
@@ -14,6 +17,12 @@ pub struct RedbPersistence {
 pub const EVENTS_TABLE: redb::TableDefinition<&str, &[u8]> = redb::TableDefinition::new("events");
 pub const CHECKPOINTS_TABLE: redb::TableDefinition<&str, &[u8]> =
     redb::TableDefinition::new("checkpoints");
+pub const STATES_TABLE: redb::TableDefinition<&str, &str> =
+    redb::TableDefinition::new("instance_states");
+pub const DEBUG_BUNDLES_TABLE: redb::TableDefinition<&str, &[u8]> =
+    redb::TableDefinition::new("debug_bundles");
+pub const ROLLUPS_TABLE: redb::TableDefinition<&str, &[u8]> =
+    redb::TableDefinition::new("workflow_rollups");
 
 impl RedbPersistence {
     /// Creates a new `RedbPersistence` instance with the database at the specified path.
@@ -23,7 +32,7 @@ impl RedbPersistence {
     /// Returns an error if:
     /// - The database cannot be created at the given path
     /// - A write transaction cannot be started
-    /// - The events or checkpoints tables cannot be opened
+    /// - The events, checkpoints, instance states, debug bundles or rollups tables cannot be opened
     /// - The transaction cannot be committed
     pub fn new(path: &str) -> Result<Self> {
         let db = redb::Database::create(path).map_err(|e| Error::Database {
@@ -43,6 +52,21 @@ impl RedbPersistence {
                 .map_err(|e| Error::Database {
                     message: format!("Failed to open checkpoints table: {e}"),
                 })?;
+            write_txn
+                .open_table(STATES_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open instance states table: {e}"),
+                })?;
+            write_txn
+                .open_table(DEBUG_BUNDLES_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open debug bundles table: {e}"),
+                })?;
+            write_txn
+                .open_table(ROLLUPS_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open rollups table: {e}"),
+                })?;
         }
         write_txn.commit().map_err(|e| Error::Database {
             message: format!("Failed to commit transaction: {e}"),
@@ -51,33 +75,65 @@ impl RedbPersistence {
     }
 }
 
+fn insert_event(write_txn: &redb::WriteTransaction, event: &WorkflowEvent) -> Result<()> {
+    let mut table = write_txn
+        .open_table(EVENTS_TABLE)
+        .map_err(|e| Error::Database {
+            message: format!("Failed to open events table: {e}"),
+        })?;
+    let key = format!(
+        "{}:{}",
+        event.instance_id(),
+        Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    );
+    let value = serde_json::to_vec(event).context(SerializationSnafu)?;
+    table
+        .insert(key.as_str(), value.as_slice())
+        .map_err(|e| Error::Database {
+            message: format!("Failed to insert event: {e}"),
+        })?;
+    Ok(())
+}
+
+fn insert_checkpoint(
+    write_txn: &redb::WriteTransaction,
+    checkpoint: &WorkflowCheckpoint,
+) -> Result<()> {
+    let mut table = write_txn
+        .open_table(CHECKPOINTS_TABLE)
+        .map_err(|e| Error::Database {
+            message: format!("Failed to open checkpoints table: {e}"),
+        })?;
+    let value = serde_json::to_vec(checkpoint).context(SerializationSnafu)?;
+    table
+        .insert(checkpoint.instance_id.as_str(), value.as_slice())
+        .map_err(|e| Error::Database {
+            message: format!("Failed to insert checkpoint: {e}"),
+        })?;
+    Ok(())
+}
+
+fn parse_state(value: &str) -> Result<InstanceState> {
+    value
+        .parse()
+        .map_err(|message| Error::Persistence { message })
+}
+
 #[async_trait]
 impl PersistenceProvider for RedbPersistence {
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_event",
+        skip_all
+    )]
     async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
         let db = self.db.clone();
         tokio::task::spawn_blocking(move || -> Result<()> {
             let write_txn = db.begin_write().map_err(|e| Error::Database {
                 message: format!("Failed to begin write transaction: {e}"),
             })?;
-            {
-                let mut table =
-                    write_txn
-                        .open_table(EVENTS_TABLE)
-                        .map_err(|e| Error::Database {
-                            message: format!("Failed to open events table: {e}"),
-                        })?;
-                let key = format!(
-                    "{}:{}",
-                    event.instance_id(),
-                    Utc::now().timestamp_nanos_opt().unwrap_or(0)
-                );
-                let value = serde_json::to_vec(&event).context(SerializationSnafu)?;
-                table
-                    .insert(key.as_str(), value.as_slice())
-                    .map_err(|e| Error::Database {
-                        message: format!("Failed to insert event: {e}"),
-                    })?;
-            }
+            insert_event(&write_txn, &event)?;
             write_txn.commit().map_err(|e| Error::Database {
                 message: format!("Failed to commit transaction: {e}"),
             })?;
@@ -124,26 +180,42 @@ impl PersistenceProvider for RedbPersistence {
         })?
     }
 
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_checkpoint",
+        skip_all
+    )]
     async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
         let db = self.db.clone();
         tokio::task::spawn_blocking(move || -> Result<()> {
             let write_txn = db.begin_write().map_err(|e| Error::Database {
                 message: format!("Failed to begin write transaction: {e}"),
             })?;
-            {
-                let mut table =
-                    write_txn
-                        .open_table(CHECKPOINTS_TABLE)
-                        .map_err(|e| Error::Database {
-                            message: format!("Failed to open checkpoints table: {e}"),
-                        })?;
-                let value = serde_json::to_vec(&checkpoint).context(SerializationSnafu)?;
-                table
-                    .insert(checkpoint.instance_id.as_str(), value.as_slice())
-                    .map_err(|e| Error::Database {
-                        message: format!("Failed to insert checkpoint: {e}"),
-                    })?;
-            }
+            insert_checkpoint(&write_txn, &checkpoint)?;
+            write_txn.commit().map_err(|e| Error::Database {
+                message: format!("Failed to commit transaction: {e}"),
+            })?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let write_txn = db.begin_write().map_err(|e| Error::Database {
+                message: format!("Failed to begin write transaction: {e}"),
+            })?;
+            insert_event(&write_txn, &event)?;
+            insert_checkpoint(&write_txn, &checkpoint)?;
             write_txn.commit().map_err(|e| Error::Database {
                 message: format!("Failed to commit transaction: {e}"),
             })?;
@@ -185,4 +257,274 @@ impl PersistenceProvider for RedbPersistence {
             message: format!("Task join error: {e}"),
         })?
     }
+
+    async fn get_state(&self, instance_id: &str) -> Result<Option<InstanceState>> {
+        let db = self.db.clone();
+        let instance_id = instance_id.to_string();
+        tokio::task::spawn_blocking(move || -> Result<Option<InstanceState>> {
+            let read_txn = db.begin_read().map_err(|e| Error::Database {
+                message: format!("Failed to begin read transaction: {e}"),
+            })?;
+            let table = read_txn
+                .open_table(STATES_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open instance states table: {e}"),
+                })?;
+            let state = table
+                .get(instance_id.as_str())
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to get instance state: {e}"),
+                })?;
+            state.map(|value| parse_state(value.value())).transpose()
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn transition_state(
+        &self,
+        instance_id: &str,
+        from: Option<InstanceState>,
+        to: InstanceState,
+    ) -> Result<bool> {
+        let db = self.db.clone();
+        let instance_id = instance_id.to_string();
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let write_txn = db.begin_write().map_err(|e| Error::Database {
+                message: format!("Failed to begin write transaction: {e}"),
+            })?;
+            {
+                let mut table =
+                    write_txn
+                        .open_table(STATES_TABLE)
+                        .map_err(|e| Error::Database {
+                            message: format!("Failed to open instance states table: {e}"),
+                        })?;
+                let current = table
+                    .get(instance_id.as_str())
+                    .map_err(|e| Error::Database {
+                        message: format!("Failed to get instance state: {e}"),
+                    })?
+                    .map(|value| parse_state(value.value()))
+                    .transpose()?;
+                if current != from {
+                    return Ok(false);
+                }
+                table
+                    .insert(instance_id.as_str(), to.as_str())
+                    .map_err(|e| Error::Database {
+                        message: format!("Failed to insert instance state: {e}"),
+                    })?;
+            }
+            write_txn.commit().map_err(|e| Error::Database {
+                message: format!("Failed to commit transaction: {e}"),
+            })?;
+            Ok(true)
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn save_debug_bundle(&self, instance_id: &str, bundle: serde_json::Value) -> Result<()> {
+        let db = self.db.clone();
+        let instance_id = instance_id.to_string();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let value = serde_json::to_vec(&bundle).context(SerializationSnafu)?;
+            let write_txn = db.begin_write().map_err(|e| Error::Database {
+                message: format!("Failed to begin write transaction: {e}"),
+            })?;
+            {
+                let mut table =
+                    write_txn
+                        .open_table(DEBUG_BUNDLES_TABLE)
+                        .map_err(|e| Error::Database {
+                            message: format!("Failed to open debug bundles table: {e}"),
+                        })?;
+                table
+                    .insert(instance_id.as_str(), value.as_slice())
+                    .map_err(|e| Error::Database {
+                        message: format!("Failed to insert debug bundle: {e}"),
+                    })?;
+            }
+            write_txn.commit().map_err(|e| Error::Database {
+                message: format!("Failed to commit transaction: {e}"),
+            })?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn get_debug_bundle(&self, instance_id: &str) -> Result<Option<serde_json::Value>> {
+        let db = self.db.clone();
+        let instance_id = instance_id.to_string();
+        tokio::task::spawn_blocking(move || -> Result<Option<serde_json::Value>> {
+            let read_txn = db.begin_read().map_err(|e| Error::Database {
+                message: format!("Failed to begin read transaction: {e}"),
+            })?;
+            let table = read_txn
+                .open_table(DEBUG_BUNDLES_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open debug bundles table: {e}"),
+                })?;
+            table
+                .get(instance_id.as_str())
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to get debug bundle: {e}"),
+                })?
+                .map(|value| serde_json::from_slice(value.value()).context(SerializationSnafu))
+                .transpose()
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn update_rollup(&self, workflow_id: &str, update: &RunUpdate) -> Result<()> {
+        let db = self.db.clone();
+        let workflow_id = workflow_id.to_string();
+        let update = update.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let write_txn = db.begin_write().map_err(|e| Error::Database {
+                message: format!("Failed to begin write transaction: {e}"),
+            })?;
+            {
+                let mut table =
+                    write_txn
+                        .open_table(ROLLUPS_TABLE)
+                        .map_err(|e| Error::Database {
+                            message: format!("Failed to open rollups table: {e}"),
+                        })?;
+                let stored = table
+                    .get(workflow_id.as_str())
+                    .map_err(|e| Error::Database {
+                        message: format!("Failed to get rollup: {e}"),
+                    })?
+                    .map(|value| serde_json::from_slice(value.value()).context(SerializationSnafu))
+                    .transpose()?;
+                let mut rollup = stored.unwrap_or_else(|| WorkflowRollup::new(&workflow_id));
+                rollup.apply(&update);
+                let value = serde_json::to_vec(&rollup).context(SerializationSnafu)?;
+                table
+                    .insert(workflow_id.as_str(), value.as_slice())
+                    .map_err(|e| Error::Database {
+                        message: format!("Failed to insert rollup: {e}"),
+                    })?;
+            }
+            write_txn.commit().map_err(|e| Error::Database {
+                message: format!("Failed to commit transaction: {e}"),
+            })?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> Result<()> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let write_txn = db.begin_write().map_err(|e| Error::Database {
+                message: format!("Failed to begin write transaction: {e}"),
+            })?;
+            write_txn
+                .delete_table(ROLLUPS_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to clear rollups table: {e}"),
+                })?;
+            {
+                let mut table =
+                    write_txn
+                        .open_table(ROLLUPS_TABLE)
+                        .map_err(|e| Error::Database {
+                            message: format!("Failed to open rollups table: {e}"),
+                        })?;
+                for rollup in &rollups {
+                    let value = serde_json::to_vec(rollup).context(SerializationSnafu)?;
+                    table
+                        .insert(rollup.workflow_id.as_str(), value.as_slice())
+                        .map_err(|e| Error::Database {
+                            message: format!("Failed to insert rollup: {e}"),
+                        })?;
+                }
+            }
+            write_txn.commit().map_err(|e| Error::Database {
+                message: format!("Failed to commit transaction: {e}"),
+            })?;
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || -> Result<Option<Vec<WorkflowRollup>>> {
+            let read_txn = db.begin_read().map_err(|e| Error::Database {
+                message: format!("Failed to begin read transaction: {e}"),
+            })?;
+            let table = read_txn
+                .open_table(ROLLUPS_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open rollups table: {e}"),
+                })?;
+            let range = table.range::<&str>(..).map_err(|e| Error::Database {
+                message: format!("Failed to create range: {e}"),
+            })?;
+            let mut rollups = Vec::new();
+            for item in range {
+                let (_, value) = item.map_err(|e| Error::Database {
+                    message: format!("Failed to read item: {e}"),
+                })?;
+                rollups.push(serde_json::from_slice(value.value()).context(SerializationSnafu)?);
+            }
+            Ok((!rollups.is_empty()).then_some(rollups))
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+            let read_txn = db.begin_read().map_err(|e| Error::Database {
+                message: format!("Failed to begin read transaction: {e}"),
+            })?;
+            let table = read_txn
+                .open_table(EVENTS_TABLE)
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to open events table: {e}"),
+                })?;
+            let range = table.range::<&str>(..).map_err(|e| Error::Database {
+                message: format!("Failed to create range: {e}"),
+            })?;
+            // Keys are "<instance_id>:<timestamp>"
+            let mut instance_ids = std::collections::BTreeSet::new();
+            for item in range {
+                let (key, _) = item.map_err(|e| Error::Database {
+                    message: format!("Failed to read item: {e}"),
+                })?;
+                if let Some((instance_id, _)) = key.value().rsplit_once(':') {
+                    instance_ids.insert(instance_id.to_string());
+                }
+            }
+            Ok(instance_ids.into_iter().collect())
+        })
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Task join error: {e}"),
+        })?
+    }
 }
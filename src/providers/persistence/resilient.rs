@@ -0,0 +1,513 @@
+//! Persistence that rides out outages of the store it wraps
+//!
+//! A failed write is retried with exponential backoff. If the store is still
+//! unavailable, the write is kept in a bounded in-memory spill buffer and the
+//! instance continues; spilled writes are flushed to the store in the order
+//! they were made once it recovers, before any newer write. While writes are
+//! spilled the provider is degraded: reads merge the spilled writes with what
+//! the store returns, and `jackdaw serve` rejects requests that start or
+//! change instances until the buffer has been flushed.
+//!
+//! Serialization errors are not retried, and when the buffer is full writes
+//! fail as they would without this layer. Instance state transitions and
+//! leases are checked against the store, so they are retried but never
+//! spilled, and a spilled write the store refuses for good, such as one
+//! fenced off by a newer lease, is dropped when the buffer is flushed. Queued
+//! workflow starts and rollup updates are passed straight to the store, and
+//! the change feed is read from it, so spilled events join the feed once they
+//! are flushed.
+
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::durableengine::InstanceState;
+use crate::persistence::{
+    Change, Error, Lease, PersistenceProvider, QueuedStart, Result, RunUpdate, WorkflowRollup,
+};
+use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
+
+/// How writes to an unavailable store are retried and buffered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResilienceOptions {
+    /// Retries of a failed write before it is spilled
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub backoff: Duration,
+    /// Longest delay between retries, and between attempts to flush the buffer
+    pub max_backoff: Duration,
+    /// Writes kept while the store is unavailable; 0 to fail instead
+    pub spill_capacity: usize,
+}
+
+impl Default for ResilienceOptions {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            spill_capacity: 10_000,
+        }
+    }
+}
+
+impl ResilienceOptions {
+    /// Delay before retry `attempt`, counting from 0
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// A write that has not reached the store yet
+#[derive(Debug, Clone)]
+enum Write {
+    Event(WorkflowEvent),
+    Checkpoint(WorkflowCheckpoint),
+    /// A task's completion, committed as one write
+    Task(WorkflowEvent, WorkflowCheckpoint),
+}
+
+impl Write {
+    fn instance_id(&self) -> &str {
+        match self {
+            Self::Event(event) | Self::Task(event, _) => event.instance_id(),
+            Self::Checkpoint(checkpoint) => &checkpoint.instance_id,
+        }
+    }
+
+    async fn apply(&self, store: &dyn PersistenceProvider) -> Result<()> {
+        match self {
+            Self::Event(event) => store.save_event(event.clone()).await,
+            Self::Checkpoint(checkpoint) => store.save_checkpoint(checkpoint.clone()).await,
+            Self::Task(event, checkpoint) => {
+                store.commit_task(event.clone(), checkpoint.clone()).await
+            }
+        }
+    }
+}
+
+/// Whether a failed write may succeed when retried
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Persistence { .. } | Error::Database { .. } => true,
+        Error::Serialization { .. }
+        | Error::EventNotFound { .. }
+        | Error::CheckpointNotFound { .. }
+        | Error::LeaseLost { .. } => false,
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    store: Arc<dyn PersistenceProvider>,
+    options: ResilienceOptions,
+    /// Writes waiting for the store, oldest first; the lock also orders writes
+    spill: Mutex<VecDeque<Write>>,
+    /// Whether a background flush of the spill buffer is running
+    flushing: AtomicBool,
+}
+
+impl Shared {
+    /// Flush spilled writes in order, stopping at the first transient failure
+    async fn flush(&self, spill: &mut VecDeque<Write>) -> Result<()> {
+        while let Some(write) = spill.front() {
+            match write.apply(self.store.as_ref()).await {
+                Ok(()) => {}
+                Err(e) if is_transient(&e) => return Err(e),
+                Err(e) => tracing::warn!(
+                    "Dropping spilled write for instance {}: {e}",
+                    write.instance_id()
+                ),
+            }
+            spill.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Apply a write, retrying transient failures
+    async fn apply_with_retries(&self, write: &Write) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match write.apply(self.store.as_ref()).await {
+                Err(e) if is_transient(&e) && attempt < self.options.retries => {
+                    tracing::debug!("Persistence write failed, retrying: {e}");
+                    tokio::time::sleep(self.options.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Persistence that retries failed writes and buffers them during outages
+#[derive(Debug, Clone)]
+pub struct ResilientPersistence {
+    shared: Arc<Shared>,
+}
+
+impl ResilientPersistence {
+    #[must_use]
+    pub fn new(store: Arc<dyn PersistenceProvider>, options: ResilienceOptions) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                store,
+                options,
+                spill: Mutex::new(VecDeque::new()),
+                flushing: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Number of writes waiting for the store
+    pub async fn spilled(&self) -> usize {
+        self.shared.spill.lock().await.len()
+    }
+
+    async fn write(&self, write: Write) -> Result<()> {
+        let mut spill = self.shared.spill.lock().await;
+
+        // Newer writes must not overtake spilled ones
+        if !spill.is_empty() && self.shared.flush(&mut spill).await.is_ok() {
+            tracing::info!("Persistence recovered, spilled writes flushed");
+        }
+        let error = if spill.is_empty() {
+            match self.shared.apply_with_retries(&write).await {
+                Ok(()) => return Ok(()),
+                Err(e) if is_transient(&e) => e,
+                Err(e) => return Err(e),
+            }
+        } else {
+            Error::Persistence {
+                message: "earlier writes are still waiting for the store".to_string(),
+            }
+        };
+
+        if spill.len() >= self.shared.options.spill_capacity {
+            return Err(Error::Persistence {
+                message: format!(
+                    "store unavailable and the spill buffer of {} writes is full: {error}",
+                    self.shared.options.spill_capacity
+                ),
+            });
+        }
+        if spill.is_empty() {
+            tracing::warn!("Persistence unavailable, buffering writes in memory: {error}");
+        }
+        spill.push_back(write);
+        drop(spill);
+        self.spawn_flush();
+        Ok(())
+    }
+
+    /// Flush the spill buffer in the background until it is empty
+    fn spawn_flush(&self) {
+        if self.shared.flushing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let shared = self.shared.clone();
+        tokio::spawn(async move {
+            let mut attempt = 0;
+            loop {
+                tokio::time::sleep(shared.options.delay(attempt)).await;
+                let mut spill = shared.spill.lock().await;
+                match shared.flush(&mut spill).await {
+                    Ok(()) => {
+                        tracing::info!("Persistence recovered, spilled writes flushed");
+                        shared.flushing.store(false, Ordering::Release);
+                        return;
+                    }
+                    Err(e) => {
+                        tracing::debug!("Persistence still unavailable: {e}");
+                        attempt = attempt.saturating_add(1);
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl PersistenceProvider for ResilientPersistence {
+    async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
+        self.write(Write::Event(event)).await
+    }
+
+    async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
+        let spill = self.shared.spill.lock().await;
+        let mut events = self.shared.store.get_events(instance_id).await?;
+        events.extend(spill.iter().filter_map(|write| match write {
+            Write::Event(event) | Write::Task(event, _) if event.instance_id() == instance_id => {
+                Some(event.clone())
+            }
+            Write::Event(_) | Write::Checkpoint(_) | Write::Task(..) => None,
+        }));
+        Ok(events)
+    }
+
+    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
+        self.write(Write::Checkpoint(checkpoint)).await
+    }
+
+    async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
+        let spill = self.shared.spill.lock().await;
+        let spilled = spill.iter().rev().find_map(|write| match write {
+            Write::Checkpoint(checkpoint) | Write::Task(_, checkpoint)
+                if checkpoint.instance_id == instance_id =>
+            {
+                Some(checkpoint.clone())
+            }
+            Write::Checkpoint(_) | Write::Task(..) | Write::Event(_) => None,
+        });
+        match spilled {
+            Some(checkpoint) => Ok(Some(checkpoint)),
+            None => self.shared.store.get_checkpoint(instance_id).await,
+        }
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        let spill = self.shared.spill.lock().await;
+        let mut ids = self.shared.store.list_instance_ids().await?;
+        for write in spill.iter() {
+            if !ids.iter().any(|id| id == write.instance_id()) {
+                ids.push(write.instance_id().to_string());
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> Result<()> {
+        self.write(Write::Task(event, checkpoint)).await
+    }
+
+    async fn get_state(&self, instance_id: &str) -> Result<Option<InstanceState>> {
+        self.shared.store.get_state(instance_id).await
+    }
+
+    async fn transition_state(
+        &self,
+        instance_id: &str,
+        from: Option<InstanceState>,
+        to: InstanceState,
+    ) -> Result<bool> {
+        let mut attempt = 0;
+        loop {
+            match self
+                .shared
+                .store
+                .transition_state(instance_id, from, to)
+                .await
+            {
+                Err(e) if is_transient(&e) && attempt < self.shared.options.retries => {
+                    tracing::debug!("Recording instance state failed, retrying: {e}");
+                    tokio::time::sleep(self.shared.options.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn acquire_lease(
+        &self,
+        instance_id: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<Option<Lease>> {
+        let mut attempt = 0;
+        loop {
+            match self
+                .shared
+                .store
+                .acquire_lease(instance_id, owner, ttl)
+                .await
+            {
+                Err(e) if is_transient(&e) && attempt < self.shared.options.retries => {
+                    tracing::debug!("Taking instance lease failed, retrying: {e}");
+                    tokio::time::sleep(self.shared.options.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn release_lease(&self, lease: &Lease) -> Result<()> {
+        self.shared.store.release_lease(lease).await
+    }
+
+    async fn changes(
+        &self,
+        after: u64,
+        instance_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        self.shared.store.changes(after, instance_id, limit).await
+    }
+
+    async fn enqueue_start(&self, start: QueuedStart) -> Result<()> {
+        self.shared.store.enqueue_start(start).await
+    }
+
+    async fn queued_starts(&self, limit: usize) -> Result<Vec<QueuedStart>> {
+        self.shared.store.queued_starts(limit).await
+    }
+
+    async fn remove_start(&self, instance_id: &str) -> Result<()> {
+        self.shared.store.remove_start(instance_id).await
+    }
+
+    async fn save_debug_bundle(&self, instance_id: &str, bundle: serde_json::Value) -> Result<()> {
+        self.shared
+            .store
+            .save_debug_bundle(instance_id, bundle)
+            .await
+    }
+
+    async fn get_debug_bundle(&self, instance_id: &str) -> Result<Option<serde_json::Value>> {
+        self.shared.store.get_debug_bundle(instance_id).await
+    }
+
+    async fn update_rollup(&self, workflow_id: &str, update: &RunUpdate) -> Result<()> {
+        self.shared.store.update_rollup(workflow_id, update).await
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> Result<()> {
+        self.shared.store.replace_rollups(rollups).await
+    }
+
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        self.shared.store.workflow_rollups().await
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.shared.flushing.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::wildcard_enum_match_arm)]
+
+    use super::*;
+    use crate::providers::persistence::InMemoryPersistence;
+    use chrono::Utc;
+
+    /// A store that fails every operation while `down` is set
+    #[derive(Debug, Default)]
+    struct FlakyStore {
+        inner: InMemoryPersistence,
+        down: AtomicBool,
+    }
+
+    impl FlakyStore {
+        fn check(&self) -> Result<()> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(Error::Database {
+                    message: "connection refused".to_string(),
+                });
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl PersistenceProvider for FlakyStore {
+        async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
+            self.check()?;
+            self.inner.save_event(event).await
+        }
+
+        async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
+            self.check()?;
+            self.inner.get_events(instance_id).await
+        }
+
+        async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
+            self.check()?;
+            self.inner.save_checkpoint(checkpoint).await
+        }
+
+        async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
+            self.check()?;
+            self.inner.get_checkpoint(instance_id).await
+        }
+
+        async fn list_instance_ids(&self) -> Result<Vec<String>> {
+            self.check()?;
+            self.inner.list_instance_ids().await
+        }
+    }
+
+    fn task_started(task_name: &str) -> WorkflowEvent {
+        WorkflowEvent::TaskStarted {
+            instance_id: "i-1".to_string(),
+            task_name: task_name.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn options(spill_capacity: usize) -> ResilienceOptions {
+        ResilienceOptions {
+            retries: 1,
+            backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(20),
+            spill_capacity,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spilled_writes_flush_in_order() {
+        let store = Arc::new(FlakyStore::default());
+        let persistence = ResilientPersistence::new(store.clone(), options(10));
+
+        persistence.save_event(task_started("a")).await.unwrap();
+        store.down.store(true, Ordering::SeqCst);
+        persistence.save_event(task_started("b")).await.unwrap();
+        persistence.save_event(task_started("c")).await.unwrap();
+        assert!(persistence.is_degraded());
+        assert_eq!(persistence.spilled().await, 2);
+
+        store.down.store(false, Ordering::SeqCst);
+        persistence.save_event(task_started("d")).await.unwrap();
+        assert_eq!(persistence.spilled().await, 0);
+
+        let names: Vec<String> = store
+            .inner
+            .get_events("i-1")
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                WorkflowEvent::TaskStarted { task_name, .. } => Some(task_name.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_full_spill_buffer_fails_writes() {
+        let store = Arc::new(FlakyStore::default());
+        store.down.store(true, Ordering::SeqCst);
+        let persistence = ResilientPersistence::new(store, options(1));
+
+        persistence.save_event(task_started("a")).await.unwrap();
+        let error = persistence
+            .save_event(task_started("b"))
+            .await
+            .expect_err("the buffer is full");
+        assert!(error.to_string().contains("spill buffer"), "{error}");
+    }
+}
@@ -26,3 +26,41 @@ CREATE TABLE IF NOT EXISTS workflow_checkpoints (
     data JSONB NOT NULL,
     timestamp TIMESTAMPTZ NOT NULL
 );
+
+-- Instance States Table: Stores the lifecycle state of each workflow instance
+CREATE TABLE IF NOT EXISTS workflow_instance_states (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    state TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- Instance Leases Table: Stores which process owns each workflow instance
+CREATE TABLE IF NOT EXISTS workflow_instance_leases (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    owner TEXT NOT NULL,
+    token BIGINT NOT NULL,              -- fencing token, increased whenever the owner changes
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Workflow Queue Table: Stores workflow starts waiting for a worker
+CREATE TABLE IF NOT EXISTS workflow_queue (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    workflow JSONB NOT NULL,
+    input JSONB NOT NULL,
+    enqueued_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_queue_enqueued_at ON workflow_queue(enqueued_at);
+
+-- Debug Bundles Table: Stores the diagnostic bundle captured when an instance faulted
+CREATE TABLE IF NOT EXISTS workflow_debug_bundles (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    bundle JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+-- Workflow Rollups Table: Stores the running totals of the runs of each workflow
+CREATE TABLE IF NOT EXISTS workflow_rollups (
+    workflow_id TEXT PRIMARY KEY NOT NULL,
+    rollup JSONB NOT NULL
+);
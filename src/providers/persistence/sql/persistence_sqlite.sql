@@ -22,3 +22,41 @@ CREATE TABLE IF NOT EXISTS workflow_checkpoints (
     data TEXT NOT NULL,                 -- JSON serialized
     timestamp DATETIME NOT NULL
 );
+
+-- Instance States Table: Stores the lifecycle state of each workflow instance
+CREATE TABLE IF NOT EXISTS workflow_instance_states (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    state TEXT NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+
+-- Instance Leases Table: Stores which process owns each workflow instance
+CREATE TABLE IF NOT EXISTS workflow_instance_leases (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    owner TEXT NOT NULL,
+    token INTEGER NOT NULL,             -- fencing token, increased whenever the owner changes
+    expires_at INTEGER NOT NULL         -- milliseconds since the Unix epoch
+);
+
+-- Workflow Queue Table: Stores workflow starts waiting for a worker
+CREATE TABLE IF NOT EXISTS workflow_queue (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    workflow TEXT NOT NULL,             -- JSON serialized WorkflowDefinition
+    input TEXT NOT NULL,                -- JSON serialized
+    enqueued_at INTEGER NOT NULL        -- milliseconds since the Unix epoch
+);
+
+CREATE INDEX IF NOT EXISTS idx_queue_enqueued_at ON workflow_queue(enqueued_at);
+
+-- Debug Bundles Table: Stores the diagnostic bundle captured when an instance faulted
+CREATE TABLE IF NOT EXISTS workflow_debug_bundles (
+    instance_id TEXT PRIMARY KEY NOT NULL,
+    bundle TEXT NOT NULL,               -- JSON serialized DebugBundle
+    created_at DATETIME NOT NULL
+);
+
+-- Workflow Rollups Table: Stores the running totals of the runs of each workflow
+CREATE TABLE IF NOT EXISTS workflow_rollups (
+    workflow_id TEXT PRIMARY KEY NOT NULL,
+    rollup TEXT NOT NULL                -- JSON serialized WorkflowRollup
+);
@@ -1,13 +1,19 @@
-use crate::persistence::{Error, PersistenceProvider, Result, SerializationSnafu};
+use crate::durableengine::InstanceState;
+use crate::persistence::{
+    Change, Error, FencingTokens, Lease, PersistenceProvider, QueuedStart, Result, RunUpdate,
+    SerializationSnafu, WorkflowRollup, check_fence,
+};
 use crate::workflow::{WorkflowCheckpoint, WorkflowEvent};
 use async_trait::async_trait;
 use snafu::prelude::*;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::sqlite::{SqliteConnection, SqlitePool, SqlitePoolOptions};
+use std::time::Duration;
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct SqlitePersistence {
     pool: SqlitePool,
+    fencing: FencingTokens,
 }
 
 #[allow(dead_code)]
@@ -45,7 +51,10 @@ impl SqlitePersistence {
                 message: format!("Failed to execute schema: {e}"),
             })?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            fencing: FencingTokens::default(),
+        })
     }
 
     /// Create a new ``SQLite`` persistence provider with custom pool options
@@ -64,7 +73,99 @@ impl SqlitePersistence {
                 message: format!("Failed to execute schema: {e}"),
             })?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            fencing: FencingTokens::default(),
+        })
+    }
+
+    /// Begin a transaction
+    async fn begin(&self) -> Result<sqlx::Transaction<'_, sqlx::Sqlite>> {
+        self.pool.begin().await.map_err(|e| Error::Database {
+            message: format!("Failed to begin transaction: {e}"),
+        })
+    }
+
+    /// Commit a transaction
+    async fn commit(tx: sqlx::Transaction<'_, sqlx::Sqlite>) -> Result<()> {
+        tx.commit().await.map_err(|e| Error::Database {
+            message: format!("Failed to commit transaction: {e}"),
+        })
+    }
+
+    /// Refuse writes for an instance on `conn` once its lease has been taken over
+    async fn check_fence(&self, conn: &mut SqliteConnection, instance_id: &str) -> Result<()> {
+        let Some(token) = self.fencing.token(instance_id) else {
+            return Ok(());
+        };
+        let stored = sqlx::query_scalar::<_, i64>(
+            "SELECT token FROM workflow_instance_leases WHERE instance_id = ?",
+        )
+        .bind(instance_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to check instance lease: {e}"),
+        })?;
+
+        check_fence(
+            instance_id,
+            token,
+            stored.and_then(|stored| u64::try_from(stored).ok()),
+        )
+    }
+
+    /// Append an event to the instance's history on `conn`
+    async fn insert_event(conn: &mut SqliteConnection, event: &WorkflowEvent) -> Result<()> {
+        let instance_id = event.instance_id().to_string();
+        let event_type = Self::get_event_type(event);
+        let event_data = serde_json::to_string(event).context(SerializationSnafu)?;
+        let timestamp = chrono::Utc::now().to_rfc3339();
+
+        // Get the next sequence number for this instance
+        let sequence_number: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(sequence_number), -1) + 1 FROM workflow_events WHERE instance_id = ?"
+        )
+        .bind(&instance_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| Error::Database { message: format!("Failed to get sequence number: {e}") })?;
+
+        sqlx::query(
+            "INSERT INTO workflow_events (instance_id, event_type, event_data, timestamp, sequence_number) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&instance_id)
+        .bind(event_type)
+        .bind(&event_data)
+        .bind(&timestamp)
+        .bind(sequence_number)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| Error::Database { message: format!("Failed to save event: {e}") })?;
+
+        Ok(())
+    }
+
+    /// Replace the instance's checkpoint on `conn`
+    async fn upsert_checkpoint(
+        conn: &mut SqliteConnection,
+        checkpoint: &WorkflowCheckpoint,
+    ) -> Result<()> {
+        let data_json = serde_json::to_string(&checkpoint.data).context(SerializationSnafu)?;
+        let timestamp_str = checkpoint.timestamp.to_rfc3339();
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO workflow_checkpoints (instance_id, current_task, data, timestamp) VALUES (?, ?, ?, ?)"
+        )
+        .bind(&checkpoint.instance_id)
+        .bind(&checkpoint.current_task)
+        .bind(&data_json)
+        .bind(&timestamp_str)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| Error::Database { message: format!("Failed to save checkpoint: {e}") })?;
+
+        Ok(())
     }
 
     /// Get the event type name for a ``WorkflowEvent``
@@ -87,40 +188,30 @@ impl SqlitePersistence {
             WorkflowEvent::TaskSuspended { .. } => "TaskSuspended",
             WorkflowEvent::TaskResumed { .. } => "TaskResumed",
             WorkflowEvent::TaskFaulted { .. } => "TaskFaulted",
+            WorkflowEvent::TaskReaped { .. } => "TaskReaped",
+            WorkflowEvent::TaskCacheHit { .. } => "TaskCacheHit",
+            WorkflowEvent::TaskSkipped { .. } => "TaskSkipped",
+            WorkflowEvent::SwitchCaseMatched { .. } => "SwitchCaseMatched",
+            WorkflowEvent::ErrorCaught { .. } => "ErrorCaught",
+            WorkflowEvent::WatchesEvaluated { .. } => "WatchesEvaluated",
+            WorkflowEvent::ShadowCompared { .. } => "ShadowCompared",
         }
     }
 }
 
 #[async_trait]
 impl PersistenceProvider for SqlitePersistence {
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_event",
+        skip_all
+    )]
     async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
-        let instance_id = event.instance_id().to_string();
-        let event_type = Self::get_event_type(&event);
-        let event_data = serde_json::to_string(&event).context(SerializationSnafu)?;
-        let timestamp = chrono::Utc::now().to_rfc3339();
-
-        // Get the next sequence number for this instance
-        let sequence_number: i64 = sqlx::query_scalar(
-            "SELECT COALESCE(MAX(sequence_number), -1) + 1 FROM workflow_events WHERE instance_id = ?"
-        )
-        .bind(&instance_id)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| Error::Database { message: format!("Failed to get sequence number: {e}") })?;
-
-        sqlx::query(
-            "INSERT INTO workflow_events (instance_id, event_type, event_data, timestamp, sequence_number) VALUES (?, ?, ?, ?, ?)"
-        )
-        .bind(&instance_id)
-        .bind(event_type)
-        .bind(&event_data)
-        .bind(&timestamp)
-        .bind(sequence_number)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::Database { message: format!("Failed to save event: {e}") })?;
-
-        Ok(())
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, event.instance_id()).await?;
+        Self::insert_event(&mut tx, &event).await?;
+        Self::commit(tx).await
     }
 
     async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
@@ -142,22 +233,29 @@ impl PersistenceProvider for SqlitePersistence {
         Ok(events)
     }
 
+    #[tracing::instrument(
+        target = "jackdaw::profile",
+        level = "trace",
+        name = "persistence.save_checkpoint",
+        skip_all
+    )]
     async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
-        let data_json = serde_json::to_string(&checkpoint.data).context(SerializationSnafu)?;
-        let timestamp_str = checkpoint.timestamp.to_rfc3339();
-
-        sqlx::query(
-            "INSERT OR REPLACE INTO workflow_checkpoints (instance_id, current_task, data, timestamp) VALUES (?, ?, ?, ?)"
-        )
-        .bind(&checkpoint.instance_id)
-        .bind(&checkpoint.current_task)
-        .bind(&data_json)
-        .bind(&timestamp_str)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::Database { message: format!("Failed to save checkpoint: {e}") })?;
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, &checkpoint.instance_id).await?;
+        Self::upsert_checkpoint(&mut tx, &checkpoint).await?;
+        Self::commit(tx).await
+    }
 
-        Ok(())
+    async fn commit_task(
+        &self,
+        event: WorkflowEvent,
+        checkpoint: WorkflowCheckpoint,
+    ) -> Result<()> {
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, event.instance_id()).await?;
+        Self::insert_event(&mut tx, &event).await?;
+        Self::upsert_checkpoint(&mut tx, &checkpoint).await?;
+        Self::commit(tx).await
     }
 
     async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
@@ -188,6 +286,362 @@ impl PersistenceProvider for SqlitePersistence {
             None => Ok(None),
         }
     }
+
+    async fn get_state(&self, instance_id: &str) -> Result<Option<InstanceState>> {
+        let state = sqlx::query_scalar::<_, String>(
+            "SELECT state FROM workflow_instance_states WHERE instance_id = ?",
+        )
+        .bind(instance_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to get instance state: {e}"),
+        })?;
+
+        state
+            .map(|state| {
+                state
+                    .parse()
+                    .map_err(|message| Error::Persistence { message })
+            })
+            .transpose()
+    }
+
+    async fn transition_state(
+        &self,
+        instance_id: &str,
+        from: Option<InstanceState>,
+        to: InstanceState,
+    ) -> Result<bool> {
+        let mut tx = self.begin().await?;
+        self.check_fence(&mut tx, instance_id).await?;
+
+        // Only the first state of an instance is inserted; later ones must
+        // match the state they were checked against
+        let result = match from {
+            None => sqlx::query(
+                "INSERT INTO workflow_instance_states (instance_id, state, updated_at) VALUES (?, ?, ?) ON CONFLICT (instance_id) DO NOTHING",
+            )
+            .bind(instance_id)
+            .bind(to.as_str())
+            .bind(chrono::Utc::now())
+            .execute(&mut *tx)
+            .await,
+            Some(from) => sqlx::query(
+                "UPDATE workflow_instance_states SET state = ?, updated_at = ? WHERE instance_id = ? AND state = ?",
+            )
+            .bind(to.as_str())
+            .bind(chrono::Utc::now())
+            .bind(instance_id)
+            .bind(from.as_str())
+            .execute(&mut *tx)
+            .await,
+        }
+        .map_err(|e| Error::Database {
+            message: format!("Failed to save instance state: {e}"),
+        })?;
+
+        Self::commit(tx).await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn acquire_lease(
+        &self,
+        instance_id: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<Option<Lease>> {
+        // The token only changes hands with the lease, and another owner's
+        // lease is only taken once it has lapsed
+        let expires_at = Lease::expiry(ttl);
+        let token = sqlx::query_scalar::<_, i64>(
+            r"
+            INSERT INTO workflow_instance_leases (instance_id, owner, token, expires_at)
+            VALUES (?, ?, 1, ?)
+            ON CONFLICT (instance_id)
+            DO UPDATE SET
+                token = CASE
+                    WHEN workflow_instance_leases.owner = excluded.owner
+                    THEN workflow_instance_leases.token
+                    ELSE workflow_instance_leases.token + 1
+                END,
+                owner = excluded.owner,
+                expires_at = excluded.expires_at
+            WHERE workflow_instance_leases.owner = excluded.owner
+                OR workflow_instance_leases.expires_at <= ?
+            RETURNING token
+            ",
+        )
+        .bind(instance_id)
+        .bind(owner)
+        .bind(expires_at.timestamp_millis())
+        .bind(chrono::Utc::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to acquire instance lease: {e}"),
+        })?;
+
+        let lease = token.map(|token| Lease {
+            instance_id: instance_id.to_string(),
+            owner: owner.to_string(),
+            token: u64::try_from(token).unwrap_or_default(),
+            expires_at,
+        });
+        if let Some(lease) = &lease {
+            self.fencing.hold(lease);
+        }
+        Ok(lease)
+    }
+
+    async fn release_lease(&self, lease: &Lease) -> Result<()> {
+        // The lease is kept, lapsed, so the next owner's token is still newer
+        sqlx::query(
+            "UPDATE workflow_instance_leases SET expires_at = ? WHERE instance_id = ? AND owner = ? AND token = ?",
+        )
+        .bind(chrono::Utc::now().timestamp_millis())
+        .bind(&lease.instance_id)
+        .bind(&lease.owner)
+        .bind(i64::try_from(lease.token).unwrap_or(i64::MAX))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to release instance lease: {e}"),
+        })?;
+
+        self.fencing.release(lease);
+        Ok(())
+    }
+
+    async fn changes(
+        &self,
+        after: u64,
+        instance_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        // SQLite commits one write at a time, so event IDs become visible in order
+        let rows = sqlx::query_as::<_, (i64, String)>(
+            "SELECT id, event_data FROM workflow_events WHERE id > ?1 AND (?2 IS NULL OR instance_id = ?2) ORDER BY id LIMIT ?3",
+        )
+        .bind(i64::try_from(after).unwrap_or(i64::MAX))
+        .bind(instance_id)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to read change feed: {e}"),
+        })?;
+
+        rows.into_iter()
+            .map(|(id, event_data)| {
+                Ok(Change {
+                    offset: u64::try_from(id).unwrap_or_default(),
+                    event: serde_json::from_str(&event_data).context(SerializationSnafu)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn enqueue_start(&self, start: QueuedStart) -> Result<()> {
+        let workflow = serde_json::to_string(&start.workflow).context(SerializationSnafu)?;
+        let input = serde_json::to_string(&start.input).context(SerializationSnafu)?;
+
+        sqlx::query(
+            "INSERT INTO workflow_queue (instance_id, workflow, input, enqueued_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&start.instance_id)
+        .bind(&workflow)
+        .bind(&input)
+        .bind(start.enqueued_at.timestamp_millis())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to queue workflow start: {e}"),
+        })?;
+
+        Ok(())
+    }
+
+    async fn queued_starts(&self, limit: usize) -> Result<Vec<QueuedStart>> {
+        // Starts whose instance is running under a live lease are skipped
+        let rows = sqlx::query_as::<_, (String, String, String, i64)>(
+            r"
+            SELECT q.instance_id, q.workflow, q.input, q.enqueued_at
+            FROM workflow_queue q
+            LEFT JOIN workflow_instance_leases l ON l.instance_id = q.instance_id
+            WHERE l.instance_id IS NULL OR l.expires_at <= ?
+            ORDER BY q.enqueued_at, q.rowid
+            LIMIT ?
+            ",
+        )
+        .bind(chrono::Utc::now().timestamp_millis())
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to list queued workflow starts: {e}"),
+        })?;
+
+        rows.into_iter()
+            .map(|(instance_id, workflow, input, enqueued_at)| {
+                Ok(QueuedStart {
+                    instance_id,
+                    workflow: serde_json::from_str(&workflow).context(SerializationSnafu)?,
+                    input: serde_json::from_str(&input).context(SerializationSnafu)?,
+                    enqueued_at: chrono::DateTime::from_timestamp_millis(enqueued_at)
+                        .unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    async fn remove_start(&self, instance_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM workflow_queue WHERE instance_id = ?")
+            .bind(instance_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to remove queued workflow start: {e}"),
+            })?;
+
+        Ok(())
+    }
+
+    async fn save_debug_bundle(&self, instance_id: &str, bundle: serde_json::Value) -> Result<()> {
+        let bundle = serde_json::to_string(&bundle).context(SerializationSnafu)?;
+
+        sqlx::query(
+            r"
+            INSERT INTO workflow_debug_bundles (instance_id, bundle, created_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (instance_id)
+            DO UPDATE SET bundle = excluded.bundle, created_at = excluded.created_at
+            ",
+        )
+        .bind(instance_id)
+        .bind(&bundle)
+        .bind(chrono::Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to save debug bundle: {e}"),
+        })?;
+
+        Ok(())
+    }
+
+    async fn get_debug_bundle(&self, instance_id: &str) -> Result<Option<serde_json::Value>> {
+        let bundle = sqlx::query_scalar::<_, String>(
+            "SELECT bundle FROM workflow_debug_bundles WHERE instance_id = ?",
+        )
+        .bind(instance_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to get debug bundle: {e}"),
+        })?;
+
+        bundle
+            .map(|bundle| serde_json::from_str(&bundle).context(SerializationSnafu))
+            .transpose()
+    }
+
+    async fn update_rollup(&self, workflow_id: &str, update: &RunUpdate) -> Result<()> {
+        let mut tx = self.begin().await?;
+
+        // Writing first takes the write lock, so concurrent updates wait for
+        // this one instead of reading the rollup it is about to replace
+        let empty =
+            serde_json::to_string(&WorkflowRollup::new(workflow_id)).context(SerializationSnafu)?;
+        sqlx::query(
+            "INSERT INTO workflow_rollups (workflow_id, rollup) VALUES (?, ?) ON CONFLICT (workflow_id) DO NOTHING",
+        )
+        .bind(workflow_id)
+        .bind(&empty)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to save rollup: {e}"),
+        })?;
+
+        let stored = sqlx::query_scalar::<_, String>(
+            "SELECT rollup FROM workflow_rollups WHERE workflow_id = ?",
+        )
+        .bind(workflow_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to get rollup: {e}"),
+        })?;
+        let mut rollup: WorkflowRollup =
+            serde_json::from_str(&stored).context(SerializationSnafu)?;
+        rollup.apply(update);
+        let rollup = serde_json::to_string(&rollup).context(SerializationSnafu)?;
+
+        sqlx::query("UPDATE workflow_rollups SET rollup = ? WHERE workflow_id = ?")
+            .bind(&rollup)
+            .bind(workflow_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to save rollup: {e}"),
+            })?;
+
+        Self::commit(tx).await
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> Result<()> {
+        let mut tx = self.begin().await?;
+        sqlx::query("DELETE FROM workflow_rollups")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database {
+                message: format!("Failed to clear rollups: {e}"),
+            })?;
+        for rollup in &rollups {
+            let value = serde_json::to_string(rollup).context(SerializationSnafu)?;
+            sqlx::query("INSERT INTO workflow_rollups (workflow_id, rollup) VALUES (?, ?)")
+                .bind(&rollup.workflow_id)
+                .bind(&value)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| Error::Database {
+                    message: format!("Failed to save rollup: {e}"),
+                })?;
+        }
+        Self::commit(tx).await
+    }
+
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        let rows = sqlx::query_scalar::<_, String>(
+            "SELECT rollup FROM workflow_rollups ORDER BY workflow_id",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to list rollups: {e}"),
+        })?;
+
+        let rollups = rows
+            .iter()
+            .map(|rollup| serde_json::from_str(rollup).context(SerializationSnafu))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((!rollups.is_empty()).then_some(rollups))
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query_as::<_, (String,)>(
+            "SELECT instance_id FROM workflow_events GROUP BY instance_id ORDER BY MIN(id)",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database {
+            message: format!("Failed to list instances: {e}"),
+        })?;
+
+        Ok(rows.into_iter().map(|(instance_id,)| instance_id).collect())
+    }
 }
 
 #[cfg(test)]
@@ -211,6 +665,7 @@ mod tests {
             workflow_id: "workflow1".to_string(),
             timestamp: Utc::now(),
             initial_data: serde_json::json!({"input": "data"}),
+            metadata: None,
         };
 
         let event2 = WorkflowEvent::TaskStarted {
@@ -301,6 +756,72 @@ mod tests {
         assert_eq!(retrieved.data, serde_json::json!({"step": 2}));
     }
 
+    #[tokio::test]
+    async fn test_sqlite_persistence_commit_task() {
+        let persistence = SqlitePersistence::new(":memory:").await.unwrap();
+
+        let event = WorkflowEvent::TaskCompleted {
+            instance_id: "test-instance-5".to_string(),
+            task_name: "task1".to_string(),
+            result: serde_json::json!({"step": 1}),
+            timestamp: Utc::now(),
+            duration_ms: 10,
+        };
+        let checkpoint = WorkflowCheckpoint {
+            instance_id: "test-instance-5".to_string(),
+            current_task: "task1".to_string(),
+            data: serde_json::json!({"step": 1}),
+            timestamp: Utc::now(),
+        };
+        persistence.commit_task(event, checkpoint).await.unwrap();
+
+        let events = persistence.get_events("test-instance-5").await.unwrap();
+        assert_eq!(events.len(), 1);
+        let retrieved = persistence
+            .get_checkpoint("test-instance-5")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(retrieved.current_task, "task1");
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_persistence_transition_state() {
+        let persistence = SqlitePersistence::new(":memory:").await.unwrap();
+        assert_eq!(
+            persistence.get_state("test-instance-6").await.unwrap(),
+            None
+        );
+
+        assert!(
+            persistence
+                .transition_state("test-instance-6", None, InstanceState::Running)
+                .await
+                .unwrap()
+        );
+        // The recorded state no longer matches
+        assert!(
+            !persistence
+                .transition_state("test-instance-6", None, InstanceState::Faulted)
+                .await
+                .unwrap()
+        );
+        assert!(
+            persistence
+                .transition_state(
+                    "test-instance-6",
+                    Some(InstanceState::Running),
+                    InstanceState::Completed
+                )
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            persistence.get_state("test-instance-6").await.unwrap(),
+            Some(InstanceState::Completed)
+        );
+    }
+
     #[tokio::test]
     async fn test_sqlite_persistence_event_ordering() {
         let persistence = SqlitePersistence::new(":memory:").await.unwrap();
@@ -332,4 +853,126 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_sqlite_persistence_list_instance_ids() {
+        let persistence = SqlitePersistence::new(":memory:").await.unwrap();
+        assert!(persistence.list_instance_ids().await.unwrap().is_empty());
+
+        for instance_id in ["first", "second", "first"] {
+            let event = WorkflowEvent::TaskEntered {
+                instance_id: instance_id.to_string(),
+                task_name: "task".to_string(),
+                timestamp: Utc::now(),
+            };
+            persistence.save_event(event).await.unwrap();
+        }
+
+        assert_eq!(
+            persistence.list_instance_ids().await.unwrap(),
+            vec!["first".to_string(), "second".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_persistence_change_feed() {
+        let persistence = SqlitePersistence::new(":memory:").await.unwrap();
+        assert!(persistence.changes(0, None, 10).await.unwrap().is_empty());
+
+        for (instance_id, task_name) in [("first", "a"), ("second", "b"), ("first", "c")] {
+            let event = WorkflowEvent::TaskEntered {
+                instance_id: instance_id.to_string(),
+                task_name: task_name.to_string(),
+                timestamp: Utc::now(),
+            };
+            persistence.save_event(event).await.unwrap();
+        }
+
+        let changes = persistence.changes(0, None, 10).await.unwrap();
+        let offsets: Vec<_> = changes.iter().map(|change| change.offset).collect();
+        assert_eq!(offsets, vec![1, 2, 3]);
+
+        // Resuming after an offset skips what was already consumed
+        let changes = persistence.changes(1, None, 1).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes.first().unwrap().event.instance_id(), "second");
+
+        // The feed of one instance keeps the global offsets
+        let changes = persistence.changes(0, Some("first"), 10).await.unwrap();
+        let offsets: Vec<_> = changes.iter().map(|change| change.offset).collect();
+        assert_eq!(offsets, vec![1, 3]);
+        match changes.last().map(|change| &change.event) {
+            Some(WorkflowEvent::TaskEntered { task_name, .. }) => assert_eq!(task_name, "c"),
+            other => panic!("Expected TaskEntered, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_persistence_lease_fencing() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            dir.path().join("leases.db").display()
+        );
+        let zombie = SqlitePersistence::new(&url).await.unwrap();
+        let survivor = SqlitePersistence::new(&url).await.unwrap();
+        let started = |instance_id: &str| WorkflowEvent::TaskStarted {
+            instance_id: instance_id.to_string(),
+            task_name: "task".to_string(),
+            timestamp: Utc::now(),
+        };
+
+        let lease = zombie
+            .acquire_lease("test-instance-8", "zombie", Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.token, 1);
+        // Renewing keeps the token
+        let lease = zombie
+            .acquire_lease("test-instance-8", "zombie", Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.token, 1);
+        zombie.save_event(started("test-instance-8")).await.unwrap();
+
+        // The lapsed lease is taken over with a newer token
+        let taken = survivor
+            .acquire_lease("test-instance-8", "survivor", Duration::from_secs(60))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(taken.token, 2);
+        assert!(
+            zombie
+                .acquire_lease("test-instance-8", "zombie", Duration::from_secs(60))
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let error = zombie
+            .save_event(started("test-instance-8"))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::LeaseLost { token: 1, .. }));
+        survivor
+            .save_event(started("test-instance-8"))
+            .await
+            .unwrap();
+        assert_eq!(
+            survivor.get_events("test-instance-8").await.unwrap().len(),
+            2
+        );
+
+        // A released lease can be taken straight away
+        survivor.release_lease(&taken).await.unwrap();
+        let retaken = zombie
+            .acquire_lease("test-instance-8", "zombie", Duration::from_secs(60))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(retaken.token, 3);
+    }
 }
@@ -0,0 +1,67 @@
+use async_trait::async_trait;
+
+use crate::secrets::{Result, SecretsProvider};
+
+/// Secrets read from the environment of the jackdaw process
+///
+/// A secret named `API_KEY` is read from the variable `<prefix>API_KEY`. The
+/// prefix is empty by default.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSecretsProvider {
+    prefix: String,
+}
+
+impl EnvSecretsProvider {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read secrets from variables starting with `prefix`
+    #[must_use]
+    pub fn with_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for EnvSecretsProvider {
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(std::env::var(format!("{}{name}", self.prefix)).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reads_process_environment() {
+        let provider = EnvSecretsProvider::new();
+        assert_eq!(
+            provider.get("PATH").await.unwrap(),
+            std::env::var("PATH").ok()
+        );
+        assert!(
+            provider
+                .require("JACKDAW_TEST_MISSING_SECRET")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prefix() {
+        let provider = EnvSecretsProvider::with_prefix("PA");
+        assert_eq!(
+            provider.get("TH").await.unwrap(),
+            std::env::var("PATH").ok()
+        );
+    }
+}
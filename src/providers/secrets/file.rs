@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+use crate::secrets::{Error, Result, SecretsProvider};
+
+/// Secrets read from the files of a directory
+///
+/// A secret named `API_KEY` is the content of the file `<dir>/API_KEY`,
+/// without its trailing newline. This is how Docker and Kubernetes mount
+/// secrets into containers.
+#[derive(Debug, Clone)]
+pub struct FileSecretsProvider {
+    dir: PathBuf,
+}
+
+impl FileSecretsProvider {
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for FileSecretsProvider {
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        // Names cannot reach outside the directory
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(Error::InvalidName {
+                name: name.to_string(),
+            });
+        }
+        match tokio::fs::read_to_string(self.dir.join(name)).await {
+            Ok(content) => Ok(Some(
+                content
+                    .strip_suffix('\n')
+                    .map(|content| content.strip_suffix('\r').unwrap_or(content))
+                    .unwrap_or(&content)
+                    .to_string(),
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(Error::Read {
+                name: name.to_string(),
+                source,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reads_secret_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("API_KEY"), "s3cr3t\n").unwrap();
+        let provider = FileSecretsProvider::new(dir.path());
+
+        assert_eq!(
+            provider.get("API_KEY").await.unwrap().as_deref(),
+            Some("s3cr3t")
+        );
+        assert_eq!(provider.get("MISSING").await.unwrap(), None);
+        assert!(provider.get("../API_KEY").await.is_err());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod env;
+pub mod file;
+pub mod vault;
+
+pub use env::EnvSecretsProvider;
+pub use file::FileSecretsProvider;
+pub use vault::VaultSecretsProvider;
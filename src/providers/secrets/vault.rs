@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::secrets::{Error, Result, SecretsProvider};
+
+/// Secrets read from a HashiCorp Vault KV version 2 secrets engine
+///
+/// The secrets are the keys of the Vault secret at `<mount>/<path>`: a secret
+/// named `API_KEY` is the `API_KEY` key of that secret. The secret is read
+/// with the token of the provider each time a secret is looked up.
+#[derive(Debug, Clone)]
+pub struct VaultSecretsProvider {
+    client: reqwest::Client,
+    address: String,
+    token: String,
+    mount: String,
+    path: String,
+}
+
+impl VaultSecretsProvider {
+    /// Read secrets from the Vault server at `address`, e.g. `https://vault:8200`
+    ///
+    /// The KV engine is expected at the `secret` mount and the secrets at the
+    /// `jackdaw` path unless set otherwise.
+    #[must_use]
+    pub fn new(address: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            address: address.into().trim_end_matches('/').to_string(),
+            token: token.into(),
+            mount: "secret".to_string(),
+            path: "jackdaw".to_string(),
+        }
+    }
+
+    /// Read from the KV engine mounted at `mount`
+    #[must_use]
+    pub fn with_mount(mut self, mount: impl Into<String>) -> Self {
+        self.mount = mount.into().trim_matches('/').to_string();
+        self
+    }
+
+    /// Read the keys of the secret at `path`
+    #[must_use]
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into().trim_matches('/').to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for VaultSecretsProvider {
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        let url = format!("{}/v1/{}/data/{}", self.address, self.mount, self.path);
+        let response = self
+            .client
+            .get(&url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(|e| Error::Backend {
+                message: format!("{url}: {e}"),
+            })?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(Error::Backend {
+                message: format!("{url}: HTTP {}", response.status()),
+            });
+        }
+        let body: Value = response.json().await.map_err(|e| Error::Backend {
+            message: format!("{url}: {e}"),
+        })?;
+
+        Ok(body
+            .pointer("/data/data")
+            .and_then(|data| data.get(name))
+            .map(|value| match value {
+                Value::String(value) => value.clone(),
+                Value::Null
+                | Value::Bool(_)
+                | Value::Number(_)
+                | Value::Array(_)
+                | Value::Object(_) => value.to_string(),
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_reads_keys_of_kv_secret() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/kv/data/apps/orders"))
+            .and(header("X-Vault-Token", "root"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": { "data": { "API_KEY": "s3cr3t" }, "metadata": { "version": 3 } }
+            })))
+            .mount(&server)
+            .await;
+        let provider = VaultSecretsProvider::new(server.uri(), "root")
+            .with_mount("kv")
+            .with_path("apps/orders");
+
+        assert_eq!(
+            provider.get("API_KEY").await.unwrap().as_deref(),
+            Some("s3cr3t")
+        );
+        assert_eq!(provider.get("MISSING").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,178 @@
+use ::layout::backends::svg::SVGWriter;
+use ::layout::gv::{DotParser, GraphBuilder};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    CallGraph, Capabilities, DiagramFilter, DiagramFormat, DiagramStyle, ExecutionState,
+    GraphvizProvider, OutputPathRequiredSnafu, Result, VisualizationProvider, VisualizationSnafu,
+};
+
+/// Draws SVG diagrams without any tool installed
+///
+/// The diagram is written as DOT, like the Graphviz provider writes it, and
+/// laid out and drawn by `layout-rs` in-process, so it renders in CI
+/// containers that have neither Graphviz nor D2. Its layout is simpler than
+/// Graphviz's: tasks are drawn as boxes or circles, the clusters of the tasks
+/// `do`, `for`, `fork` and `try` tasks run are not outlined, and borders are
+/// neither thick nor dashed, so the state of a task is shown by its color alone.
+#[derive(Debug, Clone, Default)]
+pub struct BuiltinProvider {
+    /// Writes the DOT source the diagram is drawn from
+    dot: GraphvizProvider,
+}
+
+impl BuiltinProvider {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            dot: GraphvizProvider::new(),
+        }
+    }
+
+    /// Draw diagrams in `style`
+    #[must_use]
+    pub fn with_style(mut self, style: DiagramStyle) -> Self {
+        self.dot = self.dot.with_style(style);
+        self
+    }
+
+    /// Draw only the tasks `filter` selects
+    #[must_use]
+    pub fn with_filter(mut self, filter: DiagramFilter) -> Self {
+        self.dot = self.dot.with_filter(filter);
+        self
+    }
+
+    /// Lay DOT source out and write it as SVG to `output_path`
+    fn render_dot(
+        dot_source: &str,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+    ) -> Result<()> {
+        if format != DiagramFormat::Svg {
+            return VisualizationSnafu {
+                message: format!("the built-in renderer cannot render {format:?} diagrams"),
+            }
+            .fail();
+        }
+        let output_path = output_path.ok_or_else(|| OutputPathRequiredSnafu { format }.build())?;
+
+        let mut parser = DotParser::new(dot_source);
+        let graph = parser.process().map_err(|message| {
+            VisualizationSnafu {
+                message: format!("the built-in renderer cannot read the diagram: {message}"),
+            }
+            .build()
+        })?;
+        let mut builder = GraphBuilder::new();
+        builder.visit_graph(&graph);
+        let mut visual = builder.get();
+        let mut svg = SVGWriter::new();
+        visual.do_it(false, false, false, &mut svg);
+
+        std::fs::write(output_path, svg.finalize()).context(super::IoSnafu)
+    }
+}
+
+impl VisualizationProvider for BuiltinProvider {
+    fn name(&self) -> &'static str {
+        "builtin"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            formats: &[DiagramFormat::Svg],
+            execution_state: true,
+        }
+    }
+
+    fn generate_source(
+        &self,
+        workflow: &WorkflowDefinition,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<String> {
+        self.dot.generate_source(workflow, execution_state)
+    }
+
+    fn render(
+        &self,
+        workflow: &WorkflowDefinition,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<()> {
+        let dot_source = self.generate_source(workflow, execution_state)?;
+        Self::render_dot(&dot_source, output_path, format)
+    }
+
+    fn styled(&self, style: &DiagramStyle) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_style(style.clone())))
+    }
+
+    fn filtered(&self, filter: &DiagramFilter) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_filter(filter.clone())))
+    }
+
+    fn render_call_graph(
+        &self,
+        graph: &CallGraph,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+    ) -> Result<()> {
+        Self::render_dot(&self.dot.call_graph_to_dot(graph), output_path, format)
+    }
+
+    fn is_available(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn version(&self) -> Result<String> {
+        Ok(format!("built-in ({})", env!("CARGO_PKG_VERSION")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_renders_svg_without_tools() {
+        let workflow: WorkflowDefinition = serde_yaml::from_str(
+            r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: greet
+  version: '1.0.0'
+do:
+  - hello:
+      set:
+        message: hello
+  - check:
+      switch:
+        - done:
+            then: end
+",
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("greet.svg");
+
+        BuiltinProvider::new()
+            .render(&workflow, Some(&path), DiagramFormat::Svg, None)
+            .unwrap();
+        let svg = std::fs::read_to_string(&path).unwrap();
+        assert!(svg.contains("<svg"), "{svg}");
+        assert!(svg.contains("hello"), "{svg}");
+
+        let err = BuiltinProvider::new()
+            .render(&workflow, Some(&path), DiagramFormat::Png, None)
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot render Png"), "{err}");
+    }
+}
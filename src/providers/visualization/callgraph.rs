@@ -0,0 +1,299 @@
+//! The call graph between the workflows of a registry
+//!
+//! Workflows call each other with `run.workflow` tasks. A [`CallGraph`] holds
+//! one edge per such task, from the calling workflow to the registered
+//! workflow its reference resolves to, so that references to workflows that
+//! are not registered and workflows that end up calling themselves can be
+//! found, and highlighted when the graph is rendered.
+
+use serde_json::Value;
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::durableengine::versions::VersionReq;
+
+/// A `run.workflow` task calling another workflow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowCall {
+    /// Key of the calling workflow, `namespace/name/version`
+    pub caller: String,
+    /// Name of the task making the call
+    pub task: String,
+    /// The called workflow as referenced, `namespace/name/version` with the
+    /// version possibly a range
+    pub reference: String,
+    /// Key of the registered workflow the reference resolves to; `None` if
+    /// no registered workflow matches it
+    pub callee: Option<String>,
+}
+
+/// The workflows of a registry and the calls between them
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    /// Keys of the registered workflows, `namespace/name/version`
+    pub workflows: BTreeSet<String>,
+    /// Every `run.workflow` task of the registered workflows
+    pub calls: Vec<WorkflowCall>,
+}
+
+impl CallGraph {
+    /// The call graph between `workflows`
+    ///
+    /// A reference naming a version range resolves to the highest registered
+    /// version in the range, as it would when an instance starts.
+    #[must_use]
+    pub fn new<'a>(workflows: impl IntoIterator<Item = &'a WorkflowDefinition>) -> Self {
+        let workflows: Vec<&WorkflowDefinition> = workflows.into_iter().collect();
+        let keys: BTreeSet<String> = workflows.iter().map(|workflow| key(workflow)).collect();
+
+        let mut calls = Vec::new();
+        for workflow in &workflows {
+            let mut references = Vec::new();
+            collect_nested(&workflow.do_.entries, &mut references);
+            for (task, namespace, name, version) in references {
+                let callee = match VersionReq::parse(&version) {
+                    Some(range) => {
+                        let prefix = format!("{namespace}/{name}/");
+                        range
+                            .best_match(keys.iter().filter_map(|key| key.strip_prefix(&prefix)))
+                            .map(|version| format!("{prefix}{version}"))
+                    }
+                    None => {
+                        let key = format!("{namespace}/{name}/{version}");
+                        keys.contains(&key).then_some(key)
+                    }
+                };
+                calls.push(WorkflowCall {
+                    caller: key(workflow),
+                    task,
+                    reference: format!("{namespace}/{name}/{version}"),
+                    callee,
+                });
+            }
+        }
+
+        Self {
+            workflows: keys,
+            calls,
+        }
+    }
+
+    /// The calls whose reference matches no registered workflow
+    pub fn missing(&self) -> impl Iterator<Item = &WorkflowCall> {
+        self.calls.iter().filter(|call| call.callee.is_none())
+    }
+
+    /// The groups of workflows that call each other in a cycle, each in order
+    #[must_use]
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let reachable = self.reachable();
+        let mut cycles: Vec<Vec<String>> = Vec::new();
+        for workflow in &self.workflows {
+            let in_cycle = reachable
+                .get(workflow)
+                .is_some_and(|reached| reached.contains(workflow));
+            if !in_cycle || cycles.iter().any(|cycle| cycle.contains(workflow)) {
+                continue;
+            }
+            cycles.push(
+                self.workflows
+                    .iter()
+                    .filter(|other| {
+                        reaches(&reachable, workflow, other) && reaches(&reachable, other, workflow)
+                    })
+                    .cloned()
+                    .collect(),
+            );
+        }
+        cycles
+    }
+
+    /// Whether `call` is part of a cycle
+    #[must_use]
+    pub fn is_cyclic(&self, call: &WorkflowCall) -> bool {
+        call.callee
+            .as_ref()
+            .is_some_and(|callee| reaches(&self.reachable(), callee, &call.caller))
+    }
+
+    /// The workflows each workflow calls, directly or not
+    fn reachable(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut callees: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for call in &self.calls {
+            if let Some(callee) = &call.callee {
+                callees
+                    .entry(call.caller.as_str())
+                    .or_default()
+                    .insert(callee.as_str());
+            }
+        }
+
+        let mut reachable = BTreeMap::new();
+        for workflow in &self.workflows {
+            let mut reached = BTreeSet::new();
+            let mut pending: Vec<&str> = vec![workflow.as_str()];
+            while let Some(next) = pending.pop() {
+                for callee in callees.get(next).into_iter().flatten() {
+                    if reached.insert(*callee) {
+                        pending.push(callee);
+                    }
+                }
+            }
+            reachable.insert(workflow.as_str(), reached);
+        }
+        reachable
+    }
+}
+
+fn reaches(reachable: &BTreeMap<&str, BTreeSet<&str>>, from: &str, to: &str) -> bool {
+    reachable
+        .get(from)
+        .is_some_and(|reached| reached.contains(to))
+}
+
+fn key(workflow: &WorkflowDefinition) -> String {
+    format!(
+        "{}/{}/{}",
+        workflow.document.namespace, workflow.document.name, workflow.document.version
+    )
+}
+
+/// A task name and the namespace, name and version of the workflow it runs
+type Reference = (String, String, String, String);
+
+/// Collect the workflows a task and the tasks nested in it run
+fn collect_task_references(name: &str, task: &TaskDefinition, references: &mut Vec<Reference>) {
+    match task {
+        TaskDefinition::Run(run_task) => {
+            let Ok(task) = serde_json::to_value(run_task) else {
+                return;
+            };
+            if let Some(reference) = task.pointer("/run/workflow") {
+                let field = |field: &str| {
+                    reference
+                        .get(field)
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                };
+                if let (Some(namespace), Some(workflow_name), Some(version)) =
+                    (field("namespace"), field("name"), field("version"))
+                {
+                    references.push((name.to_string(), namespace, workflow_name, version));
+                }
+            }
+        }
+        TaskDefinition::Do(t) => collect_nested(&t.do_.entries, references),
+        TaskDefinition::For(t) => collect_nested(&t.do_.entries, references),
+        TaskDefinition::Fork(t) => collect_nested(&t.fork.branches.entries, references),
+        TaskDefinition::Try(t) => {
+            collect_nested(&t.try_.entries, references);
+            if let Some(catch_tasks) = &t.catch.do_ {
+                collect_nested(&catch_tasks.entries, references);
+            }
+        }
+        TaskDefinition::Call(_)
+        | TaskDefinition::Emit(_)
+        | TaskDefinition::Listen(_)
+        | TaskDefinition::Raise(_)
+        | TaskDefinition::Set(_)
+        | TaskDefinition::Switch(_)
+        | TaskDefinition::Wait(_) => {}
+    }
+}
+
+fn collect_nested<'a, E>(entries: &'a [E], references: &mut Vec<Reference>)
+where
+    &'a E: IntoIterator<Item = (&'a String, &'a TaskDefinition)>,
+{
+    for entry in entries {
+        for (name, task) in entry {
+            collect_task_references(name, task, references);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn workflow(name: &str, version: &str, calls: &[(&str, &str)]) -> WorkflowDefinition {
+        let tasks: String = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (callee, range))| {
+                format!(
+                    "  - call{i}:\n      run:\n        workflow:\n          namespace: default\n          name: {callee}\n          version: '{range}'\n"
+                )
+            })
+            .collect();
+        let tasks = if tasks.is_empty() {
+            "  - done:\n      set:\n        done: true\n".to_string()
+        } else {
+            tasks
+        };
+        serde_yaml::from_str(&format!(
+            "document:\n  dsl: '1.0.2'\n  namespace: default\n  name: {name}\n  version: '{version}'\ndo:\n{tasks}"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ranges_resolve_to_the_highest_registered_version() {
+        let workflows = [
+            workflow("parent", "1.0.0", &[("child", "^1.0")]),
+            workflow("child", "1.0.0", &[]),
+            workflow("child", "1.2.0", &[]),
+            workflow("child", "2.0.0", &[]),
+        ];
+        let graph = CallGraph::new(&workflows);
+        assert_eq!(
+            graph.calls,
+            vec![WorkflowCall {
+                caller: "default/parent/1.0.0".to_string(),
+                task: "call0".to_string(),
+                reference: "default/child/^1.0".to_string(),
+                callee: Some("default/child/1.2.0".to_string()),
+            }]
+        );
+        assert!(graph.cycles().is_empty());
+    }
+
+    #[test]
+    fn test_missing_references_and_cycles_are_found() {
+        let workflows = [
+            workflow("a", "1.0.0", &[("b", "1.0.0"), ("ghost", "1.0.0")]),
+            workflow("b", "1.0.0", &[("c", "1.0.0")]),
+            workflow("c", "1.0.0", &[("a", "1.0.0")]),
+            workflow("d", "1.0.0", &[("a", "1.0.0")]),
+        ];
+        let graph = CallGraph::new(&workflows);
+
+        let missing: Vec<&str> = graph
+            .missing()
+            .map(|call| call.reference.as_str())
+            .collect();
+        assert_eq!(missing, vec!["default/ghost/1.0.0"]);
+        assert_eq!(
+            graph.cycles(),
+            vec![vec![
+                "default/a/1.0.0".to_string(),
+                "default/b/1.0.0".to_string(),
+                "default/c/1.0.0".to_string(),
+            ]]
+        );
+
+        let cyclic: Vec<&str> = graph
+            .calls
+            .iter()
+            .filter(|call| graph.is_cyclic(call))
+            .map(|call| call.caller.as_str())
+            .collect();
+        assert_eq!(
+            cyclic,
+            vec!["default/a/1.0.0", "default/b/1.0.0", "default/c/1.0.0"]
+        );
+    }
+}
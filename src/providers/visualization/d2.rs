@@ -1,23 +1,30 @@
 use serverless_workflow_core::models::task::TaskDefinition;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use snafu::prelude::*;
+use std::collections::BTreeSet;
 use std::fmt::Write;
 use std::path::Path;
 use std::process::Command;
+use std::sync::Arc;
 
 use super::{
-    CommandFailedSnafu, DiagramFormat, ExecuteFailedSnafu, ExecutionState, OutputPathRequiredSnafu,
-    Result, TaskExecutionState, TempDirFailedSnafu, ToolNotInstalledSnafu, VisualizationProvider,
+    CallGraph, Capabilities, CommandFailedSnafu, DiagramFilter, DiagramFormat, DiagramNode,
+    DiagramStyle, ExecuteFailedSnafu, ExecutionState, NestedTask, OutputPathRequiredSnafu, Result,
+    TaskExecutionState, TempDirFailedSnafu, ToolNotInstalledSnafu, VisualizationProvider, nested,
 };
 
 const D2: &str = "d2";
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct D2Provider {
     /// Path to d2 executable (default: "d2" from PATH)
     d2_path: String,
     /// D2 theme to use (e.g., "0", "200", etc.)
     theme: Option<String>,
+    /// Colors, color scheme and font size of the diagrams
+    style: DiagramStyle,
+    /// Tasks the diagrams draw
+    filter: DiagramFilter,
 }
 
 impl D2Provider {
@@ -26,6 +33,8 @@ impl D2Provider {
         Self {
             d2_path: D2.to_string(),
             theme: None,
+            style: DiagramStyle::default(),
+            filter: DiagramFilter::default(),
         }
     }
 
@@ -43,160 +52,269 @@ impl D2Provider {
         self
     }
 
+    /// Draw diagrams in `style`
+    #[must_use]
+    pub fn with_style(mut self, style: DiagramStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Draw only the tasks `filter` selects
+    #[must_use]
+    pub fn with_filter(mut self, filter: DiagramFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Background, font sizes and edge colors every diagram sets
+    fn diagram_header(&self, direction: &str) -> String {
+        let mut d2 = String::new();
+        let _ = writeln!(d2, "direction: {direction}\n");
+        if let Some(background) = self.style.background() {
+            let _ = writeln!(d2, "style.fill: \"{background}\"");
+            let _ = writeln!(
+                d2,
+                "(* -> *)[*].style.stroke: \"{}\"",
+                self.style.foreground()
+            );
+            let _ = writeln!(
+                d2,
+                "(* -> *)[*].style.font-color: \"{}\"",
+                self.style.foreground()
+            );
+        }
+        if let Some(font_size) = self.style.font_size {
+            let _ = writeln!(d2, "*.style.font-size: {font_size}");
+            // Edge labels are drawn smaller than node labels
+            let _ = writeln!(
+                d2,
+                "(* -> *)[*].style.font-size: {}",
+                font_size.saturating_sub(4).max(1)
+            );
+        }
+        if self.style.background().is_some() || self.style.font_size.is_some() {
+            d2.push('\n');
+        }
+        d2
+    }
+
     /// Generate D2 source for a workflow with optional execution state
-    #[allow(clippy::unused_self)]
     fn workflow_to_d2(
         &self,
         workflow: &WorkflowDefinition,
         execution_state: Option<&ExecutionState>,
-    ) -> String {
-        let mut d2 = String::new();
+    ) -> Result<String> {
+        let mut d2 = self.diagram_header("down");
 
-        // Direction and metadata
-        d2.push_str("direction: down\n\n");
+        // Metadata
         let _ = writeln!(d2, "# Workflow: {}", workflow.document.name);
         let _ = writeln!(d2, "# Version: {}\n", workflow.document.version);
 
         // Start node
         d2.push_str("Start: {\n");
         d2.push_str("  shape: circle\n");
-        d2.push_str("  style.fill: \"#90EE90\"\n");
+        let _ = writeln!(d2, "  style.fill: \"{}\"", self.style.start_color());
         d2.push_str("}\n\n");
 
-        // Collect all task names in order
-        let mut task_names = Vec::new();
-        for entry in &workflow.do_.entries {
-            for name in entry.keys() {
-                task_names.push(name.clone());
-            }
-        }
-
-        // Task nodes
-        for entry in &workflow.do_.entries {
-            for (name, task) in entry {
-                let mut style = Self::task_style_d2(task);
-                let label = Self::task_label(name, task);
-
-                // Override style based on execution state
-                if let Some(state) = execution_state
-                    && let Some(task_state) = state.task_states.get(name)
-                {
-                    let color = match task_state {
-                        TaskExecutionState::Success => "#90EE90", // Green
-                        TaskExecutionState::Failed => "#FF6B6B",  // Red
-                        TaskExecutionState::Running => "#FFD700", // Gold
-                        TaskExecutionState::NotExecuted => {
-                            // Keep default style
-                            ""
-                        }
-                    };
-
-                    if !color.is_empty() {
-                        style = format!(
-                            "  shape: rectangle\n  style.fill: \"{color}\"\n  style.border-radius: 8\n"
-                        );
+        // Task nodes, and the tasks the filter leaves out
+        let nodes = self.filter.nodes(workflow, execution_state)?;
+        let mut tasks = Vec::new();
+        for node in &nodes {
+            let (name, task) = match node {
+                DiagramNode::Task { name, task } => (name, task),
+                DiagramNode::Collapsed { id, label, state } => {
+                    let _ = writeln!(d2, "\"{id}\": {{");
+                    let _ = writeln!(d2, "  label: \"{label}\"");
+                    d2.push_str("  shape: rectangle\n");
+                    // Filled only if the tasks share a state
+                    if let Some(color) = state.as_ref().and_then(|s| self.style.state_color(s)) {
+                        let _ = writeln!(d2, "  style.fill: \"{color}\"");
                     }
+                    d2.push_str("  style.stroke-dash: 5\n");
+                    d2.push_str("}\n\n");
+                    continue;
                 }
-                let _ = writeln!(d2, "\"{name}\": {{");
-                let _ = writeln!(d2, "  label: \"{label}\"");
-                d2.push_str(&style);
-                d2.push_str(&style);
-                d2.push_str("}\n\n");
-            }
+            };
+            let task = NestedTask::new(None, name, task);
+            self.write_task(&mut d2, &task, execution_state, 0);
+            d2.push('\n');
+            tasks.push(task);
         }
 
         // End node
         d2.push_str("End: {\n");
         d2.push_str("  shape: circle\n");
-        d2.push_str("  style.fill: \"#FFB6C1\"\n");
+        let _ = writeln!(d2, "  style.fill: \"{}\"", self.style.end_color());
         d2.push_str("  style.double-border: true\n");
         d2.push_str("}\n\n");
 
         // Connections - build sequential flow
-        if task_names.is_empty() {
+        let ids: Vec<&str> = nodes.iter().map(DiagramNode::id).collect();
+        if ids.is_empty() {
             // Empty workflow
             d2.push_str("Start -> End\n");
         } else {
             // Start to first task
-            if let Some(first) = task_names.first() {
+            if let Some(first) = ids.first() {
                 let _ = writeln!(d2, "Start -> \"{first}\"");
             }
 
             // Sequential flow between tasks
-            for pair in task_names.windows(2) {
+            for pair in ids.windows(2) {
                 if let [a, b] = pair {
                     let _ = writeln!(d2, "\"{a}\" -> \"{b}\"");
                 }
             }
 
             // Last task to end
-            if let Some(last) = task_names.last() {
+            if let Some(last) = ids.last() {
                 let _ = writeln!(d2, "\"{last}\" -> End");
             }
         }
 
-        d2
-    }
-
-    /// Determine node style for D2 based on task type
-    fn task_style_d2(task: &TaskDefinition) -> String {
-        let (shape, color) = match task {
-            TaskDefinition::Call(_) => ("rectangle", "#87CEEB"),
-            TaskDefinition::Run(_) => ("rectangle", "#DDA0DD"),
-            TaskDefinition::Set(_) => ("rectangle", "#F0E68C"),
-            TaskDefinition::Switch(_) => ("diamond", "#FFD700"),
-            TaskDefinition::Fork(_) => ("parallelogram", "#FFA07A"),
-            TaskDefinition::For(_) => ("hexagon", "#98FB98"),
-            TaskDefinition::Try(_) => ("rectangle", "#FFE4B5"),
-            TaskDefinition::Listen(_) => ("rectangle", "#E0BBE4"),
-            TaskDefinition::Emit(_) => ("rectangle", "#FFDAB9"),
-            TaskDefinition::Wait(_) => ("oval", "#D3D3D3"),
-            TaskDefinition::Raise(_) => ("rectangle", "#FF6B6B"),
-            TaskDefinition::Do(_) => ("rectangle", "#B0C4DE"),
-        };
+        // Connections within the containers, between the tasks of the same
+        // one; those into and out of a container lead to the container itself
+        for edge in tasks.iter().flat_map(NestedTask::edges) {
+            let (from, to) = (nested::task_id(&edge.from), nested::task_id(&edge.to));
+            if from == to
+                || to.starts_with(&format!("{from}/"))
+                || from.starts_with(&format!("{to}/"))
+            {
+                continue;
+            }
+            let _ = writeln!(d2, "{} -> {}", Self::key(from), Self::key(to));
+        }
 
-        format!("  shape: {shape}\n  style.fill: \"{color}\"\n  style.border-radius: 8\n")
+        Ok(d2)
     }
 
-    /// Generate human-readable label for a task
-    fn task_label(name: &str, task: &TaskDefinition) -> String {
-        let task_type = match task {
-            TaskDefinition::Call(_) => "Call",
-            TaskDefinition::Run(_) => "Run",
-            TaskDefinition::Set(_) => "Set",
-            TaskDefinition::Switch(_) => "Switch",
-            TaskDefinition::Fork(_) => "Fork",
-            TaskDefinition::For(_) => "For",
-            TaskDefinition::Try(_) => "Try",
-            TaskDefinition::Listen(_) => "Listen",
-            TaskDefinition::Emit(_) => "Emit",
-            TaskDefinition::Wait(_) => "Wait",
-            TaskDefinition::Raise(_) => "Raise",
-            TaskDefinition::Do(_) => "Do",
+    /// Write the shape of a task, as a container holding the tasks it runs
+    /// if it runs any
+    fn write_task(
+        &self,
+        d2: &mut String,
+        task: &NestedTask<'_>,
+        execution_state: Option<&ExecutionState>,
+        depth: usize,
+    ) {
+        let indent = "  ".repeat(depth);
+        let mut shape = if task.is_cluster() {
+            "rectangle"
+        } else {
+            Self::task_shape_d2(task.task)
         };
-        format!("{task_type}\\n{name}")
+        let mut color = self.style.task_color(task.task);
+        let mut border = "";
+        let label = Self::task_label(task.name, task.task);
+
+        // Override style based on execution state, and mark failed
+        // and running tasks by their border as well
+        if let Some(state) = execution_state
+            && let Some(task_state) = state.task_states.get(task.name)
+            && let Some(state_color) = self.style.state_color(task_state)
+        {
+            shape = "rectangle";
+            color = state_color;
+            border = match task_state {
+                TaskExecutionState::Failed => "style.stroke-width: 4",
+                TaskExecutionState::Running => "style.stroke-dash: 3",
+                TaskExecutionState::Success | TaskExecutionState::NotExecuted => "",
+            };
+        }
+        let _ = writeln!(d2, "{indent}\"{}\": {{", task.name);
+        let _ = writeln!(d2, "{indent}  label: \"{label}\"");
+        let _ = writeln!(d2, "{indent}  shape: {shape}");
+        let _ = writeln!(d2, "{indent}  style.fill: \"{color}\"");
+        let _ = writeln!(d2, "{indent}  style.border-radius: 8");
+        if !border.is_empty() {
+            let _ = writeln!(d2, "{indent}  {border}");
+        }
+        for block in &task.blocks {
+            match block.label {
+                Some(label) => {
+                    let _ = writeln!(d2, "{indent}  \"{label}\": {{");
+                    let _ = writeln!(d2, "{indent}    style.stroke-dash: 3");
+                    for inner in &block.tasks {
+                        self.write_task(d2, inner, execution_state, depth + 2);
+                    }
+                    let _ = writeln!(d2, "{indent}  }}");
+                }
+                None => {
+                    for inner in &block.tasks {
+                        self.write_task(d2, inner, execution_state, depth + 1);
+                    }
+                }
+            }
+        }
+        let _ = writeln!(d2, "{indent}}}");
     }
-}
 
-impl VisualizationProvider for D2Provider {
-    fn name(&self) -> &'static str {
-        D2
+    /// Key of the shape of the task `id` in D2 source, the keys of the
+    /// containers it is in and its own joined by `.`
+    fn key(id: &str) -> String {
+        id.split('/')
+            .map(|name| format!("\"{name}\""))
+            .collect::<Vec<_>>()
+            .join(".")
     }
 
-    fn generate_source(
-        &self,
-        workflow: &WorkflowDefinition,
-        execution_state: Option<&ExecutionState>,
-    ) -> Result<String> {
-        Ok(self.workflow_to_d2(workflow, execution_state))
+    /// Generate D2 source for the call graph between the workflows of a registry
+    ///
+    /// Calls that are part of a cycle are drawn in the alert color and
+    /// references to workflows that are not registered as dashed nodes.
+    fn call_graph_to_d2(&self, graph: &CallGraph) -> String {
+        let mut d2 = self.diagram_header("right");
+        let alert = self.style.alert_color();
+
+        let cyclic: Vec<String> = graph.cycles().into_iter().flatten().collect();
+        for workflow in &graph.workflows {
+            let color = self.style.workflow_color(cyclic.contains(workflow));
+            let _ = writeln!(d2, "\"{workflow}\": {{");
+            d2.push_str("  shape: rectangle\n");
+            let _ = writeln!(d2, "  style.fill: \"{color}\"");
+            d2.push_str("  style.border-radius: 8\n");
+            d2.push_str("}\n\n");
+        }
+        let missing: BTreeSet<&str> = graph
+            .missing()
+            .map(|call| call.reference.as_str())
+            .collect();
+        for reference in &missing {
+            let _ = writeln!(d2, "\"{reference}\": {{");
+            let _ = writeln!(d2, "  label: \"missing: {reference}\"");
+            let _ = writeln!(d2, "  style.stroke: \"{alert}\"");
+            d2.push_str("  style.stroke-dash: 4\n");
+            let _ = writeln!(d2, "  style.font-color: \"{alert}\"");
+            d2.push_str("}\n\n");
+        }
+
+        for call in &graph.calls {
+            let target = call.callee.as_deref().unwrap_or(&call.reference);
+            let _ = write!(d2, "\"{}\" -> \"{target}\": \"{}\"", call.caller, call.task);
+            if call.callee.is_none() {
+                let _ = writeln!(
+                    d2,
+                    " {{\n  style.stroke: \"{alert}\"\n  style.stroke-dash: 4\n}}"
+                );
+            } else if graph.is_cyclic(call) {
+                let _ = writeln!(
+                    d2,
+                    " {{\n  style.stroke: \"{alert}\"\n  style.stroke-width: 3\n}}"
+                );
+            } else {
+                d2.push('\n');
+            }
+        }
+
+        d2
     }
 
-    fn render(
+    /// Render D2 source with `d2`, or as ASCII with `d2 --sketch`
+    fn render_d2(
         &self,
-        workflow: &WorkflowDefinition,
+        d2_source: &str,
         output_path: Option<&Path>,
         format: DiagramFormat,
-        execution_state: Option<&ExecutionState>,
     ) -> Result<()> {
         // Check if d2 is available
         if !self.is_available()? {
@@ -210,9 +328,6 @@ impl VisualizationProvider for D2Provider {
             .fail();
         }
 
-        // Generate D2 source
-        let d2_source = self.generate_source(workflow, execution_state)?;
-
         // D2 requires file input
         let temp_dir = tempfile::tempdir().context(TempDirFailedSnafu)?;
         let temp_source = temp_dir.path().join("workflow.d2");
@@ -273,6 +388,98 @@ impl VisualizationProvider for D2Provider {
         Ok(())
     }
 
+    /// Determine node shape for D2 based on task type
+    fn task_shape_d2(task: &TaskDefinition) -> &'static str {
+        match task {
+            TaskDefinition::Switch(_) => "diamond",
+            TaskDefinition::Fork(_) => "parallelogram",
+            TaskDefinition::For(_) => "hexagon",
+            TaskDefinition::Wait(_) => "oval",
+            TaskDefinition::Call(_)
+            | TaskDefinition::Run(_)
+            | TaskDefinition::Set(_)
+            | TaskDefinition::Try(_)
+            | TaskDefinition::Listen(_)
+            | TaskDefinition::Emit(_)
+            | TaskDefinition::Raise(_)
+            | TaskDefinition::Do(_) => "rectangle",
+        }
+    }
+
+    /// Generate human-readable label for a task
+    fn task_label(name: &str, task: &TaskDefinition) -> String {
+        let task_type = match task {
+            TaskDefinition::Call(_) => "Call",
+            TaskDefinition::Run(_) => "Run",
+            TaskDefinition::Set(_) => "Set",
+            TaskDefinition::Switch(_) => "Switch",
+            TaskDefinition::Fork(_) => "Fork",
+            TaskDefinition::For(_) => "For",
+            TaskDefinition::Try(_) => "Try",
+            TaskDefinition::Listen(_) => "Listen",
+            TaskDefinition::Emit(_) => "Emit",
+            TaskDefinition::Wait(_) => "Wait",
+            TaskDefinition::Raise(_) => "Raise",
+            TaskDefinition::Do(_) => "Do",
+        };
+        format!("{task_type}\\n{name}")
+    }
+}
+
+impl VisualizationProvider for D2Provider {
+    fn name(&self) -> &'static str {
+        D2
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            formats: &[
+                DiagramFormat::Svg,
+                DiagramFormat::Png,
+                DiagramFormat::Pdf,
+                DiagramFormat::Ascii,
+            ],
+            execution_state: true,
+        }
+    }
+
+    fn generate_source(
+        &self,
+        workflow: &WorkflowDefinition,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<String> {
+        self.workflow_to_d2(workflow, execution_state)
+    }
+
+    fn render(
+        &self,
+        workflow: &WorkflowDefinition,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<()> {
+        // Generate D2 source
+        let d2_source = self.generate_source(workflow, execution_state)?;
+        self.render_d2(&d2_source, output_path, format)
+    }
+
+    fn styled(&self, style: &DiagramStyle) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_style(style.clone())))
+    }
+
+    fn filtered(&self, filter: &DiagramFilter) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_filter(filter.clone())))
+    }
+
+    fn render_call_graph(
+        &self,
+        graph: &CallGraph,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+    ) -> Result<()> {
+        self.render_d2(&self.call_graph_to_d2(graph), output_path, format)
+    }
+
     fn is_available(&self) -> Result<bool> {
         Ok(Command::new(&self.d2_path)
             .arg("--version")
@@ -0,0 +1,244 @@
+//! Which tasks a diagram draws
+//!
+//! A diagram of a workflow with hundreds of tasks is unreadable as a single
+//! image. A [`DiagramFilter`], set with `viz_from_task`, `viz_max_depth`,
+//! `viz_collapse_successful` and `viz_only_failed` (or their `--viz-*`
+//! flags), narrows it down before the diagram source is generated. The tasks
+//! it leaves out are drawn as a single node saying how many there are, so
+//! the diagram still shows where the part drawn sits in the workflow.
+
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+
+use super::{ExecutionState, Result, TaskExecutionState, VisualizationSnafu};
+
+/// Which tasks of a workflow a diagram draws
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiagramFilter {
+    /// Task the diagram starts at, leaving out the tasks before it
+    pub from_task: Option<String>,
+    /// Number of tasks drawn from where the diagram starts
+    pub max_depth: Option<usize>,
+    /// Draw runs of consecutive successful tasks as one node
+    pub collapse_successful: bool,
+    /// Draw only the tasks that ran up to the last failed one
+    pub only_failed: bool,
+}
+
+/// A node of a filtered diagram, in the order the nodes run
+#[derive(Debug, Clone)]
+pub enum DiagramNode<'a> {
+    /// A task drawn as itself
+    Task {
+        name: &'a str,
+        task: &'a TaskDefinition,
+    },
+    /// Tasks drawn as a single node
+    Collapsed {
+        id: String,
+        label: String,
+        /// State the tasks share, if they ran
+        state: Option<TaskExecutionState>,
+    },
+}
+
+impl DiagramNode<'_> {
+    /// Identifier of the node in the diagram source
+    #[must_use]
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Task { name, .. } => name,
+            Self::Collapsed { id, .. } => id,
+        }
+    }
+
+    /// Identifier of the node the edges leaving it start at, the end of its
+    /// cluster for tasks that run tasks of their own
+    #[must_use]
+    pub fn exit_id(&self) -> String {
+        match self {
+            Self::Task { name, task } => super::nested::exit_id(name, task),
+            Self::Collapsed { id, .. } => id.clone(),
+        }
+    }
+}
+
+fn count(tasks: usize, what: &str) -> String {
+    if tasks == 1 {
+        format!("1 {what}task")
+    } else {
+        format!("{tasks} {what}tasks")
+    }
+}
+
+impl DiagramFilter {
+    /// The nodes a diagram of `workflow` draws, in the order they run
+    ///
+    /// # Errors
+    /// Returns an error if `from_task` is not a task of the workflow.
+    pub fn nodes<'a>(
+        &self,
+        workflow: &'a WorkflowDefinition,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<Vec<DiagramNode<'a>>> {
+        let tasks: Vec<(&str, &TaskDefinition)> = workflow
+            .do_
+            .entries
+            .iter()
+            .flat_map(|entry| entry.iter().map(|(name, task)| (name.as_str(), task)))
+            .collect();
+        let state = |name: &str| execution_state.and_then(|state| state.task_states.get(name));
+
+        let start = match &self.from_task {
+            Some(from_task) => tasks
+                .iter()
+                .position(|(name, _)| *name == from_task.as_str())
+                .ok_or_else(|| {
+                    VisualizationSnafu {
+                        message: format!(
+                            "Task '{from_task}' is not a task of workflow {}",
+                            workflow.document.name
+                        ),
+                    }
+                    .build()
+                })?,
+            None => 0,
+        };
+        let mut end = tasks.len();
+        if self.only_failed {
+            end = tasks
+                .iter()
+                .enumerate()
+                .skip(start)
+                .filter(|(_, (name, _))| state(*name) == Some(&TaskExecutionState::Failed))
+                .map(|(index, _)| index + 1)
+                .last()
+                .unwrap_or(start);
+        }
+        if let Some(max_depth) = self.max_depth {
+            end = end.min(start.saturating_add(max_depth));
+        }
+
+        let mut nodes = Vec::new();
+        if start > 0 {
+            nodes.push(DiagramNode::Collapsed {
+                id: "earlier tasks".to_string(),
+                label: count(start, "earlier "),
+                state: None,
+            });
+        }
+        let mut successful: Vec<&str> = Vec::new();
+        for &(name, task) in tasks.get(start..end).unwrap_or_default() {
+            let task_state = state(name);
+            let ran = task_state.is_some_and(|state| state != &TaskExecutionState::NotExecuted);
+            if self.only_failed && !ran {
+                continue;
+            }
+            if self.collapse_successful && task_state == Some(&TaskExecutionState::Success) {
+                successful.push(name);
+                continue;
+            }
+            Self::flush_successful(&mut successful, &tasks, &mut nodes);
+            nodes.push(DiagramNode::Task { name, task });
+        }
+        Self::flush_successful(&mut successful, &tasks, &mut nodes);
+        if end < tasks.len() {
+            nodes.push(DiagramNode::Collapsed {
+                id: "later tasks".to_string(),
+                label: count(tasks.len() - end, "later "),
+                state: None,
+            });
+        }
+        Ok(nodes)
+    }
+
+    /// Draw the successful tasks collected so far, as one node if there are several
+    fn flush_successful<'a>(
+        successful: &mut Vec<&'a str>,
+        tasks: &[(&'a str, &'a TaskDefinition)],
+        nodes: &mut Vec<DiagramNode<'a>>,
+    ) {
+        match successful.as_slice() {
+            [] => {}
+            [name] => {
+                if let Some(&(name, task)) = tasks.iter().find(|(other, _)| other == name) {
+                    nodes.push(DiagramNode::Task { name, task });
+                }
+            }
+            [first, ..] => nodes.push(DiagramNode::Collapsed {
+                id: format!("successful from {first}"),
+                label: count(successful.len(), "successful "),
+                state: Some(TaskExecutionState::Success),
+            }),
+        }
+        successful.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn workflow(tasks: usize) -> WorkflowDefinition {
+        let tasks: String = (0..tasks)
+            .map(|i| format!("  - task{i}:\n      set:\n        step: {i}\n"))
+            .collect();
+        serde_yaml::from_str(&format!(
+            "document:\n  dsl: '1.0.2'\n  namespace: default\n  name: large\n  version: '1.0.0'\ndo:\n{tasks}"
+        ))
+        .unwrap()
+    }
+
+    fn ids<'a>(nodes: &'a [DiagramNode<'a>]) -> Vec<&'a str> {
+        nodes.iter().map(DiagramNode::id).collect()
+    }
+
+    #[test]
+    fn test_from_task_and_max_depth_leave_out_the_rest() {
+        let workflow = workflow(10);
+        let filter = DiagramFilter {
+            from_task: Some("task4".to_string()),
+            max_depth: Some(2),
+            ..DiagramFilter::default()
+        };
+        let nodes = filter.nodes(&workflow, None).unwrap();
+        assert_eq!(
+            ids(&nodes),
+            vec!["earlier tasks", "task4", "task5", "later tasks"]
+        );
+        assert!(matches!(
+            nodes.last(),
+            Some(DiagramNode::Collapsed { label, .. }) if label == "4 later tasks"
+        ));
+
+        let unknown = DiagramFilter {
+            from_task: Some("task42".to_string()),
+            ..DiagramFilter::default()
+        };
+        assert!(unknown.nodes(&workflow, None).is_err());
+    }
+
+    #[test]
+    fn test_only_failed_collapses_successful_tasks_before_the_failure() {
+        let workflow = workflow(6);
+        let mut state = ExecutionState::new();
+        for name in ["task0", "task1", "task2"] {
+            state.mark_success(name);
+        }
+        state.mark_failed("task3");
+        state.mark_success("task4");
+
+        let filter = DiagramFilter {
+            collapse_successful: true,
+            only_failed: true,
+            ..DiagramFilter::default()
+        };
+        let nodes = filter.nodes(&workflow, Some(&state)).unwrap();
+        assert_eq!(
+            ids(&nodes),
+            vec!["successful from task0", "task3", "later tasks"]
+        );
+    }
+}
@@ -1,21 +1,31 @@
 use serverless_workflow_core::models::task::TaskDefinition;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use snafu::prelude::*;
+use std::collections::BTreeSet;
 use std::fmt::Write as FmtWrite;
 use std::io::Write;
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 
 use super::{
-    CommandFailedSnafu, DiagramFormat, ExecuteFailedSnafu, ExecutionState, OutputPathRequiredSnafu,
-    Result, SpawnFailedSnafu, StdinFailedSnafu, TaskExecutionState, ToolNotInstalledSnafu,
-    VisualizationProvider, WaitFailedSnafu, WriteStdinFailedSnafu,
+    CallGraph, Capabilities, CommandFailedSnafu, DiagramFilter, DiagramFormat, DiagramNode,
+    DiagramStyle, ExecuteFailedSnafu, ExecutionState, NestedTask, OutputPathRequiredSnafu, Result,
+    SpawnFailedSnafu, StdinFailedSnafu, TaskExecutionState, ToolNotInstalledSnafu,
+    VisualizationProvider, VisualizationSnafu, WaitFailedSnafu, WriteStdinFailedSnafu,
 };
 
-#[derive(Debug, Default)]
+/// Font size of node labels when the style sets none, Graphviz's own default
+const DEFAULT_FONT_SIZE: u32 = 14;
+
+#[derive(Debug, Clone, Default)]
 pub struct GraphvizProvider {
     /// Path to dot executable (default: "dot" from PATH)
     dot_path: String,
+    /// Colors, color scheme and font size of the diagrams
+    style: DiagramStyle,
+    /// Tasks the diagrams draw
+    filter: DiagramFilter,
 }
 
 impl GraphvizProvider {
@@ -23,6 +33,8 @@ impl GraphvizProvider {
     pub fn new() -> Self {
         Self {
             dot_path: "dot".to_string(),
+            style: DiagramStyle::default(),
+            filter: DiagramFilter::default(),
         }
     }
 
@@ -33,144 +45,249 @@ impl GraphvizProvider {
         self
     }
 
+    /// Draw diagrams in `style`
+    #[must_use]
+    pub fn with_style(mut self, style: DiagramStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Draw only the tasks `filter` selects
+    #[must_use]
+    pub fn with_filter(mut self, filter: DiagramFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Opening of a graph, with the attributes its nodes and edges share
+    fn graph_header(&self, name: &str, rankdir: &str) -> String {
+        let font_size = self.style.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+        let foreground = self.style.foreground();
+        let mut dot = String::new();
+        let _ = writeln!(&mut dot, "digraph \"{name}\" {{");
+        let _ = writeln!(&mut dot, "  rankdir={rankdir};");
+        if let Some(background) = self.style.background() {
+            let _ = writeln!(&mut dot, "  bgcolor=\"{background}\";");
+        }
+        let _ = writeln!(
+            &mut dot,
+            "  node [shape=box, style=\"rounded,filled\", fontname=\"Helvetica\", fontsize={font_size}];"
+        );
+        // Edge labels are drawn smaller than node labels
+        let _ = writeln!(
+            &mut dot,
+            "  edge [fontname=\"Helvetica\", fontsize={}, color=\"{foreground}\", fontcolor=\"{foreground}\"];\n",
+            font_size.saturating_sub(4).max(1)
+        );
+        dot
+    }
+
     /// Generate DOT source for a workflow with optional execution state
-    #[allow(clippy::unused_self)]
     fn workflow_to_dot(
         &self,
         workflow: &WorkflowDefinition,
         execution_state: Option<&ExecutionState>,
-    ) -> String {
-        let mut dot = String::new();
-
-        // Graph header
-        let _ = writeln!(&mut dot, "digraph \"{}\" {{", workflow.document.name);
-        dot.push_str("  rankdir=TB;\n");
-        dot.push_str("  node [shape=box, style=\"rounded,filled\", fontname=\"Helvetica\"];\n");
-        dot.push_str("  edge [fontname=\"Helvetica\", fontsize=10];\n\n");
+    ) -> Result<String> {
+        let mut dot = self.graph_header(&workflow.document.name, "TB");
 
         // Start node
-        dot.push_str("  start [shape=circle, label=\"Start\", fillcolor=\"#90EE90\"];\n");
-
-        // Collect all task names in order
-        let mut task_names = Vec::new();
-        for entry in &workflow.do_.entries {
-            for name in entry.keys() {
-                task_names.push(name.clone());
-            }
-        }
-
-        // Task nodes
-        for entry in &workflow.do_.entries {
-            for (name, task) in entry {
-                let (shape, mut color) = Self::task_style(task);
-                let label = Self::task_label(name, task);
-
-                // Override color based on execution state
-                if let Some(state) = execution_state
-                    && let Some(task_state) = state.task_states.get(name)
-                {
-                    color = match task_state {
-                        TaskExecutionState::Success => "#90EE90", // Green
-                        TaskExecutionState::Failed => "#FF6B6B",  // Red
-                        TaskExecutionState::Running => "#FFD700", // Gold
-                        TaskExecutionState::NotExecuted => color, // Default
+        let _ = writeln!(
+            &mut dot,
+            "  start [shape=circle, label=\"Start\", fillcolor=\"{}\"];",
+            self.style.start_color()
+        );
+
+        // Task nodes, and the tasks the filter leaves out
+        let nodes = self.filter.nodes(workflow, execution_state)?;
+        for node in &nodes {
+            let (name, task) = match node {
+                DiagramNode::Task { name, task } => (name, task),
+                DiagramNode::Collapsed { id, label, state } => {
+                    // Dashed, and filled only if the tasks share a state
+                    let style = match state.as_ref().and_then(|s| self.style.state_color(s)) {
+                        Some(color) => {
+                            format!("style=\"rounded,filled,dashed\", fillcolor=\"{color}\"")
+                        }
+                        None => "style=\"rounded,dashed\"".to_string(),
                     };
+                    let _ = writeln!(&mut dot, "  \"{id}\" [label=\"{label}\", {style}];");
+                    continue;
                 }
-                let _ = writeln!(
-                    &mut dot,
-                    "  \"{name}\" [label=\"{label}\", shape={shape}, fillcolor=\"{color}\"];"
-                );
-            }
+            };
+            self.write_task(
+                &mut dot,
+                &NestedTask::new(None, name, task),
+                execution_state,
+                1,
+            );
         }
 
         // End node
-        dot.push_str("  end [shape=doublecircle, label=\"End\", fillcolor=\"#FFB6C1\"];\n\n");
-
-        // Edges - build sequential flow
-        if task_names.is_empty() {
-            // Empty workflow
-            dot.push_str("  start -> end;\n");
-        } else {
-            // Start to first task
-            // Start to first task
-            if let Some(first) = task_names.first() {
-                let _ = writeln!(&mut dot, "  start -> \"{first}\";");
-            }
-            // Sequential flow between tasks
-            // Sequential flow between tasks
-            for pair in task_names.windows(2) {
+        let _ = writeln!(
+            &mut dot,
+            "  end [shape=doublecircle, label=\"End\", fillcolor=\"{}\"];\n",
+            self.style.end_color()
+        );
+
+        // Edges - build sequential flow, leaving the tasks that run tasks of
+        // their own at the end of their cluster
+        if let (Some(first), Some(last)) = (nodes.first(), nodes.last()) {
+            let _ = writeln!(&mut dot, "  start -> \"{}\";", first.id());
+            for pair in nodes.windows(2) {
                 if let [from, to] = pair {
-                    let _ = writeln!(&mut dot, "  \"{from}\" -> \"{to}\";");
+                    let _ = writeln!(&mut dot, "  \"{}\" -> \"{}\";", from.exit_id(), to.id());
                 }
             }
-            // Last task to end
-            // Last task to end
-            if let Some(last) = task_names.last() {
-                let _ = writeln!(&mut dot, "  \"{last}\" -> end;");
+            let _ = writeln!(&mut dot, "  \"{}\" -> end;", last.exit_id());
+        } else {
+            // Empty workflow
+            dot.push_str("  start -> end;\n");
+        }
+
+        // Edges within the clusters
+        for node in &nodes {
+            if let DiagramNode::Task { name, task } = node {
+                for edge in NestedTask::new(None, name, task).edges() {
+                    let label = edge
+                        .label
+                        .map(|label| format!(" [label=\"{label}\", style=dashed]"))
+                        .unwrap_or_default();
+                    let _ = writeln!(&mut dot, "  \"{}\" -> \"{}\"{label};", edge.from, edge.to);
+                }
             }
         }
 
         dot.push_str("}\n");
-        dot
+        Ok(dot)
     }
 
-    /// Determine node style based on task type
-    fn task_style(task: &TaskDefinition) -> (&str, &str) {
-        match task {
-            TaskDefinition::Call(_) => ("box", "#87CEEB"), // Sky blue
-            TaskDefinition::Run(_) => ("box", "#DDA0DD"),  // Plum
-            TaskDefinition::Set(_) => ("box", "#F0E68C"),  // Khaki
-            TaskDefinition::Switch(_) => ("diamond", "#FFD700"), // Gold
-            TaskDefinition::Fork(_) => ("parallelogram", "#FFA07A"), // Light salmon
-            TaskDefinition::For(_) => ("hexagon", "#98FB98"), // Pale green
-            TaskDefinition::Try(_) => ("box", "#FFE4B5"),  // Moccasin
-            TaskDefinition::Listen(_) => ("invtrapezium", "#E0BBE4"), // Lavender
-            TaskDefinition::Emit(_) => ("trapezium", "#FFDAB9"), // Peach
-            TaskDefinition::Wait(_) => ("octagon", "#D3D3D3"), // Light gray
-            TaskDefinition::Raise(_) => ("tripleoctagon", "#FF6B6B"), // Red
-            TaskDefinition::Do(_) => ("box", "#B0C4DE"),   // Light steel blue
+    /// Write the node of a task, as a cluster holding the tasks it runs if
+    /// it runs any
+    fn write_task(
+        &self,
+        dot: &mut String,
+        task: &NestedTask<'_>,
+        execution_state: Option<&ExecutionState>,
+        depth: usize,
+    ) {
+        let indent = "  ".repeat(depth);
+        if !task.is_cluster() {
+            let node = self.task_node(task, execution_state);
+            let _ = writeln!(dot, "{indent}{node}");
+            return;
         }
-    }
 
-    /// Generate human-readable label for a task
-    fn task_label(name: &str, task: &TaskDefinition) -> String {
-        let task_type = match task {
-            TaskDefinition::Call(_) => "Call",
-            TaskDefinition::Run(_) => "Run",
-            TaskDefinition::Set(_) => "Set",
-            TaskDefinition::Switch(_) => "Switch",
-            TaskDefinition::Fork(_) => "Fork",
-            TaskDefinition::For(_) => "For",
-            TaskDefinition::Try(_) => "Try",
-            TaskDefinition::Listen(_) => "Listen",
-            TaskDefinition::Emit(_) => "Emit",
-            TaskDefinition::Wait(_) => "Wait",
-            TaskDefinition::Raise(_) => "Raise",
-            TaskDefinition::Do(_) => "Do",
-        };
-        format!("{task_type}: {name}")
+        let foreground = self.style.foreground();
+        let _ = writeln!(dot, "{indent}subgraph \"cluster_{}\" {{", task.id);
+        let _ = writeln!(
+            dot,
+            "{indent}  label=\"{}\"; style=\"rounded,dashed\"; color=\"{foreground}\"; fontcolor=\"{foreground}\";",
+            task.cluster_label()
+        );
+        let _ = writeln!(dot, "{indent}  {}", self.task_node(task, execution_state));
+        for block in &task.blocks {
+            match block.label {
+                Some(label) => {
+                    let _ = writeln!(dot, "{indent}  subgraph \"cluster_{}/{label}\" {{", task.id);
+                    let _ = writeln!(dot, "{indent}    label=\"{label}\";");
+                    for inner in &block.tasks {
+                        self.write_task(dot, inner, execution_state, depth + 2);
+                    }
+                    let _ = writeln!(dot, "{indent}  }}");
+                }
+                None => {
+                    for inner in &block.tasks {
+                        self.write_task(dot, inner, execution_state, depth + 1);
+                    }
+                }
+            }
+        }
+        let _ = writeln!(
+            dot,
+            "{indent}  \"{}\" [shape=point, width=0.1, label=\"\", color=\"{foreground}\"];",
+            task.exit_id()
+        );
+        let _ = writeln!(dot, "{indent}}}");
     }
-}
 
-impl VisualizationProvider for GraphvizProvider {
-    fn name(&self) -> &'static str {
-        "graphviz"
+    /// Statement declaring the node of a task, colored by its state
+    fn task_node(&self, task: &NestedTask<'_>, execution_state: Option<&ExecutionState>) -> String {
+        let shape = Self::task_shape(task.task);
+        let label = Self::task_label(task.name, task.task);
+        let mut color = self.style.task_color(task.task);
+        let mut border = "";
+
+        // Override color based on execution state, and mark failed
+        // and running tasks by their border as well
+        if let Some(state) = execution_state
+            && let Some(task_state) = state.task_states.get(task.name)
+        {
+            if let Some(state_color) = self.style.state_color(task_state) {
+                color = state_color;
+            }
+            border = match task_state {
+                TaskExecutionState::Failed => ", penwidth=3",
+                TaskExecutionState::Running => ", style=\"rounded,filled,dashed\"",
+                TaskExecutionState::Success | TaskExecutionState::NotExecuted => "",
+            };
+        }
+        format!(
+            "\"{}\" [label=\"{label}\", shape={shape}, fillcolor=\"{color}\"{border}];",
+            task.id
+        )
     }
 
-    fn generate_source(
-        &self,
-        workflow: &WorkflowDefinition,
-        execution_state: Option<&ExecutionState>,
-    ) -> Result<String> {
-        Ok(self.workflow_to_dot(workflow, execution_state))
+    /// Generate DOT source for the call graph between the workflows of a registry
+    ///
+    /// Calls that are part of a cycle are drawn in the alert color and
+    /// references to workflows that are not registered as dashed nodes.
+    pub(super) fn call_graph_to_dot(&self, graph: &CallGraph) -> String {
+        let mut dot = self.graph_header("registry", "LR");
+        let alert = self.style.alert_color();
+
+        let cyclic: Vec<String> = graph.cycles().into_iter().flatten().collect();
+        for workflow in &graph.workflows {
+            let color = self.style.workflow_color(cyclic.contains(workflow));
+            let _ = writeln!(&mut dot, "  \"{workflow}\" [fillcolor=\"{color}\"];");
+        }
+        let missing: BTreeSet<&str> = graph
+            .missing()
+            .map(|call| call.reference.as_str())
+            .collect();
+        for reference in &missing {
+            let _ = writeln!(
+                &mut dot,
+                "  \"{reference}\" [label=\"missing: {reference}\", style=\"dashed\", color=\"{alert}\", fontcolor=\"{alert}\"];"
+            );
+        }
+        dot.push('\n');
+
+        for call in &graph.calls {
+            let target = call.callee.as_deref().unwrap_or(&call.reference);
+            let style = if call.callee.is_none() {
+                format!(", style=dashed, color=\"{alert}\"")
+            } else if graph.is_cyclic(call) {
+                format!(", color=\"{alert}\", penwidth=2")
+            } else {
+                String::new()
+            };
+            let _ = writeln!(
+                &mut dot,
+                "  \"{}\" -> \"{target}\" [label=\"{}\"{style}];",
+                call.caller, call.task
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
     }
 
-    fn render(
+    /// Render DOT source with `dot`, or as ASCII with `graph-easy`
+    fn render_dot(
         &self,
-        workflow: &WorkflowDefinition,
+        dot_source: &str,
         output_path: Option<&Path>,
         format: DiagramFormat,
-        execution_state: Option<&ExecutionState>,
     ) -> Result<()> {
         // Check if graphviz is available
         if !self.is_available()? {
@@ -185,9 +302,6 @@ impl VisualizationProvider for GraphvizProvider {
             .fail();
         }
 
-        // Generate DOT source
-        let dot_source = self.generate_source(workflow, execution_state)?;
-
         if format == DiagramFormat::Ascii {
             // Check for graph-easy
             let graph_easy_available = Command::new("graph-easy")
@@ -256,6 +370,12 @@ impl VisualizationProvider for GraphvizProvider {
                 DiagramFormat::Png => "png",
                 DiagramFormat::Pdf => "pdf",
                 DiagramFormat::Ascii => unreachable!(),
+                DiagramFormat::Markdown => {
+                    return VisualizationSnafu {
+                        message: "graphviz cannot render Markdown diagrams".to_string(),
+                    }
+                    .fail();
+                }
             };
 
             let mut cmd = Command::new(&self.dot_path)
@@ -291,6 +411,98 @@ impl VisualizationProvider for GraphvizProvider {
         Ok(())
     }
 
+    /// Determine node shape based on task type
+    fn task_shape(task: &TaskDefinition) -> &'static str {
+        match task {
+            TaskDefinition::Call(_)
+            | TaskDefinition::Run(_)
+            | TaskDefinition::Set(_)
+            | TaskDefinition::Try(_)
+            | TaskDefinition::Do(_) => "box",
+            TaskDefinition::Switch(_) => "diamond",
+            TaskDefinition::Fork(_) => "parallelogram",
+            TaskDefinition::For(_) => "hexagon",
+            TaskDefinition::Listen(_) => "invtrapezium",
+            TaskDefinition::Emit(_) => "trapezium",
+            TaskDefinition::Wait(_) => "octagon",
+            TaskDefinition::Raise(_) => "tripleoctagon",
+        }
+    }
+
+    /// Generate human-readable label for a task
+    fn task_label(name: &str, task: &TaskDefinition) -> String {
+        let task_type = match task {
+            TaskDefinition::Call(_) => "Call",
+            TaskDefinition::Run(_) => "Run",
+            TaskDefinition::Set(_) => "Set",
+            TaskDefinition::Switch(_) => "Switch",
+            TaskDefinition::Fork(_) => "Fork",
+            TaskDefinition::For(_) => "For",
+            TaskDefinition::Try(_) => "Try",
+            TaskDefinition::Listen(_) => "Listen",
+            TaskDefinition::Emit(_) => "Emit",
+            TaskDefinition::Wait(_) => "Wait",
+            TaskDefinition::Raise(_) => "Raise",
+            TaskDefinition::Do(_) => "Do",
+        };
+        format!("{task_type}: {name}")
+    }
+}
+
+impl VisualizationProvider for GraphvizProvider {
+    fn name(&self) -> &'static str {
+        "graphviz"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            formats: &[
+                DiagramFormat::Svg,
+                DiagramFormat::Png,
+                DiagramFormat::Pdf,
+                DiagramFormat::Ascii,
+            ],
+            execution_state: true,
+        }
+    }
+
+    fn generate_source(
+        &self,
+        workflow: &WorkflowDefinition,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<String> {
+        self.workflow_to_dot(workflow, execution_state)
+    }
+
+    fn render(
+        &self,
+        workflow: &WorkflowDefinition,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<()> {
+        // Generate DOT source
+        let dot_source = self.generate_source(workflow, execution_state)?;
+        self.render_dot(&dot_source, output_path, format)
+    }
+
+    fn styled(&self, style: &DiagramStyle) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_style(style.clone())))
+    }
+
+    fn filtered(&self, filter: &DiagramFilter) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_filter(filter.clone())))
+    }
+
+    fn render_call_graph(
+        &self,
+        graph: &CallGraph,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+    ) -> Result<()> {
+        self.render_dot(&self.call_graph_to_dot(graph), output_path, format)
+    }
+
     fn is_available(&self) -> Result<bool> {
         Ok(Command::new(&self.dot_path)
             .arg("-V")
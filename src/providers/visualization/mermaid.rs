@@ -0,0 +1,523 @@
+use serverless_workflow_core::models::task::TaskDefinition;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    CallGraph, Capabilities, DiagramFilter, DiagramFormat, DiagramNode, DiagramStyle,
+    ExecutionState, NestedTask, Result, TaskExecutionState, VisualizationProvider,
+    VisualizationSnafu,
+};
+
+const MERMAID: &str = "mermaid";
+
+/// Draws Mermaid flowcharts
+///
+/// Mermaid is rendered by whatever displays the diagram, such as GitHub in
+/// READMEs, issues and pull request comments, so no tool has to be
+/// installed. Diagrams are written as a fenced `mermaid` Markdown block.
+#[derive(Debug, Clone, Default)]
+pub struct MermaidProvider {
+    /// Colors, color scheme and font size of the diagrams
+    style: DiagramStyle,
+    /// Tasks the diagrams draw
+    filter: DiagramFilter,
+}
+
+impl MermaidProvider {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw diagrams in `style`
+    #[must_use]
+    pub fn with_style(mut self, style: DiagramStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Draw only the tasks `filter` selects
+    #[must_use]
+    pub fn with_filter(mut self, filter: DiagramFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Opening of a flowchart, with the background, font size and edge
+    /// color of the style
+    fn diagram_header(&self, direction: &str) -> String {
+        let mut variables = Vec::new();
+        if let Some(background) = self.style.background() {
+            variables.push(format!("\"background\": \"{background}\""));
+            variables.push(format!("\"lineColor\": \"{}\"", self.style.foreground()));
+        }
+        if let Some(font_size) = self.style.font_size {
+            variables.push(format!("\"fontSize\": \"{font_size}px\""));
+        }
+
+        let mut mermaid = String::new();
+        if !variables.is_empty() {
+            let _ = writeln!(
+                mermaid,
+                "%%{{init: {{\"themeVariables\": {{{}}}}}}}%%",
+                variables.join(", ")
+            );
+        }
+        let _ = writeln!(mermaid, "flowchart {direction}");
+        mermaid
+    }
+
+    /// Generate Mermaid source for a workflow with optional execution state
+    ///
+    /// Task names may hold characters Mermaid does not accept in node
+    /// identifiers, so nodes are numbered and the names are their labels.
+    fn workflow_to_mermaid(
+        &self,
+        workflow: &WorkflowDefinition,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<String> {
+        let mut chart = Flowchart {
+            source: self.diagram_header("TD"),
+            ..Flowchart::default()
+        };
+        let _ = writeln!(chart.source, "    %% Workflow: {}", workflow.document.name);
+        let _ = writeln!(
+            chart.source,
+            "    %% Version: {}",
+            workflow.document.version
+        );
+
+        // `end` is a keyword, so the end node is called `finish`
+        chart.source.push_str("    start((Start))\n");
+        chart
+            .styles
+            .push(format!("    style start fill:{}", self.style.start_color()));
+
+        // Task nodes, and the tasks the filter leaves out
+        let nodes = self.filter.nodes(workflow, execution_state)?;
+        let mut tasks = Vec::new();
+        for node in &nodes {
+            let (name, task) = match node {
+                DiagramNode::Task { name, task } => (name, task),
+                DiagramNode::Collapsed { id, label, state } => {
+                    let id = chart.node_id(id);
+                    let _ = writeln!(chart.source, "    {id}[\"{}\"]", escape(label));
+                    // Dashed, and filled only if the tasks share a state
+                    let fill = state
+                        .as_ref()
+                        .and_then(|s| self.style.state_color(s))
+                        .map(|color| format!("fill:{color},"))
+                        .unwrap_or_default();
+                    chart
+                        .styles
+                        .push(format!("    style {id} {fill}stroke-dasharray:5 5"));
+                    continue;
+                }
+            };
+            let task = NestedTask::new(None, name, task);
+            self.write_task(&mut chart, &task, execution_state, 1);
+            tasks.push(task);
+        }
+
+        chart.source.push_str("    finish(((End)))\n");
+        chart
+            .styles
+            .push(format!("    style finish fill:{}", self.style.end_color()));
+
+        // Connections - build sequential flow, leaving the tasks that run
+        // tasks of their own at the end of their subgraph
+        let mut previous = "start".to_string();
+        for node in &nodes {
+            let id = chart.node_id(node.id());
+            let _ = writeln!(chart.source, "    {previous} --> {id}");
+            previous = chart.node_id(&node.exit_id());
+        }
+        let _ = writeln!(chart.source, "    {previous} --> finish");
+
+        // Connections within the subgraphs
+        for edge in tasks.iter().flat_map(NestedTask::edges) {
+            let (from, to) = (chart.node_id(&edge.from), chart.node_id(&edge.to));
+            match edge.label {
+                Some(label) => {
+                    let _ = writeln!(chart.source, "    {from} -.->|\"{label}\"| {to}");
+                }
+                None => {
+                    let _ = writeln!(chart.source, "    {from} --> {to}");
+                }
+            }
+        }
+
+        let mut mermaid = chart.source;
+        for style in chart.styles {
+            mermaid.push_str(&style);
+            mermaid.push('\n');
+        }
+        Ok(mermaid)
+    }
+
+    /// Write the node of a task, as a subgraph holding the tasks it runs if
+    /// it runs any
+    fn write_task(
+        &self,
+        chart: &mut Flowchart,
+        task: &NestedTask<'_>,
+        execution_state: Option<&ExecutionState>,
+        depth: usize,
+    ) {
+        let indent = "    ".repeat(depth);
+        let id = chart.node_id(&task.id);
+        if !task.is_cluster() {
+            self.write_node(chart, &id, task, execution_state, &indent);
+            return;
+        }
+
+        let _ = writeln!(
+            chart.source,
+            "{indent}subgraph {id}_tasks [\"{}\"]",
+            escape(&task.cluster_label())
+        );
+        self.write_node(chart, &id, task, execution_state, &format!("{indent}    "));
+        for block in &task.blocks {
+            match block.label {
+                Some(label) => {
+                    let _ = writeln!(
+                        chart.source,
+                        "{indent}    subgraph {id}_{label} [\"{label}\"]"
+                    );
+                    for inner in &block.tasks {
+                        self.write_task(chart, inner, execution_state, depth + 2);
+                    }
+                    let _ = writeln!(chart.source, "{indent}    end");
+                }
+                None => {
+                    for inner in &block.tasks {
+                        self.write_task(chart, inner, execution_state, depth + 1);
+                    }
+                }
+            }
+        }
+        let exit = chart.node_id(&task.exit_id());
+        let _ = writeln!(chart.source, "{indent}    {exit}((\" \"))");
+        chart.styles.push(format!(
+            "    style {exit} fill:{},stroke-width:0px",
+            self.style.foreground()
+        ));
+        let _ = writeln!(chart.source, "{indent}end");
+    }
+
+    /// Write the node of a task, styled by its state
+    fn write_node(
+        &self,
+        chart: &mut Flowchart,
+        id: &str,
+        task: &NestedTask<'_>,
+        execution_state: Option<&ExecutionState>,
+        indent: &str,
+    ) {
+        let (mut open, mut close) = Self::task_shape(task.task);
+        let mut color = self.style.task_color(task.task);
+        let mut border = "";
+
+        // Override style based on execution state, and mark failed
+        // and running tasks by their border as well
+        if let Some(state) = execution_state
+            && let Some(task_state) = state.task_states.get(task.name)
+            && let Some(state_color) = self.style.state_color(task_state)
+        {
+            (open, close) = ("(", ")");
+            color = state_color;
+            border = match task_state {
+                TaskExecutionState::Failed => ",stroke-width:4px",
+                TaskExecutionState::Running => ",stroke-dasharray:3 3",
+                TaskExecutionState::Success | TaskExecutionState::NotExecuted => "",
+            };
+        }
+        let _ = writeln!(
+            chart.source,
+            "{indent}{id}{open}\"{}\"{close}",
+            Self::task_label(task.name, task.task)
+        );
+        chart
+            .styles
+            .push(format!("    style {id} fill:{color}{border}"));
+    }
+
+    /// Generate Mermaid source for the call graph between the workflows of a registry
+    ///
+    /// Calls that are part of a cycle are drawn in the alert color and
+    /// references to workflows that are not registered as dashed nodes.
+    fn call_graph_to_mermaid(&self, graph: &CallGraph) -> String {
+        let mut mermaid = self.diagram_header("LR");
+        let alert = self.style.alert_color();
+        let mut styles = Vec::new();
+
+        let cyclic: Vec<String> = graph.cycles().into_iter().flatten().collect();
+        let mut ids = HashMap::new();
+        for (index, workflow) in graph.workflows.iter().enumerate() {
+            let id = format!("w{index}");
+            let color = self.style.workflow_color(cyclic.contains(workflow));
+            let _ = writeln!(mermaid, "    {id}(\"{}\")", escape(workflow));
+            styles.push(format!("    style {id} fill:{color}"));
+            ids.insert(workflow.as_str(), id);
+        }
+        let missing: BTreeSet<&str> = graph
+            .missing()
+            .map(|call| call.reference.as_str())
+            .collect();
+        for (index, reference) in missing.iter().enumerate() {
+            let id = format!("m{index}");
+            let _ = writeln!(mermaid, "    {id}[\"missing: {}\"]", escape(reference));
+            styles.push(format!(
+                "    style {id} stroke:{alert},color:{alert},stroke-dasharray:4 4"
+            ));
+            ids.insert(*reference, id);
+        }
+
+        // Links are styled by the order they are declared in
+        let mut link = 0;
+        for call in &graph.calls {
+            let target = call.callee.as_deref().unwrap_or(&call.reference);
+            let (Some(from), Some(to)) = (ids.get(call.caller.as_str()), ids.get(target)) else {
+                continue;
+            };
+            let _ = writeln!(mermaid, "    {from} -->|\"{}\"| {to}", escape(&call.task));
+            if call.callee.is_none() {
+                styles.push(format!(
+                    "    linkStyle {link} stroke:{alert},stroke-dasharray:4 4"
+                ));
+            } else if graph.is_cyclic(call) {
+                styles.push(format!(
+                    "    linkStyle {link} stroke:{alert},stroke-width:3px"
+                ));
+            }
+            link += 1;
+        }
+
+        for style in styles {
+            mermaid.push_str(&style);
+            mermaid.push('\n');
+        }
+        mermaid
+    }
+
+    /// Write Mermaid source as a fenced Markdown block, to a file or stdout
+    fn render_mermaid(
+        &self,
+        source: &str,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+    ) -> Result<()> {
+        if format != DiagramFormat::Markdown {
+            return VisualizationSnafu {
+                message: format!("{MERMAID} cannot render {format:?} diagrams"),
+            }
+            .fail();
+        }
+
+        let markdown = format!("```mermaid\n{source}```\n");
+        if let Some(path) = output_path {
+            std::fs::write(path, markdown).context(super::IoSnafu)?;
+        } else {
+            print!("{markdown}");
+        }
+        Ok(())
+    }
+
+    /// Opening and closing brackets of the node shape for a task type
+    fn task_shape(task: &TaskDefinition) -> (&'static str, &'static str) {
+        match task {
+            TaskDefinition::Switch(_) => ("{", "}"),
+            TaskDefinition::Fork(_) => ("[/", "/]"),
+            TaskDefinition::For(_) => ("{{", "}}"),
+            TaskDefinition::Wait(_) => ("([", "])"),
+            TaskDefinition::Call(_)
+            | TaskDefinition::Run(_)
+            | TaskDefinition::Set(_)
+            | TaskDefinition::Try(_)
+            | TaskDefinition::Listen(_)
+            | TaskDefinition::Emit(_)
+            | TaskDefinition::Raise(_)
+            | TaskDefinition::Do(_) => ("(", ")"),
+        }
+    }
+
+    /// Generate human-readable label for a task
+    fn task_label(name: &str, task: &TaskDefinition) -> String {
+        let task_type = match task {
+            TaskDefinition::Call(_) => "Call",
+            TaskDefinition::Run(_) => "Run",
+            TaskDefinition::Set(_) => "Set",
+            TaskDefinition::Switch(_) => "Switch",
+            TaskDefinition::Fork(_) => "Fork",
+            TaskDefinition::For(_) => "For",
+            TaskDefinition::Try(_) => "Try",
+            TaskDefinition::Listen(_) => "Listen",
+            TaskDefinition::Emit(_) => "Emit",
+            TaskDefinition::Wait(_) => "Wait",
+            TaskDefinition::Raise(_) => "Raise",
+            TaskDefinition::Do(_) => "Do",
+        };
+        format!("{task_type}<br/>{}", escape(name))
+    }
+}
+
+/// Mermaid source being written
+#[derive(Debug, Default)]
+struct Flowchart {
+    source: String,
+    /// Style statements, written after the nodes and connections
+    styles: Vec<String>,
+    /// Identifier of each node in the source, by its identifier in the diagram
+    ids: HashMap<String, String>,
+}
+
+impl Flowchart {
+    /// Identifier of the node `id` in the source, numbering the nodes in the
+    /// order they are first seen
+    fn node_id(&mut self, id: &str) -> String {
+        let next = format!("n{}", self.ids.len());
+        self.ids.entry(id.to_string()).or_insert(next).clone()
+    }
+}
+
+/// Text as it can appear in a quoted Mermaid label
+fn escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+impl VisualizationProvider for MermaidProvider {
+    fn name(&self) -> &'static str {
+        MERMAID
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            formats: &[DiagramFormat::Markdown],
+            execution_state: true,
+        }
+    }
+
+    fn generate_source(
+        &self,
+        workflow: &WorkflowDefinition,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<String> {
+        self.workflow_to_mermaid(workflow, execution_state)
+    }
+
+    fn render(
+        &self,
+        workflow: &WorkflowDefinition,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<()> {
+        let source = self.generate_source(workflow, execution_state)?;
+        self.render_mermaid(&source, output_path, format)
+    }
+
+    fn styled(&self, style: &DiagramStyle) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_style(style.clone())))
+    }
+
+    fn filtered(&self, filter: &DiagramFilter) -> Option<Arc<dyn VisualizationProvider>> {
+        Some(Arc::new(self.clone().with_filter(filter.clone())))
+    }
+
+    fn render_call_graph(
+        &self,
+        graph: &CallGraph,
+        output_path: Option<&Path>,
+        format: DiagramFormat,
+    ) -> Result<()> {
+        self.render_mermaid(&self.call_graph_to_mermaid(graph), output_path, format)
+    }
+
+    /// Mermaid is drawn by whatever displays the diagram, so it is always available
+    fn is_available(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn version(&self) -> Result<String> {
+        Ok(format!("built-in ({})", env!("CARGO_PKG_VERSION")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn create_test_workflow() -> WorkflowDefinition {
+        serde_yaml::from_str(
+            r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-workflow
+  version: '1.0.0'
+do:
+  - greet:
+      set:
+        greeting: hello
+  - "say \"bye\"":
+      wait:
+        seconds: 1
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mermaid_source_generation() {
+        let source = MermaidProvider::new()
+            .generate_source(&create_test_workflow(), None)
+            .unwrap();
+
+        assert!(source.starts_with("flowchart TD\n"), "{source}");
+        assert!(source.contains("n0(\"Set<br/>greet\")"), "{source}");
+        assert!(source.contains("n1([\"Wait<br/>say #quot;bye#quot;\"])"));
+        assert!(source.contains("start --> n0\n    n0 --> n1\n    n1 --> finish"));
+    }
+
+    #[test]
+    fn test_failed_tasks_have_a_thick_border() {
+        let mut state = ExecutionState::new();
+        state.mark_success("greet");
+        state.mark_failed("say \"bye\"");
+        let source = MermaidProvider::new()
+            .generate_source(&create_test_workflow(), Some(&state))
+            .unwrap();
+
+        assert!(source.contains("n1(\"Wait<br/>say #quot;bye#quot;\")"));
+        assert!(
+            source.contains("style n1 fill:#FF6B6B,stroke-width:4px"),
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn test_renders_only_markdown() {
+        let provider = MermaidProvider::new();
+        let workflow = create_test_workflow();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workflow.md");
+        provider
+            .render(&workflow, Some(&path), DiagramFormat::Markdown, None)
+            .unwrap();
+
+        let markdown = std::fs::read_to_string(&path).unwrap();
+        assert!(markdown.starts_with("```mermaid\nflowchart TD\n"));
+        assert!(markdown.ends_with("```\n"));
+        assert!(
+            provider
+                .render(&workflow, Some(&path), DiagramFormat::Svg, None)
+                .is_err()
+        );
+    }
+}
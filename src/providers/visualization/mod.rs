@@ -1,13 +1,51 @@
+//! Workflow diagrams
+//!
+//! The providers drawing diagrams, and the trait they implement, need the
+//! `viz` feature. The settings of diagrams, which the configuration holds,
+//! are always available.
+
+#[cfg(feature = "viz")]
+pub mod builtin;
+#[cfg(feature = "viz")]
+pub mod callgraph;
+#[cfg(feature = "viz")]
 pub mod d2;
+pub mod filter;
+#[cfg(feature = "viz")]
 pub mod graphviz;
+#[cfg(feature = "viz")]
+pub mod mermaid;
+pub mod nested;
+#[cfg(feature = "viz")]
+pub mod registry;
+pub mod style;
 
+#[cfg(feature = "viz")]
+pub use self::builtin::BuiltinProvider;
+#[cfg(feature = "viz")]
+pub use self::callgraph::{CallGraph, WorkflowCall};
+#[cfg(feature = "viz")]
 pub use self::d2::D2Provider;
+pub use self::filter::{DiagramFilter, DiagramNode};
+#[cfg(feature = "viz")]
 pub use self::graphviz::GraphvizProvider;
+#[cfg(feature = "viz")]
+pub use self::mermaid::MermaidProvider;
+pub use self::nested::{Block, BlockKind, Edge, NestedTask};
+#[cfg(feature = "viz")]
+pub use self::registry::{installed_or_builtin, provider, provider_names, register_provider};
+pub use self::style::{ColorScheme, DiagramStyle, Palette};
 
+#[cfg(feature = "viz")]
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use snafu::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "viz")]
 use std::path::Path;
+#[cfg(feature = "viz")]
+use std::sync::Arc;
+
+use crate::workflow::WorkflowEvent;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -66,6 +104,9 @@ pub enum DiagramFormat {
     Png,
     Pdf,
     Ascii,
+    /// Diagram source in a fenced Markdown code block, for providers whose
+    /// diagrams are drawn by whatever displays them
+    Markdown,
 }
 
 impl DiagramFormat {
@@ -76,6 +117,7 @@ impl DiagramFormat {
             "png" => Some(DiagramFormat::Png),
             "pdf" => Some(DiagramFormat::Pdf),
             "txt" | "ascii" => Some(DiagramFormat::Ascii),
+            "md" | "markdown" => Some(DiagramFormat::Markdown),
             _ => None,
         }
     }
@@ -87,18 +129,38 @@ impl DiagramFormat {
             DiagramFormat::Png => "png",
             DiagramFormat::Pdf => "pdf",
             DiagramFormat::Ascii => "txt",
+            DiagramFormat::Markdown => "md",
         }
     }
 
     #[must_use]
     pub fn is_terminal_output(&self) -> bool {
-        matches!(self, DiagramFormat::Ascii)
+        matches!(self, DiagramFormat::Ascii | DiagramFormat::Markdown)
+    }
+}
+
+/// What a visualization provider can render, as reported by
+/// [`VisualizationProvider::capabilities`]
+#[cfg(feature = "viz")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Formats `render` accepts
+    pub formats: &'static [DiagramFormat],
+    /// Diagrams highlight the tasks an instance completed, failed or is running
+    pub execution_state: bool,
+}
+
+#[cfg(feature = "viz")]
+impl Capabilities {
+    /// Whether `render` accepts `format`
+    #[must_use]
+    pub fn supports(&self, format: DiagramFormat) -> bool {
+        self.formats.contains(&format)
     }
 }
 
 /// Execution state for a task in the workflow
 #[derive(Debug, Clone, PartialEq, Eq)]
-#[allow(dead_code)]
 pub enum TaskExecutionState {
     /// Task has not been executed
     NotExecuted,
@@ -123,30 +185,90 @@ impl ExecutionState {
         Self::default()
     }
 
-    #[allow(dead_code)]
     pub fn mark_success(&mut self, task_name: &str) {
         self.task_states
             .insert(task_name.to_string(), TaskExecutionState::Success);
     }
 
-    #[allow(dead_code)]
     pub fn mark_failed(&mut self, task_name: &str) {
         self.task_states
             .insert(task_name.to_string(), TaskExecutionState::Failed);
     }
 
-    #[allow(dead_code)]
     pub fn mark_running(&mut self, task_name: &str) {
         self.task_states
             .insert(task_name.to_string(), TaskExecutionState::Running);
     }
+
+    /// The state of each task of an instance, from the events recorded for it
+    ///
+    /// A task runs from when it is entered until it completes, or faults or
+    /// is cancelled, which fails it. Tasks whose `if` was false did not
+    /// execute, and tasks still running when the instance failed or was
+    /// cancelled failed with it.
+    #[must_use]
+    pub fn from_events(events: &[WorkflowEvent]) -> Self {
+        let mut state = Self::new();
+        let mut skipped = HashSet::new();
+        for event in events {
+            match event {
+                WorkflowEvent::TaskEntered { task_name, .. }
+                | WorkflowEvent::TaskCreated { task_name, .. }
+                | WorkflowEvent::TaskStarted { task_name, .. }
+                | WorkflowEvent::TaskRetried { task_name, .. }
+                | WorkflowEvent::TaskResumed { task_name, .. } => {
+                    skipped.remove(task_name.as_str());
+                    state.mark_running(task_name);
+                }
+                WorkflowEvent::TaskSkipped { task_name, .. } => {
+                    skipped.insert(task_name.as_str());
+                }
+                WorkflowEvent::TaskCompleted { task_name, .. } => {
+                    if skipped.contains(task_name.as_str()) {
+                        state
+                            .task_states
+                            .insert(task_name.clone(), TaskExecutionState::NotExecuted);
+                    } else {
+                        state.mark_success(task_name);
+                    }
+                }
+                WorkflowEvent::TaskFaulted { task_name, .. }
+                | WorkflowEvent::TaskCancelled { task_name, .. } => state.mark_failed(task_name),
+                WorkflowEvent::WorkflowFailed { .. } | WorkflowEvent::WorkflowCancelled { .. } => {
+                    for task_state in state.task_states.values_mut() {
+                        if *task_state == TaskExecutionState::Running {
+                            *task_state = TaskExecutionState::Failed;
+                        }
+                    }
+                }
+                WorkflowEvent::WorkflowStarted { .. }
+                | WorkflowEvent::WorkflowCompleted { .. }
+                | WorkflowEvent::WorkflowCorrelationStarted { .. }
+                | WorkflowEvent::WorkflowCorrelationCompleted { .. }
+                | WorkflowEvent::WorkflowSuspended { .. }
+                | WorkflowEvent::WorkflowResumed { .. }
+                | WorkflowEvent::TaskSuspended { .. }
+                | WorkflowEvent::TaskReaped { .. }
+                | WorkflowEvent::TaskCacheHit { .. }
+                | WorkflowEvent::SwitchCaseMatched { .. }
+                | WorkflowEvent::ErrorCaught { .. }
+                | WorkflowEvent::WatchesEvaluated { .. }
+                | WorkflowEvent::ShadowCompared { .. } => {}
+            }
+        }
+        state
+    }
 }
 
 /// Common trait for workflow visualization providers
+#[cfg(feature = "viz")]
 pub trait VisualizationProvider: Send + Sync + std::fmt::Debug {
     /// Get the name of the visualization tool (e.g., "graphviz", "d2")
     fn name(&self) -> &'static str;
 
+    /// The formats the provider renders, and whether it shows execution state
+    fn capabilities(&self) -> Capabilities;
+
     /// Generate diagram source code from a workflow definition
     ///
     /// # Arguments
@@ -181,6 +303,40 @@ pub trait VisualizationProvider: Send + Sync + std::fmt::Debug {
         execution_state: Option<&ExecutionState>,
     ) -> Result<()>;
 
+    /// A copy of the provider drawing in `style`
+    ///
+    /// Providers that draw in a style of their own keep this default, which
+    /// returns `None`.
+    fn styled(&self, _style: &DiagramStyle) -> Option<Arc<dyn VisualizationProvider>> {
+        None
+    }
+
+    /// A copy of the provider drawing only the tasks `filter` selects
+    ///
+    /// Providers that always draw every task keep this default, which
+    /// returns `None`.
+    fn filtered(&self, _filter: &DiagramFilter) -> Option<Arc<dyn VisualizationProvider>> {
+        None
+    }
+
+    /// Render the call graph between the workflows of a registry to a file or stdout
+    ///
+    /// Providers that do not draw call graphs keep this default, which fails.
+    ///
+    /// # Errors
+    /// Returns an error if the provider does not draw call graphs or rendering fails.
+    fn render_call_graph(
+        &self,
+        _graph: &CallGraph,
+        _output_path: Option<&Path>,
+        _format: DiagramFormat,
+    ) -> Result<()> {
+        VisualizationSnafu {
+            message: format!("{} does not render call graphs", self.name()),
+        }
+        .fail()
+    }
+
     /// Check if the visualization tool is installed and available
     ///
     /// # Errors
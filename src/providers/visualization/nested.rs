@@ -0,0 +1,329 @@
+//! The tasks `do`, `for`, `fork` and `try` tasks run
+//!
+//! Diagrams draw a task that runs tasks of its own as a cluster holding
+//! them, nested as deep as the tasks are: the tasks of a `do` and the body of
+//! a `for` one after another, the branches of a `fork` side by side, and the
+//! tasks of a `try` followed by its `catch` in a cluster of its own. The
+//! cluster is entered at the node of the task itself and left at a small
+//! point, so the edges of the workflow lead into and out of it.
+
+use serverless_workflow_core::models::task::TaskDefinition;
+
+/// Suffix of the identifier of the point a cluster is left at
+const EXIT: &str = "/(end)";
+
+/// How the tasks of a block run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    /// One after another, as the tasks of a `do` or a `try`
+    Sequence,
+    /// One after another for each item, as the body of a `for`
+    Loop,
+    /// Side by side, as the branches of a `fork`
+    Branches,
+    /// One after another when an error is caught, as the `catch` of a `try`
+    Handler,
+}
+
+/// Tasks a task runs
+#[derive(Debug, Clone)]
+pub struct Block<'a> {
+    pub kind: BlockKind,
+    /// Label of the cluster of its own the block is drawn in, if it has one
+    pub label: Option<&'static str>,
+    pub tasks: Vec<NestedTask<'a>>,
+}
+
+/// A task drawn in a diagram, with the tasks it runs
+#[derive(Debug, Clone)]
+pub struct NestedTask<'a> {
+    /// Identifier of the node in the diagram source: the names of the tasks
+    /// it is nested in and its own, joined by `/`
+    pub id: String,
+    pub name: &'a str,
+    pub task: &'a TaskDefinition,
+    /// Tasks it runs, none for tasks that run no tasks of their own
+    pub blocks: Vec<Block<'a>>,
+}
+
+/// An edge between two nodes of a diagram
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub label: Option<&'static str>,
+}
+
+impl Edge {
+    fn new(from: &str, to: &str, label: Option<&'static str>) -> Self {
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            label,
+        }
+    }
+}
+
+/// Identifier of the node the edges leaving the task `id` start at
+#[must_use]
+pub fn exit_id(id: &str, task: &TaskDefinition) -> String {
+    if runs_tasks(task) {
+        format!("{id}{EXIT}")
+    } else {
+        id.to_string()
+    }
+}
+
+/// Identifier of the task a node belongs to: the task itself for its own
+/// node, and the task whose cluster it is left at for the end of a cluster
+#[must_use]
+pub fn task_id(node_id: &str) -> &str {
+    node_id.strip_suffix(EXIT).unwrap_or(node_id)
+}
+
+/// Whether `task` runs tasks of its own
+fn runs_tasks(task: &TaskDefinition) -> bool {
+    match task {
+        TaskDefinition::Do(_)
+        | TaskDefinition::For(_)
+        | TaskDefinition::Fork(_)
+        | TaskDefinition::Try(_) => true,
+        TaskDefinition::Call(_)
+        | TaskDefinition::Emit(_)
+        | TaskDefinition::Listen(_)
+        | TaskDefinition::Raise(_)
+        | TaskDefinition::Run(_)
+        | TaskDefinition::Set(_)
+        | TaskDefinition::Switch(_)
+        | TaskDefinition::Wait(_) => false,
+    }
+}
+
+fn nested<'a, E>(parent: &str, entries: &'a [E]) -> Vec<NestedTask<'a>>
+where
+    &'a E: IntoIterator<Item = (&'a String, &'a TaskDefinition)>,
+{
+    entries
+        .iter()
+        .flatten()
+        .map(|(name, task)| NestedTask::new(Some(parent), name, task))
+        .collect()
+}
+
+impl<'a> NestedTask<'a> {
+    /// The task `name`, nested in the task `parent` if it is not a task of
+    /// the workflow itself
+    #[must_use]
+    pub fn new(parent: Option<&str>, name: &'a str, task: &'a TaskDefinition) -> Self {
+        let id = match parent {
+            Some(parent) => format!("{parent}/{name}"),
+            None => name.to_string(),
+        };
+        let block = |kind, label, tasks| Block { kind, label, tasks };
+        let blocks = match task {
+            TaskDefinition::Do(t) => vec![block(
+                BlockKind::Sequence,
+                None,
+                nested(&id, &t.do_.entries),
+            )],
+            TaskDefinition::For(t) => {
+                vec![block(BlockKind::Loop, None, nested(&id, &t.do_.entries))]
+            }
+            TaskDefinition::Fork(t) => vec![block(
+                BlockKind::Branches,
+                None,
+                nested(&id, &t.fork.branches.entries),
+            )],
+            TaskDefinition::Try(t) => {
+                let mut blocks = vec![block(
+                    BlockKind::Sequence,
+                    None,
+                    nested(&id, &t.try_.entries),
+                )];
+                if let Some(catch_tasks) = &t.catch.do_ {
+                    blocks.push(block(
+                        BlockKind::Handler,
+                        Some("catch"),
+                        nested(&format!("{id}/catch"), &catch_tasks.entries),
+                    ));
+                }
+                blocks
+            }
+            TaskDefinition::Call(_)
+            | TaskDefinition::Emit(_)
+            | TaskDefinition::Listen(_)
+            | TaskDefinition::Raise(_)
+            | TaskDefinition::Run(_)
+            | TaskDefinition::Set(_)
+            | TaskDefinition::Switch(_)
+            | TaskDefinition::Wait(_) => Vec::new(),
+        };
+        Self {
+            id,
+            name,
+            task,
+            blocks,
+        }
+    }
+
+    /// Whether the task is drawn as a cluster holding the tasks it runs
+    #[must_use]
+    pub fn is_cluster(&self) -> bool {
+        runs_tasks(self.task)
+    }
+
+    /// Identifier of the node the edges leaving the task start at
+    #[must_use]
+    pub fn exit_id(&self) -> String {
+        exit_id(&self.id, self.task)
+    }
+
+    /// Label of the cluster of the task
+    #[must_use]
+    pub fn cluster_label(&self) -> String {
+        match self.task {
+            TaskDefinition::For(t) => format!("for each {}", t.for_.each),
+            TaskDefinition::Fork(t) if t.fork.compete => "first branch to finish".to_string(),
+            TaskDefinition::Fork(_) => "all branches".to_string(),
+            TaskDefinition::Try(_) => "try".to_string(),
+            TaskDefinition::Do(_) => "do".to_string(),
+            // Drawn as a single node, without a cluster
+            TaskDefinition::Call(_)
+            | TaskDefinition::Emit(_)
+            | TaskDefinition::Listen(_)
+            | TaskDefinition::Raise(_)
+            | TaskDefinition::Run(_)
+            | TaskDefinition::Set(_)
+            | TaskDefinition::Switch(_)
+            | TaskDefinition::Wait(_) => String::new(),
+        }
+    }
+
+    /// The edges within the cluster of the task, those of the clusters
+    /// nested in it included
+    #[must_use]
+    pub fn edges(&self) -> Vec<Edge> {
+        let mut edges = Vec::new();
+        if !self.is_cluster() {
+            return edges;
+        }
+        let exit = self.exit_id();
+        for block in &self.blocks {
+            let (Some(first), Some(last)) = (block.tasks.first(), block.tasks.last()) else {
+                if block.kind != BlockKind::Handler {
+                    edges.push(Edge::new(&self.id, &exit, None));
+                }
+                continue;
+            };
+            match block.kind {
+                BlockKind::Branches => {
+                    for branch in &block.tasks {
+                        edges.push(Edge::new(&self.id, &branch.id, None));
+                        edges.push(Edge::new(&branch.exit_id(), &exit, None));
+                    }
+                }
+                BlockKind::Sequence | BlockKind::Loop | BlockKind::Handler => {
+                    let label = (block.kind == BlockKind::Handler).then_some("error");
+                    edges.push(Edge::new(&self.id, &first.id, label));
+                    for pair in block.tasks.windows(2) {
+                        if let [from, to] = pair {
+                            edges.push(Edge::new(&from.exit_id(), &to.id, None));
+                        }
+                    }
+                    edges.push(Edge::new(&last.exit_id(), &exit, None));
+                    if block.kind == BlockKind::Loop {
+                        edges.push(Edge::new(&last.exit_id(), &self.id, Some("next")));
+                    }
+                }
+            }
+        }
+        for block in &self.blocks {
+            for task in &block.tasks {
+                edges.extend(task.edges());
+            }
+        }
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serverless_workflow_core::models::workflow::WorkflowDefinition;
+
+    fn workflow() -> WorkflowDefinition {
+        serde_yaml::from_str(
+            r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: nested
+  version: '1.0.0'
+do:
+  - order:
+      try:
+        - charge:
+            for:
+              each: item
+              in: ${ .items }
+            do:
+              - bill:
+                  set:
+                    billed: true
+        - ship:
+            fork:
+              branches:
+                - pack:
+                    set:
+                      packed: true
+                - label:
+                    set:
+                      labelled: true
+      catch:
+        do:
+          - refund:
+              set:
+                refunded: true
+",
+        )
+        .unwrap()
+    }
+
+    fn edge(from: &str, to: &str) -> Edge {
+        Edge::new(from, to, None)
+    }
+
+    #[test]
+    fn test_clusters_nest_as_deep_as_the_tasks() {
+        let workflow = workflow();
+        let (name, task) = workflow.do_.entries.first().unwrap().iter().next().unwrap();
+        let order = NestedTask::new(None, name, task);
+
+        assert_eq!(order.exit_id(), "order/(end)");
+        let [try_block, catch_block] = order.blocks.as_slice() else {
+            panic!("{:?}", order.blocks);
+        };
+        assert_eq!(catch_block.label, Some("catch"));
+        let ids: Vec<&str> = try_block.tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["order/charge", "order/ship"]);
+        assert_eq!(catch_block.tasks.first().unwrap().id, "order/catch/refund");
+
+        let edges = order.edges();
+        for expected in [
+            edge("order", "order/charge"),
+            edge("order/charge/(end)", "order/ship"),
+            edge("order/ship/(end)", "order/(end)"),
+            Edge::new("order", "order/catch/refund", Some("error")),
+            edge("order/catch/refund", "order/(end)"),
+            edge("order/charge", "order/charge/bill"),
+            Edge::new("order/charge/bill", "order/charge", Some("next")),
+            edge("order/ship", "order/ship/pack"),
+            edge("order/ship", "order/ship/label"),
+            edge("order/ship/label", "order/ship/(end)"),
+        ] {
+            assert!(edges.contains(&expected), "{expected:?} not in {edges:?}");
+        }
+    }
+}
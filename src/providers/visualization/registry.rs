@@ -0,0 +1,77 @@
+//! Visualization providers selectable by name
+//!
+//! `viz_tool` and `--viz-tool` pick a provider from a process-wide registry
+//! holding the built-in Graphviz, D2, Mermaid and SVG providers. Crates embedding jackdaw
+//! add their own, e.g. for PlantUML, with [`register_provider`]; the engine
+//! and the configuration checks see them like the built-in ones.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use tracing::warn;
+
+use super::{
+    BuiltinProvider, D2Provider, DiagramFormat, GraphvizProvider, MermaidProvider, Result,
+    VisualizationProvider,
+};
+
+static PROVIDERS: LazyLock<RwLock<BTreeMap<String, Arc<dyn VisualizationProvider>>>> =
+    LazyLock::new(|| {
+        let builtin: [Arc<dyn VisualizationProvider>; 4] = [
+            Arc::new(GraphvizProvider::new()),
+            Arc::new(D2Provider::new()),
+            Arc::new(MermaidProvider::new()),
+            Arc::new(BuiltinProvider::new()),
+        ];
+        RwLock::new(
+            builtin
+                .into_iter()
+                .map(|provider| (provider.name().to_lowercase(), provider))
+                .collect(),
+        )
+    });
+
+/// Make `provider` selectable by its name, replacing any provider of the same name
+pub fn register_provider(provider: Arc<dyn VisualizationProvider>) {
+    if let Ok(mut providers) = PROVIDERS.write() {
+        providers.insert(provider.name().to_lowercase(), provider);
+    }
+}
+
+/// The provider registered under `name`, ignoring case
+#[must_use]
+pub fn provider(name: &str) -> Option<Arc<dyn VisualizationProvider>> {
+    PROVIDERS
+        .read()
+        .ok()
+        .and_then(|providers| providers.get(&name.to_lowercase()).cloned())
+}
+
+/// Names of the registered providers, in order
+#[must_use]
+pub fn provider_names() -> Vec<String> {
+    PROVIDERS
+        .read()
+        .map(|providers| providers.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// `provider`, or the built-in SVG renderer in its place if the tool
+/// `provider` runs is not installed and the diagram is to be an SVG
+///
+/// # Errors
+///
+/// Returns an error if probing whether the tool is installed fails.
+pub fn installed_or_builtin(
+    provider: Arc<dyn VisualizationProvider>,
+    format: DiagramFormat,
+) -> Result<Arc<dyn VisualizationProvider>> {
+    if format == DiagramFormat::Svg && !provider.is_available()? {
+        warn!(
+            "{} is not installed, drawing the diagram with the built-in renderer",
+            provider.name()
+        );
+        return Ok(Arc::new(BuiltinProvider::new()));
+    }
+    Ok(provider)
+}
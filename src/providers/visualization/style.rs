@@ -0,0 +1,287 @@
+//! How diagrams are drawn: colors, color scheme and font size
+//!
+//! The built-in providers draw in a [`DiagramStyle`], set with `viz_palette`,
+//! `viz_color_scheme`, `viz_font_size` and `viz_task_colors` (or their
+//! `--viz-*` flags). The `colorblind` palette uses the Okabe-Ito colors,
+//! which stay distinguishable with the common forms of color blindness, and
+//! the state of a task is also shown by its border: failed tasks have a thick
+//! one and running tasks a dashed one, so it can be told without the colors.
+
+use serde::{Deserialize, Serialize};
+use serverless_workflow_core::models::task::TaskDefinition;
+use std::collections::BTreeMap;
+
+use super::TaskExecutionState;
+
+/// Names of the task types whose color `viz_task_colors` sets
+pub const TASK_TYPES: &[&str] = &[
+    "call", "do", "emit", "for", "fork", "listen", "raise", "run", "set", "switch", "try", "wait",
+];
+
+/// Colors diagrams are drawn with, chosen with `viz_palette`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    #[default]
+    Default,
+    /// The Okabe-Ito colors, safe for readers with color vision deficiencies
+    Colorblind,
+}
+
+impl std::fmt::Display for Palette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::Colorblind => "colorblind",
+        })
+    }
+}
+
+impl std::str::FromStr for Palette {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "default" => Ok(Self::Default),
+            "colorblind" => Ok(Self::Colorblind),
+            _ => Err(format!(
+                "unknown palette '{name}', expected default or colorblind"
+            )),
+        }
+    }
+}
+
+/// Background diagrams are drawn on, chosen with `viz_color_scheme`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl std::fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+        })
+    }
+}
+
+impl std::str::FromStr for ColorScheme {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "light" => Ok(Self::Light),
+            "dark" => Ok(Self::Dark),
+            _ => Err(format!(
+                "unknown color scheme '{name}', expected light or dark"
+            )),
+        }
+    }
+}
+
+/// The colors of a palette
+struct Colors {
+    call: &'static str,
+    do_: &'static str,
+    emit: &'static str,
+    for_: &'static str,
+    fork: &'static str,
+    listen: &'static str,
+    raise: &'static str,
+    run: &'static str,
+    set: &'static str,
+    switch: &'static str,
+    try_: &'static str,
+    wait: &'static str,
+    start: &'static str,
+    end: &'static str,
+    success: &'static str,
+    failed: &'static str,
+    running: &'static str,
+    /// Workflows of a call graph
+    workflow: &'static str,
+    /// Workflows of a call graph that call each other in a cycle
+    cyclic: &'static str,
+    /// Missing references and cyclic calls
+    alert: &'static str,
+}
+
+const DEFAULT_COLORS: Colors = Colors {
+    call: "#87CEEB",   // Sky blue
+    do_: "#B0C4DE",    // Light steel blue
+    emit: "#FFDAB9",   // Peach
+    for_: "#98FB98",   // Pale green
+    fork: "#FFA07A",   // Light salmon
+    listen: "#E0BBE4", // Lavender
+    raise: "#FF6B6B",  // Red
+    run: "#DDA0DD",    // Plum
+    set: "#F0E68C",    // Khaki
+    switch: "#FFD700", // Gold
+    try_: "#FFE4B5",   // Moccasin
+    wait: "#D3D3D3",   // Light gray
+    start: "#90EE90",
+    end: "#FFB6C1",
+    success: "#90EE90",
+    failed: "#FF6B6B",
+    running: "#FFD700",
+    workflow: "#87CEEB",
+    cyclic: "#FFA07A",
+    alert: "#FF6B6B",
+};
+
+const COLORBLIND_COLORS: Colors = Colors {
+    call: "#56B4E9",   // Sky blue
+    do_: "#BBBBBB",    // Gray
+    emit: "#E69F00",   // Orange
+    for_: "#56B4E9",   // Sky blue
+    fork: "#E69F00",   // Orange
+    listen: "#CC79A7", // Reddish purple
+    raise: "#D55E00",  // Vermillion
+    run: "#CC79A7",    // Reddish purple
+    set: "#F0E442",    // Yellow
+    switch: "#E69F00", // Orange
+    try_: "#F0E442",   // Yellow
+    wait: "#BBBBBB",   // Gray
+    start: "#009E73",
+    end: "#CC79A7",
+    success: "#009E73",
+    failed: "#D55E00",
+    running: "#F0E442",
+    workflow: "#56B4E9",
+    cyclic: "#E69F00",
+    alert: "#D55E00",
+};
+
+/// How a diagram is drawn
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiagramStyle {
+    pub palette: Palette,
+    pub color_scheme: ColorScheme,
+    /// Font size of labels; the provider's default if `None`
+    pub font_size: Option<u32>,
+    /// Colors of task types, by the names in [`TASK_TYPES`], taking
+    /// precedence over the palette
+    pub task_colors: BTreeMap<String, String>,
+}
+
+impl DiagramStyle {
+    fn colors(&self) -> &'static Colors {
+        match self.palette {
+            Palette::Default => &DEFAULT_COLORS,
+            Palette::Colorblind => &COLORBLIND_COLORS,
+        }
+    }
+
+    /// Fill color of a task
+    #[must_use]
+    pub fn task_color(&self, task: &TaskDefinition) -> &str {
+        let colors = self.colors();
+        let (task_type, color) = match task {
+            TaskDefinition::Call(_) => ("call", colors.call),
+            TaskDefinition::Do(_) => ("do", colors.do_),
+            TaskDefinition::Emit(_) => ("emit", colors.emit),
+            TaskDefinition::For(_) => ("for", colors.for_),
+            TaskDefinition::Fork(_) => ("fork", colors.fork),
+            TaskDefinition::Listen(_) => ("listen", colors.listen),
+            TaskDefinition::Raise(_) => ("raise", colors.raise),
+            TaskDefinition::Run(_) => ("run", colors.run),
+            TaskDefinition::Set(_) => ("set", colors.set),
+            TaskDefinition::Switch(_) => ("switch", colors.switch),
+            TaskDefinition::Try(_) => ("try", colors.try_),
+            TaskDefinition::Wait(_) => ("wait", colors.wait),
+        };
+        self.task_colors
+            .get(task_type)
+            .map_or(color, String::as_str)
+    }
+
+    /// Fill color of a task an instance reached, `None` for tasks it did not run
+    #[must_use]
+    pub fn state_color(&self, state: &TaskExecutionState) -> Option<&'static str> {
+        let colors = self.colors();
+        match state {
+            TaskExecutionState::Success => Some(colors.success),
+            TaskExecutionState::Failed => Some(colors.failed),
+            TaskExecutionState::Running => Some(colors.running),
+            TaskExecutionState::NotExecuted => None,
+        }
+    }
+
+    #[must_use]
+    pub fn start_color(&self) -> &'static str {
+        self.colors().start
+    }
+
+    #[must_use]
+    pub fn end_color(&self) -> &'static str {
+        self.colors().end
+    }
+
+    /// Fill color of the workflows of a call graph, depending on whether
+    /// they are part of a cycle
+    #[must_use]
+    pub fn workflow_color(&self, cyclic: bool) -> &'static str {
+        if cyclic {
+            self.colors().cyclic
+        } else {
+            self.colors().workflow
+        }
+    }
+
+    /// Color of missing references and cyclic calls
+    #[must_use]
+    pub fn alert_color(&self) -> &'static str {
+        self.colors().alert
+    }
+
+    /// Background of the diagram, `None` for the provider's default
+    #[must_use]
+    pub fn background(&self) -> Option<&'static str> {
+        match self.color_scheme {
+            ColorScheme::Light => None,
+            ColorScheme::Dark => Some("#1E1E1E"),
+        }
+    }
+
+    /// Color of edges and of the labels drawn on the background
+    #[must_use]
+    pub fn foreground(&self) -> &'static str {
+        match self.color_scheme {
+            ColorScheme::Light => "#000000",
+            ColorScheme::Dark => "#D0D0D0",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    #[test]
+    fn test_task_colors_take_precedence_over_the_palette() {
+        let task: TaskDefinition = serde_yaml::from_str("set:\n  done: true\n").unwrap();
+        let mut style = DiagramStyle {
+            palette: Palette::Colorblind,
+            ..DiagramStyle::default()
+        };
+        assert_eq!(style.task_color(&task), "#F0E442");
+
+        style
+            .task_colors
+            .insert("set".to_string(), "#FFFFFF".to_string());
+        assert_eq!(style.task_color(&task), "#FFFFFF");
+    }
+
+    #[test]
+    fn test_palettes_parse_by_name() {
+        assert_eq!("colorblind".parse::<Palette>(), Ok(Palette::Colorblind));
+        assert_eq!("dark".parse::<ColorScheme>(), Ok(ColorScheme::Dark));
+        assert!("neon".parse::<Palette>().is_err());
+    }
+}
@@ -0,0 +1,344 @@
+//! Signature verification for workflow definitions and bundles
+//!
+//! Signatures are the detached ECDSA P-256 / SHA-256 signatures produced by
+//! `cosign sign-blob --key`: a base64 encoded DER signature stored next to the
+//! signed file as `<file>.sig`. Trusted identities are the PEM encoded public
+//! keys (as written by `cosign generate-key-pair`) listed in `trusted_keys`.
+//!
+//! The [`SignaturePolicy`] decides what happens to a file without a valid
+//! signature from a trusted key: nothing (`off`), a warning (`warn`), or a
+//! refusal to run it (`enforce`).
+
+use p256::ecdsa::signature::Verifier as _;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use snafu::prelude::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::config::JackdawConfig;
+
+/// Extension appended to a signed file's name to locate its signature
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Policies accepted by `signature_policy`
+pub const VALID_POLICIES: &[&str] = &["off", "warn", "enforce"];
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Unknown signature policy '{policy}', expected one of: off, warn, enforce"))]
+    UnknownPolicy { policy: String },
+
+    #[snafu(display("Failed to read trusted key '{}': {source}", path.display()))]
+    ReadKey {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Trusted key '{}' is not a PEM encoded P-256 public key", path.display()))]
+    InvalidKey { path: PathBuf },
+
+    #[snafu(display("Signature enforcement is enabled but no trusted keys are configured"))]
+    NoTrustedKeys,
+
+    #[snafu(display("Failed to read '{}': {source}", path.display()))]
+    ReadSigned {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Refusing to run {subject}: {reason}"))]
+    Untrusted { subject: String, reason: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// How unsigned or untrusted workflow definitions are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Signatures are not checked
+    #[default]
+    Off,
+    /// Missing or invalid signatures are reported but the workflow still runs
+    Warn,
+    /// Only workflows signed by a trusted key are run
+    Enforce,
+}
+
+impl FromStr for SignaturePolicy {
+    type Err = Error;
+
+    fn from_str(policy: &str) -> Result<Self> {
+        match policy.to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "enforce" => Ok(Self::Enforce),
+            _ => UnknownPolicySnafu { policy }.fail(),
+        }
+    }
+}
+
+impl fmt::Display for SignaturePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Warn => write!(f, "warn"),
+            Self::Enforce => write!(f, "enforce"),
+        }
+    }
+}
+
+/// A public key whose signatures are trusted
+#[derive(Debug, Clone)]
+pub struct TrustedKey {
+    /// Name reported when a signature from this key is accepted
+    pub identity: String,
+    key: VerifyingKey,
+}
+
+impl TrustedKey {
+    /// Parse a PEM encoded P-256 public key, returning `None` if `pem` is not one
+    #[must_use]
+    pub fn from_pem(identity: impl Into<String>, pem: &str) -> Option<Self> {
+        VerifyingKey::from_public_key_pem(pem.trim())
+            .ok()
+            .map(|key| Self {
+                identity: identity.into(),
+                key,
+            })
+    }
+
+    /// Load a PEM encoded public key, identified by its file path
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or holds no P-256 public key.
+    pub fn load(path: &Path) -> Result<Self> {
+        let pem = std::fs::read_to_string(path).context(ReadKeySnafu { path })?;
+        Self::from_pem(path.display().to_string(), &pem).context(InvalidKeySnafu { path })
+    }
+
+    fn verifies(&self, data: &[u8], signature: &Signature) -> bool {
+        self.key.verify(data, signature).is_ok()
+    }
+}
+
+/// Outcome of checking a signed file against the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// The policy is `off`; nothing was checked
+    Skipped,
+    /// The signature was made by the named trusted key
+    Verified { identity: String },
+    /// The signature is missing or untrusted, which the `warn` policy allows
+    Unverified { reason: String },
+}
+
+/// Checks workflow definitions and bundles against a signature policy
+#[derive(Debug, Clone, Default)]
+pub struct SignatureVerifier {
+    policy: SignaturePolicy,
+    keys: Vec<TrustedKey>,
+}
+
+impl SignatureVerifier {
+    /// Create a verifier from a policy and the keys it trusts
+    ///
+    /// # Errors
+    /// Returns an error if the policy is `enforce` and no keys are trusted.
+    pub fn new(policy: SignaturePolicy, keys: Vec<TrustedKey>) -> Result<Self> {
+        ensure!(
+            policy != SignaturePolicy::Enforce || !keys.is_empty(),
+            NoTrustedKeysSnafu
+        );
+        Ok(Self { policy, keys })
+    }
+
+    /// Create a verifier from `signature_policy` and `trusted_keys`
+    ///
+    /// # Errors
+    /// Returns an error if the policy is unknown, a key cannot be loaded, or
+    /// enforcement is enabled without trusted keys.
+    pub fn from_config(config: &JackdawConfig) -> Result<Self> {
+        let policy = match &config.signature_policy {
+            Some(policy) => policy.parse()?,
+            None => SignaturePolicy::Off,
+        };
+        if policy == SignaturePolicy::Off {
+            return Ok(Self::default());
+        }
+        let keys = config
+            .trusted_keys
+            .iter()
+            .map(|path| TrustedKey::load(path))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(policy, keys)
+    }
+
+    #[must_use]
+    pub fn policy(&self) -> SignaturePolicy {
+        self.policy
+    }
+
+    /// Verify a file against the signature stored next to it as `<file>.sig`
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, or the policy is `enforce`
+    /// and the file is not signed by a trusted key.
+    pub fn verify_file(&self, path: &Path) -> Result<Verification> {
+        if self.policy == SignaturePolicy::Off {
+            return Ok(Verification::Skipped);
+        }
+        let data = std::fs::read(path).context(ReadSignedSnafu { path })?;
+        let signature = std::fs::read_to_string(signature_path(path)).ok();
+        self.verify(&path.display().to_string(), &data, signature.as_deref())
+    }
+
+    /// Verify `data` against a base64 encoded signature
+    ///
+    /// `subject` names the data in warnings and errors.
+    ///
+    /// # Errors
+    /// Returns an error if the policy is `enforce` and `signature` is missing
+    /// or was not made by a trusted key.
+    pub fn verify(
+        &self,
+        subject: &str,
+        data: &[u8],
+        signature: Option<&str>,
+    ) -> Result<Verification> {
+        let reason = match self.policy {
+            SignaturePolicy::Off => return Ok(Verification::Skipped),
+            SignaturePolicy::Warn | SignaturePolicy::Enforce => {
+                match self.trusted_identity(data, signature) {
+                    Ok(identity) => return Ok(Verification::Verified { identity }),
+                    Err(reason) => reason,
+                }
+            }
+        };
+
+        if self.policy == SignaturePolicy::Enforce {
+            return UntrustedSnafu {
+                subject: subject.to_string(),
+                reason,
+            }
+            .fail();
+        }
+        tracing::warn!("Signature check failed for {}: {}", subject, reason);
+        Ok(Verification::Unverified { reason })
+    }
+
+    /// The identity of the trusted key that made `signature`, or why there is none
+    fn trusted_identity(
+        &self,
+        data: &[u8],
+        signature: Option<&str>,
+    ) -> std::result::Result<String, String> {
+        let signature = signature.ok_or_else(|| "no signature found".to_string())?;
+        let der = BASE64
+            .decode(signature.trim())
+            .map_err(|e| format!("signature is not valid base64: {e}"))?;
+        let signature = Signature::from_der(&der)
+            .map_err(|_| "signature is not a DER encoded ECDSA signature".to_string())?;
+        self.keys
+            .iter()
+            .find(|key| key.verifies(data, &signature))
+            .map(|key| key.identity.clone())
+            .ok_or_else(|| "signature was not made by a trusted key".to_string())
+    }
+}
+
+/// Path of the detached signature for `path`
+#[must_use]
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use p256::ecdsa::SigningKey;
+    use p256::ecdsa::signature::Signer;
+    use p256::pkcs8::{EncodePublicKey, LineEnding};
+
+    const WORKFLOW: &[u8] = b"document:\n  name: signed\n";
+
+    fn key_pair(identity: &str) -> (SigningKey, TrustedKey) {
+        let signing = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let pem = signing
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        (signing, TrustedKey::from_pem(identity, &pem).unwrap())
+    }
+
+    fn sign(key: &SigningKey, data: &[u8]) -> String {
+        let signature: Signature = key.sign(data);
+        BASE64.encode(signature.to_der().as_bytes())
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            "Enforce".parse::<SignaturePolicy>().unwrap(),
+            SignaturePolicy::Enforce
+        );
+        assert!("strict".parse::<SignaturePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_enforce_requires_trusted_keys() {
+        assert!(SignatureVerifier::new(SignaturePolicy::Enforce, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_verifies_signature_from_trusted_key() {
+        let (signing, trusted) = key_pair("release");
+        let verifier = SignatureVerifier::new(SignaturePolicy::Enforce, vec![trusted]).unwrap();
+        let signature = sign(&signing, WORKFLOW);
+
+        assert_eq!(
+            verifier
+                .verify("workflow", WORKFLOW, Some(&signature))
+                .unwrap(),
+            Verification::Verified {
+                identity: "release".to_string()
+            }
+        );
+        assert!(
+            verifier
+                .verify("workflow", b"tampered", Some(&signature))
+                .is_err()
+        );
+        assert!(verifier.verify("workflow", WORKFLOW, None).is_err());
+    }
+
+    #[test]
+    fn test_warn_policy_allows_unsigned() {
+        let (_, trusted) = key_pair("release");
+        let verifier = SignatureVerifier::new(SignaturePolicy::Warn, vec![trusted]).unwrap();
+        assert!(matches!(
+            verifier.verify("workflow", WORKFLOW, None).unwrap(),
+            Verification::Unverified { .. }
+        ));
+    }
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("flows/order.sw.yaml")),
+            PathBuf::from("flows/order.sw.yaml.sig")
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Environment passed to processes started by `run` tasks
+//!
+//! Script, shell and container tasks do not inherit the environment of the
+//! jackdaw process. Only the variables allowed by the engine's [`EnvPolicy`]
+//! are passed on, together with the task's own `environment` and any secrets
+//! it asks for. A task can adjust the policy in its metadata:
+//!
+//! ```yaml
+//! - deploy:
+//!     run:
+//!       shell:
+//!         command: ./deploy.sh
+//!     metadata:
+//!       env:
+//!         allow: [HOME, AWS_*]
+//!         deny: [AWS_SECRET_ACCESS_KEY]
+//!         fromSecret: API_KEY
+//! ```
+//!
+//! `fromSecret` takes a secret name, a list of names, or a map of variable
+//! names to secret names. Secrets are resolved through the engine's
+//! [`SecretsProvider`] when the task runs. Variables denied by the engine
+//! policy are never passed on, whatever the task allows.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::secrets::{Result, SecretsProvider};
+
+/// Which variables of the jackdaw process environment tasks may see
+///
+/// Patterns are exact variable names or prefixes ending in `*`. The default
+/// policy allows nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvPolicy {
+    /// Variables passed to every task
+    pub allow: Vec<String>,
+    /// Variables never passed to a task, even if the task allows them
+    pub deny: Vec<String>,
+}
+
+impl EnvPolicy {
+    #[must_use]
+    pub fn new(allow: Vec<String>, deny: Vec<String>) -> Self {
+        Self { allow, deny }
+    }
+
+    /// Whether the variable `name` is passed to a task with the given settings
+    #[must_use]
+    pub fn permits(&self, name: &str, task: &TaskEnv) -> bool {
+        if matches_any(&self.deny, name) || matches_any(&task.deny, name) {
+            return false;
+        }
+        matches_any(&self.allow, name) || matches_any(&task.allow, name)
+    }
+
+    /// The variables of the jackdaw process that a task may see
+    #[must_use]
+    pub fn inherited(&self, task: &TaskEnv) -> HashMap<String, String> {
+        std::env::vars()
+            .filter(|(name, _)| self.permits(name, task))
+            .collect()
+    }
+}
+
+/// A task's environment settings, read from `metadata.env`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TaskEnv {
+    /// Additional variables passed to this task
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    /// Variables withheld from this task
+    #[serde(default)]
+    pub deny: Vec<String>,
+
+    /// Secrets injected as environment variables
+    #[serde(default)]
+    pub from_secret: SecretRefs,
+}
+
+/// The secrets a task asks for in `fromSecret`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SecretRefs {
+    /// A single secret, exposed under its own name
+    One(String),
+    /// Several secrets, each exposed under its own name
+    Many(Vec<String>),
+    /// Variable names mapped to the secrets they receive
+    Mapped(HashMap<String, String>),
+}
+
+impl Default for SecretRefs {
+    fn default() -> Self {
+        Self::Many(Vec::new())
+    }
+}
+
+impl SecretRefs {
+    /// Pairs of (variable name, secret name)
+    fn entries(&self) -> Vec<(&str, &str)> {
+        match self {
+            Self::One(name) => vec![(name.as_str(), name.as_str())],
+            Self::Many(names) => names.iter().map(|n| (n.as_str(), n.as_str())).collect(),
+            Self::Mapped(names) => names
+                .iter()
+                .map(|(var, secret)| (var.as_str(), secret.as_str()))
+                .collect(),
+        }
+    }
+}
+
+impl TaskEnv {
+    /// Read `metadata.env` from a serialized task definition
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `metadata.env` is malformed
+    pub fn from_task(task: &serde_json::Value) -> serde_json::Result<Self> {
+        match task.pointer("/metadata/env") {
+            Some(env) => serde_json::from_value(env.clone()),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Resolve the secrets requested by `fromSecret`, keyed by variable name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a secret does not exist or cannot be read
+    pub async fn secrets(&self, provider: &dyn SecretsProvider) -> Result<HashMap<String, String>> {
+        let mut secrets = HashMap::new();
+        for (var, secret) in self.from_secret.entries() {
+            secrets.insert(var.to_string(), provider.require(secret).await?);
+        }
+        Ok(secrets)
+    }
+}
+
+fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => pattern == name,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    #![allow(clippy::panic)]
+
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    #[derive(Debug)]
+    struct StaticSecrets;
+
+    #[async_trait]
+    impl SecretsProvider for StaticSecrets {
+        async fn get(&self, name: &str) -> Result<Option<String>> {
+            Ok((name == "api-key").then(|| "s3cr3t".to_string()))
+        }
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_default_policy_denies_everything() {
+        let policy = EnvPolicy::default();
+        assert!(!policy.permits("PATH", &TaskEnv::default()));
+        assert!(policy.inherited(&TaskEnv::default()).is_empty());
+    }
+
+    #[test]
+    fn test_task_allow_and_deny() {
+        let policy = EnvPolicy::new(strings(&["PATH"]), strings(&["AWS_SECRET_*"]));
+        let task = TaskEnv {
+            allow: strings(&["AWS_*"]),
+            deny: strings(&["PATH"]),
+            ..TaskEnv::default()
+        };
+        assert!(policy.permits("AWS_REGION", &task));
+        assert!(!policy.permits("AWS_SECRET_ACCESS_KEY", &task));
+        assert!(!policy.permits("PATH", &task));
+        assert!(policy.permits("PATH", &TaskEnv::default()));
+    }
+
+    #[tokio::test]
+    async fn test_from_task_resolves_secrets() {
+        let task = json!({
+            "run": { "shell": { "command": "env" } },
+            "metadata": { "env": { "fromSecret": { "API_KEY": "api-key" } } }
+        });
+        let env = TaskEnv::from_task(&task).unwrap();
+        let secrets = env.secrets(&StaticSecrets).await.unwrap();
+        assert_eq!(secrets.get("API_KEY").map(String::as_str), Some("s3cr3t"));
+
+        let missing =
+            TaskEnv::from_task(&json!({ "metadata": { "env": { "fromSecret": "OTHER" } } }))
+                .unwrap();
+        assert!(missing.secrets(&StaticSecrets).await.is_err());
+    }
+
+    #[test]
+    fn test_task_without_env_metadata() {
+        let env = TaskEnv::from_task(&json!({ "run": {} })).unwrap();
+        assert!(env.allow.is_empty());
+        assert!(env.from_secret.entries().is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+//! Metrics of long-running engines, in the Prometheus text format
+//!
+//! A [`Telemetry`] given to
+//! [`DurableEngineBuilder::with_telemetry`](crate::DurableEngineBuilder::with_telemetry)
+//! counts the workflows the engine runs by how they end, times their tasks
+//! and counts the requests their listeners handle. Loops that schedule work,
+//! such as the queue polling of `jackdaw worker`, report their ticks to it.
+//! [`DurableEngine::metrics`](crate::durableengine::DurableEngine::metrics)
+//! renders these with the hit ratio of the cache and the memory gauges, for
+//! the `/metrics` endpoints of `jackdaw serve` and `jackdaw worker`.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::cache::CacheStats;
+use crate::observer::WorkflowObserver;
+use crate::workflow::WorkflowEvent;
+
+/// Upper bounds, in seconds, of the buckets of the task duration histogram
+pub const TASK_DURATION_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0, 60.0, 300.0,
+];
+
+/// Upper bounds, in seconds, of the buckets of the scheduler tick histogram
+pub const TICK_DURATION_BUCKETS: [f64; 8] = [0.001, 0.01, 0.1, 1.0, 10.0, 60.0, 300.0, 3600.0];
+
+/// What a tick of a scheduler loop did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TickOutcome {
+    /// The tick took an instance from the queue and ran it
+    Ran,
+    /// The queue was empty
+    Idle,
+    /// The queue could not be read
+    Failed,
+}
+
+impl TickOutcome {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Ran => "ran",
+            Self::Idle => "idle",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Observations sorted into cumulative buckets
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    /// Observations of at most each bound; the last is for `+Inf`
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        if let Some(count) = self.counts.get_mut(bucket) {
+            *count += 1;
+        }
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        let total: u64 = self.counts.iter().sum();
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {total}");
+        let _ = writeln!(out, "{name}_sum {}", self.sum);
+        let _ = writeln!(out, "{name}_count {total}");
+    }
+}
+
+/// The ticks of a scheduler loop
+#[derive(Debug)]
+struct SchedulerStats {
+    ticks: BTreeMap<TickOutcome, u64>,
+    durations: Histogram,
+    /// When the last tick ended, in seconds since the Unix epoch
+    last_tick: Option<f64>,
+}
+
+/// Metrics of an engine, collected while it runs
+///
+/// Counters start at zero when the telemetry is created and only grow, as
+/// Prometheus expects; they are not persisted.
+#[derive(Debug)]
+pub struct Telemetry {
+    /// Workflow events, by the status they move instances to
+    workflows: Mutex<BTreeMap<&'static str, u64>>,
+    /// Task events, by the status they move tasks to
+    tasks: Mutex<BTreeMap<&'static str, u64>>,
+    task_durations: Mutex<Histogram>,
+    /// Requests handled by listeners, by protocol, listen task and outcome
+    listener_requests: Mutex<BTreeMap<(&'static str, String, &'static str), u64>>,
+    scheduler: Mutex<SchedulerStats>,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Telemetry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            workflows: Mutex::default(),
+            tasks: Mutex::default(),
+            task_durations: Mutex::new(Histogram::new(&TASK_DURATION_BUCKETS)),
+            listener_requests: Mutex::default(),
+            scheduler: Mutex::new(SchedulerStats {
+                ticks: BTreeMap::new(),
+                durations: Histogram::new(&TICK_DURATION_BUCKETS),
+                last_tick: None,
+            }),
+        }
+    }
+
+    /// Count a request handled by the listener of the listen task `task_name`
+    pub fn record_listener_request(&self, protocol: &'static str, task_name: &str, ok: bool) {
+        let outcome = if ok { "ok" } else { "error" };
+        if let Ok(mut requests) = self.listener_requests.lock() {
+            *requests
+                .entry((protocol, task_name.to_string(), outcome))
+                .or_default() += 1;
+        }
+    }
+
+    /// Count a tick of a scheduler loop that took `duration`
+    pub fn record_tick(&self, outcome: TickOutcome, duration: Duration) {
+        if let Ok(mut scheduler) = self.scheduler.lock() {
+            *scheduler.ticks.entry(outcome).or_default() += 1;
+            scheduler.durations.observe(duration.as_secs_f64());
+            #[allow(clippy::cast_precision_loss)]
+            let now = Utc::now().timestamp_millis() as f64 / 1000.0;
+            scheduler.last_tick = Some(now);
+        }
+    }
+
+    fn count(counters: &Mutex<BTreeMap<&'static str, u64>>, status: &'static str) {
+        if let Ok(mut counters) = counters.lock() {
+            *counters.entry(status).or_default() += 1;
+        }
+    }
+
+    /// The metrics in the Prometheus text format
+    #[must_use]
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        if let Ok(workflows) = self.workflows.lock() {
+            counter(
+                &mut out,
+                "jackdaw_workflows_total",
+                "Workflow instances that reached a status",
+                workflows
+                    .iter()
+                    .map(|(status, count)| (format!("status=\"{status}\""), *count)),
+            );
+        }
+        if let Ok(tasks) = self.tasks.lock() {
+            counter(
+                &mut out,
+                "jackdaw_tasks_total",
+                "Tasks that reached a status",
+                tasks
+                    .iter()
+                    .map(|(status, count)| (format!("status=\"{status}\""), *count)),
+            );
+        }
+        if let Ok(durations) = self.task_durations.lock() {
+            durations.render(
+                &mut out,
+                "jackdaw_task_duration_seconds",
+                "How long completed tasks ran",
+            );
+        }
+        if let Ok(requests) = self.listener_requests.lock() {
+            counter(
+                &mut out,
+                "jackdaw_listener_requests_total",
+                "Requests handled by the listeners of listen tasks",
+                requests.iter().map(|((protocol, task, outcome), count)| {
+                    (
+                        format!(
+                            "protocol=\"{protocol}\",task=\"{}\",outcome=\"{outcome}\"",
+                            escape_label(task)
+                        ),
+                        *count,
+                    )
+                }),
+            );
+        }
+        if let Ok(scheduler) = self.scheduler.lock() {
+            counter(
+                &mut out,
+                "jackdaw_scheduler_ticks_total",
+                "Ticks of the loop taking queued instances, by what they did",
+                scheduler
+                    .ticks
+                    .iter()
+                    .map(|(outcome, count)| (format!("outcome=\"{}\"", outcome.label()), *count)),
+            );
+            scheduler.durations.render(
+                &mut out,
+                "jackdaw_scheduler_tick_duration_seconds",
+                "How long ticks took, including the instances they ran",
+            );
+            if let Some(last_tick) = scheduler.last_tick {
+                let _ = writeln!(
+                    out,
+                    "# HELP jackdaw_scheduler_last_tick_timestamp_seconds When the last tick ended\n\
+                     # TYPE jackdaw_scheduler_last_tick_timestamp_seconds gauge\n\
+                     jackdaw_scheduler_last_tick_timestamp_seconds {last_tick}"
+                );
+            }
+        }
+        out
+    }
+}
+
+#[async_trait]
+impl WorkflowObserver for Telemetry {
+    async fn on_event(&self, event: &WorkflowEvent) {
+        match event {
+            WorkflowEvent::WorkflowStarted { .. } => Self::count(&self.workflows, "started"),
+            WorkflowEvent::WorkflowCompleted { .. } => Self::count(&self.workflows, "completed"),
+            WorkflowEvent::WorkflowFailed { .. } => Self::count(&self.workflows, "failed"),
+            WorkflowEvent::WorkflowCancelled { .. } => Self::count(&self.workflows, "cancelled"),
+            WorkflowEvent::WorkflowSuspended { .. } => Self::count(&self.workflows, "suspended"),
+            WorkflowEvent::WorkflowResumed { .. } => Self::count(&self.workflows, "resumed"),
+            WorkflowEvent::TaskCompleted { duration_ms, .. } => {
+                Self::count(&self.tasks, "completed");
+                if let Ok(mut durations) = self.task_durations.lock() {
+                    #[allow(clippy::cast_precision_loss)]
+                    durations.observe((*duration_ms).max(0) as f64 / 1000.0);
+                }
+            }
+            WorkflowEvent::TaskFaulted { .. } => Self::count(&self.tasks, "faulted"),
+            WorkflowEvent::TaskRetried { .. } => Self::count(&self.tasks, "retried"),
+            WorkflowEvent::TaskCancelled { .. } => Self::count(&self.tasks, "cancelled"),
+            WorkflowEvent::TaskSkipped { .. } => Self::count(&self.tasks, "skipped"),
+            WorkflowEvent::TaskCacheHit { .. } => Self::count(&self.tasks, "cached"),
+            WorkflowEvent::TaskEntered { .. }
+            | WorkflowEvent::TaskCreated { .. }
+            | WorkflowEvent::TaskStarted { .. }
+            | WorkflowEvent::WorkflowCorrelationStarted { .. }
+            | WorkflowEvent::WorkflowCorrelationCompleted { .. }
+            | WorkflowEvent::TaskSuspended { .. }
+            | WorkflowEvent::TaskResumed { .. }
+            | WorkflowEvent::TaskReaped { .. }
+            | WorkflowEvent::SwitchCaseMatched { .. }
+            | WorkflowEvent::ErrorCaught { .. }
+            | WorkflowEvent::WatchesEvaluated { .. }
+            | WorkflowEvent::ShadowCompared { .. } => {}
+        }
+    }
+}
+
+/// The lookups of a cache and their hit ratio, in the Prometheus text format
+#[must_use]
+pub fn cache_metrics(stats: &CacheStats) -> String {
+    let mut out = String::new();
+    counter(
+        &mut out,
+        "jackdaw_cache_lookups_total",
+        "Lookups of cached task results",
+        [
+            ("result=\"hit\"".to_string(), stats.hits),
+            ("result=\"miss\"".to_string(), stats.misses),
+        ],
+    );
+    if let Some(ratio) = stats.hit_rate() {
+        let _ = writeln!(
+            out,
+            "# HELP jackdaw_cache_hit_ratio Share of cache lookups that were hits\n\
+             # TYPE jackdaw_cache_hit_ratio gauge\n\
+             jackdaw_cache_hit_ratio {ratio}"
+        );
+    }
+    out
+}
+
+fn counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, u64)>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{name}{{{labels}}} {value}");
+    }
+}
+
+/// `value` escaped for a label value of the Prometheus text format
+pub(crate) fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_events_are_counted() {
+        let telemetry = Telemetry::new();
+        let timestamp = Utc::now();
+        for duration_ms in [3, 40, 2_000] {
+            telemetry
+                .on_event(&WorkflowEvent::TaskCompleted {
+                    instance_id: "i-1".to_string(),
+                    task_name: "greet".to_string(),
+                    result: json!({}),
+                    timestamp,
+                    duration_ms,
+                })
+                .await;
+        }
+        telemetry
+            .on_event(&WorkflowEvent::WorkflowFailed {
+                instance_id: "i-1".to_string(),
+                error: "boom".to_string(),
+                timestamp,
+            })
+            .await;
+        telemetry.record_listener_request("http", "onOrder", true);
+        telemetry.record_tick(TickOutcome::Idle, Duration::from_millis(2));
+
+        let text = telemetry.to_prometheus();
+        assert!(text.contains("jackdaw_workflows_total{status=\"failed\"} 1"));
+        assert!(text.contains("jackdaw_tasks_total{status=\"completed\"} 3"));
+        assert!(text.contains("jackdaw_task_duration_seconds_bucket{le=\"0.005\"} 1"));
+        assert!(text.contains("jackdaw_task_duration_seconds_bucket{le=\"0.05\"} 2"));
+        assert!(text.contains("jackdaw_task_duration_seconds_bucket{le=\"+Inf\"} 3"));
+        assert!(text.contains("jackdaw_task_duration_seconds_sum 2.043"));
+        assert!(text.contains(
+            "jackdaw_listener_requests_total{protocol=\"http\",task=\"onOrder\",outcome=\"ok\"} 1"
+        ));
+        assert!(text.contains("jackdaw_scheduler_ticks_total{outcome=\"idle\"} 1"));
+        assert!(text.contains("jackdaw_scheduler_tick_duration_seconds_bucket{le=\"0.01\"} 1"));
+    }
+
+    #[test]
+    fn test_cache_hit_ratio() {
+        let text = cache_metrics(&CacheStats {
+            hits: 3,
+            misses: 1,
+            entries: 2,
+            bytes: 10,
+        });
+        assert!(text.contains("jackdaw_cache_lookups_total{result=\"miss\"} 1"));
+        assert!(text.contains("jackdaw_cache_hit_ratio 0.75"));
+        assert!(!cache_metrics(&CacheStats::default()).contains("jackdaw_cache_hit_ratio"));
+    }
+}
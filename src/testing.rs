@@ -0,0 +1,286 @@
+//! Tests declared next to workflow definitions
+//!
+//! A workflow file can carry an `x-tests` section listing test cases, which
+//! `jackdaw test` runs. Each case gives an input and checks one of:
+//!
+//! - an `expression`, evaluated against the input,
+//! - a single top-level `task`, run on its own with the input as workflow input,
+//! - or, with neither, the whole workflow.
+//!
+//! ```yaml
+//! x-tests:
+//!   - name: greets by name
+//!     task: greet
+//!     input: { name: Rex }
+//!     expect:
+//!       output: { message: Hello Rex }
+//!   - name: adds
+//!     expression: ${ .a + .b }
+//!     input: { a: 1, b: 2 }
+//!     expect:
+//!       assert: ${ . == 3 }
+//! ```
+//!
+//! `expect.output` must equal the result, `expect.assert` is an expression
+//! that must be `true` for it, and `expect.error` expects a failure whose
+//! message contains the given text. Other commands ignore the section.
+
+use serde::Deserialize;
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use snafu::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::durableengine::DurableEngine;
+use crate::expressions::{self, Evaluation};
+use crate::interpolation::interpolate_env;
+
+/// Key of the test section in workflow files
+pub const TESTS_KEY: &str = "x-tests";
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Failed to read '{}': {source}", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Failed to parse '{}': {source}", path.display()))]
+    Parse {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[snafu(display("Interpolation error: {source}"))]
+    Interpolation { source: crate::interpolation::Error },
+
+    #[snafu(display("Test '{name}' in '{}' {message}", path.display()))]
+    InvalidTest {
+        path: PathBuf,
+        name: String,
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<crate::interpolation::Error> for Error {
+    fn from(source: crate::interpolation::Error) -> Self {
+        Error::Interpolation { source }
+    }
+}
+
+/// A test case of the `x-tests` section
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowTest {
+    pub name: String,
+    /// Input of the expression, task or workflow; an empty object if omitted
+    #[serde(default = "empty_object")]
+    pub input: Value,
+    /// Expression to evaluate against the input
+    pub expression: Option<String>,
+    /// Name of the top-level task to run on its own
+    pub task: Option<String>,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+/// What a test case expects of its result
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    /// The exact output
+    pub output: Option<Value>,
+    /// An expression that must be `true` for the output
+    #[serde(rename = "assert")]
+    pub assertion: Option<String>,
+    /// Text the error message must contain; the run must fail
+    pub error: Option<String>,
+}
+
+fn empty_object() -> Value {
+    Value::Object(serde_json::Map::new())
+}
+
+/// Load a workflow file and the tests declared in it
+///
+/// `${{ env.NAME }}` placeholders are resolved as `jackdaw run` resolves them.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed, or a test case is
+/// malformed or names a task the workflow does not have.
+pub fn load(path: &Path, strict_env: bool) -> Result<(WorkflowDefinition, Vec<WorkflowTest>)> {
+    let yaml = std::fs::read_to_string(path).context(ReadSnafu { path })?;
+    let yaml = interpolate_env(&yaml, strict_env)?;
+    let mut document: serde_yaml::Value =
+        serde_yaml::from_str(&yaml).context(ParseSnafu { path })?;
+
+    let tests = match document
+        .as_mapping_mut()
+        .and_then(|mapping| mapping.remove(TESTS_KEY))
+    {
+        Some(tests) => serde_yaml::from_value(tests).context(ParseSnafu { path })?,
+        None => Vec::new(),
+    };
+    let workflow: WorkflowDefinition =
+        serde_yaml::from_value(document).context(ParseSnafu { path })?;
+
+    for test in &tests {
+        validate_test(path, &workflow, test)?;
+    }
+    Ok((workflow, tests))
+}
+
+fn validate_test(path: &Path, workflow: &WorkflowDefinition, test: &WorkflowTest) -> Result<()> {
+    let invalid = |message: String| {
+        InvalidTestSnafu {
+            path,
+            name: test.name.clone(),
+            message,
+        }
+        .fail()
+    };
+    if test.expression.is_some() && test.task.is_some() {
+        return invalid("names both an expression and a task".to_string());
+    }
+    if let Some(task) = &test.task
+        && !workflow
+            .do_
+            .entries
+            .iter()
+            .any(|entry| entry.contains_key(task))
+    {
+        return invalid(format!(
+            "names task '{task}', which is not a top-level task"
+        ));
+    }
+    Ok(())
+}
+
+/// Run a test case
+///
+/// Tasks and workflows run as instances of `engine`, so it should be one
+/// that the workflow may run in, e.g. with in-memory persistence.
+///
+/// # Errors
+///
+/// Returns why the test failed: an unmet expectation, or the error of a run
+/// that was not expected to fail.
+pub async fn run_test(
+    engine: &DurableEngine,
+    workflow: &WorkflowDefinition,
+    test: &WorkflowTest,
+    timeout: Duration,
+) -> std::result::Result<(), String> {
+    let result = match (&test.expression, &test.task) {
+        (Some(expression), _) => {
+            expressions::evaluate_expression(Evaluation::default(), expression, &test.input)
+                .map_err(|e| e.to_string())
+        }
+        (None, Some(task)) => {
+            let mut single = workflow.clone();
+            single
+                .do_
+                .entries
+                .retain(|entry| entry.contains_key(task.as_str()));
+            run_workflow(engine, single, test.input.clone(), timeout).await
+        }
+        (None, None) => run_workflow(engine, workflow.clone(), test.input.clone(), timeout).await,
+    };
+    check(&test.expect, result)
+}
+
+async fn run_workflow(
+    engine: &DurableEngine,
+    workflow: WorkflowDefinition,
+    input: Value,
+    timeout: Duration,
+) -> std::result::Result<Value, String> {
+    let handle = engine
+        .execute(workflow, input)
+        .await
+        .map_err(|e| e.to_string())?;
+    handle
+        .wait_for_completion(timeout)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Check a result against what a test case expects
+fn check(
+    expect: &Expectation,
+    result: std::result::Result<Value, String>,
+) -> std::result::Result<(), String> {
+    let output = match (result, &expect.error) {
+        (Err(error), Some(expected)) if error.contains(expected.as_str()) => return Ok(()),
+        (Err(error), Some(expected)) => {
+            return Err(format!(
+                "expected an error containing '{expected}', but it failed with: {error}"
+            ));
+        }
+        (Err(error), None) => return Err(format!("failed: {error}")),
+        (Ok(output), Some(expected)) => {
+            return Err(format!(
+                "expected an error containing '{expected}', but got {output}"
+            ));
+        }
+        (Ok(output), None) => output,
+    };
+
+    if let Some(expected) = &expect.output
+        && *expected != output
+    {
+        return Err(format!("expected output {expected}, but got {output}"));
+    }
+    if let Some(assertion) = &expect.assertion {
+        match expressions::evaluate_expression(Evaluation::default(), assertion, &output) {
+            Ok(Value::Bool(true)) => {}
+            Ok(value) => {
+                return Err(format!(
+                    "assertion {assertion} is {value} for output {output}"
+                ));
+            }
+            Err(e) => return Err(format!("assertion {assertion} failed: {e}")),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_output_and_assertion() {
+        let expect = Expectation {
+            output: Some(json!(3)),
+            assertion: Some("${ . > 2 }".to_string()),
+            error: None,
+        };
+        assert_eq!(check(&expect, Ok(json!(3))), Ok(()));
+        assert_eq!(
+            check(&expect, Ok(json!(4))),
+            Err("expected output 3, but got 4".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_expected_error() {
+        let expect = Expectation {
+            error: Some("Broken".to_string()),
+            ..Expectation::default()
+        };
+        assert_eq!(
+            check(&expect, Err("Task Broken failed".to_string())),
+            Ok(())
+        );
+        assert!(check(&expect, Ok(json!({}))).is_err());
+        assert!(check(&Expectation::default(), Err("Broken".to_string())).is_err());
+    }
+}
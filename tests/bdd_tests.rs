@@ -0,0 +1,28 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for running Gherkin scenarios against workflows
+use jackdaw::bdd::run_features;
+use std::path::Path;
+
+#[tokio::test]
+async fn test_passing_scenarios() {
+    assert!(run_features(Path::new("tests/fixtures/bdd/passing"), None, None).await);
+}
+
+#[tokio::test]
+async fn test_failing_scenarios() {
+    assert!(!run_features(Path::new("tests/fixtures/bdd/failing"), None, None).await);
+}
+
+#[tokio::test]
+async fn test_filter_skips_other_scenarios() {
+    assert!(
+        run_features(
+            Path::new("tests/fixtures/bdd/failing"),
+            Some("no such scenario".to_string()),
+            None
+        )
+        .await
+    );
+}
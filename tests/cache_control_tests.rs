@@ -0,0 +1,95 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for per-task cache control with `metadata.cache` and `metadata.cacheKey`,
+/// and the cache statistics it shows up in
+use jackdaw::DurableEngineBuilder;
+use jackdaw::cache::CacheStats;
+use jackdaw::providers::cache::mem::InMemoryCache;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Run the workflow once per input, returning how often each function was called
+/// and the engine's cache statistics
+async fn run_each(inputs: &[Value]) -> ((usize, usize), CacheStats) {
+    let quotes = Arc::new(AtomicUsize::new(0));
+    let rates = Arc::new(AtomicUsize::new(0));
+    let engine = DurableEngineBuilder::new()
+        .with_cache(Arc::new(InMemoryCache::new()))
+        .build()
+        .unwrap();
+    for (name, calls) in [("quote", quotes.clone()), ("rates", rates.clone())] {
+        engine
+            .register_function(name, move |_input: Value| {
+                let calls = calls.clone();
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, String>(json!({ "price": 42 }))
+                }
+            })
+            .unwrap();
+    }
+
+    for input in inputs {
+        let handle = engine
+            .execute(fixtures::load("cache", "control.sw.yaml"), input.clone())
+            .await
+            .unwrap();
+        handle
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .unwrap();
+    }
+    let stats = engine.cache_stats().await.unwrap();
+    (
+        (quotes.load(Ordering::SeqCst), rates.load(Ordering::SeqCst)),
+        stats,
+    )
+}
+
+#[tokio::test]
+async fn test_cache_key_expression_ignores_other_params() {
+    let (calls, stats) = run_each(&[
+        json!({ "symbol": "JDW", "requestId": 1 }),
+        json!({ "symbol": "JDW", "requestId": 2 }),
+        json!({ "symbol": "ABC", "requestId": 3 }),
+    ])
+    .await;
+
+    // The quote is keyed by symbol only; rates opts out of caching entirely
+    assert_eq!(calls, (2, 3));
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+}
+
+#[tokio::test]
+async fn test_invalid_cache_metadata_fails_task() {
+    let engine = DurableEngineBuilder::new()
+        .with_cache(Arc::new(InMemoryCache::new()))
+        .build()
+        .unwrap();
+    engine
+        .register_function("quote", |_input: Value| async move {
+            Ok::<_, String>(json!({}))
+        })
+        .unwrap();
+    let mut workflow = fixtures::load("cache", "control.sw.yaml");
+    let mut value = serde_json::to_value(&workflow).unwrap();
+    *value
+        .pointer_mut("/do/0/quote/metadata")
+        .expect("quote has metadata") = json!({ "cache": "sometimes" });
+    workflow = serde_json::from_value(value).unwrap();
+
+    let handle = engine
+        .execute(workflow, json!({ "symbol": "JDW", "requestId": 1 }))
+        .await
+        .unwrap();
+    let error = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .expect_err("metadata.cache must be a boolean");
+    assert!(error.to_string().contains("metadata.cache"), "{error}");
+}
@@ -0,0 +1,83 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for the expiry of cached task results
+use jackdaw::DurableEngineBuilder;
+use jackdaw::cache::CacheProvider;
+use jackdaw::providers::cache::mem::InMemoryCache;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Run the workflow twice against `cache`, returning how often each function was called
+async fn run_twice(
+    cache: Arc<InMemoryCache>,
+    ttl: Option<Duration>,
+    pause: Duration,
+) -> (usize, usize) {
+    let quotes = Arc::new(AtomicUsize::new(0));
+    let rates = Arc::new(AtomicUsize::new(0));
+    let mut builder = DurableEngineBuilder::new().with_cache(cache);
+    if let Some(ttl) = ttl {
+        builder = builder.with_cache_ttl(ttl);
+    }
+    let engine = builder.build().unwrap();
+    for (name, calls) in [("quote", quotes.clone()), ("rates", rates.clone())] {
+        engine
+            .register_function(name, move |_input: Value| {
+                let calls = calls.clone();
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, String>(json!({ "price": 42 }))
+                }
+            })
+            .unwrap();
+    }
+
+    for run in 0..2 {
+        if run > 0 {
+            tokio::time::sleep(pause).await;
+        }
+        let handle = engine
+            .execute(
+                fixtures::load("cache", "ttl.sw.yaml"),
+                json!({ "symbol": "JDW" }),
+            )
+            .await
+            .unwrap();
+        handle
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .unwrap();
+    }
+    (quotes.load(Ordering::SeqCst), rates.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_task_ttl_expires_cached_result() {
+    let cache = Arc::new(InMemoryCache::new());
+    let calls = run_twice(cache, None, Duration::from_millis(500)).await;
+
+    // Only the task with `metadata.cacheTtl` runs again; the other never expires
+    assert_eq!(calls, (2, 1));
+}
+
+#[tokio::test]
+async fn test_engine_ttl_applies_to_tasks_without_their_own() {
+    let cache = Arc::new(InMemoryCache::new());
+    let calls = run_twice(
+        cache.clone(),
+        Some(Duration::from_secs(3600)),
+        Duration::from_millis(500),
+    )
+    .await;
+    assert_eq!(calls, (2, 1));
+
+    // Both entries are still stored until the quote expires again, and only it is purged
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(cache.purge_expired().await.unwrap(), 1);
+    assert_eq!(cache.purge_all().await.unwrap(), 1);
+}
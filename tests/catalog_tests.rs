@@ -0,0 +1,184 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for calling functions published in local, HTTP and git catalogs
+use jackdaw::DurableEngineBuilder;
+use jackdaw::signing::{SignaturePolicy, SignatureVerifier, TrustedKey};
+use p256::ecdsa::SigningKey;
+use p256::pkcs8::{EncodePublicKey, LineEnding};
+use serde_json::{Value, json};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const ADD_FUNCTION: &str = "set:\n  sum: '${ .a + .b }'\n";
+
+/// A workflow using the catalog `name` at `uri` and running `tasks`
+fn workflow(name: &str, uri: &str, tasks: &str) -> WorkflowDefinition {
+    serde_yaml::from_str(&format!(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: use-catalog
+  version: '1.0.0'
+use:
+  catalogs:
+    {name}:
+      endpoint:
+        uri: {uri}
+do:
+{tasks}"
+    ))
+    .unwrap()
+}
+
+fn publish(root: &Path, name: &str, version: &str, manifest: &str) {
+    let dir = root.join(name).join(version);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("function.yaml"), manifest).unwrap();
+}
+
+async fn run(workflow: WorkflowDefinition, builder: DurableEngineBuilder) -> Value {
+    let engine = builder.build().unwrap();
+    engine
+        .execute(workflow, json!({}))
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_task_manifest_runs_from_named_local_catalog() {
+    let catalog = tempfile::tempdir().unwrap();
+    publish(catalog.path(), "add", "1.0.0", ADD_FUNCTION);
+
+    let output = run(
+        workflow(
+            "shared",
+            &format!("file://{}", catalog.path().display()),
+            "  - add:\n      call: add:1.0.0@shared\n      with:\n        a: 2\n        b: 3\n",
+        ),
+        DurableEngineBuilder::new(),
+    )
+    .await;
+    assert_eq!(output["sum"], json!(5));
+}
+
+#[tokio::test]
+async fn test_http_catalog_function_is_fetched_once() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/functions/greet/1.0.0/function.yaml"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+            "call: http\nwith:\n  method: get\n  endpoint: {}/greeting\n",
+            server.uri()
+        )))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/greeting"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "greeting": "hello" })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let output = run(
+        workflow(
+            "remote",
+            &server.uri(),
+            "  - first:\n      call: greet:1.0.0@remote\n  - second:\n      call: greet:1.0.0@remote\n",
+        ),
+        DurableEngineBuilder::new(),
+    )
+    .await;
+    assert_eq!(output["greeting"], json!("hello"));
+}
+
+#[tokio::test]
+async fn test_git_catalog_is_cloned_and_lists_versions() {
+    let repository = tempfile::tempdir().unwrap();
+    let functions = repository.path().join("functions");
+    publish(&functions, "add", "1.0.0", "set:\n  sum: 0\n");
+    publish(&functions, "add", "1.4.0", ADD_FUNCTION);
+    publish(&functions, "add", "2.0.0", "set:\n  sum: -1\n");
+    for args in [
+        &["init", "--quiet", "--initial-branch", "main"][..],
+        &["add", "."],
+        &[
+            "-c",
+            "user.name=jackdaw",
+            "-c",
+            "user.email=jackdaw@example.com",
+            "commit",
+            "--quiet",
+            "-m",
+            "Publish add",
+        ],
+    ] {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(repository.path())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    let clones = tempfile::tempdir().unwrap();
+    let output = run(
+        workflow(
+            "git",
+            &format!("git+file://{}#main", repository.path().display()),
+            "  - add:\n      call: add:^1\n      with:\n        a: 4\n        b: 6\n",
+        ),
+        DurableEngineBuilder::new().with_catalog_dir(clones.path()),
+    )
+    .await;
+    assert_eq!(output["sum"], json!(10));
+    assert_eq!(std::fs::read_dir(clones.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_enforce_rejects_unsigned_http_catalog_function() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/functions/add/1.0.0/function.yaml"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(ADD_FUNCTION))
+        .mount(&server)
+        .await;
+
+    let signing = SigningKey::from_slice(&[7u8; 32]).unwrap();
+    let pem = signing
+        .verifying_key()
+        .to_public_key_pem(LineEnding::LF)
+        .unwrap();
+    let key = TrustedKey::from_pem("release", &pem).unwrap();
+    let engine = DurableEngineBuilder::new()
+        .with_signature_verifier(
+            SignatureVerifier::new(SignaturePolicy::Enforce, vec![key]).unwrap(),
+        )
+        .build()
+        .unwrap();
+    let error = engine
+        .execute(
+            workflow(
+                "remote",
+                &server.uri(),
+                "  - add:\n      call: add:1.0.0@remote\n      with:\n        a: 2\n        b: 3\n",
+            ),
+            json!({}),
+        )
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("no signature found"), "{error}");
+}
@@ -1,7 +1,6 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum WorkflowStatus {
     Completed,
-    Cancelled,
     Faulted(String),
 }
 
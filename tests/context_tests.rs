@@ -0,0 +1,96 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for reading and writing the data of an instance through its context
+use jackdaw::context::{Context, Error, MergeStrategy};
+use jackdaw::providers::cache::InMemoryCache;
+use jackdaw::providers::persistence::InMemoryPersistence;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+
+async fn context(data: serde_json::Value) -> Context {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: context
+  version: '1.0.0'
+do:
+  - noop:
+      set:
+        done: true
+",
+    )
+    .unwrap();
+    Context::new(
+        &workflow,
+        Arc::new(InMemoryPersistence::new()),
+        Arc::new(InMemoryCache::new()),
+        None,
+        data,
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_pointers_read_and_write_data() {
+    let ctx = context(json!({ "order": { "items": [1, 2] } })).await;
+
+    ctx.set("/order/items/1", json!(5)).await.unwrap();
+    ctx.set("/order/items/-", json!(7)).await.unwrap();
+    ctx.set("/customer/name", json!("ada")).await.unwrap();
+    assert_eq!(ctx.get("/order/items").await, Some(json!([1, 5, 7])));
+    assert_eq!(ctx.get("/customer").await, Some(json!({ "name": "ada" })));
+
+    assert_eq!(
+        ctx.remove("/customer/name").await.unwrap(),
+        Some(json!("ada"))
+    );
+    assert_eq!(ctx.remove("/customer/name").await.unwrap(), None);
+
+    let error = ctx.set("/order/items/9", json!(0)).await.unwrap_err();
+    assert!(matches!(error, Error::Pointer { .. }), "{error}");
+    let error = ctx.set("order", json!(0)).await.unwrap_err();
+    assert!(matches!(error, Error::Pointer { .. }), "{error}");
+}
+
+#[tokio::test]
+async fn test_engine_fields_are_guarded() {
+    let ctx = context(json!({})).await;
+    for pointer in ["/__workflow/id", "/__runtime", "/__secrets/token"] {
+        let error = ctx.set(pointer, json!(null)).await.unwrap_err();
+        assert!(
+            matches!(error, Error::Reserved { .. }),
+            "{pointer}: {error}"
+        );
+        let error = ctx.remove(pointer).await.unwrap_err();
+        assert!(
+            matches!(error, Error::Reserved { .. }),
+            "{pointer}: {error}"
+        );
+    }
+    assert!(ctx.view_data().await.get("__workflow").is_some());
+}
+
+#[tokio::test]
+async fn test_merge_strategies() {
+    let ctx = context(json!({ "user": { "name": "ada", "role": "admin" } })).await;
+
+    ctx.merge_data(json!({ "user": { "role": "owner" } }), MergeStrategy::Deep)
+        .await;
+    assert_eq!(
+        ctx.get("/user").await,
+        Some(json!({ "name": "ada", "role": "owner" }))
+    );
+
+    ctx.merge_data(json!({ "user": { "id": 1 } }), MergeStrategy::Shallow)
+        .await;
+    assert_eq!(ctx.get("/user").await, Some(json!({ "id": 1 })));
+
+    ctx.merge_data(json!("done"), MergeStrategy::Replace).await;
+    assert_eq!(ctx.data().await, json!("done"));
+}
@@ -0,0 +1,70 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for checking OpenAPI and gRPC calls against the documents they reference
+use jackdaw::contract::{ContractIssue, check_workflow};
+use std::path::Path;
+
+fn violation(location: &str, message: &str) -> ContractIssue {
+    ContractIssue::Violation {
+        location: location.to_string(),
+        message: message.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_matching_calls_pass() {
+    let issues = check_workflow(
+        &fixtures::load("contract", "valid-calls.sw.yaml"),
+        Path::new("tests/fixtures/contract"),
+    )
+    .await;
+    assert_eq!(issues, Vec::new());
+}
+
+#[tokio::test]
+async fn test_broken_calls_are_reported() {
+    let issues = check_workflow(
+        &fixtures::load("contract", "broken-calls.sw.yaml"),
+        Path::new("tests/fixtures/contract"),
+    )
+    .await;
+
+    let (violations, unchecked): (Vec<_>, Vec<_>) = issues
+        .into_iter()
+        .partition(|issue| matches!(issue, ContractIssue::Violation { .. }));
+    assert_eq!(
+        violations,
+        vec![
+            violation(
+                "task.deletePet.call",
+                "Operation 'deletePet' is not defined in the OpenAPI document"
+            ),
+            violation(
+                "task.listPets.call",
+                "Required query parameter 'limit' of operation 'listPets' is not provided"
+            ),
+            violation(
+                "task.createPet.call",
+                "Request body of operation 'createPet' does not match its schema: body is missing required property 'name'"
+            ),
+            violation(
+                "task.subtract.call",
+                "Method 'Subtract' is not defined in service 'calculator.Calculator'"
+            ),
+            violation(
+                "task.add.call",
+                "Field 'c' is not defined in message calculator.AddRequest"
+            ),
+        ]
+    );
+
+    // A document that cannot be loaded leaves its call unchecked
+    assert_eq!(unchecked.len(), 1);
+    assert!(matches!(
+        unchecked.first(),
+        Some(ContractIssue::Unchecked { location, .. }) if location == "task.remote.call"
+    ));
+}
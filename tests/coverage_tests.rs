@@ -0,0 +1,70 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the coverage of workflows by test runs
+use jackdaw::bdd::run_features;
+use jackdaw::coverage::{Coverage, CoverageFormat, Tally};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[tokio::test]
+async fn test_scenarios_record_coverage() {
+    let coverage = Arc::new(Mutex::new(Coverage::new()));
+    assert!(
+        run_features(
+            Path::new("tests/fixtures/coverage"),
+            None,
+            Some(Arc::clone(&coverage))
+        )
+        .await
+    );
+
+    let coverage = coverage.lock().unwrap();
+    let routing = coverage
+        .workflows()
+        .get("default/routing/1.0.0")
+        .expect("workflow should be covered");
+    let (tasks, cases, catches) = routing.tallies();
+    assert_eq!(
+        tasks,
+        Tally {
+            covered: 2,
+            total: 5
+        }
+    );
+    assert_eq!(
+        cases,
+        Tally {
+            covered: 1,
+            total: 2
+        }
+    );
+    assert_eq!(
+        catches,
+        Tally {
+            covered: 0,
+            total: 1
+        }
+    );
+    assert_eq!(
+        routing
+            .switch_cases
+            .get("route")
+            .and_then(|cases| cases.get("big")),
+        Some(&1)
+    );
+
+    let text = coverage.render(CoverageFormat::Text);
+    assert!(text.contains("not hit: switch case route.small"));
+    assert!(text.contains("not hit: catch block handleSmall.catch"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&coverage.render(CoverageFormat::Json)).unwrap();
+    assert_eq!(
+        json.pointer("/workflows/default~1routing~11.0.0/tasks/handleBig"),
+        Some(&serde_json::json!(1))
+    );
+
+    let html = coverage.render(CoverageFormat::Html);
+    assert!(html.contains("<tr class=\"missed\"><td>task</td><td>recover</td><td>0</td></tr>"));
+}
@@ -0,0 +1,75 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for the debug bundles captured when instances fault
+use jackdaw::DurableEngineBuilder;
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_faulted_instance_has_redacted_bundle() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine
+        .execute(
+            fixtures::load("debug-bundle", "charge.sw.yaml"),
+            json!({ "amount": 42 }),
+        )
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    assert!(
+        handle
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .is_err()
+    );
+
+    let bundle = engine.debug_bundle(&instance_id).await.unwrap().unwrap();
+    assert_eq!(bundle.instance_id, instance_id);
+    assert_eq!(bundle.workflow_id, "default/charge/1.0.0");
+    assert_eq!(bundle.task_name, "charge");
+    assert_eq!(bundle.error.type_, "https://example.com/errors/declined");
+    assert_eq!(bundle.error.status, 402);
+    assert_eq!(
+        bundle.task_definition.unwrap()["raise"]["error"]["title"],
+        json!("Card declined")
+    );
+    assert_eq!(bundle.context["user"], json!("ada"));
+    assert_eq!(bundle.context["password"], json!("<redacted>"));
+    assert!(!bundle.recent_events.is_empty());
+    assert!(
+        !serde_json::to_string(&bundle.recent_events)
+            .unwrap()
+            .contains("hunter2")
+    );
+    assert_eq!(bundle.runtime.name, "jackdaw");
+}
+
+#[tokio::test]
+async fn test_completed_instance_has_no_bundle() {
+    let yaml = r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: fine
+  version: '1.0.0'
+do:
+  - greet:
+      set:
+        greeting: hello
+";
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine
+        .execute(serde_yaml::from_str(yaml).unwrap(), json!({}))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    assert!(engine.debug_bundle(&instance_id).await.unwrap().is_none());
+}
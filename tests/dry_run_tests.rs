@@ -0,0 +1,122 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for dry runs, which walk a workflow without running the tasks that
+/// reach outside the engine
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::DryRunStep;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::InMemoryPersistence;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn orders(uri: &str) -> WorkflowDefinition {
+    serde_yaml::from_str(&format!(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: orders
+  version: '1.0.0'
+do:
+  - price:
+      set:
+        total: ${{ .quantity * 10 }}
+  - discount:
+      if: ${{ .total > 1000 }}
+      set:
+        total: ${{ .total * 0.9 }}
+  - route:
+      switch:
+        - large:
+            when: ${{ .total > 100 }}
+            then: charge
+        - default:
+            then: end
+  - charge:
+      call: http
+      with:
+        method: post
+        endpoint: {uri}/charge
+        body:
+          amount: ${{ .total }}
+"
+    ))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_dry_run_reports_tasks_without_calling_out() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "charged": true })))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence.clone())
+        .build()
+        .unwrap();
+
+    let report = engine
+        .dry_run(&orders(&server.uri()), json!({ "quantity": 20 }))
+        .await
+        .unwrap();
+
+    assert_eq!(report.error, None);
+    assert_eq!(report.stubbed(), vec!["charge"]);
+    let steps: Vec<_> = report
+        .steps
+        .iter()
+        .map(|step| match step {
+            DryRunStep::Run {
+                task_name, input, ..
+            } => format!("run {task_name} {input}"),
+            DryRunStep::Skipped { task_name, .. } => format!("skip {task_name}"),
+            DryRunStep::CaseMatched { task_name, case } => format!("case {task_name} {case}"),
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        vec![
+            r#"run price {"quantity":20}"#,
+            "skip discount",
+            r#"run route {"quantity":20,"total":200}"#,
+            "case route large",
+            r#"run charge {"quantity":20,"total":200}"#,
+        ]
+    );
+
+    // Nothing of the dry run is recorded in the engine's persistence
+    assert!(persistence.list_instance_ids().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_dry_run_reports_failures() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: reject
+  version: '1.0.0'
+do:
+  - reject:
+      raise:
+        error:
+          type: https://example.com/errors/rejected
+          status: 400
+          title: Rejected
+",
+    )
+    .unwrap();
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let report = engine.dry_run(&workflow, json!({})).await.unwrap();
+    assert_eq!(report.output, None);
+    assert!(report.error.unwrap().contains("Rejected"));
+}
@@ -0,0 +1,57 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for `emit` tasks publishing CloudEvents to the engine's event sinks
+use async_trait::async_trait;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::events::{CloudEvent, EventSink, Result};
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Sink that keeps every event it receives
+#[derive(Debug, Default)]
+struct CollectingSink(Mutex<Vec<CloudEvent>>);
+
+#[async_trait]
+impl EventSink for CollectingSink {
+    async fn publish(&self, event: &CloudEvent) -> Result<()> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_emit_publishes_cloudevent_to_sinks() {
+    let sink = Arc::new(CollectingSink::default());
+    let engine = DurableEngineBuilder::new()
+        .with_event_sink(sink.clone())
+        .build()
+        .unwrap();
+
+    let workflow_yaml = std::fs::read_to_string("tests/fixtures/emit/emit-order-placed.sw.yaml")
+        .expect("Failed to read emit-order-placed.sw.yaml");
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml).unwrap();
+
+    let handle = engine
+        .execute(workflow, json!({ "orderId": "o-1", "total": 12.5 }))
+        .await
+        .unwrap();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    let events = sink.0.lock().unwrap().clone();
+    assert_eq!(events.len(), 1);
+    let event = events.first().unwrap();
+    assert_eq!(event.specversion, "1.0");
+    assert_eq!(event.event_type, "com.example.order.placed");
+    assert_eq!(event.source, "/default/emit-order-placed/1.0.0");
+    assert_eq!(event.subject.as_deref(), Some("o-1"));
+    assert_eq!(event.datacontenttype.as_deref(), Some("application/json"));
+    assert_eq!(event.data, Some(json!({ "orderId": "o-1", "total": 12.5 })));
+    assert_eq!(event.extensions.get("tenant"), Some(&json!("acme")));
+    assert!(!event.id.is_empty());
+}
@@ -0,0 +1,104 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for categorized executor errors and the retries they allow
+use jackdaw::DurableEngineBuilder;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::mem::InMemoryPersistence;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A workflow calling `endpoint`, retrying any caught error twice before recovering
+fn retrying_call(endpoint: &str) -> WorkflowDefinition {
+    let yaml = format!(
+        r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-error-kind
+  version: '1.0.0'
+do:
+  - callService:
+      try:
+        - fetch:
+            call: http
+            with:
+              method: get
+              endpoint: {endpoint}
+      catch:
+        as: failure
+        retry:
+          delay:
+            milliseconds: 10
+          limit:
+            attempt:
+              count: 2
+        do:
+          - recover:
+              set:
+                category: ${{ .failure.category }}
+                retryable: ${{ .failure.retryable }}
+"#
+    );
+    serde_yaml::from_str(&yaml).unwrap()
+}
+
+/// Run a workflow to its end, returning its output and the number of retries
+async fn run(workflow: WorkflowDefinition) -> (serde_json::Value, usize) {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+        .build()
+        .unwrap();
+
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let output = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    let retries = persistence
+        .get_events(&instance_id)
+        .await
+        .unwrap()
+        .iter()
+        .filter(|event| matches!(event, WorkflowEvent::TaskRetried { .. }))
+        .count();
+    (output, retries)
+}
+
+#[tokio::test]
+async fn test_client_error_is_not_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let (output, retries) = run(retrying_call(&format!("{}/missing", server.uri()))).await;
+    assert_eq!(retries, 0);
+    assert_eq!(output["category"], json!("client"));
+    assert_eq!(output["retryable"], json!(false));
+}
+
+#[tokio::test]
+async fn test_server_error_is_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/unavailable"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+
+    let (output, retries) = run(retrying_call(&format!("{}/unavailable", server.uri()))).await;
+    assert_eq!(retries, 2);
+    assert_eq!(output["category"], json!("server"));
+    assert_eq!(output["retryable"], json!(true));
+}
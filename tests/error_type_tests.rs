@@ -0,0 +1,197 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+#![allow(clippy::panic)]
+
+/// Tests for typed workflow errors and the `catch` filters matching them
+use jackdaw::DurableEngineBuilder;
+use jackdaw::execution_handle::Error as HandleError;
+use jackdaw::executor::WorkflowError;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A workflow running `task` in a `try` block whose `catch` matches `filter`
+fn catching(task: &str, filter: &str) -> WorkflowDefinition {
+    let yaml = format!(
+        r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-error-type
+  version: '1.0.0'
+do:
+  - guarded:
+      try:
+        - failing:
+{task}
+      catch:
+        errors:
+          with:
+{filter}
+        as: failure
+        do:
+          - recover:
+              set:
+                failure: ${{ .failure }}
+"#
+    );
+    serde_yaml::from_str(&yaml).unwrap()
+}
+
+async fn run(workflow: WorkflowDefinition) -> Result<serde_json::Value, HandleError> {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    handle.wait_for_completion(Duration::from_secs(30)).await
+}
+
+const RAISE_REJECTED: &str = "            raise:
+              error:
+                type: https://example.com/errors/rejected
+                title: Rejected
+                status: 422
+                detail: Order has no items";
+
+#[tokio::test]
+async fn test_raised_error_is_caught_by_type() {
+    let output = run(catching(
+        RAISE_REJECTED,
+        "            type: https://example.com/errors/rejected
+            status: 422",
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(
+        output["failure"],
+        json!({
+            "type": "https://example.com/errors/rejected",
+            "status": 422,
+            "title": "Rejected",
+            "detail": "Order has no items",
+            "instance": "/do/0/failing",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_error_of_another_type_is_not_caught() {
+    let error = run(catching(
+        RAISE_REJECTED,
+        "            type: https://example.com/errors/unavailable",
+    ))
+    .await
+    .unwrap_err();
+
+    let HandleError::WorkflowExecution { message } = error else {
+        panic!("expected the workflow to fail, got {error}");
+    };
+    let raised: WorkflowError = serde_json::from_str(&message).unwrap();
+    assert_eq!(raised.type_, "https://example.com/errors/rejected");
+    assert_eq!(raised.status, 422);
+    assert_eq!(raised.instance.as_deref(), Some("/do/0/failing"));
+}
+
+#[tokio::test]
+async fn test_standard_type_matches_spec_spelling() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/missing"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let call = format!(
+        "            call: http
+            with:
+              method: get
+              endpoint: {}/missing",
+        server.uri()
+    );
+    let output = run(catching(
+        &call,
+        "            type: https://serverlessworkflow.io/spec/1.0.0/errors/communication
+            status: 404",
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(
+        output["failure"]["type"],
+        json!(WorkflowError::COMMUNICATION)
+    );
+    assert_eq!(output["failure"]["status"], json!(404));
+    assert_eq!(output["failure"]["category"], json!("client"));
+}
+
+#[tokio::test]
+async fn test_expression_error_has_expression_type() {
+    let output = run(catching(
+        "            set:
+              broken: ${ .value | not_a_function }",
+        "            type: https://serverlessworkflow.io/spec/1.0.0/errors/expression",
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(output["failure"]["type"], json!(WorkflowError::EXPRESSION));
+    assert_eq!(output["failure"]["status"], json!(400));
+    assert_eq!(
+        output["failure"]["instance"],
+        json!("/do/0/guarded/try/0/failing")
+    );
+}
+
+/// A workflow raising a 422 error, caught when `conditions` hold
+fn conditional(conditions: &str) -> WorkflowDefinition {
+    let yaml = format!(
+        r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-catch-conditions
+  version: '1.0.0'
+do:
+  - guarded:
+      try:
+        - failing:
+{RAISE_REJECTED}
+      catch:
+        as: failure
+{conditions}
+        do:
+          - recover:
+              set:
+                caught: ${{ $failure.title }}
+"#
+    );
+    serde_yaml::from_str(&yaml).unwrap()
+}
+
+#[tokio::test]
+async fn test_catch_when_sees_bound_error() {
+    let output = run(conditional("        when: ${ $failure.status == 422 }"))
+        .await
+        .unwrap();
+    assert_eq!(output["caught"], json!("Rejected"));
+
+    let error = run(conditional("        when: ${ $failure.status >= 500 }")).await;
+    assert!(error.is_err());
+}
+
+#[tokio::test]
+async fn test_catch_except_when_lets_error_through() {
+    let error = run(conditional(
+        "        exceptWhen: ${ $failure.type == \"https://example.com/errors/rejected\" }",
+    ))
+    .await;
+    assert!(error.is_err());
+
+    let output = run(conditional(
+        "        exceptWhen: ${ $failure.status == 404 }",
+    ))
+    .await
+    .unwrap();
+    assert_eq!(output["caught"], json!("Rejected"));
+}
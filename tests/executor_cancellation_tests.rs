@@ -0,0 +1,223 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests that executors stop the work they started when their cancellation
+/// token fires: requests are aborted and child processes killed
+use jackdaw::context::Context;
+use jackdaw::executor::{Error, Executor};
+use jackdaw::providers::cache::mem::InMemoryCache;
+use jackdaw::providers::executors::{
+    AsyncApiExecutor, GrpcExecutor, OpenApiExecutor, PythonExecutor, RestExecutor,
+    TypeScriptExecutor,
+};
+use jackdaw::providers::persistence::mem::InMemoryPersistence;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+const WORKFLOW: &str = r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: cancellation
+  version: '1.0.0'
+do:
+  - wait:
+      wait:
+        seconds: 1
+";
+
+/// How long after the task starts its token is cancelled
+const CANCEL_AFTER: Duration = Duration::from_millis(300);
+
+/// Upper bound on how long a cancelled executor may take to return
+const STOP_WITHIN: Duration = Duration::from_secs(5);
+
+async fn context() -> Context {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(WORKFLOW).unwrap();
+    Context::new(
+        &workflow,
+        Arc::new(InMemoryPersistence::new()),
+        Arc::new(InMemoryCache::new()),
+        None,
+        json!({}),
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+/// A TCP server that accepts connections and never answers
+async fn silent_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            connections.push(stream);
+        }
+    });
+    port
+}
+
+/// Run an executor, cancelling its token shortly after it starts
+async fn exec_cancelled(
+    executor: &dyn Executor,
+    params: serde_json::Value,
+) -> (jackdaw::executor::Result<serde_json::Value>, Duration) {
+    let ctx = context().await;
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(CANCEL_AFTER).await;
+        trigger.cancel();
+    });
+
+    let started = Instant::now();
+    let result = tokio::time::timeout(
+        Duration::from_secs(30),
+        executor.exec("task", &params, &ctx, None, &cancel),
+    )
+    .await
+    .expect("executor did not stop when cancelled");
+    (result, started.elapsed())
+}
+
+fn assert_cancelled(result: &jackdaw::executor::Result<serde_json::Value>, elapsed: Duration) {
+    assert!(
+        matches!(result, Err(Error::Cancelled { .. })),
+        "expected a cancellation error, got {result:?}"
+    );
+    assert!(elapsed < STOP_WITHIN, "cancellation took {elapsed:?}");
+}
+
+#[tokio::test]
+async fn test_http_request_aborted() {
+    let port = silent_server().await;
+    let executor = RestExecutor(reqwest::Client::new());
+
+    let (result, elapsed) = exec_cancelled(
+        &executor,
+        json!({ "method": "get", "endpoint": format!("http://127.0.0.1:{port}/slow") }),
+    )
+    .await;
+
+    assert_cancelled(&result, elapsed);
+}
+
+#[tokio::test]
+async fn test_openapi_call_aborted() {
+    let port = silent_server().await;
+    let executor = OpenApiExecutor(reqwest::Client::new());
+
+    let (result, elapsed) = exec_cancelled(
+        &executor,
+        json!({
+            "document": { "endpoint": format!("http://127.0.0.1:{port}/openapi.json") },
+            "operationId": "getPet"
+        }),
+    )
+    .await;
+
+    assert_cancelled(&result, elapsed);
+}
+
+#[tokio::test]
+async fn test_grpc_call_aborted() {
+    let port = silent_server().await;
+    let executor = GrpcExecutor::new();
+
+    let (result, elapsed) = exec_cancelled(
+        &executor,
+        json!({
+            "proto": { "endpoint": "file://tests/fixtures/listeners/specs/calculator.proto" },
+            "service": { "name": "calculator.Calculator", "host": "127.0.0.1", "port": port },
+            "method": "Add",
+            "arguments": { "a": 1, "b": 2 }
+        }),
+    )
+    .await;
+
+    assert_cancelled(&result, elapsed);
+}
+
+#[tokio::test]
+async fn test_asyncapi_receive_aborted() {
+    let port = silent_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let document = dir.path().join("asyncapi.yaml");
+    std::fs::write(
+        &document,
+        format!(
+            r"
+asyncapi: 3.0.0
+info: {{ title: Orders, version: 1.0.0 }}
+servers:
+  local:
+    host: '127.0.0.1:{port}'
+    protocol: mqtt
+channels:
+  orderShipped:
+    address: orders/shipped
+operations:
+  onOrderShipped:
+    action: receive
+    channel: {{ $ref: '#/channels/orderShipped' }}
+"
+        ),
+    )
+    .unwrap();
+    let executor = AsyncApiExecutor(reqwest::Client::new());
+
+    let (result, elapsed) = exec_cancelled(
+        &executor,
+        json!({
+            "document": { "endpoint": document.to_string_lossy() },
+            "operation": "onOrderShipped"
+        }),
+    )
+    .await;
+
+    assert_cancelled(&result, elapsed);
+}
+
+#[tokio::test]
+async fn test_python_script_killed() {
+    let executor = PythonExecutor::new();
+
+    let (result, elapsed) = exec_cancelled(
+        &executor,
+        json!({ "script": "import time\ntime.sleep(30)\nprint('finished')" }),
+    )
+    .await;
+
+    if let Err(Error::Execution { message }) = &result
+        && message.contains("Failed to spawn")
+    {
+        eprintln!("Skipping test: Python not available");
+        return;
+    }
+    assert_cancelled(&result, elapsed);
+}
+
+#[tokio::test]
+async fn test_node_script_killed() {
+    let executor = TypeScriptExecutor::new();
+
+    let (result, elapsed) = exec_cancelled(
+        &executor,
+        json!({ "script": "setTimeout(() => console.log('finished'), 30000);" }),
+    )
+    .await;
+
+    if let Err(Error::Execution { message }) = &result
+        && message.contains("Failed to spawn")
+    {
+        eprintln!("Skipping test: Node.js not available");
+        return;
+    }
+    assert_cancelled(&result, elapsed);
+}
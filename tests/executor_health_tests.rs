@@ -0,0 +1,61 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for executor health checks
+use jackdaw::DurableEngineBuilder;
+use jackdaw::executor::Executor;
+use jackdaw::providers::executors::{PythonExecutor, TypeScriptExecutor};
+
+#[tokio::test]
+async fn test_every_registered_executor_is_checked() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let health = engine.check_executors().await;
+    for call_type in [
+        "http",
+        "rest",
+        "openapi",
+        "grpc",
+        "asyncapi",
+        "python",
+        "javascript",
+    ] {
+        assert!(
+            health.contains_key(call_type),
+            "{call_type} was not checked"
+        );
+    }
+    // Executors without external dependencies are always ready
+    assert!(health.get("http").unwrap().is_ready());
+    assert!(health.get("grpc").unwrap().is_ready());
+}
+
+#[tokio::test]
+async fn test_missing_interpreter_is_reported() {
+    let python = PythonExecutor::with_python_path("jackdaw-missing-python".to_string());
+    let health = python.health_check().await;
+    assert!(!health.is_ready());
+    assert_eq!(health.version, None);
+    assert!(
+        health
+            .missing
+            .first()
+            .unwrap()
+            .contains("jackdaw-missing-python")
+    );
+
+    // The result is kept, including by clones sharing the executor's state
+    assert_eq!(python.clone().health_check().await, health);
+
+    let node = TypeScriptExecutor::with_node_path("jackdaw-missing-node".to_string());
+    assert!(!node.health_check().await.is_ready());
+}
+
+#[tokio::test]
+async fn test_interpreter_without_version_output_is_ready() {
+    // `true` accepts `--version` and exits successfully without printing one
+    let executor = PythonExecutor::with_python_path("true".to_string());
+    let health = executor.health_check().await;
+    assert!(health.is_ready());
+    assert_eq!(health.version, None);
+}
@@ -0,0 +1,252 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the `evaluate` settings of workflows: the language expressions are
+/// written in, and whether they may read missing fields
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::DurableEngine;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_js_expressions_drive_tasks() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: js-expressions
+  version: '1.0.0'
+evaluate:
+  language: js
+do:
+  - total:
+      for:
+        each: order
+        in: ${ orders.filter((order) => order.paid) }
+      do:
+        - add:
+            set:
+              sum: ${ ($data.sum ?? 0) + $order.amount }
+  - classify:
+      switch:
+        - large:
+            when: ${ sum > 100 }
+            then: markLarge
+        - default:
+            then: end
+  - markLarge:
+      set:
+        sum: ${ sum }
+        label: ${ `large order from ${$input.customer.toUpperCase()}` }
+",
+    )
+    .unwrap();
+
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let input = json!({
+        "customer": "ada",
+        "orders": [
+            { "amount": 80, "paid": true },
+            { "amount": 500, "paid": false },
+            { "amount": 40, "paid": true }
+        ]
+    });
+    let output = engine
+        .execute(workflow, input)
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert_eq!(output["sum"], json!(120));
+    assert_eq!(output["label"], json!("large order from ADA"));
+}
+
+#[tokio::test]
+async fn test_js_expressions_drive_export_and_output() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: js-transforms
+  version: '1.0.0'
+evaluate:
+  language: js
+do:
+  - greet:
+      set:
+        greeting: hello
+      export:
+        as: ${ ({ shout: greeting.toUpperCase() }) }
+  - reply:
+      set:
+        reply: ${ shout + "!" }
+output:
+  as: ${ ({ message: reply }) }
+"#,
+    )
+    .unwrap();
+
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let output = engine
+        .execute(workflow, json!({}))
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert_eq!(output, json!({ "message": "HELLO!" }));
+}
+
+#[tokio::test]
+async fn test_unsupported_language_is_rejected() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: cel-expressions
+  version: '1.0.0'
+evaluate:
+  language: cel
+do:
+  - greet:
+      set:
+        greeting: hello
+",
+    )
+    .unwrap();
+
+    let error = DurableEngine::validate_workflow_graph(&workflow)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("Unsupported expression language 'cel'"),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn test_strict_mode_fails_tasks_reading_missing_fields() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: strict-expressions
+  version: '1.0.0'
+evaluate:
+  mode: strict
+do:
+  - greet:
+      set:
+        greeting: ${ .user.name }
+",
+    )
+    .unwrap();
+
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let output = engine
+        .execute(workflow.clone(), json!({ "user": { "name": "ada" } }))
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert_eq!(output["greeting"], json!("ada"));
+
+    let error = engine
+        .execute(workflow, json!({ "account": {} }))
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("missing field user"), "{error}");
+}
+
+#[tokio::test]
+async fn test_strict_mode_applies_to_export_and_output() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let cases = [
+        (
+            "
+  - greet:
+      set:
+        greeting: hello
+      export:
+        as: ${ .user }
+",
+            "missing field user",
+        ),
+        (
+            "
+  - greet:
+      set:
+        greeting: hello
+output:
+  as: ${ .summary.total }
+",
+            "missing field summary",
+        ),
+    ];
+    for (tasks, expected) in cases {
+        let workflow: WorkflowDefinition = serde_yaml::from_str(&format!(
+            "
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: strict-transforms
+  version: '1.0.0'
+evaluate:
+  mode: strict
+do:{tasks}"
+        ))
+        .unwrap();
+
+        let error = engine
+            .execute(workflow, json!({}))
+            .await
+            .unwrap()
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(expected), "{error}");
+    }
+}
+
+#[tokio::test]
+async fn test_strict_mode_applies_to_null_input() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: strict-null-input
+  version: '1.0.0'
+evaluate:
+  mode: strict
+do:
+  - greet:
+      set:
+        greeting: ${ .user.name }
+",
+    )
+    .unwrap();
+
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let error = engine
+        .execute(workflow, serde_json::Value::Null)
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("cannot read field user of null"), "{error}");
+}
@@ -0,0 +1,17 @@
+Feature: Greeter
+
+  Scenario: expects the wrong greeting
+    Given the workflow file "../../testing/greeter.sw.yaml"
+    And the workflow input is:
+      """
+      name: Rex
+      """
+    When the workflow is executed
+    Then the workflow output should contain:
+      """
+      message: Goodbye Rex
+      """
+
+  Scenario: uses an unknown step
+    Given the workflow file "../../testing/greeter.sw.yaml"
+    When the workflow is sung
@@ -0,0 +1,35 @@
+Feature: Greeter
+
+  Scenario: greets by name
+    Given the workflow file "../../testing/greeter.sw.yaml"
+    And the workflow input is:
+      """
+      name: Rex
+      """
+    When the workflow is executed
+    Then the workflow should complete with output:
+      """
+      message: Hello Rex
+      """
+    And task "greet" should complete
+    And a TaskCompleted event should be recorded for task "greet"
+
+  Scenario: inline definition
+    Given a workflow with definition:
+      """yaml
+      document:
+        dsl: '1.0.2'
+        namespace: default
+        name: inline
+        version: '1.0.0'
+      do:
+        - reject:
+            raise:
+              error:
+                type: https://example.com/errors/rejected
+                status: 400
+                title: Rejected
+      """
+    When the workflow is executed
+    Then the workflow should fault with error containing "Rejected"
+    And a WorkflowFailed event should be recorded
@@ -0,0 +1,13 @@
+Feature: Routing
+
+  Scenario: routes big numbers
+    Given the workflow file "routing.sw.yaml"
+    And the workflow input is:
+      """
+      n: 20
+      """
+    When the workflow is executed
+    Then the workflow should complete with output:
+      """
+      big: true
+      """
@@ -0,0 +1,8 @@
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+
+/// Load the workflow at `tests/fixtures/<dir>/<name>`
+pub fn load(dir: &str, name: &str) -> WorkflowDefinition {
+    let yaml = std::fs::read_to_string(format!("tests/fixtures/{dir}/{name}"))
+        .expect("Failed to read fixture");
+    serde_yaml::from_str(&yaml).unwrap()
+}
@@ -6,6 +6,7 @@ use jackdaw::cache::CacheProvider;
 use jackdaw::persistence::PersistenceProvider;
 use jackdaw::providers::cache::RedbCache;
 use jackdaw::providers::persistence::RedbPersistence;
+use jackdaw::providers::persistence::mem::InMemoryPersistence;
 use serde_json::json;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use std::sync::Arc;
@@ -228,3 +229,169 @@ do:
     assert_eq!(output_exit.get("value"), Some(&json!("exit")));
     assert_eq!(output_end.get("value"), Some(&json!("end")));
 }
+
+async fn run(workflow_yaml: &str) -> serde_json::Value {
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::new(InMemoryPersistence::new()) as Arc<dyn PersistenceProvider>)
+        .build()
+        .unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(workflow_yaml).unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    handle
+        .wait_for_completion(Duration::from_secs(60))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_then_on_any_task_jumps_to_named_task() {
+    let output = run(r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-then-goto
+  version: '1.0.0'
+do:
+  - first:
+      set:
+        visited: ['first']
+      then: third
+  - second:
+      set:
+        visited: '${ .visited + ["second"] }'
+      then: end
+  - third:
+      set:
+        visited: '${ .visited + ["third"] }'
+      then: second
+"#)
+    .await;
+
+    assert_eq!(output["visited"], json!(["first", "third", "second"]));
+}
+
+#[tokio::test]
+async fn test_continue_runs_next_task_after_explicit_transitions() {
+    // A task without `then` continues in declaration order even when other
+    // tasks of the workflow transition explicitly
+    let output = run(r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-then-continue
+  version: '1.0.0'
+do:
+  - first:
+      set:
+        visited: ['first']
+      then: continue
+  - second:
+      set:
+        visited: '${ .visited + ["second"] }'
+      then: fourth
+  - third:
+      set:
+        visited: '${ .visited + ["third"] }'
+  - fourth:
+      set:
+        visited: '${ .visited + ["fourth"] }'
+  - fifth:
+      set:
+        visited: '${ .visited + ["fifth"] }'
+"#)
+    .await;
+
+    assert_eq!(
+        output["visited"],
+        json!(["first", "second", "fourth", "fifth"])
+    );
+}
+
+#[tokio::test]
+async fn test_exit_in_nested_do_leaves_only_the_block() {
+    let output = run(r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-nested-exit
+  version: '1.0.0'
+do:
+  - block:
+      do:
+        - inner1:
+            set:
+              visited: ['inner1']
+            then: exit
+        - inner2:
+            set:
+              visited: '${ .visited + ["inner2"] }'
+  - after:
+      set:
+        visited: '${ .visited + ["after"] }'
+"#)
+    .await;
+
+    assert_eq!(output["visited"], json!(["inner1", "after"]));
+}
+
+#[tokio::test]
+async fn test_end_in_nested_do_completes_the_workflow() {
+    let output = run(r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-nested-end
+  version: '1.0.0'
+do:
+  - outer:
+      do:
+        - block:
+            do:
+              - inner1:
+                  set:
+                    visited: ['inner1']
+                  then: end
+              - inner2:
+                  set:
+                    visited: '${ .visited + ["inner2"] }'
+        - sibling:
+            set:
+              visited: '${ .visited + ["sibling"] }'
+  - after:
+      set:
+        visited: '${ .visited + ["after"] }'
+"#)
+    .await;
+
+    assert_eq!(output["visited"], json!(["inner1"]));
+}
+
+#[tokio::test]
+async fn test_then_jumps_between_tasks_of_nested_do() {
+    let output = run(r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-nested-goto
+  version: '1.0.0'
+do:
+  - block:
+      do:
+        - inner1:
+            set:
+              visited: ['inner1']
+            then: inner3
+        - inner2:
+            set:
+              visited: '${ .visited + ["inner2"] }'
+        - inner3:
+            set:
+              visited: '${ .visited + ["inner3"] }'
+  - after:
+      set:
+        visited: '${ .visited + ["after"] }'
+"#)
+    .await;
+
+    assert_eq!(output["visited"], json!(["inner1", "inner3", "after"]));
+}
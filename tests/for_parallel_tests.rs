@@ -0,0 +1,160 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for running the iterations of `for` loops concurrently
+///
+/// Each iteration calls a painter server that counts the requests it is
+/// answering at once, so the tests see how many iterations ran together.
+use axum::Json;
+use axum::extract::State;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::DurableEngine;
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// Requests the painter server is answering, and the most it answered at once
+#[derive(Default)]
+struct Painter {
+    delay: Duration,
+    in_flight: AtomicUsize,
+    most_in_flight: AtomicUsize,
+}
+
+impl Painter {
+    fn most_in_flight(&self) -> usize {
+        self.most_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+async fn paint(State(painter): State<Arc<Painter>>) -> Json<serde_json::Value> {
+    let in_flight = painter.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+    painter
+        .most_in_flight
+        .fetch_max(in_flight, Ordering::SeqCst);
+    tokio::time::sleep(painter.delay).await;
+    painter.in_flight.fetch_sub(1, Ordering::SeqCst);
+    Json(json!({ "mixed": true }))
+}
+
+/// Start a painter server answering each request after `delay`, returning it
+/// and its URL
+async fn painter(delay: Duration) -> (Arc<Painter>, String) {
+    let painter = Arc::new(Painter {
+        delay,
+        ..Painter::default()
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/paint", listener.local_addr().unwrap());
+    let app = axum::Router::new()
+        .route("/paint", axum::routing::get(paint))
+        .with_state(Arc::clone(&painter));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (painter, url)
+}
+
+/// Run a workflow to its end against a painter server, returning its outcome
+/// and the most iterations that called the painter at once
+async fn run(engine: &DurableEngine, workflow: &str) -> (Result<serde_json::Value, String>, usize) {
+    let (painter, url) = painter(Duration::from_millis(300)).await;
+    let handle = engine
+        .execute(fixtures::load("for", workflow), json!({ "painter": url }))
+        .await
+        .unwrap();
+    let outcome = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .map_err(|e| e.to_string());
+    (outcome, painter.most_in_flight())
+}
+
+#[tokio::test]
+async fn test_parallel_loop_returns_the_last_result() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let (outcome, most_in_flight) = run(&engine, "for-parallel.sw.yaml").await;
+    assert_eq!(outcome.unwrap(), json!({"color": "blue"}));
+    assert_eq!(most_in_flight, 3);
+}
+
+#[tokio::test]
+async fn test_loops_run_in_order_by_default() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let (outcome, most_in_flight) = run(&engine, "for-default.sw.yaml").await;
+    assert_eq!(outcome.unwrap(), json!({"color": "blue"}));
+    assert_eq!(most_in_flight, 1);
+}
+
+#[tokio::test]
+async fn test_engine_parallelism_only_applies_to_parallel_loops() {
+    let engine = DurableEngineBuilder::new()
+        .with_for_parallelism(3)
+        .build()
+        .unwrap();
+
+    // A loop that does not ask to run in parallel keeps its output
+    let (outcome, most_in_flight) = run(&engine, "for-default.sw.yaml").await;
+    assert_eq!(outcome.unwrap(), json!({"color": "blue"}));
+    assert_eq!(most_in_flight, 1);
+
+    // A loop that asks to run in order still does
+    let (outcome, most_in_flight) = run(&engine, "for-sequential.sw.yaml").await;
+    assert_eq!(outcome.unwrap(), json!({"color": "blue"}));
+    assert_eq!(most_in_flight, 1);
+}
+
+#[tokio::test]
+async fn test_engine_parallelism_sets_the_workers_of_parallel_loops() {
+    let engine = DurableEngineBuilder::new()
+        .with_for_parallelism(1)
+        .build()
+        .unwrap();
+
+    let (outcome, most_in_flight) = run(&engine, "for-parallel.sw.yaml").await;
+    assert_eq!(outcome.unwrap(), json!({"color": "blue"}));
+    assert_eq!(most_in_flight, 1);
+}
+
+#[tokio::test]
+async fn test_loop_limit_bounds_running_iterations() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let (outcome, most_in_flight) = run(&engine, "for-limited.sw.yaml").await;
+    assert_eq!(outcome.unwrap(), json!({"color": "blue"}));
+    assert_eq!(most_in_flight, 1);
+}
+
+#[tokio::test]
+async fn test_failed_iteration_cancels_the_others() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    // The painter never answers the other iterations, so the loop only ends
+    // with the failure if they are cancelled
+    let (_painter, url) = painter(Duration::from_secs(3600)).await;
+
+    let error = engine
+        .execute(
+            fixtures::load("for", "for-failing.sw.yaml"),
+            json!({ "painter": url }),
+        )
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("no such color"), "{error}");
+}
+
+#[tokio::test]
+async fn test_invalid_loop_limit_fails() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let (outcome, _) = run(&engine, "for-invalid-limit.sw.yaml").await;
+    let error = outcome.unwrap_err();
+    assert!(error.contains("maxParallelism"), "{error}");
+}
@@ -0,0 +1,95 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for limiting how many fork branches run at once, and in which order
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::DurableEngine;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+/// Run a workflow to its end, returning its outcome and how long it took
+async fn run(
+    engine: &DurableEngine,
+    workflow: &str,
+) -> (Result<serde_json::Value, String>, Duration) {
+    let started = Instant::now();
+    let handle = engine
+        .execute(fixtures::load("fork", workflow), json!({}))
+        .await
+        .unwrap();
+    let outcome = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .map_err(|e| e.to_string());
+    (outcome, started.elapsed())
+}
+
+#[tokio::test]
+async fn test_fork_limit_runs_branches_one_at_a_time() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let (outcome, elapsed) = run(&engine, "fork-limited.sw.yaml").await;
+    outcome.unwrap();
+    assert!(elapsed >= Duration::from_millis(900), "{elapsed:?}");
+
+    let (outcome, elapsed) = run(&engine, "fork-unlimited.sw.yaml").await;
+    outcome.unwrap();
+    assert!(elapsed < Duration::from_millis(900), "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn test_engine_limit_applies_to_every_fork() {
+    let engine = DurableEngineBuilder::new()
+        .with_fork_max_parallelism(1)
+        .build()
+        .unwrap();
+
+    let (outcome, elapsed) = run(&engine, "fork-unlimited.sw.yaml").await;
+    outcome.unwrap();
+    assert!(elapsed >= Duration::from_millis(900), "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn test_nested_forks_run_under_their_branch_permit() {
+    let engine = DurableEngineBuilder::new()
+        .with_fork_max_parallelism(1)
+        .build()
+        .unwrap();
+
+    // With a single permit, nested forks waiting for another would never finish
+    let (outcome, elapsed) = run(&engine, "fork-nested.sw.yaml").await;
+    outcome.unwrap();
+    assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn test_invalid_fork_limit_fails() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let (outcome, _) = run(&engine, "fork-invalid-limit.sw.yaml").await;
+    let error = outcome.unwrap_err();
+    assert!(error.contains("maxParallelism"), "{error}");
+}
+
+#[tokio::test]
+async fn test_branches_start_by_priority() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    // One branch at a time, so the branches complete in the order they start
+    let (outcome, _) = run(&engine, "fork-priorities.sw.yaml").await;
+    let output = outcome.unwrap();
+    assert_eq!(output["order"], json!(["page", "sms", "email"]));
+    assert_eq!(output["paged"], json!("page"));
+    assert!(output.get("__branches").is_none(), "{output}");
+}
+
+#[tokio::test]
+async fn test_priority_of_unknown_branch_fails() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let (outcome, _) = run(&engine, "fork-unknown-priority.sw.yaml").await;
+    let error = outcome.unwrap_err();
+    assert!(error.contains("fax"), "{error}");
+}
@@ -0,0 +1,73 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for `call: grpc` against a gRPC listener running in the test process
+use jackdaw::DurableEngineBuilder;
+use jackdaw::listeners::Listener;
+use jackdaw::listeners::grpc::{GrpcListener, compile_proto};
+use prost_reflect::{DescriptorPool, DynamicMessage, Value};
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PROTO: &str = "tests/fixtures/listeners/specs/calculator.proto";
+const BIND_ADDR: &str = "127.0.0.1:50871";
+
+/// Start a Calculator service whose Add method sums its arguments
+async fn start_calculator() -> GrpcListener {
+    let pool = DescriptorPool::from_file_descriptor_set(compile_proto(PROTO).unwrap()).unwrap();
+    let response = pool.get_message_by_name("calculator.AddResponse").unwrap();
+
+    let add: Arc<
+        dyn Fn(DynamicMessage) -> jackdaw::listeners::Result<DynamicMessage> + Send + Sync,
+    > = Arc::new(move |request: DynamicMessage| {
+        let field = |name: &str| {
+            request
+                .get_field_by_name(name)
+                .and_then(|v| v.as_i32())
+                .unwrap_or_default()
+        };
+        let mut message = DynamicMessage::new(response.clone());
+        message.set_field_by_name("result", Value::I32(field("a") + field("b")));
+        Ok(message)
+    });
+
+    let listener = GrpcListener::new_multi_method(
+        BIND_ADDR.to_string(),
+        PROTO,
+        "calculator.Calculator",
+        HashMap::from([("Add".to_string(), add)]),
+    )
+    .unwrap();
+    listener.start().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    listener
+}
+
+#[tokio::test]
+async fn test_call_grpc_method() {
+    let listener = start_calculator().await;
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let workflow_yaml = std::fs::read_to_string("tests/fixtures/grpc/call-grpc-add.sw.yaml")
+        .expect("Failed to read call-grpc-add.sw.yaml");
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml).unwrap();
+
+    let handle = engine
+        .execute(workflow, json!({ "a": 2, "b": 40 }))
+        .await
+        .unwrap();
+    let result = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    listener.stop().await.unwrap();
+
+    assert_eq!(
+        result.get("result").and_then(serde_json::Value::as_i64),
+        Some(42),
+        "Unexpected output: {result}"
+    );
+}
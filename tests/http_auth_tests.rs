@@ -0,0 +1,140 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the authentication policies of HTTP calls
+use jackdaw::DurableEngineBuilder;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+async fn run(yaml: &str) -> serde_json::Value {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(yaml).unwrap();
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_oauth2_token_is_requested_once_and_reused() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .and(body_string_contains("grant_type=client_credentials"))
+        .and(body_string_contains("client_id=orders"))
+        .and(body_string_contains("scope=orders%3Awrite"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "tok-1",
+            "token_type": "Bearer",
+            "expires_in": 3600
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/orders"))
+        .and(header("authorization", "Bearer tok-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "accepted": true })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let output = run(&format!(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-oauth2
+  version: '1.0.0'
+use:
+  authentications:
+    ordersApi:
+      oauth2:
+        authority: {uri}
+        grant: client_credentials
+        client:
+          id: orders
+          secret: s3cr3t
+        scopes: [orders:write]
+do:
+  - first:
+      call: http
+      with:
+        method: post
+        endpoint:
+          uri: {uri}/orders
+          authentication:
+            use: ordersApi
+        body: {{ id: 1 }}
+  - second:
+      call: http
+      with:
+        method: post
+        endpoint:
+          uri: {uri}/orders
+          authentication:
+            use: ordersApi
+        body: {{ id: 2 }}
+",
+        uri = server.uri()
+    ))
+    .await;
+    assert_eq!(output["accepted"], true);
+}
+
+#[tokio::test]
+async fn test_bearer_and_api_key_policies() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/bearer"))
+        .and(header("authorization", "Bearer abc"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "bearer": true })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/key"))
+        .and(query_param("api_key", "k-123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "key": true })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output = run(&format!(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-bearer-api-key
+  version: '1.0.0'
+do:
+  - bearer:
+      call: http
+      with:
+        method: get
+        endpoint:
+          uri: {uri}/bearer
+          authentication:
+            bearer:
+              token: abc
+  - key:
+      call: http
+      with:
+        method: get
+        endpoint:
+          uri: {uri}/key
+          authentication:
+            apiKey:
+              name: api_key
+              key: k-123
+              in: query
+",
+        uri = server.uri()
+    ))
+    .await;
+    assert_eq!(output["key"], true);
+}
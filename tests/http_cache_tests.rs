@@ -0,0 +1,108 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for HTTP caching of GET responses by the call executors
+use jackdaw::DurableEngineBuilder;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A workflow fetching `endpoint` twice, from tasks the task cache keeps apart
+fn fetch_twice(endpoint: &str) -> WorkflowDefinition {
+    let yaml = format!(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-http-cache
+  version: '1.0.0'
+do:
+  - first:
+      call: http
+      with:
+        method: get
+        endpoint: {endpoint}
+  - second:
+      call: http
+      with:
+        method: get
+        endpoint: {endpoint}
+"
+    );
+    serde_yaml::from_str(&yaml).unwrap()
+}
+
+async fn run(workflow: WorkflowDefinition) -> serde_json::Value {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_stale_response_is_revalidated_with_etag() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/catalog"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304).insert_header("etag", "\"v1\""))
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/catalog"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .insert_header("cache-control", "no-cache")
+                .set_body_json(json!({"items": ["a", "b"]})),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output = run(fetch_twice(&format!("{}/catalog", server.uri()))).await;
+    assert_eq!(output, json!({"items": ["a", "b"]}));
+}
+
+#[tokio::test]
+async fn test_fresh_response_is_reused_without_request() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/catalog"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("cache-control", "max-age=60")
+                .set_body_json(json!({"items": ["a"]})),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output = run(fetch_twice(&format!("{}/catalog", server.uri()))).await;
+    assert_eq!(output, json!({"items": ["a"]}));
+}
+
+#[tokio::test]
+async fn test_no_store_response_is_fetched_again() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/catalog"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .insert_header("cache-control", "no-store")
+                .set_body_json(json!({"items": []})),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let output = run(fetch_twice(&format!("{}/catalog", server.uri()))).await;
+    assert_eq!(output, json!({"items": []}));
+}
@@ -0,0 +1,224 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for querying instance status reconstructed from persisted events
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::{DurableEngine, Error, InstanceFilter, InstanceState};
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::InMemoryPersistence;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Run a workflow to its end, returning its instance ID
+async fn run(engine: &DurableEngine, workflow: &str) -> String {
+    let handle = engine
+        .execute(fixtures::load("instance-status", workflow), json!({}))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let _ = handle.wait_for_completion(Duration::from_secs(10)).await;
+    instance_id
+}
+
+#[tokio::test]
+async fn test_status_of_finished_instances() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let completed = run(&engine, "greet.sw.yaml").await;
+    let status = engine.get_instance_status(&completed).await.unwrap();
+    assert_eq!(status.state, InstanceState::Completed);
+    assert_eq!(status.workflow_id.as_deref(), Some("default/greet/1.0.0"));
+    assert_eq!(status.output, Some(json!({ "message": "Hello" })));
+    assert_eq!(status.current_task, None);
+    assert!(status.started_at.is_some());
+    assert!(status.finished_at >= status.started_at);
+
+    let failed = run(&engine, "fail.sw.yaml").await;
+    let status = engine.get_instance_status(&failed).await.unwrap();
+    assert_eq!(status.state, InstanceState::Faulted);
+    assert_eq!(status.current_task.as_deref(), Some("failTask"));
+    assert!(status.error.unwrap().contains("Broken"));
+    assert!(status.finished_at.is_some());
+}
+
+#[tokio::test]
+async fn test_status_of_running_and_cancelled_instance() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let handle = engine
+        .execute(fixtures::load("instance-status", "slow.sw.yaml"), json!({}))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let status = engine.get_instance_status(&instance_id).await.unwrap();
+    assert_eq!(status.state, InstanceState::Running);
+    assert_eq!(status.current_task.as_deref(), Some("pause"));
+    assert_eq!(status.finished_at, None);
+
+    engine
+        .cancel_instance(&instance_id, Some("no longer needed".to_string()))
+        .await
+        .unwrap();
+    let _ = handle.wait_for_completion(Duration::from_secs(10)).await;
+
+    let status = engine.get_instance_status(&instance_id).await.unwrap();
+    assert_eq!(status.state, InstanceState::Cancelled);
+    assert!(status.finished_at.is_some());
+}
+
+#[tokio::test]
+async fn test_list_instances_with_filter() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let first = run(&engine, "greet.sw.yaml").await;
+    let failed = run(&engine, "fail.sw.yaml").await;
+    let second = run(&engine, "greet.sw.yaml").await;
+
+    let all = engine
+        .list_instances(&InstanceFilter::default())
+        .await
+        .unwrap();
+    let ids: Vec<&str> = all.iter().map(|s| s.instance_id.as_str()).collect();
+    assert_eq!(ids, vec![first.as_str(), failed.as_str(), second.as_str()]);
+
+    let completed = engine
+        .list_instances(&InstanceFilter::default().with_state(InstanceState::Completed))
+        .await
+        .unwrap();
+    assert_eq!(completed.len(), 2);
+
+    let failures = engine
+        .list_instances(&InstanceFilter::default().with_workflow_id("default/fail/1.0.0"))
+        .await
+        .unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures.first().unwrap().instance_id, failed);
+
+    let limited = engine
+        .list_instances(&InstanceFilter {
+            limit: Some(1),
+            ..InstanceFilter::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(limited.len(), 1);
+    assert_eq!(limited.first().unwrap().instance_id, first);
+}
+
+#[tokio::test]
+async fn test_unknown_instance_is_not_found() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let result = engine.get_instance_status("missing").await;
+    assert!(matches!(result, Err(Error::InstanceNotFound { .. })));
+}
+
+#[tokio::test]
+async fn test_state_is_recorded_for_each_transition() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let handle = engine
+        .execute(fixtures::load("instance-status", "slow.sw.yaml"), json!({}))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(
+        engine.instance_state(&instance_id).await.unwrap(),
+        InstanceState::Running
+    );
+
+    engine.suspend_instance(&instance_id, None).await.unwrap();
+    let _ = handle.wait_for_completion(Duration::from_secs(10)).await;
+    assert_eq!(
+        engine.instance_state(&instance_id).await.unwrap(),
+        InstanceState::Suspended
+    );
+
+    engine.cancel_instance(&instance_id, None).await.unwrap();
+    assert_eq!(
+        engine.instance_state(&instance_id).await.unwrap(),
+        InstanceState::Cancelled
+    );
+}
+
+#[tokio::test]
+async fn test_illegal_transitions_are_rejected() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let completed = run(&engine, "greet.sw.yaml").await;
+    let result = engine
+        .resume_instance(
+            fixtures::load("instance-status", "greet.sw.yaml"),
+            &completed,
+        )
+        .await;
+    assert!(matches!(
+        result,
+        Err(Error::IllegalTransition {
+            from: InstanceState::Completed,
+            to: InstanceState::Running,
+            ..
+        })
+    ));
+    let result = engine
+        .resume(
+            fixtures::load("instance-status", "greet.sw.yaml"),
+            completed.clone(),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::IllegalTransition { .. })));
+    assert_eq!(
+        engine.instance_state(&completed).await.unwrap(),
+        InstanceState::Completed
+    );
+
+    let failed = run(&engine, "fail.sw.yaml").await;
+    let error = engine
+        .cancel_instance(&failed, None)
+        .await
+        .expect_err("a faulted instance cannot be cancelled");
+    assert_eq!(
+        error.to_string(),
+        format!("Workflow instance {failed} is faulted and cannot become cancelled")
+    );
+}
+
+#[tokio::test]
+async fn test_status_queries_use_read_persistence() {
+    let primary = Arc::new(InMemoryPersistence::new());
+    let replica = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(primary.clone())
+        .with_read_persistence(replica.clone())
+        .build()
+        .unwrap();
+
+    let instance_id = run(&engine, "greet.sw.yaml").await;
+    let error = engine.get_instance_status(&instance_id).await.unwrap_err();
+    assert!(matches!(error, Error::InstanceNotFound { .. }));
+    assert!(
+        engine
+            .list_instances(&InstanceFilter::default())
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // Once the replica has caught up, the instance is reported from it
+    for event in primary.get_events(&instance_id).await.unwrap() {
+        replica.save_event(event).await.unwrap();
+    }
+    let status = engine.get_instance_status(&instance_id).await.unwrap();
+    assert_eq!(status.state, InstanceState::Completed);
+    let listed = engine
+        .list_instances(&InstanceFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 1);
+}
@@ -0,0 +1,75 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for workflows with many tasks, which must parse and build their
+/// graph within a startup budget
+use jackdaw::durableengine::DurableEngine;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// How long parsing and building the graph of [`TASKS`] tasks may take
+///
+/// Generous, since tests run unoptimized; `just bench` measures the real cost.
+const BUDGET: Duration = Duration::from_secs(10);
+
+const TASKS: usize = 10_000;
+
+/// A workflow of `tasks` tasks, mixing the kinds of task large workflows
+/// are made of
+fn large_workflow(tasks: usize) -> String {
+    let mut yaml = String::from(
+        "document:\n  dsl: '1.0.2'\n  namespace: default\n  name: large\n  version: '1.0.0'\ndo:\n",
+    );
+    for i in 0..tasks {
+        match i % 4 {
+            0 => writeln!(
+                yaml,
+                "  - task{i}:\n      set:\n        step: {i}\n        total: ${{ .total + {i} }}"
+            ),
+            1 => writeln!(
+                yaml,
+                "  - task{i}:\n      switch:\n        - even:\n            when: ${{ .total % 2 == 0 }}\n            then: continue\n        - default:\n            then: continue"
+            ),
+            2 => writeln!(
+                yaml,
+                "  - task{i}:\n      call: http\n      with:\n        method: get\n        endpoint: https://example.com/tasks/{i}"
+            ),
+            _ => writeln!(
+                yaml,
+                "  - task{i}:\n      if: ${{ .total > {i} }}\n      wait:\n        seconds: 1"
+            ),
+        }
+        .unwrap();
+    }
+    yaml
+}
+
+#[test]
+fn test_large_workflow_starts_within_budget() {
+    let yaml = large_workflow(TASKS);
+
+    let started = Instant::now();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&yaml).unwrap();
+    let (graph, task_names) = DurableEngine::validate_workflow_graph(&workflow).unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(graph.node_count(), TASKS);
+    assert_eq!(task_names.len(), TASKS);
+    assert!(
+        elapsed < BUDGET,
+        "parsing {TASKS} tasks took {elapsed:?}, over the {BUDGET:?} budget"
+    );
+}
+
+#[test]
+fn test_graph_borrows_tasks_from_the_workflow() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&large_workflow(8)).unwrap();
+    let (graph, task_names) = DurableEngine::validate_workflow_graph(&workflow).unwrap();
+
+    for (name, task) in workflow.do_.entries.iter().flatten() {
+        let (node_name, node_task) = graph[task_names[name.as_str()]];
+        assert!(std::ptr::eq(node_name, name.as_str()));
+        assert!(std::ptr::eq(node_task, task));
+    }
+}
@@ -0,0 +1,81 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+#![allow(clippy::wildcard_enum_match_arm)]
+
+mod fixtures;
+
+/// Tests for taking over the instances of a process that stopped renewing its leases
+use chrono::Utc;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::Error;
+use jackdaw::persistence::{self, PersistenceProvider};
+use jackdaw::providers::persistence::SqlitePersistence;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn started(instance_id: &str) -> WorkflowEvent {
+    WorkflowEvent::WorkflowStarted {
+        instance_id: instance_id.to_string(),
+        workflow_id: "default/leased/1.0.0".to_string(),
+        timestamp: Utc::now(),
+        initial_data: json!({}),
+        metadata: None,
+    }
+}
+
+#[tokio::test]
+async fn test_lapsed_lease_is_taken_over_and_fences_old_owner() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!(
+        "sqlite://{}?mode=rwc",
+        dir.path().join("jackdaw.db").display()
+    );
+
+    // A process that took the instance's lease and then stopped renewing it
+    let crashed = SqlitePersistence::new(&url).await.unwrap();
+    crashed
+        .acquire_lease("orphan", "crashed", Duration::from_millis(300))
+        .await
+        .unwrap()
+        .unwrap();
+    crashed.save_event(started("orphan")).await.unwrap();
+
+    let persistence = Arc::new(SqlitePersistence::new(&url).await.unwrap());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence.clone())
+        .build()
+        .unwrap();
+
+    let error = engine
+        .resume(
+            fixtures::load("lease", "greet.sw.yaml"),
+            "orphan".to_string(),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, Error::InstanceLeased { .. }));
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let output = engine
+        .resume(
+            fixtures::load("lease", "greet.sw.yaml"),
+            "orphan".to_string(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(output["message"], json!("Hello"));
+
+    // Late writes of the old owner are refused
+    let error = crashed.save_event(started("orphan")).await.unwrap_err();
+    assert!(matches!(
+        error,
+        persistence::Error::LeaseLost { token: 1, .. }
+    ));
+    let events = persistence.get_events("orphan").await.unwrap();
+    assert!(matches!(
+        events.last(),
+        Some(WorkflowEvent::WorkflowCompleted { .. })
+    ));
+}
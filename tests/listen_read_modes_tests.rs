@@ -299,3 +299,101 @@ async fn test_listen_read_mode_default_is_envelope() {
         "Default read mode should be envelope with CloudEvent id"
     );
 }
+
+#[tokio::test]
+async fn test_listen_reads_binary_mode_cloudevents() {
+    // Set up Python path for handler
+    let handlers_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/handlers");
+    unsafe {
+        env::set_var("PYTHONPATH", handlers_dir.to_str().unwrap());
+    }
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let engine = setup_test_engine(&temp_dir).await;
+
+    let fixture = PathBuf::from("tests/fixtures/listen-read-modes/test-listen-read-binary.sw.yaml");
+    let workflow_yaml = std::fs::read_to_string(&fixture).unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml).unwrap();
+
+    let _handle = engine
+        .execute(workflow, json!({}))
+        .await
+        .expect("Failed to start workflow");
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+    // The context attributes of a binary mode CloudEvent are headers
+    let response = reqwest::Client::new()
+        .post("http://localhost:8085/webhook")
+        .header("ce-specversion", "1.0")
+        .header("ce-type", "test.event.v1")
+        .header("ce-source", "binary-test")
+        .header("ce-id", "test-321")
+        .json(&json!({ "message": "Binary mode" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200, "Listener request failed");
+
+    let response_body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(response_body["type"], json!("test.event.v1"));
+    assert_eq!(response_body["id"], json!("test-321"));
+    assert_eq!(response_body["datacontenttype"], json!("application/json"));
+    assert_eq!(response_body["data"], json!({ "message": "Binary mode" }));
+}
+
+#[tokio::test]
+async fn test_listen_read_mode_raw_passes_text_bodies() {
+    // Set up Python path for handler
+    let handlers_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/handlers");
+    unsafe {
+        env::set_var("PYTHONPATH", handlers_dir.to_str().unwrap());
+    }
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let engine = setup_test_engine(&temp_dir).await;
+
+    let fixture =
+        PathBuf::from("tests/fixtures/listen-read-modes/test-listen-read-raw-text.sw.yaml");
+    let workflow_yaml = std::fs::read_to_string(&fixture).unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml).unwrap();
+
+    let _handle = engine
+        .execute(workflow, json!({}))
+        .await
+        .expect("Failed to start workflow");
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+    // A body that is not JSON reaches the handler as a string
+    let response = reqwest::Client::new()
+        .post("http://localhost:8086/webhook")
+        .header("content-type", "text/csv")
+        .body("id,name\n7,Rex")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200, "Listener request failed");
+
+    let response_body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(response_body, json!("id,name\n7,Rex"));
+}
+
+#[tokio::test]
+async fn test_listen_invalid_read_mode_fails() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let engine = setup_test_engine(&temp_dir).await;
+
+    let fixture =
+        PathBuf::from("tests/fixtures/listen-read-modes/test-listen-read-invalid.sw.yaml");
+    let workflow_yaml = std::fs::read_to_string(&fixture).unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml).unwrap();
+
+    let error = match engine.execute(workflow, json!({})).await {
+        Ok(handle) => handle
+            .wait_for_completion(std::time::Duration::from_secs(30))
+            .await
+            .unwrap_err()
+            .to_string(),
+        Err(error) => error.to_string(),
+    };
+    assert!(error.contains("Invalid read mode 'body'"), "{error}");
+}
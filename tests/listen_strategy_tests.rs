@@ -0,0 +1,152 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for the `listen.to` consumption strategies: `all`, `any` and `one`,
+/// with `until` given as an expression or as a strategy
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::DurableEngine;
+use jackdaw::events::CloudEvent;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+fn event(event_type: &str, data: Value) -> CloudEvent {
+    CloudEvent::from_attributes(
+        json!({ "type": event_type, "data": data })
+            .as_object()
+            .unwrap()
+            .clone(),
+        "/tests",
+    )
+    .unwrap()
+}
+
+/// Run a workflow, publishing `events` once its listen task is waiting
+async fn run_with_events(engine: &DurableEngine, workflow: &str, events: &[CloudEvent]) -> Value {
+    let handle = engine
+        .execute(fixtures::load("listen-strategies", workflow), json!({}))
+        .await
+        .unwrap();
+
+    // Give the listen task time to subscribe
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    for event in events {
+        engine.publish_event(event).unwrap();
+    }
+
+    handle
+        .wait_for_completion(Duration::from_secs(10))
+        .await
+        .unwrap()
+}
+
+fn event_types(output: &Value) -> Vec<&str> {
+    output
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event.get("type").and_then(Value::as_str).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_listen_all_waits_for_every_filter() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let output = run_with_events(
+        &engine,
+        "listen-all.sw.yaml",
+        &[
+            event("com.example.payment.received", json!({ "amount": 10 })),
+            event("com.example.user.created", json!({})),
+            event("com.example.payment.received", json!({ "amount": 20 })),
+            event("com.example.order.placed", json!({ "orderId": 1 })),
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        event_types(&output),
+        vec!["com.example.payment.received", "com.example.order.placed"]
+    );
+    assert_eq!(output.pointer("/0/data/amount"), Some(&json!(10)));
+}
+
+#[tokio::test]
+async fn test_listen_any_until_expression() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let output = run_with_events(
+        &engine,
+        "listen-any-until-expression.sw.yaml",
+        &[
+            event("com.example.order.placed", json!({ "orderId": 1 })),
+            event("com.example.user.created", json!({ "userId": 7 })),
+            event("com.example.order.shipped", json!({ "orderId": 1 })),
+            event("com.example.order.placed", json!({ "orderId": 2 })),
+        ],
+    )
+    .await;
+
+    // `read: data` returns only the data of the consumed events
+    assert_eq!(output, json!([{ "orderId": 1 }, { "orderId": 1 }]));
+}
+
+#[tokio::test]
+async fn test_listen_any_until_strategy() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let output = run_with_events(
+        &engine,
+        "listen-any-until-strategy.sw.yaml",
+        &[
+            event("com.example.order.placed", json!({ "orderId": 1 })),
+            event("com.example.order.placed", json!({ "orderId": 2 })),
+            event("com.example.orders.closed", json!({})),
+            event("com.example.order.placed", json!({ "orderId": 3 })),
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        event_types(&output),
+        vec!["com.example.order.placed", "com.example.order.placed"]
+    );
+    assert_eq!(output.pointer("/1/data/orderId"), Some(&json!(2)));
+}
+
+#[tokio::test]
+async fn test_listen_one_consumes_emitted_event() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let handle = engine
+        .execute(
+            fixtures::load("listen-strategies", "listen-one-emitted.sw.yaml"),
+            json!({}),
+        )
+        .await
+        .unwrap();
+    let output = handle
+        .wait_for_completion(Duration::from_secs(10))
+        .await
+        .unwrap();
+
+    // The low-priority event does not match the filter's expression
+    assert_eq!(output.get("receive"), Some(&json!([{ "attempt": 2 }])));
+}
+
+#[tokio::test]
+async fn test_listen_task_times_out_without_events() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+
+    let handle = engine
+        .execute(
+            fixtures::load("listen-strategies", "listen-timeout.sw.yaml"),
+            json!({}),
+        )
+        .await
+        .unwrap();
+    let result = handle.wait_for_completion(Duration::from_secs(10)).await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,138 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for authentication of the requests HTTP listeners receive
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jackdaw::listeners::{HttpListener, Listener, ListenerAuth};
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const SIGNING_SECRET: &[u8] = b"listener-auth-test-signing-secret";
+
+type Handler = Arc<dyn Fn(Value) -> jackdaw::listeners::Result<Value> + Send + Sync>;
+
+/// Start a listener echoing the requests to `/orders`, authenticated with `auth`
+async fn start_listener(auth: ListenerAuth) -> (HttpListener, String) {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let handler: Handler = Arc::new(|body| Ok(json!({ "received": body })));
+    let listener = HttpListener::new_multi_route(
+        format!("127.0.0.1:{port}"),
+        HashMap::from([("/orders".to_string(), handler)]),
+    )
+    .unwrap()
+    .with_auth("/orders", auth);
+    listener.start().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    (listener, format!("http://127.0.0.1:{port}/orders"))
+}
+
+async fn post(url: &str, authorization: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new().post(url).json(&json!({ "id": 7 }));
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    request.send().await.unwrap()
+}
+
+async fn jwks_server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/jwks"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "keys": [{
+                "kty": "oct",
+                "kid": "k1",
+                "alg": "HS256",
+                "k": URL_SAFE_NO_PAD.encode(SIGNING_SECRET),
+            }]
+        })))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn token(kid: &str, secret: &[u8], audience: &str) -> String {
+    let mut header = Header::new(jsonwebtoken::Algorithm::HS256);
+    header.kid = Some(kid.to_string());
+    let expires = chrono::Utc::now().timestamp() + 300;
+    jsonwebtoken::encode(
+        &header,
+        &json!({ "sub": "ops", "aud": audience, "iss": "https://idp.example.com", "exp": expires }),
+        &EncodingKey::from_secret(secret),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_bearer_protected_listener_rejects_missing_or_wrong_tokens() {
+    let (listener, url) = start_listener(ListenerAuth::Bearer {
+        token: "s3cr3t".to_string(),
+    })
+    .await;
+
+    let missing = post(&url, None).await;
+    assert_eq!(missing.status(), 401);
+    assert_eq!(
+        missing.headers()["www-authenticate"],
+        "Bearer realm=\"jackdaw\""
+    );
+    assert_eq!(post(&url, Some("Bearer guess")).await.status(), 401);
+
+    let accepted = post(&url, Some("Bearer s3cr3t")).await;
+    assert_eq!(accepted.status(), 200);
+    let body: Value = accepted.json().await.unwrap();
+    assert_eq!(body["received"]["id"], 7);
+
+    listener.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_jwt_protected_listener_checks_tokens_against_jwks() {
+    let server = jwks_server().await;
+    let (listener, url) = start_listener(ListenerAuth::Jwt {
+        jwks_uri: format!("{}/jwks", server.uri()),
+        issuer: Some("https://idp.example.com".to_string()),
+        audience: Some("orders".to_string()),
+    })
+    .await;
+
+    let valid = token("k1", SIGNING_SECRET, "orders");
+    assert_eq!(
+        post(&url, Some(&format!("Bearer {valid}"))).await.status(),
+        200
+    );
+
+    let other_audience = token("k1", SIGNING_SECRET, "billing");
+    assert_eq!(
+        post(&url, Some(&format!("Bearer {other_audience}")))
+            .await
+            .status(),
+        401
+    );
+
+    let forged = token("k1", b"not-the-signing-secret", "orders");
+    assert_eq!(
+        post(&url, Some(&format!("Bearer {forged}"))).await.status(),
+        401
+    );
+
+    let unknown_key = token("k2", SIGNING_SECRET, "orders");
+    assert_eq!(
+        post(&url, Some(&format!("Bearer {unknown_key}")))
+            .await
+            .status(),
+        401
+    );
+
+    listener.stop().await.unwrap();
+}
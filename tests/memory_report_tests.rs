@@ -0,0 +1,39 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for the memory reports of a long-running engine
+use jackdaw::DurableEngineBuilder;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_report_measures_in_memory_cache() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    for name in ["quote", "rates"] {
+        engine
+            .register_function(name, |_input: Value| async move {
+                Ok::<_, String>(json!({ "price": 42 }))
+            })
+            .unwrap();
+    }
+
+    let handle = engine
+        .execute(
+            fixtures::load("cache", "ttl.sw.yaml"),
+            json!({ "symbol": "JDW" }),
+        )
+        .await
+        .unwrap();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    let report = engine.memory_report().await.unwrap();
+    let cache = report.cache.expect("in-memory cache reports its footprint");
+    assert_eq!(cache.entries, 2);
+    assert!(cache.bytes > 0);
+    assert!(report.to_prometheus().contains("jackdaw_cache_entries 2"));
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for mock servers of OpenAPI documents and routing calls to them
+use jackdaw::DurableEngineBuilder;
+use jackdaw::mock::MockApi;
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_mock_serves_examples_and_schemas() {
+    let api = MockApi::load(Path::new("tests/fixtures/mock/petstore.yaml")).unwrap();
+    let server = api.start("127.0.0.1:0").await.unwrap();
+    let client = reqwest::Client::new();
+
+    let pet: serde_json::Value = client
+        .get(format!("{}/v1/pets/7", server.url()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pet, json!({"id": 7, "name": "Rex"}));
+
+    let pets: serde_json::Value = client
+        .get(format!("{}/v1/pets", server.url()))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pets, json!([{"id": 0, "name": "Fido"}]));
+
+    let missing = client
+        .delete(format!("{}/v1/pets/7", server.url()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_calls_to_mocked_api_are_routed_to_the_mock() {
+    let api = MockApi::load(Path::new("tests/fixtures/mock/petstore.yaml")).unwrap();
+    let server_url = api.server_url().unwrap().to_string();
+    let server = api.start("127.0.0.1:0").await.unwrap();
+
+    let engine = DurableEngineBuilder::new()
+        .with_mock_route(server_url, format!("{}/v1", server.url()))
+        .build()
+        .unwrap();
+    let handle = engine
+        .execute(fixtures::load("mock", "get-pet.sw.yaml"), json!({}))
+        .await
+        .unwrap();
+    let output = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert_eq!(output, json!({"id": 7, "name": "Rex"}));
+}
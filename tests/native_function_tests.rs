@@ -0,0 +1,99 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for calling Rust functions registered with the engine
+use jackdaw::DurableEngineBuilder;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+fn fraud_check(input: Value) -> Result<Value, String> {
+    let amount = input
+        .get("amount")
+        .and_then(Value::as_f64)
+        .ok_or("amount is missing")?;
+    Ok(json!({ "suspicious": amount > 10_000.0 }))
+}
+
+#[tokio::test]
+async fn test_call_registered_function_by_name() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    engine
+        .register_function("fraud-check", |input| async move { fraud_check(input) })
+        .unwrap();
+
+    let handle = engine
+        .execute(
+            fixtures::load("native", "fraud-check.sw.yaml"),
+            json!({ "order": { "total": 25_000 } }),
+        )
+        .await
+        .unwrap();
+    let result = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    assert_eq!(result, json!({ "suspicious": true }));
+}
+
+#[tokio::test]
+async fn test_function_error_fails_task() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    engine
+        .register_function("fraud-check", |input| async move { fraud_check(input) })
+        .unwrap();
+
+    let handle = engine
+        .execute(
+            fixtures::load("native", "fraud-check.sw.yaml"),
+            json!({ "order": {} }),
+        )
+        .await
+        .unwrap();
+    let error = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .expect_err("Function should have failed");
+
+    assert!(
+        error.to_string().contains("amount is missing"),
+        "Unexpected error: {error}"
+    );
+}
+
+#[tokio::test]
+async fn test_call_native_receives_task_input() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    engine
+        .register_function("double", |input: Value| async move {
+            let value = input
+                .get("value")
+                .and_then(Value::as_i64)
+                .unwrap_or_default();
+            Ok::<_, String>(json!({ "value": value * 2 }))
+        })
+        .unwrap();
+
+    let handle = engine
+        .execute(
+            fixtures::load("native", "task-input.sw.yaml"),
+            json!({ "value": 21 }),
+        )
+        .await
+        .unwrap();
+    let result = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    assert_eq!(result, json!({ "value": 42 }));
+}
+
+#[test]
+fn test_builtin_call_types_cannot_be_replaced() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let result = engine.register_function("http", |input| async move { Ok::<_, String>(input) });
+    assert!(result.is_err());
+}
@@ -0,0 +1,148 @@
+#![allow(clippy::unwrap_used)]
+
+/// Tests for how the numbers of workflow data are held
+use jackdaw::DurableEngineBuilder;
+use jackdaw::numbers::NumberMode;
+use serde_json::Value;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+
+/// A workflow that passes an amount on through an expression
+fn invoice_workflow() -> WorkflowDefinition {
+    serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: invoice
+  version: '1.0.0'
+do:
+  - total:
+      set:
+        total: ${ .amount }
+        lines: ${ [.amount, .fee] }
+",
+    )
+    .unwrap()
+}
+
+/// An input with an amount `f64` cannot hold
+fn invoice_input() -> Value {
+    serde_json::from_str(r#"{"amount": 1234567890.123456789, "fee": 0.1}"#).unwrap()
+}
+
+#[tokio::test]
+async fn test_float_mode_rounds_amounts() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let output = engine
+        .execute(invoice_workflow(), invoice_input())
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert_eq!(output["total"].to_string(), "1234567890.1234567");
+    assert_eq!(output["lines"].to_string(), "[1234567890.1234567,0.1]");
+}
+
+#[cfg(not(feature = "arbitrary-precision"))]
+#[test]
+fn test_decimal_mode_needs_the_feature() {
+    let error = DurableEngineBuilder::new()
+        .with_number_mode(NumberMode::Decimal)
+        .build()
+        .unwrap_err();
+    assert!(error.to_string().contains("arbitrary-precision"), "{error}");
+}
+
+#[cfg(feature = "arbitrary-precision")]
+mod decimal {
+    use super::*;
+    use jackdaw::cache::compute_cache_key;
+    use jackdaw::persistence::PersistenceProvider;
+    use jackdaw::providers::persistence::RedbPersistence;
+    use jackdaw::workflow::WorkflowEvent;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_amounts_keep_their_digits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let persistence = Arc::new(RedbPersistence::new(db_path.to_str().unwrap()).unwrap());
+        let engine = DurableEngineBuilder::new()
+            .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+            .with_number_mode(NumberMode::Decimal)
+            .build()
+            .unwrap();
+
+        let handle = engine
+            .execute(invoice_workflow(), invoice_input())
+            .await
+            .unwrap();
+        let instance_id = handle.instance_id().to_string();
+        let output = handle
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert_eq!(output["total"].to_string(), "1234567890.123456789");
+        assert_eq!(output["lines"].to_string(), "[1234567890.123456789,0.1]");
+
+        // The recorded events hold the amount as it was written
+        let events = persistence.get_events(&instance_id).await.unwrap();
+        let Some(WorkflowEvent::WorkflowCompleted { final_data, .. }) = events.last() else {
+            panic!("the instance did not complete: {events:?}");
+        };
+        assert_eq!(final_data["total"].to_string(), "1234567890.123456789");
+    }
+
+    #[tokio::test]
+    async fn test_arithmetic_is_computed_on_f64() {
+        let workflow: WorkflowDefinition = serde_yaml::from_str(
+            r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: sum
+  version: '1.0.0'
+do:
+  - add:
+      set:
+        sum: ${ .a + .b }
+        a: ${ .a }
+",
+        )
+        .unwrap();
+        let engine = DurableEngineBuilder::new()
+            .with_number_mode(NumberMode::Decimal)
+            .build()
+            .unwrap();
+
+        let output = engine
+            .execute(
+                workflow,
+                serde_json::from_str(r#"{"a": 0.1, "b": 0.2}"#).unwrap(),
+            )
+            .await
+            .unwrap()
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .unwrap();
+        // The numbers are passed on as written, but their sum is an f64
+        assert_eq!(output["a"].to_string(), "0.1");
+        assert_eq!(output["sum"].to_string(), "0.30000000000000004");
+    }
+
+    #[test]
+    fn test_cache_keys_tell_amounts_apart() {
+        let exact: Value = serde_json::from_str(r#"{"amount": 1234567890.123456789}"#).unwrap();
+        let rounded: Value = serde_json::from_str(r#"{"amount": 1234567890.1234567}"#).unwrap();
+        assert_ne!(
+            compute_cache_key("charge", &exact),
+            compute_cache_key("charge", &rounded)
+        );
+        assert_eq!(
+            compute_cache_key("charge", &exact),
+            compute_cache_key("charge", &NumberMode::Decimal.apply(exact.clone()))
+        );
+    }
+}
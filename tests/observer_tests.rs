@@ -0,0 +1,95 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+#![allow(clippy::wildcard_enum_match_arm)]
+
+mod fixtures;
+
+/// Tests for observing the events of an instance as they are recorded
+use async_trait::async_trait;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::observer::WorkflowObserver;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Collects the kinds of the events it is called with
+#[derive(Debug, Default)]
+struct Recorder(Mutex<Vec<String>>);
+
+#[async_trait]
+impl WorkflowObserver for Recorder {
+    async fn on_event(&self, event: &WorkflowEvent) {
+        let kind = match event {
+            WorkflowEvent::TaskCompleted { task_name, .. } => format!("completed {task_name}"),
+            WorkflowEvent::WorkflowFailed { .. } => "failed".to_string(),
+            _ => return,
+        };
+        self.0.lock().unwrap().push(kind);
+    }
+}
+
+#[tokio::test]
+async fn test_channel_receives_events_as_recorded() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let (events, mut progress) = tokio::sync::mpsc::channel(100);
+    let handle = engine
+        .start_with_observer(
+            fixtures::load("observer", "two-steps.sw.yaml"),
+            json!({}),
+            Arc::new(events),
+        )
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+
+    // The channel closes when the instance finishes
+    let mut received = Vec::new();
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while let Some(event) = progress.recv().await {
+            received.push(event);
+        }
+    })
+    .await
+    .expect("Channel was not closed");
+
+    assert!(
+        received
+            .iter()
+            .all(|event| event.instance_id() == instance_id)
+    );
+    let completed: Vec<&str> = received
+        .iter()
+        .filter_map(|event| match event {
+            WorkflowEvent::TaskCompleted { task_name, .. } => Some(task_name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(completed, vec!["greet", "shout"]);
+    assert!(matches!(
+        received.first(),
+        Some(WorkflowEvent::WorkflowStarted { .. })
+    ));
+    assert!(matches!(
+        received.last(),
+        Some(WorkflowEvent::WorkflowCompleted { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_observer_sees_failure() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let recorder = Arc::new(Recorder::default());
+    let handle = engine
+        .start_with_observer(
+            fixtures::load("observer", "failing.sw.yaml"),
+            json!({}),
+            Arc::clone(&recorder) as Arc<dyn WorkflowObserver>,
+        )
+        .await
+        .unwrap();
+    let result = handle.wait_for_completion(Duration::from_secs(30)).await;
+    assert!(result.is_err());
+
+    assert_eq!(*recorder.0.lock().unwrap(), vec!["failed".to_string()]);
+}
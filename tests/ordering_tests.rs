@@ -0,0 +1,115 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the order tasks run in and the fields of their outputs are kept in
+use jackdaw::DurableEngineBuilder;
+use serde_json::{Value, json};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+
+async fn run(workflow: &str, input: Value) -> Value {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(workflow).unwrap();
+    DurableEngineBuilder::new()
+        .build()
+        .unwrap()
+        .execute(workflow, input)
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap()
+}
+
+fn keys(value: &Value) -> Vec<&str> {
+    value
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .filter(|key| !key.starts_with("__"))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_do_entries_run_in_document_order() {
+    let output = run(
+        r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: trail
+  version: '1.0.0'
+do:
+  - zulu:
+      set:
+        trail: ${ .trail + ["zulu"] }
+  - alpha:
+      set:
+        trail: ${ .trail + ["alpha"] }
+  - mike:
+      set:
+        trail: ${ .trail + ["mike"] }
+"#,
+        json!({ "trail": [] }),
+    )
+    .await;
+    assert_eq!(output["trail"], json!(["zulu", "alpha", "mike"]));
+}
+
+const BRANCHES: &str = r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: branches
+  version: '1.0.0'
+do:
+  - fanOut:
+      fork:
+        branches:
+          - zulu:
+              set:
+                value: 1
+          - alpha:
+              set:
+                value: 2
+          - mike:
+              set:
+                value: 3
+";
+
+#[tokio::test]
+async fn test_fork_outputs_follow_branch_order() {
+    for _ in 0..5 {
+        let output = run(BRANCHES, json!({})).await;
+        assert_eq!(keys(&output), ["zulu", "alpha", "mike"]);
+    }
+}
+
+#[tokio::test]
+async fn test_objects_keep_the_order_they_were_written_in() {
+    let output = run(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: objects
+  version: '1.0.0'
+do:
+  - describe:
+      set:
+        order: '${ { mike: .alpha, bravo: .zulu } }'
+        count: 2
+",
+        serde_json::from_str(r#"{ "zulu": 1, "alpha": 2 }"#).unwrap(),
+    )
+    .await;
+    // Fields of `set` have no document order once parsed, so they are set in key order
+    let set: Vec<_> = keys(&output)
+        .into_iter()
+        .filter(|key| ["count", "order"].contains(key))
+        .collect();
+    assert_eq!(set, ["count", "order"]);
+    // Objects built by expressions keep the order they were written in
+    assert_eq!(keys(&output["order"]), ["mike", "bravo"]);
+    assert_eq!(output["order"], json!({ "bravo": 1, "mike": 2 }));
+}
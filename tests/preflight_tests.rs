@@ -0,0 +1,72 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for pre-flight checks of the resources a workflow references
+use jackdaw::DurableEngineBuilder;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::path::Path;
+
+const FIXTURE: &str = "tests/fixtures/preflight/missing-resources.sw.yaml";
+
+#[tokio::test]
+async fn test_preflight_reports_every_missing_resource() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let yaml = std::fs::read_to_string(FIXTURE).expect("Failed to read fixture");
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&yaml).unwrap();
+    engine
+        .set_workflow_path(&workflow, Path::new(FIXTURE))
+        .await;
+
+    let report = engine.preflight(&workflow).await;
+
+    assert!(!report.is_ok());
+    assert_eq!(report.checked, 4);
+    let problems: Vec<(&str, &str)> = report
+        .problems
+        .iter()
+        .map(|problem| (problem.task.as_str(), problem.resource.as_str()))
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            ("add", "file://./missing.proto"),
+            ("script", "file://./missing.py"),
+            ("child", "default/unregistered-child/1.0.0"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_preflight_accepts_registered_subworkflows() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let child: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: unregistered-child
+  version: '1.0.0'
+do:
+  - greet:
+      set:
+        greeting: hello
+",
+    )
+    .unwrap();
+    engine.register_workflow(child).await.unwrap();
+    let yaml = std::fs::read_to_string(FIXTURE).expect("Failed to read fixture");
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&yaml).unwrap();
+    engine
+        .set_workflow_path(&workflow, Path::new(FIXTURE))
+        .await;
+
+    let report = engine.preflight(&workflow).await;
+
+    assert!(
+        !report
+            .problems
+            .iter()
+            .any(|problem| problem.task == "child"),
+        "{report}"
+    );
+}
@@ -0,0 +1,70 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for workers sharing a queue of workflow starts
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::InstanceState;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::SqlitePersistence;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_workers_share_queued_starts() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!(
+        "sqlite://{}?mode=rwc",
+        dir.path().join("jackdaw.db").display()
+    );
+    let worker = |persistence: Arc<SqlitePersistence>| {
+        DurableEngineBuilder::new()
+            .with_persistence(persistence)
+            .build()
+            .unwrap()
+    };
+    let persistence = Arc::new(SqlitePersistence::new(&url).await.unwrap());
+    let first = worker(persistence.clone());
+    let second = worker(Arc::new(SqlitePersistence::new(&url).await.unwrap()));
+
+    let workflow = fixtures::load("queue", "greet.sw.yaml");
+    let ada = first
+        .enqueue(&workflow, json!({"name": "Ada"}))
+        .await
+        .unwrap();
+    let bob = first
+        .enqueue(&workflow, json!({"name": "Bob"}))
+        .await
+        .unwrap();
+    assert_eq!(
+        first.instance_state(&ada).await.unwrap(),
+        InstanceState::Pending
+    );
+
+    // A start another worker holds the lease of is left to it
+    let crashed = SqlitePersistence::new(&url).await.unwrap();
+    crashed
+        .acquire_lease(&ada, "crashed", Duration::from_millis(300))
+        .await
+        .unwrap()
+        .unwrap();
+    let run = second.run_next_queued().await.unwrap().unwrap();
+    assert_eq!(run.instance_id, bob);
+    assert_eq!(run.output.unwrap()["message"], json!("Hello Bob"));
+    assert!(second.run_next_queued().await.unwrap().is_none());
+
+    // Once the lease lapses the start is claimed by the next worker to poll
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let run = first.run_next_queued().await.unwrap().unwrap();
+    assert_eq!(run.instance_id, ada);
+    assert_eq!(run.output.unwrap()["message"], json!("Hello Ada"));
+    assert_eq!(
+        first.instance_state(&ada).await.unwrap(),
+        InstanceState::Completed
+    );
+
+    assert!(persistence.queued_starts(10).await.unwrap().is_empty());
+    assert!(first.run_next_queued().await.unwrap().is_none());
+}
@@ -0,0 +1,81 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for resuming the instances a stopped process left running
+use chrono::Utc;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::InstanceState;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::SqlitePersistence;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Record an instance as a process does before it stops mid-run
+async fn crash(persistence: &SqlitePersistence, instance_id: &str, workflow_id: &str) {
+    persistence
+        .acquire_lease(instance_id, "crashed", Duration::from_millis(300))
+        .await
+        .unwrap()
+        .unwrap();
+    persistence
+        .transition_state(instance_id, None, InstanceState::Running)
+        .await
+        .unwrap();
+    persistence
+        .save_event(WorkflowEvent::WorkflowStarted {
+            instance_id: instance_id.to_string(),
+            workflow_id: workflow_id.to_string(),
+            timestamp: Utc::now(),
+            initial_data: json!({"name": "Ada"}),
+            metadata: None,
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_recover_incomplete_resumes_instances_once_their_lease_lapses() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!(
+        "sqlite://{}?mode=rwc",
+        dir.path().join("jackdaw.db").display()
+    );
+    let crashed = SqlitePersistence::new(&url).await.unwrap();
+    crash(&crashed, "orphan", "default/recovered/1.0.0").await;
+    crash(&crashed, "unknown", "default/unregistered/1.0.0").await;
+
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::new(SqlitePersistence::new(&url).await.unwrap()))
+        .build()
+        .unwrap();
+    engine
+        .register_workflow(fixtures::load("recovery", "greet.sw.yaml"))
+        .await
+        .unwrap();
+
+    // Instances another process still holds the lease of are left to it
+    assert!(engine.recover_incomplete().await.unwrap().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let runs = engine.recover_incomplete().await.unwrap();
+    assert_eq!(runs.len(), 1);
+    let run = runs.into_iter().next().unwrap();
+    assert_eq!(run.instance_id, "orphan");
+    // The instance keeps the input it was started with
+    assert_eq!(run.output.unwrap()["message"], json!("Hello Ada"));
+    assert_eq!(
+        engine.instance_state("orphan").await.unwrap(),
+        InstanceState::Completed
+    );
+
+    // An instance whose workflow is not registered stays running
+    assert_eq!(
+        engine.instance_state("unknown").await.unwrap(),
+        InstanceState::Running
+    );
+    assert!(engine.recover_incomplete().await.unwrap().is_empty());
+}
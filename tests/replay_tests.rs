@@ -0,0 +1,96 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for replaying recorded instances to check they are deterministic
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::{DurableEngine, Error};
+use serde_json::json;
+use std::time::Duration;
+
+/// Run a workflow to its end, returning its instance ID and output
+async fn run(engine: &DurableEngine, workflow: &str) -> (String, serde_json::Value) {
+    let handle = engine
+        .execute(fixtures::load("replay", workflow), json!({}))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let output = handle
+        .wait_for_completion(Duration::from_secs(10))
+        .await
+        .unwrap();
+    (instance_id, output)
+}
+
+#[tokio::test]
+async fn test_replay_of_unchanged_workflow_is_deterministic() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let (instance_id, output) = run(&engine, "route.sw.yaml").await;
+    let status = engine.get_instance_status(&instance_id).await.unwrap();
+
+    let report = engine
+        .replay_instance(fixtures::load("replay", "route.sw.yaml"), &instance_id)
+        .await
+        .unwrap();
+    assert!(report.is_deterministic(), "{:?}", report.divergence);
+    // The shell task prints the time, so only its recorded result can match
+    assert_eq!(report.replayed, vec!["stamp"]);
+    assert_eq!(report.reexecuted, vec!["prepare", "route", "large"]);
+    assert_eq!(report.output, Some(output));
+
+    // The recorded events are left as they were
+    assert_eq!(
+        engine.get_instance_status(&instance_id).await.unwrap(),
+        status
+    );
+}
+
+#[tokio::test]
+async fn test_replay_flags_a_different_path() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let (instance_id, _) = run(&engine, "route.sw.yaml").await;
+
+    let report = engine
+        .replay_instance(
+            fixtures::load("replay", "route-changed.sw.yaml"),
+            &instance_id,
+        )
+        .await
+        .unwrap();
+    let divergence = report.divergence.expect("the switch took another branch");
+    assert_eq!(divergence.position, 3);
+    assert_eq!(divergence.expected.as_deref(), Some("large"));
+    assert_eq!(divergence.actual.as_deref(), Some("small"));
+}
+
+#[tokio::test]
+async fn test_replay_flags_a_different_task_result() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let (instance_id, _) = run(&engine, "route.sw.yaml").await;
+
+    let report = engine
+        .replay_instance(
+            fixtures::load("replay", "route-edited.sw.yaml"),
+            &instance_id,
+        )
+        .await
+        .unwrap();
+    let divergence = report.divergence.expect("the set task changed");
+    assert_eq!(divergence.position, 1);
+    assert_eq!(divergence.expected.as_deref(), Some("prepare"));
+    assert_eq!(divergence.reason, "the task produced a different result");
+    assert_eq!(report.replayed, vec!["stamp"]);
+}
+
+#[tokio::test]
+async fn test_replay_of_unknown_instance() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let result = engine
+        .replay_instance(
+            fixtures::load("replay", "route.sw.yaml"),
+            "no-such-instance",
+        )
+        .await;
+    assert!(matches!(result, Err(Error::InstanceNotFound { .. })));
+}
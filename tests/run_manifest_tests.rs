@@ -0,0 +1,108 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the run manifests recorded when instances start
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::DurableEngine;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+
+const PARENT: &str = r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: manifest-parent
+  version: '1.0.0'
+do:
+  - runChild:
+      run:
+        workflow:
+          namespace: default
+          name: manifest-child
+          version: '1.0.0'
+";
+
+const CHILD: &str = r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: manifest-child
+  version: '1.0.0'
+do:
+  - answer:
+      set:
+        answer: 42
+";
+
+async fn engine(config: serde_json::Value) -> DurableEngine {
+    let engine = DurableEngineBuilder::new()
+        .with_run_manifests(config)
+        .build()
+        .unwrap();
+    engine
+        .register_workflow(serde_yaml::from_str(CHILD).unwrap())
+        .await
+        .unwrap();
+    engine
+}
+
+async fn run(engine: &DurableEngine, input: serde_json::Value) -> String {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(PARENT).unwrap();
+    let handle = engine.execute(workflow, input).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    instance_id
+}
+
+#[tokio::test]
+async fn test_manifest_is_recorded_at_instance_start() {
+    let engine = engine(json!({ "cache_ttl": "PT1H" })).await;
+    let instance_id = run(&engine, json!({ "order": 1 })).await;
+
+    let manifest = engine.manifest(&instance_id).await.unwrap().unwrap();
+    assert_eq!(manifest.engine_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest.workflow, "default/manifest-parent/1.0.0");
+    assert!(manifest.workflow_digest.starts_with("sha256:"));
+    assert!(manifest.input_digest.starts_with("sha256:"));
+    assert_eq!(manifest.config, json!({ "cache_ttl": "PT1H" }));
+    assert_eq!(manifest.resources.len(), 1);
+    let child = manifest.resources.first().unwrap();
+    assert_eq!(child.task, "runChild");
+    assert_eq!(child.resource, "default/manifest-child/1.0.0");
+    assert!(child.digest.is_some());
+}
+
+#[tokio::test]
+async fn test_manifests_of_runs_differ_only_in_what_changed() {
+    let engine = engine(json!({})).await;
+    let first = run(&engine, json!({ "order": 1 })).await;
+    let second = run(&engine, json!({ "order": 1 })).await;
+    let third = run(&engine, json!({ "order": 2 })).await;
+
+    let first = engine.manifest(&first).await.unwrap().unwrap();
+    let second = engine.manifest(&second).await.unwrap().unwrap();
+    let third = engine.manifest(&third).await.unwrap().unwrap();
+    assert!(first.differences(&second).is_empty());
+    let fields: Vec<_> = first
+        .differences(&third)
+        .into_iter()
+        .map(|difference| difference.field)
+        .collect();
+    assert_eq!(fields, vec!["inputDigest"]);
+}
+
+#[tokio::test]
+async fn test_no_manifest_without_run_manifests() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    engine
+        .register_workflow(serde_yaml::from_str(CHILD).unwrap())
+        .await
+        .unwrap();
+    let instance_id = run(&engine, json!({})).await;
+
+    assert!(engine.manifest(&instance_id).await.unwrap().is_none());
+}
@@ -0,0 +1,109 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+#![allow(clippy::panic)]
+
+/// Tests for validating workflow and task input and output against their schemas
+use jackdaw::DurableEngineBuilder;
+use jackdaw::execution_handle::Error as HandleError;
+use jackdaw::executor::WorkflowError;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+
+const ORDER_WORKFLOW: &str = r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-schema-validation
+  version: '1.0.0'
+input:
+  schema:
+    format: json
+    document:
+      type: object
+      required: [orderId]
+      properties:
+        orderId:
+          type: string
+do:
+  - accept:
+      set:
+        accepted: ${ .orderId }
+"#;
+
+const GUARDED_WORKFLOW: &str = r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-schema-validation-caught
+  version: '1.0.0'
+do:
+  - guarded:
+      try:
+        - count:
+            set:
+              quantity: two
+            output:
+              schema:
+                document:
+                  type: object
+                  properties:
+                    quantity:
+                      type: integer
+      catch:
+        errors:
+          with:
+            type: https://serverlessworkflow.io/dsl/errors/types/validation
+        as: failure
+        do:
+          - recover:
+              set:
+                failure: ${ .failure }
+"#;
+
+async fn run(yaml: &str, input: serde_json::Value) -> Result<serde_json::Value, HandleError> {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(yaml).unwrap();
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine.execute(workflow, input).await.unwrap();
+    handle.wait_for_completion(Duration::from_secs(30)).await
+}
+
+#[tokio::test]
+async fn test_input_matching_schema_is_accepted() {
+    let output = run(ORDER_WORKFLOW, json!({ "orderId": "a1" }))
+        .await
+        .unwrap();
+    assert_eq!(output["accepted"], json!("a1"));
+}
+
+#[tokio::test]
+async fn test_input_not_matching_schema_fails_the_workflow() {
+    let error = run(ORDER_WORKFLOW, json!({ "orderId": 42 }))
+        .await
+        .unwrap_err();
+
+    let HandleError::WorkflowExecution { message } = error else {
+        panic!("expected the workflow to fail, got {error}");
+    };
+    assert!(
+        message.contains("Workflow input does not match its schema"),
+        "unexpected error: {message}"
+    );
+    assert!(message.contains("/orderId"), "unexpected error: {message}");
+}
+
+#[tokio::test]
+async fn test_output_not_matching_schema_is_caught_as_validation_error() {
+    let output = run(GUARDED_WORKFLOW, json!({})).await.unwrap();
+
+    let failure: WorkflowError = serde_json::from_value(output["failure"].clone()).unwrap();
+    assert_eq!(failure.type_, WorkflowError::VALIDATION);
+    assert_eq!(failure.status, 400);
+    assert!(
+        failure
+            .detail
+            .as_deref()
+            .unwrap_or_default()
+            .contains("Output of task 'count' does not match its schema")
+    );
+}
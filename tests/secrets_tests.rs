@@ -0,0 +1,86 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the secrets workflows declare in `use.secrets`
+use async_trait::async_trait;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::mem::InMemoryPersistence;
+use jackdaw::secrets::SecretsProvider;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use std::time::Duration;
+
+const WORKFLOW: &str = r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-secrets
+  version: '1.0.0'
+use:
+  secrets:
+    - API_KEY
+do:
+  - authorize:
+      set:
+        authorized: ${ $secrets.API_KEY == "s3cr3t" }
+        header: ${ "Bearer " + $secrets.API_KEY }
+"#;
+
+#[derive(Debug)]
+struct StaticSecrets;
+
+#[async_trait]
+impl SecretsProvider for StaticSecrets {
+    async fn get(&self, name: &str) -> jackdaw::secrets::Result<Option<String>> {
+        Ok((name == "API_KEY").then(|| "s3cr3t".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_secrets_are_readable_but_never_persisted() {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+        .with_secrets(Arc::new(StaticSecrets))
+        .build()
+        .unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(WORKFLOW).unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let output = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    assert_eq!(output["authorized"], true);
+    assert!(output.get("__secrets").is_none());
+
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    let recorded = serde_json::to_string(&events).unwrap();
+    assert!(!recorded.contains("s3cr3t"));
+    assert!(!recorded.contains("__secrets"));
+    assert!(recorded.contains("Bearer ***"));
+
+    let checkpoint = persistence.get_checkpoint(&instance_id).await.unwrap();
+    let recorded = serde_json::to_string(&checkpoint).unwrap();
+    assert!(!recorded.contains("s3cr3t"));
+}
+
+#[tokio::test]
+async fn test_missing_declared_secret_fails_the_instance() {
+    let engine = DurableEngineBuilder::new()
+        .with_secrets(Arc::new(StaticSecrets))
+        .build()
+        .unwrap();
+    let workflow: WorkflowDefinition =
+        serde_yaml::from_str(&WORKFLOW.replace("- API_KEY", "- DB_PASSWORD")).unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    let error = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("DB_PASSWORD"));
+}
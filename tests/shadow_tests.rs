@@ -0,0 +1,204 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for shadow runs of candidate workflow versions
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::ShadowMode;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::InMemoryPersistence;
+use jackdaw::workflow::{OutputDifference, WorkflowEvent};
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn pricing(uri: &str, version: &str, last_task: &str) -> WorkflowDefinition {
+    serde_yaml::from_str(&format!(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: pricing
+  version: '{version}'
+do:
+  - getPrice:
+      call: http
+      with:
+        method: get
+        endpoint: {uri}/price
+{last_task}
+"
+    ))
+    .unwrap()
+}
+
+const DOUBLE: &str = r"
+  - total:
+      set:
+        total: ${ .price * 2 }";
+
+const TRIPLE: &str = r"
+  - total:
+      set:
+        total: ${ .price * 3 }";
+
+const REJECT: &str = r"
+  - reject:
+      raise:
+        error:
+          type: https://example.com/errors/rejected
+          status: 400
+          title: Rejected";
+
+async fn price_server(calls: u64) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/price"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "price": 10 })))
+        .expect(calls)
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Run the current version with `candidate` in its shadow, returning the comparison
+async fn compare(
+    current: WorkflowDefinition,
+    candidate: WorkflowDefinition,
+    mode: ShadowMode,
+) -> WorkflowEvent {
+    compare_with(DurableEngineBuilder::new(), current, candidate, mode).await
+}
+
+/// Like [`compare`], with an engine built by `builder`
+async fn compare_with(
+    builder: DurableEngineBuilder,
+    current: WorkflowDefinition,
+    candidate: WorkflowDefinition,
+    mode: ShadowMode,
+) -> WorkflowEvent {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = builder
+        .with_persistence(persistence.clone())
+        .with_shadow(candidate, mode)
+        .build()
+        .unwrap();
+    let handle = engine.execute(current, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    engine.wait_for_shadows().await;
+
+    persistence
+        .get_events(&instance_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|event| matches!(event, WorkflowEvent::ShadowCompared { .. }))
+        .expect("ShadowCompared event should be persisted")
+}
+
+#[tokio::test]
+async fn test_stubbed_shadow_records_output_differences() {
+    // The candidate takes the recorded price instead of calling again
+    let server = price_server(1).await;
+    let uri = server.uri();
+    let event = compare(
+        pricing(&uri, "1.0.0", DOUBLE),
+        pricing(&uri, "2.0.0", TRIPLE),
+        ShadowMode::Stub,
+    )
+    .await;
+
+    let WorkflowEvent::ShadowCompared {
+        candidate,
+        differences,
+        error,
+        stubbed,
+        ..
+    } = event
+    else {
+        unreachable!()
+    };
+    assert_eq!(candidate, "default/pricing/2.0.0");
+    assert_eq!(error, None);
+    assert_eq!(stubbed, vec!["getPrice"]);
+    assert_eq!(
+        differences,
+        vec![OutputDifference {
+            path: "/total".to_string(),
+            left: Some(json!(20)),
+            right: Some(json!(30)),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_sandboxed_shadow_sends_calls_to_mocks() {
+    // Both versions call the mock, which the instance's calls are routed to as well
+    let server = price_server(2).await;
+    let uri = server.uri();
+    let event = compare_with(
+        DurableEngineBuilder::new().with_mock_route(&uri, &uri),
+        pricing(&uri, "1.0.0", DOUBLE),
+        pricing(&uri, "1.1.0", DOUBLE),
+        ShadowMode::Sandbox,
+    )
+    .await;
+
+    let WorkflowEvent::ShadowCompared {
+        differences,
+        error,
+        stubbed,
+        ..
+    } = event
+    else {
+        unreachable!()
+    };
+    assert_eq!(error, None);
+    assert!(stubbed.is_empty());
+    assert!(differences.is_empty());
+}
+
+#[tokio::test]
+async fn test_failing_shadow_does_not_fail_the_instance() {
+    let server = price_server(1).await;
+    let uri = server.uri();
+    let event = compare(
+        pricing(&uri, "1.0.0", DOUBLE),
+        pricing(&uri, "2.0.0", REJECT),
+        ShadowMode::Stub,
+    )
+    .await;
+
+    let WorkflowEvent::ShadowCompared {
+        differences, error, ..
+    } = event
+    else {
+        unreachable!()
+    };
+    assert!(differences.is_empty());
+    assert!(error.unwrap().contains("Rejected"));
+}
+
+#[tokio::test]
+async fn test_sandboxed_shadow_refuses_calls_without_a_mock() {
+    // Only the instance calls the real service
+    let server = price_server(1).await;
+    let uri = server.uri();
+    let event = compare(
+        pricing(&uri, "1.0.0", DOUBLE),
+        pricing(&uri, "1.1.0", DOUBLE),
+        ShadowMode::Sandbox,
+    )
+    .await;
+
+    let WorkflowEvent::ShadowCompared { error, .. } = event else {
+        unreachable!()
+    };
+    assert!(error.unwrap().contains("No mock server answers calls"));
+}
@@ -0,0 +1,143 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for engines started from a snapshot of another engine's setup
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::EngineSnapshot;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A workflow that looks a pet up through the `OpenAPI` document at `server`
+fn pets_workflow(server: &str) -> WorkflowDefinition {
+    serde_yaml::from_str(&format!(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: pets
+  version: '1.0.0'
+do:
+  - getPet:
+      call: openapi
+      with:
+        document:
+          endpoint: {server}/openapi.json
+        operationId: getPet
+        parameters:
+          petId: ${{ .petId }}
+  - describe:
+      set:
+        description: ${{ .name + " the " + .kind }}
+"
+    ))
+    .unwrap()
+}
+
+async fn pets_server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/openapi.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "openapi": "3.0.0",
+            "info": { "title": "Pets", "version": "1.0.0" },
+            "servers": [{ "url": server.uri() }],
+            "paths": {
+                "/pets/{petId}": {
+                    "get": {
+                        "operationId": "getPet",
+                        "parameters": [{
+                            "name": "petId",
+                            "in": "path",
+                            "required": true,
+                            "schema": { "type": "integer" }
+                        }],
+                        "responses": { "200": { "description": "The pet" } }
+                    }
+                }
+            }
+        })))
+        // Fetched when the snapshot is taken, and never again
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/pets/7"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "name": "Rex", "kind": "dog" })),
+        )
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn test_engine_starts_from_snapshot() {
+    let server = pets_server().await;
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    engine
+        .register_workflow(pets_workflow(&server.uri()))
+        .await
+        .unwrap();
+
+    let snapshot = engine.snapshot().await.unwrap();
+    assert_eq!(snapshot.workflows.len(), 1);
+    assert_eq!(snapshot.openapi_documents.len(), 1);
+    assert!(
+        snapshot
+            .expressions
+            .iter()
+            .any(|expression| expression.source == ".petId")
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("engine.snapshot.json");
+    snapshot.write(&path).unwrap();
+
+    let warm = DurableEngineBuilder::new()
+        .with_snapshot(EngineSnapshot::read(&path).unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(warm.registered_workflows().await, ["default/pets/1.0.0"]);
+
+    let workflow = warm
+        .get_registered_workflow("default/pets/1.0.0")
+        .await
+        .unwrap();
+    let output = warm
+        .execute(workflow, json!({ "petId": 7 }))
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert_eq!(output["description"], json!("Rex the dog"));
+}
+
+#[tokio::test]
+async fn test_snapshot_of_another_version_is_refused() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let mut snapshot = engine.snapshot().await.unwrap();
+    snapshot.jackdaw_version = "0.0.0-other".to_string();
+
+    let error = DurableEngineBuilder::new()
+        .with_snapshot(snapshot)
+        .build()
+        .unwrap_err();
+    assert!(error.to_string().contains("take it again"), "{error}");
+}
+
+#[tokio::test]
+async fn test_snapshot_leaves_out_unreachable_documents() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    engine
+        .register_workflow(pets_workflow("http://127.0.0.1:9"))
+        .await
+        .unwrap();
+
+    let snapshot = engine.snapshot().await.unwrap();
+    assert_eq!(snapshot.workflows.len(), 1);
+    assert!(snapshot.openapi_documents.is_empty());
+}
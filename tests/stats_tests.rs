@@ -0,0 +1,168 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for the per-workflow rollups that run stats are read from
+use async_trait::async_trait;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::durableengine::{DurableEngine, InstanceFilter, WorkflowStats};
+use jackdaw::persistence::{PersistenceProvider, Result, RunUpdate, WorkflowRollup};
+use jackdaw::providers::cache::InMemoryCache;
+use jackdaw::providers::persistence::SqlitePersistence;
+use jackdaw::workflow::{WorkflowCheckpoint, WorkflowEvent};
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Run a workflow to its end
+async fn run(engine: &DurableEngine, workflow: &str) {
+    let handle = engine
+        .execute(fixtures::load("stats", workflow), json!({}))
+        .await
+        .unwrap();
+    let _ = handle.wait_for_completion(Duration::from_secs(10)).await;
+}
+
+/// A store shared by the processes of a test, each opening it anew
+fn store_url(dir: &tempfile::TempDir) -> String {
+    format!(
+        "sqlite://{}?mode=rwc",
+        dir.path().join("jackdaw.db").display()
+    )
+}
+
+/// Persistence that counts how often the recorded history is read
+#[derive(Debug)]
+struct CountingPersistence {
+    inner: SqlitePersistence,
+    history_reads: AtomicUsize,
+}
+
+#[async_trait]
+impl PersistenceProvider for CountingPersistence {
+    async fn save_event(&self, event: WorkflowEvent) -> Result<()> {
+        self.inner.save_event(event).await
+    }
+
+    async fn get_events(&self, instance_id: &str) -> Result<Vec<WorkflowEvent>> {
+        self.history_reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.get_events(instance_id).await
+    }
+
+    async fn save_checkpoint(&self, checkpoint: WorkflowCheckpoint) -> Result<()> {
+        self.inner.save_checkpoint(checkpoint).await
+    }
+
+    async fn get_checkpoint(&self, instance_id: &str) -> Result<Option<WorkflowCheckpoint>> {
+        self.inner.get_checkpoint(instance_id).await
+    }
+
+    async fn list_instance_ids(&self) -> Result<Vec<String>> {
+        self.history_reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.list_instance_ids().await
+    }
+
+    async fn update_rollup(&self, workflow_id: &str, update: &RunUpdate) -> Result<()> {
+        self.inner.update_rollup(workflow_id, update).await
+    }
+
+    async fn replace_rollups(&self, rollups: Vec<WorkflowRollup>) -> Result<()> {
+        self.inner.replace_rollups(rollups).await
+    }
+
+    async fn workflow_rollups(&self) -> Result<Option<Vec<WorkflowRollup>>> {
+        self.inner.workflow_rollups().await
+    }
+}
+
+fn find<'a>(stats: &'a [WorkflowStats], workflow_id: &str) -> &'a WorkflowStats {
+    stats
+        .iter()
+        .find(|stats| stats.workflow_id == workflow_id)
+        .expect("No stats for workflow")
+}
+
+#[tokio::test]
+async fn test_stats_survive_a_restart_without_a_rescan() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = store_url(&dir);
+
+    {
+        let engine = DurableEngineBuilder::new()
+            .with_persistence(Arc::new(SqlitePersistence::new(&url).await.unwrap()))
+            .build()
+            .unwrap();
+        run(&engine, "greet.sw.yaml").await;
+        run(&engine, "greet.sw.yaml").await;
+        run(&engine, "fail.sw.yaml").await;
+    }
+
+    // A new process reads the rollups without going through the history
+    let persistence = Arc::new(CountingPersistence {
+        inner: SqlitePersistence::new(&url).await.unwrap(),
+        history_reads: AtomicUsize::new(0),
+    });
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence.clone())
+        .build()
+        .unwrap();
+    let stats = engine
+        .workflow_stats(&InstanceFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(persistence.history_reads.load(Ordering::SeqCst), 0);
+
+    assert_eq!(stats.len(), 2);
+    let greet = find(&stats, "default/greet/1.0.0");
+    assert_eq!(greet.runs, 2);
+    assert_eq!(greet.completed, 2);
+    assert_eq!(greet.in_flight, 0);
+    assert_eq!(greet.success_rate, Some(1.0));
+    assert!(greet.duration_ms.is_some());
+
+    let fail = find(&stats, "default/fail/1.0.0");
+    assert_eq!(fail.runs, 1);
+    assert_eq!(fail.failed, 1);
+    assert_eq!(fail.success_rate, Some(0.0));
+    assert_eq!(fail.failing_tasks.first().unwrap().task_name, "failTask");
+
+    // Runs of the new process are added to the stored rollups
+    run(&engine, "greet.sw.yaml").await;
+    let stats = engine
+        .workflow_stats(&InstanceFilter::default().with_workflow_id("default/greet/1.0.0"))
+        .await
+        .unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats.first().unwrap().runs, 3);
+}
+
+#[tokio::test]
+async fn test_rebuilt_rollups_count_runs_recorded_without_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = store_url(&dir);
+
+    // An engine that is not built by the builder keeps no rollups
+    let persistence = Arc::new(SqlitePersistence::new(&url).await.unwrap());
+    let engine = DurableEngine::new(persistence.clone(), Arc::new(InMemoryCache::new())).unwrap();
+    run(&engine, "greet.sw.yaml").await;
+    run(&engine, "fail.sw.yaml").await;
+    assert!(persistence.workflow_rollups().await.unwrap().is_none());
+
+    // Without rollups, the stats are computed from the history
+    let rescanned = engine
+        .workflow_stats(&InstanceFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(rescanned.len(), 2);
+
+    engine.rebuild_workflow_rollups().await.unwrap();
+    let rollups = persistence.workflow_rollups().await.unwrap().unwrap();
+    assert_eq!(rollups.len(), 2);
+    let stats = engine
+        .workflow_stats(&InstanceFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(stats, rescanned);
+}
@@ -77,9 +77,6 @@ async fn then_workflow_faults_with_error(world: &mut CtKWorld, step: &cucumber::
         Some(WorkflowStatus::Completed) => {
             panic!("Expected workflow to fault, but it completed successfully");
         }
-        Some(WorkflowStatus::Cancelled) => {
-            panic!("Expected workflow to fault, but it was cancelled");
-        }
         None => {
             panic!("Expected workflow to fault, but no status was set");
         }
@@ -103,9 +103,6 @@ async fn then_workflow_faults(world: &mut CtKWorld) {
         Some(WorkflowStatus::Completed) => {
             panic!("Expected workflow to fault, but it completed successfully");
         }
-        Some(WorkflowStatus::Cancelled) => {
-            panic!("Expected workflow to fault, but it was cancelled");
-        }
         None => {
             panic!("Expected workflow to fault, but no status was set");
         }
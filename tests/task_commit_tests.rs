@@ -0,0 +1,120 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+#![allow(clippy::wildcard_enum_match_arm)]
+
+mod fixtures;
+
+/// Tests for committing task completions and repairing instances that stopped mid-commit
+use chrono::{Duration, Utc};
+use jackdaw::DurableEngineBuilder;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::InMemoryPersistence;
+use jackdaw::workflow::{WorkflowCheckpoint, WorkflowEvent};
+use serde_json::json;
+use std::sync::Arc;
+
+fn completions(events: &[WorkflowEvent], name: &str) -> usize {
+    events
+        .iter()
+        .filter(|event| {
+            matches!(event, WorkflowEvent::TaskCompleted { task_name, .. } if task_name == name)
+        })
+        .count()
+}
+
+#[tokio::test]
+async fn test_completion_is_committed_with_checkpoint() {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence.clone())
+        .build()
+        .unwrap();
+
+    let handle = engine
+        .execute(fixtures::load("commit", "export.sw.yaml"), json!({}))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    handle
+        .wait_for_completion(std::time::Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    let checkpoint = persistence
+        .get_checkpoint(&instance_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(checkpoint.current_task, "shout");
+    assert_eq!(checkpoint.data["loud"], json!("Hello!"));
+    let last_completion = events
+        .iter()
+        .rev()
+        .find_map(|event| match event {
+            WorkflowEvent::TaskCompleted { timestamp, .. } => Some(*timestamp),
+            _ => None,
+        })
+        .unwrap();
+    assert!(last_completion <= checkpoint.timestamp);
+}
+
+#[tokio::test]
+async fn test_completion_after_checkpoint_runs_again_on_resume() {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let instance_id = "interrupted-commit".to_string();
+    let started = Utc::now() - Duration::seconds(3);
+
+    // The process stopped after recording shout's completion but before its checkpoint
+    for event in [
+        WorkflowEvent::WorkflowStarted {
+            instance_id: instance_id.clone(),
+            workflow_id: "committed".to_string(),
+            timestamp: started,
+            initial_data: json!({}),
+            metadata: None,
+        },
+        WorkflowEvent::TaskCompleted {
+            instance_id: instance_id.clone(),
+            task_name: "greet".to_string(),
+            result: json!({"message": "Hello"}),
+            timestamp: started,
+            duration_ms: 0,
+        },
+        WorkflowEvent::TaskCompleted {
+            instance_id: instance_id.clone(),
+            task_name: "shout".to_string(),
+            result: json!({"message": "Hello", "loud": "stale"}),
+            timestamp: started + Duration::seconds(2),
+            duration_ms: 0,
+        },
+    ] {
+        persistence.save_event(event).await.unwrap();
+    }
+    persistence
+        .save_checkpoint(WorkflowCheckpoint {
+            instance_id: instance_id.clone(),
+            current_task: "greet".to_string(),
+            data: json!({"message": "Hello"}),
+            timestamp: started + Duration::seconds(1),
+        })
+        .await
+        .unwrap();
+
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence.clone())
+        .build()
+        .unwrap();
+    let result = engine
+        .resume(
+            fixtures::load("commit", "export.sw.yaml"),
+            instance_id.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(result["loud"], json!("Hello!"));
+
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    assert_eq!(completions(&events, "greet"), 1);
+    assert_eq!(completions(&events, "shout"), 2);
+}
@@ -0,0 +1,89 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for task-level `if` conditions
+use jackdaw::DurableEngineBuilder;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::mem::InMemoryPersistence;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use std::time::Duration;
+
+const WORKFLOW: &str = r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-task-condition
+  version: '1.0.0'
+do:
+  - init:
+      set:
+        visited: ['init']
+  - standardShipping:
+      if: '${ .express != true }'
+      set:
+        visited: '${ .visited + ["standardShipping"] }'
+  - expressShipping:
+      if: '${ .express }'
+      set:
+        visited: '${ .visited + ["expressShipping"] }'
+  - done:
+      set:
+        visited: '${ .visited + ["done"] }'
+"#;
+
+async fn run(input: serde_json::Value) -> (serde_json::Value, Vec<WorkflowEvent>) {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+        .build()
+        .unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(WORKFLOW).unwrap();
+    let handle = engine.execute(workflow, input).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let output = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    (output, events)
+}
+
+fn skipped(events: &[WorkflowEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            WorkflowEvent::TaskSkipped { task_name, .. } => Some(task_name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_false_condition_skips_task() {
+    let (output, events) = run(json!({ "express": true })).await;
+
+    assert_eq!(
+        output["visited"],
+        json!(["init", "expressShipping", "done"])
+    );
+    assert_eq!(skipped(&events), vec!["standardShipping"]);
+    // A skipped task is never started
+    assert!(!events.iter().any(|event| matches!(
+        event,
+        WorkflowEvent::TaskStarted { task_name, .. } if task_name == "standardShipping"
+    )));
+}
+
+#[tokio::test]
+async fn test_true_condition_runs_task() {
+    let (output, events) = run(json!({ "express": false })).await;
+
+    assert_eq!(
+        output["visited"],
+        json!(["init", "standardShipping", "done"])
+    );
+    assert_eq!(skipped(&events), vec!["expressShipping"]);
+}
@@ -324,3 +324,41 @@ async fn test_task_retried_includes_attempt_number() {
         "TaskRetried events should be properly structured"
     );
 }
+
+#[tokio::test]
+async fn test_task_retried_until_retry_limit() {
+    let (engine, persistence, _temp_dir) = setup_test_engine().await;
+
+    let fixture = PathBuf::from("tests/fixtures/task-events/test-retry-exhausted.sw.yaml");
+    let workflow_yaml =
+        std::fs::read_to_string(&fixture).expect("Failed to read test-retry-exhausted.sw.yaml");
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml).unwrap();
+
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let result = handle.wait_for_completion(Duration::from_secs(30)).await;
+    assert!(
+        result.is_ok(),
+        "Catch handler should run once retries are exhausted"
+    );
+
+    let events = persistence
+        .get_events(&instance_id)
+        .await
+        .expect("Failed to get events");
+
+    let attempts: Vec<_> = events
+        .iter()
+        .filter_map(|e| match e {
+            WorkflowEvent::TaskRetried {
+                task_name, attempt, ..
+            } => {
+                assert_eq!(task_name, "unstableTask");
+                Some(*attempt)
+            }
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(attempts, vec![1, 2], "Expected one TaskRetried per retry");
+}
@@ -0,0 +1,67 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the metrics an engine built with telemetry reports
+use jackdaw::DurableEngineBuilder;
+use jackdaw::telemetry::Telemetry;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_metrics_count_workflows_and_tasks() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: greet
+  version: '1.0.0'
+do:
+  - greet:
+      set:
+        greeting: hello
+  - shout:
+      if: ${ .loud }
+      set:
+        greeting: HELLO
+",
+    )
+    .unwrap();
+    let engine = DurableEngineBuilder::new()
+        .with_telemetry(Arc::new(Telemetry::new()))
+        .build()
+        .unwrap();
+
+    for _ in 0..2 {
+        engine
+            .execute(workflow.clone(), json!({ "loud": false }))
+            .await
+            .unwrap()
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .unwrap();
+    }
+
+    let metrics = engine.metrics().await.unwrap();
+    assert!(
+        metrics.contains("jackdaw_workflows_total{status=\"completed\"} 2"),
+        "{metrics}"
+    );
+    // A skipped task completes with its input
+    assert!(metrics.contains("jackdaw_tasks_total{status=\"completed\"} 4"));
+    assert!(metrics.contains("jackdaw_tasks_total{status=\"skipped\"} 2"));
+    assert!(metrics.contains("jackdaw_task_duration_seconds_count 4"));
+    assert!(metrics.contains("jackdaw_cache_lookups_total{result=\"hit\"}"));
+}
+
+#[tokio::test]
+async fn test_metrics_without_telemetry() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    assert!(engine.telemetry().is_none());
+
+    let metrics = engine.metrics().await.unwrap();
+    assert!(metrics.contains("jackdaw_cache_lookups_total"));
+    assert!(!metrics.contains("jackdaw_workflows_total"));
+}
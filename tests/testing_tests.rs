@@ -0,0 +1,51 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for the `x-tests` section of workflow files
+use jackdaw::DurableEngineBuilder;
+use jackdaw::testing::{self, Error};
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[tokio::test]
+async fn test_declared_tests_pass() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    for fixture in ["greeter.sw.yaml", "rejecting.sw.yaml"] {
+        let path = Path::new("tests/fixtures/testing").join(fixture);
+        let (workflow, tests) = testing::load(&path, false).unwrap();
+        assert!(!tests.is_empty());
+        for test in &tests {
+            let outcome = testing::run_test(&engine, &workflow, test, TIMEOUT).await;
+            assert_eq!(outcome, Ok(()), "{}", test.name);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_unmet_expectation_fails() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let (workflow, tests) =
+        testing::load(Path::new("tests/fixtures/testing/greeter.sw.yaml"), false).unwrap();
+    let mut test = tests.into_iter().find(|test| test.task.is_some()).unwrap();
+    test.expect.output = Some(json!({"message": "Hi Rex"}));
+
+    let outcome = testing::run_test(&engine, &workflow, &test, TIMEOUT).await;
+    assert_eq!(
+        outcome,
+        Err(r#"expected output {"message":"Hi Rex"}, but got {"message":"Hello Rex"}"#.to_string())
+    );
+}
+
+#[test]
+fn test_unknown_task_is_rejected() {
+    let result = testing::load(
+        Path::new("tests/fixtures/testing/unknown-task.sw.yaml"),
+        false,
+    );
+    assert!(
+        matches!(result, Err(Error::InvalidTest { name, .. }) if name == "runs a missing task")
+    );
+}
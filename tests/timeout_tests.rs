@@ -15,6 +15,7 @@ use jackdaw::durableengine::DurableEngine;
 use jackdaw::persistence::PersistenceProvider;
 use jackdaw::providers::cache::RedbCache;
 use jackdaw::providers::persistence::RedbPersistence;
+use jackdaw::workflow::{ReapedResource, WorkflowEvent};
 use serde_json::json;
 use serverless_workflow_core::models::workflow::WorkflowDefinition;
 use std::path::PathBuf;
@@ -272,3 +273,51 @@ async fn test_timeout_with_milliseconds() {
         elapsed
     );
 }
+
+#[tokio::test]
+async fn test_task_timeout_reaps_shell_process() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("test.db");
+    let persistence = Arc::new(RedbPersistence::new(db_path.to_str().unwrap()).unwrap());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+        .build()
+        .unwrap();
+
+    let fixture = PathBuf::from("tests/fixtures/timeout/task-timeout-shell-reaped.sw.yaml");
+    let workflow_yaml = std::fs::read_to_string(&fixture).unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&workflow_yaml).unwrap();
+
+    let start = Instant::now();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let result = handle.wait_for_completion(Duration::from_secs(10)).await;
+
+    assert!(result.is_err(), "Task should timeout and fail workflow");
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "Timed-out shell process should be killed, not waited on"
+    );
+
+    // The killed process is recorded against the timed-out task
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    let (reason, resources, abandoned) = events
+        .iter()
+        .find_map(|event| match event {
+            WorkflowEvent::TaskReaped {
+                task_name,
+                reason,
+                resources,
+                abandoned,
+                ..
+            } if task_name == "slowShell" => Some((reason, resources, *abandoned)),
+            _ => None,
+        })
+        .expect("TaskReaped event should be persisted");
+    assert_eq!(reason, "timeout");
+    assert!(!abandoned);
+    assert!(matches!(
+        resources.first(),
+        Some(ReapedResource::Process { pid: Some(_), command }) if command == "sleep"
+    ));
+}
@@ -0,0 +1,94 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for version ranges in sub-workflow and catalog function references
+use jackdaw::DurableEngineBuilder;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::mem::InMemoryPersistence;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+const FIXTURES: &str = "tests/fixtures/versions";
+
+#[tokio::test]
+async fn test_ranges_resolve_to_highest_match_and_are_pinned() {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+        .build()
+        .unwrap();
+    for version in ["1.0.0", "1.3.0", "2.0.0"] {
+        engine
+            .register_workflow(fixtures::load(
+                "versions",
+                &format!("child-{version}.sw.yaml"),
+            ))
+            .await
+            .unwrap();
+    }
+    let parent = fixtures::load("versions", "parent.sw.yaml");
+    engine
+        .set_workflow_path(&parent, &Path::new(FIXTURES).join("parent.sw.yaml"))
+        .await;
+
+    let handle = engine.execute(parent, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    let output = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert_eq!(output["child"], json!("1.3.0"));
+    assert_eq!(output["function"], json!("1.2.0"));
+
+    let metadata = persistence
+        .get_events(&instance_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .find_map(|event| {
+            if let WorkflowEvent::WorkflowStarted { metadata, .. } = event {
+                metadata
+            } else {
+                None
+            }
+        })
+        .unwrap();
+    assert_eq!(
+        metadata["resolvedVersions"],
+        json!({
+            "workflow:default/child@^1.0": "1.3.0",
+            "function:greet@^1": "1.2.0",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_unmatched_range_fails_at_start() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    engine
+        .register_workflow(fixtures::load("versions", "child-2.0.0.sw.yaml"))
+        .await
+        .unwrap();
+    let parent = fixtures::load("versions", "parent.sw.yaml");
+    engine
+        .set_workflow_path(&parent, &Path::new(FIXTURES).join("parent.sw.yaml"))
+        .await;
+
+    let error = engine
+        .execute(parent, json!({}))
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap_err();
+    let message = error.to_string();
+    assert!(
+        message.contains("No version of default/child matches ^1.0 (available: 2.0.0)"),
+        "{message}"
+    );
+}
@@ -0,0 +1,347 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for visualization providers registered by embedding crates
+use jackdaw::DurableEngineBuilder;
+use jackdaw::config::JackdawConfig;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::InMemoryPersistence;
+use jackdaw::providers::visualization::{
+    self, BuiltinProvider, Capabilities, D2Provider, DiagramFilter, DiagramFormat, DiagramStyle,
+    ExecutionState, GraphvizProvider, MermaidProvider, Result, TaskExecutionState,
+    VisualizationProvider,
+};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Writes PlantUML source for the tasks of a workflow
+#[derive(Debug)]
+struct PlantUmlProvider;
+
+impl VisualizationProvider for PlantUmlProvider {
+    fn name(&self) -> &'static str {
+        "plantuml"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            formats: &[DiagramFormat::Ascii],
+            execution_state: false,
+        }
+    }
+
+    fn generate_source(
+        &self,
+        workflow: &WorkflowDefinition,
+        _execution_state: Option<&ExecutionState>,
+    ) -> Result<String> {
+        let tasks: Vec<String> = workflow
+            .do_
+            .entries
+            .iter()
+            .flat_map(|entry| entry.keys().map(|name| format!(":{name};")))
+            .collect();
+        Ok(format!(
+            "@startuml\nstart\n{}\nstop\n@enduml\n",
+            tasks.join("\n")
+        ))
+    }
+
+    fn render(
+        &self,
+        workflow: &WorkflowDefinition,
+        output_path: Option<&Path>,
+        _format: DiagramFormat,
+        execution_state: Option<&ExecutionState>,
+    ) -> Result<()> {
+        let source = self.generate_source(workflow, execution_state)?;
+        if let Some(path) = output_path {
+            std::fs::write(path, source).map_err(|source| visualization::Error::Io { source })?;
+        }
+        Ok(())
+    }
+
+    fn is_available(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn version(&self) -> Result<String> {
+        Ok("1.0.0".to_string())
+    }
+}
+
+fn workflow() -> WorkflowDefinition {
+    serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: greet
+  version: '1.0.0'
+do:
+  - greet:
+      set:
+        greeting: hello
+",
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_registered_provider_is_selectable_by_name() {
+    visualization::register_provider(Arc::new(PlantUmlProvider));
+    assert!(visualization::provider_names().contains(&"plantuml".to_string()));
+
+    let config = JackdawConfig {
+        viz_tool: Some("PlantUML".to_string()),
+        ..JackdawConfig::default()
+    };
+    assert!(config.validate().is_empty());
+
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let workflow = workflow();
+    let handle = engine
+        .execute(workflow.clone(), serde_json::json!({}))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("greet.puml");
+    engine
+        .visualize_execution(
+            &workflow,
+            &instance_id,
+            Some(&output),
+            DiagramFormat::Ascii,
+            "plantuml",
+            &DiagramStyle::default(),
+            &DiagramFilter::default(),
+        )
+        .await
+        .unwrap();
+    let source = std::fs::read_to_string(&output).unwrap();
+    assert!(source.contains(":greet;"), "unexpected source: {source}");
+
+    let err = engine
+        .visualize_execution(
+            &workflow,
+            &instance_id,
+            Some(&output),
+            DiagramFormat::Svg,
+            "plantuml",
+            &DiagramStyle::default(),
+            &DiagramFilter::default(),
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("cannot render Svg diagrams"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn test_builtin_providers_report_their_capabilities() {
+    for name in ["graphviz", "d2", "builtin"] {
+        let capabilities = visualization::provider(name).unwrap().capabilities();
+        assert!(capabilities.supports(DiagramFormat::Svg));
+        assert!(capabilities.execution_state);
+    }
+    let mermaid = visualization::provider("mermaid").unwrap().capabilities();
+    assert!(mermaid.supports(DiagramFormat::Markdown));
+    assert!(!mermaid.supports(DiagramFormat::Svg));
+    assert!(visualization::provider("excalidraw").is_none());
+}
+
+#[test]
+fn test_builtin_providers_draw_only_the_filtered_tasks() {
+    let tasks: String = (0..50)
+        .map(|i| format!("  - step{i}:\n      set:\n        step: {i}\n"))
+        .collect();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(&format!(
+        "document:\n  dsl: '1.0.2'\n  namespace: default\n  name: large\n  version: '1.0.0'\ndo:\n{tasks}"
+    ))
+    .unwrap();
+    let filter = DiagramFilter {
+        from_task: Some("step10".to_string()),
+        max_depth: Some(3),
+        ..DiagramFilter::default()
+    };
+
+    for provider in [
+        Arc::new(GraphvizProvider::new().with_filter(filter.clone()))
+            as Arc<dyn VisualizationProvider>,
+        Arc::new(D2Provider::new().with_filter(filter.clone())),
+    ] {
+        let source = provider.generate_source(&workflow, None).unwrap();
+        assert!(source.contains("10 earlier tasks"), "{source}");
+        assert!(source.contains("\"step12\""), "{source}");
+        assert!(!source.contains("\"step13\""), "{source}");
+        assert!(source.contains("37 later tasks"), "{source}");
+    }
+}
+
+#[test]
+fn test_builtin_providers_draw_nested_tasks_in_clusters() {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: orders
+  version: '1.0.0'
+do:
+  - order:
+      try:
+        - charge:
+            for:
+              each: item
+              in: ${ .items }
+            do:
+              - bill:
+                  set:
+                    billed: true
+        - ship:
+            fork:
+              branches:
+                - pack:
+                    set:
+                      packed: true
+                - label:
+                    set:
+                      labelled: true
+      catch:
+        do:
+          - refund:
+              set:
+                refunded: true
+  - notify:
+      set:
+        notified: true
+",
+    )
+    .unwrap();
+
+    let dot = GraphvizProvider::new()
+        .generate_source(&workflow, None)
+        .unwrap();
+    assert!(dot.contains("subgraph \"cluster_order\""), "{dot}");
+    assert!(dot.contains("subgraph \"cluster_order/catch\""), "{dot}");
+    assert!(dot.contains("label=\"for each item\""), "{dot}");
+    assert!(
+        dot.contains("\"order/charge/(end)\" -> \"order/ship\";"),
+        "{dot}"
+    );
+    assert!(
+        dot.contains("\"order/ship\" -> \"order/ship/pack\";"),
+        "{dot}"
+    );
+    assert!(dot.contains("\"order/(end)\" -> \"notify\";"), "{dot}");
+
+    let d2 = D2Provider::new().generate_source(&workflow, None).unwrap();
+    assert!(d2.contains("  \"charge\": {\n"), "{d2}");
+    assert!(
+        d2.contains("\"order\".\"charge\" -> \"order\".\"ship\""),
+        "{d2}"
+    );
+    assert!(d2.contains("\"order\" -> \"notify\""), "{d2}");
+
+    let mermaid = MermaidProvider::new()
+        .generate_source(&workflow, None)
+        .unwrap();
+    assert!(mermaid.contains("subgraph n0_tasks [\"try\"]"), "{mermaid}");
+    assert!(
+        mermaid.contains("subgraph n0_catch [\"catch\"]"),
+        "{mermaid}"
+    );
+    assert!(mermaid.contains("-.->|\"error\"|"), "{mermaid}");
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("orders.svg");
+    BuiltinProvider::new()
+        .render(&workflow, Some(&path), DiagramFormat::Svg, None)
+        .unwrap();
+    let svg = std::fs::read_to_string(&path).unwrap();
+    assert!(svg.contains("refund"), "{svg}");
+}
+
+#[tokio::test]
+async fn test_execution_state_is_built_from_recorded_events() {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(persistence.clone())
+        .build()
+        .unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(
+        r"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: broken
+  version: '1.0.0'
+do:
+  - greet:
+      set:
+        greeting: hello
+  - shout:
+      if: ${ .loud }
+      set:
+        greeting: HELLO
+  - fail:
+      raise:
+        error:
+          type: https://serverlessworkflow.io/spec/1.0.0/errors/runtime
+          status: 500
+          title: Broken
+  - never:
+      set:
+        greeting: bye
+",
+    )
+    .unwrap();
+
+    let handle = engine
+        .execute(workflow.clone(), serde_json::json!({ "loud": false }))
+        .await
+        .unwrap();
+    let instance_id = handle.instance_id().to_string();
+    assert!(
+        handle
+            .wait_for_completion(Duration::from_secs(30))
+            .await
+            .is_err()
+    );
+
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    let state = ExecutionState::from_events(&events);
+    assert_eq!(
+        state.task_states.get("greet"),
+        Some(&TaskExecutionState::Success)
+    );
+    assert_eq!(
+        state.task_states.get("shout"),
+        Some(&TaskExecutionState::NotExecuted)
+    );
+    assert_eq!(
+        state.task_states.get("fail"),
+        Some(&TaskExecutionState::Failed)
+    );
+    assert_eq!(state.task_states.get("never"), None);
+
+    for provider in [
+        Arc::new(GraphvizProvider::new()) as Arc<dyn VisualizationProvider>,
+        Arc::new(D2Provider::new()),
+    ] {
+        let plain = provider.generate_source(&workflow, None).unwrap();
+        let colored = provider.generate_source(&workflow, Some(&state)).unwrap();
+        assert_ne!(plain, colored, "{}", provider.name());
+    }
+    let dot = GraphvizProvider::new()
+        .generate_source(&workflow, Some(&state))
+        .unwrap();
+    assert!(dot.contains("penwidth=3"), "{dot}");
+}
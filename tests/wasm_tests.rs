@@ -0,0 +1,79 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+mod fixtures;
+
+/// Tests for `call: wasm` with WebAssembly text modules in the fixtures
+use jackdaw::DurableEngineBuilder;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_module_receives_task_input() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine
+        .execute(
+            fixtures::load("wasm", "echo-input.sw.yaml"),
+            json!({ "name": "Rex", "age": 3 }),
+        )
+        .await
+        .unwrap();
+    let result = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    assert_eq!(result, json!({ "name": "Rex", "age": 3 }));
+}
+
+#[tokio::test]
+async fn test_module_receives_evaluated_input() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine
+        .execute(
+            fixtures::load("wasm", "echo-with.sw.yaml"),
+            json!({ "name": "Rex" }),
+        )
+        .await
+        .unwrap();
+    let result = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    assert_eq!(result, json!({ "greeting": "Hello Rex" }));
+}
+
+#[tokio::test]
+async fn test_non_zero_exit_fails_task() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let handle = engine
+        .execute(fixtures::load("wasm", "exit.sw.yaml"), json!({}))
+        .await
+        .unwrap();
+    let error = handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .expect_err("Module exited with code 3");
+
+    let message = error.to_string();
+    assert!(
+        message.contains("exit code 3"),
+        "Unexpected error: {message}"
+    );
+    assert!(message.contains("bad input"), "Unexpected error: {message}");
+}
+
+#[tokio::test]
+async fn test_timeout_stops_running_module() {
+    let engine = DurableEngineBuilder::new().build().unwrap();
+    let started = Instant::now();
+    let handle = engine
+        .execute(fixtures::load("wasm", "loop.sw.yaml"), json!({}))
+        .await
+        .unwrap();
+    let result = handle.wait_for_completion(Duration::from_secs(30)).await;
+
+    assert!(result.is_err(), "Module should have timed out");
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
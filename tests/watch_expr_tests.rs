@@ -0,0 +1,101 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for watch expressions evaluated after every task
+use jackdaw::DurableEngineBuilder;
+use jackdaw::persistence::PersistenceProvider;
+use jackdaw::providers::persistence::mem::InMemoryPersistence;
+use jackdaw::workflow::WorkflowEvent;
+use serde_json::json;
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::sync::Arc;
+use std::time::Duration;
+
+const WORKFLOW: &str = r#"
+document:
+  dsl: '1.0.2'
+  namespace: default
+  name: test-watch-expr
+  version: '1.0.0'
+do:
+  - placeOrder:
+      set:
+        order:
+          status: placed
+  - fulfil:
+      do:
+        - ship:
+            set:
+              order:
+                status: shipped
+"#;
+
+#[tokio::test]
+async fn test_watch_expressions_are_recorded_after_every_task() {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+        .with_watch_expr(".order.status")
+        .with_watch_expr("${ .missing }")
+        .build()
+        .unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(WORKFLOW).unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    let watched: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            WorkflowEvent::WatchesEvaluated {
+                task_name, values, ..
+            } => Some((task_name.as_str(), values.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let statuses: Vec<_> = watched
+        .iter()
+        .map(|(task, values)| (*task, values[".order.status"].clone()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("placeOrder", json!("placed")),
+            ("ship", json!("shipped")),
+            ("fulfil", json!("shipped")),
+        ]
+    );
+    assert!(
+        watched
+            .iter()
+            .all(|(_, values)| values["${ .missing }"].is_null())
+    );
+}
+
+#[tokio::test]
+async fn test_no_watch_events_without_expressions() {
+    let persistence = Arc::new(InMemoryPersistence::new());
+    let engine = DurableEngineBuilder::new()
+        .with_persistence(Arc::clone(&persistence) as Arc<dyn PersistenceProvider>)
+        .build()
+        .unwrap();
+    let workflow: WorkflowDefinition = serde_yaml::from_str(WORKFLOW).unwrap();
+    let handle = engine.execute(workflow, json!({})).await.unwrap();
+    let instance_id = handle.instance_id().to_string();
+    handle
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+        .unwrap();
+
+    let events = persistence.get_events(&instance_id).await.unwrap();
+    assert!(
+        !events
+            .iter()
+            .any(|event| matches!(event, WorkflowEvent::WatchesEvaluated { .. }))
+    );
+}
@@ -0,0 +1,223 @@
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+/// Tests for sub-workflows loaded from workflow sources, and the call depth limit
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use jackdaw::DurableEngineBuilder;
+use jackdaw::execution_handle::Result;
+use jackdaw::signing::{SignaturePolicy, SignatureVerifier, TrustedKey};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::pkcs8::{EncodePublicKey, LineEnding};
+use serde_json::{Value, json};
+use serverless_workflow_core::models::workflow::WorkflowDefinition;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A workflow `name` running `tasks`
+fn workflow(name: &str, version: &str, tasks: &str) -> String {
+    format!(
+        "document:\n  dsl: '1.0.2'\n  namespace: default\n  name: {name}\n  version: '{version}'\ndo:\n{tasks}"
+    )
+}
+
+/// A task running the workflow `name` at `version`
+fn run_task(task: &str, name: &str, version: &str) -> String {
+    format!(
+        "  - {task}:\n      run:\n        workflow:\n          namespace: default\n          name: {name}\n          version: '{version}'\n"
+    )
+}
+
+fn child(version: &str) -> String {
+    workflow(
+        "child",
+        version,
+        &format!("  - answer:\n      set:\n        version: '{version}'\n"),
+    )
+}
+
+/// A signing key, and a verifier enforcing signatures made with it
+fn enforcing() -> (SigningKey, SignatureVerifier) {
+    let signing = SigningKey::from_slice(&[7u8; 32]).unwrap();
+    let pem = signing
+        .verifying_key()
+        .to_public_key_pem(LineEnding::LF)
+        .unwrap();
+    let key = TrustedKey::from_pem("release", &pem).unwrap();
+    let verifier = SignatureVerifier::new(SignaturePolicy::Enforce, vec![key]).unwrap();
+    (signing, verifier)
+}
+
+fn sign(key: &SigningKey, data: &str) -> String {
+    let signature: Signature = key.sign(data.as_bytes());
+    BASE64.encode(signature.to_der().as_bytes())
+}
+
+async fn run(builder: DurableEngineBuilder, workflow: &str) -> Result<Value> {
+    let workflow: WorkflowDefinition = serde_yaml::from_str(workflow).unwrap();
+    builder
+        .build()
+        .unwrap()
+        .execute(workflow, json!({}))
+        .await
+        .unwrap()
+        .wait_for_completion(Duration::from_secs(30))
+        .await
+}
+
+#[tokio::test]
+async fn test_range_resolves_against_local_source() {
+    let source = tempfile::tempdir().unwrap();
+    let nested = source.path().join("children");
+    std::fs::create_dir_all(&nested).unwrap();
+    for version in ["1.0.0", "1.4.0", "2.0.0"] {
+        std::fs::write(nested.join(format!("child-{version}.yaml")), child(version)).unwrap();
+    }
+
+    let output = run(
+        DurableEngineBuilder::new().with_workflow_source(source.path().display().to_string()),
+        &workflow("parent", "1.0.0", &run_task("runChild", "child", "^1")),
+    )
+    .await
+    .unwrap();
+    assert_eq!(output["version"], json!("1.4.0"));
+}
+
+#[tokio::test]
+async fn test_http_source_is_fetched_once() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/workflows/default/child/1.0.0/workflow.yaml"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(child("1.0.0")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let tasks = format!(
+        "{}{}",
+        run_task("first", "child", "1.0.0"),
+        run_task("second", "child", "1.0.0")
+    );
+    let output = run(
+        DurableEngineBuilder::new().with_workflow_source(server.uri()),
+        &workflow("parent", "1.0.0", &tasks),
+    )
+    .await
+    .unwrap();
+    assert_eq!(output["version"], json!("1.0.0"));
+}
+
+#[tokio::test]
+async fn test_missing_workflow_names_its_key() {
+    let source = tempfile::tempdir().unwrap();
+    let error = run(
+        DurableEngineBuilder::new().with_workflow_source(source.path().display().to_string()),
+        &workflow("parent", "1.0.0", &run_task("runChild", "child", "1.0.0")),
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(
+        error.contains("Workflow not found in registry: default/child/1.0.0"),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn test_recursive_calls_stop_at_max_call_depth() {
+    let looping = workflow("loop", "1.0.0", &run_task("again", "loop", "1.0.0"));
+    let source = tempfile::tempdir().unwrap();
+    std::fs::write(source.path().join("loop.yaml"), &looping).unwrap();
+
+    let error = run(
+        DurableEngineBuilder::new()
+            .with_workflow_source(source.path().display().to_string())
+            .with_max_call_depth(3),
+        &looping,
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(
+        error.contains(
+            "Recursive workflow call default/loop/1.0.0 -> default/loop/1.0.0 exceeds the maximum call depth of 3"
+        ),
+        "{error}"
+    );
+}
+
+#[tokio::test]
+async fn test_enforce_rejects_unsigned_http_source_workflow() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/workflows/default/child/1.0.0/workflow.yaml"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(child("1.0.0")))
+        .mount(&server)
+        .await;
+
+    let (_, verifier) = enforcing();
+    let error = run(
+        DurableEngineBuilder::new()
+            .with_workflow_source(server.uri())
+            .with_signature_verifier(verifier),
+        &workflow("parent", "1.0.0", &run_task("runChild", "child", "1.0.0")),
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("no signature found"), "{error}");
+}
+
+#[tokio::test]
+async fn test_enforce_runs_signed_http_source_workflow() {
+    let server = MockServer::start().await;
+    let (signing, verifier) = enforcing();
+    let content = child("1.0.0");
+    Mock::given(method("GET"))
+        .and(path("/workflows/default/child/1.0.0/workflow.yaml"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(content.clone()))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/workflows/default/child/1.0.0/workflow.yaml.sig"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(sign(&signing, &content)))
+        .mount(&server)
+        .await;
+
+    let output = run(
+        DurableEngineBuilder::new()
+            .with_workflow_source(server.uri())
+            .with_signature_verifier(verifier),
+        &workflow("parent", "1.0.0", &run_task("runChild", "child", "1.0.0")),
+    )
+    .await
+    .unwrap();
+    assert_eq!(output["version"], json!("1.0.0"));
+}
+
+#[tokio::test]
+async fn test_source_workflow_placeholders_are_substituted() {
+    let source = tempfile::tempdir().unwrap();
+    std::fs::write(
+        source.path().join("child.yaml"),
+        workflow(
+            "child",
+            "1.0.0",
+            "  - answer:\n      set:\n        region: ${{ env.JACKDAW_TEST_UNSET_REGION }}\n",
+        ),
+    )
+    .unwrap();
+
+    let error = run(
+        DurableEngineBuilder::new()
+            .with_workflow_source(source.path().display().to_string())
+            .with_strict_env(true),
+        &workflow("parent", "1.0.0", &run_task("runChild", "child", "1.0.0")),
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("JACKDAW_TEST_UNSET_REGION"), "{error}");
+}